@@ -0,0 +1,33 @@
+//! Encryption and decryption throughput of each cipher over representative file sizes.
+//!
+//! Run with `cargo bench --bench ciphers`; `envcrypt bench` measures the same cases
+//! without a Rust toolchain.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use envcrypt::cipher::{get_cipher, get_supported_ciphers};
+use envcrypt::cli::BENCH_FILE_SIZES;
+
+fn bench_ciphers(c: &mut Criterion) {
+    let encryption_key = [7u8; 32];
+    let mac_key = [9u8; 32];
+
+    for cipher_name in get_supported_ciphers() {
+        let cipher = get_cipher(cipher_name).unwrap();
+        let mut group = c.benchmark_group(cipher_name);
+        for &size in BENCH_FILE_SIZES {
+            let plaintext = vec![b'A'; size];
+            let ciphertext = cipher.encrypt(&plaintext, &encryption_key, &mac_key).unwrap();
+            group.throughput(Throughput::Bytes(size as u64));
+            group.bench_with_input(BenchmarkId::new("encrypt", size), &plaintext, |b, plaintext| {
+                b.iter(|| cipher.encrypt(plaintext, &encryption_key, &mac_key).unwrap())
+            });
+            group.bench_with_input(BenchmarkId::new("decrypt", size), &ciphertext, |b, ciphertext| {
+                b.iter(|| cipher.decrypt(ciphertext, &encryption_key, &mac_key).unwrap())
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_ciphers);
+criterion_main!(benches);
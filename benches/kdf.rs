@@ -0,0 +1,30 @@
+//! Key derivation cost for each PBKDF2 iteration count and for raw keys.
+//!
+//! Run with `cargo bench --bench kdf`. The results show what a default iteration count
+//! costs on the machine, next to `envcrypt calibrate`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use envcrypt::cli::BENCH_KDF_ITERATIONS;
+use envcrypt::key::{derive_keys_from_raw_key, derive_keys_with_schedule, KeySchedule};
+
+fn bench_kdf(c: &mut Criterion) {
+    let salt = [3u8; 16];
+
+    let mut group = c.benchmark_group("pbkdf2");
+    // A single derivation takes up to a few hundred milliseconds
+    group.sample_size(10);
+    for &iterations in BENCH_KDF_ITERATIONS {
+        for schedule in [KeySchedule::HkdfExpand, KeySchedule::Split] {
+            let id = BenchmarkId::new(format!("{:?}", schedule), iterations);
+            group.bench_with_input(id, &iterations, |b, &iterations| {
+                b.iter(|| derive_keys_with_schedule("envcrypt-benchmark", &salt, iterations, schedule))
+            });
+        }
+    }
+    group.finish();
+
+    c.bench_function("hkdf/raw_key", |b| b.iter(|| derive_keys_from_raw_key(&[5u8; 32], &salt)));
+}
+
+criterion_group!(benches, bench_kdf);
+criterion_main!(benches);
@@ -0,0 +1,62 @@
+//! Node.js bindings for in-memory encryption and decryption (`npm install envcrypt`).
+//!
+//! ```js
+//! const envcrypt = require("envcrypt");
+//!
+//! const contents = envcrypt.encrypt(Buffer.from("APP_KEY=test123"), "my-key", { cipher: "AES-256-GCM" });
+//! envcrypt.decrypt(contents, "my-key").toString(); // "APP_KEY=test123"
+//! ```
+
+use napi::bindgen_prelude::Buffer;
+use napi::{Error, Result};
+use napi_derive::napi;
+
+use envcrypt::armor::FileFormat;
+use envcrypt::envelope;
+use envcrypt::format::Kdf;
+use envcrypt::key::DEFAULT_PBKDF2_ITERATIONS;
+
+/// Options for `encrypt()`.
+#[napi(object)]
+#[derive(Default)]
+pub struct EncryptOptions {
+    /// Cipher name or alias, as for `envcrypt encrypt --cipher` (default: AES-256-CBC).
+    pub cipher: Option<String>,
+    /// PBKDF2 iteration count, from 1000 to 100000000 (default: 100000). Ignored with
+    /// `rawKey`.
+    pub kdf_iterations: Option<u32>,
+    /// The key is a base64-encoded 32-byte key to use with HKDF instead of PBKDF2.
+    pub raw_key: Option<bool>,
+    /// Context string to bind the file to, like `--aad`.
+    pub context: Option<String>,
+    /// Return raw bytes instead of base64 armor.
+    pub binary: Option<bool>,
+}
+
+/// Encrypts `plaintext` into the contents of an encrypted file.
+#[napi]
+pub fn encrypt(plaintext: Buffer, key: String, options: Option<EncryptOptions>) -> Result<Buffer> {
+    let options = options.unwrap_or_default();
+    let kdf = if options.raw_key.unwrap_or(false) {
+        Kdf::HkdfSha256
+    } else {
+        Kdf::Pbkdf2Sha256 { iterations: options.kdf_iterations.unwrap_or(DEFAULT_PBKDF2_ITERATIONS) }
+    };
+    let file_format = if options.binary.unwrap_or(false) { FileFormat::Binary } else { FileFormat::Armor };
+    let cipher = options.cipher.as_deref().unwrap_or("AES-256-CBC");
+
+    envelope::encrypt(&plaintext, &key, cipher, kdf, options.context.as_deref(), file_format)
+        .map(Buffer::from)
+        .map_err(|e| Error::from_reason(e.to_string()))
+}
+
+/// Decrypts the contents of an encrypted file, armored or binary. `context` is the
+/// context string the file was bound to, if any. Legacy files without a header are
+/// decrypted with `legacyCipher` (default: AES-256-CBC), like `envcrypt decrypt --cipher`.
+#[napi]
+pub fn decrypt(contents: Buffer, key: String, context: Option<String>, legacy_cipher: Option<String>) -> Result<Buffer> {
+    let legacy_cipher = legacy_cipher.as_deref().unwrap_or("AES-256-CBC");
+    envelope::decrypt_with_legacy_cipher(&contents, &key, context.as_deref(), legacy_cipher)
+        .map(|plaintext| Buffer::from(plaintext.as_slice()))
+        .map_err(|e| Error::from_reason(e.to_string()))
+}
@@ -0,0 +1,72 @@
+//! Python bindings for in-memory encryption and decryption (`pip install envcrypt`).
+//!
+//! ```python
+//! import envcrypt
+//!
+//! contents = envcrypt.encrypt(b"APP_KEY=test123", "my-key", cipher="AES-256-GCM")
+//! assert envcrypt.decrypt(contents, "my-key") == b"APP_KEY=test123"
+//! ```
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use envcrypt::armor::FileFormat;
+use envcrypt::envelope;
+use envcrypt::format::Kdf;
+use envcrypt::key::DEFAULT_PBKDF2_ITERATIONS;
+
+create_exception!(envcrypt, EnvcryptError, PyException, "Raised when encryption or decryption fails.");
+
+/// Encrypts `plaintext` into the contents of an encrypted file.
+///
+/// `cipher` accepts the same names and aliases as `envcrypt encrypt --cipher`. With
+/// `raw_key`, `key` must be a base64-encoded 32-byte key and HKDF replaces PBKDF2. A
+/// `context` binds the file to a context string, like `--aad`. `kdf_iterations` must be
+/// between 1000 and 100000000. Returns base64 armor, or raw bytes with `binary`.
+#[pyfunction]
+#[allow(clippy::too_many_arguments)] // Each one is a keyword argument in Python
+#[pyo3(signature = (plaintext, key, cipher = "AES-256-CBC", kdf_iterations = None, raw_key = false, context = None, binary = false))]
+fn encrypt<'py>(
+    py: Python<'py>,
+    plaintext: &[u8],
+    key: &str,
+    cipher: &str,
+    kdf_iterations: Option<u32>,
+    raw_key: bool,
+    context: Option<&str>,
+    binary: bool,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let kdf = if raw_key {
+        Kdf::HkdfSha256
+    } else {
+        Kdf::Pbkdf2Sha256 { iterations: kdf_iterations.unwrap_or(DEFAULT_PBKDF2_ITERATIONS) }
+    };
+    let file_format = if binary { FileFormat::Binary } else { FileFormat::Armor };
+    let contents = py.allow_threads(|| envelope::encrypt(plaintext, key, cipher, kdf, context, file_format))
+        .map_err(|e| EnvcryptError::new_err(e.to_string()))?;
+    Ok(PyBytes::new(py, &contents))
+}
+
+/// Decrypts the contents of an encrypted file, armored or binary.
+///
+/// `context` is the context string the file was bound to, if any. Legacy files without
+/// a header are decrypted with `legacy_cipher`, like `envcrypt decrypt --cipher`.
+#[pyfunction]
+#[pyo3(signature = (contents, key, context = None, legacy_cipher = "AES-256-CBC"))]
+fn decrypt<'py>(py: Python<'py>, contents: &[u8], key: &str, context: Option<&str>, legacy_cipher: &str) -> PyResult<Bound<'py, PyBytes>> {
+    let plaintext = py.allow_threads(|| envelope::decrypt_with_legacy_cipher(contents, key, context, legacy_cipher))
+        .map_err(|e| EnvcryptError::new_err(e.to_string()))?;
+    Ok(PyBytes::new(py, &plaintext))
+}
+
+#[pymodule]
+#[pyo3(name = "envcrypt")]
+fn envcrypt_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    m.add("EnvcryptError", m.py().get_type::<EnvcryptError>())?;
+    m.add_function(wrap_pyfunction!(encrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(decrypt, m)?)?;
+    Ok(())
+}
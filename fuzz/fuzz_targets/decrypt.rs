@@ -0,0 +1,36 @@
+//! Decrypts arbitrary file contents, as an attacker could hand envcrypt.
+
+#![no_main]
+
+use envcrypt::armor::decode_file;
+use envcrypt::cipher::get_cipher;
+use envcrypt::envelope;
+use envcrypt::format::{self, Kdf};
+use libfuzzer_sys::fuzz_target;
+
+const RAW_KEY: &str = "q83vEjRWeJCrze8SNFZ4kKvN7xI0VniQq83vEjRWeJA=";
+
+/// Highest PBKDF2 iteration count derived with, to keep executions fast.
+const MAX_FUZZ_ITERATIONS: u32 = 1_000;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok((decoded, _)) = decode_file(data) else {
+        return;
+    };
+    let Ok((header, body)) = format::decode(&decoded) else {
+        return;
+    };
+
+    match header.kdf {
+        Kdf::Pbkdf2Sha256 { iterations } if iterations > MAX_FUZZ_ITERATIONS => {
+            // Skip the key derivation, but still feed the body to the cipher
+            if let Ok(cipher) = get_cipher(header.cipher.as_deref().unwrap_or("AES-256-CBC")) {
+                assert!(cipher.decrypt(body, &[1u8; 32], &[2u8; 32]).is_err());
+            }
+        }
+        _ => {
+            let _ = envelope::decrypt(data, RAW_KEY, None);
+            let _ = envelope::decrypt(data, RAW_KEY, Some("fuzz"));
+        }
+    }
+});
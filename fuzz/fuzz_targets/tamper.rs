@@ -0,0 +1,46 @@
+//! Truncates and flips bits in valid encrypted files; every change must be rejected.
+
+#![no_main]
+
+use envcrypt::armor::FileFormat;
+use envcrypt::cipher::get_supported_ciphers;
+use envcrypt::envelope;
+use envcrypt::format::Kdf;
+use libfuzzer_sys::arbitrary::{self, Arbitrary};
+use libfuzzer_sys::fuzz_target;
+
+const RAW_KEY: &str = "q83vEjRWeJCrze8SNFZ4kKvN7xI0VniQq83vEjRWeJA=";
+
+#[derive(Arbitrary, Debug)]
+struct Tamper {
+    cipher: u8,
+    plaintext: Vec<u8>,
+    context: Option<String>,
+    truncate: Option<u16>,
+    flips: Vec<(u16, u8)>,
+}
+
+fuzz_target!(|input: Tamper| {
+    let ciphers = get_supported_ciphers();
+    let cipher = ciphers[input.cipher as usize % ciphers.len()];
+    let context = input.context.as_deref();
+    let original = envelope::encrypt(&input.plaintext, RAW_KEY, cipher, Kdf::HkdfSha256, context, FileFormat::Binary)
+        .expect("encryption with a raw key succeeds");
+
+    let mut tampered = original.clone();
+    if let Some(len) = input.truncate {
+        tampered.truncate(len as usize);
+    }
+    for (index, mask) in input.flips {
+        if let Some(byte) = tampered.get_mut(index as usize) {
+            *byte ^= mask;
+        }
+    }
+
+    let result = envelope::decrypt(&tampered, RAW_KEY, context);
+    if tampered == original {
+        assert_eq!(result.expect("untampered file decrypts").as_slice(), input.plaintext.as_slice());
+    } else {
+        assert!(result.is_err(), "tampered file decrypted");
+    }
+});
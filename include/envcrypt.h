@@ -0,0 +1,91 @@
+/*
+ * C interface to envcrypt's in-memory encryption and decryption.
+ *
+ * Build the shared library with:
+ *
+ *     cargo build --release --lib --no-default-features --features ffi
+ *
+ * Every function returns an envcrypt_status. Buffers filled by the library must be
+ * released with envcrypt_buffer_free(), which also zeroizes them. After a failure,
+ * envcrypt_last_error() describes what went wrong on the calling thread.
+ */
+
+#ifndef ENVCRYPT_H
+#define ENVCRYPT_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef enum envcrypt_status {
+    /* The call succeeded. */
+    ENVCRYPT_OK = 0,
+    /* A required pointer was null, a string was not valid UTF-8, or an option was
+     * invalid (for example an unknown cipher or a raw key that is not 32 bytes). */
+    ENVCRYPT_INVALID_ARGUMENT = 1,
+    /* The input is not an encrypted file this version can read. */
+    ENVCRYPT_INVALID_FORMAT = 2,
+    /* The context string does not match how the file was encrypted. */
+    ENVCRYPT_CONTEXT_MISMATCH = 3,
+    /* Decryption failed: wrong key or context string, or the file was modified. */
+    ENVCRYPT_AUTHENTICATION_FAILED = 4,
+    /* Encryption failed. */
+    ENVCRYPT_ENCRYPTION_FAILED = 5
+} envcrypt_status;
+
+/* A byte buffer allocated by the library. data is NULL for an empty buffer. */
+typedef struct envcrypt_buffer {
+    uint8_t *data;
+    size_t len;
+} envcrypt_buffer;
+
+/* Options for envcrypt_encrypt(). Pass NULL instead to use the defaults. */
+typedef struct envcrypt_encrypt_options {
+    /* Cipher name or alias ("AES-256-GCM", "chacha20", ...), or NULL for AES-256-CBC. */
+    const char *cipher;
+    /* PBKDF2 iteration count between 1000 and 100000000, or 0 for the default (100000).
+     * Ignored with raw_key. */
+    uint32_t kdf_iterations;
+    /* Non-zero if the key is a base64-encoded 32-byte key to use with HKDF. */
+    int raw_key;
+    /* Context string to bind the file to, or NULL. */
+    const char *context;
+    /* Non-zero to write raw bytes instead of base64 armor. */
+    int binary;
+} envcrypt_encrypt_options;
+
+/* Encrypts plaintext_len bytes at plaintext with key into the contents of an encrypted
+ * file. plaintext may be NULL if plaintext_len is 0. */
+envcrypt_status envcrypt_encrypt(const uint8_t *plaintext, size_t plaintext_len, const char *key,
+                                 const envcrypt_encrypt_options *options, envcrypt_buffer *out);
+
+/* Decrypts the contents of an encrypted file, armored or binary. context is the context
+ * string the file was bound to, or NULL. */
+envcrypt_status envcrypt_decrypt(const uint8_t *contents, size_t contents_len, const char *key,
+                                 const char *context, envcrypt_buffer *out);
+
+/* Like envcrypt_decrypt(), but decrypts legacy files without a header with legacy_cipher
+ * (a cipher name or alias, or NULL for AES-256-CBC), as `envcrypt decrypt --cipher` does.
+ * Files with a header use the cipher it records. */
+envcrypt_status envcrypt_decrypt_with_legacy_cipher(const uint8_t *contents, size_t contents_len,
+                                                    const char *key, const char *context,
+                                                    const char *legacy_cipher, envcrypt_buffer *out);
+
+/* Zeroizes and frees a buffer filled by the library, leaving it empty. */
+void envcrypt_buffer_free(envcrypt_buffer *buffer);
+
+/* Description of the last error on the calling thread, or NULL. Valid until the next
+ * failing call on the same thread. */
+const char *envcrypt_last_error(void);
+
+/* Library version, e.g. "0.2.1". */
+const char *envcrypt_version(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ENVCRYPT_H */
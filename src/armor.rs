@@ -0,0 +1,114 @@
+//! Encodings of encrypted files: base64 armor or raw binary.
+//!
+//! An encrypted file is the [format header](crate::format) followed by the cipher output,
+//! stored either as base64 text or as the raw bytes. Both encodings are recognised
+//! automatically when reading.
+
+use base64::Engine;
+
+use crate::format::{MAGIC, SALT_LEN};
+
+/// How the encrypted file contents are written to disk.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(any(feature = "encrypt", feature = "decrypt"), derive(clap::ValueEnum))]
+pub enum FileFormat {
+    /// Base64 text, safe to paste into config systems and diffs
+    #[default]
+    Armor,
+    /// Raw bytes starting with the "ENVC" magic, about 25% smaller than armor
+    Binary,
+}
+
+impl std::fmt::Display for FileFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileFormat::Armor => write!(f, "armor"),
+            FileFormat::Binary => write!(f, "binary"),
+        }
+    }
+}
+
+/// Encodes the encrypted data (header and cipher output) for writing in `format`.
+pub fn encode_file(data: &[u8], format: FileFormat) -> Vec<u8> {
+    match format {
+        FileFormat::Armor => base64::engine::general_purpose::STANDARD.encode(data).into_bytes(),
+        FileFormat::Binary => data.to_vec(),
+    }
+}
+
+/// Decodes the contents of an encrypted file in either format.
+///
+/// Binary files are recognised by the magic bytes of the [format header](crate::format);
+/// everything else is read as armor. Whitespace in armored files is ignored, so
+/// line-wrapped base64 is accepted.
+///
+/// # Returns
+///
+/// Returns the encrypted data and the format it was stored in.
+pub fn decode_file(contents: &[u8]) -> Result<(Vec<u8>, FileFormat), String> {
+    if contents.starts_with(MAGIC) {
+        return Ok((contents.to_vec(), FileFormat::Binary));
+    }
+
+    let text: Vec<u8> = contents.iter().copied().filter(|b| !b.is_ascii_whitespace()).collect();
+    base64::engine::general_purpose::STANDARD.decode(text)
+        .map(|data| (data, FileFormat::Armor))
+        .map_err(|e| format!("Invalid base64 in encrypted file: {}", e))
+}
+
+/// Smallest legacy file: the salt followed by the shortest cipher output (a 12-byte
+/// nonce and a 16-byte tag around an empty plaintext).
+const MIN_LEGACY_LEN: usize = SALT_LEN + 12 + 16;
+
+/// Checks whether `contents` look like an encrypted file in either format.
+///
+/// Files with a header are recognised by the magic bytes, raw or inside the armor.
+/// Legacy files have no header, so an armored file is also treated as encrypted if it is
+/// a single base64 block long enough to be one. Dotenv content never is, because of the
+/// `=` in the middle of its lines.
+pub fn looks_encrypted(contents: &[u8]) -> bool {
+    if contents.starts_with(MAGIC) {
+        return true;
+    }
+    match decode_file(contents) {
+        Ok((data, _)) => data.starts_with(MAGIC) || data.len() >= MIN_LEGACY_LEN,
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip_both_formats() {
+        let data = [MAGIC.as_slice(), &[1, 0, 0, 0xff, 0x10]].concat();
+        for format in [FileFormat::Armor, FileFormat::Binary] {
+            let encoded = encode_file(&data, format);
+            assert_eq!(decode_file(&encoded).unwrap(), (data.clone(), format));
+        }
+    }
+
+    #[test]
+    fn test_wrapped_armor_is_accepted() {
+        let encoded = String::from_utf8(encode_file(&[7u8; 100], FileFormat::Armor)).unwrap();
+        let wrapped = encoded.as_bytes().chunks(64)
+            .map(|line| std::str::from_utf8(line).unwrap())
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(decode_file(wrapped.as_bytes()).unwrap().0, vec![7u8; 100]);
+    }
+
+    #[test]
+    fn test_looks_encrypted() {
+        let data = [MAGIC.as_slice(), &[2, 0, 0]].concat();
+        assert!(looks_encrypted(&encode_file(&data, FileFormat::Binary)));
+        assert!(looks_encrypted(&encode_file(&data, FileFormat::Armor)));
+        assert!(looks_encrypted(&encode_file(&[1u8; 80], FileFormat::Armor)));
+
+        assert!(!looks_encrypted(b"APP_KEY=test123\nDEBUG=true\n"));
+        assert!(!looks_encrypted(b"# just a comment\n"));
+        assert!(!looks_encrypted(b""));
+        assert!(!looks_encrypted(b"TOKEN"));
+    }
+}
@@ -2,6 +2,11 @@
 //!
 //! This module provides the [`Cipher`] trait and implementations for encrypting and decrypting
 //! data. All implementations use authenticated encryption with HMAC for integrity verification.
+//! Ciphers are selected by name with [`get_cipher()`], which also accepts common aliases.
+//!
+//! Each cipher only implements the [`Aead`] core, encrypting and decrypting in place with a
+//! given nonce; nonce generation, key checks and the `[nonce][encrypted data][tag]` layout
+//! live in one framing layer that makes every `Aead` a [`Cipher`].
 //!
 //! # Security Considerations
 //!
@@ -34,30 +39,186 @@ use cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
 use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, Zeroizing};
 
 #[cfg(feature = "cipher")]
 use aes_gcm::{
-    aead::{Aead as AeadTrait, KeyInit},
-    Aes256Gcm as Aes256GcmImpl, Nonce as AesGcmNonce, Key as AesGcmKey,
+    aead::{AeadInPlace, KeyInit, Nonce, Tag},
+    Aes256Gcm as Aes256GcmImpl,
 };
 
 #[cfg(feature = "cipher")]
-use chacha20poly1305::{
-    ChaCha20Poly1305 as ChaCha20Poly1305Impl, Nonce as ChaChaNonce, Key as ChaChaKey,
-};
+use chacha20poly1305::ChaCha20Poly1305 as ChaCha20Poly1305Impl;
 
-use crate::key;
 
 type Aes256CbcEnc = Encryptor<Aes256>;
 type Aes256CbcDec = Decryptor<Aes256>;
 type HmacSha256 = Hmac<Sha256>;
 
-/// Generates a cryptographically secure random 12-byte nonce for AEAD ciphers.
-fn generate_nonce_12() -> [u8; 12] {
-    use rand::RngCore;
-    let mut nonce = [0u8; 12];
-    rand::thread_rng().fill_bytes(&mut nonce);
-    nonce
+/// Length of the encryption key and, for ciphers that use one, the MAC key (256 bits).
+const KEY_LEN: usize = 32;
+
+/// Feeds the AES-256-CBC MAC input into `mac`.
+///
+/// Without associated data the MAC covers `iv + encrypted_data`, as it always has. With
+/// associated data it covers `aad + iv + encrypted_data + aad length (u64 BE)`; the
+/// trailing length keeps the boundary between the associated data and the IV unambiguous.
+///
+/// Files with a header always pass it as associated data (see
+/// [`format::associated_data()`](crate::format::associated_data)), so their MAC always
+/// ends with the length; only legacy files without a header use the bare form. The
+/// unauthenticated version 1 header, which would have allowed a header file to be
+/// re-framed with an empty `aad`, is rejected by [`format::decode()`](crate::format::decode).
+fn update_cbc_mac(mac: &mut HmacSha256, aad: &[u8], iv: &[u8], encrypted_data: &[u8]) {
+    mac.update(aad);
+    mac.update(iv);
+    mac.update(encrypted_data);
+    if !aad.is_empty() {
+        mac.update(&(aad.len() as u64).to_be_bytes());
+    }
+}
+
+/// The keys an [`Aead`] encrypts and authenticates with.
+pub struct AeadKeys<'a> {
+    /// 256-bit encryption key.
+    pub encryption_key: &'a [u8; KEY_LEN],
+    /// 256-bit MAC key; only used, and checked to be 32 bytes, by ciphers with
+    /// [`Aead::USES_MAC_KEY`].
+    pub mac_key: &'a [u8],
+}
+
+/// The core of an authenticated cipher: encrypting and decrypting in place with a given
+/// nonce.
+///
+/// This is all a new cipher implements. Every `Aead` is a [`Cipher`] through a single
+/// framing layer, which checks the keys, generates a random nonce for each encryption,
+/// lays out the output as `[nonce][encrypted data][tag]` in one allocation, rejects input
+/// too short for that layout, and zeroizes the buffers it hands to the core.
+pub trait Aead {
+    /// Length of the nonce (IV) in bytes.
+    const NONCE_LEN: usize;
+    /// Length of the authentication tag (MAC) in bytes.
+    const TAG_LEN: usize;
+    /// Whether the cipher authenticates with the separate MAC key; ciphers with built-in
+    /// authentication ignore it.
+    const USES_MAC_KEY: bool = false;
+    /// Length of the shortest valid encrypted data, e.g. one block for padded block ciphers.
+    const MIN_ENCRYPTED_LEN: usize = 0;
+
+    /// Length of the encrypted data for `plaintext_len` bytes of plaintext; the same for
+    /// stream ciphers, which is the default.
+    fn encrypted_len(&self, plaintext_len: usize) -> usize {
+        plaintext_len
+    }
+
+    /// Encrypts the first `plaintext_len` bytes of `data` in place, filling all of it,
+    /// and writes the tag over `aad`, `nonce` and the encrypted data to `tag`.
+    ///
+    /// `data` is [`encrypted_len()`](Aead::encrypted_len) bytes long, so bytes after the
+    /// plaintext are room for padding. `nonce` and `tag` are exactly
+    /// [`NONCE_LEN`](Aead::NONCE_LEN) and [`TAG_LEN`](Aead::TAG_LEN) bytes long.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CipherError::EncryptionFailed`] if encryption fails.
+    fn seal_in_place(
+        &self,
+        keys: &AeadKeys,
+        nonce: &[u8],
+        aad: &[u8],
+        data: &mut [u8],
+        plaintext_len: usize,
+        tag: &mut [u8],
+    ) -> Result<(), CipherError>;
+
+    /// Verifies `tag` and decrypts `data` in place, returning the length of the plaintext
+    /// at its start.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CipherError::MacVerificationFailed`] if the tag does not match and
+    /// [`CipherError::DecryptionFailed`] if the decrypted data is invalid. `data` may be
+    /// partially decrypted on error; the framing layer zeroizes it.
+    fn open_in_place(&self, keys: &AeadKeys, nonce: &[u8], aad: &[u8], data: &mut [u8], tag: &[u8]) -> Result<usize, CipherError>;
+}
+
+/// Checks the key lengths for an [`Aead`], returning `error` if they are wrong.
+fn aead_keys<'a, A: Aead>(encryption_key: &'a [u8], mac_key: &'a [u8], error: impl Fn(&str) -> CipherError) -> Result<AeadKeys<'a>, CipherError> {
+    let encryption_key = encryption_key.try_into()
+        .map_err(|_| error("Encryption key must be 32 bytes (256 bits)"))?;
+    if A::USES_MAC_KEY && mac_key.len() != KEY_LEN {
+        return Err(error("MAC key must be 32 bytes (256 bits)"));
+    }
+    Ok(AeadKeys { encryption_key, mac_key })
+}
+
+/// The framing layer shared by all ciphers.
+impl<A: Aead> Cipher for A {
+    fn encrypt_with_aad(&self, plaintext: &[u8], aad: &[u8], encryption_key: &[u8], mac_key: &[u8]) -> Result<Vec<u8>, CipherError> {
+        use rand::RngCore;
+        let mut nonce = vec![0u8; A::NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        self.encrypt_with_nonce(plaintext, aad, &nonce, encryption_key, mac_key)
+    }
+
+    fn encrypt_with_nonce(&self, plaintext: &[u8], aad: &[u8], nonce: &[u8], encryption_key: &[u8], mac_key: &[u8]) -> Result<Vec<u8>, CipherError> {
+        let keys = aead_keys::<A>(encryption_key, mac_key, |message| CipherError::EncryptionFailed(message.to_string()))?;
+        if nonce.len() != A::NONCE_LEN {
+            return Err(CipherError::EncryptionFailed(format!("Nonce must be {} bytes", A::NONCE_LEN)));
+        }
+
+        // Allocate the output once with its final size and encrypt the plaintext in place
+        // in it, so the plaintext is never copied anywhere else, also not by a reallocation
+        let encrypted_len = self.encrypted_len(plaintext.len());
+        let mut output = Vec::with_capacity(A::NONCE_LEN + encrypted_len + A::TAG_LEN);
+        output.extend_from_slice(nonce);
+        output.extend_from_slice(plaintext);
+        output.resize(A::NONCE_LEN + encrypted_len + A::TAG_LEN, 0);
+
+        let (nonce, rest) = output.split_at_mut(A::NONCE_LEN);
+        let (data, tag) = rest.split_at_mut(encrypted_len);
+        if let Err(e) = self.seal_in_place(&keys, nonce, aad, data, plaintext.len(), tag) {
+            output.zeroize();
+            return Err(e);
+        }
+        Ok(output)
+    }
+
+    fn decrypt_with_aad(&self, ciphertext: &[u8], aad: &[u8], encryption_key: &[u8], mac_key: &[u8]) -> Result<Zeroizing<Vec<u8>>, CipherError> {
+        let keys = aead_keys::<A>(encryption_key, mac_key, |_| CipherError::DecryptionFailed)?;
+        if ciphertext.len() < A::NONCE_LEN + A::MIN_ENCRYPTED_LEN + A::TAG_LEN {
+            return Err(CipherError::InvalidFormat);
+        }
+
+        let (nonce, rest) = ciphertext.split_at(A::NONCE_LEN);
+        let (encrypted_data, tag) = rest.split_at(rest.len() - A::TAG_LEN);
+        // Decrypt in place in the returned buffer, so the plaintext exists in this one
+        // buffer only. Truncating keeps the capacity, so bytes cut off are zeroized too.
+        let mut buffer = Zeroizing::new(encrypted_data.to_vec());
+        let plaintext_len = self.open_in_place(&keys, nonce, aad, &mut buffer, tag)?;
+        buffer.truncate(plaintext_len);
+        Ok(buffer)
+    }
+}
+
+/// [`Aead::seal_in_place()`] for the AEADs of the RustCrypto project.
+#[cfg(feature = "cipher")]
+fn seal_detached<C: AeadInPlace + KeyInit>(key: &[u8; KEY_LEN], nonce: &[u8], aad: &[u8], data: &mut [u8], tag: &mut [u8]) -> Result<(), String> {
+    let cipher = C::new_from_slice(key).map_err(|e| e.to_string())?;
+    let computed = cipher.encrypt_in_place_detached(Nonce::<C>::from_slice(nonce), aad, data)
+        .map_err(|e| format!("{:?}", e))?;
+    tag.copy_from_slice(&computed);
+    Ok(())
+}
+
+/// [`Aead::open_in_place()`] for the AEADs of the RustCrypto project; a wrong tag is a
+/// MAC verification failure.
+#[cfg(feature = "cipher")]
+fn open_detached<C: AeadInPlace + KeyInit>(key: &[u8; KEY_LEN], nonce: &[u8], aad: &[u8], data: &mut [u8], tag: &[u8]) -> Result<usize, CipherError> {
+    let cipher = C::new_from_slice(key).map_err(|_| CipherError::DecryptionFailed)?;
+    cipher.decrypt_in_place_detached(Nonce::<C>::from_slice(nonce), aad, data, Tag::<C>::from_slice(tag))
+        .map_err(|_| CipherError::MacVerificationFailed)?;
+    Ok(data.len())
 }
 
 /// Trait for encryption/decryption operations with authenticated encryption.
@@ -65,6 +226,8 @@ fn generate_nonce_12() -> [u8; 12] {
 /// Implementations of this trait provide both confidentiality (encryption) and
 /// authenticity (MAC verification) for data protection.
 ///
+/// Implemented for every [`Aead`], which is what ciphers implement.
+///
 /// # Security Model
 ///
 /// All implementations must:
@@ -103,8 +266,49 @@ pub trait Cipher {
     ///
     /// The encryption key and MAC key should be derived from a user-provided password
     /// using a key derivation function like PBKDF2. Never use user passwords directly.
-    fn encrypt(&self, plaintext: &[u8], encryption_key: &[u8], mac_key: &[u8]) -> Result<Vec<u8>, CipherError>;
-    
+    fn encrypt(&self, plaintext: &[u8], encryption_key: &[u8], mac_key: &[u8]) -> Result<Vec<u8>, CipherError> {
+        self.encrypt_with_aad(plaintext, &[], encryption_key, mac_key)
+    }
+
+    /// Encrypts plaintext like [`encrypt()`](Cipher::encrypt), additionally authenticating
+    /// `aad` (associated data).
+    ///
+    /// The associated data is not part of the output; the same bytes must be passed to
+    /// [`decrypt_with_aad()`](Cipher::decrypt_with_aad). Empty associated data produces
+    /// the same output format as [`encrypt()`](Cipher::encrypt).
+    ///
+    /// # Errors
+    ///
+    /// See [`encrypt()`](Cipher::encrypt).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use envcrypt::cipher::{Cipher, Aes256Cbc};
+    ///
+    /// let (encryption_key, mac_key) = ([1u8; 32], [2u8; 32]);
+    /// let ciphertext = Aes256Cbc.encrypt_with_aad(b"secret", b"header", &encryption_key, &mac_key)?;
+    ///
+    /// assert_eq!(Aes256Cbc.decrypt_with_aad(&ciphertext, b"header", &encryption_key, &mac_key)?.as_slice(), b"secret");
+    /// assert!(Aes256Cbc.decrypt_with_aad(&ciphertext, b"other", &encryption_key, &mac_key).is_err());
+    /// assert!(Aes256Cbc.decrypt(&ciphertext, &encryption_key, &mac_key).is_err());
+    /// # Ok::<(), envcrypt::cipher::CipherError>(())
+    /// ```
+    fn encrypt_with_aad(&self, plaintext: &[u8], aad: &[u8], encryption_key: &[u8], mac_key: &[u8]) -> Result<Vec<u8>, CipherError>;
+
+    /// Encrypts plaintext like [`encrypt_with_aad()`](Cipher::encrypt_with_aad), but with
+    /// the given IV or nonce instead of a random one.
+    ///
+    /// This exists for reproducible output such as test vectors. Never encrypt two
+    /// different plaintexts with the same key and nonce: for AES-256-GCM and
+    /// ChaCha20-Poly1305 that reveals both plaintexts and allows forgeries.
+    ///
+    /// # Errors
+    ///
+    /// See [`encrypt()`](Cipher::encrypt); also fails if `nonce` has the wrong length for
+    /// the cipher (see [`cipher_layout()`]).
+    fn encrypt_with_nonce(&self, plaintext: &[u8], aad: &[u8], nonce: &[u8], encryption_key: &[u8], mac_key: &[u8]) -> Result<Vec<u8>, CipherError>;
+
     /// Decrypts raw ciphertext bytes and verifies authenticity.
     ///
     /// This function performs authenticated decryption:
@@ -121,7 +325,8 @@ pub trait Cipher {
     ///
     /// # Returns
     ///
-    /// Returns a `Vec<u8>` containing the decrypted plaintext.
+    /// Returns the decrypted plaintext, which is zeroized when dropped. It is decrypted
+    /// in place in the only buffer allocated for it.
     ///
     /// # Errors
     ///
@@ -134,7 +339,18 @@ pub trait Cipher {
     ///
     /// MAC verification is performed in constant time to prevent timing attacks.
     /// Decryption only occurs after successful MAC verification to prevent padding oracle attacks.
-    fn decrypt(&self, ciphertext: &[u8], encryption_key: &[u8], mac_key: &[u8]) -> Result<Vec<u8>, CipherError>;
+    fn decrypt(&self, ciphertext: &[u8], encryption_key: &[u8], mac_key: &[u8]) -> Result<Zeroizing<Vec<u8>>, CipherError> {
+        self.decrypt_with_aad(ciphertext, &[], encryption_key, mac_key)
+    }
+
+    /// Decrypts ciphertext produced by [`encrypt_with_aad()`](Cipher::encrypt_with_aad),
+    /// verifying it together with the associated data `aad`.
+    ///
+    /// # Errors
+    ///
+    /// See [`decrypt()`](Cipher::decrypt). Different associated data fails like a
+    /// tampered ciphertext.
+    fn decrypt_with_aad(&self, ciphertext: &[u8], aad: &[u8], encryption_key: &[u8], mac_key: &[u8]) -> Result<Zeroizing<Vec<u8>>, CipherError>;
 }
 
 /// Errors that can occur during encryption or decryption operations.
@@ -220,107 +436,54 @@ impl std::error::Error for CipherError {}
 /// ```
 pub struct Aes256Cbc;
 
-impl Cipher for Aes256Cbc {
-    fn encrypt(&self, plaintext: &[u8], encryption_key: &[u8], mac_key: &[u8]) -> Result<Vec<u8>, CipherError> {
-        // Validate key length
-        if encryption_key.len() != 32 {
-            return Err(CipherError::EncryptionFailed("Encryption key must be 32 bytes (256 bits)".to_string()));
-        }
-        if mac_key.len() != 32 {
-            return Err(CipherError::EncryptionFailed("MAC key must be 32 bytes (256 bits)".to_string()));
-        }
+impl Aead for Aes256Cbc {
+    const NONCE_LEN: usize = 16;
+    const TAG_LEN: usize = 32;
+    const USES_MAC_KEY: bool = true;
+    const MIN_ENCRYPTED_LEN: usize = 16;
 
-        // Generate random IV (16 bytes for AES block size)
-        let iv = key::generate_salt(); // Reusing salt generation for IV
-        
-        // Convert key and IV to arrays
-        let key_array: [u8; 32] = encryption_key.try_into()
-            .map_err(|_| CipherError::EncryptionFailed("Invalid key length".to_string()))?;
-        let iv_array: [u8; 16] = iv.try_into()
-            .map_err(|_| CipherError::EncryptionFailed("Invalid IV length".to_string()))?;
-        
-        // Encrypt using AES-256-CBC
-        let cipher = Aes256CbcEnc::new(&key_array.into(), &iv_array.into());
-        
-        // Prepare buffer with plaintext
-        let mut buffer = plaintext.to_vec();
-        let pt_len = buffer.len();
-        // Ensure buffer is large enough for padding (add one block)
-        buffer.resize(buffer.len() + 16, 0);
-        
-        let encrypted = cipher.encrypt_padded_mut::<cipher::block_padding::Pkcs7>(&mut buffer, pt_len)
-            .map_err(|e| CipherError::EncryptionFailed(format!("Encryption failed: {:?}", e)))?;
-        
-        let buffer = encrypted.to_vec();
-        
-        // Compute HMAC of (iv + encrypted_data)
-        let mut mac_input = Vec::with_capacity(iv.len() + buffer.len());
-        mac_input.extend_from_slice(&iv);
-        mac_input.extend_from_slice(&buffer);
-        
-        let mut mac = <HmacSha256 as Mac>::new_from_slice(mac_key)
+    fn encrypted_len(&self, plaintext_len: usize) -> usize {
+        // PKCS7 always pads, with a whole block if the plaintext fills its last one
+        (plaintext_len / 16 + 1) * 16
+    }
+
+    fn seal_in_place(
+        &self,
+        keys: &AeadKeys,
+        iv: &[u8],
+        aad: &[u8],
+        data: &mut [u8],
+        plaintext_len: usize,
+        tag: &mut [u8],
+    ) -> Result<(), CipherError> {
+        let mut mac = <HmacSha256 as Mac>::new_from_slice(keys.mac_key)
             .map_err(|e| CipherError::EncryptionFailed(format!("Failed to create MAC: {:?}", e)))?;
-        mac.update(&mac_input);
-        let mac_result = mac.finalize();
-        let mac_bytes = mac_result.into_bytes();
-        
-        // Combine: iv + encrypted_data + mac
-        let mut output = Vec::with_capacity(iv.len() + buffer.len() + mac_bytes.len());
-        output.extend_from_slice(&iv);
-        output.extend_from_slice(&buffer);
-        output.extend_from_slice(mac_bytes.as_slice());
-        
-        Ok(output)
+        let cipher = Aes256CbcEnc::new_from_slices(keys.encryption_key, iv)
+            .map_err(|e| CipherError::EncryptionFailed(format!("Encryption failed: {:?}", e)))?;
+        cipher.encrypt_padded_mut::<cipher::block_padding::Pkcs7>(data, plaintext_len)
+            .map_err(|e| CipherError::EncryptionFailed(format!("Encryption failed: {:?}", e)))?;
+
+        // MAC of (aad + iv + encrypted_data + aad length)
+        update_cbc_mac(&mut mac, aad, iv, data);
+        tag.copy_from_slice(&mac.finalize().into_bytes());
+        Ok(())
     }
-    
-    fn decrypt(&self, ciphertext: &[u8], encryption_key: &[u8], mac_key: &[u8]) -> Result<Vec<u8>, CipherError> {
-        // Validate key length
-        if encryption_key.len() != 32 {
-            return Err(CipherError::DecryptionFailed);
-        }
-        if mac_key.len() != 32 {
-            return Err(CipherError::DecryptionFailed);
-        }
 
-        // Validate minimum size: iv (16) + at least 1 block (16) + mac (32) = 64 bytes
-        if ciphertext.len() < 64 {
-            return Err(CipherError::InvalidFormat);
-        }
-        
-        // Extract components
-        let iv = &ciphertext[0..16];
-        let encrypted_data = &ciphertext[16..ciphertext.len() - 32];
-        let provided_mac = &ciphertext[ciphertext.len() - 32..];
-        
-        // Verify MAC
-        let mut mac_input = Vec::with_capacity(iv.len() + encrypted_data.len());
-        mac_input.extend_from_slice(iv);
-        mac_input.extend_from_slice(encrypted_data);
-        
-        let mut mac = <HmacSha256 as Mac>::new_from_slice(mac_key)
+    fn open_in_place(&self, keys: &AeadKeys, iv: &[u8], aad: &[u8], data: &mut [u8], tag: &[u8]) -> Result<usize, CipherError> {
+        // Verify the MAC before decrypting anything, to prevent padding oracle attacks
+        let mut mac = <HmacSha256 as Mac>::new_from_slice(keys.mac_key)
             .map_err(|_| CipherError::MacVerificationFailed)?;
-        mac.update(&mac_input);
-        let mac_result = mac.finalize();
-        
+        update_cbc_mac(&mut mac, aad, iv, data);
         // Constant-time MAC comparison to prevent timing attacks
-        if mac_result.into_bytes().as_slice().ct_eq(provided_mac).unwrap_u8() == 0 {
+        if mac.finalize().into_bytes().as_slice().ct_eq(tag).unwrap_u8() == 0 {
             return Err(CipherError::MacVerificationFailed);
         }
-        
-        // Convert key and IV to arrays
-        let key_array: [u8; 32] = encryption_key.try_into()
-            .map_err(|_| CipherError::DecryptionFailed)?;
-        let iv_array: [u8; 16] = iv.try_into()
+
+        let cipher = Aes256CbcDec::new_from_slices(keys.encryption_key, iv)
             .map_err(|_| CipherError::DecryptionFailed)?;
-        
-        // Decrypt
-        let cipher = Aes256CbcDec::new(&key_array.into(), &iv_array.into());
-        
-        let mut buffer = encrypted_data.to_vec();
-        let decrypted = cipher.decrypt_padded_mut::<cipher::block_padding::Pkcs7>(&mut buffer)
+        let plaintext = cipher.decrypt_padded_mut::<cipher::block_padding::Pkcs7>(data)
             .map_err(|_| CipherError::DecryptionFailed)?;
-        
-        Ok(decrypted.to_vec())
+        Ok(plaintext.len())
     }
 }
 
@@ -360,66 +523,25 @@ impl Cipher for Aes256Cbc {
 pub struct Aes256Gcm;
 
 #[cfg(feature = "cipher")]
-impl Cipher for Aes256Gcm {
-    fn encrypt(&self, plaintext: &[u8], encryption_key: &[u8], _mac_key: &[u8]) -> Result<Vec<u8>, CipherError> {
-        // Validate key length
-        if encryption_key.len() != 32 {
-            return Err(CipherError::EncryptionFailed("Encryption key must be 32 bytes (256 bits)".to_string()));
-        }
+impl Aead for Aes256Gcm {
+    const NONCE_LEN: usize = 12;
+    const TAG_LEN: usize = 16;
 
-        // Generate random nonce (12 bytes for GCM)
-        let nonce = generate_nonce_12();
-        
-        // Convert key to array
-        let key_array: [u8; 32] = encryption_key.try_into()
-            .map_err(|_| CipherError::EncryptionFailed("Invalid key length".to_string()))?;
-        
-        // Create cipher instance
-        let key = AesGcmKey::<Aes256GcmImpl>::from_slice(&key_array);
-        let cipher = Aes256GcmImpl::new(key);
-        
-        // Encrypt with authentication
-        let nonce_array = AesGcmNonce::from_slice(&nonce);
-        let ciphertext = AeadTrait::encrypt(&cipher, nonce_array, plaintext)
-            .map_err(|e| CipherError::EncryptionFailed(format!("GCM encryption failed: {:?}", e)))?;
-        
-        // Combine: nonce + encrypted_data + tag
-        let mut output = Vec::with_capacity(nonce.len() + ciphertext.len());
-        output.extend_from_slice(&nonce);
-        output.extend_from_slice(&ciphertext);
-        
-        Ok(output)
+    fn seal_in_place(
+        &self,
+        keys: &AeadKeys,
+        nonce: &[u8],
+        aad: &[u8],
+        data: &mut [u8],
+        _plaintext_len: usize,
+        tag: &mut [u8],
+    ) -> Result<(), CipherError> {
+        seal_detached::<Aes256GcmImpl>(keys.encryption_key, nonce, aad, data, tag)
+            .map_err(|e| CipherError::EncryptionFailed(format!("GCM encryption failed: {}", e)))
     }
-    
-    fn decrypt(&self, ciphertext: &[u8], encryption_key: &[u8], _mac_key: &[u8]) -> Result<Vec<u8>, CipherError> {
-        // Validate key length
-        if encryption_key.len() != 32 {
-            return Err(CipherError::DecryptionFailed);
-        }
 
-        // Validate minimum size: nonce (12) + tag (16) = 28 bytes
-        if ciphertext.len() < 28 {
-            return Err(CipherError::InvalidFormat);
-        }
-        
-        // Extract components
-        let nonce = &ciphertext[0..12];
-        let encrypted_data = &ciphertext[12..];
-        
-        // Convert key to array
-        let key_array: [u8; 32] = encryption_key.try_into()
-            .map_err(|_| CipherError::DecryptionFailed)?;
-        
-        // Create cipher instance
-        let key = AesGcmKey::<Aes256GcmImpl>::from_slice(&key_array);
-        let cipher = Aes256GcmImpl::new(key);
-        
-        // Decrypt with authentication
-        let nonce_array = AesGcmNonce::from_slice(nonce);
-        let plaintext = AeadTrait::decrypt(&cipher, nonce_array, encrypted_data)
-            .map_err(|_| CipherError::MacVerificationFailed)?; // GCM auth failure
-        
-        Ok(plaintext)
+    fn open_in_place(&self, keys: &AeadKeys, nonce: &[u8], aad: &[u8], data: &mut [u8], tag: &[u8]) -> Result<usize, CipherError> {
+        open_detached::<Aes256GcmImpl>(keys.encryption_key, nonce, aad, data, tag)
     }
 }
 
@@ -460,65 +582,371 @@ impl Cipher for Aes256Gcm {
 pub struct ChaCha20Poly1305;
 
 #[cfg(feature = "cipher")]
-impl Cipher for ChaCha20Poly1305 {
-    fn encrypt(&self, plaintext: &[u8], encryption_key: &[u8], _mac_key: &[u8]) -> Result<Vec<u8>, CipherError> {
-        // Validate key length
-        if encryption_key.len() != 32 {
-            return Err(CipherError::EncryptionFailed("Encryption key must be 32 bytes (256 bits)".to_string()));
-        }
+impl Aead for ChaCha20Poly1305 {
+    const NONCE_LEN: usize = 12;
+    const TAG_LEN: usize = 16;
 
-        // Generate random nonce (12 bytes for ChaCha20-Poly1305)
-        let nonce = generate_nonce_12();
-        
-        // Convert key to array
-        let key_array: [u8; 32] = encryption_key.try_into()
-            .map_err(|_| CipherError::EncryptionFailed("Invalid key length".to_string()))?;
-        
-        // Create cipher instance
-        let key = ChaChaKey::from_slice(&key_array);
-        let cipher = ChaCha20Poly1305Impl::new(key);
-        
-        // Encrypt with authentication
-        let nonce_array = ChaChaNonce::from_slice(&nonce);
-        let ciphertext = AeadTrait::encrypt(&cipher, nonce_array, plaintext)
-            .map_err(|e| CipherError::EncryptionFailed(format!("ChaCha20-Poly1305 encryption failed: {:?}", e)))?;
-        
-        // Combine: nonce + encrypted_data + tag
-        let mut output = Vec::with_capacity(nonce.len() + ciphertext.len());
-        output.extend_from_slice(&nonce);
-        output.extend_from_slice(&ciphertext);
-        
-        Ok(output)
+    fn seal_in_place(
+        &self,
+        keys: &AeadKeys,
+        nonce: &[u8],
+        aad: &[u8],
+        data: &mut [u8],
+        _plaintext_len: usize,
+        tag: &mut [u8],
+    ) -> Result<(), CipherError> {
+        seal_detached::<ChaCha20Poly1305Impl>(keys.encryption_key, nonce, aad, data, tag)
+            .map_err(|e| CipherError::EncryptionFailed(format!("ChaCha20-Poly1305 encryption failed: {}", e)))
+    }
+
+    fn open_in_place(&self, keys: &AeadKeys, nonce: &[u8], aad: &[u8], data: &mut [u8], tag: &[u8]) -> Result<usize, CipherError> {
+        open_detached::<ChaCha20Poly1305Impl>(keys.encryption_key, nonce, aad, data, tag)
+    }
+}
+
+/// Cipher recommended for new files: an AEAD, where AES-256-CBC needs a separate
+/// HMAC-SHA256 to detect tampering.
+pub const RECOMMENDED_CIPHER: &str = "AES-256-GCM";
+
+/// Returns a list of all supported cipher names.
+///
+/// The list respects feature flags, so it only includes ciphers that are
+/// available in the current build configuration.
+///
+/// # Returns
+///
+/// Returns a vector of cipher name strings. The first cipher in the list
+/// is the default cipher.
+pub fn get_supported_ciphers() -> Vec<&'static str> {
+    let mut ciphers = vec!["AES-256-CBC"];
+    
+    #[cfg(feature = "cipher")]
+    {
+        ciphers.push("AES-256-GCM");
+        ciphers.push("CHACHA20-POLY1305");
     }
     
-    fn decrypt(&self, ciphertext: &[u8], encryption_key: &[u8], _mac_key: &[u8]) -> Result<Vec<u8>, CipherError> {
-        // Validate key length
-        if encryption_key.len() != 32 {
-            return Err(CipherError::DecryptionFailed);
+    ciphers
+}
+
+/// Other spellings accepted for each cipher, compared after upper-casing.
+const CIPHER_ALIASES: &[(&str, &str)] = &[
+    ("AES256CBC", "AES-256-CBC"),
+    ("AES-CBC", "AES-256-CBC"),
+    ("CBC", "AES-256-CBC"),
+    ("AES256GCM", "AES-256-GCM"),
+    ("AES-GCM", "AES-256-GCM"),
+    ("GCM", "AES-256-GCM"),
+    ("CHACHA20POLY1305", "CHACHA20-POLY1305"),
+    ("CHACHA20", "CHACHA20-POLY1305"),
+    ("CHACHA", "CHACHA20-POLY1305"),
+];
+
+/// Resolves a cipher name or alias to the canonical name of a supported cipher.
+///
+/// Names are case-insensitive, and common aliases such as `AES256GCM`, `gcm` or
+/// `chacha20` are accepted. Only ciphers enabled in this build are returned.
+///
+/// # Errors
+///
+/// Returns an error string if the name is not recognized, suggesting the closest
+/// supported cipher when the name looks like a typo of one.
+///
+/// # Example
+///
+/// ```
+/// use envcrypt::cipher::canonical_cipher_name;
+///
+/// assert_eq!(canonical_cipher_name("aes-256-cbc")?, "AES-256-CBC");
+/// assert_eq!(canonical_cipher_name("AES256CBC")?, "AES-256-CBC");
+/// assert!(canonical_cipher_name("AES-256-CBX").unwrap_err().contains("did you mean AES-256-CBC?"));
+/// # Ok::<(), String>(())
+/// ```
+pub fn canonical_cipher_name(cipher_name: &str) -> Result<&'static str, String> {
+    let supported = get_supported_ciphers();
+    let name = cipher_name.trim().to_uppercase();
+
+    let canonical = supported.iter().copied().find(|&cipher| cipher == name)
+        .or_else(|| {
+            CIPHER_ALIASES.iter()
+                .find(|&&(alias, _)| alias == name)
+                .map(|&(_, cipher)| cipher)
+                .filter(|cipher| supported.contains(cipher))
+        });
+    if let Some(canonical) = canonical {
+        return Ok(canonical);
+    }
+
+    let suggestion = match closest_cipher(&name, &supported) {
+        Some(cipher) => format!(" (did you mean {}?)", cipher),
+        None => String::new(),
+    };
+    Err(format!(
+        "Unsupported cipher: {}{}. Supported ciphers: {}",
+        cipher_name,
+        suggestion,
+        supported.join(", ")
+    ))
+}
+
+/// Finds the supported cipher whose name or alias is closest to `name`, if any is
+/// within a couple of typos of it.
+fn closest_cipher(name: &str, supported: &[&'static str]) -> Option<&'static str> {
+    // Short names allow fewer edits, so "ab" is not mistaken for "CBC"
+    let max_distance = (name.chars().count() / 3).min(2);
+    let candidates = supported.iter().map(|&cipher| (cipher, cipher))
+        .chain(CIPHER_ALIASES.iter().copied())
+        .filter(|(_, cipher)| supported.contains(cipher));
+
+    candidates
+        .map(|(spelling, cipher)| (edit_distance(name, spelling), cipher))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, cipher)| cipher)
+}
+
+/// Levenshtein distance between two strings, counted in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
         }
+        previous = current;
+    }
+    previous[b.len()]
+}
 
-        // Validate minimum size: nonce (12) + tag (16) = 28 bytes
-        if ciphertext.len() < 28 {
-            return Err(CipherError::InvalidFormat);
+/// Creates a cipher instance from a cipher name string.
+///
+/// This function maps cipher name strings to their corresponding [`Cipher`] implementations.
+/// The cipher name is case-insensitive, and aliases are accepted as described in
+/// [`canonical_cipher_name()`].
+///
+/// # Arguments
+///
+/// * `cipher_name` - The name of the cipher (e.g., "AES-256-CBC"). Case-insensitive.
+///
+/// # Returns
+///
+/// Returns a boxed trait object implementing [`Cipher`], or an error if the cipher
+/// name is not supported.
+///
+/// # Errors
+///
+/// Returns an error string if the cipher name is not recognized. Supported ciphers:
+/// - AES-256-CBC (default)
+/// - AES-256-GCM
+/// - CHACHA20-POLY1305
+///
+/// # Example
+///
+/// ```no_run
+/// use envcrypt::cipher::get_cipher;
+///
+/// let cipher = get_cipher("AES-256-CBC")?;
+/// // Use cipher for encryption/decryption...
+/// # Ok::<(), String>(())
+/// ```
+pub fn get_cipher(cipher_name: &str) -> Result<Box<dyn Cipher>, String> {
+    match canonical_cipher_name(cipher_name)? {
+        #[cfg(feature = "cipher")]
+        "AES-256-GCM" => Ok(Box::new(Aes256Gcm)),
+        #[cfg(feature = "cipher")]
+        "CHACHA20-POLY1305" => Ok(Box::new(ChaCha20Poly1305)),
+        _ => Ok(Box::new(Aes256Cbc)),
+    }
+}
+
+/// Returns the IV or nonce length and the MAC or tag length of a cipher's output.
+///
+/// Known for every cipher envcrypt has ever written, whether or not it is enabled in
+/// this build, so files can be described without decrypting them.
+pub fn cipher_layout(cipher_name: &str) -> Option<(usize, usize)> {
+    match cipher_name.to_uppercase().as_str() {
+        "AES-256-CBC" => Some((16, 32)),
+        "AES-256-GCM" | "CHACHA20-POLY1305" => Some((12, 16)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_get_cipher_aes256cbc() {
+        let result = get_cipher("AES-256-CBC");
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_get_cipher_aes256cbc_lowercase() {
+        let result = get_cipher("aes-256-cbc");
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_get_cipher_unsupported() {
+        let result: Result<Box<dyn Cipher>, String> = get_cipher("UNSUPPORTED");
+        assert!(result.is_err());
+        if let Err(err_msg) = result {
+            assert!(err_msg.contains("Unsupported cipher"));
+        }
+    }
+
+    #[cfg(feature = "cipher")]
+    #[test]
+    fn test_get_cipher_aes256gcm() {
+        let result = get_cipher("AES-256-GCM");
+        assert!(result.is_ok());
+    }
+
+    #[cfg(feature = "cipher")]
+    #[test]
+    fn test_get_cipher_aes256gcm_lowercase() {
+        let result = get_cipher("aes-256-gcm");
+        assert!(result.is_ok());
+    }
+
+    #[cfg(feature = "cipher")]
+    #[test]
+    fn test_get_cipher_chacha20poly1305() {
+        let result = get_cipher("CHACHA20-POLY1305");
+        assert!(result.is_ok());
+    }
+
+    #[cfg(feature = "cipher")]
+    #[test]
+    fn test_get_cipher_chacha20poly1305_no_dash_alias() {
+        let result = get_cipher("CHACHA20POLY1305");
+        assert!(result.is_ok());
+    }
+
+    #[cfg(feature = "cipher")]
+    #[test]
+    fn test_canonical_cipher_name_aliases() {
+        assert_eq!(canonical_cipher_name("aes256gcm").unwrap(), "AES-256-GCM");
+        assert_eq!(canonical_cipher_name("gcm").unwrap(), "AES-256-GCM");
+        assert_eq!(canonical_cipher_name("ChaCha20").unwrap(), "CHACHA20-POLY1305");
+        assert_eq!(canonical_cipher_name("cbc").unwrap(), "AES-256-CBC");
+    }
+
+    #[cfg(feature = "cipher")]
+    #[test]
+    fn test_canonical_cipher_name_suggests_closest() {
+        let err = canonical_cipher_name("aes-265-gcm").unwrap_err();
+        assert!(err.starts_with("Unsupported cipher: aes-265-gcm (did you mean AES-256-GCM?)."));
+        let err = canonical_cipher_name("chacha20-poly1350").unwrap_err();
+        assert!(err.contains("did you mean CHACHA20-POLY1305?"));
+        let err = canonical_cipher_name("blowfish").unwrap_err();
+        assert!(!err.contains("did you mean"));
+        assert!(err.ends_with("Supported ciphers: AES-256-CBC, AES-256-GCM, CHACHA20-POLY1305"));
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("GCM", "GCM"), 0);
+        assert_eq!(edit_distance("GCN", "GCM"), 1);
+        assert_eq!(edit_distance("AES-265-GCM", "AES-256-GCM"), 2);
+        assert_eq!(edit_distance("", "CBC"), 3);
+    }
+
+    #[cfg(feature = "cipher")]
+    #[test]
+    fn test_get_cipher_chacha20poly1305_lowercase() {
+        let result = get_cipher("chacha20-poly1305");
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_roundtrip_at_block_boundaries() {
+        let (encryption_key, mac_key) = ([1u8; 32], [2u8; 32]);
+        for cipher_name in get_supported_ciphers() {
+            let cipher = get_cipher(cipher_name).unwrap();
+            let (iv_len, mac_len) = cipher_layout(cipher_name).unwrap();
+            for len in [0, 1, 15, 16, 17, 32, 1000] {
+                let plaintext = vec![b'x'; len];
+                let ciphertext = cipher.encrypt_with_aad(&plaintext, b"aad", &encryption_key, &mac_key).unwrap();
+                let padded_len = if cipher_name == "AES-256-CBC" { (len / 16 + 1) * 16 } else { len };
+                assert_eq!(ciphertext.len(), iv_len + padded_len + mac_len, "{} {}", cipher_name, len);
+                // The output is allocated once with its final size
+                assert_eq!(ciphertext.capacity(), ciphertext.len(), "{} {}", cipher_name, len);
+
+                let decrypted = cipher.decrypt_with_aad(&ciphertext, b"aad", &encryption_key, &mac_key).unwrap();
+                assert_eq!(decrypted.as_slice(), plaintext.as_slice(), "{} {}", cipher_name, len);
+                assert!(cipher.decrypt_with_aad(&ciphertext, b"other", &encryption_key, &mac_key).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_decrypt_rejects_short_ciphertext() {
+        let (encryption_key, mac_key) = ([1u8; 32], [2u8; 32]);
+        for cipher_name in get_supported_ciphers() {
+            let cipher = get_cipher(cipher_name).unwrap();
+            assert!(matches!(cipher.decrypt(&[0u8; 27], &encryption_key, &mac_key), Err(CipherError::InvalidFormat)));
+        }
+    }
+
+    /// A toy core that XORs with the key and uses a checksum as tag, to test the framing.
+    struct XorCore;
+
+    impl Aead for XorCore {
+        const NONCE_LEN: usize = 4;
+        const TAG_LEN: usize = 1;
+
+        fn seal_in_place(&self, keys: &AeadKeys, _nonce: &[u8], _aad: &[u8], data: &mut [u8], _plaintext_len: usize, tag: &mut [u8]) -> Result<(), CipherError> {
+            data.iter_mut().zip(keys.encryption_key.iter().cycle()).for_each(|(b, k)| *b ^= k);
+            tag[0] = data.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+            Ok(())
+        }
+
+        fn open_in_place(&self, keys: &AeadKeys, _nonce: &[u8], _aad: &[u8], data: &mut [u8], tag: &[u8]) -> Result<usize, CipherError> {
+            if data.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) != tag[0] {
+                return Err(CipherError::MacVerificationFailed);
+            }
+            data.iter_mut().zip(keys.encryption_key.iter().cycle()).for_each(|(b, k)| *b ^= k);
+            Ok(data.len())
+        }
+    }
+
+    #[test]
+    fn test_framing_layer() {
+        let encryption_key = [5u8; 32];
+        let ciphertext = XorCore.encrypt(b"secret", &encryption_key, &[]).unwrap();
+        assert_eq!(ciphertext.len(), 4 + 6 + 1);
+        assert_eq!(XorCore.decrypt(&ciphertext, &encryption_key, &[]).unwrap().as_slice(), b"secret");
+        // Fresh nonces each time
+        assert_ne!(ciphertext[..4], XorCore.encrypt(b"secret", &encryption_key, &[]).unwrap()[..4]);
+
+        let mut tampered = ciphertext.clone();
+        tampered[5] ^= 1;
+        assert!(matches!(XorCore.decrypt(&tampered, &encryption_key, &[]), Err(CipherError::MacVerificationFailed)));
+        assert!(matches!(XorCore.decrypt(&ciphertext[..4], &encryption_key, &[]), Err(CipherError::InvalidFormat)));
+        assert!(matches!(XorCore.encrypt(b"secret", &[0u8; 16], &[]), Err(CipherError::EncryptionFailed(_))));
+        // The MAC key is only checked for ciphers that use it
+        assert!(matches!(Aes256Cbc.encrypt(b"secret", &encryption_key, &[]), Err(CipherError::EncryptionFailed(_))));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_roundtrip_every_cipher(
+            plaintext in proptest::collection::vec(any::<u8>(), 0..1024),
+            aad in proptest::collection::vec(any::<u8>(), 0..64),
+            encryption_key in any::<[u8; 32]>(),
+            mac_key in any::<[u8; 32]>(),
+        ) {
+            for cipher_name in get_supported_ciphers() {
+                let cipher = get_cipher(cipher_name).unwrap();
+                let ciphertext = cipher.encrypt_with_aad(&plaintext, &aad, &encryption_key, &mac_key).unwrap();
+                let decrypted = cipher.decrypt_with_aad(&ciphertext, &aad, &encryption_key, &mac_key).unwrap();
+                prop_assert_eq!(decrypted.as_slice(), plaintext.as_slice());
+            }
         }
-        
-        // Extract components
-        let nonce = &ciphertext[0..12];
-        let encrypted_data = &ciphertext[12..];
-        
-        // Convert key to array
-        let key_array: [u8; 32] = encryption_key.try_into()
-            .map_err(|_| CipherError::DecryptionFailed)?;
-        
-        // Create cipher instance
-        let key = ChaChaKey::from_slice(&key_array);
-        let cipher = ChaCha20Poly1305Impl::new(key);
-        
-        // Decrypt with authentication
-        let nonce_array = ChaChaNonce::from_slice(nonce);
-        let plaintext = AeadTrait::decrypt(&cipher, nonce_array, encrypted_data)
-            .map_err(|_| CipherError::MacVerificationFailed)?; // Poly1305 auth failure
-        
-        Ok(plaintext)
     }
 }
@@ -0,0 +1,117 @@
+//! Recording and restoring the permissions and modification time of plaintext files.
+//!
+//! The attributes of the `.env` file are stored in the header [`Metadata`] when it is
+//! encrypted, so decrypting recreates it with the same mode bits instead of whatever the
+//! umask allows, and optionally with the same modification time.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::format::Metadata;
+use crate::cli::audit::note_written;
+
+/// Permissions and modification time of a plaintext file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct FileAttributes {
+    /// Unix permission bits; not recorded on other platforms.
+    pub mode: Option<u32>,
+    /// Modification time in seconds since the Unix epoch.
+    pub modified: Option<u64>,
+}
+
+impl FileAttributes {
+    /// Reads the attributes of `path`. Attributes that cannot be read are left unset.
+    pub fn read(path: &Path) -> Self {
+        let Ok(metadata) = fs::metadata(path) else {
+            return Self::default();
+        };
+        Self {
+            mode: mode_bits(&metadata),
+            modified: metadata.modified().ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs()),
+        }
+    }
+
+    /// Takes the attributes recorded in a file header.
+    pub fn from_metadata(metadata: &Metadata) -> Self {
+        Self { mode: metadata.mode, modified: metadata.modified }
+    }
+}
+
+#[cfg(unix)]
+fn mode_bits(metadata: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn mode_bits(_metadata: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+/// Writes `contents` to `path` with the permission bits `mode`, if given.
+///
+/// The permissions are applied before any data is written, so the plaintext is never
+/// readable with looser permissions, also when an existing file is overwritten.
+pub(crate) fn write_with_mode(path: &Path, contents: &[u8], mode: Option<u32>) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(mode);
+    }
+    let mut file = options.open(path)?;
+
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        // The mode passed to open() only applies to new files and is masked by the umask
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(mode))?;
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    file.write_all(contents)?;
+    note_written(&path.to_string_lossy());
+    Ok(())
+}
+
+/// Sets the modification time of `path` to `secs` seconds since the Unix epoch.
+pub(crate) fn set_modified(path: &Path, secs: u64) -> io::Result<()> {
+    let time = SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+    File::options().write(true).open(path)?.set_modified(time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_with_mode_tightens_existing_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+        fs::write(&path, "OLD=1").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        write_with_mode(&path, b"NEW=1", Some(0o600)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "NEW=1");
+        assert_eq!(FileAttributes::read(&path).mode, Some(0o600));
+    }
+
+    #[test]
+    fn test_set_modified() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+        write_with_mode(&path, b"A=1", None).unwrap();
+
+        set_modified(&path, 1_600_000_000).unwrap();
+        assert_eq!(FileAttributes::read(&path).modified, Some(1_600_000_000));
+    }
+}
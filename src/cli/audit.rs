@@ -0,0 +1,202 @@
+//! Opt-in audit log of the operations run (`audit` and `audit_log` in `envcrypt.toml`).
+//!
+//! While a command runs, the files it reads and writes and fingerprints of the keys it
+//! uses are noted by the functions doing the work; when it finishes, one JSON line
+//! describing it is appended to the log. Values and keys are never recorded.
+
+use std::cell::RefCell;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use pbkdf2::pbkdf2_hmac;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use crate::key::DEFAULT_PBKDF2_ITERATIONS;
+use crate::cli::dirs::data_dir;
+use crate::cli::inspect::format_timestamp;
+
+/// Salt for [`key_fingerprint()`]; fixed so the same key has the same fingerprint everywhere.
+const FINGERPRINT_SALT: &[u8] = b"envcrypt-audit-key-fingerprint";
+
+/// What the running command has touched so far.
+#[derive(Default)]
+struct Recording {
+    read: Vec<String>,
+    written: Vec<String>,
+    /// SHA-256 digests of the keys seen, to fingerprint each key only once, and the
+    /// fingerprints. The digests stay in memory.
+    keys: Vec<(Zeroizing<[u8; 32]>, String)>,
+}
+
+thread_local! {
+    static RECORDING: RefCell<Option<Recording>> = const { RefCell::new(None) };
+}
+
+fn record(update: impl FnOnce(&mut Recording)) {
+    RECORDING.with(|recording| {
+        if let Some(recording) = recording.borrow_mut().as_mut() {
+            update(recording);
+        }
+    });
+}
+
+/// Notes that the running command read `path`, if the audit log is enabled.
+pub(crate) fn note_read(path: &str) {
+    record(|recording| {
+        if !recording.read.iter().any(|p| p == path) {
+            recording.read.push(path.to_string());
+        }
+    });
+}
+
+/// Notes that the running command wrote `path`, if the audit log is enabled.
+pub(crate) fn note_written(path: &str) {
+    record(|recording| {
+        if !recording.written.iter().any(|p| p == path) {
+            recording.written.push(path.to_string());
+        }
+    });
+}
+
+/// Notes that the running command used `key`, if the audit log is enabled.
+pub(crate) fn note_key(key: &str) {
+    record(|recording| {
+        let digest = Zeroizing::new(<[u8; 32]>::from(Sha256::digest(key.as_bytes())));
+        if !recording.keys.iter().any(|(seen, _)| **seen == *digest) {
+            recording.keys.push((digest, key_fingerprint(key)));
+        }
+    });
+}
+
+/// Fingerprint identifying a key in the audit log: 16 hex digits of a PBKDF2 hash of it.
+///
+/// The same key always has the same fingerprint, so entries can be matched to keys, but
+/// guessing a key from its fingerprint is as slow as guessing it from a file encrypted
+/// with the default settings.
+pub(crate) fn key_fingerprint(key: &str) -> String {
+    let mut output = Zeroizing::new([0u8; 8]);
+    pbkdf2_hmac::<Sha256>(key.as_bytes(), FINGERPRINT_SALT, DEFAULT_PBKDF2_ITERATIONS, &mut *output);
+    output.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Default location of the audit log: `audit.log` in the [`data_dir()`]
+/// (`~/.local/share/envcrypt/audit.log` on Linux).
+pub(crate) fn default_log_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("audit.log"))
+}
+
+/// An open audit log recording the running command.
+pub(crate) struct AuditLog {
+    file: File,
+    path: PathBuf,
+    command: String,
+    dry_run: bool,
+}
+
+impl AuditLog {
+    /// Opens the log at `path` for appending and starts recording `command`.
+    ///
+    /// The log is opened before the command runs, so commands are never run unaudited
+    /// because the log cannot be written.
+    pub(crate) fn start(path: &Path, command: &str, dry_run: bool) -> Result<Self, String> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Cannot create the audit log directory {}: {}", parent.display(), e))?;
+        }
+        let mut options = OpenOptions::new();
+        options.create(true).append(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let file = options.open(path)
+            .map_err(|e| format!("Cannot open the audit log {}: {}", path.display(), e))?;
+
+        RECORDING.with(|recording| *recording.borrow_mut() = Some(Recording::default()));
+        Ok(Self { file, path: path.to_path_buf(), command: command.to_string(), dry_run })
+    }
+
+    /// Stops recording and appends the entry for the command, which failed with `error`
+    /// if given.
+    pub(crate) fn finish(mut self, error: Option<&str>) -> Result<(), String> {
+        let recording = RECORDING.with(|recording| recording.borrow_mut().take()).unwrap_or_default();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        let mut entry = serde_json::Map::new();
+        entry.insert("timestamp".into(), format_timestamp(now).into());
+        entry.insert("user".into(), current_user().into());
+        entry.insert("host".into(), current_host().into());
+        entry.insert("cwd".into(), std::env::current_dir().ok().map(|dir| dir.display().to_string()).into());
+        entry.insert("command".into(), self.command.into());
+        if self.dry_run {
+            entry.insert("dry_run".into(), true.into());
+        }
+        entry.insert("read".into(), recording.read.into());
+        entry.insert("written".into(), recording.written.into());
+        let fingerprints: Vec<String> = recording.keys.into_iter().map(|(_, fingerprint)| fingerprint).collect();
+        entry.insert("key_fingerprints".into(), fingerprints.into());
+        entry.insert("outcome".into(), if error.is_some() { "error" } else { "success" }.into());
+        if let Some(error) = error {
+            entry.insert("error".into(), error.into());
+        }
+
+        let line = format!("{}\n", serde_json::Value::Object(entry));
+        self.file.write_all(line.as_bytes())
+            .map_err(|e| format!("Error writing the audit log {}: {}", self.path.display(), e))
+    }
+}
+
+fn current_user() -> Option<String> {
+    std::env::var("USER").or_else(|_| std::env::var("USERNAME")).ok()
+}
+
+fn current_host() -> Option<String> {
+    std::env::var("HOSTNAME").or_else(|_| std::env::var("COMPUTERNAME")).ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok().map(|name| name.trim().to_string()))
+        .filter(|name| !name.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_fingerprint() {
+        let fingerprint = key_fingerprint("my-key");
+        assert_eq!(fingerprint.len(), 16);
+        assert!(fingerprint.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(fingerprint, key_fingerprint("my-key"));
+        assert_ne!(fingerprint, key_fingerprint("my-key2"));
+    }
+
+    #[test]
+    fn test_recording() {
+        // Nothing is recorded unless a log was started
+        note_read("ignored");
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("logs").join("audit.log");
+
+        let log = AuditLog::start(&path, "decrypt", false).unwrap();
+        note_read(".env.encrypted");
+        note_read(".env.encrypted");
+        note_written(".env");
+        note_key("my-key");
+        note_key("my-key");
+        log.finish(Some("Authentication failed")).unwrap();
+
+        let entry: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(entry["command"], "decrypt");
+        assert_eq!(entry["read"], serde_json::json!([".env.encrypted"]));
+        assert_eq!(entry["written"], serde_json::json!([".env"]));
+        assert_eq!(entry["key_fingerprints"], serde_json::json!([key_fingerprint("my-key")]));
+        assert_eq!(entry["outcome"], "error");
+        assert_eq!(entry["error"], "Authentication failed");
+        assert!(entry.get("dry_run").is_none());
+    }
+}
@@ -0,0 +1,120 @@
+//! Backups of files that are about to be overwritten (`--backup`).
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::output::{dry_run, OutputConfig};
+
+/// Where and how many backups to keep.
+///
+/// Backups are named `<file name><suffix>` and placed next to the original file, or in
+/// `dir` if set. With `keep` greater than one, older backups are rotated to
+/// `<file name><suffix>.1`, `<file name><suffix>.2`, and so on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupConfig {
+    /// Suffix appended to the file name (default ".bak").
+    pub suffix: String,
+    /// Directory to store backups in instead of next to the original file.
+    pub dir: Option<String>,
+    /// Number of backups to keep per file (at least 1).
+    pub keep: u32,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            suffix: ".bak".to_string(),
+            dir: None,
+            keep: 1,
+        }
+    }
+}
+
+impl BackupConfig {
+    /// Returns the path of the most recent backup of `path`.
+    pub fn backup_path(&self, path: &Path) -> PathBuf {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(&self.suffix);
+        match &self.dir {
+            Some(dir) => Path::new(dir).join(name),
+            None => path.with_file_name(name),
+        }
+    }
+}
+
+/// Copies `path` to its backup location, rotating older backups.
+///
+/// # Returns
+///
+/// Returns the backup path, or `None` if `path` does not exist and nothing was backed up.
+pub fn backup_file(path: &Path, config: &BackupConfig) -> Result<Option<PathBuf>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let backup = config.backup_path(path);
+    if let Some(dir) = &config.dir {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Error creating backup directory {}: {}", dir, e))?;
+    }
+
+    // Shift older backups: .bak.(n-1) -> .bak.n, ..., .bak -> .bak.1
+    let rotated = |n: u32| {
+        let mut name = backup.as_os_str().to_os_string();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    };
+    for n in (1..config.keep.max(1)).rev() {
+        let from = if n == 1 { backup.clone() } else { rotated(n - 1) };
+        if from.exists() {
+            fs::rename(&from, rotated(n))
+                .map_err(|e| format!("Error rotating backup {}: {}", from.display(), e))?;
+        }
+    }
+
+    fs::copy(path, &backup)
+        .map_err(|e| format!("Error backing up {} to {}: {}", path.display(), backup.display(), e))?;
+    Ok(Some(backup))
+}
+
+/// Reports what writing `path` would do, for `--dry-run`.
+///
+/// Mentions the backup that `--backup` would make and whether an existing file would be
+/// overwritten directly (`force`) or only after confirmation.
+pub fn report_planned_write(output_config: &OutputConfig, path: &Path, force: bool, backup: Option<&BackupConfig>) {
+    let name = path.display();
+    if !path.exists() {
+        dry_run(output_config, &format!("Would write {}", name));
+        return;
+    }
+
+    if let Some(backup) = backup {
+        dry_run(output_config, &format!("Would back up {} to {}", name, backup.backup_path(path).display()));
+    }
+    if force {
+        dry_run(output_config, &format!("Would overwrite {}", name));
+    } else {
+        dry_run(output_config, &format!("Would ask before overwriting {}", name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backup_path_next_to_file() {
+        let config = BackupConfig::default();
+        assert_eq!(config.backup_path(Path::new("config/.env")), PathBuf::from("config/.env.bak"));
+    }
+
+    #[test]
+    fn test_backup_path_in_directory() {
+        let config = BackupConfig {
+            suffix: "~".to_string(),
+            dir: Some("backups".to_string()),
+            keep: 1,
+        };
+        assert_eq!(config.backup_path(Path::new("config/.env")), PathBuf::from("backups/.env~"));
+    }
+}
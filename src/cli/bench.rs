@@ -0,0 +1,150 @@
+//! Measuring cipher and key derivation speed on this machine (`envcrypt bench`).
+//!
+//! The same cases are measured more thoroughly by the criterion benchmarks in `benches/`;
+//! this quick version needs no Rust toolchain, so it can be run wherever envcrypt is
+//! installed.
+
+use std::time::{Duration, Instant};
+
+use crate::cipher::{get_cipher, get_supported_ciphers};
+use crate::key::{
+    derive_keys_from_raw_key, derive_keys_with_iterations, DEFAULT_PBKDF2_ITERATIONS, MIN_PBKDF2_ITERATIONS,
+};
+
+/// Plaintext sizes the cipher benchmarks encrypt: a typical env file, a large one, and
+/// a 1 MiB file.
+pub const BENCH_FILE_SIZES: &[usize] = &[1024, 64 * 1024, 1024 * 1024];
+
+/// PBKDF2 iteration counts the key derivation benchmarks use: the minimum, the default,
+/// and the 600,000 OWASP recommends for PBKDF2-HMAC-SHA256.
+pub const BENCH_KDF_ITERATIONS: &[u32] = &[MIN_PBKDF2_ITERATIONS, DEFAULT_PBKDF2_ITERATIONS, 600_000];
+
+/// Timing of one benchmark run by [`run_benchmarks()`].
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    /// What was measured, e.g. `AES-256-GCM encrypt 64 KiB`.
+    pub name: String,
+    /// Average time of one operation.
+    pub per_operation: Duration,
+    /// Bytes processed per operation, for throughput; `None` for key derivation.
+    pub bytes: Option<usize>,
+}
+
+impl BenchResult {
+    /// Throughput in MiB per second, if the benchmark processes data.
+    pub fn mib_per_sec(&self) -> Option<f64> {
+        let bytes = self.bytes?;
+        Some(bytes as f64 / (1024.0 * 1024.0) / self.per_operation.as_secs_f64().max(f64::MIN_POSITIVE))
+    }
+}
+
+/// Measures every supported cipher at each of [`BENCH_FILE_SIZES`] and key derivation at
+/// each of [`BENCH_KDF_ITERATIONS`] and with a raw key.
+///
+/// Each case is repeated until it has run for at least `budget` (at least once), and
+/// `progress` is called with the name of each case before it starts.
+///
+/// # Errors
+///
+/// Returns an error string if a cipher fails to encrypt or decrypt.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use envcrypt::cli::run_benchmarks;
+///
+/// for result in run_benchmarks(Duration::from_millis(200), |_| {})? {
+///     println!("{}: {:?}", result.name, result.per_operation);
+/// }
+/// # Ok::<(), String>(())
+/// ```
+pub fn run_benchmarks(budget: Duration, mut progress: impl FnMut(&str)) -> Result<Vec<BenchResult>, String> {
+    let mut results = Vec::new();
+    let encryption_key = [7u8; 32];
+    let mac_key = [9u8; 32];
+
+    for cipher_name in get_supported_ciphers() {
+        let cipher = get_cipher(cipher_name)?;
+        for &size in BENCH_FILE_SIZES {
+            let plaintext = vec![b'A'; size];
+            let ciphertext = cipher.encrypt(&plaintext, &encryption_key, &mac_key).map_err(|e| e.to_string())?;
+
+            let name = format!("{} encrypt {}", cipher_name, format_size(size));
+            progress(&name);
+            let per_operation = measure(budget, || cipher.encrypt(&plaintext, &encryption_key, &mac_key).map(drop))
+                .map_err(|e| e.to_string())?;
+            results.push(BenchResult { name, per_operation, bytes: Some(size) });
+
+            let name = format!("{} decrypt {}", cipher_name, format_size(size));
+            progress(&name);
+            let per_operation = measure(budget, || cipher.decrypt(&ciphertext, &encryption_key, &mac_key).map(drop))
+                .map_err(|e| e.to_string())?;
+            results.push(BenchResult { name, per_operation, bytes: Some(size) });
+        }
+    }
+
+    let salt = [3u8; 16];
+    for &iterations in BENCH_KDF_ITERATIONS {
+        let name = format!("PBKDF2-HMAC-SHA256 {} iterations", iterations);
+        progress(&name);
+        let per_operation = measure(budget, || {
+            derive_keys_with_iterations("envcrypt-benchmark", &salt, iterations);
+            Ok::<_, String>(())
+        })?;
+        results.push(BenchResult { name, per_operation, bytes: None });
+    }
+    let name = "HKDF-SHA256 (raw key)".to_string();
+    progress(&name);
+    let per_operation = measure(budget, || {
+        derive_keys_from_raw_key(&[5u8; 32], &salt);
+        Ok::<_, String>(())
+    })?;
+    results.push(BenchResult { name, per_operation, bytes: None });
+
+    Ok(results)
+}
+
+/// Runs `operation` until `budget` has passed and returns its average duration.
+fn measure<E>(budget: Duration, mut operation: impl FnMut() -> Result<(), E>) -> Result<Duration, E> {
+    let start = Instant::now();
+    let mut runs = 0u32;
+    loop {
+        operation()?;
+        runs += 1;
+        let elapsed = start.elapsed();
+        if elapsed >= budget {
+            return Ok(elapsed / runs);
+        }
+    }
+}
+
+/// Formats a byte count as `512 B`, `64 KiB` or `1 MiB`.
+fn format_size(bytes: usize) -> String {
+    match bytes {
+        b if b >= 1024 * 1024 && b.is_multiple_of(1024 * 1024) => format!("{} MiB", b / (1024 * 1024)),
+        b if b >= 1024 && b.is_multiple_of(1024) => format!("{} KiB", b / 1024),
+        b => format!("{} B", b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(1024), "1 KiB");
+        assert_eq!(format_size(64 * 1024), "64 KiB");
+        assert_eq!(format_size(1024 * 1024), "1 MiB");
+        assert_eq!(format_size(1500), "1500 B");
+    }
+
+    #[test]
+    fn test_mib_per_sec() {
+        let result = BenchResult { name: "x".to_string(), per_operation: Duration::from_millis(500), bytes: Some(1024 * 1024) };
+        assert_eq!(result.mib_per_sec(), Some(2.0));
+        let kdf = BenchResult { name: "y".to_string(), per_operation: Duration::from_millis(500), bytes: None };
+        assert_eq!(kdf.mib_per_sec(), None);
+    }
+}
@@ -0,0 +1,307 @@
+//! Validating the variables of encrypted files against a schema (`envcrypt check`).
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::rc::Rc;
+
+use regex::Regex;
+use serde::Deserialize;
+use zeroize::Zeroizing;
+
+use crate::cli::decrypt::{decrypt_file, DecryptOptions};
+use crate::cli::dotenv::{is_valid_key, load_variables, parse_dotenv};
+use crate::cli::key_handling::{KeyFormat, KeyProvider};
+use crate::cli::memory::lock_secret;
+use crate::cli::output::{success, warning, OutputConfig};
+
+/// Options controlling how [`check_env()`] decrypts the file.
+#[derive(Default)]
+pub struct CheckOptions {
+    /// Skip interactive prompts (error if no key is provided).
+    pub no_interaction: bool,
+    /// Encoding of the provided or prompted key.
+    pub key_format: KeyFormat,
+    /// Lock the key, derived keys, and plaintext into RAM so they are not swapped to disk.
+    pub mlock: bool,
+    /// Context string the file was bound to when it was encrypted.
+    pub aad: Option<String>,
+    /// Where to get the key when none is passed as `key_arg` (default: interactive prompt).
+    pub key_provider: Option<Rc<dyn KeyProvider>>,
+    /// Take `$VAR` references in values literally instead of expanding them.
+    pub no_expand: bool,
+}
+
+/// What a variable described in a schema must look like.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Rule {
+    /// Whether the variable must be set to a non-empty value (default: yes).
+    #[serde(default = "required_by_default")]
+    required: bool,
+    #[serde(default, rename = "type")]
+    value_type: ValueType,
+    /// Regular expression the whole value must match.
+    pattern: Option<String>,
+}
+
+fn required_by_default() -> bool {
+    true
+}
+
+/// Type a variable's value must have.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ValueType {
+    #[default]
+    String,
+    Int,
+    Bool,
+    Url,
+}
+
+/// A schema rule with its pattern compiled.
+struct CompiledRule {
+    required: bool,
+    value_type: ValueType,
+    pattern: Option<Regex>,
+}
+
+/// Decrypts an encrypted environment file in memory and checks its variables against a
+/// schema and/or the variables of an example file.
+///
+/// The schema is a TOML file with a table per variable:
+///
+/// ```toml
+/// [DATABASE_URL]
+/// type = "url"
+///
+/// [PORT]
+/// type = "int"
+///
+/// [LOG_LEVEL]
+/// required = false
+/// pattern = "debug|info|warn|error"
+/// ```
+///
+/// Variables are required (set to a non-empty value) unless `required = false`. `type`
+/// is `string` (the default), `int`, `bool` (`true`, `false`, `1`, `0`, `yes`, `no`, `on`
+/// or `off`, in any case) or `url` (an absolute URL such as `https://example.com`), and
+/// the whole value must match `pattern`, if given. Optional variables that are not set
+/// are not checked. With `example_path`, every variable assigned in that plaintext file,
+/// typically `.env.example`, must be set; variables that it does not mention are
+/// reported as warnings.
+///
+/// Values are expanded as [`crate::cli::export_env()`] does unless `no_expand` is set, and
+/// never appear in the report.
+///
+/// # Returns
+///
+/// Returns the number of variables in the file.
+///
+/// # Errors
+///
+/// Returns an error string if the schema or example file cannot be read or is invalid,
+/// the file cannot be decrypted or is not a valid dotenv file, or, listing every
+/// problem, if any variable does not pass the checks.
+///
+/// # Example
+///
+/// ```no_run
+/// use envcrypt::cli::{check_env, CheckOptions, OutputConfig};
+///
+/// let output_config = OutputConfig::new(false, false, 0);
+/// check_env(Some("my-key"), ".env.production.encrypted", Some("env.schema.toml"), None, &output_config, &CheckOptions::default())?;
+/// # Ok::<(), String>(())
+/// ```
+pub fn check_env(
+    key_arg: Option<&str>,
+    input_path: &str,
+    schema_path: Option<&str>,
+    example_path: Option<&str>,
+    output_config: &OutputConfig,
+    options: &CheckOptions,
+) -> Result<usize, String> {
+    // Read the schema and example first, so mistakes in them show up before the key prompt
+    let schema = schema_path.map(load_schema).transpose()?;
+    let example_keys = example_path
+        .map(|path| {
+            let text = read_file(path)?;
+            let variables = parse_dotenv(&text).map_err(|e| format!("{} is not a valid dotenv file: {}", path, e))?;
+            Ok::<_, String>(variables.into_iter().map(|(key, _)| key).collect::<Vec<_>>())
+        })
+        .transpose()?;
+
+    let _span = tracing::info_span!("check", input = input_path).entered();
+    let decrypt_options = DecryptOptions {
+        no_interaction: options.no_interaction,
+        key_format: options.key_format,
+        mlock: options.mlock,
+        aad: options.aad.clone(),
+        key_provider: options.key_provider.clone(),
+        ..DecryptOptions::default()
+    };
+    let (_, plaintext) = decrypt_file(key_arg, input_path, output_config, &decrypt_options)?;
+    let _plaintext_lock = lock_secret(output_config, options.mlock, plaintext.as_bytes());
+    let variables = load_variables(&plaintext, !options.no_expand)
+        .map_err(|e| format!("{} is not a valid dotenv file: {}", input_path, e))?;
+
+    let mut problems = Vec::new();
+    let mut checked_against = Vec::new();
+    if let (Some(schema), Some(path)) = (&schema, schema_path) {
+        problems.extend(check_schema(&variables, schema));
+        checked_against.push(path);
+    }
+    if let (Some(keys), Some(path)) = (&example_keys, example_path) {
+        for key in keys {
+            if !variables.iter().any(|(name, _)| name == key) {
+                problems.push(format!("{} is in {} but not set", key, path));
+            }
+        }
+        for (name, _) in &variables {
+            if !keys.contains(name) {
+                warning(output_config, &format!("{} is set but not in {}", name, path));
+            }
+        }
+        checked_against.push(path);
+    }
+
+    if !problems.is_empty() {
+        let noun = if problems.len() == 1 { "problem" } else { "problems" };
+        let list: Vec<String> = problems.iter().map(|problem| format!("  - {}", problem)).collect();
+        return Err(format!("{} failed the check ({} {}):\n{}", input_path, problems.len(), noun, list.join("\n")));
+    }
+    success(output_config, &format!(
+        "{} passed the check against {} ({} variables)",
+        input_path,
+        checked_against.join(" and "),
+        variables.len(),
+    ));
+    Ok(variables.len())
+}
+
+fn read_file(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => format!("{} file not found", path),
+        _ => format!("Error reading {} file: {}", path, e),
+    })
+}
+
+/// Reads a schema file and compiles its patterns.
+fn load_schema(path: &str) -> Result<BTreeMap<String, CompiledRule>, String> {
+    let rules: BTreeMap<String, Rule> = toml::from_str(&read_file(path)?)
+        .map_err(|e| format!("Invalid schema in {}: {}", path, e))?;
+    rules.into_iter()
+        .map(|(name, rule)| {
+            if !is_valid_key(&name) {
+                return Err(format!("Invalid schema in {}: {} is not a valid variable name", path, name));
+            }
+            let pattern = rule.pattern
+                .map(|pattern| Regex::new(&format!("^(?:{})$", pattern)))
+                .transpose()
+                .map_err(|e| format!("Invalid pattern for {} in {}: {}", name, path, e))?;
+            Ok((name, CompiledRule { required: rule.required, value_type: rule.value_type, pattern }))
+        })
+        .collect()
+}
+
+/// Checks `variables` against `schema`, returning a description of every problem.
+fn check_schema(variables: &[(String, Zeroizing<String>)], schema: &BTreeMap<String, CompiledRule>) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (name, rule) in schema {
+        let value = variables.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+        let value = match value {
+            Some(value) if !value.is_empty() => value,
+            _ if rule.required => {
+                problems.push(format!("{} is required but not set", name));
+                continue;
+            }
+            _ => continue,
+        };
+
+        let type_ok = match rule.value_type {
+            ValueType::String => true,
+            ValueType::Int => value.parse::<i64>().is_ok(),
+            ValueType::Bool => ["true", "false", "1", "0", "yes", "no", "on", "off"]
+                .iter()
+                .any(|word| value.eq_ignore_ascii_case(word)),
+            ValueType::Url => is_url(value),
+        };
+        if !type_ok {
+            let expected = match rule.value_type {
+                ValueType::Int => "an integer",
+                ValueType::Bool => "a boolean",
+                _ => "a URL",
+            };
+            problems.push(format!("{} must be {}", name, expected));
+        } else if let Some(pattern) = &rule.pattern {
+            if !pattern.is_match(value) {
+                problems.push(format!("{} does not match the pattern {}", name, pattern.as_str()));
+            }
+        }
+    }
+    problems
+}
+
+/// Checks that `value` is an absolute URL: a scheme, `://`, and more, without whitespace.
+fn is_url(value: &str) -> bool {
+    let Some((scheme, rest)) = value.split_once("://") else {
+        return false;
+    };
+    let mut scheme_chars = scheme.chars();
+    matches!(scheme_chars.next(), Some(c) if c.is_ascii_alphabetic())
+        && scheme_chars.all(|c| c.is_ascii_alphanumeric() || "+.-".contains(c))
+        && !rest.is_empty()
+        && !value.chars().any(char::is_whitespace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(required: bool, value_type: ValueType, pattern: Option<&str>) -> CompiledRule {
+        CompiledRule {
+            required,
+            value_type,
+            pattern: pattern.map(|pattern| Regex::new(&format!("^(?:{})$", pattern)).unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_check_schema() {
+        let schema = BTreeMap::from([
+            ("DATABASE_URL".to_string(), rule(true, ValueType::Url, None)),
+            ("DEBUG".to_string(), rule(false, ValueType::Bool, None)),
+            ("LOG_LEVEL".to_string(), rule(false, ValueType::String, Some("debug|info"))),
+            ("MISSING".to_string(), rule(true, ValueType::String, None)),
+            ("OPTIONAL".to_string(), rule(false, ValueType::Int, None)),
+            ("PORT".to_string(), rule(true, ValueType::Int, None)),
+        ]);
+        let variables: Vec<(String, Zeroizing<String>)> = [
+            ("DATABASE_URL", "postgres://db:5432/app"),
+            ("DEBUG", "maybe"),
+            ("LOG_LEVEL", "verbose-info"),
+            ("PORT", "80a"),
+            ("OPTIONAL", ""),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), Zeroizing::new(v.to_string())))
+        .collect();
+
+        assert_eq!(check_schema(&variables, &schema), vec![
+            "DEBUG must be a boolean",
+            "LOG_LEVEL does not match the pattern ^(?:debug|info)$",
+            "MISSING is required but not set",
+            "PORT must be an integer",
+        ]);
+    }
+
+    #[test]
+    fn test_is_url() {
+        assert!(is_url("https://example.com/path?q=1"));
+        assert!(is_url("redis+tls://cache:6379"));
+        assert!(!is_url("example.com"));
+        assert!(!is_url("https://"));
+        assert!(!is_url("1http://example.com"));
+        assert!(!is_url("https://exa mple.com"));
+    }
+}
@@ -0,0 +1,113 @@
+//! Removing temporary files when the process is interrupted.
+//!
+//! Temporary files are removed when their [`TempPath`] is dropped, but Ctrl-C, `kill` or a
+//! closed terminal end the process without running destructors. [`install_handler()`]
+//! catches SIGINT, SIGTERM and SIGHUP (Ctrl-C, Ctrl-Break and closing the console on
+//! Windows) and removes every temporary file that still exists before exiting, so a
+//! temporary file holding a secret is not left behind.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, Once};
+
+/// Exit code after an interruption, as shells report for SIGINT.
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// Temporary files and directories that exist right now.
+static TEMP_PATHS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+static INSTALL_HANDLER: Once = Once::new();
+
+/// Removes the registered temporary files and exits when the process is interrupted.
+/// Installing it again does nothing.
+pub(crate) fn install_handler() {
+    INSTALL_HANDLER.call_once(|| {
+        let installed = ctrlc::set_handler(|| {
+            remove_all();
+            std::process::exit(INTERRUPTED_EXIT_CODE);
+        });
+        if let Err(e) = installed {
+            tracing::debug!("Cannot remove temporary files on interruption: {}", e);
+        }
+    });
+}
+
+/// A temporary file or directory, removed when dropped or when the process is
+/// interrupted (see [`install_handler()`]).
+///
+/// Register the path before creating it, so an interruption while it is written still
+/// removes it. Renaming the file away is fine: a path that no longer exists is skipped.
+#[derive(Debug)]
+pub(crate) struct TempPath(PathBuf);
+
+impl TempPath {
+    /// Registers `path`, which is about to be created.
+    pub(crate) fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        lock_paths().push(path.clone());
+        TempPath(path)
+    }
+
+    /// The registered path.
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        remove(&self.0);
+        let mut paths = lock_paths();
+        if let Some(index) = paths.iter().rposition(|path| *path == self.0) {
+            paths.remove(index);
+        }
+    }
+}
+
+/// Removes every registered temporary file and directory that still exists.
+fn remove_all() {
+    for path in lock_paths().drain(..) {
+        remove(&path);
+    }
+}
+
+fn remove(path: &Path) {
+    let _ = if path.is_dir() { fs::remove_dir_all(path) } else { fs::remove_file(path) };
+}
+
+/// Locks the registry, even if a thread panicked while holding it: the list stays valid.
+fn lock_paths() -> std::sync::MutexGuard<'static, Vec<PathBuf>> {
+    TEMP_PATHS.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registered(path: &Path) -> bool {
+        lock_paths().iter().any(|registered| registered == path)
+    }
+
+    #[test]
+    fn test_temp_path_is_removed_when_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let temp = TempPath::new(dir.path().join(".env.tmp"));
+        fs::write(temp.path(), "SECRET=1").unwrap();
+        let path = temp.path().to_path_buf();
+        assert!(registered(&path));
+
+        drop(temp);
+        assert!(!path.exists());
+        assert!(!registered(&path));
+    }
+
+    #[test]
+    fn test_renamed_temp_path_is_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let temp = TempPath::new(dir.path().join("a.tmp"));
+        fs::write(temp.path(), "new").unwrap();
+        fs::rename(temp.path(), dir.path().join("a")).unwrap();
+        drop(temp);
+        assert_eq!(fs::read_to_string(dir.path().join("a")).unwrap(), "new");
+    }
+}
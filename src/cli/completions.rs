@@ -0,0 +1,213 @@
+//! Shell completion scripts (`envcrypt completions`) that complete environment names from
+//! the env files in the current directory and from the keyring.
+//!
+//! The scripts call back into envcrypt with the command line to complete, so the
+//! candidates reflect the files and keyring at the time Tab is pressed.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+
+use clap::ValueEnum;
+use clap_complete::engine::CompletionCandidate;
+use clap_complete::env::{Bash, Elvish, EnvCompleter, Fish, Powershell, Zsh};
+use clap_complete::CompleteEnv;
+
+use crate::cli::config::{config_override, Config};
+use crate::cli::keyring::Keyring;
+use crate::cli::paths::ENCRYPTED_SUFFIX;
+use crate::cli::prompt::TerminalPrompt;
+use crate::cli::sign::SIGNATURE_SUFFIX;
+
+/// Environment variable the completion scripts set, to the name of the shell, when they
+/// run envcrypt to complete a command line.
+pub const COMPLETE_ENV_VAR: &str = "_ENVCRYPT_COMPLETE";
+
+/// Shell to generate a completion script for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Elvish,
+    Fish,
+    Powershell,
+    Zsh,
+}
+
+impl Shell {
+    fn completer(self) -> &'static dyn EnvCompleter {
+        match self {
+            Shell::Bash => &Bash,
+            Shell::Elvish => &Elvish,
+            Shell::Fish => &Fish,
+            Shell::Powershell => &Powershell,
+            Shell::Zsh => &Zsh,
+        }
+    }
+}
+
+/// Writes the completion script for `shell` to `out`. The script runs `envcrypt` from the
+/// `PATH` with [`COMPLETE_ENV_VAR`] set whenever it completes a command line.
+///
+/// # Errors
+///
+/// Returns an error string if writing to `out` fails.
+///
+/// # Example
+///
+/// ```
+/// use envcrypt::cli::{write_completion_script, Shell};
+///
+/// let mut script = Vec::new();
+/// write_completion_script(Shell::Bash, &mut script)?;
+/// assert!(String::from_utf8_lossy(&script).contains("_ENVCRYPT_COMPLETE"));
+/// # Ok::<(), String>(())
+/// ```
+pub fn write_completion_script(shell: Shell, out: &mut dyn Write) -> Result<(), String> {
+    shell
+        .completer()
+        .write_registration(COMPLETE_ENV_VAR, "envcrypt", "envcrypt", "envcrypt", out)
+        .map_err(|e| format!("Error writing the completion script: {}", e))
+}
+
+/// Prints the completions of the command line `args` if envcrypt was run by a completion
+/// script, returning `false` for a regular run.
+pub(crate) fn complete_command_line(args: &[String]) -> bool {
+    let current_dir = std::env::current_dir().ok();
+    CompleteEnv::with_factory(super::help::command)
+        .var(COMPLETE_ENV_VAR)
+        .bin("envcrypt")
+        .completer("envcrypt")
+        .try_complete(args, current_dir.as_deref())
+        .unwrap_or_else(|e| e.exit())
+}
+
+/// Completes `--env` of commands that read a plaintext file: the environments of the
+/// `.env.{env}` files in the current directory and of the keyring.
+pub(crate) fn plaintext_environments() -> Vec<CompletionCandidate> {
+    let mut environments = file_environments(Path::new("."), false);
+    add_keyring_environments(&mut environments);
+    candidates(environments)
+}
+
+/// Completes `--env` of commands that read an encrypted file: the environments of the
+/// `.env.{env}.encrypted` files in the current directory and of the keyring.
+pub(crate) fn encrypted_environments() -> Vec<CompletionCandidate> {
+    let mut environments = file_environments(Path::new("."), true);
+    add_keyring_environments(&mut environments);
+    candidates(environments)
+}
+
+/// Completes the environment of `key add`: the environments of the env files in the
+/// current directory, encrypted or not.
+pub(crate) fn file_environment_names() -> Vec<CompletionCandidate> {
+    let mut environments = file_environments(Path::new("."), true);
+    for (environment, file) in file_environments(Path::new("."), false) {
+        environments.entry(environment).or_insert(file);
+    }
+    candidates(environments)
+}
+
+/// Completes the environment of `key remove`: the environments in the keyring.
+pub(crate) fn keyring_environment_names() -> Vec<CompletionCandidate> {
+    let mut environments = BTreeMap::new();
+    add_keyring_environments(&mut environments);
+    candidates(environments)
+}
+
+fn candidates(environments: BTreeMap<String, String>) -> Vec<CompletionCandidate> {
+    environments
+        .into_iter()
+        .map(|(environment, source)| CompletionCandidate::new(environment).help(Some(source.into())))
+        .collect()
+}
+
+/// Returns the environments of the `.env.{env}` files (or `.env.{env}.encrypted` files if
+/// `encrypted`) in `dir`, with the name of their file. Examples, signatures, backups and
+/// the default `.env.encrypted` are skipped.
+fn file_environments(dir: &Path, encrypted: bool) -> BTreeMap<String, String> {
+    let mut environments = BTreeMap::new();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return environments;
+    };
+    for entry in entries.flatten() {
+        let Ok(file) = entry.file_name().into_string() else {
+            continue;
+        };
+        let Some(name) = file.strip_prefix(".env.") else {
+            continue;
+        };
+        let environment = match (encrypted, name.strip_suffix(ENCRYPTED_SUFFIX)) {
+            (true, Some(environment)) => environment,
+            (false, None) if !is_derived_file(name) && name != &ENCRYPTED_SUFFIX[1..] => name,
+            _ => continue,
+        };
+        if is_environment_name(environment) && environment != "example" {
+            environments.insert(environment.to_string(), file);
+        }
+    }
+    environments
+}
+
+/// Whether `name`, a file name after `.env.`, is a signature or backup of another file.
+fn is_derived_file(name: &str) -> bool {
+    let last = name.rsplit('.').next().unwrap_or(name);
+    name.ends_with(SIGNATURE_SUFFIX)
+        || name.ends_with('~')
+        || last == "bak"
+        || (name.contains(".bak.") && last.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Whether `environment` is a name the keyring accepts.
+fn is_environment_name(environment: &str) -> bool {
+    !environment.is_empty()
+        && environment.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Adds the environments of the keyring, if it can be unlocked without a prompt (with
+/// `ENVCRYPT_KEYRING_PASSPHRASE` or the OS keychain). Completion never asks for the
+/// passphrase.
+fn add_keyring_environments(environments: &mut BTreeMap<String, String>) {
+    let Ok(config) = Config::load_merged(config_override(None).as_deref()) else {
+        return;
+    };
+    let options = config.keyring_options(true);
+    let Ok(keyring) = Keyring::open(&options, &TerminalPrompt) else {
+        return;
+    };
+    for (environment, _) in keyring.entries() {
+        environments.entry(environment.to_string()).or_insert_with(|| "keyring".to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_environments() {
+        let dir = tempfile::tempdir().unwrap();
+        for file in [
+            ".env",
+            ".env.local",
+            ".env.staging",
+            ".env.staging.encrypted",
+            ".env.production.encrypted",
+            ".env.production.encrypted.sig",
+            ".env.example",
+            ".env.local.bak",
+            ".env.local.bak.2",
+            ".env.local~",
+            ".env.encrypted",
+            ".env.bad name",
+        ] {
+            std::fs::write(dir.path().join(file), "").unwrap();
+        }
+
+        let plaintext = file_environments(dir.path(), false);
+        assert_eq!(plaintext.keys().collect::<Vec<_>>(), ["local", "staging"]);
+        assert_eq!(plaintext["local"], ".env.local");
+        let encrypted = file_environments(dir.path(), true);
+        assert_eq!(encrypted.keys().collect::<Vec<_>>(), ["production", "staging"]);
+        assert_eq!(encrypted["staging"], ".env.staging.encrypted");
+    }
+}
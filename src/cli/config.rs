@@ -0,0 +1,265 @@
+//! Configuration loaded from the project's `envcrypt.toml` and the user configuration
+//! beneath it.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::key::{decode_raw_key, DEFAULT_PBKDF2_ITERATIONS};
+use crate::recovery::RECOVERY_KEY_LEN;
+use crate::signature::SIGNING_KEY_LEN;
+use super::audit::default_log_path;
+use super::backup::BackupConfig;
+use super::dirs::config_dir;
+use super::key_handling::strip_base64_prefix;
+use super::keyring::{KeyringOptions, KEYRING_FILE};
+use super::tpm::TPM_KEY_FILE;
+use super::trash::TrashConfig;
+use super::walk::WalkOptions;
+
+/// Name of the project configuration file, looked up in the current directory.
+pub const CONFIG_FILE: &str = "envcrypt.toml";
+
+/// Environment variable naming a configuration file to use instead of `envcrypt.toml`
+/// (overridden by `--config`).
+pub const CONFIG_ENV_VAR: &str = "ENVCRYPT_CONFIG";
+
+/// Settings read from the project configuration file.
+///
+/// Every setting is optional; command-line flags take precedence over the file, and the
+/// file over the user configuration (see [`Config::load_merged()`]).
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// PBKDF2 iteration count used for new encryptions (see `envcrypt calibrate`).
+    pub kdf_iterations: Option<u32>,
+    /// Base64-encoded recovery public key; every encryption also wraps the file keys for
+    /// it (see `envcrypt key recovery-keygen`).
+    pub recovery_public_key: Option<String>,
+    /// OpenPGP key IDs every encryption also encrypts the file keys to with `gpg`
+    /// (overridden by `--recipient-gpg`).
+    pub gpg_recipients: Vec<String>,
+    /// SSH public keys (key lines, `.pub` files or `github:<username>`) every encryption
+    /// also encrypts the file keys to (overridden by `--recipient-ssh`).
+    pub ssh_recipients: Vec<String>,
+    /// Public keys `envcrypt verify` trusts when `--signer-pub` is not given (see
+    /// `envcrypt key sign-keygen`).
+    pub signer_public_keys: Vec<String>,
+    /// Suffix for backups written by `--backup` (default ".bak").
+    pub backup_suffix: Option<String>,
+    /// Directory for backups written by `--backup` (default: next to the original file).
+    pub backup_dir: Option<String>,
+    /// Number of backups to keep per file (default 1).
+    pub backup_keep: Option<u32>,
+    /// Directory `--prune=backup` moves originals to (default `.envcrypt-trash`).
+    pub prune_dir: Option<String>,
+    /// Days originals stay in the trash before `envcrypt prune --gc` deletes them
+    /// (default 7).
+    pub prune_keep_days: Option<u32>,
+    /// Context string files are bound to when `--aad` is not given.
+    pub aad: Option<String>,
+    /// Key provider used when `--key` is not given (default "prompt").
+    pub key_provider: Option<String>,
+    /// Key file read by the `file` key provider.
+    pub key_file: Option<String>,
+    /// 1Password secret reference read by the `1password` key provider, e.g.
+    /// `op://Vault/envcrypt/password`.
+    pub onepassword_ref: Option<String>,
+    /// Bitwarden item whose password the `bitwarden` key provider reads.
+    pub bitwarden_item: Option<String>,
+    /// Base64 ID of the hardware token credential used by the `fido2` key provider,
+    /// written by `envcrypt key fido2-enroll`.
+    pub fido2_credential: Option<String>,
+    /// Device path of the hardware token, e.g. `/dev/hidraw0` (default: the first one
+    /// found by `fido2-token -L`).
+    pub fido2_device: Option<String>,
+    /// Key sealed to this machine's TPM, read by the `tpm` key provider and written by
+    /// `envcrypt key tpm-seal` (default `.envcrypt.tpm`).
+    pub tpm_key_file: Option<String>,
+    /// Gitignore-style patterns of files and directories `migrate` and `tui` skip when
+    /// searching directories (extended by `--exclude`).
+    pub exclude: Vec<String>,
+    /// Gitignore-style patterns of the only files `migrate` and `tui` pick up when
+    /// searching directories (extended by `--include`).
+    pub include: Vec<String>,
+    /// Record every command in an append-only audit log at the default location
+    /// (`~/.local/share/envcrypt/audit.log`).
+    pub audit: Option<bool>,
+    /// Audit log file; setting it enables the log unless `audit = false`.
+    pub audit_log: Option<String>,
+    /// Keyring mapping environments to keys (default `.envcrypt.keys`).
+    pub keyring_file: Option<String>,
+    /// Keep the master passphrase of the keyring in the OS keychain instead of asking
+    /// for it.
+    pub keyring_keychain: Option<bool>,
+    /// Suggest `envcrypt recrypt` when `decrypt` reads a file encrypted with a cipher
+    /// weaker than the recommended one (default true).
+    pub warn_on_legacy_cipher: Option<bool>,
+    /// Refuse new encryptions with ciphers that are not AEADs or with fewer PBKDF2
+    /// iterations than recommended, as `--strict-crypto` does (default false).
+    pub strict_crypto: Option<bool>,
+}
+
+impl Config {
+    /// Loads the configuration from `path`, returning defaults if the file does not exist.
+    pub fn load(path: &str) -> Result<Self, String> {
+        if !Path::new(path).exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Error reading {}: {}", path, e))?;
+        toml::from_str(&content)
+            .map_err(|e| format!("Invalid configuration in {}: {}", path, e))
+    }
+
+    /// Loads the project configuration from `path` (default: `envcrypt.toml` if it
+    /// exists) over the user configuration, `config.toml` in the [`config_dir()`]
+    /// (`~/.config/envcrypt/config.toml` on Linux).
+    ///
+    /// A setting in the project file replaces the same setting in the user file, so
+    /// personal defaults such as `key_provider` need not be committed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error string if `path` is given but does not exist, or if either file
+    /// cannot be read or has an invalid setting.
+    pub fn load_merged(path: Option<&str>) -> Result<Self, String> {
+        let mut table = match user_config_path() {
+            Some(user_path) if user_path.exists() => read_table(&user_path.display().to_string())?,
+            _ => toml::Table::new(),
+        };
+        let project = match path {
+            Some(path) if !Path::new(path).exists() => {
+                return Err(format!("Configuration file {} not found", path));
+            }
+            Some(path) => Some(read_table(path)?),
+            None if Path::new(CONFIG_FILE).exists() => Some(read_table(CONFIG_FILE)?),
+            None => None,
+        };
+        table.extend(project.unwrap_or_default());
+        toml::Value::Table(table)
+            .try_into()
+            .map_err(|e| format!("Invalid configuration: {}", e))
+    }
+
+    /// Returns the backup settings used by `--backup`.
+    pub fn backup_config(&self) -> BackupConfig {
+        let defaults = BackupConfig::default();
+        BackupConfig {
+            suffix: self.backup_suffix.clone().unwrap_or(defaults.suffix),
+            dir: self.backup_dir.clone(),
+            keep: self.backup_keep.unwrap_or(defaults.keep),
+        }
+    }
+
+    /// Returns the trash settings used by `--prune=backup` and `envcrypt prune`.
+    pub fn trash_config(&self) -> TrashConfig {
+        let defaults = TrashConfig::default();
+        TrashConfig {
+            dir: self.prune_dir.as_deref().map(PathBuf::from).unwrap_or(defaults.dir),
+            keep_days: self.prune_keep_days.unwrap_or(defaults.keep_days),
+        }
+    }
+
+    /// Returns where the keyring is and how it is unlocked.
+    pub fn keyring_options(&self, no_interaction: bool) -> KeyringOptions {
+        KeyringOptions {
+            path: PathBuf::from(self.keyring_file.as_deref().unwrap_or(KEYRING_FILE)),
+            use_keychain: self.keyring_keychain.unwrap_or(false),
+            no_interaction,
+            kdf_iterations: self.kdf_iterations.unwrap_or(DEFAULT_PBKDF2_ITERATIONS),
+        }
+    }
+
+    /// Returns which files a command searching a directory picks up, adding the patterns
+    /// given on the command line to the configured ones.
+    pub fn walk_options(&self, recursive: bool, exclude: Vec<String>, include: Vec<String>) -> WalkOptions {
+        WalkOptions {
+            recursive,
+            exclude: self.exclude.iter().cloned().chain(exclude).collect(),
+            include: self.include.iter().cloned().chain(include).collect(),
+        }
+    }
+
+    /// Returns where the key sealed to the TPM is kept.
+    pub fn tpm_key_path(&self) -> PathBuf {
+        PathBuf::from(self.tpm_key_file.as_deref().unwrap_or(TPM_KEY_FILE))
+    }
+
+    /// Decodes the configured recovery public key, if any.
+    pub fn recovery_public_key(&self) -> Result<Option<[u8; RECOVERY_KEY_LEN]>, String> {
+        self.recovery_public_key.as_deref()
+            .map(|key| {
+                decode_raw_key(strip_base64_prefix(key.trim()))
+                    .ok_or_else(|| format!("Invalid recovery_public_key in {}: expected a base64-encoded 32-byte key", CONFIG_FILE))
+            })
+            .transpose()
+    }
+
+    /// Decodes the configured signer public keys.
+    pub fn signer_public_keys(&self) -> Result<Vec<[u8; SIGNING_KEY_LEN]>, String> {
+        self.signer_public_keys.iter()
+            .map(|key| {
+                decode_raw_key(strip_base64_prefix(key.trim()))
+                    .ok_or_else(|| format!("Invalid key in signer_public_keys in {}: expected a base64-encoded 32-byte key", CONFIG_FILE))
+            })
+            .collect()
+    }
+
+    /// Returns where commands are logged, or `None` if the audit log is not enabled.
+    pub fn audit_log_path(&self) -> Result<Option<PathBuf>, String> {
+        match (self.audit, &self.audit_log) {
+            (Some(false), _) | (None, None) => Ok(None),
+            (_, Some(path)) => Ok(Some(PathBuf::from(path))),
+            (Some(true), None) => default_log_path().map(Some).ok_or_else(|| {
+                format!("Cannot find the default audit log location; set audit_log in {}", CONFIG_FILE)
+            }),
+        }
+    }
+}
+
+/// Returns the configuration file given with `--config` (`flag`) or `ENVCRYPT_CONFIG`, or
+/// `None` to use `envcrypt.toml`.
+pub(crate) fn config_override(flag: Option<&str>) -> Option<String> {
+    flag.map(str::to_string)
+        .or_else(|| std::env::var(CONFIG_ENV_VAR).ok().filter(|path| !path.is_empty()))
+}
+
+/// Location of the user configuration: `config.toml` in the [`config_dir()`].
+pub(crate) fn user_config_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("config.toml"))
+}
+
+/// Reads the configuration file at `path` as a table, checking its settings so errors
+/// name the file they are in.
+fn read_table(path: &str) -> Result<toml::Table, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Error reading {}: {}", path, e))?;
+    toml::from_str::<Config>(&content)
+        .map_err(|e| format!("Invalid configuration in {}: {}", path, e))?;
+    content.parse()
+        .map_err(|e| format!("Invalid configuration in {}: {}", path, e))
+}
+
+/// Sets a single top-level value in the configuration file, creating it if needed.
+///
+/// Other settings already present in the file are preserved.
+pub fn write_config_value(path: &str, key: &str, value: toml::Value) -> Result<(), String> {
+    let mut table = if Path::new(path).exists() {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Error reading {}: {}", path, e))?;
+        content.parse::<toml::Table>()
+            .map_err(|e| format!("Invalid configuration in {}: {}", path, e))?
+    } else {
+        toml::Table::new()
+    };
+
+    table.insert(key.to_string(), value);
+
+    let content = toml::to_string(&table)
+        .map_err(|e| format!("Error serializing configuration: {}", e))?;
+    fs::write(path, content)
+        .map_err(|e| format!("Error writing {}: {}", path, e))
+}
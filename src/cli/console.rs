@@ -0,0 +1,137 @@
+//! Reading secrets from the terminal without echoing them, on every kind of terminal.
+//!
+//! rpassword turns echo off through the terminal driver on Unix and through the console
+//! API on Windows. That works in Windows consoles, including ConPTY hosts such as Windows
+//! Terminal, PowerShell and VS Code, but not in mintty (Git Bash's default window): Cygwin
+//! and MSYS2 terminals are pipes to Windows programs, with no console handle whose echo
+//! could be switched off, so reading the console hangs. Piped input on Windows is also read
+//! from the console rather than the pipe, and hangs the same way.
+
+use std::io::{self, BufRead, IsTerminal};
+use std::process::{Command, Stdio};
+
+use zeroize::Zeroizing;
+
+/// The kind of terminal stdin is attached to, which decides how a secret is read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Console {
+    /// A Unix TTY or a Windows console, whose echo rpassword can switch off.
+    Native,
+    /// A Cygwin or MSYS2 pseudo terminal such as mintty, whose echo is switched off with
+    /// the `stty` that comes with it.
+    Mintty,
+    /// Piped or redirected input on Windows, read a line at a time like any other input.
+    Pipe,
+}
+
+impl Console {
+    /// Detects the kind of terminal stdin is attached to.
+    pub(crate) fn detect() -> Self {
+        Self::classify(
+            cfg!(windows),
+            io::stdin().is_terminal(),
+            std::env::var("TERM_PROGRAM").ok().as_deref(),
+        )
+    }
+
+    /// Classifies stdin on Windows (`windows`) or another platform. The standard library
+    /// counts Cygwin and MSYS2 pseudo terminals as terminals, so mintty is recognized by the
+    /// `TERM_PROGRAM` it sets.
+    fn classify(windows: bool, stdin_is_terminal: bool, term_program: Option<&str>) -> Self {
+        match (windows, stdin_is_terminal) {
+            (false, _) => Console::Native,
+            (true, false) => Console::Pipe,
+            (true, true) if term_program == Some("mintty") => Console::Mintty,
+            (true, true) => Console::Native,
+        }
+    }
+}
+
+/// Reads a secret from stdin in the way that works for `console`, without echoing it
+/// where stdin is a terminal. The secret is zeroized when dropped.
+///
+/// # Errors
+///
+/// Returns an error if input cannot be read, or if echo cannot be switched off in mintty.
+pub(crate) fn read_secret(console: Console) -> io::Result<Zeroizing<String>> {
+    match console {
+        Console::Native => rpassword::read_password().map(Zeroizing::new),
+        Console::Mintty => {
+            let echo = EchoOff::new()?;
+            let secret = read_secret_line(io::stdin().lock());
+            drop(echo);
+            // The Enter key was not echoed either
+            println!();
+            secret
+        }
+        Console::Pipe => read_secret_line(io::stdin().lock()),
+    }
+}
+
+/// Reads a line from `input` without its line ending. An input that is already closed
+/// reads as an empty line.
+fn read_secret_line(mut input: impl BufRead) -> io::Result<Zeroizing<String>> {
+    let mut line = Zeroizing::new(String::new());
+    input.read_line(&mut line)?;
+    let len = line.trim_end_matches(['\r', '\n']).len();
+    line.truncate(len);
+    Ok(line)
+}
+
+/// Switches echo off in a Cygwin or MSYS2 terminal until dropped.
+struct EchoOff;
+
+impl EchoOff {
+    fn new() -> io::Result<Self> {
+        stty("-echo").map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("cannot hide input in this terminal ({}); run envcrypt with winpty, or pass the key with --key or ENVCRYPT_KEY", e),
+            )
+        })?;
+        Ok(EchoOff)
+    }
+}
+
+impl Drop for EchoOff {
+    fn drop(&mut self) {
+        let _ = stty("echo");
+    }
+}
+
+/// Runs `stty` on the terminal stdin is attached to.
+fn stty(setting: &str) -> io::Result<()> {
+    let status = Command::new("stty")
+        .arg(setting)
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("stty {} failed with {}", setting, status)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(Console::classify(false, true, Some("mintty")), Console::Native);
+        assert_eq!(Console::classify(false, false, None), Console::Native);
+        assert_eq!(Console::classify(true, true, None), Console::Native);
+        assert_eq!(Console::classify(true, true, Some("vscode")), Console::Native);
+        assert_eq!(Console::classify(true, true, Some("mintty")), Console::Mintty);
+        assert_eq!(Console::classify(true, false, Some("mintty")), Console::Pipe);
+    }
+
+    #[test]
+    fn test_read_secret_line() {
+        assert_eq!(read_secret_line("hunter2\r\nrest\n".as_bytes()).unwrap().as_str(), "hunter2");
+        assert_eq!(read_secret_line(" spaced key \n".as_bytes()).unwrap().as_str(), " spaced key ");
+        assert_eq!(read_secret_line("".as_bytes()).unwrap().as_str(), "");
+    }
+}
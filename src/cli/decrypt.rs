@@ -1,15 +1,101 @@
 //! Decryption command implementation.
 
 use std::fs;
+use std::io::{Read, Write};
 use std::path::Path;
-use base64::Engine;
-use zeroize::Zeroize;
+use std::rc::Rc;
+use zeroize::{Zeroize, Zeroizing};
 
-use crate::cipher::CipherError;
-use crate::key::derive_keys;
-use crate::cli::cipher::get_cipher;
-use crate::cli::key_handling::get_encryption_key;
-use crate::cli::output::{OutputConfig, info, verbose, debug};
+use crate::format;
+use crate::key::{decode_raw_key, KeyMaterial};
+use crate::envelope::{derive_file_keys, derive_file_keys_cached};
+use crate::recovery::unwrap_keys;
+use crate::armor::{decode_file, looks_encrypted};
+use crate::cipher::{get_supported_ciphers, RECOMMENDED_CIPHER};
+use crate::cli::dotenv::{looks_like_dotenv, parse_dotenv};
+use crate::cli::backup::{backup_file, report_planned_write, BackupConfig};
+use crate::cli::cipher::{canonical_cipher_name, get_cipher};
+use crate::cli::key_handling::{decode_key, get_key, strip_base64_prefix, KeyFormat, KeyProvider, KeyRequest};
+use crate::cli::encoding::{check_utf8, decode_text, encode_text, Encoding};
+use crate::cli::memory::lock_secret;
+use crate::cli::newline::{convert_newlines, Newline};
+use crate::cli::prompt::confirm_overwrite;
+use crate::cli::lock::{lock_file, LockWait};
+use crate::cli::output::{OutputConfig, info, success, warning, Spinner};
+use crate::cli::config::CONFIG_FILE;
+use crate::cli::logging::Timer;
+use crate::cli::attributes::{set_modified, write_with_mode, FileAttributes};
+use crate::cli::audit::note_read;
+use crate::cli::gpg::{unwrap_keys_gpg, FileKeys};
+use crate::cli::ssh::unwrap_keys_ssh;
+
+/// Error reported for every failure to authenticate or decrypt a file.
+///
+/// A wrong key, a tampered file, a bad MAC and bad padding all look the same to the
+/// caller, so the command cannot be used as an oracle. The cause is only shown at `-vvv`.
+pub(crate) const AUTHENTICATION_FAILED: &str = "Authentication failed - the key is incorrect or the encrypted file has been tampered with";
+
+/// Logs the specific cause of a decryption failure and returns [`AUTHENTICATION_FAILED`].
+fn authentication_failed(cause: &dyn std::fmt::Display) -> String {
+    tracing::trace!("Decryption failed: {}", cause);
+    AUTHENTICATION_FAILED.to_string()
+}
+
+/// Wraps derived or unwrapped `(encryption_key, mac_key)` so they are zeroized when dropped.
+fn zeroizing_keys((encryption_key, mac_key): (Vec<u8>, Vec<u8>)) -> FileKeys {
+    (Zeroizing::new(encryption_key), Zeroizing::new(mac_key))
+}
+
+/// Options controlling how [`decrypt_env()`] handles files and prompts.
+#[derive(Default)]
+pub struct DecryptOptions {
+    /// Overwrite an existing output file instead of failing.
+    pub force: bool,
+    /// Skip interactive prompts (error if no key is provided).
+    pub no_interaction: bool,
+    /// Encoding of the provided or prompted key.
+    pub key_format: KeyFormat,
+    /// Recovery private key to unwrap the file keys with instead of using the user key.
+    pub recovery_key: Option<String>,
+    /// Decrypt the file keys wrapped for GPG recipients with `gpg` (and the GPG agent)
+    /// instead of using the user key.
+    pub gpg: bool,
+    /// Decrypt the file keys wrapped for SSH recipients with an SSH private key instead
+    /// of using the user key.
+    pub ssh: bool,
+    /// SSH private key used with `ssh` (default: `~/.ssh/id_ed25519`, then `~/.ssh/id_rsa`).
+    pub ssh_identity: Option<String>,
+    /// Back up an existing output file before overwriting it.
+    pub backup: Option<BackupConfig>,
+    /// Validate everything and report the planned actions without writing files.
+    pub dry_run: bool,
+    /// Lock the key, derived keys, and plaintext into RAM so they are not swapped to disk.
+    pub mlock: bool,
+    /// Convert the line endings of the plaintext before writing it (default: keep them).
+    pub newline: Option<Newline>,
+    /// Context string the file was bound to when it was encrypted.
+    pub aad: Option<String>,
+    /// Where to get the key when none is passed as `key_arg` (default: interactive prompt).
+    pub key_provider: Option<Rc<dyn KeyProvider>>,
+    /// Also restore the modification time recorded when the file was encrypted.
+    pub preserve_times: bool,
+    /// Keys derived by earlier decryptions, so decrypting a file again with the same key
+    /// skips key derivation.
+    pub key_cache: Option<Rc<KeyMaterial>>,
+    /// Compare the plaintext with the existing output file instead of writing it, failing
+    /// with a summary of the differences (without values) if they differ.
+    pub check: bool,
+    /// Encoding to write the plaintext in.
+    pub encoding: Encoding,
+    /// How long to wait for another envcrypt process writing the output file.
+    pub lock_wait: LockWait,
+    /// Further keys to try, in order, when the key does not decrypt the file (`--key`
+    /// given more than once, or `--key-file`). Encoded as `key_format` says.
+    pub fallback_keys: Vec<String>,
+    /// Suggest re-encrypting files that use a cipher weaker than the recommended one
+    /// (`warn_on_legacy_cipher` in `envcrypt.toml`).
+    pub warn_on_legacy_cipher: bool,
+}
 
 /// Decrypts an encrypted environment file using the specified cipher and key.
 ///
@@ -18,14 +104,16 @@ use crate::cli::output::{OutputConfig, info, verbose, debug};
 ///
 /// # Arguments
 ///
-/// * `cipher_name` - Name of the cipher to use (must match the cipher used for encryption)
-/// * `key_arg` - Optional decryption key. If `None`, the user will be prompted (unless `no_interaction` is true).
-///               Keys can include the "base64:" prefix which will be stripped.
+/// * `cipher_name` - Name of the cipher to use. If `None`, the cipher recorded in the file
+///   header is used (legacy files without a header default to AES-256-CBC). If given, it
+///   must match the cipher recorded in the header.
+/// * `key_arg` - Optional decryption key. If `None`, the user will be prompted (unless `no_interaction` is set).
+///   Keys are decoded according to `key_format` (see [`crate::cli::decode_key`]).
 /// * `input_path` - Path to the encrypted file (typically `.env.encrypted`)
 /// * `output_path` - Path where the decrypted `.env` file will be written
 /// * `output_config` - Output configuration for verbosity control
-/// * `force` - If `true`, overwrite existing output file without error
-/// * `no_interaction` - If `true`, skip interactive prompts (error if key not provided)
+/// * `options` - File handling and prompting options. If `recovery_key`, `gpg` or `ssh` is set,
+///   `key_arg` is ignored and the file keys are unwrapped from the header instead.
 ///
 /// # Returns
 ///
@@ -35,111 +123,453 @@ use crate::cli::output::{OutputConfig, info, verbose, debug};
 ///
 /// Returns an error string if:
 /// - The input file doesn't exist
-/// - The output file exists and `force` is `false`
+/// - The output file exists, `force` is `false`, and the user did not confirm the overwrite
+///   (or `no_interaction` is set)
 /// - File I/O operations fail
-/// - The cipher name is unsupported
+/// - The cipher name is unsupported or does not match the file header
 /// - The encrypted file format is invalid
-/// - MAC verification fails (indicating tampering or incorrect key)
+/// - A recovery key is given but the file has no wrapped keys or they were wrapped for another key
+/// - `gpg` is set but the file has no keys wrapped for GPG recipients or `gpg` cannot decrypt them
+/// - `ssh` is set but the file has no keys wrapped for SSH recipients or the private key is not one of them
+/// - The file is bound to a context string and `aad` is not set, or `aad` is set for a file
+///   that is not bound to one
+/// - MAC verification fails (indicating tampering, an incorrect key, or a different `aad`)
 /// - Decryption fails (incorrect key or corrupted data)
+/// - `check` is set and the output file does not exist or differs from the plaintext
 /// - The decrypted data is not valid UTF-8
 ///
 /// # Security
 ///
 /// - MAC verification is performed before decryption (authenticate-then-decrypt)
 /// - MAC comparison is performed in constant time to prevent timing attacks
-/// - Derived keys are automatically zeroized after use
+/// - Headers in format version 2 and later, including their metadata, are authenticated
+/// - Derived keys, the key string, and the plaintext are automatically zeroized after use
 ///
 /// # File Format
 ///
-/// Expects the encrypted file to contain base64-encoded data with the format:
-/// `base64([Salt (16 bytes)][IV (16 bytes)][Encrypted Data][MAC (32 bytes)])`
+/// Expects the encrypted file to contain data in the versioned format described in
+/// [`crate::format`], either base64-encoded (armor) or as raw bytes (binary); the
+/// encoding is detected automatically. Legacy files without a header
+/// (`base64([Salt (16 bytes)][IV (16 bytes)][Encrypted Data][MAC (32 bytes)])`) are
+/// still supported.
 ///
 /// # Example
 ///
 /// ```no_run
-/// use envcrypt::cli::{decrypt_env, OutputConfig};
+/// use envcrypt::cli::{decrypt_env, DecryptOptions, OutputConfig};
 ///
 /// let output_config = OutputConfig::new(false, false, 0);
-/// decrypt_env("AES-256-CBC", Some("my-key"), ".env.encrypted", ".env", &output_config, false, false)?;
+/// decrypt_env(None, Some("my-key"), ".env.encrypted", ".env", &output_config, &DecryptOptions::default())?;
 /// # Ok::<(), String>(())
 /// ```
 pub fn decrypt_env(
-    cipher_name: &str,
+    cipher_name: Option<&str>,
     key_arg: Option<&str>,
     input_path: &str,
     output_path: &str,
     output_config: &OutputConfig,
-    force: bool,
-    no_interaction: bool,
+    options: &DecryptOptions,
 ) -> Result<(), String> {
     let encrypted_path = Path::new(input_path);
     let env_path = Path::new(output_path);
 
     if !encrypted_path.exists() {
-        return Err(format!("{} file not found", input_path));
+        return Err(format!("{} file not found{}", input_path, input_suggestions(input_path)));
     }
 
-    // Check if output file exists and handle --force flag
-    if env_path.exists() && !force {
-        return Err(format!("Output file {} already exists. Use --force to overwrite.", output_path));
+    let _lock = if options.check || options.dry_run {
+        None
+    } else {
+        Some(lock_file(env_path, options.lock_wait, output_config)?)
+    };
+    // Check if output file exists: overwrite with --force, otherwise ask (dry runs only report it)
+    if !options.check && (!options.dry_run || options.no_interaction) {
+        confirm_overwrite(env_path, options.force, options.no_interaction)?;
     }
 
-    debug(output_config, &format!("Starting decryption: {} -> {}", input_path, output_path));
-    debug(output_config, &format!("Cipher: {}", cipher_name));
-    verbose(output_config, &format!("Input file: {}", input_path));
-    verbose(output_config, &format!("Output file: {}", output_path));
+    let _span = tracing::info_span!("decrypt", input = input_path, output = output_path).entered();
+    tracing::trace!("Starting decryption: {} -> {}", input_path, output_path);
+    tracing::debug!("Input file: {}", input_path);
+    tracing::debug!("Output file: {}", output_path);
 
-    // Get decryption key
-    let key_input = get_encryption_key(key_arg, false, no_interaction)?;
-    
-    // Get cipher
-    let cipher = get_cipher(cipher_name)?;
-    
-    // Read encrypted file
-    let encrypted_content = fs::read_to_string(encrypted_path)
+    // Read encrypted file (armored or binary)
+    let timer = Timer::start("Reading input");
+    let encrypted_content = fs::read(encrypted_path)
         .map_err(|e| format!("Error reading {} file: {}", input_path, e))?;
-    
-    // Decode base64
-    let data = base64::engine::general_purpose::STANDARD.decode(encrypted_content.trim())
-        .map_err(|e| format!("Invalid base64 in encrypted file: {}", e))?;
-    
-    // Extract salt (first 16 bytes) and encrypted data (iv + encrypted_data + mac)
-    if data.len() < 16 {
-        return Err("Invalid encrypted file format".to_string());
+    timer.finish();
+    note_read(input_path);
+    if !looks_encrypted(&encrypted_content) && looks_like_dotenv(&encrypted_content) {
+        return Err(format!(
+            "{} looks unencrypted - did you mean encrypt?{}",
+            input_path,
+            input_suggestions(input_path),
+        ));
     }
+    let (header, plaintext_str) = decrypt_encoded(&encrypted_content, cipher_name, key_arg, output_config, options)?;
+    if options.warn_on_legacy_cipher {
+        warn_on_legacy_cipher(header.cipher.as_deref().or(cipher_name), input_path, output_config);
+    }
+    let _plaintext_lock = lock_secret(output_config, options.mlock, plaintext_str.as_bytes());
+
+    if options.check {
+        return check_plaintext(&plaintext_str, options.encoding, input_path, output_path, output_config);
+    }
+    let output = encode_text(&plaintext_str, options.encoding)
+        .map_err(|e| format!("Cannot write {}: {}", output_path, e))?;
+    let _output_lock = lock_secret(output_config, options.mlock, &output);
     
-    let salt: [u8; 16] = data[0..16].try_into()
-        .map_err(|_| "Invalid salt in encrypted file".to_string())?;
-    let encrypted_data = &data[16..];
-    
-    // Derive keys using the stored salt
-    let (mut encryption_key, mut mac_key) = derive_keys(&key_input, &salt);
-    
-    // Decrypt (encrypted_data contains: iv + encrypted_data + mac)
-    let plaintext = cipher.decrypt(encrypted_data, &encryption_key, &mac_key)
-        .map_err(|e| {
-            // Zeroize keys on error
-            encryption_key.zeroize();
-            mac_key.zeroize();
-            match e {
-                CipherError::MacVerificationFailed => "MAC verification failed - the encrypted file may have been tampered with or the key is incorrect".to_string(),
-                CipherError::DecryptionFailed => "Decryption failed - incorrect key or corrupted data".to_string(),
-                _ => format!("Decryption error: {}", e),
-            }
-        })?;
-    
-    // Zeroize keys after use
-    encryption_key.zeroize();
-    mac_key.zeroize();
-    
-    let plaintext_str = String::from_utf8(plaintext)
-        .map_err(|e| format!("Decrypted data is not valid UTF-8: {}", e))?;
-    
+    if options.dry_run {
+        report_planned_write(output_config, env_path, options.force, options.backup.as_ref());
+        return Ok(());
+    }
+
+    if let Some(backup) = &options.backup {
+        if let Some(backup_path) = backup_file(env_path, backup)? {
+            info(output_config, &format!("Backed up {} to {}", output_path, backup_path.display()));
+        }
+    }
+
     // Write decrypted file
-    debug(output_config, "Writing decrypted data to file");
-    fs::write(env_path, plaintext_str)
+    tracing::trace!("Writing decrypted data to file");
+    // Recreate the file with the permissions it had when it was encrypted
+    let attributes = FileAttributes::from_metadata(&header.metadata);
+    let timer = Timer::start("Writing output");
+    write_with_mode(env_path, &output, attributes.mode)
         .map_err(|e| format!("Error writing {}: {}", output_path, e))?;
+    timer.finish();
+    if let Some(mode) = attributes.mode {
+        tracing::debug!("Restored permissions {:04o}", mode);
+    }
+    if let (true, Some(modified)) = (options.preserve_times, attributes.modified) {
+        set_modified(env_path, modified)
+            .map_err(|e| format!("Error setting the modification time of {}: {}", output_path, e))?;
+        tracing::debug!("Restored modification time");
+    }
     
-    info(output_config, &format!("Successfully decrypted {} to {}", input_path, output_path));
+    success(output_config, &format!("Successfully decrypted {} to {}", input_path, output_path));
     Ok(())
 }
+
+/// Decrypts everything `reader` yields and writes the plaintext to `writer`.
+///
+/// This is [`decrypt_env()`] without the filesystem: the encrypted file can come from
+/// stdin, a network stream or memory, and the plaintext can go to stdout or a buffer. The
+/// whole input is read and authenticated before anything is written. The options that only
+/// concern files (`force`, `backup`, `dry_run`, `check`, `preserve_times`,
+/// `warn_on_legacy_cipher`) are ignored, and no file permissions are restored.
+///
+/// # Errors
+///
+/// Returns an error string if reading from `reader` or writing to `writer` fails, or for
+/// any of the reasons [`decrypt_env()`] gives that do not concern files.
+///
+/// # Example
+///
+/// See [`encrypt_reader_to_writer()`](crate::cli::encrypt_reader_to_writer).
+pub fn decrypt_reader_to_writer<R: Read, W: Write>(
+    cipher_name: Option<&str>,
+    key_arg: Option<&str>,
+    mut reader: R,
+    mut writer: W,
+    output_config: &OutputConfig,
+    options: &DecryptOptions,
+) -> Result<(), String> {
+    let _span = tracing::info_span!("decrypt").entered();
+    let mut encrypted_content = Vec::new();
+    reader.read_to_end(&mut encrypted_content)
+        .map_err(|e| format!("Error reading input: {}", e))?;
+    let (_, plaintext_str) = decrypt_encoded(&encrypted_content, cipher_name, key_arg, output_config, options)?;
+    let _plaintext_lock = lock_secret(output_config, options.mlock, plaintext_str.as_bytes());
+    let output = encode_text(&plaintext_str, options.encoding)
+        .map_err(|e| format!("Cannot write output: {}", e))?;
+    let _output_lock = lock_secret(output_config, options.mlock, &output);
+    writer.write_all(&output)
+        .and_then(|()| writer.flush())
+        .map_err(|e| format!("Error writing output: {}", e))
+}
+
+/// Decodes and decrypts the contents of an encrypted file (armored or binary), returning
+/// its header and the plaintext with its line endings converted as `options.newline` asks.
+fn decrypt_encoded(
+    encrypted_content: &[u8],
+    cipher_name: Option<&str>,
+    key_arg: Option<&str>,
+    output_config: &OutputConfig,
+    options: &DecryptOptions,
+) -> Result<(format::Header, Zeroizing<String>), String> {
+    let (data, file_format) = decode_file(encrypted_content)?;
+    tracing::trace!("File format: {}", file_format);
+
+    let (header, mut plaintext_str) = decrypt_contents(&data, cipher_name, key_arg, output_config, options)?;
+    if let Some(newline) = options.newline {
+        // Converting valid UTF-8 line endings keeps it valid UTF-8
+        plaintext_str = Zeroizing::new(String::from_utf8_lossy(&convert_newlines(plaintext_str.as_bytes(), newline)).into_owned());
+    }
+    Ok((header, plaintext_str))
+}
+
+/// Suggests encrypted files next to `input_path` as `--input` values, as a sentence to
+/// append to an error message (empty if there are none).
+fn input_suggestions(input_path: &str) -> String {
+    const MAX_SUGGESTIONS: usize = 5;
+
+    let input = Path::new(input_path);
+    let dir = input.parent().unwrap_or(Path::new(""));
+    let listed_dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+    let Ok(entries) = fs::read_dir(listed_dir) else {
+        return String::new();
+    };
+
+    let mut candidates: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| name.ends_with(".encrypted") && Some(name.as_str()) != input.file_name().and_then(|n| n.to_str()))
+        .map(|name| dir.join(name).to_string_lossy().into_owned())
+        .collect();
+    if candidates.is_empty() {
+        return String::new();
+    }
+    candidates.sort();
+    candidates.truncate(MAX_SUGGESTIONS);
+    let flags: Vec<String> = candidates.iter().map(|path| format!("--input {}", path)).collect();
+    format!(". Encrypted files nearby: {}", flags.join(", "))
+}
+
+/// Compares the decrypted `plaintext` of `input_path` with the file at `output_path`,
+/// read in `encoding`.
+///
+/// If they differ, the error lists the variables that were added, changed or removed,
+/// by name only, so it is safe to show in CI logs.
+fn check_plaintext(plaintext: &str, encoding: Encoding, input_path: &str, output_path: &str, output_config: &OutputConfig) -> Result<(), String> {
+    let existing = match fs::read(output_path) {
+        Ok(existing) => Zeroizing::new(existing),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(format!("{} does not exist - decrypt {} to create it", output_path, input_path));
+        }
+        Err(e) => return Err(format!("Error reading {}: {}", output_path, e)),
+    };
+    note_read(output_path);
+    let existing = decode_text(&existing, encoding).ok();
+    if existing.as_deref().map(String::as_str) == Some(plaintext) {
+        success(output_config, &format!("{} matches {}", output_path, input_path));
+        return Ok(());
+    }
+
+    let existing_variables = existing.and_then(|text| parse_dotenv(&text).ok());
+    let (Some(existing_variables), Ok(variables)) = (existing_variables, parse_dotenv(plaintext)) else {
+        return Err(format!("{} differs from {}", output_path, input_path));
+    };
+    let mut differences = Vec::new();
+    for (name, value) in &variables {
+        match existing_variables.iter().find(|(existing_name, _)| existing_name == name) {
+            None => differences.push(format!("{}: only in {}", name, input_path)),
+            Some((_, existing_value)) if existing_value != value => differences.push(format!("{}: value differs", name)),
+            Some(_) => {}
+        }
+    }
+    for (name, _) in &existing_variables {
+        if !variables.iter().any(|(decrypted_name, _)| decrypted_name == name) {
+            differences.push(format!("{}: only in {}", name, output_path));
+        }
+    }
+
+    if differences.is_empty() {
+        return Err(format!("{} differs from {} only in comments, formatting or order", output_path, input_path));
+    }
+    let noun = if differences.len() == 1 { "variable differs" } else { "variables differ" };
+    let list: Vec<String> = differences.iter().map(|difference| format!("  - {}", difference)).collect();
+    Err(format!("{} differs from {} ({} {}):\n{}", output_path, input_path, differences.len(), noun, list.join("\n")))
+}
+
+/// Reads and decrypts an encrypted file in memory, returning its header and the plaintext.
+///
+/// This is [`decrypt_env()`] without writing the output, for commands that hand the
+/// variables elsewhere. The cipher is taken from the header.
+pub(crate) fn decrypt_file(
+    key_arg: Option<&str>,
+    input_path: &str,
+    output_config: &OutputConfig,
+    options: &DecryptOptions,
+) -> Result<(format::Header, Zeroizing<String>), String> {
+    let encrypted_path = Path::new(input_path);
+    if !encrypted_path.exists() {
+        return Err(format!("{} file not found{}", input_path, input_suggestions(input_path)));
+    }
+    let encrypted_content = fs::read(encrypted_path)
+        .map_err(|e| format!("Error reading {} file: {}", input_path, e))?;
+    note_read(input_path);
+    let (data, _) = decode_file(&encrypted_content)?;
+    decrypt_contents(&data, None, key_arg, output_config, options)
+}
+
+/// Decrypts the decoded contents of an encrypted file, returning its header and the plaintext.
+///
+/// This is the in-memory part of [`decrypt_env()`]; see there for the meaning of the
+/// arguments and the possible errors. `data` is the output of
+/// [`decode_file()`](crate::armor::decode_file).
+pub(crate) fn decrypt_contents(
+    data: &[u8],
+    cipher_name: Option<&str>,
+    key_arg: Option<&str>,
+    output_config: &OutputConfig,
+    options: &DecryptOptions,
+) -> Result<(format::Header, Zeroizing<String>), String> {
+    // Split header (or legacy salt) from the cipher output (iv + encrypted_data + mac)
+    let (header, encrypted_data) = format::decode(data)
+        .map_err(|e| format!("Invalid encrypted file format: {}", e))?;
+
+    let cipher_name = resolve_cipher_name(cipher_name, header.cipher.as_deref())?;
+    match (header.context_bound, &options.aad) {
+        (true, None) => {
+            return Err("This file is bound to a context string - provide it with --aad or set aad in envcrypt.toml".to_string());
+        }
+        (false, Some(_)) => {
+            return Err("This file is not bound to a context string, but one was given with --aad or aad in envcrypt.toml".to_string());
+        }
+        _ => {}
+    }
+    tracing::trace!("Format version: {}", header.version);
+    tracing::trace!("Cipher: {}", cipher_name);
+    tracing::trace!("KDF: {}", header.kdf);
+    tracing::trace!("Key schedule: {}", header.key_schedule);
+
+    // Get cipher
+    let cipher = get_cipher(&cipher_name)?;
+    
+    // Authenticated headers and the context are bound to the ciphertext as associated data
+    let aad = if header.is_authenticated() {
+        format::associated_data(&data[..header.encoded_len(data, encrypted_data)], options.aad.as_deref())
+    } else {
+        Vec::new()
+    };
+    // The file keys are zeroized when the closure drops them
+    let decrypt = |(encryption_key, mac_key): FileKeys| {
+        let _derived_key_locks = (
+            lock_secret(output_config, options.mlock, &encryption_key),
+            lock_secret(output_config, options.mlock, &mac_key),
+        );
+        let _spinner = Spinner::start(output_config, "Decrypting...");
+        let timer = Timer::start("Decryption");
+        let plaintext = cipher.decrypt_with_aad(encrypted_data, &aad, &encryption_key, &mac_key)
+            .map_err(|e| authentication_failed(&e));
+        timer.finish();
+        plaintext
+    };
+
+    let mut plaintext = if options.gpg {
+        // Let the GPG agent unwrap the file keys instead of deriving them from the user key
+        let message = header.gpg.as_deref()
+            .ok_or_else(|| "This file has no keys wrapped for GPG recipients".to_string())?;
+        tracing::trace!("Using file keys unwrapped with GPG");
+        decrypt(unwrap_keys_gpg(message)?)?
+    } else if options.ssh {
+        let message = header.ssh.as_deref()
+            .ok_or_else(|| "This file has no keys wrapped for SSH recipients".to_string())?;
+        decrypt(zeroizing_keys(unwrap_keys_ssh(message, options.ssh_identity.as_deref().map(Path::new), options.no_interaction)?))?
+    } else if let Some(recovery_key) = &options.recovery_key {
+        // Unwrap the escrowed file keys instead of deriving them from the user key
+        let wrapped = header.recovery.as_deref()
+            .ok_or_else(|| "This file has no keys wrapped for a recovery recipient".to_string())?;
+        let mut secret = decode_raw_key(strip_base64_prefix(recovery_key.trim()))
+            .ok_or_else(|| "Recovery key must be a base64-encoded 32-byte key".to_string())?;
+        let keys = unwrap_keys(&secret, wrapped).map_err(|e| authentication_failed(&e));
+        secret.zeroize();
+        tracing::trace!("Using file keys unwrapped with the recovery key");
+        decrypt(zeroizing_keys(keys?))?
+    } else {
+        // Get decryption key
+        let request = KeyRequest { is_encrypt: false, no_interaction: options.no_interaction, key_format: options.key_format, challenge: header.challenge };
+        let mut key_inputs = vec![get_key(key_arg, options.key_provider.as_deref(), &request)?.value];
+        for key in &options.fallback_keys {
+            key_inputs.push(decode_key(key, options.key_format)?);
+        }
+
+        // Try the keys in turn: the header does not say which key a file is encrypted
+        // with, since that would let guesses be checked without deriving the file keys
+        let mut result = Err(AUTHENTICATION_FAILED.to_string());
+        for (index, key_input) in key_inputs.iter().enumerate() {
+            let _key_lock = lock_secret(output_config, options.mlock, key_input.as_bytes());
+
+            // Derive keys using the stored salt and parameters
+            let spinner = Spinner::start(output_config, "Deriving keys...");
+            let timer = Timer::start("Key derivation");
+            let keys = match &options.key_cache {
+                Some(cache) => derive_file_keys_cached(&header, key_input, cache),
+                None => derive_file_keys(&header, key_input),
+            };
+            timer.finish();
+            drop(spinner);
+
+            // A key that does not fit the file's KDF (say, a passphrase for a raw-key
+            // file) is a failed attempt like a wrong one, so the next key is still tried
+            result = match keys {
+                Ok(keys) => decrypt(zeroizing_keys(keys)),
+                Err(e) => Err(e.to_string()),
+            };
+            if result.is_ok() {
+                if key_inputs.len() > 1 {
+                    tracing::debug!("Decrypted with key {} of {}", index + 1, key_inputs.len());
+                }
+                break;
+            }
+        }
+        result?
+    };
+
+    let _plaintext_lock = lock_secret(output_config, options.mlock, &plaintext);
+    // Move the buffer into the string instead of copying it
+    let plaintext_str = String::from_utf8(std::mem::take(&mut *plaintext))
+        .map_err(|e| {
+            let message = format!("Decrypted data is {}", check_utf8(e.as_bytes()).unwrap_err());
+            e.into_bytes().zeroize();
+            message
+        })?;
+
+    Ok((header, Zeroizing::new(plaintext_str)))
+}
+
+/// Suggests `envcrypt recrypt` if the file at `input_path` is encrypted with a cipher
+/// weaker than [`RECOMMENDED_CIPHER`] and this build can write that one.
+fn warn_on_legacy_cipher(cipher_name: Option<&str>, input_path: &str, output_config: &OutputConfig) {
+    let cipher_name = cipher_name.unwrap_or("AES-256-CBC").to_uppercase();
+    if let Some(upgrade) = cipher_upgrade(&cipher_name, &get_supported_ciphers()) {
+        warning(output_config, &format!(
+            "{} is encrypted with {}; {} is recommended. Upgrade it with `envcrypt recrypt {} --to-cipher {}`, or set warn_on_legacy_cipher = false in {} to hide this advice",
+            input_path, cipher_name, upgrade, input_path, upgrade, CONFIG_FILE,
+        ));
+    }
+}
+
+/// The cipher to recrypt a file encrypted with `cipher_name` to: [`RECOMMENDED_CIPHER`]
+/// where the file only has AES-256-CBC with HMAC, if it is among `supported`.
+fn cipher_upgrade(cipher_name: &str, supported: &[&str]) -> Option<&'static str> {
+    (cipher_name == "AES-256-CBC" && supported.contains(&RECOMMENDED_CIPHER)).then_some(RECOMMENDED_CIPHER)
+}
+
+/// Picks the cipher for decryption from the `--cipher` flag and the file header.
+fn resolve_cipher_name(requested: Option<&str>, recorded: Option<&str>) -> Result<String, String> {
+    let requested = requested.map(canonical_cipher_name).transpose()?;
+    match (requested, recorded) {
+        (Some(requested), Some(recorded)) if !requested.eq_ignore_ascii_case(recorded) => Err(format!(
+            "File was encrypted with {} but --cipher {} was given",
+            recorded, requested
+        )),
+        (_, Some(recorded)) => Ok(recorded.to_string()),
+        (Some(requested), None) => Ok(requested.to_string()),
+        (None, None) => Ok("AES-256-CBC".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cipher_upgrade() {
+        assert_eq!(cipher_upgrade("AES-256-CBC", &["AES-256-CBC", "AES-256-GCM"]), Some("AES-256-GCM"));
+        assert_eq!(cipher_upgrade("AES-256-CBC", &["AES-256-CBC"]), None);
+        assert_eq!(cipher_upgrade("CHACHA20-POLY1305", &["AES-256-CBC", "AES-256-GCM"]), None);
+        assert_eq!(cipher_upgrade("AES-256-GCM", &["AES-256-CBC", "AES-256-GCM"]), None);
+    }
+}
@@ -0,0 +1,56 @@
+//! Per-user directories of the state envcrypt keeps outside projects: the user
+//! configuration, the audit log and caches.
+//!
+//! `ENVCRYPT_HOME` puts all of them in one directory. Otherwise `XDG_CONFIG_HOME`,
+//! `XDG_DATA_HOME` and `XDG_CACHE_HOME` are honored on every platform, falling back to
+//! the platform directories: `~/.config`, `~/.local/share` and `~/.cache` on Linux,
+//! `~/Library/Application Support` and `~/Library/Caches` on macOS, and `%APPDATA%` and
+//! `%LOCALAPPDATA%` on Windows.
+
+use std::path::PathBuf;
+
+/// Environment variable naming one directory for all per-user state of envcrypt.
+pub const HOME_ENV_VAR: &str = "ENVCRYPT_HOME";
+
+/// Directory of the user configuration (`config.toml`).
+///
+/// # Example
+///
+/// ```
+/// use envcrypt::cli::config_dir;
+///
+/// std::env::set_var("ENVCRYPT_HOME", "/srv/envcrypt");
+/// assert_eq!(config_dir(), Some("/srv/envcrypt".into()));
+/// ```
+pub fn config_dir() -> Option<PathBuf> {
+    envcrypt_home().or_else(|| xdg_dir("XDG_CONFIG_HOME").or_else(::dirs::config_dir).map(|dir| dir.join("envcrypt")))
+}
+
+/// Directory of the data envcrypt writes, such as the audit log.
+pub fn data_dir() -> Option<PathBuf> {
+    envcrypt_home().or_else(|| xdg_dir("XDG_DATA_HOME").or_else(::dirs::data_local_dir).map(|dir| dir.join("envcrypt")))
+}
+
+/// Directory of caches, which can be deleted at any time. With `ENVCRYPT_HOME` it is its
+/// `cache` subdirectory.
+pub fn cache_dir() -> Option<PathBuf> {
+    match envcrypt_home() {
+        Some(home) => Some(home.join("cache")),
+        None => xdg_dir("XDG_CACHE_HOME").or_else(::dirs::cache_dir).map(|dir| dir.join("envcrypt")),
+    }
+}
+
+/// The home directory of the user, for paths like `~/.ssh`.
+pub(crate) fn home_dir() -> Option<PathBuf> {
+    ::dirs::home_dir()
+}
+
+fn envcrypt_home() -> Option<PathBuf> {
+    std::env::var_os(HOME_ENV_VAR).filter(|value| !value.is_empty()).map(PathBuf::from)
+}
+
+/// Returns the directory in the XDG variable `name`. Relative paths are ignored, as the
+/// XDG Base Directory specification requires.
+fn xdg_dir(name: &str) -> Option<PathBuf> {
+    std::env::var_os(name).map(PathBuf::from).filter(|dir| dir.is_absolute())
+}
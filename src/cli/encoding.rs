@@ -0,0 +1,153 @@
+//! Text encodings of env files: UTF-8 checks with precise errors, byte order marks, and
+//! the Latin-1 conversion for legacy files (`--encoding`).
+
+use clap::ValueEnum;
+use zeroize::Zeroizing;
+
+/// The UTF-8 byte order mark some Windows editors put at the start of files.
+pub(crate) const UTF8_BOM: &str = "\u{feff}";
+
+/// Encoding of a plaintext env file read or written by envcrypt. Encrypted files always
+/// hold UTF-8.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Encoding {
+    /// UTF-8, with or without a byte order mark
+    #[default]
+    Utf8,
+    /// Latin-1 (ISO-8859-1), converted to UTF-8 when encrypting and back when decrypting
+    Latin1,
+}
+
+/// Reads the plaintext file `bytes` as text in `encoding`.
+///
+/// # Errors
+///
+/// Returns an error string saying where the first invalid byte is (see
+/// [`check_utf8()`]), with a hint to use Latin-1, if `encoding` is UTF-8 and `bytes` are
+/// not valid UTF-8.
+///
+/// # Example
+///
+/// ```
+/// use envcrypt::cli::{decode_text, Encoding};
+///
+/// assert_eq!(decode_text(b"NAME=Jos\xe9\n", Encoding::Latin1)?.as_str(), "NAME=José\n");
+/// assert!(decode_text(b"NAME=Jos\xe9\n", Encoding::Utf8).is_err());
+/// # Ok::<(), String>(())
+/// ```
+pub fn decode_text(bytes: &[u8], encoding: Encoding) -> Result<Zeroizing<String>, String> {
+    match encoding {
+        Encoding::Utf8 => check_utf8(bytes)
+            .map(|text| Zeroizing::new(text.to_string()))
+            .map_err(|e| {
+                if e.contains("UTF-16") {
+                    e
+                } else {
+                    format!("{}. If the file is Latin-1 (ISO-8859-1), pass --encoding latin1", e)
+                }
+            }),
+        Encoding::Latin1 => Ok(Zeroizing::new(bytes.iter().map(|&byte| char::from(byte)).collect())),
+    }
+}
+
+/// Returns `text` encoded in `encoding`. A byte order mark is dropped when encoding to
+/// Latin-1, which has none.
+///
+/// # Errors
+///
+/// Returns an error string naming the first character, with its line and column, that
+/// `encoding` cannot represent.
+///
+/// # Example
+///
+/// ```
+/// use envcrypt::cli::{encode_text, Encoding};
+///
+/// assert_eq!(encode_text("NAME=José\n", Encoding::Latin1)?.as_slice(), b"NAME=Jos\xe9\n");
+/// assert!(encode_text("NAME=€\n", Encoding::Latin1).is_err());
+/// # Ok::<(), String>(())
+/// ```
+pub fn encode_text(text: &str, encoding: Encoding) -> Result<Zeroizing<Vec<u8>>, String> {
+    match encoding {
+        Encoding::Utf8 => Ok(Zeroizing::new(text.as_bytes().to_vec())),
+        Encoding::Latin1 => {
+            let text = text.strip_prefix(UTF8_BOM).unwrap_or(text);
+            let mut bytes = Zeroizing::new(Vec::with_capacity(text.len()));
+            for (offset, c) in text.char_indices() {
+                let byte = u8::try_from(u32::from(c)).map_err(|_| {
+                    let (line, column) = position(text.as_bytes(), offset);
+                    format!("U+{:04X} at line {}, column {} cannot be written as Latin-1", u32::from(c), line, column)
+                })?;
+                bytes.push(byte);
+            }
+            Ok(bytes)
+        }
+    }
+}
+
+/// Checks that `bytes` are UTF-8 text.
+///
+/// # Errors
+///
+/// Returns an error string giving the first invalid byte with its line, column and byte
+/// offset, and recognizing UTF-16 files by their byte order mark, so the problem can be
+/// found in an editor.
+///
+/// # Example
+///
+/// ```
+/// use envcrypt::cli::check_utf8;
+///
+/// assert_eq!(
+///     check_utf8(b"A=1\nNAME=Jos\xe9\n").unwrap_err(),
+///     "not valid UTF-8: byte 0xE9 at line 2, column 9 (byte offset 12)",
+/// );
+/// ```
+pub fn check_utf8(bytes: &[u8]) -> Result<&str, String> {
+    std::str::from_utf8(bytes).map_err(|e| {
+        if bytes.starts_with(&[0xff, 0xfe]) || bytes.starts_with(&[0xfe, 0xff]) {
+            return "not valid UTF-8: the file is UTF-16 encoded; save it as UTF-8".to_string();
+        }
+        let offset = e.valid_up_to();
+        let (line, column) = position(bytes, offset);
+        let byte = match e.error_len() {
+            Some(_) => format!("byte 0x{:02X}", bytes[offset]),
+            None => "incomplete character".to_string(),
+        };
+        format!("not valid UTF-8: {} at line {}, column {} (byte offset {})", byte, line, column, offset)
+    })
+}
+
+/// Returns the line and column (in characters, both starting at 1) of byte `offset` in
+/// `bytes`, which are valid UTF-8 up to `offset`.
+fn position(bytes: &[u8], offset: usize) -> (usize, usize) {
+    let before = &bytes[..offset];
+    let line_start = before.iter().rposition(|&byte| byte == b'\n').map_or(0, |index| index + 1);
+    let column = String::from_utf8_lossy(&before[line_start..]).chars().count() + 1;
+    (before.iter().filter(|&&byte| byte == b'\n').count() + 1, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_utf8_errors() {
+        assert_eq!(check_utf8(b"A=1\n").unwrap(), "A=1\n");
+        let error = check_utf8(b"A=\xc3").unwrap_err();
+        assert!(error.starts_with("not valid UTF-8: incomplete character at line 1, column 3 (byte offset 2)"), "{}", error);
+        let error = check_utf8(b"A=\xc3\xa9\xe9!").unwrap_err();
+        assert!(error.starts_with("not valid UTF-8: byte 0xE9 at line 1, column 4 (byte offset 4)"), "{}", error);
+        assert_eq!(check_utf8(b"\xff\xfeA\0=\0").unwrap_err(), "not valid UTF-8: the file is UTF-16 encoded; save it as UTF-8");
+    }
+
+    #[test]
+    fn test_latin1_roundtrip() {
+        let latin1: Vec<u8> = (0..=255).collect();
+        let text = decode_text(&latin1, Encoding::Latin1).unwrap();
+        assert_eq!(text.chars().count(), 256);
+        assert_eq!(encode_text(&text, Encoding::Latin1).unwrap().as_slice(), latin1.as_slice());
+        assert_eq!(encode_text("\u{feff}A=1", Encoding::Latin1).unwrap().as_slice(), b"A=1");
+        assert_eq!(encode_text("A=1\nB=€", Encoding::Latin1).unwrap_err(), "U+20AC at line 2, column 3 cannot be written as Latin-1");
+    }
+}
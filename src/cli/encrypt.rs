@@ -1,16 +1,137 @@
 //! Encryption command implementation.
 
 use std::fs;
+use std::io::{Read, Write};
 use std::path::Path;
-use base64::Engine;
-use zeroize::Zeroize;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zeroize::{Zeroize, Zeroizing};
 
-use crate::key::{derive_keys, generate_salt};
-use crate::cli::cipher::get_cipher;
-use crate::cli::key_handling::get_encryption_key;
-use crate::cli::output::{OutputConfig, info, verbose, debug};
+use crate::format::{Header, Kdf, Metadata, CHALLENGE_LEN};
+use crate::key::{
+    decode_raw_key, derive_keys_from_raw_key, derive_keys_with_iterations, generate_salt,
+    DEFAULT_PBKDF2_ITERATIONS, MAX_PBKDF2_ITERATIONS, MIN_PBKDF2_ITERATIONS,
+};
+use crate::recovery::{wrap_keys, RECOVERY_KEY_LEN};
+use crate::signature::{append_signature, sign_detached, signing_public_key, SIGNING_KEY_LEN};
+use crate::armor::{encode_file, looks_encrypted, FileFormat};
+use crate::envelope::seal;
+use crate::cipher::RECOMMENDED_CIPHER;
+use crate::cli::backup::{backup_file, report_planned_write, BackupConfig};
+use crate::cli::config::CONFIG_FILE;
+use crate::cli::cipher::canonical_cipher_name;
+use crate::cli::key_handling::{assess_key_strength, get_key, KeyFormat, KeyProvider, KeyRequest, KeyStrength, UserKey};
+use crate::cli::encoding::{decode_text, Encoding};
+use crate::cli::memory::lock_secret;
+use crate::cli::newline::{convert_newlines, Newline};
+use crate::cli::prompt::{assumed_yes, confirm, confirm_overwrite, Prompt, TerminalPrompt};
+use crate::cli::output::{OutputConfig, info, dry_run, success, warning, Spinner};
+use crate::cli::logging::Timer;
+use crate::cli::attributes::FileAttributes;
+use crate::cli::trash::{trash_file, TrashConfig};
+use crate::cli::lock::{lock_file, FileLock, LockWait};
+use crate::cli::audit::{note_read, note_written};
+use crate::cli::gpg::wrap_keys_gpg;
+use crate::cli::sign::{detached_signature_path, public_key_text, write_detached_signature};
+use crate::cli::ssh::{resolve_ssh_recipients, wrap_keys_ssh};
 // Note: resolve_encrypt_input_path and resolve_encrypt_output_path are only used in mod.rs
 
+/// Maximum length of a header comment in bytes.
+pub(crate) const MAX_COMMENT_LEN: usize = 1024;
+
+/// Options controlling how [`encrypt_env()`] handles files, prompts, and key derivation.
+#[derive(Clone)]
+pub struct EncryptOptions {
+    /// Overwrite an existing output file instead of failing, and accept keys that
+    /// are too weak to be used otherwise.
+    pub force: bool,
+    /// Delete the original input file after successful encryption. On a terminal the
+    /// user is asked first, unless `--yes` or `ENVCRYPT_ASSUME_YES` is given.
+    pub prune: bool,
+    /// Move the original to this trash instead of deleting it when pruning.
+    pub trash: Option<TrashConfig>,
+    /// Skip interactive prompts (auto-generate a key if none is provided).
+    pub no_interaction: bool,
+    /// Number of PBKDF2 iterations used to derive the file keys.
+    pub kdf_iterations: u32,
+    /// Treat the key as a raw base64-encoded 32-byte key and split it with HKDF instead
+    /// of running PBKDF2. Keys generated by envcrypt always use this mode.
+    pub raw_key: bool,
+    /// Encoding of the provided or prompted key.
+    pub key_format: KeyFormat,
+    /// Recovery public key to additionally wrap the file keys for (key escrow).
+    pub recovery_public_key: Option<[u8; RECOVERY_KEY_LEN]>,
+    /// OpenPGP key IDs to additionally encrypt the file keys to with `gpg`.
+    pub gpg_recipients: Vec<String>,
+    /// SSH public keys to additionally encrypt the file keys to: key lines, public key
+    /// files, or `github:<username>`.
+    pub ssh_recipients: Vec<String>,
+    /// Back up an existing output file before overwriting it.
+    pub backup: Option<BackupConfig>,
+    /// Validate everything and report the planned actions without writing or deleting files.
+    pub dry_run: bool,
+    /// Lock the key, derived keys, and plaintext into RAM so they are not swapped to disk.
+    pub mlock: bool,
+    /// Convert the line endings of the plaintext before encrypting it (default: keep them).
+    pub newline: Option<Newline>,
+    /// Encoding of the written file.
+    pub format: FileFormat,
+    /// Comment recorded in the file header (readable without the key).
+    pub comment: Option<String>,
+    /// Context string the ciphertext is bound to; decryption requires the same string.
+    pub aad: Option<String>,
+    /// Where to get the key when none is passed as `key_arg` (default: interactive prompt).
+    pub key_provider: Option<Rc<dyn KeyProvider>>,
+    /// Encrypt the input even if it already looks like an envcrypt file.
+    pub allow_double_encrypt: bool,
+    /// Encoding of the input file; it is encrypted as UTF-8.
+    pub encoding: Encoding,
+    /// Hardware token challenge the key was derived from, recorded in the header so the
+    /// key can be derived again. Filled in from the key provider when it derives the key.
+    pub challenge: Option<[u8; CHALLENGE_LEN]>,
+    /// Ed25519 private key to sign the encrypted file with (see [`crate::signature`]).
+    pub sign_key: Option<Zeroizing<[u8; SIGNING_KEY_LEN]>>,
+    /// Write the signature to `<output>.sig` instead of embedding it in the file.
+    pub detached_signature: bool,
+    /// How long to wait for another envcrypt process writing the output file.
+    pub lock_wait: LockWait,
+    /// Only allow AEAD ciphers and, for passphrases, at least
+    /// [`STRICT_MIN_PBKDF2_ITERATIONS`] PBKDF2 iterations (`--strict-crypto`).
+    pub strict_crypto: bool,
+}
+
+impl Default for EncryptOptions {
+    fn default() -> Self {
+        Self {
+            force: false,
+            prune: false,
+            trash: None,
+            no_interaction: false,
+            kdf_iterations: DEFAULT_PBKDF2_ITERATIONS,
+            raw_key: false,
+            key_format: KeyFormat::Auto,
+            recovery_public_key: None,
+            gpg_recipients: Vec::new(),
+            ssh_recipients: Vec::new(),
+            backup: None,
+            dry_run: false,
+            mlock: false,
+            newline: None,
+            format: FileFormat::Armor,
+            comment: None,
+            aad: None,
+            key_provider: None,
+            allow_double_encrypt: false,
+            encoding: Encoding::Utf8,
+            challenge: None,
+            sign_key: None,
+            detached_signature: false,
+            lock_wait: LockWait::Forever,
+            strict_crypto: false,
+        }
+    }
+}
+
 /// Encrypts an environment file using the specified cipher and key.
 ///
 /// This function reads a plaintext environment file, encrypts it using the specified
@@ -20,48 +141,63 @@ use crate::cli::output::{OutputConfig, info, verbose, debug};
 /// # Arguments
 ///
 /// * `cipher_name` - Name of the cipher to use (e.g., "AES-256-CBC")
-/// * `key_arg` - Optional encryption key. If `None`, the user will be prompted (unless `no_interaction` is true).
-///               Keys can include the "base64:" prefix which will be stripped.
+/// * `key_arg` - Optional encryption key. If `None`, the user will be prompted (unless `no_interaction` is set).
+///   Keys are decoded according to `key_format` (see [`crate::cli::decode_key`]).
 /// * `input_path` - Path to the plaintext `.env` file to encrypt
 /// * `output_path` - Path where the encrypted file will be written
 /// * `output_config` - Output configuration for verbosity control
-/// * `force` - If `true`, overwrite existing output file without error
-/// * `prune` - If `true`, delete the original input file after successful encryption
-/// * `no_interaction` - If `true`, skip interactive prompts (auto-generate key if not provided)
+/// * `options` - File handling, prompting, and key derivation options
 ///
 /// # Returns
 ///
-/// Returns `Ok(key_string)` where `key_string` is the encryption key that was used
-/// (for display to the user). Returns an error if encryption fails.
+/// Returns `Ok(key)` where `key` is the encryption key that was used (for display to
+/// the user), and whether envcrypt generated it; the key is zeroized when dropped.
+/// Returns an error if encryption fails.
 ///
 /// # Errors
 ///
 /// Returns an error string if:
 /// - The input file doesn't exist
-/// - The output file exists and `force` is `false`
+/// - The output file exists, `force` is `false`, and the user did not confirm the overwrite
+///   (or `no_interaction` is set)
 /// - File I/O operations fail
 /// - The cipher name is unsupported
+/// - A GPG recipient's public key is not in the local keyring
+/// - An SSH recipient cannot be read or fetched, or has no ssh-ed25519 or ssh-rsa key
+/// - The KDF iteration count is below the supported minimum
+/// - A user-chosen key is too easy to guess and `force` is `false`
+/// - `raw_key` is set but the key is not a base64-encoded 32-byte key
+/// - The comment is longer than 1024 bytes, or the `aad` context string is empty
 /// - Key derivation or encryption fails
 ///
 /// # Security
 ///
 /// - A random salt is generated for each encryption
 /// - A random IV is generated for each encryption
-/// - Derived keys are automatically zeroized after use
+/// - Passphrases are stretched with PBKDF2; generated (raw) keys are split with HKDF
+/// - Derived keys, the key string, and the plaintext are automatically zeroized after use
+/// - If a recovery public key is set, the derived keys are also wrapped for it
+/// - If GPG recipients are set, the derived keys are also encrypted to them with `gpg`
+/// - If SSH recipients are set, the derived keys are also encrypted to them as with `age`
+/// - If `options.aad` is set, the ciphertext is bound to it and only decrypts with the same string
 /// - The encryption key is returned for user storage (should be kept secure)
 ///
 /// # File Format
 ///
-/// The encrypted file contains base64-encoded data with the format:
-/// `base64([Salt (16 bytes)][IV (16 bytes)][Encrypted Data][MAC (32 bytes)])`
+/// The encrypted file contains data in the versioned format described in
+/// [`crate::format`]: a header recording the cipher, KDF parameters, salt, creation time,
+/// tool version, and `options.comment`, followed by the cipher output. The header is
+/// authenticated together with the ciphertext. It is base64-encoded unless `options.format` is
+/// [`FileFormat::Binary`](crate::cli::FileFormat::Binary).
 ///
 /// # Example
 ///
 /// ```no_run
-/// use envcrypt::cli::{encrypt_env, OutputConfig};
+/// use envcrypt::cli::{encrypt_env, EncryptOptions, OutputConfig};
 ///
 /// let output_config = OutputConfig::new(false, false, 0);
-/// let key = encrypt_env("AES-256-CBC", Some("my-key"), ".env", ".env.encrypted", &output_config, false, false, false)?;
+/// let options = EncryptOptions::default();
+/// let key = encrypt_env("AES-256-CBC", Some("my-key"), ".env", ".env.encrypted", &output_config, &options)?;
 /// # Ok::<(), String>(())
 /// ```
 pub fn encrypt_env(
@@ -70,77 +206,480 @@ pub fn encrypt_env(
     input_path: &str,
     output_path: &str,
     output_config: &OutputConfig,
-    force: bool,
-    prune: bool,
-    no_interaction: bool,
-) -> Result<String, String> {
+    options: &EncryptOptions,
+) -> Result<UserKey, String> {
     let env_path = Path::new(input_path);
-    let encrypted_path = Path::new(output_path);
 
     if !env_path.exists() {
         return Err(format!("{} file not found", input_path));
     }
 
-    // Check if output file exists and handle --force flag
-    if encrypted_path.exists() && !force {
-        return Err(format!("Output file {} already exists. Use --force to overwrite.", output_path));
+    let _lock = check_output(output_path, output_config, options)?;
+
+    let _span = tracing::info_span!("encrypt", input = input_path, output = output_path, cipher = cipher_name).entered();
+    tracing::trace!("Starting encryption: {} -> {}", input_path, output_path);
+    tracing::trace!("Cipher: {}", cipher_name);
+    tracing::debug!("Input file: {}", input_path);
+    tracing::debug!("Output file: {}", output_path);
+
+    // Read plaintext
+    let timer = Timer::start("Reading input");
+    let plaintext = Zeroizing::new(fs::read(env_path)
+        .map_err(|e| format!("Error reading {} file: {}", input_path, e))?);
+    timer.finish();
+    note_read(input_path);
+    let _plaintext_lock = lock_secret(output_config, options.mlock, &plaintext);
+    let text = check_plaintext(&plaintext, input_path, options)?;
+    let _text_lock = lock_secret(output_config, options.mlock, text.as_bytes());
+
+    let key_input = write_encrypted(
+        cipher_name,
+        key_arg,
+        text.as_bytes(),
+        &FileAttributes::read(env_path),
+        output_path,
+        output_config,
+        options,
+    )?;
+    if options.dry_run {
+        match (&options.trash, options.prune) {
+            (Some(trash), true) => dry_run(output_config, &format!("Would move {} to {}", input_path, trash.dir.display())),
+            (None, true) => dry_run(output_config, &format!("Would delete {}", input_path)),
+            _ => {}
+        }
+        return Ok(key_input);
+    }
+    
+    success(output_config, &format!("\nSuccessfully encrypted {} to {}", input_path, output_path));
+
+    // Handle --prune flag: move the original file to the trash, or delete it after asking
+    // when someone is at the terminal
+    if let (Some(trash), true) = (&options.trash, options.prune) {
+        let trashed = trash_file(env_path, trash)?;
+        info(output_config, &format!("Moved {} to {}", input_path, trashed.display()));
+    } else if options.prune && confirm_prune(input_path, options)? {
+        tracing::trace!("Pruning original file: {}", input_path);
+        fs::remove_file(env_path)
+            .map_err(|e| format!("Error removing original file {}: {}", input_path, e))?;
+        tracing::debug!("Removed original file: {}", input_path);
+    } else if options.prune {
+        info(output_config, &format!("Kept {}", input_path));
     }
 
-    debug(output_config, &format!("Starting encryption: {} -> {}", input_path, output_path));
-    debug(output_config, &format!("Cipher: {}", cipher_name));
-    verbose(output_config, &format!("Input file: {}", input_path));
-    verbose(output_config, &format!("Output file: {}", output_path));
+    Ok(key_input)
+}
+
+/// Encrypts everything `reader` yields and writes the encrypted file to `writer`.
+///
+/// This is [`encrypt_env()`] without the filesystem: the plaintext can come from stdin, a
+/// network stream or memory, and the encrypted file can go to stdout or a buffer. It reads
+/// the whole plaintext before encrypting it, since the MAC covers all of it. As nothing is
+/// known about the source, no file permissions are recorded, and the options that only
+/// concern files (`force`, `prune`, `backup`, `dry_run`, detached signatures) are ignored.
+///
+/// # Returns
+///
+/// Returns `Ok(key)` where `key` is the encryption key that was used, as for
+/// [`encrypt_env()`].
+///
+/// # Errors
+///
+/// Returns an error string if reading from `reader` or writing to `writer` fails, or for
+/// any of the reasons [`encrypt_env()`] gives that do not concern files.
+///
+/// # Example
+///
+/// ```
+/// use envcrypt::cli::{decrypt_reader_to_writer, encrypt_reader_to_writer, DecryptOptions, EncryptOptions, OutputConfig};
+///
+/// let output_config = OutputConfig::new(true, false, 0);
+/// let options = EncryptOptions { kdf_iterations: 10_000, ..EncryptOptions::default() };
+/// let mut encrypted = Vec::new();
+/// encrypt_reader_to_writer("AES-256-GCM", Some("correct horse battery staple"), "API_KEY=secret\n".as_bytes(), &mut encrypted, &output_config, &options)?;
+///
+/// let mut plaintext = Vec::new();
+/// decrypt_reader_to_writer(None, Some("correct horse battery staple"), encrypted.as_slice(), &mut plaintext, &output_config, &DecryptOptions::default())?;
+/// assert_eq!(plaintext, b"API_KEY=secret\n");
+/// # Ok::<(), String>(())
+/// ```
+pub fn encrypt_reader_to_writer<R: Read, W: Write>(
+    cipher_name: &str,
+    key_arg: Option<&str>,
+    mut reader: R,
+    mut writer: W,
+    output_config: &OutputConfig,
+    options: &EncryptOptions,
+) -> Result<UserKey, String> {
+    let _span = tracing::info_span!("encrypt", cipher = cipher_name).entered();
+    let mut plaintext = Zeroizing::new(Vec::new());
+    reader.read_to_end(&mut plaintext)
+        .map_err(|e| format!("Error reading input: {}", e))?;
+    let _plaintext_lock = lock_secret(output_config, options.mlock, &plaintext);
+    let text = check_plaintext(&plaintext, "input", options)?;
+    let _text_lock = lock_secret(output_config, options.mlock, text.as_bytes());
+
+    let (output, key_input) = encrypt_with_user_key(cipher_name, key_arg, text.as_bytes(), &FileAttributes::default(), output_config, options)?;
+    writer.write_all(&output)
+        .and_then(|()| writer.flush())
+        .map_err(|e| format!("Error writing output: {}", e))?;
+    Ok(key_input)
+}
+
+/// Checks that `plaintext`, read from `input_name`, is not already encrypted (unless
+/// `allow_double_encrypt` is set) and decodes it from `options.encoding`.
+fn check_plaintext(plaintext: &[u8], input_name: &str, options: &EncryptOptions) -> Result<Zeroizing<String>, String> {
+    // Encrypting an encrypted file is almost always a mix-up of input and output
+    if !options.allow_double_encrypt && looks_encrypted(plaintext) {
+        return Err(format!("Input {} already appears encrypted; use --allow-double-encrypt to override", input_name));
+    }
+    decode_text(plaintext, options.encoding)
+        .map_err(|e| format!("Error reading {} file: {}", input_name, e))
+}
+
+/// Whether the plaintext at `input_path` may be deleted after encrypting it: asks when
+/// someone is at the terminal, unless `--yes` or `ENVCRYPT_ASSUME_YES` answers for them.
+fn confirm_prune(input_path: &str, options: &EncryptOptions) -> Result<bool, String> {
+    if options.no_interaction || !TerminalPrompt.is_interactive() || assumed_yes()? {
+        return Ok(true);
+    }
+    confirm(&TerminalPrompt, &format!("Delete {}?", input_path))
+}
+
+/// Locks the encrypted file against other envcrypt processes (except in dry runs) and
+/// fails early if it cannot be written: it exists and overwriting it was not confirmed,
+/// or the KDF iteration count is too low.
+///
+/// The lock is held until the returned value is dropped, so keep it until the file has
+/// been written.
+pub(crate) fn check_output(output_path: &str, output_config: &OutputConfig, options: &EncryptOptions) -> Result<Option<FileLock>, String> {
+    let lock = if options.dry_run {
+        None
+    } else {
+        Some(lock_file(Path::new(output_path), options.lock_wait, output_config)?)
+    };
+    // Check if output file exists: overwrite with --force, otherwise ask (dry runs only report it)
+    if !options.dry_run || options.no_interaction {
+        confirm_overwrite(Path::new(output_path), options.force, options.no_interaction)?;
+    }
+
+    check_kdf_iterations(options)?;
+    Ok(lock)
+}
+
+/// Fails if `options.kdf_iterations` is outside the supported range.
+pub(crate) fn check_kdf_iterations(options: &EncryptOptions) -> Result<(), String> {
+    check_iteration_range(options.kdf_iterations)
+}
+
+/// Fails if `iterations` is below [`MIN_PBKDF2_ITERATIONS`] or above [`MAX_PBKDF2_ITERATIONS`].
+pub(crate) fn check_iteration_range(iterations: u32) -> Result<(), String> {
+    if iterations < MIN_PBKDF2_ITERATIONS {
+        return Err(format!("KDF iterations must be at least {} (got {})", MIN_PBKDF2_ITERATIONS, iterations));
+    }
+    if iterations > MAX_PBKDF2_ITERATIONS {
+        return Err(format!("KDF iterations must be at most {} (got {})", MAX_PBKDF2_ITERATIONS, iterations));
+    }
+    Ok(())
+}
+
+/// Lowest PBKDF2 iteration count `--strict-crypto` allows, as recommended by OWASP for
+/// PBKDF2-HMAC-SHA256.
+pub const STRICT_MIN_PBKDF2_ITERATIONS: u32 = 600_000;
+
+/// Checks a new encryption against the `--strict-crypto` policy: the cipher must be an
+/// AEAD, and a passphrase must be stretched with at least
+/// [`STRICT_MIN_PBKDF2_ITERATIONS`] iterations. Raw keys are random, so HKDF is allowed.
+pub(crate) fn check_strict_crypto(cipher_name: &str, use_raw_key: bool, kdf_iterations: u32) -> Result<(), String> {
+    if cipher_name == "AES-256-CBC" {
+        return Err(format!(
+            "{} is not allowed with --strict-crypto, since it is not an AEAD cipher. Use --cipher {} or --to-cipher {} (or set ENVCRYPT_CIPHER)",
+            cipher_name, RECOMMENDED_CIPHER, RECOMMENDED_CIPHER,
+        ));
+    }
+    if !use_raw_key && kdf_iterations < STRICT_MIN_PBKDF2_ITERATIONS {
+        return Err(format!(
+            "{} PBKDF2 iterations are not allowed with --strict-crypto, which requires at least {}. Pass --kdf-iterations {} or set kdf_iterations in {}, or use a generated raw key",
+            kdf_iterations, STRICT_MIN_PBKDF2_ITERATIONS, STRICT_MIN_PBKDF2_ITERATIONS, CONFIG_FILE,
+        ));
+    }
+    Ok(())
+}
+
+/// Gets the key, encrypts `plaintext` and writes it to `output_path` (backing up the
+/// existing file if requested), returning the key that was used.
+///
+/// This is the part of [`encrypt_env()`] after the plaintext has been read and checked;
+/// with `options.dry_run` it only reports the planned write. `source` describes the
+/// plaintext file, as for [`encrypt_contents()`].
+pub(crate) fn write_encrypted(
+    cipher_name: &str,
+    key_arg: Option<&str>,
+    plaintext: &[u8],
+    source: &FileAttributes,
+    output_path: &str,
+    output_config: &OutputConfig,
+    options: &EncryptOptions,
+) -> Result<UserKey, String> {
+    let encrypted_path = Path::new(output_path);
+    let (final_output, key_input) = encrypt_with_user_key(cipher_name, key_arg, plaintext, source, output_config, options)?;
+    let _key_lock = lock_secret(output_config, options.mlock, key_input.value.as_bytes());
 
+    let detached_signature = match &options.sign_key {
+        Some(sign_key) if options.detached_signature => Some(sign_detached(sign_key, &final_output)),
+        _ => None,
+    };
+    let signature_path = detached_signature_path(output_path);
+    if options.dry_run {
+        report_planned_write(output_config, encrypted_path, options.force, options.backup.as_ref());
+        if detached_signature.is_some() {
+            dry_run(output_config, &format!("Would write the signature to {}", signature_path));
+        }
+        return Ok(key_input);
+    }
+
+    if let Some(backup) = &options.backup {
+        if let Some(backup_path) = backup_file(encrypted_path, backup)? {
+            info(output_config, &format!("Backed up {} to {}", output_path, backup_path.display()));
+        }
+    }
+
+    // Write encrypted file
+    tracing::trace!("Writing encrypted data to file");
+    let timer = Timer::start("Writing output");
+    fs::write(encrypted_path, final_output)
+        .map_err(|e| format!("Error writing {}: {}", output_path, e))?;
+    timer.finish();
+    note_written(output_path);
+    if let Some(signature) = detached_signature {
+        write_detached_signature(&signature_path, &signature)?;
+    }
+
+    Ok(key_input)
+}
+
+/// Gets the key and encrypts `plaintext` with it, returning the file contents and the key.
+///
+/// A passphrase that is too easy to guess is refused unless `options.force` is set, and
+/// the line endings are converted first if `options.newline` asks for it.
+fn encrypt_with_user_key(
+    cipher_name: &str,
+    key_arg: Option<&str>,
+    plaintext: &[u8],
+    source: &FileAttributes,
+    output_config: &OutputConfig,
+    options: &EncryptOptions,
+) -> Result<(Vec<u8>, UserKey), String> {
     // Get encryption key
-    let key_input = get_encryption_key(key_arg, true, no_interaction)?;
-    
-    // Get cipher
-    let cipher = get_cipher(cipher_name)?;
+    let request = KeyRequest { is_encrypt: true, no_interaction: options.no_interaction, key_format: options.key_format, challenge: None };
+    let user_key = get_key(key_arg, options.key_provider.as_deref(), &request)?;
+    let key_input = &user_key.value;
+    let options = &EncryptOptions { challenge: user_key.challenge.or(options.challenge), ..options.clone() };
+    let _key_lock = lock_secret(output_config, options.mlock, key_input.as_bytes());
+
+    // Custom passphrases are the weakest link; generated, hardware and raw keys are random
+    let random_key = user_key.generated || options.challenge.is_some();
+    if !random_key && !options.raw_key {
+        match assess_key_strength(key_input) {
+            KeyStrength::TooWeak(advice) if !options.force => {
+                return Err(format!("Encryption key is too weak: {} Use a longer passphrase or a generated key, or pass --force to use it anyway.", advice));
+            }
+            KeyStrength::TooWeak(advice) | KeyStrength::Weak(advice) => {
+                warning(output_config, &format!("Encryption key is weak: {}", advice));
+            }
+            KeyStrength::Strong => {}
+        }
+    }
     
-    // Read plaintext
-    let plaintext = fs::read_to_string(env_path)
-        .map_err(|e| format!("Error reading {} file: {}", input_path, e))?;
+    let converted = options.newline.map(|newline| convert_newlines(plaintext, newline));
+    let _converted_lock = converted.as_ref().map(|converted| lock_secret(output_config, options.mlock, converted));
+    let plaintext = converted.as_deref().map_or(plaintext, Vec::as_slice);
+
+    let final_output = encrypt_contents(
+        cipher_name,
+        plaintext,
+        source,
+        key_input,
+        options.raw_key || random_key,
+        output_config,
+        options,
+    )?;
+    Ok((final_output, user_key))
+}
+
+/// Encrypts plaintext with an already obtained key, returning the file contents encoded
+/// in `options.format`.
+///
+/// This is the in-memory part of [`encrypt_env()`]. If `use_raw_key` is `true` the key
+/// must be a base64-encoded 32-byte key and is split with HKDF; otherwise it is stretched
+/// with PBKDF2 using `options.kdf_iterations`. The file keys are also wrapped for
+/// `options.recovery_public_key` and encrypted to `options.gpg_recipients` and
+/// `options.ssh_recipients`, if set. `source` is recorded so decryption can restore
+/// the plaintext file's permissions and modification time.
+pub(crate) fn encrypt_contents(
+    cipher_name: &str,
+    plaintext: &[u8],
+    source: &FileAttributes,
+    key_input: &str,
+    use_raw_key: bool,
+    output_config: &OutputConfig,
+    options: &EncryptOptions,
+) -> Result<Vec<u8>, String> {
+    if options.comment.as_ref().is_some_and(|comment| comment.len() > MAX_COMMENT_LEN) {
+        return Err(format!("Comment must be at most {} bytes", MAX_COMMENT_LEN));
+    }
+    if options.aad.as_ref().is_some_and(|aad| aad.is_empty()) {
+        return Err("--aad must not be empty".to_string());
+    }
+
+    // Check the cipher up front, recording its canonical name in the header
+    let cipher_name = canonical_cipher_name(cipher_name)?;
+    if options.strict_crypto {
+        check_strict_crypto(cipher_name, use_raw_key, options.kdf_iterations)?;
+    }
+    let ssh_recipients = resolve_ssh_recipients(&options.ssh_recipients, output_config)?;
     
     // Generate salt for key derivation
     let salt = generate_salt();
     
-    // Derive keys
-    let (mut encryption_key, mut mac_key) = derive_keys(&key_input, &salt);
-    
-    // Encrypt (returns: iv + encrypted_data + mac)
-    let encrypted = cipher.encrypt(plaintext.as_bytes(), &encryption_key, &mac_key)
-        .map_err(|e| {
-            // Zeroize keys on error
-            encryption_key.zeroize();
-            mac_key.zeroize();
-            format!("Encryption failed: {}", e)
-        })?;
+    // Derive keys: random keys only need HKDF, passphrases are stretched with PBKDF2
+    let spinner = Spinner::start(output_config, "Deriving keys...");
+    let timer = Timer::start("Key derivation");
+    let (kdf, (mut encryption_key, mut mac_key)) = if use_raw_key {
+        let mut raw_key = decode_raw_key(key_input)
+            .ok_or_else(|| "--raw-key requires a base64-encoded 32-byte key (as generated by envcrypt)".to_string())?;
+        let keys = derive_keys_from_raw_key(&raw_key, &salt);
+        raw_key.zeroize();
+        (Kdf::HkdfSha256, keys)
+    } else {
+        let keys = derive_keys_with_iterations(key_input, &salt, options.kdf_iterations);
+        (Kdf::Pbkdf2Sha256 { iterations: options.kdf_iterations }, keys)
+    };
+    timer.finish();
+    drop(spinner);
+    let _derived_key_locks = (
+        lock_secret(output_config, options.mlock, &encryption_key),
+        lock_secret(output_config, options.mlock, &mac_key),
+    );
+    tracing::trace!("KDF: {}", kdf);
     
+    // Escrow the file keys so the recovery key holder can decrypt without the user key
+    let mut header = Header::new(cipher_name, kdf, salt);
+    header.challenge = options.challenge;
+    header.signed = options.sign_key.is_some() && !options.detached_signature;
+    header.recovery = options.recovery_public_key.as_ref()
+        .map(|recipient| wrap_keys(recipient, &encryption_key, &mac_key));
+    if header.recovery.is_some() {
+        tracing::debug!("Wrapped file keys for the recovery recipient");
+    }
+    if let Err(e) = wrap_for_recipients(&mut header, options, &ssh_recipients, &encryption_key, &mac_key) {
+        encryption_key.zeroize();
+        mac_key.zeroize();
+        return Err(e);
+    }
+    header.metadata = Metadata {
+        created: SystemTime::now().duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs()),
+        tool_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+        comment: options.comment.clone(),
+        mode: source.mode,
+        modified: source.modified,
+    };
+    if let Some(context) = &options.aad {
+        tracing::debug!("Binding ciphertext to context: {}", context);
+    }
+
+    // Encrypt (header + iv + encrypted_data + mac)
+    let spinner = Spinner::start(output_config, "Encrypting...");
+    let timer = Timer::start("Encryption");
+    let output = seal(plaintext, &mut header, &encryption_key, &mac_key, options.aad.as_deref());
+    timer.finish();
+    drop(spinner);
+
     // Zeroize keys after use
     encryption_key.zeroize();
     mac_key.zeroize();
-    
-    // Store salt + encrypted data
-    // Format: base64(salt + iv + encrypted_data + mac)
-    let mut output = Vec::with_capacity(salt.len() + encrypted.len());
-    output.extend_from_slice(&salt);
-    output.extend_from_slice(&encrypted);
-    let final_output = base64::engine::general_purpose::STANDARD.encode(&output);
-    
-    // Write encrypted file
-    debug(output_config, "Writing encrypted data to file");
-    fs::write(encrypted_path, final_output)
-        .map_err(|e| format!("Error writing {}: {}", output_path, e))?;
-    
-    info(output_config, &format!("\nSuccessfully encrypted {} to {}", input_path, output_path));
+    let mut output = output.map_err(|e| format!("Encryption failed: {}", e))?;
+    if let (true, Some(sign_key)) = (header.signed, &options.sign_key) {
+        append_signature(sign_key, &mut output);
+        tracing::debug!("Embedded a signature by {}", public_key_text(&signing_public_key(sign_key)));
+    }
 
-    // Handle --prune flag: delete original file after successful encryption
-    if prune {
-        debug(output_config, &format!("Pruning original file: {}", input_path));
-        fs::remove_file(env_path)
-            .map_err(|e| format!("Error removing original file {}: {}", input_path, e))?;
-        verbose(output_config, &format!("Removed original file: {}", input_path));
+    tracing::trace!("Output format: {}", options.format);
+    Ok(encode_file(&output, options.format))
+}
+
+/// Encrypts the file keys to the GPG and SSH recipients, storing the results in `header`.
+fn wrap_for_recipients(
+    header: &mut Header,
+    options: &EncryptOptions,
+    ssh_recipients: &[age::ssh::Recipient],
+    encryption_key: &[u8],
+    mac_key: &[u8],
+) -> Result<(), String> {
+    if !options.gpg_recipients.is_empty() {
+        header.gpg = Some(wrap_keys_gpg(&options.gpg_recipients, encryption_key, mac_key)?);
     }
+    if !ssh_recipients.is_empty() {
+        header.ssh = Some(wrap_keys_ssh(ssh_recipients, encryption_key, mac_key)?);
+    }
+    Ok(())
+}
 
-    Ok(key_input)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::decrypt::{decrypt_reader_to_writer, DecryptOptions, AUTHENTICATION_FAILED};
+
+    const KEY: &str = "correct horse battery staple";
+
+    fn quiet() -> OutputConfig {
+        OutputConfig::new(true, false, 0)
+    }
+
+    fn encrypt(plaintext: &[u8], options: &EncryptOptions) -> Result<Vec<u8>, String> {
+        let mut encrypted = Vec::new();
+        encrypt_reader_to_writer("AES-256-GCM", Some(KEY), plaintext, &mut encrypted, &quiet(), options)?;
+        Ok(encrypted)
+    }
+
+    fn fast() -> EncryptOptions {
+        EncryptOptions { kdf_iterations: MIN_PBKDF2_ITERATIONS, ..EncryptOptions::default() }
+    }
+
+    #[test]
+    fn test_reader_to_writer_round_trip() {
+        let encrypted = encrypt(b"API_KEY=secret\nDEBUG=false\n", &fast()).unwrap();
+        assert!(looks_encrypted(&encrypted));
+
+        let mut plaintext = Vec::new();
+        decrypt_reader_to_writer(None, Some(KEY), encrypted.as_slice(), &mut plaintext, &quiet(), &DecryptOptions::default()).unwrap();
+        assert_eq!(plaintext, b"API_KEY=secret\nDEBUG=false\n");
+
+        let result = decrypt_reader_to_writer(None, Some("another long passphrase"), encrypted.as_slice(), &mut Vec::new(), &quiet(), &DecryptOptions::default());
+        assert_eq!(result.unwrap_err(), AUTHENTICATION_FAILED);
+    }
+
+    #[test]
+    fn test_reader_to_writer_refuses_encrypted_input() {
+        let encrypted = encrypt(b"API_KEY=secret\n", &fast()).unwrap();
+        let error = encrypt(&encrypted, &fast()).unwrap_err();
+        assert!(error.contains("already appears encrypted"), "{}", error);
+
+        let options = EncryptOptions { allow_double_encrypt: true, ..fast() };
+        assert!(encrypt(&encrypted, &options).is_ok());
+    }
+
+    #[test]
+    fn test_reader_to_writer_converts_newlines() {
+        let options = EncryptOptions { newline: Some(Newline::Lf), ..fast() };
+        let encrypted = encrypt(b"A=1\r\nB=2\r\n", &options).unwrap();
+
+        let mut plaintext = Vec::new();
+        decrypt_reader_to_writer(None, Some(KEY), encrypted.as_slice(), &mut plaintext, &quiet(), &DecryptOptions::default()).unwrap();
+        assert_eq!(plaintext, b"A=1\nB=2\n");
+
+        let mut plaintext = Vec::new();
+        let options = DecryptOptions { newline: Some(Newline::Crlf), ..DecryptOptions::default() };
+        decrypt_reader_to_writer(None, Some(KEY), encrypted.as_slice(), &mut plaintext, &quiet(), &options).unwrap();
+        assert_eq!(plaintext, b"A=1\r\nB=2\r\n");
+    }
 }
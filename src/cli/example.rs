@@ -0,0 +1,220 @@
+//! Generating `.env.example` files from encrypted files (`envcrypt example`).
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::cli::audit::note_read;
+use crate::cli::backup::{backup_file, report_planned_write, BackupConfig};
+use crate::cli::decrypt::{decrypt_file, DecryptOptions};
+use crate::cli::dotenv::{dotenv_lines, is_valid_key, parse_dotenv, quoted_len};
+use crate::cli::encoding::UTF8_BOM;
+use crate::cli::key_handling::{KeyFormat, KeyProvider};
+use crate::cli::memory::lock_secret;
+use crate::cli::newline::{convert_newlines, Newline};
+use crate::cli::output::{dry_run, info, success, OutputConfig};
+use crate::cli::prompt::confirm_overwrite;
+
+/// Default file [`example_env()`] writes.
+pub const EXAMPLE_FILE: &str = ".env.example";
+
+/// Options controlling how [`example_env()`] decrypts the file and writes the example.
+#[derive(Default)]
+pub struct ExampleOptions {
+    /// Overwrite an existing, different example file instead of asking.
+    pub force: bool,
+    /// Skip interactive prompts (error if no key is provided).
+    pub no_interaction: bool,
+    /// Encoding of the provided or prompted key.
+    pub key_format: KeyFormat,
+    /// Back up an existing example file before overwriting it.
+    pub backup: Option<BackupConfig>,
+    /// Validate everything and report the planned actions without writing anything.
+    pub dry_run: bool,
+    /// Lock the key, derived keys, and plaintext into RAM so they are not swapped to disk.
+    pub mlock: bool,
+    /// Line endings of the example (default: those of the encrypted file).
+    pub newline: Option<Newline>,
+    /// Context string the file was bound to when it was encrypted.
+    pub aad: Option<String>,
+    /// Where to get the key when none is passed as `key_arg` (default: interactive prompt).
+    pub key_provider: Option<Rc<dyn KeyProvider>>,
+    /// Value every variable gets in the example (default: empty).
+    pub placeholder: String,
+    /// Compare with the existing example file instead of writing it, failing if it is
+    /// out of date.
+    pub check: bool,
+}
+
+/// Decrypts an encrypted environment file in memory and writes an example of it (see
+/// [`example_template()`]) to `output_path`, or prints it to stdout if `output_path` is
+/// `None`.
+///
+/// An example file that is already up to date is left alone, so the command can run on
+/// every change. With `check`, nothing is written and an out-of-date example is an error.
+///
+/// # Errors
+///
+/// Returns an error string if the encrypted file cannot be read or decrypted or is not a
+/// valid dotenv file, the example file exists and may not be overwritten, is out of date
+/// with `check`, or cannot be written.
+///
+/// # Example
+///
+/// ```no_run
+/// use envcrypt::cli::{example_env, ExampleOptions, OutputConfig};
+///
+/// let output_config = OutputConfig::new(false, false, 0);
+/// example_env(Some("my-key"), ".env.encrypted", Some(".env.example"), &output_config, &ExampleOptions::default())?;
+/// # Ok::<(), String>(())
+/// ```
+pub fn example_env(
+    key_arg: Option<&str>,
+    input_path: &str,
+    output_path: Option<&str>,
+    output_config: &OutputConfig,
+    options: &ExampleOptions,
+) -> Result<(), String> {
+    let _span = tracing::info_span!("example", input = input_path).entered();
+    let decrypt_options = DecryptOptions {
+        no_interaction: options.no_interaction,
+        key_format: options.key_format,
+        mlock: options.mlock,
+        aad: options.aad.clone(),
+        key_provider: options.key_provider.clone(),
+        ..DecryptOptions::default()
+    };
+    let (_, plaintext) = decrypt_file(key_arg, input_path, output_config, &decrypt_options)?;
+    let _plaintext_lock = lock_secret(output_config, options.mlock, plaintext.as_bytes());
+    parse_dotenv(&plaintext)
+        .map_err(|e| format!("{} is not a valid dotenv file: {}", input_path, e))?;
+    let mut example = example_template(&plaintext, &options.placeholder);
+    if let Some(newline) = options.newline {
+        example = String::from_utf8_lossy(&convert_newlines(example.as_bytes(), newline)).into_owned();
+    }
+
+    let Some(output_path) = output_path else {
+        if options.dry_run {
+            dry_run(output_config, &format!("Would print the example of {}", input_path));
+        } else {
+            std::io::stdout()
+                .write_all(example.as_bytes())
+                .map_err(|e| format!("Error writing to stdout: {}", e))?;
+        }
+        return Ok(());
+    };
+
+    let path = Path::new(output_path);
+    let existing = match fs::read_to_string(path) {
+        Ok(existing) => {
+            note_read(output_path);
+            Some(existing)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(format!("Error reading {} file: {}", output_path, e)),
+    };
+    if existing.as_deref() == Some(example.as_str()) {
+        info(output_config, &format!("{} is up to date", output_path));
+        return Ok(());
+    }
+    if options.check {
+        return Err(match existing {
+            Some(_) => format!("{} is out of date with {}. Run `envcrypt example` to update it.", output_path, input_path),
+            None => format!("{} file not found. Run `envcrypt example` to create it.", output_path),
+        });
+    }
+    if !options.dry_run || options.no_interaction {
+        confirm_overwrite(path, options.force, options.no_interaction)?;
+    }
+    if options.dry_run {
+        report_planned_write(output_config, path, options.force, options.backup.as_ref());
+        return Ok(());
+    }
+    if let Some(backup) = &options.backup {
+        if let Some(backup_path) = backup_file(path, backup)? {
+            info(output_config, &format!("Backed up {} to {}", output_path, backup_path.display()));
+        }
+    }
+    fs::write(path, &example).map_err(|e| format!("Error writing {}: {}", output_path, e))?;
+
+    success(output_config, &format!("Wrote {} from {}", output_path, input_path));
+    Ok(())
+}
+
+/// Returns `text`, a dotenv file, with the value of every variable replaced by
+/// `placeholder`.
+///
+/// Comments, blank lines, `export` prefixes and comments after values are kept as they
+/// are, so the example documents the variables like the original does. Values spanning
+/// several lines are replaced as a whole, and a byte order mark is kept.
+///
+/// # Example
+///
+/// ```
+/// use envcrypt::cli::example_template;
+///
+/// let text = "# Database\nexport DB_URL=\"postgres://...\" # primary\nAPI_TOKEN=secret\n";
+/// assert_eq!(example_template(text, ""), "# Database\nexport DB_URL= # primary\nAPI_TOKEN=\n");
+/// assert_eq!(example_template("PORT=8080", "changeme"), "PORT=changeme");
+/// ```
+pub fn example_template(text: &str, placeholder: &str) -> String {
+    let mut example = String::with_capacity(text.len());
+    if text.starts_with(UTF8_BOM) {
+        example.push_str(UTF8_BOM);
+    }
+    for (_, line) in dotenv_lines(text) {
+        let content = line.trim_end_matches(['\r', '\n']);
+        let newline = &line[content.len()..];
+        let assignment = content.trim_start();
+        let assignment = assignment.strip_prefix("export ").unwrap_or(assignment);
+        match assignment.split_once('=') {
+            Some((key, value)) if !content.trim_start().starts_with('#') && is_valid_key(key.trim_end()) => {
+                let value = value.trim_start();
+                example.push_str(&content[..content.len() - value.len()]);
+                example.push_str(placeholder);
+                example.push_str(trailing_comment(value));
+            }
+            _ => example.push_str(content),
+        }
+        example.push_str(newline);
+    }
+    example
+}
+
+/// Returns the comment after the value `raw` (starting with the whitespace before `#`),
+/// or `""` if it has none.
+fn trailing_comment(raw: &str) -> &str {
+    let rest = if raw.starts_with(['\'', '"']) {
+        &raw[quoted_len(raw).unwrap_or(raw.len())..]
+    } else {
+        raw
+    };
+    let start = rest.find(" #").or_else(|| rest.find("\t#"));
+    start.map(|start| &rest[start..]).unwrap_or("")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_example_template_keeps_structure() {
+        let text = "# App\n\nAPP_KEY=base64:abc\r\n  export DEBUG=true # local only\nNAME='x # y' #quoted\nMSG=\"a \\\" # b\" # note\n";
+        assert_eq!(
+            example_template(text, ""),
+            "# App\n\nAPP_KEY=\r\n  export DEBUG= # local only\nNAME= #quoted\nMSG= # note\n"
+        );
+    }
+
+    #[test]
+    fn test_example_template_multiline_value() {
+        let text = "CERT=\"-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----\" # TLS\nPORT=8080\n";
+        assert_eq!(example_template(text, ""), "CERT= # TLS\nPORT=\n");
+    }
+
+    #[test]
+    fn test_example_template_placeholder() {
+        assert_eq!(example_template("A=1\nB = 2", "<set me>"), "A=<set me>\nB = <set me>");
+    }
+}
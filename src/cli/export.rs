@@ -0,0 +1,439 @@
+//! Handing decrypted variables to other systems (`envcrypt export`).
+
+use std::fmt::Write as _;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::rc::Rc;
+
+use clap::ValueEnum;
+use zeroize::Zeroizing;
+
+use crate::cli::audit::note_written;
+use crate::cli::decrypt::{decrypt_file, DecryptOptions};
+use crate::cli::dotenv::{is_valid_key, load_variables};
+use crate::cli::key_handling::{KeyFormat, KeyProvider};
+use crate::cli::memory::lock_secret;
+use crate::cli::output::{dry_run, success, OutputConfig};
+use crate::cli::paths::derive_output_path;
+use crate::cli::process::{check_program, run_program};
+
+/// Where [`export_env()`] sends the decrypted variables.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ExportTarget {
+    /// Docker secrets (`docker secret create`), for Swarm services and Compose
+    #[default]
+    DockerSecrets,
+    /// The environment of the following steps of a GitHub Actions job (`$GITHUB_ENV`),
+    /// with every value masked in the job log
+    GithubActions,
+}
+
+impl ExportTarget {
+    /// What one exported secret is called on this target, for messages.
+    fn noun(self, count: usize) -> &'static str {
+        match (self, count) {
+            (ExportTarget::DockerSecrets, 1) => "Docker secret",
+            (ExportTarget::DockerSecrets, _) => "Docker secrets",
+            (ExportTarget::GithubActions, 1) => "GitHub Actions variable",
+            (ExportTarget::GithubActions, _) => "GitHub Actions variables",
+        }
+    }
+}
+
+/// How [`export_env()`] prints the decrypted variables when there is no target.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// KEY=value lines, quoted where needed
+    #[default]
+    Dotenv,
+    /// `export KEY='value'` lines for eval "$(envcrypt export --format shell)"
+    Shell,
+    /// A JSON object
+    Json,
+    /// A YAML map
+    Yaml,
+}
+
+/// Options controlling how [`export_env()`] decrypts and exports a file.
+#[derive(Default)]
+pub struct ExportOptions {
+    /// Where to export the variables to (default: print them to stdout in `format`).
+    pub target: Option<ExportTarget>,
+    /// Format to print the variables in when there is no `target`.
+    pub format: ExportFormat,
+    /// Skip interactive prompts (error if no key is provided).
+    pub no_interaction: bool,
+    /// Encoding of the provided or prompted key.
+    pub key_format: KeyFormat,
+    /// Validate everything and report the planned actions without exporting anything.
+    pub dry_run: bool,
+    /// Lock the key, derived keys, and plaintext into RAM so they are not swapped to disk.
+    pub mlock: bool,
+    /// Context string the file was bound to when it was encrypted.
+    pub aad: Option<String>,
+    /// Where to get the key when none is passed as `key_arg` (default: interactive prompt).
+    pub key_provider: Option<Rc<dyn KeyProvider>>,
+    /// Export the whole file as a single secret instead of one secret per variable.
+    pub blob: bool,
+    /// Prepended to the variable names to form the secret (or variable) names.
+    pub prefix: Option<String>,
+    /// Name of the secret created with `blob` (default: the decrypted file name without
+    /// its leading dot, e.g. `env.production`).
+    pub name: Option<String>,
+    /// Take `$VAR` references in values literally instead of expanding them.
+    pub no_expand: bool,
+}
+
+/// Decrypts an encrypted environment file in memory and exports its variables.
+///
+/// References to other variables in the values, such as `${HOST}`, are expanded as
+/// [`crate::cli::parse_dotenv_expanded()`] describes, unless `no_expand` is set. With
+/// `blob`, the file is exported as it is.
+///
+/// Without a target, the variables are printed to stdout in `format` (see
+/// [`format_variables()`]). With [`ExportTarget::DockerSecrets`], each variable becomes a Docker secret named
+/// after it (with `prefix` prepended), or the whole file becomes one secret with `blob`.
+/// Values are passed to `docker secret create` on stdin, so no plaintext is written to
+/// disk or shows up in the process list. Docker secrets cannot be changed, so existing
+/// secrets with the same name are removed and created again; this fails while a service
+/// still uses them.
+///
+/// With [`ExportTarget::GithubActions`], each variable (or the whole file with `blob`) is
+/// appended to the file named by `$GITHUB_ENV`, which makes it available to the following
+/// steps of the job. Every value is first registered with an `::add-mask::` workflow
+/// command on stdout, so the runner hides it wherever it shows up in the log. Values are
+/// written with a random heredoc delimiter and may span several lines.
+///
+/// # Returns
+///
+/// Returns the number of variables printed or secrets exported (or that would be, with
+/// `dry_run`).
+///
+/// # Errors
+///
+/// Returns an error string if the file cannot be read or decrypted, its contents are not
+/// a valid dotenv file or use a required variable that is missing, stdout cannot be written, a `docker` command fails, or, for GitHub
+/// Actions, `$GITHUB_ENV` is not set or cannot be written or a name is not a valid
+/// variable name.
+///
+/// # Example
+///
+/// ```no_run
+/// use envcrypt::cli::{export_env, ExportFormat, ExportOptions, OutputConfig};
+///
+/// let output_config = OutputConfig::new(false, false, 0);
+/// let options = ExportOptions { format: ExportFormat::Json, ..Default::default() };
+/// export_env(Some("my-key"), ".env.production.encrypted", &output_config, &options)?;
+/// # Ok::<(), String>(())
+/// ```
+pub fn export_env(
+    key_arg: Option<&str>,
+    input_path: &str,
+    output_config: &OutputConfig,
+    options: &ExportOptions,
+) -> Result<usize, String> {
+    let _span = tracing::info_span!("export", input = input_path).entered();
+    let decrypt_options = DecryptOptions {
+        no_interaction: options.no_interaction,
+        key_format: options.key_format,
+        mlock: options.mlock,
+        aad: options.aad.clone(),
+        key_provider: options.key_provider.clone(),
+        ..DecryptOptions::default()
+    };
+    let (_, plaintext) = decrypt_file(key_arg, input_path, output_config, &decrypt_options)?;
+    let _plaintext_lock = lock_secret(output_config, options.mlock, plaintext.as_bytes());
+    let variables = load_variables(&plaintext, !options.no_expand)
+        .map_err(|e| format!("{} is not a valid dotenv file: {}", input_path, e))?;
+    tracing::debug!("Parsed {} variables", variables.len());
+
+    let Some(target) = options.target else {
+        if options.dry_run {
+            let format = options.format.to_possible_value().map(|v| v.get_name().to_string()).unwrap_or_default();
+            dry_run(output_config, &format!("Would print {} variables as {}", variables.len(), format));
+        } else {
+            std::io::stdout()
+                .write_all(format_variables(&variables, options.format).as_bytes())
+                .map_err(|e| format!("Error writing to stdout: {}", e))?;
+        }
+        return Ok(variables.len());
+    };
+
+    let secrets: Vec<(String, &[u8])> = if options.blob {
+        let name = options.name.clone().unwrap_or_else(|| default_blob_name(input_path));
+        vec![(name, plaintext.as_bytes())]
+    } else {
+        let prefix = options.prefix.as_deref().unwrap_or("");
+        variables.iter().map(|(key, value)| (format!("{}{}", prefix, key), value.as_bytes())).collect()
+    };
+
+    match target {
+        ExportTarget::GithubActions => {
+            if let Some((name, _)) = secrets.iter().find(|(name, _)| !is_valid_key(name)) {
+                let hint = if options.blob && options.name.is_none() { "; choose another one with --name" } else { "" };
+                return Err(format!("{} is not a valid variable name for GitHub Actions{}", name, hint));
+            }
+            if options.dry_run {
+                for (name, _) in &secrets {
+                    dry_run(output_config, &format!("Would set GitHub Actions variable {}", name));
+                }
+            } else {
+                export_github_actions(&secrets)?;
+            }
+        }
+        ExportTarget::DockerSecrets => {
+            for (name, value) in &secrets {
+                let exists = run_program("docker", &["secret", "inspect", name], None)?.success;
+                if options.dry_run {
+                    let action = if exists { "replace" } else { "create" };
+                    dry_run(output_config, &format!("Would {} Docker secret {}", action, name));
+                    continue;
+                }
+                if exists {
+                    check_program("docker", &["secret", "rm", name], None)?;
+                    tracing::debug!("Removed existing Docker secret {}", name);
+                }
+                check_program("docker", &["secret", "create", name, "-"], Some(value))?;
+                tracing::debug!("Created Docker secret {}", name);
+            }
+        }
+    }
+
+    if !options.dry_run {
+        success(output_config, &format!("Exported {} to {} {}", input_path, secrets.len(), target.noun(secrets.len())));
+    }
+    Ok(secrets.len())
+}
+
+/// Masks every value in the job log and appends the variables to `$GITHUB_ENV`.
+fn export_github_actions(secrets: &[(String, &[u8])]) -> Result<(), String> {
+    let env_path = std::env::var_os("GITHUB_ENV")
+        .filter(|path| !path.is_empty())
+        .ok_or("GITHUB_ENV is not set; --target github-actions only works in a GitHub Actions job")?;
+
+    let mut masks = Zeroizing::new(String::new());
+    let mut entries = Zeroizing::new(String::new());
+    for (name, value) in secrets {
+        let value = std::str::from_utf8(value).map_err(|_| format!("{} is not valid UTF-8", name))?;
+        masks.push_str(&github_mask_commands(value));
+        entries.push_str(&github_env_entry(name, value));
+    }
+
+    // Register the masks before the values can show up anywhere in the log
+    let mut stdout = std::io::stdout();
+    stdout.write_all(masks.as_bytes())
+        .and_then(|_| stdout.flush())
+        .map_err(|e| format!("Error writing to stdout: {}", e))?;
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&env_path)
+        .and_then(|mut file| file.write_all(entries.as_bytes()))
+        .map_err(|e| format!("Error writing to {}: {}", env_path.to_string_lossy(), e))?;
+    note_written(&env_path.to_string_lossy());
+    tracing::debug!("Appended {} variables to {}", secrets.len(), env_path.to_string_lossy());
+    Ok(())
+}
+
+/// `::add-mask::` workflow commands hiding `value` in the job log. Each line of a
+/// multi-line value is masked as well, as the runner may log the lines on their own.
+fn github_mask_commands(value: &str) -> Zeroizing<String> {
+    let mut commands = Zeroizing::new(String::new());
+    let mut masked = |text: &str| {
+        if !text.is_empty() {
+            let escaped = Zeroizing::new(text.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A"));
+            let _ = writeln!(commands, "::add-mask::{}", escaped.as_str());
+        }
+    };
+    masked(value);
+    if value.contains('\n') {
+        for line in value.lines() {
+            masked(line);
+        }
+    }
+    commands
+}
+
+/// A `$GITHUB_ENV` entry setting `name` to `value`, using a random heredoc delimiter
+/// that does not occur in the value, so no value can end the entry early.
+fn github_env_entry(name: &str, value: &str) -> Zeroizing<String> {
+    let delimiter = loop {
+        let delimiter = format!("ENVCRYPT_EOF_{:016x}", rand::random::<u64>());
+        if !value.contains(&delimiter) {
+            break delimiter;
+        }
+    };
+    Zeroizing::new(format!("{}<<{}\n{}\n{}\n", name, delimiter, value, delimiter))
+}
+
+/// Formats parsed variables as dotenv, shell, JSON or YAML text.
+///
+/// Values are quoted and escaped so that reading the output back yields exactly the
+/// same strings: single quotes for the shell, double quotes with backslash escapes for
+/// dotenv (only where needed) and for JSON and YAML (always, so values such as `true`
+/// or `8080` stay strings).
+///
+/// # Example
+///
+/// ```
+/// use envcrypt::cli::{format_variables, ExportFormat};
+/// use zeroize::Zeroizing;
+///
+/// let variables = vec![("GREETING".to_string(), Zeroizing::new("it's here".to_string()))];
+/// assert_eq!(format_variables(&variables, ExportFormat::Shell).as_str(), "export GREETING='it'\\''s here'\n");
+/// assert_eq!(format_variables(&variables, ExportFormat::Json).as_str(), "{\n  \"GREETING\": \"it's here\"\n}\n");
+/// ```
+pub fn format_variables(variables: &[(String, Zeroizing<String>)], format: ExportFormat) -> Zeroizing<String> {
+    let mut output = Zeroizing::new(String::new());
+    match format {
+        ExportFormat::Dotenv => {
+            for (key, value) in variables {
+                if value.chars().all(is_plain_char) {
+                    let _ = writeln!(output, "{}={}", key, value.as_str());
+                } else {
+                    let _ = writeln!(output, "{}=\"{}\"", key, escape_double_quoted(value, true).as_str());
+                }
+            }
+        }
+        ExportFormat::Shell => {
+            for (key, value) in variables {
+                if !value.is_empty() && value.chars().all(is_plain_char) {
+                    let _ = writeln!(output, "export {}={}", key, value.as_str());
+                } else {
+                    let _ = writeln!(output, "export {}='{}'", key, value.replace('\'', "'\\''"));
+                }
+            }
+        }
+        ExportFormat::Json => {
+            if variables.is_empty() {
+                output.push_str("{}\n");
+            } else {
+                output.push_str("{\n");
+                for (index, (key, value)) in variables.iter().enumerate() {
+                    let separator = if index + 1 < variables.len() { "," } else { "" };
+                    let _ = writeln!(output, "  \"{}\": \"{}\"{}", key, escape_double_quoted(value, false).as_str(), separator);
+                }
+                output.push_str("}\n");
+            }
+        }
+        ExportFormat::Yaml => {
+            if variables.is_empty() {
+                output.push_str("{}\n");
+            }
+            for (key, value) in variables {
+                // Keys that YAML 1.1 reads as booleans or null must be quoted to stay strings
+                const RESERVED: [&str; 10] = ["y", "n", "yes", "no", "on", "off", "true", "false", "null", "~"];
+                if RESERVED.iter().any(|word| key.eq_ignore_ascii_case(word)) {
+                    let _ = writeln!(output, "\"{}\": \"{}\"", key, escape_double_quoted(value, false).as_str());
+                } else {
+                    let _ = writeln!(output, "{}: \"{}\"", key, escape_double_quoted(value, false).as_str());
+                }
+            }
+        }
+    }
+    output
+}
+
+/// Characters that can appear in unquoted dotenv and shell values.
+fn is_plain_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_-./:@%+,=".contains(c)
+}
+
+/// Escapes `value` for a double-quoted dotenv, JSON or YAML string. `$` is escaped as
+/// well for dotenv, where it would otherwise start an interpolation.
+fn escape_double_quoted(value: &str, dotenv: bool) -> Zeroizing<String> {
+    let mut escaped = Zeroizing::new(String::with_capacity(value.len()));
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '$' if dotenv => escaped.push_str("\\$"),
+            c if c.is_control() && !dotenv => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Name of the secret holding a whole file: the decrypted file name without its leading
+/// dot (`.env.production.encrypted` becomes `env.production`).
+fn default_blob_name(input_path: &str) -> String {
+    let output = derive_output_path(input_path, false);
+    let file_name = Path::new(&output).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or(output);
+    file_name.trim_start_matches('.').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::dotenv::parse_dotenv;
+
+    fn variables(pairs: &[(&str, &str)]) -> Vec<(String, Zeroizing<String>)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), Zeroizing::new(v.to_string()))).collect()
+    }
+
+    #[test]
+    fn test_format_variables() {
+        let vars = variables(&[("PLAIN", "postgres://db:5432/app"), ("QUOTED", "it's \"$HOME\"\n"), ("EMPTY", ""), ("ON", "true")]);
+
+        assert_eq!(
+            format_variables(&vars, ExportFormat::Dotenv).as_str(),
+            "PLAIN=postgres://db:5432/app\nQUOTED=\"it's \\\"\\$HOME\\\"\\n\"\nEMPTY=\nON=true\n"
+        );
+        assert_eq!(
+            format_variables(&vars, ExportFormat::Shell).as_str(),
+            "export PLAIN=postgres://db:5432/app\nexport QUOTED='it'\\''s \"$HOME\"\n'\nexport EMPTY=''\nexport ON=true\n"
+        );
+        assert_eq!(
+            format_variables(&vars, ExportFormat::Json).as_str(),
+            "{\n  \"PLAIN\": \"postgres://db:5432/app\",\n  \"QUOTED\": \"it's \\\"$HOME\\\"\\n\",\n  \"EMPTY\": \"\",\n  \"ON\": \"true\"\n}\n"
+        );
+        assert_eq!(
+            format_variables(&vars, ExportFormat::Yaml).as_str(),
+            "PLAIN: \"postgres://db:5432/app\"\nQUOTED: \"it's \\\"$HOME\\\"\\n\"\nEMPTY: \"\"\n\"ON\": \"true\"\n"
+        );
+        assert_eq!(format_variables(&[], ExportFormat::Json).as_str(), "{}\n");
+    }
+
+    #[test]
+    fn test_formatted_dotenv_parses_back() {
+        let vars = variables(&[("A", "tab\there # not a comment"), ("B", "back\\slash 'quote'"), ("C", "$NOT_EXPANDED"),
+            ("PEM", "-----BEGIN KEY-----\nMIIB\r\n\"x\"\n-----END KEY-----\n")]);
+        let parsed = parse_dotenv(&format_variables(&vars, ExportFormat::Dotenv)).unwrap();
+        assert_eq!(parsed, vars);
+    }
+
+    #[test]
+    fn test_github_mask_commands() {
+        assert_eq!(github_mask_commands("s3cret").as_str(), "::add-mask::s3cret\n");
+        assert_eq!(
+            github_mask_commands("100%\nline two\n").as_str(),
+            "::add-mask::100%25%0Aline two%0A\n::add-mask::100%25\n::add-mask::line two\n"
+        );
+        assert_eq!(github_mask_commands("").as_str(), "");
+    }
+
+    #[test]
+    fn test_github_env_entry() {
+        let entry = github_env_entry("CERT", "line one\nline two");
+        let lines: Vec<&str> = entry.lines().collect();
+        assert_eq!(lines.len(), 4);
+        let delimiter = lines[0].strip_prefix("CERT<<").unwrap();
+        assert!(delimiter.starts_with("ENVCRYPT_EOF_"));
+        assert_eq!(&lines[1..], ["line one", "line two", delimiter]);
+    }
+
+    #[test]
+    fn test_default_blob_name() {
+        assert_eq!(default_blob_name(".env.encrypted"), "env");
+        assert_eq!(default_blob_name("config/.env.production.encrypted"), "env.production");
+        assert_eq!(default_blob_name("secrets.env.encrypted"), "secrets.env");
+    }
+}
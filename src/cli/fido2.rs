@@ -0,0 +1,143 @@
+//! Deriving file keys from a FIDO2 hardware token (YubiKey, SoloKey, Nitrokey, ...) with
+//! the `hmac-secret` extension, through the libfido2 command-line tools.
+//!
+//! `envcrypt key fido2-enroll` creates a credential on the token and saves its ID as
+//! `fido2_credential` in `envcrypt.toml`. The `fido2` key provider then has the token
+//! compute the HMAC of a random 32-byte challenge, which requires a touch, and uses the
+//! result as the raw key of the file. The challenge is stored in the file header so
+//! decryption can ask the token for the same HMAC; every encryption picks a new one.
+//! The key never exists outside the token until it is touched, so a file encrypted this
+//! way cannot be decrypted without physical access to the token.
+
+use base64::Engine;
+use rand::RngCore;
+use zeroize::Zeroizing;
+
+use crate::format::CHALLENGE_LEN;
+
+use super::key_handling::{KeyProvider, KeyRequest, UserKey};
+use super::output::{OutputConfig, Spinner};
+use super::process::check_program;
+
+/// Relying party the credential is created for. Credentials are scoped to it, so tokens
+/// can hold credentials for other sites alongside.
+const RELYING_PARTY: &str = "envcrypt";
+
+/// Derives the key from a FIDO2 hardware token (`fido2` key provider).
+///
+/// When encrypting, a fresh challenge is generated and returned with the key; when
+/// decrypting, the challenge comes from the file header.
+pub struct Fido2KeyProvider {
+    /// Base64 credential ID, from `fido2_credential` in `envcrypt.toml`.
+    pub credential: Option<String>,
+    /// Device path, from `fido2_device` in `envcrypt.toml` (default: the first token found).
+    pub device: Option<String>,
+    /// Output configuration for the touch prompt.
+    pub output_config: OutputConfig,
+}
+
+impl KeyProvider for Fido2KeyProvider {
+    fn name(&self) -> &str {
+        "fido2"
+    }
+
+    fn provide(&self, request: &KeyRequest) -> Result<UserKey, String> {
+        let credential = self.credential.as_deref()
+            .ok_or_else(|| "The fido2 key provider requires fido2_credential in envcrypt.toml. Create one with `envcrypt key fido2-enroll`.".to_string())?;
+        let challenge = match request.challenge {
+            Some(challenge) => challenge,
+            None if request.is_encrypt => {
+                let mut challenge = [0u8; CHALLENGE_LEN];
+                rand::thread_rng().fill_bytes(&mut challenge);
+                challenge
+            }
+            None => return Err("This file has no hardware token challenge in its header; it was not encrypted with the fido2 key provider".to_string()),
+        };
+        let device = match &self.device {
+            Some(device) => device.clone(),
+            None => find_device()?,
+        };
+
+        let engine = &base64::engine::general_purpose::STANDARD;
+        let input = Zeroizing::new(format!(
+            "{}\n{}\n{}\n{}\n",
+            engine.encode(client_data_hash()),
+            RELYING_PARTY,
+            credential,
+            engine.encode(challenge),
+        ));
+        let spinner = Spinner::start(&self.output_config, "Touch your security key...");
+        let output = check_program("fido2-assert", &["-G", "-h", &device], Some(input.as_bytes()));
+        drop(spinner);
+        let output = output?;
+
+        // The HMAC secret is the last line of the assertion
+        let secret = std::str::from_utf8(&output).ok()
+            .and_then(|output| output.lines().next_back())
+            .and_then(|line| engine.decode(line.trim()).ok())
+            .map(Zeroizing::new)
+            .filter(|secret| secret.len() == CHALLENGE_LEN)
+            .ok_or_else(|| "fido2-assert did not return an HMAC secret. Was the credential created with `envcrypt key fido2-enroll`?".to_string())?;
+        tracing::debug!("Derived the key from the hardware token {}", device);
+        Ok(UserKey {
+            value: Zeroizing::new(engine.encode(&*secret)),
+            generated: false,
+            challenge: Some(challenge),
+        })
+    }
+}
+
+/// Creates a credential with the `hmac-secret` extension on the token at `device` (default:
+/// the first token found), returning its base64 ID.
+///
+/// # Errors
+///
+/// Returns an error string if no token is found, the libfido2 tools are not installed, or
+/// the token refuses to create the credential.
+pub(crate) fn enroll(device: Option<&str>, output_config: &OutputConfig) -> Result<String, String> {
+    let device = match device {
+        Some(device) => device.to_string(),
+        None => find_device()?,
+    };
+    let engine = &base64::engine::general_purpose::STANDARD;
+    let mut user_id = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut user_id);
+    let input = format!(
+        "{}\n{}\n{}\n{}\n",
+        engine.encode(client_data_hash()),
+        RELYING_PARTY,
+        RELYING_PARTY,
+        engine.encode(user_id),
+    );
+    let spinner = Spinner::start(output_config, "Touch your security key...");
+    let output = check_program("fido2-cred", &["-M", "-h", &device], Some(input.as_bytes()));
+    drop(spinner);
+    let output = output?;
+
+    // The credential ID follows the client data hash, relying party, format and
+    // authenticator data
+    String::from_utf8_lossy(&output)
+        .lines()
+        .nth(4)
+        .map(|line| line.trim().to_string())
+        .filter(|id| engine.decode(id).is_ok_and(|id| !id.is_empty()))
+        .ok_or_else(|| "fido2-cred did not return a credential ID".to_string())
+}
+
+/// Returns the path of the first token listed by `fido2-token -L`.
+fn find_device() -> Result<String, String> {
+    let devices = check_program("fido2-token", &["-L"], None)?;
+    // Lines look like "/dev/hidraw0: vendor=0x1050, product=0x0407 (Yubico YubiKey)"
+    String::from_utf8_lossy(&devices)
+        .lines()
+        .find_map(|line| line.split_once(": ").map(|(path, _)| path.to_string()))
+        .ok_or_else(|| "No FIDO2 security key found. Insert one or set fido2_device in envcrypt.toml.".to_string())
+}
+
+/// Random client data hash. Only the HMAC secret is used, so the assertion it signs is
+/// never verified.
+fn client_data_hash() -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut hash);
+    hash
+}
@@ -0,0 +1,83 @@
+//! Wrapping file keys for OpenPGP recipients with the `gpg` CLI.
+//!
+//! `encrypt --recipient-gpg` encrypts the file keys (the derived encryption and MAC keys)
+//! to public keys from the local GnuPG keyring and stores the resulting OpenPGP message
+//! in the file header. `decrypt --gpg` hands the message back to `gpg --decrypt`, so the
+//! secret key stays in the GPG agent (or on the smartcard behind it) and no symmetric key
+//! has to be distributed.
+//!
+//! This shells out to `gpg` rather than implementing OpenPGP with a library such as
+//! sequoia-openpgp: only `gpg` can use the user's existing keyring, agent, pinentry and
+//! smartcards, which is the point of the feature, and a native implementation would add
+//! a large dependency tree while still needing the agent for secret keys.
+
+use zeroize::Zeroizing;
+
+use super::process::run_program;
+
+/// Length of each wrapped file key (encryption key and MAC key) in bytes.
+const FILE_KEY_LEN: usize = 32;
+
+/// Largest OpenPGP message stored in a header, leaving room for the other fields within
+/// the 64 KiB header limit.
+const MAX_MESSAGE_LEN: usize = 32 * 1024;
+
+/// File keys `(encryption_key, mac_key)`, zeroized when dropped.
+pub(crate) type FileKeys = (Zeroizing<Vec<u8>>, Zeroizing<Vec<u8>>);
+
+/// Encrypts the file keys to `recipients` (key IDs, fingerprints or user IDs), returning
+/// a binary OpenPGP message that any one of them can decrypt.
+///
+/// # Errors
+///
+/// Returns an error string if a recipient looks like an option, `gpg` is not installed,
+/// a recipient's public key is not in the keyring, or the message is too large.
+pub(crate) fn wrap_keys_gpg(recipients: &[String], encryption_key: &[u8], mac_key: &[u8]) -> Result<Vec<u8>, String> {
+    if let Some(recipient) = recipients.iter().find(|recipient| recipient.is_empty() || recipient.starts_with('-')) {
+        return Err(format!("Invalid GPG recipient '{}'", recipient));
+    }
+
+    // Recipients are named explicitly, so skip the web-of-trust check that fails in batch
+    // mode for keys nobody has certified
+    let mut args = vec!["--encrypt", "--batch", "--quiet", "--trust-model", "always"];
+    for recipient in recipients {
+        args.extend(["--recipient", recipient.as_str()]);
+    }
+
+    let mut file_keys = Zeroizing::new(Vec::with_capacity(2 * FILE_KEY_LEN));
+    file_keys.extend_from_slice(encryption_key);
+    file_keys.extend_from_slice(mac_key);
+    let output = run_program("gpg", &args, Some(&file_keys))?;
+    if !output.success {
+        return Err(format!("Cannot encrypt the file keys to the GPG recipients: {}", output.stderr));
+    }
+    if output.stdout.len() > MAX_MESSAGE_LEN {
+        return Err(format!("Too many GPG recipients: the wrapped keys exceed {} bytes", MAX_MESSAGE_LEN));
+    }
+    tracing::debug!("Wrapped file keys for {} GPG recipient(s)", recipients.len());
+    Ok(output.stdout.to_vec())
+}
+
+/// Decrypts file keys wrapped by [`wrap_keys_gpg()`] with `gpg --decrypt`, which asks the
+/// GPG agent for the secret key.
+///
+/// # Returns
+///
+/// Returns `(encryption_key, mac_key)`, zeroized when dropped.
+///
+/// # Errors
+///
+/// Returns an error string if `gpg` is not installed, none of the recipients' secret keys
+/// is available, or the message does not contain file keys.
+pub(crate) fn unwrap_keys_gpg(message: &[u8]) -> Result<FileKeys, String> {
+    let output = run_program("gpg", &["--decrypt", "--quiet"], Some(message))?;
+    if !output.success {
+        return Err(format!("Cannot decrypt the file keys with GPG: {}", output.stderr));
+    }
+    if output.stdout.len() != 2 * FILE_KEY_LEN {
+        return Err("The GPG message in the file header does not contain file keys".to_string());
+    }
+    // Copy the keys out of the output buffer, which is zeroized when dropped
+    let (encryption_key, mac_key) = output.stdout.split_at(FILE_KEY_LEN);
+    Ok((Zeroizing::new(encryption_key.to_vec()), Zeroizing::new(mac_key.to_vec())))
+}
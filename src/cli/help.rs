@@ -0,0 +1,322 @@
+//! Extended help: examples, `--help-long` and man pages (`envcrypt man`).
+//!
+//! The examples of each command live in [`EXAMPLES`] and are added to the clap command
+//! as its long help, so `envcrypt <command> --help`, `--help-long` and the EXAMPLES
+//! section of the man pages are all generated from the same definitions as the flags.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::{Command, CommandFactory};
+use clap_mangen::roff::{bold, roman, Roff};
+use clap_mangen::Man;
+
+use super::Cli;
+
+/// Examples per command, keyed by the subcommand path (`""` for `envcrypt` itself):
+/// a description and the command line it describes.
+const EXAMPLES: &[(&str, &[(&str, &str)])] = &[
+    ("", &[
+        ("Encrypt .env, choosing or generating a key interactively", "envcrypt encrypt"),
+        ("Decrypt .env.production.encrypted to .env.production", "envcrypt decrypt --env production"),
+        ("Fail in CI if .env has drifted from .env.encrypted", "envcrypt decrypt --check --key-provider env"),
+        ("Print the help of every command", "envcrypt --help-long"),
+    ]),
+    ("encrypt", &[
+        ("Encrypt .env to .env.encrypted, choosing or generating a key interactively", "envcrypt encrypt"),
+        ("Encrypt .env.production with a key from ENVCRYPT_KEY", "envcrypt encrypt --env production --key-provider env"),
+        ("Encrypt with AES-256-GCM and bind the file to a context string", "envcrypt encrypt --cipher AES-256-GCM --aad production:payments"),
+        ("Encrypt in CI, generating a key without asking", "envcrypt encrypt --no-interaction"),
+        ("Show a generated key as a QR code to scan it with a phone", "envcrypt encrypt --key-qr"),
+        ("Save a generated key to a file outside the repository", "envcrypt encrypt --no-interaction --key-out ~/.config/envcrypt/app.key"),
+        ("Also let two team members decrypt with their GPG keys", "envcrypt encrypt --recipient-gpg alice@example.com --recipient-gpg 0x1A2B3C4D5E6F7A8B"),
+        ("Also let teammates decrypt with their SSH keys", "envcrypt encrypt --recipient-ssh ~/.ssh/id_ed25519.pub --recipient-ssh github:octocat"),
+        ("Encrypt a legacy Latin-1 file, converting it to UTF-8", "envcrypt encrypt .env.legacy --encoding latin1"),
+        ("Sign the file in the release pipeline with a key from a CI secret", "envcrypt encrypt --env production --key-provider env --sign-key \"$SIGNING_KEY_FILE\""),
+    ]),
+    ("decrypt", &[
+        ("Decrypt .env.encrypted to .env", "envcrypt decrypt --key \"$ENVCRYPT_KEY\""),
+        ("Decrypt .env.staging.encrypted to .env.staging, overwriting it after a backup", "envcrypt decrypt --env staging --force --backup"),
+        ("Check that .env matches .env.encrypted without writing anything", "envcrypt decrypt --check"),
+        ("Decrypt with Windows line endings, whatever the file was encrypted with", "envcrypt decrypt --newline crlf"),
+        ("Decrypt with the recovery private key after losing the key", "envcrypt decrypt --recovery-key <PRIVATE_KEY>"),
+        ("Decrypt a file encrypted with --recipient-gpg using your GPG key", "envcrypt decrypt --gpg"),
+        ("Decrypt a file encrypted with --recipient-ssh using ~/.ssh/id_ed25519", "envcrypt decrypt --ssh"),
+    ]),
+    ("recrypt", &[
+        ("Switch a file to ChaCha20-Poly1305 in place", "envcrypt recrypt --to-cipher ChaCha20-Poly1305"),
+        ("Raise the PBKDF2 iteration count of a file", "envcrypt recrypt .env.production.encrypted --kdf-iterations 600000"),
+    ]),
+    ("migrate", &[
+        ("Rewrite every legacy file under config/ in the current format", "envcrypt migrate config --recursive"),
+        ("Migrate the current directory in CI, where no one can confirm", "envcrypt migrate --yes --key \"$KEY\""),
+    ]),
+    ("rotate", &[
+        ("Rotate the key of every encrypted file in the repository", "envcrypt rotate --recursive --old-key \"$OLD_KEY\" --new-key \"$NEW_KEY\""),
+        ("Check which files the old key opens, writing the report to a file", "envcrypt rotate --dir services --recursive --dry-run --report rotation.json"),
+    ]),
+    ("export", &[
+        ("Load the variables into the current shell", "eval \"$(envcrypt export --format shell)\""),
+        ("Print the variables as JSON", "envcrypt export .env.production.encrypted --format json"),
+        ("Create a Docker secret per variable, prefixed with app_", "envcrypt export --target docker-secrets --prefix app_"),
+        ("Add the variables to the following steps of a GitHub Actions job", "envcrypt export --target github-actions --key-provider env"),
+    ]),
+    ("render", &[
+        ("Render an nginx config with the production variables", "envcrypt render nginx.conf.tpl --input .env.production.encrypted -o app.conf"),
+    ]),
+    ("peek", &[
+        ("Check that .env.production.encrypted holds the production values without showing them", "envcrypt peek --env production"),
+    ]),
+    ("example", &[
+        ("Write .env.example with the variables of .env.encrypted and empty values", "envcrypt example"),
+        ("Fail in CI if .env.example is out of date", "envcrypt example --check --key-provider env"),
+    ]),
+    ("check", &[
+        ("Check the variables against a schema", "envcrypt check --schema env.schema.toml"),
+        ("Check that every variable of .env.example is set", "envcrypt check .env.production.encrypted --against .env.example"),
+    ]),
+    ("lint", &[
+        ("Lint the plaintext .env", "envcrypt lint"),
+        ("Lint an encrypted file", "envcrypt lint .env.encrypted --key-provider env"),
+    ]),
+    ("merge", &[
+        ("Layer production overrides over a shared base", "envcrypt merge base.env.encrypted production.env.encrypted -o .env.production.encrypted"),
+    ]),
+    ("import", &[
+        ("Encrypt a JSON map of variables exported from another system", "envcrypt import secrets.json --output .env.production.encrypted"),
+    ]),
+    ("sync aws-secretsmanager", &[
+        ("Make a Secrets Manager secret match the encrypted file", "envcrypt sync aws-secretsmanager --secret-id app/prod --push"),
+        ("List what pulling the secret would change", "envcrypt sync aws-secretsmanager --secret-id app/prod --pull --dry-run"),
+    ]),
+    ("sync aws-ssm", &[
+        ("Make the parameters under /app/prod/ match the encrypted file", "envcrypt sync aws-ssm --path /app/prod/ --push --region eu-west-1"),
+    ]),
+    ("inspect", &[
+        ("Show the cipher, KDF and metadata of a file without the key", "envcrypt inspect .env.production.encrypted"),
+    ]),
+    ("verify", &[
+        ("Check that .env.production.encrypted was signed by a key in signer_public_keys", "envcrypt verify .env.production.encrypted"),
+        ("Check a detached signature against a given public key", "envcrypt verify --signer-pub base64:<PUBLIC_KEY> --signature release.sig"),
+    ]),
+    ("prune", &[
+        ("Encrypt .env and keep the original in the trash for a week", "envcrypt encrypt --prune=backup"),
+        ("Delete the originals whose grace period is over", "envcrypt prune --gc"),
+    ]),
+    ("calibrate", &[
+        ("Save the iteration count that takes 500ms on this machine", "envcrypt calibrate --target-ms 500"),
+    ]),
+    ("self-update", &[
+        ("Check for a new release without installing it", "envcrypt self-update --dry-run"),
+        ("Update to the newest pre-release", "envcrypt self-update --channel prerelease"),
+    ]),
+    #[cfg(feature = "tui")]
+    ("tui", &[
+        ("Browse the env files in the current directory and its subdirectories", "envcrypt tui --recursive"),
+        ("Use the key from ENVCRYPT_KEY for every file", "envcrypt tui --key-provider env"),
+    ]),
+    ("man", &[
+        ("Install the man pages for the current user", "envcrypt man --output ~/.local/share/man/man1"),
+    ]),
+    ("completions", &[
+        ("Enable completion in bash, generating the script anew in each shell", "echo 'source <(envcrypt completions bash)' >> ~/.bashrc"),
+        ("Enable completion in fish", "echo 'envcrypt completions fish | source' >> ~/.config/fish/config.fish"),
+    ]),
+    ("key split", &[
+        ("Split a key so that any 3 of 5 holders can recover it", "envcrypt key split --shares 5 --threshold 3"),
+    ]),
+    ("key recover", &[
+        ("Recover a key, prompting for the shares", "envcrypt key recover"),
+    ]),
+    ("key add", &[
+        ("Generate a key for staging and store it in the keyring", "envcrypt key add staging --generate"),
+        ("Store an existing key for production, prompting for it", "envcrypt key add production"),
+    ]),
+    ("key remove", &[
+        ("Remove the key of staging from the keyring", "envcrypt key remove staging"),
+    ]),
+    ("key list", &[
+        ("List the environments in the keyring", "envcrypt key list"),
+    ]),
+    ("key recovery-keygen", &[
+        ("Generate a recovery key pair and save the public key to envcrypt.toml", "envcrypt key recovery-keygen"),
+    ]),
+    ("key sign-keygen", &[
+        ("Generate a signing key pair, keeping the private key out of the repository", "envcrypt key sign-keygen --out ~/.config/envcrypt/signing.key"),
+    ]),
+    ("key tpm-seal", &[
+        ("Seal the production key to this server's TPM and decrypt with it", "envcrypt key tpm-seal --key \"$KEY\" && envcrypt decrypt --env production --key-provider tpm"),
+    ]),
+    #[cfg(feature = "fido2")]
+    ("key fido2-enroll", &[
+        ("Create a credential on the security key and use it for every file", "envcrypt key fido2-enroll && envcrypt encrypt --key-provider fido2"),
+    ]),
+];
+
+/// The clap command of the CLI with the examples of each command as its long help, the
+/// command aliases, and the environment variables of the global options.
+pub(super) fn command() -> Command {
+    let mut command = super::with_aliases(super::with_env_vars(Cli::command()));
+    for (path, examples) in EXAMPLES {
+        let path: Vec<&str> = path.split_whitespace().collect();
+        command = add_examples(command, &path, examples);
+    }
+    command
+}
+
+fn add_examples(command: Command, path: &[&str], examples: &[(&str, &str)]) -> Command {
+    match path.split_first() {
+        Some((name, rest)) => command.mut_subcommand(*name, |sub| add_examples(sub, rest, examples)),
+        None => {
+            let header = command.get_styles().get_header();
+            let mut text = format!("{}Examples:{}\n", header.render(), header.render_reset());
+            for (description, line) in examples {
+                text.push_str(&format!("  # {}\n  {}\n\n", description, line));
+            }
+            command.after_long_help(text.trim_end().to_string())
+        }
+    }
+}
+
+/// Renders `--help-long`: the long help of the command named by the subcommands in
+/// `args` followed by that of each of its visible subcommands.
+pub(super) fn help_long(args: &[String]) -> String {
+    let mut command = command();
+    command.build();
+
+    let mut target = &command;
+    for arg in args.iter().skip(1).take_while(|arg| *arg != "--") {
+        if let Some(sub) = target.find_subcommand(arg) {
+            target = sub;
+        }
+    }
+
+    let mut help = String::new();
+    render_help_long(&mut target.clone(), &mut help);
+    help
+}
+
+fn render_help_long(command: &mut Command, help: &mut String) {
+    if !help.is_empty() {
+        let name = command.get_bin_name().unwrap_or_else(|| command.get_name()).to_string();
+        help.push_str(&format!("\n{}\n{}\n\n", name, "=".repeat(name.len())));
+        // The global options were listed with the first command
+        *command = std::mem::take(command).mut_args(|arg| if arg.is_global_set() { arg.hide(true) } else { arg });
+    }
+    help.push_str(&command.render_long_help().to_string());
+    for sub in command.get_subcommands_mut() {
+        if !sub.is_hide_set() && sub.get_name() != "help" {
+            render_help_long(sub, help);
+        }
+    }
+}
+
+/// Renders the man page of `envcrypt` (section 1).
+///
+/// # Errors
+///
+/// Returns an error string if the page cannot be rendered.
+pub(super) fn man_page() -> Result<Vec<u8>, String> {
+    let mut command = command().disable_help_subcommand(true);
+    command.build();
+    let mut page = Vec::new();
+    render_man_page(&command, &mut page).map_err(|e| format!("Error rendering the man page: {}", e))?;
+    Ok(page)
+}
+
+/// Writes the man page of `envcrypt` and one for each visible subcommand (e.g.
+/// `envcrypt-key-split.1`) to `dir`, creating it if needed.
+///
+/// # Returns
+///
+/// Returns the paths of the written pages.
+///
+/// # Errors
+///
+/// Returns an error string if the directory or a page cannot be written.
+pub(super) fn write_man_pages(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Error creating {}: {}", dir.display(), e))?;
+    let mut command = command().disable_help_subcommand(true);
+    command.build();
+    let mut paths = Vec::new();
+    write_man_page(&command, dir, &mut paths)?;
+    Ok(paths)
+}
+
+fn write_man_page(command: &Command, dir: &Path, paths: &mut Vec<PathBuf>) -> Result<(), String> {
+    let path = dir.join(Man::new(command.clone()).get_filename());
+    let mut page = Vec::new();
+    render_man_page(command, &mut page)
+        .and_then(|()| fs::write(&path, page))
+        .map_err(|e| format!("Error writing {}: {}", path.display(), e))?;
+    paths.push(path);
+
+    for sub in command.get_subcommands().filter(|sub| !sub.is_hide_set()) {
+        write_man_page(sub, dir, paths)?;
+    }
+    Ok(())
+}
+
+/// Renders the sections `clap_mangen` would, but with the examples as an EXAMPLES
+/// section of their own instead of running them together as text.
+fn render_man_page(command: &Command, page: &mut Vec<u8>) -> std::io::Result<()> {
+    let man = Man::new(command.clone());
+    man.render_title(page)?;
+    man.render_name_section(page)?;
+    man.render_synopsis_section(page)?;
+    man.render_description_section(page)?;
+    if command.get_arguments().any(|arg| !arg.is_hide_set()) {
+        man.render_options_section(page)?;
+    }
+    if command.get_subcommands().any(|sub| !sub.is_hide_set()) {
+        man.render_subcommands_section(page)?;
+    }
+
+    if let Some((_, examples)) = EXAMPLES.iter().find(|(path, _)| *path == subcommand_path(command)) {
+        let mut roff = Roff::new();
+        roff.control("SH", ["EXAMPLES"]);
+        for (description, line) in *examples {
+            roff.control("TP", []).text([roman(*description)]).text([bold(*line)]);
+        }
+        roff.to_writer(page)?;
+    }
+
+    if command.get_version().is_some() {
+        man.render_version_section(page)?;
+    }
+    Ok(())
+}
+
+/// Subcommand path of a built command as used in [`EXAMPLES`], e.g. `key split`.
+fn subcommand_path(command: &Command) -> &str {
+    let bin_name = command.get_bin_name().unwrap_or_else(|| command.get_name());
+    bin_name.split_once(' ').map_or("", |(_, path)| path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn visible_paths(command: &Command, paths: &mut Vec<String>) {
+        paths.push(subcommand_path(command).to_string());
+        for sub in command.get_subcommands().filter(|sub| !sub.is_hide_set() && sub.get_name() != "help") {
+            visible_paths(sub, paths);
+        }
+    }
+
+    #[test]
+    fn test_examples_cover_commands() {
+        let mut command = command();
+        command.build();
+        let mut paths = Vec::new();
+        visible_paths(&command, &mut paths);
+
+        // Commands grouping subcommands only need examples on the subcommands
+        for path in paths.iter().filter(|path| *path != "sync" && *path != "key") {
+            assert!(EXAMPLES.iter().any(|(name, _)| name == path), "no examples for `{}`", path);
+        }
+        for (name, _) in EXAMPLES {
+            assert!(paths.iter().any(|path| path == name), "examples for unknown command `{}`", name);
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! Converting JSON and YAML maps into encrypted env files (`envcrypt import`).
+
+use std::fs;
+use std::path::Path;
+
+use clap::ValueEnum;
+use zeroize::Zeroizing;
+
+use crate::cli::attributes::FileAttributes;
+use crate::cli::audit::note_read;
+use crate::cli::dotenv::is_valid_key;
+use crate::cli::encoding::UTF8_BOM;
+use crate::cli::encrypt::{check_output, write_encrypted, EncryptOptions};
+use crate::cli::export::{format_variables, ExportFormat};
+use crate::cli::key_handling::UserKey;
+use crate::cli::memory::lock_secret;
+use crate::cli::newline::Newline;
+use crate::cli::output::{success, OutputConfig};
+
+/// Format of the file read by [`import_env()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImportFormat {
+    /// A JSON object
+    Json,
+    /// A YAML map
+    Yaml,
+}
+
+impl ImportFormat {
+    /// Detects the format from the file extension (`.json`, `.yaml` or `.yml`).
+    fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "json" => Some(ImportFormat::Json),
+            "yaml" | "yml" => Some(ImportFormat::Yaml),
+            _ => None,
+        }
+    }
+}
+
+/// Converts a flat JSON or YAML map of variables into dotenv syntax and encrypts it.
+///
+/// `format` is detected from the extension of `source_path` if not given. Keys must be
+/// valid variable names; string values are taken as they are, numbers and booleans as
+/// written, and `null` becomes an empty value. The variables are written to
+/// `output_path` in the order they appear, quoted where needed, with the line endings of
+/// the source unless `options.newline` is set, and encrypted as
+/// [`crate::cli::encrypt_env()`] would; the converted plaintext never touches the disk.
+///
+/// # Returns
+///
+/// Returns the encryption key that was used, as [`crate::cli::encrypt_env()`] does.
+///
+/// # Errors
+///
+/// Returns an error string if the source cannot be read or its format detected, it is
+/// not a map, a key is not a valid variable name, a value is a nested map or list, or
+/// for any error [`crate::cli::encrypt_env()`] would report.
+///
+/// # Example
+///
+/// ```no_run
+/// use envcrypt::cli::{import_env, EncryptOptions, OutputConfig};
+///
+/// let output_config = OutputConfig::new(false, false, 0);
+/// import_env("AES-256-GCM", Some("my-key"), "secrets.json", ".env.production.encrypted", None, &output_config, &EncryptOptions::default())?;
+/// # Ok::<(), String>(())
+/// ```
+pub fn import_env(
+    cipher_name: &str,
+    key_arg: Option<&str>,
+    source_path: &str,
+    output_path: &str,
+    format: Option<ImportFormat>,
+    output_config: &OutputConfig,
+    options: &EncryptOptions,
+) -> Result<UserKey, String> {
+    let source = Path::new(source_path);
+    if !source.exists() {
+        return Err(format!("{} file not found", source_path));
+    }
+    let format = format.or_else(|| ImportFormat::from_path(source)).ok_or_else(|| {
+        format!("Cannot tell the format of {} from its extension; use --from json or --from yaml", source_path)
+    })?;
+
+    let _lock = check_output(output_path, output_config, options)?;
+
+    let _span = tracing::info_span!("import", input = source_path, output = output_path).entered();
+    let text = Zeroizing::new(fs::read_to_string(source)
+        .map_err(|e| format!("Error reading {} file: {}", source_path, e))?);
+    note_read(source_path);
+    let variables = parse_variables(&text, format)
+        .map_err(|e| format!("Error importing {}: {}", source_path, e))?;
+    tracing::debug!("Converted {} variables", variables.len());
+
+    let plaintext = format_variables(&variables, ExportFormat::Dotenv);
+    let _plaintext_lock = lock_secret(output_config, options.mlock, plaintext.as_bytes());
+    let options = &EncryptOptions { newline: options.newline.or_else(|| Newline::detect(text.as_bytes())), ..options.clone() };
+    let key_input = write_encrypted(
+        cipher_name,
+        key_arg,
+        plaintext.as_bytes(),
+        &FileAttributes::default(),
+        output_path,
+        output_config,
+        options,
+    )?;
+
+    if !options.dry_run {
+        let noun = if variables.len() == 1 { "variable" } else { "variables" };
+        success(output_config, &format!("\nImported {} {} from {} to {}", variables.len(), noun, source_path, output_path));
+    }
+    Ok(key_input)
+}
+
+/// Reads the variables of a flat JSON or YAML map, in order.
+pub(crate) fn parse_variables(text: &str, format: ImportFormat) -> Result<Vec<(String, Zeroizing<String>)>, String> {
+    let text = text.strip_prefix(UTF8_BOM).unwrap_or(text);
+    let entries: Vec<(String, Option<String>)> = match format {
+        ImportFormat::Json => {
+            let value: serde_json::Value = serde_json::from_str(text).map_err(|e| format!("invalid JSON: {}", e))?;
+            let serde_json::Value::Object(map) = value else {
+                return Err("expected a JSON object of variables".to_string());
+            };
+            map.into_iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        serde_json::Value::String(s) => Some(s),
+                        serde_json::Value::Null => Some(String::new()),
+                        serde_json::Value::Bool(b) => Some(b.to_string()),
+                        serde_json::Value::Number(n) => Some(n.to_string()),
+                        serde_json::Value::Array(_) | serde_json::Value::Object(_) => None,
+                    };
+                    (key, value)
+                })
+                .collect()
+        }
+        ImportFormat::Yaml => {
+            let value: serde_yaml::Value = serde_yaml::from_str(text).map_err(|e| format!("invalid YAML: {}", e))?;
+            let serde_yaml::Value::Mapping(map) = value else {
+                return Err("expected a YAML map of variables".to_string());
+            };
+            map.into_iter()
+                .map(|(key, value)| {
+                    let serde_yaml::Value::String(key) = key else {
+                        return Err(format!("keys must be strings, found {:?}", key));
+                    };
+                    let value = match value {
+                        serde_yaml::Value::String(s) => Some(s),
+                        serde_yaml::Value::Null => Some(String::new()),
+                        serde_yaml::Value::Bool(b) => Some(b.to_string()),
+                        serde_yaml::Value::Number(n) => Some(n.to_string()),
+                        serde_yaml::Value::Sequence(_) | serde_yaml::Value::Mapping(_) | serde_yaml::Value::Tagged(_) => None,
+                    };
+                    Ok((key, value))
+                })
+                .collect::<Result<_, String>>()?
+        }
+    };
+
+    entries.into_iter()
+        .map(|(key, value)| {
+            if !is_valid_key(&key) {
+                return Err(format!("{} is not a valid variable name", key));
+            }
+            let value = value.ok_or_else(|| format!("{} has a nested value; only flat maps can be imported", key))?;
+            Ok((key, Zeroizing::new(value)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(variables: &[(String, Zeroizing<String>)]) -> Vec<(&str, &str)> {
+        variables.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect()
+    }
+
+    #[test]
+    fn test_parse_variables_json() {
+        let variables = parse_variables(r#"{"PORT": 8080, "DEBUG": false, "NAME": "app \"one\"", "EMPTY": null}"#, ImportFormat::Json).unwrap();
+        assert_eq!(pairs(&variables), vec![("PORT", "8080"), ("DEBUG", "false"), ("NAME", "app \"one\""), ("EMPTY", "")]);
+
+        assert!(parse_variables(r#"["A"]"#, ImportFormat::Json).unwrap_err().contains("expected a JSON object"));
+        assert!(parse_variables(r#"{"A": {"B": 1}}"#, ImportFormat::Json).unwrap_err().contains("A has a nested value"));
+        assert!(parse_variables(r#"{"not valid": 1}"#, ImportFormat::Json).unwrap_err().contains("not a valid variable name"));
+    }
+
+    #[test]
+    fn test_parse_variables_yaml() {
+        let variables = parse_variables("DB_HOST: localhost\nDB_PORT: 5432\nMULTILINE: |\n  one\n  two\n", ImportFormat::Yaml).unwrap();
+        assert_eq!(pairs(&variables), vec![("DB_HOST", "localhost"), ("DB_PORT", "5432"), ("MULTILINE", "one\ntwo\n")]);
+
+        assert!(parse_variables("- A\n- B\n", ImportFormat::Yaml).unwrap_err().contains("expected a YAML map"));
+        assert!(parse_variables("LIST: [1, 2]\n", ImportFormat::Yaml).unwrap_err().contains("LIST has a nested value"));
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(ImportFormat::from_path(Path::new("secrets.json")), Some(ImportFormat::Json));
+        assert_eq!(ImportFormat::from_path(Path::new("config/secrets.YML")), Some(ImportFormat::Yaml));
+        assert_eq!(ImportFormat::from_path(Path::new("secrets.txt")), None);
+    }
+}
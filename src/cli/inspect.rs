@@ -0,0 +1,139 @@
+//! Describing encrypted files without decrypting them (`envcrypt inspect`).
+
+use std::fs;
+use std::path::Path;
+
+use crate::format;
+use crate::armor::decode_file;
+use crate::cipher::cipher_layout;
+use crate::cli::audit::note_read;
+
+/// Describes an encrypted file without decrypting it.
+///
+/// No key is needed: everything reported is stored in the clear, namely the encoding,
+/// format version, cipher, KDF and its parameters, salt, IV/nonce and tag lengths,
+/// ciphertext size, whether the file is bound to a context or has recovery, GPG or SSH
+/// keys, whether its key comes from a hardware token, and the metadata. For
+/// authenticated headers none of this can have been changed without
+/// making the file undecryptable, but that is only checked when the file is decrypted.
+///
+/// # Returns
+///
+/// Returns the report as label/value pairs in display order.
+///
+/// # Errors
+///
+/// Returns an error string if the file does not exist, cannot be read, or is not a
+/// valid encrypted file.
+pub fn inspect_env(input_path: &str) -> Result<Vec<(&'static str, String)>, String> {
+    let path = Path::new(input_path);
+    if !path.exists() {
+        return Err(format!("{} file not found", input_path));
+    }
+
+    let contents = fs::read(path)
+        .map_err(|e| format!("Error reading {} file: {}", input_path, e))?;
+    note_read(input_path);
+    let (data, file_format) = decode_file(&contents)?;
+    let (header, body) = format::decode(&data)
+        .map_err(|e| format!("Invalid encrypted file format: {}", e))?;
+
+    let bytes = |n: usize| format!("{} bytes", n);
+    let mut report = vec![
+        ("File", input_path.to_string()),
+        ("Size", bytes(contents.len())),
+        ("Encoding", file_format.to_string()),
+    ];
+
+    if header.is_legacy() {
+        report.push(("Version", "legacy (no header)".to_string()));
+    } else {
+        report.push(("Version", header.version.to_string()));
+    }
+    let cipher_name = header.cipher.clone().unwrap_or_else(|| "AES-256-CBC".to_string());
+    report.push(("Cipher", match &header.cipher {
+        Some(cipher) => cipher.clone(),
+        None => format!("not recorded ({} assumed)", cipher_name),
+    }));
+    report.push(("KDF", header.kdf.to_string()));
+    report.push(("Key schedule", header.key_schedule.to_string()));
+    report.push(("Salt", bytes(header.salt.len())));
+
+    match cipher_layout(&cipher_name) {
+        Some((iv_len, tag_len)) if body.len() >= iv_len + tag_len => {
+            report.push(("IV/nonce", bytes(iv_len)));
+            report.push(("MAC/tag", bytes(tag_len)));
+            report.push(("Ciphertext", bytes(body.len() - iv_len - tag_len)));
+        }
+        Some(_) => report.push(("Ciphertext", format!("{} (truncated)", bytes(body.len())))),
+        None => report.push(("Ciphertext", format!("{} including IV and tag", bytes(body.len())))),
+    }
+
+    report.push(("Context", if header.context_bound { "bound (decrypt with --aad)" } else { "none" }.to_string()));
+    report.push(("Recovery", match &header.recovery {
+        Some(_) => "file keys wrapped for a recovery key".to_string(),
+        None => "none".to_string(),
+    }));
+    if header.gpg.is_some() {
+        report.push(("GPG", "file keys encrypted to GPG recipients (decrypt with --gpg)".to_string()));
+    }
+    if header.ssh.is_some() {
+        report.push(("SSH", "file keys encrypted to SSH recipients (decrypt with --ssh)".to_string()));
+    }
+    if header.challenge.is_some() {
+        report.push(("Key", "derived by a hardware token (decrypt with --key-provider fido2)".to_string()));
+    }
+    if header.signed {
+        report.push(("Signature", "embedded (check with envcrypt verify)".to_string()));
+    }
+
+    let metadata = &header.metadata;
+    let unknown = || "unknown".to_string();
+    report.push(("Created", metadata.created.map(format_timestamp).unwrap_or_else(unknown)));
+    report.push(("Tool version", metadata.tool_version.clone().unwrap_or_else(unknown)));
+    if let Some(comment) = &metadata.comment {
+        report.push(("Comment", comment.clone()));
+    }
+    if let Some(mode) = metadata.mode {
+        report.push(("Mode", format!("{:04o}", mode)));
+    }
+    if let Some(modified) = metadata.modified {
+        report.push(("Modified", format_timestamp(modified)));
+    }
+    report.push(("Authenticated", if header.is_authenticated() { "yes" } else { "no (older format)" }.to_string()));
+    Ok(report)
+}
+
+/// Formats seconds since the Unix epoch as a UTC date and time.
+pub(crate) fn format_timestamp(secs: u64) -> String {
+    let days = secs / 86_400;
+    let time = secs % 86_400;
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year, month, day, time / 3600, time % 3600 / 60, time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00:00 UTC");
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14 22:13:20 UTC");
+    }
+}
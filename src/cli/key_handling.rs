@@ -1,6 +1,20 @@
 //! Key input and parsing utilities.
 
+use std::fmt;
+use std::io::{BufRead, IsTerminal};
+use std::rc::Rc;
+
 use base64::Engine;
+use clap::ValueEnum;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::format::CHALLENGE_LEN;
+
+use super::audit::note_key;
+use super::config::Config;
+use super::process::check_program;
+use super::tpm::{TpmKeyProvider, TPM_KEY_FILE};
+use super::prompt::{Prompt, TerminalPrompt, KEY_CHOICE_ENV_VAR};
 
 /// Strips the optional "base64:" prefix from a key string.
 ///
@@ -29,115 +43,526 @@ pub fn strip_base64_prefix(key: &str) -> &str {
     key.strip_prefix("base64:").unwrap_or(key)
 }
 
+/// Encoding of a key supplied on the command line or at a prompt.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum KeyFormat {
+    /// Detect from the prefix: `hex:` keys are hex, `base64:` or unprefixed keys are used as-is
+    #[default]
+    Auto,
+    /// Base64-encoded key (the `base64:` prefix is optional)
+    Base64,
+    /// Hex-encoded key (the `hex:` prefix is optional)
+    Hex,
+    /// Literal passphrase; no prefix is stripped
+    Utf8,
+}
+
+/// Decodes a user-supplied key into the canonical key string used for key derivation.
+///
+/// The canonical form of an encoded key is its base64 text without prefix, which is what
+/// envcrypt prints for generated keys. Hex keys are therefore re-encoded as base64, so
+/// `hex:abcd...` and `base64:q80...` name the same key. Surrounding whitespace is always
+/// trimmed. The returned key is zeroized when dropped.
+///
+/// # Arguments
+///
+/// * `key` - The key as entered by the user
+/// * `format` - How to interpret the key (see [`KeyFormat`])
+///
+/// # Errors
+///
+/// Returns an error string if the key is not valid for the requested encoding.
+///
+/// # Example
+///
+/// ```
+/// use envcrypt::cli::{decode_key, KeyFormat};
+///
+/// assert_eq!(decode_key("base64:q80=", KeyFormat::Auto)?.as_str(), "q80=");
+/// assert_eq!(decode_key("hex:abcd", KeyFormat::Auto)?.as_str(), "q80=");
+/// assert_eq!(decode_key("base64:literal", KeyFormat::Utf8)?.as_str(), "base64:literal");
+/// # Ok::<(), String>(())
+/// ```
+pub fn decode_key(key: &str, format: KeyFormat) -> Result<Zeroizing<String>, String> {
+    let key = key.trim();
+    match format {
+        KeyFormat::Auto => match key.strip_prefix("hex:") {
+            Some(hex) => hex_to_base64(hex),
+            None => Ok(Zeroizing::new(strip_base64_prefix(key).to_string())),
+        },
+        KeyFormat::Base64 => {
+            let key = strip_base64_prefix(key);
+            base64::engine::general_purpose::STANDARD.decode(key)
+                .map_err(|_| "Key is not valid base64".to_string())?
+                .zeroize();
+            Ok(Zeroizing::new(key.to_string()))
+        }
+        KeyFormat::Hex => hex_to_base64(key.strip_prefix("hex:").unwrap_or(key)),
+        KeyFormat::Utf8 => Ok(Zeroizing::new(key.to_string())),
+    }
+}
+
+/// Decodes a hex string and re-encodes the bytes as base64.
+fn hex_to_base64(hex: &str) -> Result<Zeroizing<String>, String> {
+    let invalid = || "Key is not valid hex".to_string();
+    if hex.is_empty() || !hex.len().is_multiple_of(2) {
+        return Err(invalid());
+    }
+
+    let bytes = Zeroizing::new(hex.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair).map_err(|_| invalid())?;
+            u8::from_str_radix(pair, 16).map_err(|_| invalid())
+        })
+        .collect::<Result<Vec<u8>, String>>()?);
+    Ok(Zeroizing::new(base64::engine::general_purpose::STANDARD.encode(&*bytes)))
+}
+
 /// Gets the key argument from the command-line option.
 pub fn get_key_arg(key: &Option<String>) -> Option<&str> {
     key.as_deref()
 }
 
+/// A key obtained from the command line, an interactive prompt, or generated on the user's behalf.
+pub struct UserKey {
+    /// Key string with surrounding whitespace and any "base64:" prefix removed. Zeroized
+    /// when dropped.
+    pub value: Zeroizing<String>,
+    /// `true` if the key was freshly generated by envcrypt, i.e. it is a raw 32-byte random key.
+    pub generated: bool,
+    /// Challenge a hardware token turned into this raw key, to record in the file header
+    /// so the same key can be derived again for decryption.
+    pub challenge: Option<[u8; CHALLENGE_LEN]>,
+}
+
+impl fmt::Debug for UserKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UserKey").field("generated", &self.generated).finish_non_exhaustive()
+    }
+}
+
+impl UserKey {
+    pub(crate) fn provided(value: Zeroizing<String>) -> Self {
+        Self { value, generated: false, challenge: None }
+    }
+
+    fn generated(value: Zeroizing<String>) -> Self {
+        Self { value, generated: true, challenge: None }
+    }
+}
+
+/// Estimated strength of a user-chosen key.
+#[derive(Debug, PartialEq, Eq)]
+pub enum KeyStrength {
+    /// The key resists offline guessing well.
+    Strong,
+    /// The key is usable but could be guessed with moderate effort. Contains advice.
+    Weak(String),
+    /// The key could be guessed quickly and should not be used. Contains advice.
+    TooWeak(String),
+}
+
+/// Estimates how hard a user-chosen key is to guess using zxcvbn.
+///
+/// Keys that zxcvbn estimates to fall within 10^6 guesses are [`KeyStrength::TooWeak`],
+/// keys within 10^8 guesses are [`KeyStrength::Weak`], and anything stronger is
+/// [`KeyStrength::Strong`]. Generated keys never need this check.
+pub fn assess_key_strength(key: &str) -> KeyStrength {
+    let estimate = zxcvbn::zxcvbn(key, &[]);
+    let advice = match estimate.feedback() {
+        Some(feedback) => {
+            let mut parts: Vec<String> = feedback.warning().map(|w| w.to_string()).into_iter().collect();
+            parts.extend(feedback.suggestions().iter().map(|s| s.to_string()));
+            parts.join(" ")
+        }
+        None => String::new(),
+    };
+
+    match estimate.score() {
+        zxcvbn::Score::Zero | zxcvbn::Score::One => {
+            KeyStrength::TooWeak(format!("it could be guessed in under a million attempts. {}", advice).trim_end().to_string())
+        }
+        zxcvbn::Score::Two => {
+            KeyStrength::Weak(format!("it could be guessed in under a hundred million attempts. {}", advice).trim_end().to_string())
+        }
+        _ => KeyStrength::Strong,
+    }
+}
+
 enum KeyChoice {
     GenerateNew,
     UseCustom,
 }
 
-fn show_key_menu() -> Result<KeyChoice, String> {
-    use std::io::{self, Write};
-    
-    println!("\nSelect encryption key option:");
-    println!("  1) Generate a new key (default)");
-    println!("  2) Use a custom key");
-    print!("\nEnter choice [1]: ");
-    io::stdout().flush().map_err(|e| format!("Failed to flush stdout: {}", e))?;
+/// Error for key prompts when nobody can answer them.
+const NO_TTY_ERROR: &str = "Cannot prompt for the key: stdin is not a terminal. Pass --key, set ENVCRYPT_KEY and use --key-provider env, or set key_file in envcrypt.toml and use --key-provider file";
+
+/// Fails with [`NO_TTY_ERROR`] if `prompt` cannot be answered, so piped and detached
+/// invocations do not block on a prompt.
+fn require_interactive(prompt: &dyn Prompt, hint: &str) -> Result<(), String> {
+    if prompt.is_interactive() {
+        Ok(())
+    } else {
+        Err(format!("{}{}", NO_TTY_ERROR, hint))
+    }
+}
+
+/// Takes the menu answer from `ENVCRYPT_KEY_CHOICE` if it is set.
+fn key_choice_from_env() -> Result<Option<KeyChoice>, String> {
+    let value = match std::env::var(KEY_CHOICE_ENV_VAR) {
+        Ok(value) if !value.trim().is_empty() => value,
+        _ => return Ok(None),
+    };
+    match value.trim().to_lowercase().as_str() {
+        "generate" => Ok(Some(KeyChoice::GenerateNew)),
+        "custom" => Ok(Some(KeyChoice::UseCustom)),
+        other => Err(format!("Invalid value for {}: {} (expected generate or custom)", KEY_CHOICE_ENV_VAR, other)),
+    }
+}
+
+fn show_key_menu(prompt: &dyn Prompt) -> Result<KeyChoice, String> {
+    if let Some(choice) = key_choice_from_env()? {
+        return Ok(choice);
+    }
+    require_interactive(prompt, ". To generate a new key, use --no-interaction or ENVCRYPT_KEY_CHOICE=generate")?;
+
+    prompt.message("\nSelect encryption key option:");
+    prompt.message("  1) Generate a new key (default)");
+    prompt.message("  2) Use a custom key");
     
-    let mut input = String::new();
-    io::stdin().read_line(&mut input)
-        .map_err(|e| format!("Failed to read input: {}", e))?;
+    let input = prompt.read_line("\nEnter choice [1]: ")?;
     
     let choice = input.trim();
     match choice {
         "2" => Ok(KeyChoice::UseCustom),
         "1" | "" => Ok(KeyChoice::GenerateNew),
         _ => {
-            println!("Invalid choice, defaulting to generate new key");
+            prompt.message("Invalid choice, defaulting to generate new key");
             Ok(KeyChoice::GenerateNew)
         }
     }
 }
 
-fn generate_base64_key() -> String {
+pub(crate) fn generate_base64_key() -> Zeroizing<String> {
     use rand::RngCore;
     // Generate 32 random bytes (256 bits) and encode as base64
-    let mut key_bytes = [0u8; 32];
-    rand::thread_rng().fill_bytes(&mut key_bytes);
-    base64::engine::general_purpose::STANDARD.encode(&key_bytes)
+    let mut key_bytes = Zeroizing::new([0u8; 32]);
+    rand::thread_rng().fill_bytes(&mut *key_bytes);
+    Zeroizing::new(base64::engine::general_purpose::STANDARD.encode(*key_bytes))
 }
 
-fn get_encrypt_key_with_menu() -> Result<String, String> {
-    match show_key_menu()? {
+fn get_encrypt_key_with_menu(prompt: &dyn Prompt, key_format: KeyFormat) -> Result<UserKey, String> {
+    match show_key_menu(prompt)? {
         KeyChoice::GenerateNew => {
             let key = generate_base64_key();
-            println!("\nGenerated new encryption key");
-            Ok(key)
+            prompt.message("\nGenerated new encryption key");
+            Ok(UserKey::generated(key))
         }
         KeyChoice::UseCustom => {
-            print!("Enter encryption key: ");
-            use std::io::Write;
-            std::io::stdout().flush().map_err(|e| format!("Failed to flush stdout: {}", e))?;
-            
-            let key = rpassword::read_password()
-                .map_err(|e| format!("Failed to read password: {}", e))?;
-            Ok(strip_base64_prefix(key.trim()).to_string())
+            require_interactive(prompt, "")?;
+            let key = prompt.read_secret("Enter encryption key: ")?;
+            // A typo here would produce a file nobody can decrypt, so ask twice
+            let confirmation = prompt.read_secret("Confirm encryption key: ")?;
+            if *key != *confirmation {
+                return Err("Encryption keys do not match".to_string());
+            }
+            Ok(UserKey::provided(decode_key(&key, key_format)?))
         }
     }
 }
 
-fn get_decrypt_key() -> Result<String, String> {
-    print!("Enter decryption key: ");
-    use std::io::Write;
-    std::io::stdout().flush().map_err(|e| format!("Failed to flush stdout: {}", e))?;
-    
-    let key = rpassword::read_password()
-        .map_err(|e| format!("Failed to read password: {}", e))?;
-    Ok(strip_base64_prefix(key.trim()).to_string())
+fn get_decrypt_key(prompt: &dyn Prompt, key_format: KeyFormat) -> Result<UserKey, String> {
+    require_interactive(prompt, "")?;
+    let key = prompt.read_secret("Enter decryption key: ")?;
+    Ok(UserKey::provided(decode_key(&key, key_format)?))
 }
 
-/// Gets the encryption/decryption key from command-line argument or prompts the user.
-///
-/// # Arguments
+/// Prompts for a key on the terminal without echoing it. The returned text is zeroized
+/// when dropped.
+pub(crate) fn read_secret(prompt: &str) -> Result<Zeroizing<String>, String> {
+    TerminalPrompt.read_secret(prompt)
+}
+
+/// Gets a key from the command-line argument or, if none was given, from `provider`.
 ///
-/// * `key_arg` - Optional key provided via command-line flag
-/// * `is_encrypt` - `true` for encryption, `false` for decryption
-/// * `no_interaction` - If `true`, skip interactive prompts. For encryption, auto-generate key if not provided. For decryption, error if key not provided.
+/// A key passed with `--key` always wins. Without a provider the interactive
+/// [`PromptKeyProvider`] is used.
+pub fn get_key(key_arg: Option<&str>, provider: Option<&dyn KeyProvider>, request: &KeyRequest) -> Result<UserKey, String> {
+    let key = match (key_arg, provider) {
+        (Some(key), _) => UserKey::provided(decode_key(key, request.key_format)?),
+        (None, Some(provider)) => provider.provide(request)?,
+        (None, None) => PromptKeyProvider::default().provide(request)?,
+    };
+    note_key(&key.value);
+    Ok(key)
+}
+
+/// What a [`KeyProvider`] is asked for.
+#[derive(Debug, Clone, Copy)]
+pub struct KeyRequest {
+    /// `true` if the key will be used to encrypt, `false` to decrypt.
+    pub is_encrypt: bool,
+    /// If `true`, the provider must not prompt.
+    pub no_interaction: bool,
+    /// How the key is encoded (see [`decode_key()`]).
+    pub key_format: KeyFormat,
+    /// Hardware token challenge from the header of the file being decrypted, if any.
+    pub challenge: Option<[u8; CHALLENGE_LEN]>,
+}
+
+/// A source of encryption keys, selected with `--key-provider` or `key_provider` in
+/// `envcrypt.toml`.
 ///
-/// # Returns
+/// Providers are looked up by [`name()`](KeyProvider::name) in a [`KeyProviderRegistry`].
+/// Integrations such as system keychains or secret managers implement this trait and
+/// register themselves there.
+pub trait KeyProvider {
+    /// Name used to select the provider.
+    fn name(&self) -> &str;
+
+    /// Returns the key, decoded according to `request.key_format`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error string if no key is available or it cannot be decoded.
+    fn provide(&self, request: &KeyRequest) -> Result<UserKey, String>;
+}
+
+/// Asks for the key interactively: offers to generate one when encrypting and prompts
+/// for it when decrypting. With `no_interaction`, encryption generates a key and
+/// decryption fails. `ENVCRYPT_KEY_CHOICE` answers the encryption menu in advance.
 ///
-/// Returns the key string, or an error if key input fails.
-pub fn get_encryption_key(key_arg: Option<&str>, is_encrypt: bool, no_interaction: bool) -> Result<String, String> {
-    // If key was provided via flag, use it
-    if let Some(key) = key_arg {
-        return Ok(strip_base64_prefix(key.trim()).to_string());
+/// All input goes through a [`Prompt`], the terminal by default.
+pub struct PromptKeyProvider {
+    prompt: Rc<dyn Prompt>,
+}
+
+impl PromptKeyProvider {
+    /// Creates a provider that asks through `prompt`.
+    pub fn new(prompt: Rc<dyn Prompt>) -> Self {
+        Self { prompt }
     }
-    
-    if no_interaction {
-        if is_encrypt {
+}
+
+impl Default for PromptKeyProvider {
+    fn default() -> Self {
+        Self::new(Rc::new(TerminalPrompt))
+    }
+}
+
+impl KeyProvider for PromptKeyProvider {
+    fn name(&self) -> &str {
+        "prompt"
+    }
+
+    fn provide(&self, request: &KeyRequest) -> Result<UserKey, String> {
+        match (request.no_interaction, request.is_encrypt) {
             // Auto-generate new key for encryption
-            let key = generate_base64_key();
-            Ok(key)
-        } else {
+            (true, true) => Ok(UserKey::generated(generate_base64_key())),
             // For decryption, cannot proceed without key
-            Err("Decryption key is required when using --no-interaction. Please provide --key".to_string())
+            (true, false) => Err("Decryption key is required when using --no-interaction. Please provide --key".to_string()),
+            (false, true) => get_encrypt_key_with_menu(self.prompt.as_ref(), request.key_format),
+            (false, false) => get_decrypt_key(self.prompt.as_ref(), request.key_format),
         }
-    } else {
-        if is_encrypt {
-            get_encrypt_key_with_menu()
-        } else {
-            get_decrypt_key()
+    }
+}
+
+/// Name of the environment variable read by [`EnvKeyProvider`].
+pub const KEY_ENV_VAR: &str = "ENVCRYPT_KEY";
+
+/// Reads the key from the `ENVCRYPT_KEY` environment variable.
+pub struct EnvKeyProvider;
+
+impl KeyProvider for EnvKeyProvider {
+    fn name(&self) -> &str {
+        "env"
+    }
+
+    fn provide(&self, request: &KeyRequest) -> Result<UserKey, String> {
+        let key = Zeroizing::new(std::env::var(KEY_ENV_VAR)
+            .map_err(|_| format!("{} is not set", KEY_ENV_VAR))?);
+        Ok(UserKey::provided(decode_key(&key, request.key_format)?))
+    }
+}
+
+/// Reads the key from a file, such as a mounted secret. Surrounding whitespace is ignored.
+pub struct FileKeyProvider {
+    /// Path of the key file, from `key_file` in `envcrypt.toml`.
+    pub path: Option<String>,
+}
+
+impl KeyProvider for FileKeyProvider {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    fn provide(&self, request: &KeyRequest) -> Result<UserKey, String> {
+        let path = self.path.as_deref()
+            .ok_or_else(|| "The file key provider requires key_file in envcrypt.toml".to_string())?;
+        let key = Zeroizing::new(std::fs::read_to_string(path)
+            .map_err(|e| format!("Error reading key file {}: {}", path, e))?);
+        Ok(UserKey::provided(decode_key(&key, request.key_format)?))
+    }
+}
+
+/// Reads the keys listed in a file (`decrypt --key-file`), one per line. Blank lines and
+/// lines starting with `#` are ignored.
+pub(crate) fn read_key_list(path: &str) -> Result<Vec<String>, String> {
+    let contents = Zeroizing::new(std::fs::read_to_string(path)
+        .map_err(|e| format!("Error reading key file {}: {}", path, e))?);
+    let keys: Vec<String> = contents.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect();
+    if keys.is_empty() {
+        return Err(format!("Key file {} lists no keys", path));
+    }
+    Ok(keys)
+}
+
+/// Reads the key from standard input (`--key-stdin`), so it can be piped in from a
+/// secrets manager without appearing in argv or the environment.
+///
+/// Only the first line is used; without a newline, everything up to EOF is the key.
+pub struct StdinKeyProvider;
+
+impl KeyProvider for StdinKeyProvider {
+    fn name(&self) -> &str {
+        "stdin"
+    }
+
+    fn provide(&self, request: &KeyRequest) -> Result<UserKey, String> {
+        let stdin = std::io::stdin();
+        if stdin.is_terminal() {
+            return Err("--key-stdin expects the key to be piped in, but stdin is a terminal".to_string());
         }
+        let mut key = Zeroizing::new(String::new());
+        stdin.lock().read_line(&mut key)
+            .map_err(|e| format!("Error reading key from stdin: {}", e))?;
+        if key.trim().is_empty() {
+            return Err("No key was provided on stdin".to_string());
+        }
+        Ok(UserKey::provided(decode_key(&key, request.key_format)?))
+    }
+}
+
+/// Reads the key from 1Password with the `op` CLI (`op read <reference>`).
+///
+/// The CLI must be signed in, e.g. with a service account token in
+/// `OP_SERVICE_ACCOUNT_TOKEN`. Surrounding whitespace is ignored.
+pub struct OnePasswordKeyProvider {
+    /// Secret reference of the key, such as `op://Vault/envcrypt/password`, from
+    /// `onepassword_ref` in `envcrypt.toml`.
+    pub reference: Option<String>,
+}
+
+impl KeyProvider for OnePasswordKeyProvider {
+    fn name(&self) -> &str {
+        "1password"
+    }
+
+    fn provide(&self, request: &KeyRequest) -> Result<UserKey, String> {
+        let reference = self.reference.as_deref()
+            .ok_or_else(|| "The 1password key provider requires onepassword_ref in envcrypt.toml".to_string())?;
+        let key = check_program("op", &["read", "--no-newline", reference], None)?;
+        provided_key_from_output("op", &key, request.key_format)
+    }
+}
+
+/// Reads the key from the password of a Bitwarden item with the `bw` CLI
+/// (`bw get password <item>`).
+///
+/// The vault must be unlocked, with the session key in `BW_SESSION`. Surrounding
+/// whitespace is ignored.
+pub struct BitwardenKeyProvider {
+    /// Name or ID of the item, from `bitwarden_item` in `envcrypt.toml`.
+    pub item: Option<String>,
+}
+
+impl KeyProvider for BitwardenKeyProvider {
+    fn name(&self) -> &str {
+        "bitwarden"
+    }
+
+    fn provide(&self, request: &KeyRequest) -> Result<UserKey, String> {
+        let item = self.item.as_deref()
+            .ok_or_else(|| "The bitwarden key provider requires bitwarden_item in envcrypt.toml".to_string())?;
+        let key = check_program("bw", &["get", "password", item, "--nointeraction"], None)?;
+        provided_key_from_output("bw", &key, request.key_format)
+    }
+}
+
+/// Decodes a key printed by a password manager CLI.
+fn provided_key_from_output(program: &str, output: &[u8], key_format: KeyFormat) -> Result<UserKey, String> {
+    let key = std::str::from_utf8(output)
+        .map_err(|_| format!("The key printed by {} is not valid UTF-8", program))?;
+    if key.trim().is_empty() {
+        return Err(format!("{} printed an empty key", program));
+    }
+    Ok(UserKey::provided(decode_key(key, key_format)?))
+}
+
+/// Key providers available by name.
+pub struct KeyProviderRegistry {
+    providers: Vec<Rc<dyn KeyProvider>>,
+}
+
+impl KeyProviderRegistry {
+    /// Creates a registry with the built-in providers: `prompt`, `env`, `file` (reading
+    /// `key_file`), `stdin`, `1password`, `bitwarden` and `tpm`. The password manager
+    /// providers are not configured and `tpm` reads the default sealed key file; use
+    /// [`KeyProviderRegistry::from_config()`] to configure them.
+    pub fn new(key_file: Option<String>) -> Self {
+        let mut registry = Self { providers: Vec::new() };
+        registry.register(Rc::new(PromptKeyProvider::default()));
+        registry.register(Rc::new(EnvKeyProvider));
+        registry.register(Rc::new(FileKeyProvider { path: key_file }));
+        registry.register(Rc::new(StdinKeyProvider));
+        registry.register(Rc::new(OnePasswordKeyProvider { reference: None }));
+        registry.register(Rc::new(BitwardenKeyProvider { item: None }));
+        registry.register(Rc::new(TpmKeyProvider { path: TPM_KEY_FILE.into() }));
+        registry
+    }
+
+    /// Creates a registry with the built-in providers configured from `envcrypt.toml`.
+    pub fn from_config(config: &Config) -> Self {
+        let mut registry = Self::new(config.key_file.clone());
+        registry.register(Rc::new(OnePasswordKeyProvider { reference: config.onepassword_ref.clone() }));
+        registry.register(Rc::new(BitwardenKeyProvider { item: config.bitwarden_item.clone() }));
+        registry.register(Rc::new(TpmKeyProvider { path: config.tpm_key_path() }));
+        registry
+    }
+
+    /// Adds a provider, replacing any provider with the same name.
+    pub fn register(&mut self, provider: Rc<dyn KeyProvider>) {
+        self.providers.retain(|p| p.name() != provider.name());
+        self.providers.push(provider);
+    }
+
+    /// Returns the names of all registered providers.
+    pub fn names(&self) -> Vec<&str> {
+        self.providers.iter().map(|p| p.name()).collect()
+    }
+
+    /// Looks up a provider by name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error string listing the available providers if `name` is unknown.
+    pub fn get(&self, name: &str) -> Result<Rc<dyn KeyProvider>, String> {
+        self.providers.iter()
+            .find(|p| p.name() == name)
+            .cloned()
+            .ok_or_else(|| format!("Unknown key provider: {}. Available providers: {}", name, self.names().join(", ")))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cli::prompt::ScriptedPrompt;
 
     #[test]
     fn test_strip_base64_prefix_with_prefix() {
@@ -153,4 +578,147 @@ mod tests {
     fn test_strip_base64_prefix_empty() {
         assert_eq!(strip_base64_prefix(""), "");
     }
+
+    #[test]
+    fn test_decode_key_auto_hex_prefix() {
+        assert_eq!(decode_key("hex:abcd", KeyFormat::Auto).unwrap().as_str(), "q80=");
+    }
+
+    #[test]
+    fn test_decode_key_auto_base64_prefix_and_whitespace() {
+        assert_eq!(decode_key("  base64:test123  ", KeyFormat::Auto).unwrap().as_str(), "test123");
+    }
+
+    #[test]
+    fn test_decode_key_hex_without_prefix() {
+        assert_eq!(decode_key("ABCD", KeyFormat::Hex).unwrap().as_str(), "q80=");
+    }
+
+    #[test]
+    fn test_decode_key_invalid_hex() {
+        assert!(decode_key("hex:abc", KeyFormat::Auto).is_err());
+        assert!(decode_key("xyz1", KeyFormat::Hex).is_err());
+    }
+
+    #[test]
+    fn test_decode_key_base64_validates() {
+        assert_eq!(decode_key("base64:q80=", KeyFormat::Base64).unwrap().as_str(), "q80=");
+        assert!(decode_key("not base64!", KeyFormat::Base64).is_err());
+    }
+
+    #[test]
+    fn test_decode_key_utf8_keeps_prefix() {
+        assert_eq!(decode_key(" hex:abcd ", KeyFormat::Utf8).unwrap().as_str(), "hex:abcd");
+    }
+
+    struct ScriptedKeyProvider(&'static str);
+
+    impl KeyProvider for ScriptedKeyProvider {
+        fn name(&self) -> &str {
+            "scripted"
+        }
+
+        fn provide(&self, request: &KeyRequest) -> Result<UserKey, String> {
+            Ok(UserKey::provided(decode_key(self.0, request.key_format)?))
+        }
+    }
+
+    const DECRYPT: KeyRequest = KeyRequest { is_encrypt: false, no_interaction: true, key_format: KeyFormat::Auto, challenge: None };
+
+    #[test]
+    fn test_get_key_uses_provider() {
+        let key = get_key(None, Some(&ScriptedKeyProvider("hex:abcd")), &DECRYPT).unwrap();
+        assert_eq!(key.value.as_str(), "q80=");
+        assert!(!key.generated);
+    }
+
+    #[test]
+    fn test_get_key_prefers_key_arg() {
+        let key = get_key(Some("from-flag"), Some(&ScriptedKeyProvider("scripted")), &DECRYPT).unwrap();
+        assert_eq!(key.value.as_str(), "from-flag");
+    }
+
+    fn encrypt_request() -> KeyRequest {
+        KeyRequest { is_encrypt: true, no_interaction: false, key_format: KeyFormat::Auto, challenge: None }
+    }
+
+    #[test]
+    fn test_prompt_provider_generates_key_by_default() {
+        let prompt = Rc::new(ScriptedPrompt::new([""]));
+        let key = PromptKeyProvider::new(prompt.clone()).provide(&encrypt_request()).unwrap();
+        assert!(key.generated);
+        assert_eq!(decode_raw_key_len(&key), 32);
+        assert!(prompt.transcript().contains(&"\nGenerated new encryption key".to_string()));
+    }
+
+    #[test]
+    fn test_prompt_provider_custom_key_is_confirmed() {
+        let prompt = Rc::new(ScriptedPrompt::new(["2", "hex:abcd", "hex:abcd"]));
+        let key = PromptKeyProvider::new(prompt).provide(&encrypt_request()).unwrap();
+        assert_eq!(key.value.as_str(), "q80=");
+        assert!(!key.generated);
+    }
+
+    #[test]
+    fn test_prompt_provider_custom_key_mismatch() {
+        let prompt = Rc::new(ScriptedPrompt::new(["2", "first", "second"]));
+        let err = PromptKeyProvider::new(prompt).provide(&encrypt_request()).err().unwrap();
+        assert_eq!(err, "Encryption keys do not match");
+    }
+
+    #[test]
+    fn test_prompt_provider_decrypt_asks_for_key() {
+        let prompt = Rc::new(ScriptedPrompt::new(["  base64:q80=  "]));
+        let request = KeyRequest { is_encrypt: false, ..encrypt_request() };
+        let key = PromptKeyProvider::new(prompt.clone()).provide(&request).unwrap();
+        assert_eq!(key.value.as_str(), "q80=");
+        assert_eq!(prompt.transcript(), vec!["Enter decryption key: ".to_string()]);
+    }
+
+    fn decode_raw_key_len(key: &UserKey) -> usize {
+        base64::engine::general_purpose::STANDARD.decode(key.value.as_str()).unwrap().len()
+    }
+
+    #[test]
+    fn test_registry_lookup_and_register() {
+        let mut registry = KeyProviderRegistry::new(None);
+        assert_eq!(registry.names(), vec!["prompt", "env", "file", "stdin", "1password", "bitwarden", "tpm"]);
+        assert!(registry.get("vault").err().unwrap().contains("Available providers: prompt, env, file"));
+
+        registry.register(Rc::new(ScriptedKeyProvider("secret")));
+        assert_eq!(registry.get("scripted").unwrap().provide(&DECRYPT).unwrap().value.as_str(), "secret");
+    }
+
+    #[test]
+    fn test_file_provider_requires_path() {
+        let err = FileKeyProvider { path: None }.provide(&DECRYPT).err().unwrap();
+        assert!(err.contains("key_file"));
+    }
+
+    #[test]
+    fn test_password_manager_providers_require_config() {
+        let err = OnePasswordKeyProvider { reference: None }.provide(&DECRYPT).err().unwrap();
+        assert!(err.contains("onepassword_ref"));
+        let err = BitwardenKeyProvider { item: None }.provide(&DECRYPT).err().unwrap();
+        assert!(err.contains("bitwarden_item"));
+
+        let config = Config { onepassword_ref: Some("op://Vault/envcrypt/password".to_string()), ..Config::default() };
+        assert!(KeyProviderRegistry::from_config(&config).get("1password").is_ok());
+    }
+
+    #[test]
+    fn test_provided_key_from_output() {
+        assert_eq!(provided_key_from_output("op", b"secret\n", KeyFormat::Auto).unwrap().value.as_str(), "secret");
+        assert!(provided_key_from_output("op", b" \n", KeyFormat::Auto).err().unwrap().contains("op printed an empty key"));
+    }
+
+    #[test]
+    fn test_assess_key_strength_common_password() {
+        assert!(matches!(assess_key_strength("password"), KeyStrength::TooWeak(_)));
+    }
+
+    #[test]
+    fn test_assess_key_strength_long_passphrase() {
+        assert_eq!(assess_key_strength("correct horse battery staple"), KeyStrength::Strong);
+    }
 }
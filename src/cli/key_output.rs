@@ -0,0 +1,424 @@
+//! Showing the key a file was encrypted with after `encrypt`, `import` and `sync --pull`.
+//!
+//! Printing the key puts it in the terminal scrollback, screen shares and CI logs, so by
+//! default only its fingerprint is printed and the key itself is shown on request.
+//! `--copy-key` (`clipboard` feature) also takes the key off the clipboard again, and
+//! `--key-out` writes the key to a file for automation.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+#[cfg(feature = "clipboard")]
+use std::time::Duration;
+
+use qrcode::render::unicode::Dense1x2;
+use qrcode::QrCode;
+use zeroize::Zeroizing;
+
+use crate::cli::attributes::write_with_mode;
+use crate::cli::audit::key_fingerprint;
+#[cfg(feature = "clipboard")]
+use crate::cli::output::Spinner;
+use crate::cli::output::{critical, info, styled, success, warning, OutputConfig, Style};
+use crate::cli::prompt::{confirm, confirm_overwrite, Prompt};
+
+/// How [`show_encryption_key()`] hands over the key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeyDisplay {
+    /// Print the fingerprint and ask whether to show the key.
+    #[default]
+    Ask,
+    /// Print the key (`--show-key`).
+    Show,
+    /// Only print the fingerprint (`--hide-key`).
+    Hide,
+    /// Copy the key to the clipboard and print the fingerprint (`--copy`).
+    Copy,
+    /// Print the key as a QR code (`encrypt --key-qr`).
+    Qr,
+    /// Like [`KeyDisplay::Copy`], but clear the clipboard again after `clear_after`
+    /// (`--copy-key`).
+    #[cfg(feature = "clipboard")]
+    CopyKey {
+        /// How long the key stays on the clipboard.
+        clear_after: Duration,
+    },
+}
+
+/// Where [`show_encryption_key()`] hands over the key.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct KeyOutput<'a> {
+    /// How the key is shown.
+    pub display: KeyDisplay,
+    /// File to write the key to (`--key-out`), checked with [`check_key_out()`] before
+    /// the key is created.
+    pub key_out: Option<&'a Path>,
+    /// Asks whether to show the key; `None` with `--no-interaction`.
+    pub prompt: Option<&'a dyn Prompt>,
+    /// The key was generated by envcrypt ([`UserKey::generated`]), so nobody else has it.
+    ///
+    /// [`UserKey::generated`]: crate::cli::UserKey::generated
+    pub generated: bool,
+    /// Accept that a generated key hidden by `--silent` is lost
+    /// (`--i-know-what-im-doing`).
+    pub allow_lost_key: bool,
+}
+
+/// Hands over the key a file was encrypted with. Messages are not shown when output is
+/// quiet or silent, except that a key generated by envcrypt is still printed when output
+/// is quiet, since nobody else has it.
+///
+/// The key is printed with `--show-key`, copied to the clipboard with `--copy`, written
+/// to the `--key-out` file, and otherwise only its fingerprint (as recorded in the audit
+/// log) is printed. If the key was not handed over otherwise, the user is asked whether
+/// to show it, if `prompt` is given and interactive; if nobody can be asked, a key
+/// generated by envcrypt is shown, since nobody else has it.
+///
+/// With `--copy-key`, this waits until the key is cleared from the clipboard.
+///
+/// # Errors
+///
+/// Returns an error string if the `--key-out` file cannot be written. The key is then
+/// printed (unless output is quiet or silent, or silent for a generated key), so it is
+/// not lost. Also returns an error string if a generated key was not handed over because
+/// output is silent.
+pub(crate) fn show_encryption_key(output_config: &OutputConfig, key: &str, output: &KeyOutput) -> Result<(), String> {
+    let mut display = output.display;
+    let mut written = false;
+    let mut error = None;
+    if let Some(path) = output.key_out {
+        let contents = Zeroizing::new(format!("base64:{}\n", key));
+        match write_with_mode(path, contents.as_bytes(), Some(0o600)) {
+            Ok(()) => {
+                success(output_config, &format!("Wrote the encryption key to {}", path.display()));
+                written = true;
+            }
+            Err(e) => {
+                display = KeyDisplay::Show;
+                error = Some(format!("Could not write the encryption key to {}: {}", path.display(), e));
+            }
+        }
+    }
+
+    #[cfg(feature = "clipboard")]
+    let mut clipboard = None;
+    let copied = match display {
+        KeyDisplay::Copy => report_copy(output_config, copy_to_clipboard(key)
+            .map(|program| format!("Copied the encryption key to the clipboard ({})", program))),
+        #[cfg(feature = "clipboard")]
+        KeyDisplay::CopyKey { clear_after } => report_copy(output_config, copy_with_arboard(key).map(|copied| {
+            clipboard = Some(copied);
+            format!("Copied the encryption key to the clipboard, clearing it in {} seconds", clear_after.as_secs())
+        })),
+        _ => false,
+    };
+    if output_config.should_show_info() {
+        print_key_banner(output_config, key, display, copied || written, output);
+    } else if output.generated && !copied && !written && display != KeyDisplay::Hide {
+        // Nobody has a generated key yet, so it is shown even when other messages are not
+        if output_config.should_show_critical() {
+            let message = Zeroizing::new(format!("Encryption key: base64:{}", key));
+            critical(output_config, &message);
+        } else if !output.allow_lost_key {
+            error = error.or_else(|| Some(format!(
+                "--silent hid the generated encryption key ({}), so nothing encrypted with it can be decrypted. Use --key-out to save generated keys when running with --silent, or pass --key",
+                key_fingerprint(key),
+            )));
+        }
+    }
+
+    #[cfg(feature = "clipboard")]
+    if let (Some(clipboard), KeyDisplay::CopyKey { clear_after }) = (clipboard, display) {
+        clear_after_timeout(output_config, clipboard, key, clear_after);
+    }
+    error.map_or(Ok(()), Err)
+}
+
+/// Fails before a key is generated that would never be handed over, as anything
+/// encrypted with it could not be decrypted. The key must be written to `--key-out`,
+/// copied to the clipboard, or shown: `--hide-key` and `--silent` never show it, while
+/// a generated key is printed when there is no interactive prompt to ask.
+/// `output.allow_lost_key` skips the check.
+///
+/// # Errors
+///
+/// Returns an error string if `output` does not hand over the key.
+pub(crate) fn check_generated_key_output(output_config: &OutputConfig, output: &KeyOutput) -> Result<(), String> {
+    let (copied, shown) = match output.display {
+        KeyDisplay::Copy => (true, false),
+        #[cfg(feature = "clipboard")]
+        KeyDisplay::CopyKey { .. } => (true, false),
+        KeyDisplay::Show | KeyDisplay::Qr | KeyDisplay::Ask => (false, true),
+        KeyDisplay::Hide => (false, false),
+    };
+    if (shown && output_config.should_show_critical()) || output.key_out.is_some() || copied || output.allow_lost_key {
+        return Ok(());
+    }
+    let (reason, show) = if output_config.should_show_critical() {
+        ("would not be shown", "show it with --show-key, ")
+    } else {
+        ("--silent would hide", "")
+    };
+    Err(format!(
+        "Refusing to generate a key that {}, as nothing encrypted with it could be decrypted. Pass --key or --key-provider, {}save the key with --key-out, or pass --i-know-what-im-doing to encrypt anyway",
+        reason, show,
+    ))
+}
+
+/// Checks that the key may be written to `path` (`--key-out`) before a key is created.
+///
+/// Paths inside a Git repository are refused unless `force` is set, since the key would
+/// end up next to the files it protects and could be committed with them. Existing files
+/// are only overwritten as allowed by [`confirm_overwrite()`].
+///
+/// # Errors
+///
+/// Returns an error string if the key must not be written to `path`.
+pub(crate) fn check_key_out(path: &Path, force: bool, no_interaction: bool) -> Result<(), String> {
+    let path = std::env::current_dir()
+        .map_err(|e| format!("Cannot read the current directory: {}", e))?
+        .join(path);
+    let parent = path.parent().unwrap_or(&path);
+    if !parent.is_dir() {
+        return Err(format!("Cannot write the key to {}: {} is not a directory", path.display(), parent.display()));
+    }
+    if !force {
+        if let Some(repository) = parent.ancestors().find(|dir| dir.join(".git").exists()) {
+            return Err(format!(
+                "Refusing to write the key to {}, which is inside the Git repository {}. Use --force to write it there anyway.",
+                path.display(),
+                repository.display(),
+            ));
+        }
+    }
+    confirm_overwrite(&path, force, no_interaction)
+}
+
+/// Reports the outcome of copying the key, returning whether it was copied.
+fn report_copy(output_config: &OutputConfig, result: Result<String, String>) -> bool {
+    match result {
+        Ok(message) => {
+            success(output_config, &message);
+            true
+        }
+        Err(e) => {
+            warning(output_config, &format!("Could not copy the encryption key to the clipboard: {}", e));
+            false
+        }
+    }
+}
+
+fn print_key_banner(output_config: &OutputConfig, key: &str, display: KeyDisplay, handed_over: bool, output: &KeyOutput) {
+    styled(output_config, Style::Warning, "\n⚠️  IMPORTANT: Store this encryption key in a safe place!");
+    info(output_config, "   You will need it to decrypt your .env file later.");
+    if display != KeyDisplay::Show {
+        info(output_config, &format!("\n   Key fingerprint: {}", key_fingerprint(key)));
+    }
+    if display == KeyDisplay::Qr {
+        // A QR code that cannot be rendered falls back to asking
+        match key_qr_code(key) {
+            Ok(code) => {
+                info(output_config, "\n   Scan the key, e.g. into a password manager on your phone:\n");
+                info(output_config, &code);
+                info(output_config, "\n   This key will not be shown again. Make sure to save it securely.");
+                return;
+            }
+            Err(e) => warning(output_config, &format!("Could not render the key as a QR code: {}", e)),
+        }
+    }
+
+    let show = match display {
+        KeyDisplay::Show => true,
+        KeyDisplay::Hide => false,
+        // A failed copy falls back to asking
+        _ if handed_over => false,
+        _ => match output.prompt.filter(|prompt| prompt.is_interactive()) {
+            Some(prompt) => confirm(prompt, "   Show the key?").unwrap_or(false),
+            // Fingerprints are for keys the user has; a generated key would be lost
+            None => output.generated,
+        },
+    };
+
+    if show {
+        let message = Zeroizing::new(format!("\n   Encryption key: base64:{}", key));
+        styled(output_config, Style::Emphasis, &message);
+        info(output_config, "\n   This key will not be shown again. Make sure to save it securely.");
+    } else if !handed_over {
+        info(output_config, "\n   The key was not shown. Use --show-key to print it or --copy to copy it to the clipboard.");
+    }
+}
+
+/// Renders `base64:<key>` as a QR code of Unicode half blocks. The modules are drawn
+/// light on dark, as terminals usually have a dark background.
+fn key_qr_code(key: &str) -> Result<Zeroizing<String>, String> {
+    let text = Zeroizing::new(format!("base64:{}", key));
+    let code = QrCode::new(text.as_bytes()).map_err(|e| e.to_string())?;
+    Ok(Zeroizing::new(code.render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build()))
+}
+
+/// Copies `text` to the clipboard with the platform's clipboard tool, returning its name.
+fn copy_to_clipboard(text: &str) -> Result<&'static str, String> {
+    let candidates: &[(&'static str, &[&str])] = if cfg!(windows) {
+        &[("clip", &[])]
+    } else if cfg!(target_os = "macos") {
+        &[("pbcopy", &[])]
+    } else if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        &[("wl-copy", &[]), ("xclip", &["-selection", "clipboard"]), ("xsel", &["--clipboard", "--input"])]
+    } else {
+        &[("xclip", &["-selection", "clipboard"]), ("xsel", &["--clipboard", "--input"])]
+    };
+
+    let mut errors = Vec::new();
+    for (program, args) in candidates {
+        // xclip and xsel leave a process behind to serve the clipboard, which would keep
+        // piped output open, so it is discarded
+        let child = Command::new(program)
+            .args(*args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                errors.push(format!("{}: {}", program, e));
+                continue;
+            }
+        };
+        let written = child.stdin.take()
+            .map(|mut stdin| stdin.write_all(text.as_bytes()))
+            .unwrap_or(Ok(()));
+        let status = child.wait();
+        match (written, status) {
+            (Ok(()), Ok(status)) if status.success() => return Ok(program),
+            (Err(e), _) | (_, Err(e)) => errors.push(format!("{}: {}", program, e)),
+            (_, Ok(status)) => errors.push(format!("{}: {}", program, status)),
+        }
+    }
+    Err(format!("no clipboard tool worked ({})", errors.join(", ")))
+}
+
+/// Puts `text` on the system clipboard. The returned handle keeps serving it on Linux,
+/// where the clipboard belongs to a running program.
+#[cfg(feature = "clipboard")]
+fn copy_with_arboard(text: &str) -> Result<arboard::Clipboard, String> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|e| e.to_string())?;
+    clipboard.set_text(text).map_err(|e| e.to_string())?;
+    Ok(clipboard)
+}
+
+/// Waits `clear_after`, then clears the clipboard unless something else was copied since.
+#[cfg(feature = "clipboard")]
+fn clear_after_timeout(output_config: &OutputConfig, mut clipboard: arboard::Clipboard, key: &str, clear_after: Duration) {
+    {
+        let _spinner = Spinner::start(output_config, &format!("Clearing the clipboard in {} seconds", clear_after.as_secs()));
+        std::thread::sleep(clear_after);
+    }
+
+    let still_copied = clipboard.get_text().map(|text| Zeroizing::new(text).as_str() == key);
+    match still_copied {
+        Ok(false) => info(output_config, "The clipboard changed since the key was copied, so it was left as is"),
+        Ok(true) | Err(_) => match clipboard.clear() {
+            Ok(()) => success(output_config, "Cleared the encryption key from the clipboard"),
+            Err(e) => warning(output_config, &format!("Could not clear the clipboard: {}", e)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::prompt::ScriptedPrompt;
+
+    #[test]
+    fn test_asks_to_show_key_only_by_default() {
+        let output_config = OutputConfig::new(false, false, 0);
+        let prompt = ScriptedPrompt::new(["n"]);
+        show_encryption_key(&output_config, "a2V5", &KeyOutput { prompt: Some(&prompt), ..KeyOutput::default() }).unwrap();
+        assert_eq!(prompt.transcript(), ["   Show the key? [y/N] "]);
+
+        for display in [KeyDisplay::Show, KeyDisplay::Hide] {
+            let prompt = ScriptedPrompt::new(Vec::<String>::new());
+            show_encryption_key(&output_config, "a2V5", &KeyOutput { display, prompt: Some(&prompt), ..KeyOutput::default() }).unwrap();
+            assert!(prompt.transcript().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_quiet_output_does_not_ask() {
+        let prompt = ScriptedPrompt::new(Vec::<String>::new());
+        let output = KeyOutput { prompt: Some(&prompt), ..KeyOutput::default() };
+        show_encryption_key(&OutputConfig::new(false, true, 0), "a2V5", &output).unwrap();
+        assert!(prompt.transcript().is_empty());
+    }
+
+    #[test]
+    fn test_generated_key_is_not_hidden_silently() {
+        let generated = KeyOutput { generated: true, ..KeyOutput::default() };
+        let (quiet, silent) = (OutputConfig::new(false, true, 0), OutputConfig::new(true, false, 0));
+        show_encryption_key(&quiet, "a2V5", &generated).unwrap();
+        let error = show_encryption_key(&silent, "a2V5", &generated).unwrap_err();
+        assert!(error.contains("--silent hid the generated encryption key"), "{}", error);
+
+        // Keys the user chose, and keys hidden on request, are not handed over
+        show_encryption_key(&silent, "a2V5", &KeyOutput::default()).unwrap();
+        show_encryption_key(&silent, "a2V5", &KeyOutput { display: KeyDisplay::Hide, ..generated }).unwrap();
+    }
+
+    #[test]
+    fn test_generated_key_output_is_checked() {
+        let (normal, silent) = (OutputConfig::new(false, false, 0), OutputConfig::new(true, false, 0));
+        let prompt = ScriptedPrompt::new(Vec::<String>::new());
+        check_generated_key_output(&normal, &KeyOutput { prompt: Some(&prompt), ..KeyOutput::default() }).unwrap();
+        check_generated_key_output(&normal, &KeyOutput { display: KeyDisplay::Show, ..KeyOutput::default() }).unwrap();
+        check_generated_key_output(&OutputConfig::new(false, true, 0), &KeyOutput { display: KeyDisplay::Qr, ..KeyOutput::default() }).unwrap();
+        // Nobody can be asked, so the generated key is printed
+        check_generated_key_output(&normal, &KeyOutput::default()).unwrap();
+        check_generated_key_output(&OutputConfig::new(false, true, 0), &KeyOutput::default()).unwrap();
+
+        // Hidden on request, or silenced
+        let error = check_generated_key_output(&normal, &KeyOutput { display: KeyDisplay::Hide, ..KeyOutput::default() }).unwrap_err();
+        assert!(error.contains("would not be shown"), "{}", error);
+        assert!(check_generated_key_output(&silent, &KeyOutput::default()).is_err());
+        assert!(check_generated_key_output(&normal, &KeyOutput { display: KeyDisplay::Hide, prompt: Some(&prompt), ..KeyOutput::default() }).is_err());
+        let error = check_generated_key_output(&silent, &KeyOutput { display: KeyDisplay::Show, ..KeyOutput::default() }).unwrap_err();
+        assert!(error.contains("--silent would hide"), "{}", error);
+
+        check_generated_key_output(&silent, &KeyOutput { key_out: Some(Path::new("key")), ..KeyOutput::default() }).unwrap();
+        check_generated_key_output(&silent, &KeyOutput { display: KeyDisplay::Copy, ..KeyOutput::default() }).unwrap();
+        check_generated_key_output(&silent, &KeyOutput { allow_lost_key: true, ..KeyOutput::default() }).unwrap();
+    }
+
+    #[test]
+    fn test_key_out_writes_key_without_asking() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("key");
+        let prompt = ScriptedPrompt::new(Vec::<String>::new());
+        let output = KeyOutput { key_out: Some(&path), prompt: Some(&prompt), ..KeyOutput::default() };
+        show_encryption_key(&OutputConfig::new(false, false, 0), "a2V5", &output).unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "base64:a2V5\n");
+        assert!(prompt.transcript().is_empty());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_key_out_refused_inside_repository() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join(".git")).unwrap();
+        std::fs::create_dir_all(dir.path().join("config")).unwrap();
+        let path = dir.path().join("config").join("key");
+
+        let error = check_key_out(&path, false, true).unwrap_err();
+        assert!(error.contains("inside the Git repository"), "{}", error);
+        check_key_out(&path, true, true).unwrap();
+        assert!(check_key_out(&dir.path().join("missing").join("key"), true, true).is_err());
+    }
+}
@@ -0,0 +1,89 @@
+//! Splitting keys into Shamir shares and recovering them (`envcrypt key split` / `key recover`).
+
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::shamir::{self, ShamirError, Share};
+use super::key_handling::{decode_key, read_secret, KeyFormat};
+
+/// Splits a key into `shares` shares, any `threshold` of which recover it.
+///
+/// The key is normalized with [`decode_key()`] first, so the recovered key is the same
+/// string envcrypt would derive the encryption keys from.
+///
+/// # Arguments
+///
+/// * `key_arg` - Key provided via `--key`; prompted for if `None`
+/// * `key_format` - How the key is encoded
+/// * `shares` - Number of shares to create
+/// * `threshold` - Number of shares needed to recover the key
+/// * `no_interaction` - If `true`, fail instead of prompting for a missing key
+///
+/// # Returns
+///
+/// Returns the encoded shares, one per team member.
+pub fn split_key(key_arg: Option<&str>, key_format: KeyFormat, shares: u8, threshold: u8, no_interaction: bool) -> Result<Vec<String>, String> {
+    let key = match key_arg {
+        Some(key) => decode_key(key, key_format)?,
+        None if no_interaction => {
+            return Err("A key is required when using --no-interaction. Please provide --key".to_string());
+        }
+        None => decode_key(&read_secret("Enter key to split: ")?, key_format)?,
+    };
+
+    shamir::split(key.as_bytes(), shares, threshold)
+        .map(|shares| shares.iter().map(Share::encode).collect())
+        .map_err(|e| e.to_string())
+}
+
+/// Recovers a key from its shares.
+///
+/// If no shares are given on the command line, they are prompted for one at a time until
+/// the threshold recorded in the first share is reached.
+///
+/// # Arguments
+///
+/// * `share_args` - Shares provided via `--share`
+/// * `no_interaction` - If `true`, fail instead of prompting when no shares are given
+///
+/// # Returns
+///
+/// Returns the recovered key, which is zeroized when dropped.
+pub fn recover_key(share_args: &[String], no_interaction: bool) -> Result<Zeroizing<String>, String> {
+    let mut shares = share_args.iter()
+        .enumerate()
+        .map(|(position, s)| decode_share(s, position + 1))
+        .collect::<Result<Vec<Share>, _>>()?;
+
+    if shares.is_empty() {
+        if no_interaction {
+            return Err("Shares are required when using --no-interaction. Please provide --share".to_string());
+        }
+        loop {
+            let share = read_secret(&format!("Enter share {}: ", shares.len() + 1))?;
+            shares.push(decode_share(&share, shares.len() + 1)?);
+            if shares.len() >= shares[0].threshold as usize {
+                break;
+            }
+        }
+    }
+
+    let secret = shamir::combine(&shares).map_err(|e| e.to_string())?;
+    for share in shares.iter_mut() {
+        share.data.zeroize();
+    }
+    String::from_utf8(secret)
+        .map(Zeroizing::new)
+        .map_err(|e| {
+            e.into_bytes().zeroize();
+            "Cannot recover secret: the shares do not belong to the same key".to_string()
+        })
+}
+
+/// Parses the share given at `position` (counting from 1), naming the position if it is
+/// invalid.
+fn decode_share(text: &str, position: usize) -> Result<Share, String> {
+    Share::decode(text).map_err(|e| match e {
+        ShamirError::InvalidShare(reason) => format!("Invalid share {}: {}", position, reason),
+        e => e.to_string(),
+    })
+}
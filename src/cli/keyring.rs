@@ -0,0 +1,369 @@
+//! Team keyring (`.envcrypt.keys`): an encrypted file mapping environment names to keys.
+//!
+//! The keyring is encrypted like an env file, with a master passphrase taken from
+//! `ENVCRYPT_KEYRING_PASSPHRASE`, the OS keychain (`keyring_keychain = true` in
+//! `envcrypt.toml`) or a prompt. When it exists, it provides the key for commands that
+//! are given neither `--key` nor a key provider: `envcrypt decrypt --env staging` uses the
+//! `staging` key, and commands without `--env` use the `default` key.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use zeroize::Zeroizing;
+
+use crate::armor::decode_file;
+use crate::cli::attributes::{write_with_mode, FileAttributes};
+use crate::cli::decrypt::{decrypt_contents, DecryptOptions};
+use crate::cli::encrypt::{encrypt_contents, EncryptOptions};
+use crate::cli::key_handling::{generate_base64_key, KeyFormat, KeyProvider, KeyRequest, PromptKeyProvider, UserKey};
+use crate::cli::output::OutputConfig;
+use crate::cli::process::run_program;
+use crate::cli::prompt::{Prompt, TerminalPrompt};
+
+/// Default keyring file, in the current directory.
+pub const KEYRING_FILE: &str = ".envcrypt.keys";
+
+/// Environment variable holding the master passphrase of the keyring.
+pub const KEYRING_PASSPHRASE_ENV_VAR: &str = "ENVCRYPT_KEYRING_PASSPHRASE";
+
+/// Environment whose key is used by commands without `--env`.
+pub const DEFAULT_ENVIRONMENT: &str = "default";
+
+/// Service name the master passphrase is stored under in the OS keychain.
+const KEYCHAIN_SERVICE: &str = "envcrypt-keyring";
+
+/// Cipher the keyring is encrypted with.
+const KEYRING_CIPHER: &str = "AES-256-GCM";
+
+/// Where the keyring is and how it is unlocked.
+#[derive(Debug, Clone)]
+pub struct KeyringOptions {
+    /// Path of the keyring file.
+    pub path: PathBuf,
+    /// Keep the master passphrase in the OS keychain instead of asking for it.
+    pub use_keychain: bool,
+    /// If `true`, never prompt for the passphrase.
+    pub no_interaction: bool,
+    /// PBKDF2 iteration count used when the keyring is written.
+    pub kdf_iterations: u32,
+}
+
+/// An unlocked keyring.
+pub struct Keyring {
+    options: KeyringOptions,
+    passphrase: Zeroizing<String>,
+    keys: BTreeMap<String, Zeroizing<String>>,
+}
+
+impl Keyring {
+    /// Unlocks the keyring at `options.path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error string if the keyring does not exist, no passphrase is available
+    /// or it is wrong.
+    pub fn open(options: &KeyringOptions, prompt: &dyn Prompt) -> Result<Self, String> {
+        if !options.path.exists() {
+            return Err(format!("Keyring {} not found. Add a key with `envcrypt key add <ENV>`.", options.path.display()));
+        }
+        let passphrase = passphrase(options, prompt, false)?;
+        let contents = std::fs::read(&options.path)
+            .map_err(|e| format!("Error reading {}: {}", options.path.display(), e))?;
+        let (data, _) = decode_file(&contents)?;
+        let decrypt_options = DecryptOptions {
+            no_interaction: true,
+            key_format: KeyFormat::Utf8,
+            ..DecryptOptions::default()
+        };
+        let (_, plaintext) = decrypt_contents(&data, None, Some(&passphrase), &OutputConfig::new(false, true, 0), &decrypt_options)
+            .map_err(|e| format!("Cannot unlock {}: {}", options.path.display(), e))?;
+        Ok(Self { options: options.clone(), passphrase, keys: parse_keyring(&plaintext)? })
+    }
+
+    /// Unlocks the keyring at `options.path`, or starts a new one if it does not exist.
+    /// A new keyring asks for a new passphrase (or stores a generated one in the OS
+    /// keychain) and is only written by [`Keyring::save()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error string if the keyring cannot be unlocked or no passphrase is
+    /// available.
+    pub fn open_or_create(options: &KeyringOptions, prompt: &dyn Prompt) -> Result<Self, String> {
+        if options.path.exists() {
+            return Self::open(options, prompt);
+        }
+        let passphrase = passphrase(options, prompt, true)?;
+        Ok(Self { options: options.clone(), passphrase, keys: BTreeMap::new() })
+    }
+
+    /// Returns the key of `environment`, if the keyring has one.
+    pub fn get(&self, environment: &str) -> Option<&str> {
+        self.keys.get(environment).map(|key| key.as_str())
+    }
+
+    /// Returns the environment names and their keys, sorted by name.
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.keys.iter().map(|(name, key)| (name.as_str(), key.as_str()))
+    }
+
+    /// Sets the key of `environment`, returning `true` if it replaced another key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error string if `environment` is not a valid name.
+    pub fn insert(&mut self, environment: &str, key: Zeroizing<String>) -> Result<bool, String> {
+        validate_environment(environment)?;
+        Ok(self.keys.insert(environment.to_string(), key).is_some())
+    }
+
+    /// Removes the key of `environment`, returning `true` if there was one.
+    pub fn remove(&mut self, environment: &str) -> bool {
+        self.keys.remove(environment).is_some()
+    }
+
+    /// Encrypts the keyring and writes it, readable only by the owner.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error string if the keyring cannot be encrypted or written.
+    pub fn save(&self) -> Result<(), String> {
+        let mut plaintext = Zeroizing::new(String::from("# envcrypt keyring: <environment>=<key>\n"));
+        for (name, key) in &self.keys {
+            plaintext.push_str(&format!("{}={}\n", name, key.as_str()));
+        }
+        let encrypt_options = EncryptOptions {
+            kdf_iterations: self.options.kdf_iterations,
+            ..EncryptOptions::default()
+        };
+        let contents = encrypt_contents(
+            KEYRING_CIPHER,
+            plaintext.as_bytes(),
+            &FileAttributes::default(),
+            &self.passphrase,
+            false,
+            &OutputConfig::new(false, true, 0),
+            &encrypt_options,
+        )?;
+        write_with_mode(&self.options.path, &contents, Some(0o600))
+            .map_err(|e| format!("Error writing {}: {}", self.options.path.display(), e))
+    }
+}
+
+/// Provides the key of one environment from the keyring (`keyring` key provider).
+///
+/// When encrypting an environment the keyring has no key for, the key is asked for (or
+/// generated) as with the `prompt` provider.
+pub struct KeyringKeyProvider {
+    /// Where the keyring is and how it is unlocked.
+    pub options: KeyringOptions,
+    /// Environment whose key is provided.
+    pub environment: String,
+}
+
+impl KeyProvider for KeyringKeyProvider {
+    fn name(&self) -> &str {
+        "keyring"
+    }
+
+    fn provide(&self, request: &KeyRequest) -> Result<UserKey, String> {
+        let options = KeyringOptions { no_interaction: request.no_interaction, ..self.options.clone() };
+        let keyring = Keyring::open(&options, &TerminalPrompt)?;
+        match keyring.get(&self.environment) {
+            // Keys are stored decoded, as `key add` reads them
+            Some(key) => Ok(UserKey::provided(Zeroizing::new(key.to_string()))),
+            None if request.is_encrypt => PromptKeyProvider::default().provide(request),
+            None => Err(format!(
+                "Keyring {} has no key for environment '{}'. Add one with `envcrypt key add {}`.",
+                options.path.display(),
+                self.environment,
+                self.environment,
+            )),
+        }
+    }
+}
+
+/// Parses the decrypted keyring: `<environment>=<key>` lines, ignoring blank lines and
+/// `#` comments.
+fn parse_keyring(plaintext: &str) -> Result<BTreeMap<String, Zeroizing<String>>, String> {
+    let mut keys = BTreeMap::new();
+    for (number, line) in plaintext.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (name, key) = line.split_once('=')
+            .ok_or_else(|| format!("Invalid keyring entry on line {}", number + 1))?;
+        keys.insert(name.to_string(), Zeroizing::new(key.to_string()));
+    }
+    Ok(keys)
+}
+
+/// Checks that `environment` can be used as a keyring entry name.
+fn validate_environment(environment: &str) -> Result<(), String> {
+    let valid = !environment.is_empty()
+        && environment.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid environment name '{}': use letters, digits, '-', '_' and '.'", environment))
+    }
+}
+
+/// Gets the master passphrase: from `ENVCRYPT_KEYRING_PASSPHRASE`, the OS keychain or a
+/// prompt. For a new keyring (`create`), the keychain gets a generated passphrase and the
+/// prompt asks twice.
+fn passphrase(options: &KeyringOptions, prompt: &dyn Prompt, create: bool) -> Result<Zeroizing<String>, String> {
+    if let Ok(passphrase) = std::env::var(KEYRING_PASSPHRASE_ENV_VAR) {
+        return Ok(Zeroizing::new(passphrase.trim().to_string()));
+    }
+
+    if options.use_keychain {
+        let account = keychain_account(&options.path)?;
+        if let Some(passphrase) = keychain_get(&account)? {
+            return Ok(passphrase);
+        }
+        if !create {
+            return Err(format!("No passphrase for {} in the OS keychain", options.path.display()));
+        }
+        let passphrase = generate_base64_key();
+        keychain_set(&account, &passphrase)?;
+        return Ok(passphrase);
+    }
+
+    if options.no_interaction || !prompt.is_interactive() {
+        return Err(format!(
+            "The keyring {} is locked - set {} or keyring_keychain = true in envcrypt.toml",
+            options.path.display(),
+            KEYRING_PASSPHRASE_ENV_VAR,
+        ));
+    }
+    if !create {
+        return prompt.read_secret("Keyring passphrase: ");
+    }
+    let passphrase = prompt.read_secret("New keyring passphrase: ")?;
+    if passphrase.is_empty() {
+        return Err("The keyring passphrase must not be empty".to_string());
+    }
+    if *prompt.read_secret("Repeat the keyring passphrase: ")? != *passphrase {
+        return Err("The passphrases do not match".to_string());
+    }
+    Ok(passphrase)
+}
+
+/// Keychain account of a keyring: its absolute path, so each keyring has its own entry.
+fn keychain_account(path: &Path) -> Result<String, String> {
+    let path = std::env::current_dir()
+        .map_err(|e| format!("Cannot read the current directory: {}", e))?
+        .join(path);
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// Reads the passphrase stored for `account`, or `None` if there is none.
+fn keychain_get(account: &str) -> Result<Option<Zeroizing<String>>, String> {
+    let output = if cfg!(target_os = "macos") {
+        run_program("security", &["find-generic-password", "-s", KEYCHAIN_SERVICE, "-a", account, "-w"], None)?
+    } else if cfg!(unix) {
+        run_program("secret-tool", &["lookup", "service", KEYCHAIN_SERVICE, "path", account], None)?
+    } else {
+        return Err(keychain_unsupported());
+    };
+    if !output.success {
+        return Ok(None);
+    }
+    let passphrase = Zeroizing::new(String::from_utf8_lossy(&output.stdout).trim().to_string());
+    Ok(Some(passphrase).filter(|passphrase| !passphrase.is_empty()))
+}
+
+/// Stores `passphrase` for `account`.
+fn keychain_set(account: &str, passphrase: &str) -> Result<(), String> {
+    let output = if cfg!(target_os = "macos") {
+        // `security -i` reads the command from stdin, which keeps the passphrase out of
+        // the process list
+        if account.contains('"') {
+            return Err(format!("Cannot store a passphrase for {} in the keychain: the path contains a quote", account));
+        }
+        let command = Zeroizing::new(format!(
+            "add-generic-password -U -s {} -a \"{}\" -w {}\n",
+            KEYCHAIN_SERVICE, account, passphrase,
+        ));
+        run_program("security", &["-i"], Some(command.as_bytes()))?
+    } else if cfg!(unix) {
+        let label = format!("envcrypt keyring {}", account);
+        run_program(
+            "secret-tool",
+            &["store", "--label", &label, "service", KEYCHAIN_SERVICE, "path", account],
+            Some(passphrase.as_bytes()),
+        )?
+    } else {
+        return Err(keychain_unsupported());
+    };
+    if output.success {
+        Ok(())
+    } else {
+        Err(format!("Cannot store the keyring passphrase in the OS keychain: {}", output.stderr))
+    }
+}
+
+fn keychain_unsupported() -> String {
+    "The OS keychain is supported on macOS (security) and Linux (secret-tool); use ENVCRYPT_KEYRING_PASSPHRASE instead".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::prompt::ScriptedPrompt;
+
+    fn options(dir: &Path) -> KeyringOptions {
+        KeyringOptions {
+            path: dir.join(KEYRING_FILE),
+            use_keychain: false,
+            no_interaction: false,
+            kdf_iterations: crate::key::MIN_PBKDF2_ITERATIONS,
+        }
+    }
+
+    #[test]
+    fn test_keyring_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = options(dir.path());
+        let prompt = ScriptedPrompt::new(["correct horse", "correct horse"]);
+        let mut keyring = Keyring::open_or_create(&options, &prompt).unwrap();
+        assert!(!keyring.insert("staging", Zeroizing::new("base64:c3RhZ2luZw==".to_string())).unwrap());
+        assert!(!keyring.insert("default", Zeroizing::new("base64:ZGVmYXVsdA==".to_string())).unwrap());
+        keyring.save().unwrap();
+
+        let prompt = ScriptedPrompt::new(["correct horse"]);
+        let mut keyring = Keyring::open(&options, &prompt).unwrap();
+        assert_eq!(keyring.get("staging"), Some("base64:c3RhZ2luZw=="));
+        assert_eq!(keyring.entries().map(|(name, _)| name).collect::<Vec<_>>(), ["default", "staging"]);
+        assert!(keyring.remove("staging"));
+        assert!(!keyring.remove("staging"));
+
+        let prompt = ScriptedPrompt::new(["wrong horse"]);
+        assert!(Keyring::open(&options, &prompt).is_err());
+    }
+
+    #[test]
+    fn test_new_passphrase_must_match() {
+        let dir = tempfile::tempdir().unwrap();
+        let prompt = ScriptedPrompt::new(["correct horse", "correct hose"]);
+        let error = Keyring::open_or_create(&options(dir.path()), &prompt).err().unwrap();
+        assert_eq!(error, "The passphrases do not match");
+    }
+
+    #[test]
+    fn test_locked_keyring_without_interaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = KeyringOptions { no_interaction: true, ..options(dir.path()) };
+        let error = Keyring::open_or_create(&options, &ScriptedPrompt::default()).err().unwrap();
+        assert!(error.contains(KEYRING_PASSPHRASE_ENV_VAR), "{}", error);
+    }
+
+    #[test]
+    fn test_environment_names() {
+        assert!(validate_environment("staging").is_ok());
+        assert!(validate_environment("eu-west.prod_2").is_ok());
+        assert!(validate_environment("").is_err());
+        assert!(validate_environment("a=b").is_err());
+    }
+}
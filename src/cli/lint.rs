@@ -0,0 +1,230 @@
+//! Finding dotenv syntax problems before they reach a deployment (`envcrypt lint`).
+
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use zeroize::Zeroizing;
+
+use crate::armor::looks_encrypted;
+use crate::cli::audit::note_read;
+use crate::cli::decrypt::{decrypt_file, DecryptOptions};
+use crate::cli::dotenv::{dotenv_lines, is_valid_key, parse_value};
+use crate::cli::encoding::{decode_text, Encoding};
+use crate::cli::key_handling::{KeyFormat, KeyProvider};
+use crate::cli::memory::lock_secret;
+use crate::cli::output::{success, OutputConfig};
+
+/// Options controlling how [`lint_env()`] decrypts encrypted files.
+#[derive(Default)]
+pub struct LintOptions {
+    /// Skip interactive prompts (error if no key is provided).
+    pub no_interaction: bool,
+    /// Encoding of the provided or prompted key.
+    pub key_format: KeyFormat,
+    /// Lock the key, derived keys, and plaintext into RAM so they are not swapped to disk.
+    pub mlock: bool,
+    /// Context string the file was bound to when it was encrypted.
+    pub aad: Option<String>,
+    /// Where to get the key when none is passed as `key_arg` (default: interactive prompt).
+    pub key_provider: Option<Rc<dyn KeyProvider>>,
+    /// Encoding of plaintext files.
+    pub encoding: Encoding,
+}
+
+/// A problem found by [`lint_dotenv()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintIssue {
+    /// Line the problem is on, starting at 1.
+    pub line: usize,
+    /// Description of the problem; never includes a value.
+    pub message: String,
+}
+
+/// Checks a plaintext or encrypted env file for syntax problems (see [`lint_dotenv()`]).
+///
+/// Encrypted files are recognized by their contents and decrypted in memory, which
+/// needs the key; plaintext files are checked as they are.
+///
+/// # Returns
+///
+/// Returns the number of lines checked.
+///
+/// # Errors
+///
+/// Returns an error string if the file cannot be read or decrypted or is not text in
+/// `options.encoding`, or, listing every issue as `path:line: message`, if any problem is found.
+///
+/// # Example
+///
+/// ```no_run
+/// use envcrypt::cli::{lint_env, LintOptions, OutputConfig};
+///
+/// let output_config = OutputConfig::new(false, false, 0);
+/// lint_env(None, ".env", &output_config, &LintOptions::default())?;
+/// # Ok::<(), String>(())
+/// ```
+pub fn lint_env(
+    key_arg: Option<&str>,
+    path: &str,
+    output_config: &OutputConfig,
+    options: &LintOptions,
+) -> Result<usize, String> {
+    if !Path::new(path).exists() {
+        return Err(format!("{} file not found", path));
+    }
+    let contents = Zeroizing::new(fs::read(path).map_err(|e| format!("Error reading {} file: {}", path, e))?);
+    note_read(path);
+
+    let _span = tracing::info_span!("lint", input = path).entered();
+    let text = if looks_encrypted(&contents) {
+        tracing::debug!("{} is encrypted", path);
+        let decrypt_options = DecryptOptions {
+            no_interaction: options.no_interaction,
+            key_format: options.key_format,
+            mlock: options.mlock,
+            aad: options.aad.clone(),
+            key_provider: options.key_provider.clone(),
+            ..DecryptOptions::default()
+        };
+        decrypt_file(key_arg, path, output_config, &decrypt_options)?.1
+    } else {
+        decode_text(&contents, options.encoding).map_err(|e| format!("{} is {}", path, e))?
+    };
+    let _plaintext_lock = lock_secret(output_config, options.mlock, text.as_bytes());
+
+    let issues = lint_dotenv(&text);
+    if !issues.is_empty() {
+        let noun = if issues.len() == 1 { "problem" } else { "problems" };
+        let list: Vec<String> = issues.iter()
+            .map(|issue| format!("  {}:{}: {}", path, issue.line, issue.message))
+            .collect();
+        return Err(format!("Found {} {} in {}:\n{}", issues.len(), noun, path, list.join("\n")));
+    }
+
+    let lines = text.lines().count();
+    success(output_config, &format!("{}: no problems found in {} lines", path, lines));
+    Ok(lines)
+}
+
+/// Finds the dotenv syntax problems that tools disagree about or that are usually
+/// mistakes:
+///
+/// - lines that are not `KEY=value` assignments, comments or blank, and invalid names
+/// - quoted values that are never closed
+/// - variables assigned more than once
+/// - unquoted values containing spaces, which shells and some loaders cut off
+/// - trailing whitespace, which some loaders keep as part of the value
+/// - a mix of CRLF and LF line endings (reported once, on the first line that differs)
+///
+/// # Example
+///
+/// ```
+/// use envcrypt::cli::lint_dotenv;
+///
+/// let issues = lint_dotenv("GREETING=hello world\nGREETING=hi\n");
+/// assert_eq!(issues[0].message, "unquoted value of GREETING contains spaces; quote it");
+/// assert_eq!(issues[1].message, "GREETING is already assigned on line 1");
+/// ```
+pub fn lint_dotenv(text: &str) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+    let mut assigned: Vec<(&str, usize)> = Vec::new();
+    let mut first_ending: Option<bool> = None;
+    let mut mixed_endings_reported = false;
+
+    for (number, raw_line) in dotenv_lines(text) {
+        let mut issue = |message: String| issues.push(LintIssue { line: number, message });
+
+        let crlf = raw_line.ends_with("\r\n");
+        let line = raw_line.strip_suffix('\n').map(|line| line.strip_suffix('\r').unwrap_or(line)).unwrap_or(raw_line);
+        if raw_line.ends_with('\n') {
+            match first_ending {
+                None => first_ending = Some(crlf),
+                Some(first) if first != crlf && !mixed_endings_reported => {
+                    let name = |crlf: bool| if crlf { "CRLF" } else { "LF" };
+                    issue(format!("line ends with {}, but earlier lines end with {}", name(crlf), name(first)));
+                    mixed_endings_reported = true;
+                }
+                Some(_) => {}
+            }
+        }
+        if line.ends_with([' ', '\t']) {
+            issue("trailing whitespace".to_string());
+        }
+
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            issue("expected KEY=value".to_string());
+            continue;
+        };
+        let key = key.trim_end();
+        if !is_valid_key(key) {
+            issue(format!("{} is not a valid variable name", key));
+            continue;
+        }
+
+        let value = value.trim_start();
+        if let Err(e) = parse_value(value, key, None) {
+            issue(e);
+        } else if !value.starts_with(['"', '\'']) {
+            let end = value.find(" #").or_else(|| value.find("\t#")).unwrap_or(value.len());
+            if value[..end].trim_end().contains([' ', '\t']) {
+                issue(format!("unquoted value of {} contains spaces; quote it", key));
+            }
+        }
+
+        match assigned.iter().find(|(name, _)| *name == key) {
+            Some((_, first)) => issue(format!("{} is already assigned on line {}", key, first)),
+            None => assigned.push((key, number)),
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(text: &str) -> Vec<(usize, String)> {
+        lint_dotenv(text).into_iter().map(|issue| (issue.line, issue.message)).collect()
+    }
+
+    #[test]
+    fn test_lint_clean_file() {
+        let text = "# Database\nexport DB_URL=postgres://localhost/app # local\n\nGREETING=\"hello world\"\nNAME='a b'\nEMPTY=\n";
+        assert!(lint_dotenv(text).is_empty());
+        assert!(lint_dotenv("A=1\r\nB=2\r\n").is_empty());
+        assert!(lint_dotenv("").is_empty());
+        // Multi-line values take up several lines; the lines after them are numbered as such
+        assert_eq!(messages("KEY=\"-----BEGIN KEY-----\nabc def\n-----END KEY-----\"\nKEY=2\n"), vec![
+            (4, "KEY is already assigned on line 1".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_lint_issues() {
+        let text = "A=1 \nnot an assignment\n1KEY=x\nB=\"open\nC=two words # comment\nA=2\n\t\n";
+        assert_eq!(messages(text), vec![
+            (1, "trailing whitespace".to_string()),
+            (2, "expected KEY=value".to_string()),
+            (3, "1KEY is not a valid variable name".to_string()),
+            (4, "unterminated quoted value for B".to_string()),
+            (5, "unquoted value of C contains spaces; quote it".to_string()),
+            (6, "A is already assigned on line 1".to_string()),
+            (7, "trailing whitespace".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_lint_mixed_line_endings() {
+        assert_eq!(messages("A=1\r\nB=2\nC=3\nD=4\r\n"), vec![
+            (2, "line ends with LF, but earlier lines end with CRLF".to_string()),
+        ]);
+        // The last line has no line ending to compare
+        assert!(lint_dotenv("A=1\r\nB=2").is_empty());
+    }
+}
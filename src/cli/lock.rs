@@ -0,0 +1,167 @@
+//! Advisory locks that keep envcrypt processes from writing the same file at the same
+//! time (`--wait`, `--no-wait`).
+//!
+//! The lock of a file is a separate lock file in the `locks` directory of the
+//! [`cache_dir()`], named after the absolute path of the file, so no lock files are left
+//! in projects. The operating system releases the lock when the process exits, also when
+//! it is killed, so a crashed run never leaves a file locked.
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
+
+use super::dirs::cache_dir;
+use super::output::{info, OutputConfig};
+
+/// How often a lock held by another process is tried again while waiting with a timeout.
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long to wait for another envcrypt process that is writing the same file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LockWait {
+    /// Wait until the other process is done.
+    #[default]
+    Forever,
+    /// Give up after this long; zero fails at once (`--no-wait`).
+    Timeout(Duration),
+}
+
+/// A lock on a file, held until it is dropped.
+#[derive(Debug)]
+pub struct FileLock {
+    _file: File,
+}
+
+/// Locks `path` against other envcrypt processes for a read-modify-write, waiting as
+/// `wait` allows when another process holds the lock. `path` need not exist yet.
+///
+/// # Errors
+///
+/// Returns an error string saying the file is locked by another envcrypt process if the
+/// lock is not free in time, or if the lock file cannot be created.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use envcrypt::cli::{lock_file, LockWait, OutputConfig};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let output_config = OutputConfig::new(true, false, 0);
+/// let path = dir.path().join(".env.encrypted");
+/// let lock = lock_file(&path, LockWait::Forever, &output_config)?;
+/// assert!(lock_file(&path, LockWait::Timeout(Duration::ZERO), &output_config).is_err());
+/// drop(lock);
+/// assert!(lock_file(&path, LockWait::Timeout(Duration::ZERO), &output_config).is_ok());
+/// # Ok::<(), String>(())
+/// ```
+pub fn lock_file(path: &Path, wait: LockWait, output_config: &OutputConfig) -> Result<FileLock, String> {
+    let lock_path = lock_path(path)?;
+    if let Some(parent) = lock_path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Cannot create the lock directory {}: {}", parent.display(), e))?;
+    }
+    let file = File::options()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&lock_path)
+        .map_err(|e| format!("Cannot create the lock file {}: {}", lock_path.display(), e))?;
+    let lock_error = |e: std::io::Error| format!("Cannot lock {}: {}", path.display(), e);
+
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(fs::TryLockError::Error(e)) => return Err(lock_error(e)),
+        Err(fs::TryLockError::WouldBlock) => match wait {
+            LockWait::Timeout(timeout) if timeout.is_zero() => {
+                return Err(format!(
+                    "{} is locked by another envcrypt process; try again when it is done, or leave out --no-wait to wait for it",
+                    path.display()
+                ));
+            }
+            LockWait::Timeout(timeout) => {
+                info(output_config, &format!("Waiting up to {}s for another envcrypt process writing {}", timeout.as_secs(), path.display()));
+                let deadline = Instant::now() + timeout;
+                loop {
+                    std::thread::sleep(RETRY_INTERVAL.min(deadline.saturating_duration_since(Instant::now())));
+                    match file.try_lock() {
+                        Ok(()) => break,
+                        Err(fs::TryLockError::Error(e)) => return Err(lock_error(e)),
+                        Err(fs::TryLockError::WouldBlock) if Instant::now() >= deadline => {
+                            return Err(format!(
+                                "{} is still locked by another envcrypt process after {}s",
+                                path.display(),
+                                timeout.as_secs()
+                            ));
+                        }
+                        Err(fs::TryLockError::WouldBlock) => {}
+                    }
+                }
+            }
+            LockWait::Forever => {
+                info(output_config, &format!("Waiting for another envcrypt process writing {}", path.display()));
+                file.lock().map_err(lock_error)?;
+            }
+        },
+    }
+    tracing::debug!("Locked {} with {}", path.display(), lock_path.display());
+    Ok(FileLock { _file: file })
+}
+
+/// Returns the lock file of `path`: `<file name>-<hash of the absolute path>.lock` in the
+/// `locks` directory of the cache directory, or of the temporary directory if there is
+/// no cache directory.
+fn lock_path(path: &Path) -> Result<PathBuf, String> {
+    let name = path.file_name()
+        .ok_or_else(|| format!("Cannot lock {}: not a file", path.display()))?;
+    // The file may not exist yet, so resolve its directory, which must
+    let parent = match path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        Some(parent) => parent.to_path_buf(),
+        None => PathBuf::from("."),
+    };
+    let absolute = fs::canonicalize(&parent)
+        .map_err(|e| format!("Cannot lock {}: {}", path.display(), e))?
+        .join(name);
+
+    let hash = Sha256::digest(absolute.to_string_lossy().as_bytes());
+    let hash: String = hash[..8].iter().map(|b| format!("{:02x}", b)).collect();
+    let locks_dir = cache_dir()
+        .map(|dir| dir.join("locks"))
+        .unwrap_or_else(|| std::env::temp_dir().join("envcrypt-locks"));
+    Ok(locks_dir.join(format!("{}-{}.lock", name.to_string_lossy(), hash)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_path_is_per_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = lock_path(&dir.path().join(".env.encrypted")).unwrap();
+        let nested = dir.path().join("config");
+        fs::create_dir(&nested).unwrap();
+        let second = lock_path(&nested.join(".env.encrypted")).unwrap();
+
+        assert_ne!(first, second);
+        assert!(first.file_name().unwrap().to_string_lossy().starts_with(".env.encrypted-"));
+        assert_eq!(first, lock_path(&dir.path().join("config").join("..").join(".env.encrypted")).unwrap());
+    }
+
+    #[test]
+    fn test_lock_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+        let output_config = OutputConfig::new(true, false, 0);
+        let _lock = lock_file(&path, LockWait::Forever, &output_config).unwrap();
+
+        let error = lock_file(&path, LockWait::Timeout(Duration::ZERO), &output_config).unwrap_err();
+        assert!(error.ends_with("is locked by another envcrypt process; try again when it is done, or leave out --no-wait to wait for it"), "{}", error);
+        let started = Instant::now();
+        let error = lock_file(&path, LockWait::Timeout(Duration::from_millis(250)), &output_config).unwrap_err();
+        assert!(error.contains("is still locked by another envcrypt process after"), "{}", error);
+        assert!(started.elapsed() >= Duration::from_millis(250));
+    }
+}
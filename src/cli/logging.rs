@@ -0,0 +1,199 @@
+//! Diagnostic logging with `tracing` (`-v`, `--log-format`, `--log-file`).
+//!
+//! Diagnostics are emitted as `tracing` events: `debug!` for verbose details (`-vv`) and
+//! `trace!` for debug details (`-vvv`). Completed operations and warnings are also
+//! recorded at `info` and `warn` level, so log files and JSON logs contain them even
+//! without `-v`.
+//!
+//! On the terminal, text logs look like the plain output they replace: verbose details
+//! go to stdout and debug details to stderr with a `[DEBUG]` prefix. JSON logs are
+//! written one object per line to stderr, or to `--log-file`.
+
+use std::fmt::Write as _;
+use std::fs::OpenOptions;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use clap::ValueEnum;
+use tracing::field::{Field, Visit};
+use tracing::subscriber::DefaultGuard;
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::Registry;
+
+/// Format of diagnostic logs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per event, for log collectors
+    Json,
+}
+
+/// Logging settings from the global flags.
+#[derive(Debug, Default)]
+pub struct LogConfig {
+    /// Output format.
+    pub format: LogFormat,
+    /// File to append logs to instead of writing them to the terminal.
+    pub file: Option<String>,
+    /// Number of `-v` flags.
+    pub verbose: u8,
+    /// `--quiet`: only errors reach the terminal.
+    pub quiet: bool,
+    /// `--silent`: nothing reaches the terminal.
+    pub silent: bool,
+}
+
+impl LogConfig {
+    /// Most detailed level recorded, following `-v`: `info` by default, `debug` with
+    /// `-vv`, and `trace` with `-vvv`.
+    fn level(&self) -> LevelFilter {
+        match self.verbose {
+            0 | 1 => LevelFilter::INFO,
+            2 => LevelFilter::DEBUG,
+            _ => LevelFilter::TRACE,
+        }
+    }
+
+    /// Level filter for logs written to the terminal, honouring `--quiet`/`--silent`.
+    fn terminal_level(&self) -> LevelFilter {
+        if self.silent {
+            LevelFilter::OFF
+        } else if self.quiet {
+            LevelFilter::ERROR
+        } else {
+            self.level()
+        }
+    }
+}
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Installs the subscriber for the current thread until the returned guard is dropped.
+///
+/// # Errors
+///
+/// Returns an error string if the log file cannot be opened.
+pub fn init(config: &LogConfig) -> Result<DefaultGuard, String> {
+    let mut layers: Vec<BoxedLayer> = Vec::new();
+
+    match (&config.file, config.format) {
+        (None, LogFormat::Text) => {}
+        (None, LogFormat::Json) => layers.push(Box::new(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .with_writer(std::io::stderr)
+                .with_filter(config.terminal_level()),
+        )),
+        (Some(path), format) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| format!("Error opening log file {}: {}", path, e))?;
+            let writer = Mutex::new(file);
+            layers.push(match format {
+                LogFormat::Text => Box::new(
+                    tracing_subscriber::fmt::layer()
+                        .with_ansi(false)
+                        .with_writer(writer)
+                        .with_filter(config.level()),
+                ),
+                LogFormat::Json => Box::new(
+                    tracing_subscriber::fmt::layer()
+                        .json()
+                        .with_writer(writer)
+                        .with_filter(config.level()),
+                ),
+            });
+        }
+    }
+
+    // Text output on the terminal is kept unless JSON logs take its place there
+    if config.format == LogFormat::Text || config.file.is_some() {
+        let level = match config.terminal_level() {
+            level if level >= LevelFilter::DEBUG => level,
+            _ => LevelFilter::OFF,
+        };
+        layers.push(Box::new(TerminalLayer.with_filter(level)));
+    }
+
+    Ok(tracing::subscriber::set_default(Registry::default().with(layers)))
+}
+
+/// Measures how long a step takes and reports it at `-vv`.
+///
+/// The duration is also recorded as the `duration_ms` field, with the step name in
+/// `step`, so JSON logs carry the numbers. Steps that fail before [`Timer::finish()`]
+/// are not reported.
+pub(crate) struct Timer {
+    step: &'static str,
+    started: Instant,
+}
+
+impl Timer {
+    /// Starts timing `step`.
+    pub(crate) fn start(step: &'static str) -> Self {
+        Self { step, started: Instant::now() }
+    }
+
+    /// Logs the time taken since [`Timer::start()`].
+    pub(crate) fn finish(self) {
+        let elapsed = self.started.elapsed();
+        tracing::debug!(
+            step = self.step,
+            duration_ms = elapsed.as_secs_f64() * 1000.0,
+            "{} took {:.2?}", self.step, elapsed
+        );
+    }
+}
+
+/// Prints verbose and debug events the way the CLI always has.
+///
+/// Info, warning and error events are skipped: the CLI already shows them as regular
+/// output.
+struct TerminalLayer;
+
+impl<S: Subscriber> Layer<S> for TerminalLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut message = MessageVisitor::default();
+        event.record(&mut message);
+        match *event.metadata().level() {
+            Level::DEBUG => println!("{}", message.0),
+            Level::TRACE => eprintln!("[DEBUG] {}", message.0),
+            _ => {}
+        }
+    }
+}
+
+/// Collects the message of an event. Other fields are left to structured formats.
+#[derive(Default)]
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, "{:?}", value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels_follow_verbosity() {
+        let config = |verbose, quiet, silent| LogConfig { verbose, quiet, silent, ..Default::default() };
+        assert_eq!(config(0, false, false).level(), LevelFilter::INFO);
+        assert_eq!(config(2, false, false).level(), LevelFilter::DEBUG);
+        assert_eq!(config(3, false, false).terminal_level(), LevelFilter::TRACE);
+        assert_eq!(config(3, true, false).terminal_level(), LevelFilter::ERROR);
+        assert_eq!(config(3, true, true).terminal_level(), LevelFilter::OFF);
+        // Files record everything requested regardless of terminal settings
+        assert_eq!(config(3, false, true).level(), LevelFilter::TRACE);
+    }
+}
@@ -0,0 +1,32 @@
+//! Optional memory locking for secrets handled by the CLI (`--mlock`).
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::memlock::{self, MemoryLock};
+use super::output::{warning, OutputConfig};
+
+/// Set once the "could not lock memory" warning has been shown.
+static LOCK_WARNING_SHOWN: AtomicBool = AtomicBool::new(false);
+
+/// Locks `data` into RAM if `enabled`, falling back gracefully if that is not possible.
+///
+/// The first failure prints a warning; subsequent failures are silent so one command does
+/// not repeat it for every buffer. Keep the returned guard alive for as long as the data.
+pub fn lock_secret(output_config: &OutputConfig, enabled: bool, data: &[u8]) -> Option<MemoryLock> {
+    if !enabled {
+        return None;
+    }
+
+    match memlock::lock(data) {
+        Ok(guard) => {
+            tracing::trace!("Locked {} bytes of secret data in memory", data.len());
+            Some(guard)
+        }
+        Err(e) => {
+            if !LOCK_WARNING_SHOWN.swap(true, Ordering::Relaxed) {
+                warning(output_config, &format!("Could not lock memory ({}); secrets may be swapped to disk", e));
+            }
+            None
+        }
+    }
+}
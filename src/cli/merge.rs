@@ -0,0 +1,146 @@
+//! Combining layered env files into one encrypted file (`envcrypt merge`).
+
+use std::rc::Rc;
+
+use zeroize::Zeroizing;
+
+use crate::format::Kdf;
+use crate::key::KeyMaterial;
+use crate::cli::attributes::FileAttributes;
+use crate::cli::decrypt::{decrypt_file, DecryptOptions};
+use crate::cli::dotenv::parse_dotenv;
+use crate::cli::encoding::UTF8_BOM;
+use crate::cli::encrypt::{check_output, write_encrypted, EncryptOptions};
+use crate::cli::export::{format_variables, ExportFormat};
+use crate::cli::key_handling::{get_key, KeyFormat, KeyRequest};
+use crate::cli::memory::lock_secret;
+use crate::cli::newline::Newline;
+use crate::cli::output::{success, OutputConfig};
+
+/// Decrypts several encrypted environment files and writes their variables, merged,
+/// to a new encrypted file.
+///
+/// Later inputs take precedence: a variable set in more than one input gets the value of
+/// the last one, at the position where it first appeared. Values are copied as they are,
+/// so `${VAR}` references are only expanded when the merged file is used. The result is
+/// written as normalized dotenv (comments are not kept) with the permissions, byte order
+/// mark and line endings of the first input, unless `options.newline` is set.
+///
+/// All inputs must be encrypted with the same key, which is asked for once and also used
+/// for the output. The output uses `cipher_name`, or the cipher of the first input if
+/// `None`, and a raw key (HKDF) if the first input does.
+///
+/// # Returns
+///
+/// Returns the number of variables in the merged file.
+///
+/// # Errors
+///
+/// Returns an error string if fewer than two inputs are given, an input cannot be
+/// decrypted with the key or is not a valid dotenv file, or for any error
+/// [`crate::cli::encrypt_env()`] would report for the output.
+///
+/// # Example
+///
+/// ```no_run
+/// use envcrypt::cli::{merge_env, EncryptOptions, OutputConfig};
+///
+/// let output_config = OutputConfig::new(false, false, 0);
+/// let inputs = ["base.env.encrypted", "production.env.encrypted"];
+/// merge_env(None, Some("my-key"), &inputs, ".env.production.encrypted", &output_config, &EncryptOptions::default())?;
+/// # Ok::<(), String>(())
+/// ```
+pub fn merge_env(
+    cipher_name: Option<&str>,
+    key_arg: Option<&str>,
+    input_paths: &[&str],
+    output_path: &str,
+    output_config: &OutputConfig,
+    options: &EncryptOptions,
+) -> Result<usize, String> {
+    if input_paths.len() < 2 {
+        return Err("Merging needs at least two input files".to_string());
+    }
+    let _lock = check_output(output_path, output_config, options)?;
+
+    let _span = tracing::info_span!("merge", output = output_path).entered();
+    let request = KeyRequest { is_encrypt: false, no_interaction: options.no_interaction, key_format: options.key_format, challenge: None };
+    let key_input = get_key(key_arg, options.key_provider.as_deref(), &request)?.value;
+    let decrypt_options = DecryptOptions {
+        no_interaction: options.no_interaction,
+        key_format: KeyFormat::Utf8,
+        mlock: options.mlock,
+        aad: options.aad.clone(),
+        // Copies of a file share its salt, so their keys are derived only once
+        key_cache: Some(Rc::new(KeyMaterial::new())),
+        ..DecryptOptions::default()
+    };
+
+    let mut merged: Vec<(String, Zeroizing<String>)> = Vec::new();
+    let mut overridden = 0;
+    let mut first_header = None;
+    let mut first_newline = None;
+    let mut first_bom = false;
+    for input_path in input_paths {
+        let (header, plaintext) = decrypt_file(Some(&key_input), input_path, output_config, &decrypt_options)?;
+        let _plaintext_lock = lock_secret(output_config, options.mlock, plaintext.as_bytes());
+        let variables = parse_dotenv(&plaintext)
+            .map_err(|e| format!("{} is not a valid dotenv file: {}", input_path, e))?;
+        tracing::debug!("Read {} variables from {}", variables.len(), input_path);
+
+        for (key, value) in variables {
+            match merged.iter_mut().find(|(existing, _)| *existing == key) {
+                Some((_, existing)) => {
+                    tracing::trace!("{} overrides {}", input_path, key);
+                    overridden += 1;
+                    *existing = value;
+                }
+                None => merged.push((key, value)),
+            }
+        }
+        if first_header.is_none() {
+            first_header = Some(header);
+            first_newline = Newline::detect(plaintext.as_bytes());
+            first_bom = plaintext.starts_with(UTF8_BOM);
+        }
+    }
+
+    let header = first_header.ok_or("Merging needs at least two input files")?;
+    let cipher = cipher_name.map(str::to_string)
+        .or_else(|| header.cipher.clone())
+        .unwrap_or_else(|| "AES-256-CBC".to_string());
+    let output_options = EncryptOptions {
+        key_format: KeyFormat::Utf8,
+        raw_key: options.raw_key || header.kdf == Kdf::HkdfSha256,
+        newline: options.newline.or(first_newline),
+        ..options.clone()
+    };
+
+    let formatted = format_variables(&merged, ExportFormat::Dotenv);
+    let mut plaintext = Zeroizing::new(String::with_capacity(UTF8_BOM.len() + formatted.len()));
+    if first_bom {
+        plaintext.push_str(UTF8_BOM);
+    }
+    plaintext.push_str(&formatted);
+    let _plaintext_lock = lock_secret(output_config, options.mlock, plaintext.as_bytes());
+    write_encrypted(
+        &cipher,
+        Some(&key_input),
+        plaintext.as_bytes(),
+        &FileAttributes::from_metadata(&header.metadata),
+        output_path,
+        output_config,
+        &output_options,
+    )?;
+
+    if !options.dry_run {
+        success(output_config, &format!(
+            "Merged {} into {} ({} variables, {} overridden)",
+            input_paths.join(", "),
+            output_path,
+            merged.len(),
+            overridden,
+        ));
+    }
+    Ok(merged.len())
+}
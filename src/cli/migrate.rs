@@ -0,0 +1,184 @@
+//! Upgrading files from older formats (`envcrypt migrate`).
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::format::{self, FORMAT_VERSION};
+use crate::armor::decode_file;
+use crate::cli::key_handling::{get_key, KeyFormat, KeyRequest};
+use crate::cli::memory::lock_secret;
+use crate::cli::output::{info, success, warning, OutputConfig};
+use crate::cli::paths::ENCRYPTED_SUFFIX;
+use crate::cli::prompt::{assumed_yes, confirm, Prompt, TerminalPrompt};
+use crate::cli::backup::backup_file;
+use crate::cli::recrypt::{recrypt_contents, recrypt_env, RecryptOptions};
+use crate::cli::transaction::{recover, Transaction};
+use crate::cli::walk::{collect_files, WalkOptions};
+
+/// Rewrites encrypted files written in an older format in the current format.
+///
+/// `path` is either a single encrypted file or a directory, in which case every file
+/// whose name ends in `.encrypted` is checked (and, with `walk.recursive`, those in its
+/// subdirectories as well), skipping those excluded by `walk` (see [`WalkOptions`]). Files
+/// that are already in
+/// the current format are left untouched. Each outdated file is migrated with
+/// [`recrypt_env()`], so the cipher and KDF are kept unless `options` ask for others.
+///
+/// Since migrating a directory rewrites files the user may not have looked at, it only goes
+/// ahead with `options.force`, `--yes` or `ENVCRYPT_ASSUME_YES`, or once the user confirms
+/// the list of files at the terminal. The key is requested once and used for every file.
+///
+/// The files in a directory are migrated together: all of them are re-encrypted to
+/// temporary files first, and the originals are only replaced once every file succeeded.
+/// A journal in the directory lets the next `migrate` finish or undo a batch that was
+/// interrupted, so the files are never left half migrated.
+///
+/// # Returns
+///
+/// Returns the paths of the files that were migrated (or would be, with `dry_run`).
+///
+/// # Errors
+///
+/// Returns an error string if `path` does not exist, is a file that is not encrypted, if
+/// migrating a directory was not confirmed, or if migrating any file fails, in which case no
+/// file in the directory was changed.
+///
+/// # Example
+///
+/// ```no_run
+/// use envcrypt::cli::{migrate_env, OutputConfig, RecryptOptions, WalkOptions};
+///
+/// let output_config = OutputConfig::new(false, false, 0);
+/// let walk = WalkOptions { recursive: true, ..WalkOptions::default() };
+/// let options = RecryptOptions { force: true, ..RecryptOptions::default() };
+/// let migrated = migrate_env(Some("my-key"), "config", &walk, &output_config, &options)?;
+/// println!("Migrated {} files", migrated.len());
+/// # Ok::<(), String>(())
+/// ```
+pub fn migrate_env(
+    key_arg: Option<&str>,
+    path: &str,
+    walk: &WalkOptions,
+    output_config: &OutputConfig,
+    options: &RecryptOptions,
+) -> Result<Vec<PathBuf>, String> {
+    let root = Path::new(path);
+    if !root.exists() {
+        return Err(format!("{} not found", path));
+    }
+
+    if root.is_dir() && !options.dry_run {
+        recover(root, options.lock_wait, output_config)?;
+    }
+    let outdated = if root.is_dir() {
+        let candidates = collect_files(root, walk, |name| name.ends_with(ENCRYPTED_SUFFIX))?;
+        let mut outdated = Vec::new();
+        for candidate in candidates {
+            match format_version(&candidate) {
+                Ok(version) if version < FORMAT_VERSION => outdated.push(candidate),
+                Ok(_) => tracing::debug!("{} is already in the current format", candidate.display()),
+                Err(e) => warning(output_config, &format!("Skipping {}: {}", candidate.display(), e)),
+            }
+        }
+        outdated
+    } else if format_version(root)? < FORMAT_VERSION {
+        vec![root.to_path_buf()]
+    } else {
+        Vec::new()
+    };
+
+    if outdated.is_empty() {
+        info(output_config, &format!("No files in an older format found in {}", path));
+        return Ok(outdated);
+    }
+    if root.is_dir() && !options.dry_run {
+        confirm_batch(path, &outdated, output_config, options)?;
+    }
+
+    // Ask for the key once and hand the decoded key to every file verbatim
+    let request = KeyRequest { is_encrypt: false, no_interaction: options.no_interaction, key_format: options.key_format, challenge: None };
+    let key_input = get_key(key_arg, options.key_provider.as_deref(), &request)?.value;
+    let _key_lock = lock_secret(output_config, options.mlock, key_input.as_bytes());
+    let file_options = RecryptOptions { key_format: KeyFormat::Utf8, ..options.clone() };
+
+    if root.is_dir() && !options.dry_run {
+        migrate_batch(root, &outdated, &key_input, output_config, &file_options)?;
+    } else {
+        for file in &outdated {
+            let file_path = file.to_string_lossy();
+            recrypt_env(Some(&key_input), &file_path, output_config, &file_options)
+                .map_err(|e| format!("Error migrating {}: {}", file_path, e))?;
+        }
+    }
+
+    if !options.dry_run {
+        let noun = if outdated.len() == 1 { "file" } else { "files" };
+        success(output_config, &format!("Migrated {} {} to format version {}", outdated.len(), noun, FORMAT_VERSION));
+    }
+    Ok(outdated)
+}
+
+/// Migrates the `files` found in the directory `root` in one [`Transaction`]: every file
+/// is re-encrypted and written next to the original before any original is replaced.
+fn migrate_batch(
+    root: &Path,
+    files: &[PathBuf],
+    key_input: &str,
+    output_config: &OutputConfig,
+    options: &RecryptOptions,
+) -> Result<(), String> {
+    let mut transaction = Transaction::begin(root, options.lock_wait, output_config)?;
+    let mut ciphers = Vec::with_capacity(files.len());
+    for file in files {
+        let file_path = file.to_string_lossy();
+        transaction.lock(file, options.lock_wait, output_config)?;
+        let (new_content, to_cipher) = recrypt_contents(Some(key_input), None, &file_path, output_config, options)
+            .map_err(|e| format!("Error migrating {}: {}; no files were changed", file_path, e))?;
+        if let Some(backup) = &options.backup {
+            if let Some(backup_path) = backup_file(file, backup)? {
+                info(output_config, &format!("Backed up {} to {}", file_path, backup_path.display()));
+            }
+        }
+        transaction.stage(file, &new_content)?;
+        ciphers.push(to_cipher);
+    }
+    transaction.commit()?;
+
+    for (file, to_cipher) in files.iter().zip(ciphers) {
+        success(output_config, &format!("Successfully re-encrypted {} with {}", file.display(), to_cipher));
+    }
+    Ok(())
+}
+
+/// Checks that the `files` found in the directory `path` may be rewritten: `force`,
+/// `--yes` and `ENVCRYPT_ASSUME_YES` pass; otherwise the files are listed and the user is
+/// asked, unless no one is there to answer.
+fn confirm_batch(path: &str, files: &[PathBuf], output_config: &OutputConfig, options: &RecryptOptions) -> Result<(), String> {
+    if options.force || assumed_yes()? {
+        return Ok(());
+    }
+    let noun = if files.len() == 1 { "file" } else { "files" };
+    if options.no_interaction || !TerminalPrompt.is_interactive() {
+        return Err(format!(
+            "Migrating {} rewrites {} {} in place. Pass --yes to go ahead, or --dry-run to list them.",
+            path, files.len(), noun,
+        ));
+    }
+    for file in files {
+        info(output_config, &format!("  {}", file.display()));
+    }
+    if confirm(&TerminalPrompt, &format!("Rewrite these {} {} in place?", files.len(), noun))? {
+        Ok(())
+    } else {
+        Err("Migration cancelled; no files were changed".to_string())
+    }
+}
+
+/// Returns the format version of the encrypted file at `path` (0 for legacy files).
+fn format_version(path: &Path) -> Result<u8, String> {
+    let contents = fs::read(path)
+        .map_err(|e| format!("Error reading {} file: {}", path.display(), e))?;
+    let (data, _) = decode_file(&contents)?;
+    let (header, _) = format::decode(&data).map_err(|e| e.to_string())?;
+    Ok(header.version)
+}
@@ -7,28 +7,153 @@
 //!
 //! The CLI is typically invoked through the [`run()`] function with command-line arguments.
 
-use clap::{ArgAction, Parser, Subcommand, builder::PossibleValuesParser};
+use clap::builder::BoolishValueParser;
+use clap::{ArgAction, ArgMatches, Args, Command, FromArgMatches, Parser, Subcommand};
+use clap_complete::ArgValueCandidates;
 
 mod encrypt;
 mod decrypt;
 mod paths;
 mod key_handling;
 mod cipher;
+mod config;
+mod lock;
+mod transaction;
+mod cleanup;
+mod dirs;
+mod audit;
+mod dotenv;
+mod encoding;
+mod newline;
+mod backup;
+mod trash;
+mod version;
+mod prompt;
+mod console;
+mod memory;
+mod key_shares;
+mod keyring;
+mod recrypt;
+mod migrate;
+mod rotate;
+mod export;
+mod import;
+mod render;
+mod example;
+mod peek;
+mod check;
+mod lint;
+mod merge;
+mod process;
+mod gpg;
+mod ssh;
+mod tpm;
+mod sign;
+#[cfg(feature = "fido2")]
+mod fido2;
+mod sync;
+mod inspect;
+mod bench;
+mod vectors;
+mod self_update;
+mod help;
+mod completions;
+mod key_output;
+#[cfg(feature = "tui")]
+mod tui;
+mod walk;
+mod attributes;
+mod logging;
 pub mod output;
 
 // Re-export public APIs
 pub use paths::derive_output_path;
-pub use key_handling::strip_base64_prefix;
-pub use cipher::get_cipher;
-pub use encrypt::encrypt_env;
-pub use decrypt::decrypt_env;
-pub use output::OutputConfig;
+pub use key_handling::{
+    decode_key, get_key, strip_base64_prefix, BitwardenKeyProvider, EnvKeyProvider, FileKeyProvider, KeyFormat,
+    KeyProvider, KeyProviderRegistry, KeyRequest, OnePasswordKeyProvider, PromptKeyProvider, StdinKeyProvider, UserKey,
+};
+pub use cipher::{canonical_cipher_name, get_cipher, DEFAULT_CIPHER};
+pub use encrypt::{encrypt_env, encrypt_reader_to_writer, EncryptOptions, STRICT_MIN_PBKDF2_ITERATIONS};
+pub use decrypt::{decrypt_env, decrypt_reader_to_writer, DecryptOptions};
+pub use output::{format_error, OutputConfig};
+pub use config::{Config, CONFIG_ENV_VAR, CONFIG_FILE};
+pub use crate::armor::FileFormat;
+pub use backup::BackupConfig;
+pub use trash::{gc_trash, trash_file, PruneMode, TrashConfig, DEFAULT_TRASH_DAYS, TRASH_DIR};
+pub use version::{render_version, VersionFormat, VersionInfo};
+pub use dirs::{cache_dir, config_dir, data_dir, HOME_ENV_VAR};
+pub use lock::{lock_file, FileLock, LockWait};
+pub use encoding::{check_utf8, decode_text, encode_text, Encoding};
+pub use newline::{convert_newlines, Newline};
+pub use prompt::{Prompt, ScriptedPrompt, TerminalPrompt, ASSUME_YES_ENV_VAR, KEY_CHOICE_ENV_VAR};
+pub use key_shares::{recover_key, split_key};
+pub use keyring::{Keyring, KeyringKeyProvider, KeyringOptions, DEFAULT_ENVIRONMENT, KEYRING_FILE, KEYRING_PASSPHRASE_ENV_VAR};
+pub use ssh::SSH_PASSPHRASE_ENV_VAR;
+pub use tpm::{TpmKeyProvider, TPM_KEY_FILE};
+pub use sign::{decode_signing_public_key, read_signing_key, verify_env, SIGNATURE_SUFFIX};
+pub use recrypt::{recrypt_env, RecryptOptions};
+pub use migrate::migrate_env;
+pub use rotate::{rotate_env, RotateFailure, RotateReport};
+pub use dotenv::{parse_dotenv, parse_dotenv_expanded};
+pub use export::{export_env, format_variables, ExportFormat, ExportOptions, ExportTarget};
+pub use import::{import_env, ImportFormat};
+pub use render::{render_env, render_template, RenderOptions};
+pub use example::{example_env, example_template, ExampleOptions, EXAMPLE_FILE};
+pub use peek::{mask_value, peek_env, PeekOptions};
+pub use check::{check_env, CheckOptions};
+pub use lint::{lint_dotenv, lint_env, LintIssue, LintOptions};
+pub use merge::merge_env;
+pub use sync::{sync_env, SyncChanges, SyncDirection, SyncOptions, SyncStore};
+pub use inspect::inspect_env;
+pub use bench::{run_benchmarks, BenchResult, BENCH_FILE_SIZES, BENCH_KDF_ITERATIONS};
+pub use self_update::{self_update, SelfUpdateOptions, UpdateChannel, REPO_ENV_VAR};
+pub use vectors::{generate_test_vectors, test_vectors_json, TestVector, VECTOR_CONTEXT, VECTOR_KEY, VECTOR_PLAINTEXT};
+pub use logging::{LogConfig, LogFormat};
+pub use completions::{write_completion_script, Shell, COMPLETE_ENV_VAR};
+pub use walk::{WalkOptions, DEFAULT_EXCLUDES};
+#[cfg(feature = "tui")]
+pub use tui::{run_tui, TuiOptions};
+#[cfg(feature = "fido2")]
+pub use fido2::Fido2KeyProvider;
 
 // Internal use
-use paths::{resolve_encrypt_input_path, resolve_encrypt_output_path, resolve_decrypt_input};
-use key_handling::get_key_arg;
-use output::info;
-use cipher::get_supported_ciphers;
+use paths::{resolve_encrypt_input_path, resolve_encrypt_output_path, resolve_decrypt_env_input, resolve_decrypt_input};
+use key_handling::{generate_base64_key, get_key_arg, read_key_list};
+use key_output::{check_generated_key_output, check_key_out, show_encryption_key, KeyDisplay, KeyOutput};
+use output::{dry_run, info, success, Spinner};
+use cipher::CipherValueParser;
+use config::write_config_value;
+use attributes::write_with_mode;
+use audit::key_fingerprint;
+use std::rc::Rc;
+use std::time::Duration;
+use crate::key::{calibrate_pbkdf2_iterations, DEFAULT_PBKDF2_ITERATIONS};
+use crate::recovery::generate_recovery_keypair;
+use crate::signature::generate_signing_keypair;
+use sign::public_key_text;
+use base64::Engine;
+use zeroize::{Zeroize, Zeroizing};
+
+/// Prefix of the environment variables that set global options: `ENVCRYPT_` followed by
+/// the option name in upper case with underscores, e.g. `ENVCRYPT_NO_INTERACTION=1` for
+/// `--no-interaction`.
+pub const ENV_VAR_PREFIX: &str = "ENVCRYPT_";
+
+/// Environment variable with the default cipher of new encrypted files (`--cipher`).
+pub const CIPHER_ENV_VAR: &str = "ENVCRYPT_CIPHER";
+
+/// Global options that are not set through [`ENV_VAR_PREFIX`] variables: `--yes` has
+/// `ENVCRYPT_ASSUME_YES`, and `--help-long` is not a setting.
+const GLOBAL_OPTIONS_WITHOUT_ENV_VAR: &[&str] = &["yes", "help_long"];
+
+/// Short names of the commands typed most often, shown in `--help`. Subcommands are
+/// named by their path, e.g. `key list`.
+const COMMAND_ALIASES: &[(&str, &[&str])] = &[
+    ("encrypt", &["enc", "e"]),
+    ("decrypt", &["dec", "d"]),
+    ("key list", &["ls"]),
+    ("key remove", &["rm"]),
+];
 
 // Version string with release date
 // Release date is read from Cargo.toml [package.metadata.release-date] via build script
@@ -39,6 +164,18 @@ const VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), " (", env!("RELEASE_DAT
 #[command(about = "Encrypt and decrypt environment files")]
 #[command(version = VERSION)]
 pub struct Cli {
+    /// Format of --version: text, or json with the compiled features, ciphers, KDFs and file format versions
+    #[arg(long, value_enum, value_name = "FORMAT", requires = "version")]
+    pub format: Option<VersionFormat>,
+
+    /// Run as if envcrypt was started in DIR: paths, envcrypt.toml and --log-file are resolved relative to it
+    #[arg(short = 'C', long = "cwd", value_name = "DIR", global = true)]
+    pub cwd: Option<String>,
+
+    /// Configuration file to use instead of envcrypt.toml (also ENVCRYPT_CONFIG); settings missing from it are taken from the user configuration
+    #[arg(long, value_name = "FILE", global = true)]
+    pub config: Option<String>,
+
     /// Do not output any message
     #[arg(long, global = true)]
     pub silent: bool,
@@ -47,13 +184,57 @@ pub struct Cli {
     #[arg(long, global = true)]
     pub force: bool,
 
+    /// Answer yes to confirmation questions, such as deleting the original file with --prune or migrating a directory (also ENVCRYPT_ASSUME_YES)
+    #[arg(short = 'y', long, visible_alias = "assume-yes", global = true)]
+    pub yes: bool,
+
     /// Only errors are displayed. All other output is suppressed
     #[arg(short = 'q', long, global = true)]
     pub quiet: bool,
 
-    /// Delete the original environment file (encrypt only)
+    /// Back up files before overwriting them (to <name>.bak unless configured otherwise in envcrypt.toml)
+    #[arg(long, global = true)]
+    pub backup: bool,
+
+    /// Validate inputs, keys and ciphers and print the planned actions without changing any files
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// Lock keys and decrypted data into RAM so they are never swapped to disk (warns if not possible)
     #[arg(long, global = true)]
-    pub prune: bool,
+    pub mlock: bool,
+
+    /// Line endings of the env files written (default: keep those of the input)
+    #[arg(long, value_enum, global = true)]
+    pub newline: Option<Newline>,
+
+    /// Where to get the key when --key is not given: prompt, env (ENVCRYPT_KEY), file (key_file in envcrypt.toml), stdin, 1password (onepassword_ref), bitwarden (bitwarden_item), keyring, tpm (tpm_key_file) or fido2 (hardware token, fido2 feature)
+    #[arg(long, global = true)]
+    pub key_provider: Option<String>,
+
+    /// Read the key from stdin, e.g. piped from a secrets manager (same as --key-provider stdin)
+    #[arg(long, global = true, conflicts_with = "key_provider")]
+    pub key_stdin: bool,
+
+    /// Delete the original environment file (encrypt only), or with --prune=backup move it to the trash directory
+    #[arg(long, value_enum, value_name = "MODE", num_args = 0..=1, require_equals = true, default_missing_value = "delete", global = true)]
+    pub prune: Option<PruneMode>,
+
+    /// When another envcrypt process is writing the same file, wait for it to finish, or at most SECONDS
+    #[arg(long, value_name = "SECONDS", num_args = 0..=1, require_equals = true, global = true, conflicts_with = "no_wait")]
+    pub wait: Option<Option<u64>>,
+
+    /// Fail at once when another envcrypt process is writing the same file, instead of waiting for it
+    #[arg(long, global = true)]
+    pub no_wait: bool,
+
+    /// Only allow AEAD ciphers and at least 600,000 PBKDF2 iterations for new encryptions (also strict_crypto in envcrypt.toml)
+    #[arg(long, global = true)]
+    pub strict_crypto: bool,
+
+    /// Do not use colors in output (also disabled by NO_COLOR or when not writing to a terminal)
+    #[arg(long, global = true)]
+    pub no_color: bool,
 
     /// Do not ask any interactive question
     #[arg(short = 'n', long = "no-interaction", global = true)]
@@ -63,6 +244,48 @@ pub struct Cli {
     #[arg(short = 'v', long, action = ArgAction::Count, global = true)]
     pub verbose: u8,
 
+    /// Format of diagnostic logs
+    #[arg(long, value_enum, default_value_t = LogFormat::Text, global = true)]
+    pub log_format: LogFormat,
+
+    /// Append diagnostic logs to this file instead of printing them
+    #[arg(long, global = true)]
+    pub log_file: Option<String>,
+
+    /// Print the help of the command and all its subcommands, with examples
+    #[arg(long, global = true)]
+    pub help_long: bool,
+
+    /// Print a newly created key instead of only its fingerprint
+    #[arg(long, global = true, conflicts_with_all = ["hide_key", "copy"])]
+    pub show_key: bool,
+
+    /// Only print the fingerprint of a newly created key, without asking to show it
+    #[arg(long, global = true, conflicts_with = "copy")]
+    pub hide_key: bool,
+
+    /// Copy a newly created key to the clipboard instead of printing it
+    #[arg(long, global = true)]
+    pub copy: bool,
+
+    /// Copy a newly created key to the clipboard and clear it again after --copy-key-timeout
+    #[cfg(feature = "clipboard")]
+    #[arg(long, global = true, conflicts_with_all = ["show_key", "hide_key", "copy"])]
+    pub copy_key: bool,
+
+    /// Seconds the key stays on the clipboard with --copy-key
+    #[cfg(feature = "clipboard")]
+    #[arg(long, global = true, value_name = "SECONDS", default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..))]
+    pub copy_key_timeout: u64,
+
+    /// Write a newly created key to this file, readable only by you (refused inside a Git repository unless --force is given)
+    #[arg(long, global = true, value_name = "PATH")]
+    pub key_out: Option<String>,
+
+    /// Encrypt with a generated key even if --silent hides it and nothing saves it, so the file can never be decrypted
+    #[arg(long, global = true)]
+    pub i_know_what_im_doing: bool,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -72,29 +295,685 @@ pub enum Commands {
     /// Encrypt a .env file to .env.encrypted
     Encrypt {
         /// Cipher to use for encryption
-        #[arg(long, default_value = "AES-256-CBC", value_parser = PossibleValuesParser::new(get_supported_ciphers()), ignore_case = true)]
+        #[arg(long, default_value = DEFAULT_CIPHER, env = CIPHER_ENV_VAR, value_parser = CipherValueParser)]
         cipher: String,
         /// Encryption key (will prompt if not provided)
         #[arg(long)]
         key: Option<String>,
+        /// Encoding of the key (auto detects "hex:" and "base64:" prefixes)
+        #[arg(long, value_enum, default_value_t = KeyFormat::Auto)]
+        key_format: KeyFormat,
+        /// Input .env file path (same as --input)
+        #[arg(conflicts_with = "input")]
+        file: Option<String>,
         /// Input .env file path (default: .env, or .env.{env} if --env is specified)
         #[arg(long)]
         input: Option<String>,
         /// Environment name (e.g., local, production, development). When specified, defaults input to .env.{env} and output to .env.{env}.encrypted
-        #[arg(long)]
+        #[arg(long, add = ArgValueCandidates::new(completions::plaintext_environments))]
         env: Option<String>,
+        /// Number of PBKDF2 iterations (default: kdf_iterations from envcrypt.toml, or 100000)
+        #[arg(long)]
+        kdf_iterations: Option<u32>,
+        /// Use the key as a raw base64-encoded 32-byte key (HKDF instead of PBKDF2). Implied for generated keys
+        #[arg(long, conflicts_with = "kdf_iterations")]
+        raw_key: bool,
+        /// Encoding of the encrypted file: base64 text (armor) or raw bytes (binary)
+        #[arg(long, value_enum, default_value_t = FileFormat::Armor)]
+        format: FileFormat,
+        /// Comment to record in the file header, readable with `inspect` without the key
+        #[arg(long)]
+        comment: Option<String>,
+        /// Encrypt the input even if it already looks encrypted
+        #[arg(long)]
+        allow_double_encrypt: bool,
+        /// Encoding of the input; latin1 converts a legacy Latin-1 file to UTF-8 before encrypting it
+        #[arg(long, value_enum, default_value_t = Encoding::Utf8)]
+        encoding: Encoding,
+        /// Bind the ciphertext to a context string (e.g. "production:payments-service"); decryption requires the same string (default: aad from envcrypt.toml)
+        #[arg(long)]
+        aad: Option<String>,
+        /// Also encrypt the file keys to this OpenPGP key ID with gpg, so its owner can decrypt with `decrypt --gpg` (repeat for several recipients; default: gpg_recipients from envcrypt.toml)
+        #[arg(long = "recipient-gpg", value_name = "KEYID")]
+        recipient_gpg: Vec<String>,
+        /// Also encrypt the file keys to an SSH public key (a key, a .pub file or github:<username>), so its owner can decrypt with `decrypt --ssh` (repeat for several recipients; default: ssh_recipients from envcrypt.toml)
+        #[arg(long = "recipient-ssh", value_name = "KEY")]
+        recipient_ssh: Vec<String>,
+        /// Sign the encrypted file with the Ed25519 private key in this file (see `key sign-keygen`), so `verify` can prove who wrote it
+        #[arg(long, value_name = "FILE")]
+        sign_key: Option<String>,
+        /// Write the signature to <output>.sig instead of embedding it in the encrypted file
+        #[arg(long, requires = "sign_key")]
+        detached_signature: bool,
+        /// Print a newly created key as a QR code, e.g. to scan it into a password manager on a phone
+        #[arg(long, conflicts_with_all = ["show_key", "hide_key", "copy"])]
+        #[cfg_attr(feature = "clipboard", arg(conflicts_with = "copy_key"))]
+        key_qr: bool,
     },
     /// Decrypt a .env.encrypted file to .env
     Decrypt {
-        /// Cipher to use for decryption
-        #[arg(long, default_value = "AES-256-CBC", value_parser = PossibleValuesParser::new(get_supported_ciphers()), ignore_case = true)]
-        cipher: String,
+        /// Cipher to use for decryption (default: the cipher recorded in the file, or AES-256-CBC for legacy files)
+        #[arg(long, value_parser = CipherValueParser)]
+        cipher: Option<String>,
+        /// Decryption key (will prompt if not provided); repeat to try several keys in turn, e.g. the old and new key during a rotation
+        #[arg(long)]
+        key: Vec<String>,
+        /// File listing keys to try in turn, one per line (blank lines and lines starting with # are ignored)
+        #[arg(long, value_name = "FILE")]
+        key_file: Option<String>,
+        /// Encoding of the key (auto detects "hex:" and "base64:" prefixes)
+        #[arg(long, value_enum, default_value_t = KeyFormat::Auto)]
+        key_format: KeyFormat,
+        /// Input .env.encrypted file path (same as --input)
+        #[arg(conflicts_with = "input")]
+        file: Option<String>,
+        /// Input .env.encrypted file path (default: .env.encrypted)
+        #[arg(long, default_value = ".env.encrypted")]
+        input: String,
+        /// Environment name: decrypts .env.{env}.encrypted, with the key of the environment in the keyring if there is one
+        #[arg(long, conflicts_with_all = ["file", "input"], add = ArgValueCandidates::new(completions::encrypted_environments))]
+        env: Option<String>,
+        /// Decrypt with the recovery private key instead of the encryption key
+        #[arg(long, conflicts_with_all = ["key", "key_file"])]
+        recovery_key: Option<String>,
+        /// Decrypt with your GPG key (through the GPG agent) instead of the encryption key; the file must have been encrypted with --recipient-gpg
+        #[arg(long, conflicts_with_all = ["key", "key_file", "recovery_key"])]
+        gpg: bool,
+        /// Decrypt with your SSH private key (default: ~/.ssh/id_ed25519, then ~/.ssh/id_rsa) instead of the encryption key; the file must have been encrypted with --recipient-ssh
+        #[arg(long, value_name = "PRIVATE_KEY", num_args = 0..=1, conflicts_with_all = ["key", "key_file", "recovery_key", "gpg"])]
+        ssh: Option<Option<String>>,
+        /// Context string the file was bound to with `encrypt --aad` (default: aad from envcrypt.toml)
+        #[arg(long)]
+        aad: Option<String>,
+        /// Also restore the modification time the file had when it was encrypted
+        #[arg(long)]
+        preserve_times: bool,
+        /// Compare with the existing decrypted file instead of writing it; fails, listing the differing variables without their values, if they differ
+        #[arg(long, conflicts_with = "preserve_times")]
+        check: bool,
+        /// Encoding to write the decrypted file in; latin1 converts it back for legacy consumers
+        #[arg(long, value_enum, default_value_t = Encoding::Utf8)]
+        encoding: Encoding,
+    },
+    /// Re-encrypt an encrypted file in place with a different cipher or KDF
+    Recrypt {
+        /// Cipher the file is currently encrypted with (default: the cipher recorded in the file, or AES-256-CBC for legacy files)
+        #[arg(long, value_parser = CipherValueParser)]
+        from_cipher: Option<String>,
+        /// Cipher to re-encrypt with (default: keep the current cipher)
+        #[arg(long, value_parser = CipherValueParser)]
+        to_cipher: Option<String>,
+        /// Encryption key (will prompt if not provided)
+        #[arg(long)]
+        key: Option<String>,
+        /// Encoding of the key (auto detects "hex:" and "base64:" prefixes)
+        #[arg(long, value_enum, default_value_t = KeyFormat::Auto)]
+        key_format: KeyFormat,
+        /// Encrypted file to migrate (same as --input)
+        #[arg(conflicts_with = "input")]
+        file: Option<String>,
+        /// Encrypted file to migrate (default: .env.encrypted)
+        #[arg(long, default_value = ".env.encrypted")]
+        input: String,
+        /// Number of PBKDF2 iterations to re-encrypt with (default: keep the current count)
+        #[arg(long)]
+        kdf_iterations: Option<u32>,
+        /// Switch to raw key mode (HKDF instead of PBKDF2); the key must be a base64-encoded 32-byte key
+        #[arg(long, conflicts_with = "kdf_iterations")]
+        raw_key: bool,
+        /// Encoding to write the file in (default: keep the current encoding)
+        #[arg(long, value_enum)]
+        format: Option<FileFormat>,
+        /// Context string the file is bound to; the re-encrypted file stays bound to it (default: aad from envcrypt.toml)
+        #[arg(long)]
+        aad: Option<String>,
+    },
+    /// Rewrite encrypted files from older formats, such as legacy files without a header, in the current format
+    Migrate {
+        /// Encrypted file, or directory to search for *.encrypted files
+        #[arg(default_value = ".")]
+        path: String,
+        /// Also search subdirectories
+        #[arg(long, short)]
+        recursive: bool,
+        /// Skip files and directories matching this gitignore-style pattern (repeat for several; added to exclude from envcrypt.toml)
+        #[arg(long, value_name = "PATTERN")]
+        exclude: Vec<String>,
+        /// Only migrate files matching this gitignore-style pattern (repeat for several; added to include from envcrypt.toml)
+        #[arg(long, value_name = "PATTERN")]
+        include: Vec<String>,
+        /// Cipher legacy files are encrypted with (default: AES-256-CBC)
+        #[arg(long, value_parser = CipherValueParser)]
+        from_cipher: Option<String>,
+        /// Cipher to re-encrypt with (default: keep the current cipher)
+        #[arg(long, value_parser = CipherValueParser)]
+        to_cipher: Option<String>,
+        /// Encryption key (will prompt if not provided)
+        #[arg(long)]
+        key: Option<String>,
+        /// Encoding of the key (auto detects "hex:" and "base64:" prefixes)
+        #[arg(long, value_enum, default_value_t = KeyFormat::Auto)]
+        key_format: KeyFormat,
+        /// Number of PBKDF2 iterations to re-encrypt with (default: keep the current count)
+        #[arg(long)]
+        kdf_iterations: Option<u32>,
+        /// Switch to raw key mode (HKDF instead of PBKDF2); the key must be a base64-encoded 32-byte key
+        #[arg(long, conflicts_with = "kdf_iterations")]
+        raw_key: bool,
+        /// Context string the files are bound to (default: aad from envcrypt.toml)
+        #[arg(long)]
+        aad: Option<String>,
+    },
+    /// Re-encrypt every encrypted file in a directory tree with a new key and report the files rotated, skipped and failed as JSON
+    Rotate {
+        /// Directory to search for *.encrypted files, or a single encrypted file
+        #[arg(long, default_value = ".")]
+        dir: String,
+        /// Also search subdirectories
+        #[arg(long, short)]
+        recursive: bool,
+        /// Skip files and directories matching this gitignore-style pattern (repeat for several; added to exclude from envcrypt.toml)
+        #[arg(long, value_name = "PATTERN")]
+        exclude: Vec<String>,
+        /// Only rotate files matching this gitignore-style pattern (repeat for several; added to include from envcrypt.toml)
+        #[arg(long, value_name = "PATTERN")]
+        include: Vec<String>,
+        /// Key the files are encrypted with (will prompt if not provided)
+        #[arg(long)]
+        old_key: Option<String>,
+        /// Key to re-encrypt the files with (will prompt if not provided)
+        #[arg(long)]
+        new_key: Option<String>,
+        /// Encoding of the keys (auto detects "hex:" and "base64:" prefixes)
+        #[arg(long, value_enum, default_value_t = KeyFormat::Auto)]
+        key_format: KeyFormat,
+        /// Cipher to re-encrypt with (default: keep the current cipher)
+        #[arg(long, value_parser = CipherValueParser)]
+        to_cipher: Option<String>,
+        /// Number of PBKDF2 iterations to re-encrypt with (default: keep the current count)
+        #[arg(long)]
+        kdf_iterations: Option<u32>,
+        /// Context string the files are bound to (default: aad from envcrypt.toml)
+        #[arg(long)]
+        aad: Option<String>,
+        /// File to write the JSON report to (default: stdout)
+        #[arg(long, value_name = "FILE")]
+        report: Option<String>,
+    },
+    /// Decrypt an encrypted file in memory and print its variables, or export them as Docker secrets or to a GitHub Actions job
+    Export {
+        /// Format to print the variables in (default: dotenv)
+        #[arg(long, value_enum)]
+        format: Option<ExportFormat>,
+        /// Export the variables to this system instead of printing them
+        #[arg(long, value_enum, conflicts_with = "format")]
+        target: Option<ExportTarget>,
+        /// Encryption key (will prompt if not provided)
+        #[arg(long)]
+        key: Option<String>,
+        /// Encoding of the key (auto detects "hex:" and "base64:" prefixes)
+        #[arg(long, value_enum, default_value_t = KeyFormat::Auto)]
+        key_format: KeyFormat,
+        /// Encrypted file to export (same as --input)
+        #[arg(conflicts_with = "input")]
+        file: Option<String>,
+        /// Encrypted file to export (default: .env.encrypted)
+        #[arg(long, default_value = ".env.encrypted")]
+        input: String,
+        /// Context string the file was bound to when it was encrypted (default: aad from envcrypt.toml)
+        #[arg(long)]
+        aad: Option<String>,
+        /// Export the whole file as a single secret (or variable) instead of one per variable
+        #[arg(long, requires = "target")]
+        blob: bool,
+        /// Prefix for the secret or variable names, e.g. "app_" to create app_DB_PASSWORD
+        #[arg(long, requires = "target", conflicts_with = "blob")]
+        prefix: Option<String>,
+        /// Name of the secret or variable created with --blob (default: the decrypted file name without its leading dot, e.g. env.production)
+        #[arg(long, requires = "blob")]
+        name: Option<String>,
+        /// Take $VAR and ${VAR} references in values literally instead of expanding them
+        #[arg(long)]
+        no_expand: bool,
+    },
+    /// Fill in the ${VAR} and {{VAR}} placeholders of a template with the variables of an encrypted file
+    Render {
+        /// Template to render, e.g. nginx.conf.tpl
+        template: String,
+        /// Write the rendered template to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<String>,
+        /// Encrypted file with the variables (default: .env.encrypted)
+        #[arg(long, default_value = ".env.encrypted")]
+        input: String,
+        /// Encryption key (will prompt if not provided)
+        #[arg(long)]
+        key: Option<String>,
+        /// Encoding of the key (auto detects "hex:" and "base64:" prefixes)
+        #[arg(long, value_enum, default_value_t = KeyFormat::Auto)]
+        key_format: KeyFormat,
+        /// Context string the file was bound to when it was encrypted (default: aad from envcrypt.toml)
+        #[arg(long)]
+        aad: Option<String>,
+        /// Leave placeholders for undefined variables as they are instead of failing
+        #[arg(long)]
+        keep_undefined: bool,
+        /// Take $VAR and ${VAR} references in the values of the env file literally instead of expanding them
+        #[arg(long)]
+        no_expand: bool,
+    },
+    /// List the variables of an encrypted file with only the first and last characters of their values
+    Peek {
+        /// Encrypted file to preview (same as --input)
+        #[arg(conflicts_with = "input")]
+        file: Option<String>,
+        /// Encrypted file to preview (default: .env.encrypted)
+        #[arg(long, default_value = ".env.encrypted")]
+        input: String,
+        /// Environment name: previews .env.{env}.encrypted, with the key of the environment in the keyring if there is one
+        #[arg(long, conflicts_with_all = ["file", "input"], add = ArgValueCandidates::new(completions::encrypted_environments))]
+        env: Option<String>,
         /// Decryption key (will prompt if not provided)
         #[arg(long)]
         key: Option<String>,
-        /// Input .env.encrypted file path (default: .env.encrypted)
+        /// Encoding of the key (auto detects "hex:" and "base64:" prefixes)
+        #[arg(long, value_enum, default_value_t = KeyFormat::Auto)]
+        key_format: KeyFormat,
+        /// Context string the file was bound to when it was encrypted (default: aad from envcrypt.toml)
+        #[arg(long)]
+        aad: Option<String>,
+    },
+    /// Write a .env.example with the variables and comments of an encrypted file, without their values
+    Example {
+        /// Encrypted file to take the variables from (same as --input)
+        #[arg(conflicts_with = "input")]
+        file: Option<String>,
+        /// Encrypted file to take the variables from (default: .env.encrypted)
+        #[arg(long, default_value = ".env.encrypted")]
+        input: String,
+        /// Example file to write
+        #[arg(short, long, default_value = EXAMPLE_FILE)]
+        output: String,
+        /// Print the example instead of writing it
+        #[arg(long, conflicts_with_all = ["output", "check"])]
+        stdout: bool,
+        /// Value to give every variable (default: empty)
+        #[arg(long, default_value = "")]
+        placeholder: String,
+        /// Fail if the example file is missing or out of date instead of writing it, e.g. in CI
+        #[arg(long)]
+        check: bool,
+        /// Encryption key (will prompt if not provided)
+        #[arg(long)]
+        key: Option<String>,
+        /// Encoding of the key (auto detects "hex:" and "base64:" prefixes)
+        #[arg(long, value_enum, default_value_t = KeyFormat::Auto)]
+        key_format: KeyFormat,
+        /// Context string the file was bound to when it was encrypted (default: aad from envcrypt.toml)
+        #[arg(long)]
+        aad: Option<String>,
+    },
+    /// Check the variables of an encrypted file against a schema or an example file
+    Check {
+        /// Encrypted file to check (same as --input)
+        #[arg(conflicts_with = "input")]
+        file: Option<String>,
+        /// Encrypted file to check (default: .env.encrypted)
         #[arg(long, default_value = ".env.encrypted")]
         input: String,
+        /// TOML schema describing the required variables, their types and patterns
+        #[arg(long, required_unless_present = "against")]
+        schema: Option<String>,
+        /// Plaintext file, e.g. .env.example, whose variables must all be set
+        #[arg(long)]
+        against: Option<String>,
+        /// Encryption key (will prompt if not provided)
+        #[arg(long)]
+        key: Option<String>,
+        /// Encoding of the key (auto detects "hex:" and "base64:" prefixes)
+        #[arg(long, value_enum, default_value_t = KeyFormat::Auto)]
+        key_format: KeyFormat,
+        /// Context string the file was bound to when it was encrypted (default: aad from envcrypt.toml)
+        #[arg(long)]
+        aad: Option<String>,
+        /// Take $VAR and ${VAR} references in values literally instead of expanding them
+        #[arg(long)]
+        no_expand: bool,
+    },
+    /// Check a plaintext or encrypted env file for dotenv syntax problems
+    Lint {
+        /// File to check (same as --input)
+        #[arg(conflicts_with = "input")]
+        file: Option<String>,
+        /// File to check, plaintext or encrypted (default: .env)
+        #[arg(long, default_value = ".env")]
+        input: String,
+        /// Encryption key for encrypted files (will prompt if not provided)
+        #[arg(long)]
+        key: Option<String>,
+        /// Encoding of the key (auto detects "hex:" and "base64:" prefixes)
+        #[arg(long, value_enum, default_value_t = KeyFormat::Auto)]
+        key_format: KeyFormat,
+        /// Context string the file was bound to when it was encrypted (default: aad from envcrypt.toml)
+        #[arg(long)]
+        aad: Option<String>,
+        /// Encoding of a plaintext file
+        #[arg(long, value_enum, default_value_t = Encoding::Utf8)]
+        encoding: Encoding,
+    },
+    /// Merge encrypted files into one, later files overriding the variables of earlier ones
+    Merge {
+        /// Encrypted files to merge, e.g. base.env.encrypted production.env.encrypted
+        #[arg(required = true, num_args = 2..)]
+        inputs: Vec<String>,
+        /// Encrypted file to write
+        #[arg(short, long)]
+        output: String,
+        /// Cipher to use for the merged file (default: the cipher of the first input)
+        #[arg(long, value_parser = CipherValueParser)]
+        cipher: Option<String>,
+        /// Encryption key of the inputs, also used for the merged file (will prompt if not provided)
+        #[arg(long)]
+        key: Option<String>,
+        /// Encoding of the key (auto detects "hex:" and "base64:" prefixes)
+        #[arg(long, value_enum, default_value_t = KeyFormat::Auto)]
+        key_format: KeyFormat,
+        /// Number of PBKDF2 iterations (default: kdf_iterations from envcrypt.toml, or 100000)
+        #[arg(long)]
+        kdf_iterations: Option<u32>,
+        /// Encoding of the encrypted file: base64 text (armor) or raw bytes (binary)
+        #[arg(long, value_enum, default_value_t = FileFormat::Armor)]
+        format: FileFormat,
+        /// Comment to record in the file header, readable with `inspect` without the key
+        #[arg(long)]
+        comment: Option<String>,
+        /// Context string the inputs are bound to, also used for the merged file (default: aad from envcrypt.toml)
+        #[arg(long)]
+        aad: Option<String>,
+    },
+    /// Convert a flat JSON or YAML map of variables to dotenv and encrypt it
+    Import {
+        /// JSON or YAML file to import
+        source: String,
+        /// Encrypted file to write
+        #[arg(long, default_value = ".env.encrypted")]
+        output: String,
+        /// Format of the source file (default: detected from its extension)
+        #[arg(long, value_enum)]
+        from: Option<ImportFormat>,
+        /// Cipher to use for encryption
+        #[arg(long, default_value = DEFAULT_CIPHER, env = CIPHER_ENV_VAR, value_parser = CipherValueParser)]
+        cipher: String,
+        /// Encryption key (will prompt if not provided)
+        #[arg(long)]
+        key: Option<String>,
+        /// Encoding of the key (auto detects "hex:" and "base64:" prefixes)
+        #[arg(long, value_enum, default_value_t = KeyFormat::Auto)]
+        key_format: KeyFormat,
+        /// Number of PBKDF2 iterations (default: kdf_iterations from envcrypt.toml, or 100000)
+        #[arg(long)]
+        kdf_iterations: Option<u32>,
+        /// Use the key as a raw base64-encoded 32-byte key (HKDF instead of PBKDF2). Implied for generated keys
+        #[arg(long, conflicts_with = "kdf_iterations")]
+        raw_key: bool,
+        /// Encoding of the encrypted file: base64 text (armor) or raw bytes (binary)
+        #[arg(long, value_enum, default_value_t = FileFormat::Armor)]
+        format: FileFormat,
+        /// Comment to record in the file header, readable with `inspect` without the key
+        #[arg(long)]
+        comment: Option<String>,
+        /// Bind the ciphertext to a context string; decryption requires the same string (default: aad from envcrypt.toml)
+        #[arg(long)]
+        aad: Option<String>,
+    },
+    /// Push the variables of an encrypted file to a cloud secret store, or pull them into it
+    Sync {
+        #[command(subcommand)]
+        command: SyncCommands,
+    },
+    /// Show the header, sizes and metadata of an encrypted file without decrypting it
+    Inspect {
+        /// Encrypted file to inspect
+        #[arg(default_value = ".env.encrypted")]
+        file: String,
+    },
+    /// Check that an encrypted file was signed by a trusted key (see `encrypt --sign-key`)
+    Verify {
+        /// Encrypted file to verify
+        #[arg(default_value = ".env.encrypted")]
+        file: String,
+        /// Public key of a trusted signer (repeat for several; default: signer_public_keys from envcrypt.toml)
+        #[arg(long = "signer-pub", value_name = "KEY")]
+        signer_pub: Vec<String>,
+        /// Detached signature file (default: the embedded signature, or <file>.sig)
+        #[arg(long, value_name = "FILE")]
+        signature: Option<String>,
+    },
+    /// List the originals --prune=backup moved to the trash directory, or delete expired ones with --gc
+    Prune {
+        /// Delete the files whose grace period (prune_keep_days in envcrypt.toml, default 7) is over
+        #[arg(long)]
+        gc: bool,
+        /// With --gc, delete every file in the trash
+        #[arg(long, requires = "gc")]
+        all: bool,
+    },
+    /// Measure how many PBKDF2 iterations fit in a time budget and save it to envcrypt.toml
+    Calibrate {
+        /// Target key derivation time in milliseconds
+        #[arg(long, default_value_t = 250)]
+        target_ms: u64,
+        /// Print the result without writing envcrypt.toml
+        #[arg(long)]
+        no_save: bool,
+    },
+    /// Update envcrypt to the latest release from GitHub, verifying its checksum
+    SelfUpdate {
+        /// Releases to update to
+        #[arg(long, value_enum, default_value_t = UpdateChannel::Stable)]
+        channel: UpdateChannel,
+    },
+    /// Browse, encrypt, decrypt and edit env files in an interactive terminal UI
+    #[cfg(feature = "tui")]
+    Tui {
+        /// Directory to list env files in
+        #[arg(default_value = ".")]
+        dir: String,
+        /// Also list env files in subdirectories
+        #[arg(long)]
+        recursive: bool,
+        /// Do not list files and directories matching this gitignore-style pattern (repeat for several; added to exclude from envcrypt.toml)
+        #[arg(long, value_name = "PATTERN")]
+        exclude: Vec<String>,
+        /// Only list files matching this gitignore-style pattern (repeat for several; added to include from envcrypt.toml)
+        #[arg(long, value_name = "PATTERN")]
+        include: Vec<String>,
+        /// Cipher to encrypt plaintext files with
+        #[arg(long, default_value = DEFAULT_CIPHER, env = CIPHER_ENV_VAR, value_parser = CipherValueParser)]
+        cipher: String,
+        /// Key for every file (will prompt for each file if not provided)
+        #[arg(long)]
+        key: Option<String>,
+        /// Encoding of the key (auto detects "hex:" and "base64:" prefixes)
+        #[arg(long, value_enum, default_value_t = KeyFormat::Auto)]
+        key_format: KeyFormat,
+        /// Number of PBKDF2 iterations when encrypting (default: kdf_iterations from envcrypt.toml, or 100000)
+        #[arg(long)]
+        kdf_iterations: Option<u32>,
+        /// Context string the files are bound to (default: aad from envcrypt.toml)
+        #[arg(long)]
+        aad: Option<String>,
+    },
+    /// Generate the man pages of envcrypt and its commands
+    Man {
+        /// Directory to write envcrypt.1 and a page per command to, e.g. /usr/local/share/man/man1 (default: print envcrypt.1 to stdout)
+        #[arg(long)]
+        output: Option<String>,
+    },
+    /// Print a shell completion script that also completes --env and keyring environments
+    Completions {
+        /// Shell to complete for
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Measure cipher and key derivation speed on this machine
+    #[command(hide = true)]
+    Bench {
+        /// Time to spend on each measurement in milliseconds
+        #[arg(long, default_value_t = 200)]
+        budget_ms: u64,
+    },
+    /// Regenerate the known-answer test vectors of the file format
+    #[command(hide = true)]
+    Vectors {
+        /// File to write the vectors to, e.g. test-vectors/vectors.json (default: stdout)
+        #[arg(long)]
+        output: Option<String>,
+    },
+    /// Manage encryption keys
+    Key {
+        #[command(subcommand)]
+        command: KeyCommands,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SyncCommands {
+    /// Sync with an AWS Secrets Manager secret holding a JSON object of the variables
+    AwsSecretsmanager {
+        /// Name or ARN of the secret (created on push if it does not exist)
+        #[arg(long)]
+        secret_id: String,
+        #[command(flatten)]
+        args: SyncArgs,
+    },
+    /// Sync with AWS SSM Parameter Store, one SecureString parameter per variable under a path
+    AwsSsm {
+        /// Path the parameters live under, e.g. /app/prod/
+        #[arg(long)]
+        path: String,
+        #[command(flatten)]
+        args: SyncArgs,
+    },
+}
+
+/// Arguments shared by the `sync` stores.
+#[derive(Args)]
+pub struct SyncArgs {
+    /// Make the store match the encrypted file
+    #[arg(long, required_unless_present = "pull", conflicts_with = "pull")]
+    push: bool,
+    /// Make the encrypted file match the store (it is created if it does not exist)
+    #[arg(long)]
+    pull: bool,
+    /// Encrypted file to sync (same as --input)
+    #[arg(conflicts_with = "input")]
+    file: Option<String>,
+    /// Encrypted file to sync (default: .env.encrypted)
+    #[arg(long, default_value = ".env.encrypted")]
+    input: String,
+    /// Encryption key (will prompt if not provided)
+    #[arg(long)]
+    key: Option<String>,
+    /// Encoding of the key (auto detects "hex:" and "base64:" prefixes)
+    #[arg(long, value_enum, default_value_t = KeyFormat::Auto)]
+    key_format: KeyFormat,
+    /// Cipher to encrypt with when pulling (default: keep the current cipher, or AES-256-CBC for a new file)
+    #[arg(long, value_parser = CipherValueParser)]
+    cipher: Option<String>,
+    /// Number of PBKDF2 iterations when pulling (default: kdf_iterations from envcrypt.toml, or 100000)
+    #[arg(long)]
+    kdf_iterations: Option<u32>,
+    /// Context string the file is bound to (default: aad from envcrypt.toml)
+    #[arg(long)]
+    aad: Option<String>,
+    /// AWS region (default: from the AWS CLI configuration)
+    #[arg(long)]
+    region: Option<String>,
+    /// AWS CLI profile (default: from the AWS CLI configuration)
+    #[arg(long)]
+    profile: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum KeyCommands {
+    /// Split a key into shares so that only a quorum of holders can recover it
+    Split {
+        /// Key to split (will prompt if not provided)
+        #[arg(long)]
+        key: Option<String>,
+        /// Encoding of the key (auto detects "hex:" and "base64:" prefixes)
+        #[arg(long, value_enum, default_value_t = KeyFormat::Auto)]
+        key_format: KeyFormat,
+        /// Number of shares to create
+        #[arg(long, value_parser = clap::value_parser!(u8).range(2..))]
+        shares: u8,
+        /// Number of shares required to recover the key
+        #[arg(long, value_parser = clap::value_parser!(u8).range(2..))]
+        threshold: u8,
+    },
+    /// Recover a key from its shares
+    Recover {
+        /// A share printed by `key split` (repeat for each share; will prompt if not provided)
+        #[arg(long = "share")]
+        shares: Vec<String>,
+    },
+    /// Add the key of an environment to the keyring (.envcrypt.keys)
+    Add {
+        /// Environment name, e.g. staging ("default" is used by commands without --env)
+        #[arg(add = ArgValueCandidates::new(completions::file_environment_names))]
+        environment: String,
+        /// Key to add (will prompt if not provided)
+        #[arg(long, conflicts_with = "generate")]
+        key: Option<String>,
+        /// Encoding of the key (auto detects "hex:" and "base64:" prefixes)
+        #[arg(long, value_enum, default_value_t = KeyFormat::Auto)]
+        key_format: KeyFormat,
+        /// Generate a new random key instead of entering one
+        #[arg(long)]
+        generate: bool,
+    },
+    /// Remove the key of an environment from the keyring
+    Remove {
+        /// Environment name
+        #[arg(add = ArgValueCandidates::new(completions::keyring_environment_names))]
+        environment: String,
+    },
+    /// List the environments in the keyring with the fingerprints of their keys
+    List,
+    /// Generate a recovery key pair and save the public key to envcrypt.toml
+    RecoveryKeygen {
+        /// Print the key pair without writing envcrypt.toml
+        #[arg(long)]
+        no_save: bool,
+    },
+    /// Generate an Ed25519 key pair for signing encrypted files and trust its public key in envcrypt.toml
+    SignKeygen {
+        /// Write the private key to this file (mode 600) instead of printing it
+        #[arg(long, value_name = "FILE")]
+        out: Option<String>,
+        /// Do not add the public key to signer_public_keys in envcrypt.toml
+        #[arg(long)]
+        no_save: bool,
+    },
+    /// Seal a key to this machine's TPM for the tpm key provider, so it is not stored in plaintext
+    TpmSeal {
+        /// Key to seal (will prompt if not provided)
+        #[arg(long, conflicts_with = "generate")]
+        key: Option<String>,
+        /// Encoding of the key (auto detects "hex:" and "base64:" prefixes)
+        #[arg(long, value_enum, default_value_t = KeyFormat::Auto)]
+        key_format: KeyFormat,
+        /// Generate a new random key instead of entering one
+        #[arg(long)]
+        generate: bool,
+    },
+    /// Create a credential on a FIDO2 security key and save its ID to envcrypt.toml for the fido2 key provider
+    #[cfg(feature = "fido2")]
+    Fido2Enroll {
+        /// Device path of the security key (default: fido2_device in envcrypt.toml, or the first one found)
+        #[arg(long)]
+        device: Option<String>,
     },
 }
 
@@ -133,16 +1012,233 @@ pub fn run<I>(args: I) -> anyhow::Result<()>
 where
     I: IntoIterator<Item = String>,
 {
-    let cli = Cli::parse_from(args);
+    let args: Vec<String> = args.into_iter().collect();
+    // The completion scripts run envcrypt with the command line to complete
+    if completions::complete_command_line(&args) {
+        return Ok(());
+    }
+    if let Some(format) = version::requested_format(&args) {
+        println!("{}", version::render_version(format));
+        return Ok(());
+    }
+    // Handled before parsing so it works without a command's required arguments, like --help
+    if args.iter().skip(1).take_while(|arg| *arg != "--").any(|arg| arg == "--help-long") {
+        print!("{}", help::help_long(&args));
+        return Ok(());
+    }
+
+    let matches = help::command().get_matches_from(args);
+    let cli = Cli::from_arg_matches(&matches)
+        .map_err(|e| e.format(&mut help::command()))
+        .unwrap_or_else(|e| e.exit());
+    let command_name = subcommand_path(&matches);
+
+    if let Some(dir) = &cli.cwd {
+        std::env::set_current_dir(dir)
+            .map_err(|e| anyhow::anyhow!("Cannot change to directory {}: {}", dir, e))?;
+    }
 
     // Create output configuration from global flags
     let output_config = OutputConfig::new(cli.silent, cli.quiet, cli.verbose);
+    if cli.no_color {
+        output::disable_color();
+    }
+    if cli.yes {
+        prompt::set_assume_yes();
+    }
+    cleanup::install_handler();
+
+    let _log_guard = logging::init(&LogConfig {
+        format: cli.log_format,
+        file: cli.log_file.clone(),
+        verbose: cli.verbose,
+        quiet: cli.quiet,
+        silent: cli.silent,
+    }).map_err(|e| anyhow::anyhow!("{}", e))?;
+
+    let result = execute(cli, &command_name, output_config);
+    if let Err(e) = &result {
+        tracing::error!("{}", e);
+    }
+    result
+}
+
+/// Lets every global option of `command` be set with an environment variable (see
+/// [`ENV_VAR_PREFIX`]), so container entrypoints can configure envcrypt without changing
+/// its command line. An option on the command line takes precedence over the variable,
+/// and the variable over the default. Flags accept `1`/`0`, `true`/`false`, `yes`/`no`
+/// and `on`/`off`.
+fn with_env_vars(command: Command) -> Command {
+    command.mut_args(|arg| {
+        let id = arg.get_id().as_str();
+        if !arg.is_global_set() || GLOBAL_OPTIONS_WITHOUT_ENV_VAR.contains(&id) {
+            return arg;
+        }
+        let name = format!("{}{}", ENV_VAR_PREFIX, id.to_uppercase());
+        match arg.get_action() {
+            ArgAction::SetTrue => arg.env(name).value_parser(BoolishValueParser::new()),
+            _ => arg.env(name),
+        }
+    })
+}
+
+/// How long to wait for the lock of a file another envcrypt process is writing, from
+/// `--wait[=SECONDS]` and `--no-wait`.
+fn lock_wait(wait: Option<Option<u64>>, no_wait: bool) -> LockWait {
+    match (wait, no_wait) {
+        (_, true) => LockWait::Timeout(Duration::ZERO),
+        (Some(Some(seconds)), false) => LockWait::Timeout(Duration::from_secs(seconds)),
+        _ => LockWait::Forever,
+    }
+}
+
+/// Adds the [`COMMAND_ALIASES`] to the subcommands of `command`.
+fn with_aliases(mut command: Command) -> Command {
+    for (path, aliases) in COMMAND_ALIASES {
+        let path: Vec<&str> = path.split_whitespace().collect();
+        command = add_aliases(command, &path, aliases);
+    }
+    command
+}
+
+fn add_aliases(command: Command, path: &[&str], aliases: &'static [&'static str]) -> Command {
+    match path.split_first() {
+        Some((name, rest)) => command.mut_subcommand(*name, |sub| add_aliases(sub, rest, aliases)),
+        None => command.visible_aliases(aliases),
+    }
+}
+
+/// Names of the subcommands given on the command line, e.g. `key split`.
+fn subcommand_path(matches: &ArgMatches) -> String {
+    let mut names = Vec::new();
+    let mut matches = matches;
+    while let Some((name, sub_matches)) = matches.subcommand() {
+        names.push(name);
+        matches = sub_matches;
+    }
+    names.join(" ")
+}
+
+/// Runs the parsed command, recording it in the audit log if that is enabled.
+fn execute(mut cli: Cli, command_name: &str, output_config: OutputConfig) -> anyhow::Result<()> {
+    cli.config = config::config_override(cli.config.as_deref());
+    let config = Config::load_merged(cli.config.as_deref()).map_err(|e| anyhow::anyhow!("{}", e))?;
+    let audit_log = match config.audit_log_path().map_err(|e| anyhow::anyhow!("{}", e))? {
+        Some(path) => Some(audit::AuditLog::start(&path, command_name, cli.dry_run).map_err(|e| anyhow::anyhow!("{}", e))?),
+        None => None,
+    };
+
+    let result = execute_command(cli, &config, output_config);
+    if let Some(audit_log) = audit_log {
+        let error = result.as_ref().err().map(|e| e.to_string());
+        if let Err(e) = audit_log.finish(error.as_deref()) {
+            output::warning(&output_config, &e);
+        }
+    }
+    result
+}
+
+/// How new keys are handed over, from --show-key, --hide-key, --copy and --copy-key.
+fn key_display(cli: &Cli) -> KeyDisplay {
+    #[cfg(feature = "clipboard")]
+    if cli.copy_key {
+        return KeyDisplay::CopyKey { clear_after: std::time::Duration::from_secs(cli.copy_key_timeout) };
+    }
+    if cli.show_key {
+        KeyDisplay::Show
+    } else if cli.hide_key {
+        KeyDisplay::Hide
+    } else if cli.copy {
+        KeyDisplay::Copy
+    } else {
+        KeyDisplay::Ask
+    }
+}
+
+/// Runs the parsed command with the loaded configuration.
+fn execute_command(cli: Cli, config: &Config, output_config: OutputConfig) -> anyhow::Result<()> {
+    let config_file = cli.config.clone().unwrap_or_else(|| CONFIG_FILE.to_string());
+    let backup = cli.backup.then(|| config.backup_config());
+    let key_out = cli.key_out.as_deref().map(std::path::Path::new);
+    if let Some(path) = key_out {
+        check_key_out(path, cli.force, cli.no_interaction).map_err(|e| anyhow::anyhow!("{}", e))?;
+    }
+    let key_output = KeyOutput {
+        display: key_display(&cli),
+        key_out,
+        prompt: (!cli.no_interaction).then_some(&TerminalPrompt),
+        generated: false,
+        allow_lost_key: cli.i_know_what_im_doing,
+    };
+    let keyring_options = config.keyring_options(cli.no_interaction);
+    let environment = match &cli.command {
+        Commands::Encrypt { env, .. } | Commands::Decrypt { env, .. } | Commands::Peek { env, .. } => env.clone(),
+        _ => None,
+    };
+    let mut registry = KeyProviderRegistry::from_config(config);
+    registry.register(Rc::new(KeyringKeyProvider {
+        options: keyring_options.clone(),
+        environment: environment.unwrap_or_else(|| DEFAULT_ENVIRONMENT.to_string()),
+    }));
+    #[cfg(feature = "fido2")]
+    registry.register(Rc::new(Fido2KeyProvider {
+        credential: config.fido2_credential.clone(),
+        device: config.fido2_device.clone(),
+        output_config,
+    }));
+    let key_provider_name = if cli.key_stdin { Some("stdin") } else { cli.key_provider.as_deref() };
+    let key_provider = match key_provider_name.or(config.key_provider.as_deref()) {
+        Some(name) => Some(registry.get(name).map_err(|e| anyhow::anyhow!("{}", e))?),
+        // An existing keyring is used without being configured
+        None if keyring_options.path.exists() => Some(registry.get("keyring").map_err(|e| anyhow::anyhow!("{}", e))?),
+        None => None,
+    };
+
+    let strict_crypto = cli.strict_crypto || config.strict_crypto.unwrap_or(false);
+    // Without interaction, the prompt provider generates a key when none is given
+    let check_generated_key = |key: &Option<String>, display: KeyDisplay| -> anyhow::Result<()> {
+        if cli.no_interaction && !cli.dry_run && key.is_none() && key_provider.as_ref().is_none_or(|provider| provider.name() == "prompt") {
+            check_generated_key_output(&output_config, &KeyOutput { display, ..key_output }).map_err(|e| anyhow::anyhow!("{}", e))?;
+        }
+        Ok(())
+    };
 
     match cli.command {
-        Commands::Encrypt { cipher, key, input, env } => {
-            let input_path = resolve_encrypt_input_path(&input, &env);
+        Commands::Encrypt { cipher, key, key_format, file, input, env, kdf_iterations, raw_key, format, comment, allow_double_encrypt, encoding, aad, recipient_gpg, recipient_ssh, sign_key, detached_signature, key_qr } => {
+            let display = if key_qr { KeyDisplay::Qr } else { key_output.display };
+            check_generated_key(&key, display)?;
+            let input_path = resolve_encrypt_input_path(&file.or(input), &env);
             let output = resolve_encrypt_output_path(&input_path, &env);
             let key_arg = get_key_arg(&key);
+            let options = EncryptOptions {
+                lock_wait: lock_wait(cli.wait, cli.no_wait),
+                strict_crypto,
+                force: cli.force,
+                prune: cli.prune.is_some(),
+                trash: (cli.prune == Some(PruneMode::Backup)).then(|| config.trash_config()),
+                no_interaction: cli.no_interaction,
+                kdf_iterations: kdf_iterations
+                    .or(config.kdf_iterations)
+                    .unwrap_or(DEFAULT_PBKDF2_ITERATIONS),
+                raw_key,
+                key_format,
+                recovery_public_key: config.recovery_public_key().map_err(|e| anyhow::anyhow!("{}", e))?,
+                gpg_recipients: if recipient_gpg.is_empty() { config.gpg_recipients.clone() } else { recipient_gpg },
+                ssh_recipients: if recipient_ssh.is_empty() { config.ssh_recipients.clone() } else { recipient_ssh },
+                backup,
+                dry_run: cli.dry_run,
+                mlock: cli.mlock,
+                newline: cli.newline,
+                format,
+                comment,
+                aad: aad.or(config.aad.clone()),
+                key_provider,
+                allow_double_encrypt,
+                encoding,
+                challenge: None,
+                sign_key: sign_key.as_deref().map(read_signing_key).transpose().map_err(|e| anyhow::anyhow!("{}", e))?,
+                detached_signature,
+            };
             
             match encrypt_env(
                 &cipher,
@@ -150,41 +1246,703 @@ where
                 &input_path,
                 &output,
                 &output_config,
-                cli.force,
-                cli.prune,
-                cli.no_interaction,
+                &options,
             ) {
-                Ok(used_key) => {
-                    // Show key information unless silent
-                    if output_config.should_show_info() {
-                        info(&output_config, "\n⚠️  IMPORTANT: Store this encryption key in a safe place!");
-                        info(&output_config, "   You will need it to decrypt your .env file later.");
-                        info(&output_config, &format!("\n   Encryption key: base64:{}", used_key));
-                        info(&output_config, "\n   This key will not be shown again. Make sure to save it securely.");
-                    }
+                Ok(_) if cli.dry_run => {
+                    dry_run(&output_config, "No files were changed");
                     Ok(())
                 }
+                Ok(used_key) => {
+                    show_encryption_key(&output_config, &used_key.value, &KeyOutput { display, generated: used_key.generated, ..key_output })
+                        .map_err(|e| anyhow::anyhow!("{}", e))
+                }
                 Err(e) => {
                     anyhow::bail!("{}", e);
                 }
             }
         }
-        Commands::Decrypt { cipher, key, input } => {
-            let input = resolve_decrypt_input(input);
+        Commands::Decrypt { cipher, key, key_file, key_format, file, input, env, recovery_key, gpg, ssh, aad, preserve_times, check, encoding } => {
+            let input = match &env {
+                Some(env) => resolve_decrypt_env_input(env),
+                None => resolve_decrypt_input(file.unwrap_or(input)),
+            };
             let output = derive_output_path(&input, false);
+            let mut keys = key;
+            if let Some(path) = key_file {
+                keys.extend(read_key_list(&path).map_err(|e| anyhow::anyhow!("{}", e))?);
+            }
+            let mut keys = keys.into_iter();
+            let key = keys.next();
             let key_arg = get_key_arg(&key);
+            let options = DecryptOptions {
+                lock_wait: lock_wait(cli.wait, cli.no_wait),
+                force: cli.force,
+                no_interaction: cli.no_interaction,
+                key_format,
+                recovery_key,
+                gpg,
+                ssh: ssh.is_some(),
+                ssh_identity: ssh.flatten(),
+                backup,
+                dry_run: cli.dry_run,
+                mlock: cli.mlock,
+                newline: cli.newline,
+                aad: aad.or(config.aad.clone()),
+                key_provider,
+                preserve_times,
+                key_cache: None,
+                check,
+                encoding,
+                fallback_keys: keys.collect(),
+                warn_on_legacy_cipher: config.warn_on_legacy_cipher.unwrap_or(true),
+            };
             
             decrypt_env(
-                &cipher,
+                cipher.as_deref(),
                 key_arg,
                 &input,
                 &output,
                 &output_config,
-                cli.force,
-                cli.no_interaction,
+                &options,
             )
             .map_err(|e| anyhow::anyhow!("{}", e))?;
             Ok(())
         }
+        Commands::Recrypt { from_cipher, to_cipher, key, key_format, file, input, kdf_iterations, raw_key, format, aad } => {
+            let input = resolve_decrypt_input(file.unwrap_or(input));
+            let options = RecryptOptions {
+                lock_wait: lock_wait(cli.wait, cli.no_wait),
+                strict_crypto,
+                from_cipher,
+                to_cipher,
+                kdf_iterations,
+                raw_key,
+                no_interaction: cli.no_interaction,
+                key_format,
+                recovery_public_key: config.recovery_public_key().map_err(|e| anyhow::anyhow!("{}", e))?,
+                gpg_recipients: config.gpg_recipients.clone(),
+                ssh_recipients: config.ssh_recipients.clone(),
+                backup,
+                dry_run: cli.dry_run,
+                mlock: cli.mlock,
+                format,
+                aad: aad.or(config.aad.clone()),
+                key_provider,
+                force: cli.force,
+            };
+
+            recrypt_env(get_key_arg(&key), &input, &output_config, &options)
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+            Ok(())
+        }
+        Commands::Migrate { path, recursive, exclude, include, from_cipher, to_cipher, key, key_format, kdf_iterations, raw_key, aad } => {
+            let options = RecryptOptions {
+                lock_wait: lock_wait(cli.wait, cli.no_wait),
+                strict_crypto,
+                from_cipher,
+                to_cipher,
+                kdf_iterations,
+                raw_key,
+                no_interaction: cli.no_interaction,
+                key_format,
+                recovery_public_key: config.recovery_public_key().map_err(|e| anyhow::anyhow!("{}", e))?,
+                gpg_recipients: config.gpg_recipients.clone(),
+                ssh_recipients: config.ssh_recipients.clone(),
+                backup,
+                dry_run: cli.dry_run,
+                mlock: cli.mlock,
+                format: None,
+                aad: aad.or(config.aad.clone()),
+                key_provider,
+                force: cli.force,
+            };
+
+            migrate_env(get_key_arg(&key), &path, &config.walk_options(recursive, exclude, include), &output_config, &options)
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+            Ok(())
+        }
+        Commands::Rotate { dir, recursive, exclude, include, old_key, new_key, key_format, to_cipher, kdf_iterations, aad, report } => {
+            let options = RecryptOptions {
+                lock_wait: lock_wait(cli.wait, cli.no_wait),
+                strict_crypto,
+                to_cipher,
+                kdf_iterations,
+                no_interaction: cli.no_interaction,
+                key_format,
+                recovery_public_key: config.recovery_public_key().map_err(|e| anyhow::anyhow!("{}", e))?,
+                gpg_recipients: config.gpg_recipients.clone(),
+                ssh_recipients: config.ssh_recipients.clone(),
+                backup,
+                dry_run: cli.dry_run,
+                mlock: cli.mlock,
+                aad: aad.or(config.aad.clone()),
+                key_provider,
+                ..RecryptOptions::default()
+            };
+
+            // Keep stdout to the report when it is printed there
+            let rotate_output_config = if report.is_some() { output_config } else { OutputConfig::new(cli.silent, true, cli.verbose) };
+            let walk = config.walk_options(recursive, exclude, include);
+            let result = rotate_env(get_key_arg(&old_key), get_key_arg(&new_key), &dir, &walk, &rotate_output_config, &options)
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+            let json = serde_json::to_string_pretty(&result).map_err(|e| anyhow::anyhow!("{}", e))?;
+            match report {
+                Some(path) => {
+                    std::fs::write(&path, format!("{}\n", json)).map_err(|e| anyhow::anyhow!("Error writing {}: {}", path, e))?;
+                    success(&output_config, &format!(
+                        "Rotated {}, skipped {} and failed {} of {} files; wrote the report to {}",
+                        result.rotated.len(), result.skipped.len(), result.failed.len(),
+                        result.rotated.len() + result.skipped.len() + result.failed.len(), path,
+                    ));
+                }
+                None => println!("{}", json),
+            }
+            if !result.failed.is_empty() {
+                anyhow::bail!("Failed to rotate {} of the files; see the report", result.failed.len());
+            }
+            Ok(())
+        }
+        Commands::Export { format, target, key, key_format, file, input, aad, blob, prefix, name, no_expand } => {
+            let input = resolve_decrypt_input(file.unwrap_or(input));
+            let options = ExportOptions {
+                target,
+                format: format.unwrap_or_default(),
+                no_interaction: cli.no_interaction,
+                key_format,
+                dry_run: cli.dry_run,
+                mlock: cli.mlock,
+                aad: aad.or(config.aad.clone()),
+                key_provider,
+                blob,
+                prefix,
+                name,
+                no_expand,
+            };
+
+            export_env(get_key_arg(&key), &input, &output_config, &options)
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+            Ok(())
+        }
+        Commands::Render { template, output, input, key, key_format, aad, keep_undefined, no_expand } => {
+            let input = resolve_decrypt_input(input);
+            let options = RenderOptions {
+                force: cli.force,
+                no_interaction: cli.no_interaction,
+                key_format,
+                backup,
+                dry_run: cli.dry_run,
+                mlock: cli.mlock,
+                aad: aad.or(config.aad.clone()),
+                key_provider,
+                keep_undefined,
+                no_expand,
+            };
+
+            render_env(get_key_arg(&key), &input, &template, output.as_deref(), &output_config, &options)
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+            Ok(())
+        }
+        Commands::Peek { file, input, env, key, key_format, aad } => {
+            let input = match &env {
+                Some(env) => resolve_decrypt_env_input(env),
+                None => resolve_decrypt_input(file.unwrap_or(input)),
+            };
+            let options = PeekOptions {
+                no_interaction: cli.no_interaction,
+                key_format,
+                mlock: cli.mlock,
+                aad: aad.or(config.aad.clone()),
+                key_provider,
+            };
+
+            let previews = peek_env(get_key_arg(&key), &input, &output_config, &options)
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+            let width = previews.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+            for (name, preview) in previews {
+                println!("{:<width$}  {}", name, preview);
+            }
+            Ok(())
+        }
+        Commands::Example { file, input, output, stdout, placeholder, check, key, key_format, aad } => {
+            let input = resolve_decrypt_input(file.unwrap_or(input));
+            let options = ExampleOptions {
+                force: cli.force,
+                no_interaction: cli.no_interaction,
+                key_format,
+                backup,
+                dry_run: cli.dry_run,
+                mlock: cli.mlock,
+                newline: cli.newline,
+                aad: aad.or(config.aad.clone()),
+                key_provider,
+                placeholder,
+                check,
+            };
+
+            let output = if stdout { None } else { Some(output.as_str()) };
+            example_env(get_key_arg(&key), &input, output, &output_config, &options)
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+            Ok(())
+        }
+        Commands::Check { file, input, schema, against, key, key_format, aad, no_expand } => {
+            let input = resolve_decrypt_input(file.unwrap_or(input));
+            let options = CheckOptions {
+                no_interaction: cli.no_interaction,
+                key_format,
+                mlock: cli.mlock,
+                aad: aad.or(config.aad.clone()),
+                key_provider,
+                no_expand,
+            };
+
+            check_env(get_key_arg(&key), &input, schema.as_deref(), against.as_deref(), &output_config, &options)
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+            Ok(())
+        }
+        Commands::Lint { file, input, key, key_format, aad, encoding } => {
+            let options = LintOptions {
+                no_interaction: cli.no_interaction,
+                key_format,
+                mlock: cli.mlock,
+                aad: aad.or(config.aad.clone()),
+                key_provider,
+                encoding,
+            };
+
+            lint_env(get_key_arg(&key), &file.unwrap_or(input), &output_config, &options)
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+            Ok(())
+        }
+        Commands::Merge { inputs, output, cipher, key, key_format, kdf_iterations, format, comment, aad } => {
+            let options = EncryptOptions {
+                lock_wait: lock_wait(cli.wait, cli.no_wait),
+                strict_crypto,
+                force: cli.force,
+                no_interaction: cli.no_interaction,
+                kdf_iterations: kdf_iterations
+                    .or(config.kdf_iterations)
+                    .unwrap_or(DEFAULT_PBKDF2_ITERATIONS),
+                key_format,
+                recovery_public_key: config.recovery_public_key().map_err(|e| anyhow::anyhow!("{}", e))?,
+                gpg_recipients: config.gpg_recipients.clone(),
+                ssh_recipients: config.ssh_recipients.clone(),
+                backup,
+                dry_run: cli.dry_run,
+                mlock: cli.mlock,
+                newline: cli.newline,
+                format,
+                comment,
+                aad: aad.or(config.aad.clone()),
+                key_provider,
+                ..EncryptOptions::default()
+            };
+
+            let inputs: Vec<&str> = inputs.iter().map(String::as_str).collect();
+            merge_env(cipher.as_deref(), get_key_arg(&key), &inputs, &output, &output_config, &options)
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+            if cli.dry_run {
+                dry_run(&output_config, "No files were changed");
+            }
+            Ok(())
+        }
+        Commands::Import { source, output, from, cipher, key, key_format, kdf_iterations, raw_key, format, comment, aad } => {
+            check_generated_key(&key, key_output.display)?;
+            let options = EncryptOptions {
+                lock_wait: lock_wait(cli.wait, cli.no_wait),
+                strict_crypto,
+                force: cli.force,
+                no_interaction: cli.no_interaction,
+                kdf_iterations: kdf_iterations
+                    .or(config.kdf_iterations)
+                    .unwrap_or(DEFAULT_PBKDF2_ITERATIONS),
+                raw_key,
+                key_format,
+                recovery_public_key: config.recovery_public_key().map_err(|e| anyhow::anyhow!("{}", e))?,
+                gpg_recipients: config.gpg_recipients.clone(),
+                ssh_recipients: config.ssh_recipients.clone(),
+                backup,
+                dry_run: cli.dry_run,
+                mlock: cli.mlock,
+                newline: cli.newline,
+                format,
+                comment,
+                aad: aad.or(config.aad.clone()),
+                key_provider,
+                ..EncryptOptions::default()
+            };
+
+            let used_key = import_env(&cipher, get_key_arg(&key), &source, &output, from, &output_config, &options)
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+            if cli.dry_run {
+                dry_run(&output_config, "No files were changed");
+            } else {
+                show_encryption_key(&output_config, &used_key.value, &KeyOutput { generated: used_key.generated, ..key_output })
+                    .map_err(|e| anyhow::anyhow!("{}", e))?;
+            }
+            Ok(())
+        }
+        Commands::Sync { command } => {
+            let (store, args) = match command {
+                SyncCommands::AwsSecretsmanager { secret_id, args } => (SyncStore::AwsSecretsManager { secret_id }, args),
+                SyncCommands::AwsSsm { path, args } => (SyncStore::AwsSsm { path }, args),
+            };
+            if args.pull {
+                check_generated_key(&args.key, key_output.display)?;
+            }
+            let input = resolve_decrypt_input(args.file.unwrap_or(args.input));
+            let options = SyncOptions {
+                direction: if args.pull { SyncDirection::Pull } else { SyncDirection::Push },
+                region: args.region,
+                profile: args.profile,
+                cipher: args.cipher,
+                encrypt: EncryptOptions {
+                    force: cli.force,
+                    strict_crypto,
+                    no_interaction: cli.no_interaction,
+                    kdf_iterations: args.kdf_iterations
+                        .or(config.kdf_iterations)
+                        .unwrap_or(DEFAULT_PBKDF2_ITERATIONS),
+                    key_format: args.key_format,
+                    recovery_public_key: config.recovery_public_key().map_err(|e| anyhow::anyhow!("{}", e))?,
+                    gpg_recipients: config.gpg_recipients.clone(),
+                    ssh_recipients: config.ssh_recipients.clone(),
+                    backup,
+                    dry_run: cli.dry_run,
+                    mlock: cli.mlock,
+                    newline: cli.newline,
+                    aad: args.aad.or(config.aad.clone()),
+                    key_provider,
+                    ..EncryptOptions::default()
+                },
+            };
+
+            let changes = sync_env(&store, get_key_arg(&args.key), &input, &output_config, &options)
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+            if let Some(key) = &changes.new_file_key {
+                show_encryption_key(&output_config, &key.value, &KeyOutput { generated: key.generated, ..key_output })
+                    .map_err(|e| anyhow::anyhow!("{}", e))?;
+            }
+            Ok(())
+        }
+        Commands::Inspect { file } => {
+            let report = inspect_env(&resolve_decrypt_input(file))
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+            for (label, value) in report {
+                println!("{:<15}{}", format!("{}:", label), value);
+            }
+            Ok(())
+        }
+        Commands::Verify { file, signer_pub, signature } => {
+            let trusted = if signer_pub.is_empty() {
+                config.signer_public_keys()
+            } else {
+                signer_pub.iter().map(|key| decode_signing_public_key(key)).collect()
+            }.map_err(|e| anyhow::anyhow!("{}", e))?;
+            let input = resolve_decrypt_input(file);
+            let signer = verify_env(&input, signature.as_deref(), &trusted)
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+            success(&output_config, &format!("Signature of {} is valid (signed by {})", input, public_key_text(&signer)));
+            Ok(())
+        }
+        Commands::Prune { gc, all } => {
+            let trash = config.trash_config();
+            if gc {
+                let deleted = gc_trash(&trash, all, cli.dry_run).map_err(|e| anyhow::anyhow!("{}", e))?;
+                if cli.dry_run {
+                    for path in &deleted {
+                        dry_run(&output_config, &format!("Would delete {}", path.display()));
+                    }
+                } else {
+                    let noun = if deleted.len() == 1 { "file" } else { "files" };
+                    success(&output_config, &format!("Deleted {} {} from {}", deleted.len(), noun, trash.dir.display()));
+                }
+                return Ok(());
+            }
+
+            let entries = trash.entries().map_err(|e| anyhow::anyhow!("{}", e))?;
+            if entries.is_empty() {
+                info(&output_config, &format!("{} is empty", trash.dir.display()));
+            }
+            let seconds = |time: std::time::SystemTime| time.duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+            for (path, trashed) in entries {
+                println!(
+                    "{}  moved {}, deleted by --gc after {}",
+                    path.display(),
+                    inspect::format_timestamp(seconds(trashed)),
+                    inspect::format_timestamp(seconds(trash.expiry(trashed))),
+                );
+            }
+            Ok(())
+        }
+        Commands::Calibrate { target_ms, no_save } => {
+            let spinner = Spinner::start(&output_config, "Measuring PBKDF2 speed...");
+            let iterations = calibrate_pbkdf2_iterations(std::time::Duration::from_millis(target_ms));
+            drop(spinner);
+            info(&output_config, &format!("PBKDF2-HMAC-SHA256: {} iterations take about {}ms on this machine", iterations, target_ms));
+
+            if cli.dry_run && !no_save {
+                dry_run(&output_config, &format!("Would save kdf_iterations = {} to {}", iterations, config_file));
+            } else if !no_save {
+                write_config_value(&config_file, "kdf_iterations", toml::Value::Integer(i64::from(iterations)))
+                    .map_err(|e| anyhow::anyhow!("{}", e))?;
+                info(&output_config, &format!("Saved kdf_iterations = {} to {}", iterations, config_file));
+            }
+            Ok(())
+        }
+        Commands::Bench { budget_ms } => {
+            let mut spinner = None;
+            let results = run_benchmarks(std::time::Duration::from_millis(budget_ms), |name| {
+                drop(spinner.take());
+                spinner = Some(Spinner::start(&output_config, &format!("Measuring {}...", name)));
+            })
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+            drop(spinner);
+
+            for result in results {
+                let speed = match result.mib_per_sec() {
+                    Some(speed) => format!("{:>10.1} MiB/s", speed),
+                    None => format!("{:>10.2} ms", result.per_operation.as_secs_f64() * 1000.0),
+                };
+                println!("{:<40}{}", result.name, speed);
+            }
+            Ok(())
+        }
+        Commands::SelfUpdate { channel } => {
+            let options = SelfUpdateOptions { channel, force: cli.force, dry_run: cli.dry_run };
+            self_update(&output_config, &options).map_err(|e| anyhow::anyhow!("{}", e))?;
+            Ok(())
+        }
+        #[cfg(feature = "tui")]
+        Commands::Tui { dir, recursive, exclude, include, cipher, key, key_format, kdf_iterations, aad } => {
+            let options = TuiOptions {
+                cipher,
+                walk: config.walk_options(recursive, exclude, include),
+                encrypt: EncryptOptions {
+                    force: cli.force,
+                    strict_crypto,
+                    kdf_iterations: kdf_iterations
+                        .or(config.kdf_iterations)
+                        .unwrap_or(DEFAULT_PBKDF2_ITERATIONS),
+                    key_format,
+                    recovery_public_key: config.recovery_public_key().map_err(|e| anyhow::anyhow!("{}", e))?,
+                    gpg_recipients: config.gpg_recipients.clone(),
+                    ssh_recipients: config.ssh_recipients.clone(),
+                    backup,
+                    mlock: cli.mlock,
+                    aad: aad.or(config.aad.clone()),
+                    key_provider,
+                    ..EncryptOptions::default()
+                },
+            };
+            run_tui(&dir, get_key_arg(&key), &options).map_err(|e| anyhow::anyhow!("{}", e))
+        }
+        Commands::Man { output } => {
+            match output {
+                Some(dir) => {
+                    let pages = help::write_man_pages(std::path::Path::new(&dir)).map_err(|e| anyhow::anyhow!("{}", e))?;
+                    info(&output_config, &format!("Wrote {} man pages to {}", pages.len(), dir));
+                }
+                None => {
+                    let page = help::man_page().map_err(|e| anyhow::anyhow!("{}", e))?;
+                    std::io::Write::write_all(&mut std::io::stdout(), &page)?;
+                }
+            }
+            Ok(())
+        }
+        Commands::Completions { shell } => {
+            write_completion_script(shell, &mut std::io::stdout()).map_err(|e| anyhow::anyhow!("{}", e))
+        }
+        Commands::Vectors { output } => {
+            let vectors = generate_test_vectors().map_err(|e| anyhow::anyhow!("{}", e))?;
+            let json = test_vectors_json(&vectors);
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, json).map_err(|e| anyhow::anyhow!("Error writing {}: {}", path, e))?;
+                    info(&output_config, &format!("Wrote {} test vectors to {}", vectors.len(), path));
+                }
+                None => print!("{}", json),
+            }
+            Ok(())
+        }
+        Commands::Key { command: KeyCommands::Split { key, key_format, shares, threshold } } => {
+            let encoded = split_key(get_key_arg(&key), key_format, shares, threshold, cli.no_interaction)
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+
+            info(&output_config, &format!("Split key into {} shares; any {} of them recover it.", shares, threshold));
+            info(&output_config, "Give each share to a different key holder.\n");
+            for share in encoded {
+                println!("{}", share);
+            }
+            Ok(())
+        }
+        Commands::Key { command: KeyCommands::Recover { shares } } => {
+            let key = recover_key(&shares, cli.no_interaction)
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+            println!("{}", key.as_str());
+            Ok(())
+        }
+        Commands::Key { command: KeyCommands::Add { environment, key, key_format, generate } } => {
+            let _lock = (!cli.dry_run)
+                .then(|| lock_file(&keyring_options.path, lock_wait(cli.wait, cli.no_wait), &output_config))
+                .transpose()
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+            let mut keyring = Keyring::open_or_create(&keyring_options, &TerminalPrompt).map_err(|e| anyhow::anyhow!("{}", e))?;
+            if keyring.get(&environment).is_some() && !cli.force {
+                anyhow::bail!("{} already has a key for {}. Use --force to replace it.", keyring_options.path.display(), environment);
+            }
+            let key = match (get_key_arg(&key), generate) {
+                (_, true) => generate_base64_key(),
+                (Some(key), false) => decode_key(key, key_format).map_err(|e| anyhow::anyhow!("{}", e))?,
+                (None, false) if cli.no_interaction => anyhow::bail!("Pass --key or --generate to add a key without interaction"),
+                (None, false) => {
+                    let key = TerminalPrompt.read_secret(&format!("Key for {}: ", environment)).map_err(|e| anyhow::anyhow!("{}", e))?;
+                    decode_key(&key, key_format).map_err(|e| anyhow::anyhow!("{}", e))?
+                }
+            };
+            let fingerprint = key_fingerprint(&key);
+            keyring.insert(&environment, key).map_err(|e| anyhow::anyhow!("{}", e))?;
+            if cli.dry_run {
+                dry_run(&output_config, &format!("Would add the key of {} ({}) to {}", environment, fingerprint, keyring_options.path.display()));
+            } else {
+                keyring.save().map_err(|e| anyhow::anyhow!("{}", e))?;
+                success(&output_config, &format!("Added the key of {} ({}) to {}", environment, fingerprint, keyring_options.path.display()));
+            }
+            Ok(())
+        }
+        Commands::Key { command: KeyCommands::Remove { environment } } => {
+            let _lock = (!cli.dry_run)
+                .then(|| lock_file(&keyring_options.path, lock_wait(cli.wait, cli.no_wait), &output_config))
+                .transpose()
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+            let mut keyring = Keyring::open(&keyring_options, &TerminalPrompt).map_err(|e| anyhow::anyhow!("{}", e))?;
+            if !keyring.remove(&environment) {
+                anyhow::bail!("{} has no key for {}", keyring_options.path.display(), environment);
+            }
+            if cli.dry_run {
+                dry_run(&output_config, &format!("Would remove the key of {} from {}", environment, keyring_options.path.display()));
+            } else {
+                keyring.save().map_err(|e| anyhow::anyhow!("{}", e))?;
+                success(&output_config, &format!("Removed the key of {} from {}", environment, keyring_options.path.display()));
+            }
+            Ok(())
+        }
+        Commands::Key { command: KeyCommands::List } => {
+            let keyring = Keyring::open(&keyring_options, &TerminalPrompt).map_err(|e| anyhow::anyhow!("{}", e))?;
+            let mut entries = keyring.entries().peekable();
+            if entries.peek().is_none() {
+                info(&output_config, &format!("{} has no keys", keyring_options.path.display()));
+            }
+            for (environment, key) in entries {
+                println!("{}  {}", environment, key_fingerprint(key));
+            }
+            Ok(())
+        }
+        Commands::Key { command: KeyCommands::RecoveryKeygen { no_save } } => {
+            // Only the file written counts, not settings from the user configuration
+            let saved = Config::load(&config_file).map_err(|e| anyhow::anyhow!("{}", e))?;
+            if !no_save && saved.recovery_public_key.is_some() && !cli.force {
+                anyhow::bail!("{} already has a recovery_public_key. Use --force to replace it.", config_file);
+            }
+
+            let (mut secret, public) = generate_recovery_keypair();
+            let engine = &base64::engine::general_purpose::STANDARD;
+            println!("Recovery public key:  base64:{}", engine.encode(public));
+            println!("Recovery private key: base64:{}", engine.encode(secret));
+            secret.zeroize();
+
+            if cli.dry_run && !no_save {
+                dry_run(&output_config, &format!("\nWould save recovery_public_key to {}", config_file));
+            } else if !no_save {
+                write_config_value(&config_file, "recovery_public_key", toml::Value::String(format!("base64:{}", engine.encode(public))))
+                    .map_err(|e| anyhow::anyhow!("{}", e))?;
+                info(&output_config, &format!("\nSaved recovery_public_key to {}", config_file));
+            }
+            info(&output_config, "Store the private key offline. Files encrypted from now on can be decrypted with --recovery-key.");
+            Ok(())
+        }
+        Commands::Key { command: KeyCommands::SignKeygen { out, no_save } } => {
+            if let Some(out) = out.as_deref().filter(|out| std::path::Path::new(out).exists() && !cli.force) {
+                anyhow::bail!("{} already exists. Use --force to replace it.", out);
+            }
+
+            let (secret, public) = generate_signing_keypair();
+            let secret = Zeroizing::new(secret);
+            let public_text = public_key_text(&public);
+            println!("Signing public key:  {}", public_text);
+            match out.as_deref() {
+                Some(out) if cli.dry_run => dry_run(&output_config, &format!("Would write the signing private key to {}", out)),
+                Some(out) => {
+                    let text = Zeroizing::new(format!("{}\n", public_key_text(&secret)));
+                    write_with_mode(std::path::Path::new(out), text.as_bytes(), Some(0o600))
+                        .map_err(|e| anyhow::anyhow!("Error writing {}: {}", out, e))?;
+                    info(&output_config, &format!("Wrote the signing private key to {}", out));
+                }
+                None => println!("Signing private key: {}", public_key_text(&secret)),
+            }
+
+            if cli.dry_run && !no_save {
+                dry_run(&output_config, &format!("Would add the public key to signer_public_keys in {}", config_file));
+            } else if !no_save {
+                let saved = Config::load(&config_file).map_err(|e| anyhow::anyhow!("{}", e))?;
+                let keys = saved.signer_public_keys.into_iter().chain([public_text])
+                    .map(toml::Value::String)
+                    .collect();
+                write_config_value(&config_file, "signer_public_keys", toml::Value::Array(keys))
+                    .map_err(|e| anyhow::anyhow!("{}", e))?;
+                info(&output_config, &format!("Added the public key to signer_public_keys in {}", config_file));
+            }
+            info(&output_config, "Keep the private key where files are released, e.g. a CI secret, and sign with `encrypt --sign-key`.");
+            Ok(())
+        }
+        Commands::Key { command: KeyCommands::TpmSeal { key, key_format, generate } } => {
+            let path = config.tpm_key_path();
+            if path.exists() && !cli.force {
+                anyhow::bail!("{} already exists. Use --force to replace it.", path.display());
+            }
+            let key = match (get_key_arg(&key), generate) {
+                (_, true) => generate_base64_key(),
+                (Some(key), false) => decode_key(key, key_format).map_err(|e| anyhow::anyhow!("{}", e))?,
+                (None, false) if cli.no_interaction => anyhow::bail!("Pass --key or --generate to seal a key without interaction"),
+                (None, false) => {
+                    let key = TerminalPrompt.read_secret("Key to seal: ").map_err(|e| anyhow::anyhow!("{}", e))?;
+                    decode_key(&key, key_format).map_err(|e| anyhow::anyhow!("{}", e))?
+                }
+            };
+            let fingerprint = key_fingerprint(&key);
+            if cli.dry_run {
+                dry_run(&output_config, &format!("Would seal the key ({}) to this machine's TPM in {}", fingerprint, path.display()));
+                return Ok(());
+            }
+
+            let sealed = tpm::seal_key(&key).map_err(|e| anyhow::anyhow!("{}", e))?;
+            write_with_mode(&path, sealed.as_bytes(), Some(0o600))
+                .map_err(|e| anyhow::anyhow!("Error writing {}: {}", path.display(), e))?;
+            success(&output_config, &format!("Sealed the key ({}) to this machine's TPM in {}", fingerprint, path.display()));
+            if generate {
+                // The sealed copy is lost with the machine, so the key must be kept elsewhere too
+                show_encryption_key(&output_config, &key, &KeyOutput { generated: true, ..key_output }).map_err(|e| anyhow::anyhow!("{}", e))?;
+            }
+            Ok(())
+        }
+        #[cfg(feature = "fido2")]
+        Commands::Key { command: KeyCommands::Fido2Enroll { device } } => {
+            let saved = Config::load(&config_file).map_err(|e| anyhow::anyhow!("{}", e))?;
+            if saved.fido2_credential.is_some() && !cli.force {
+                anyhow::bail!("{} already has a fido2_credential. Use --force to replace it.", config_file);
+            }
+            if cli.dry_run {
+                dry_run(&output_config, &format!("Would create a credential on the security key and save fido2_credential to {}", config_file));
+                return Ok(());
+            }
+
+            let device = device.or(config.fido2_device.clone());
+            let credential = fido2::enroll(device.as_deref(), &output_config).map_err(|e| anyhow::anyhow!("{}", e))?;
+            write_config_value(&config_file, "fido2_credential", toml::Value::String(credential))
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+            success(&output_config, &format!("Saved fido2_credential to {}", config_file));
+            info(&output_config, "Encrypt with --key-provider fido2 (or key_provider = \"fido2\" in envcrypt.toml). Files encrypted this way can only be decrypted with this security key, so also set up a recovery key (envcrypt key recovery-keygen) or another recipient.");
+            Ok(())
+        }
     }
 }
+
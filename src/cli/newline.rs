@@ -0,0 +1,68 @@
+//! Line endings of the env files envcrypt writes (`--newline`).
+
+use clap::ValueEnum;
+use zeroize::Zeroizing;
+
+/// Line endings to write env files with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Newline {
+    /// `\n`, as on Linux and macOS
+    Lf,
+    /// `\r\n`, as on Windows
+    Crlf,
+    /// The line ending of this platform
+    Native,
+}
+
+impl Newline {
+    /// Returns the line ending as text.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Newline::Lf => "\n",
+            Newline::Crlf => "\r\n",
+            Newline::Native if cfg!(windows) => "\r\n",
+            Newline::Native => "\n",
+        }
+    }
+
+    /// Detects the line endings of `text` from its first line, or returns `None` if it
+    /// has no line break.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use envcrypt::cli::Newline;
+    ///
+    /// assert_eq!(Newline::detect(b"A=1\r\nB=2\n"), Some(Newline::Crlf));
+    /// assert_eq!(Newline::detect(b"A=1"), None);
+    /// ```
+    pub fn detect(text: &[u8]) -> Option<Newline> {
+        let end = text.iter().position(|&byte| byte == b'\n')?;
+        Some(if end > 0 && text[end - 1] == b'\r' { Newline::Crlf } else { Newline::Lf })
+    }
+}
+
+/// Returns `text` with every line ending, `\n` or `\r\n`, replaced by `newline`. Lone
+/// `\r` characters are left alone.
+///
+/// # Example
+///
+/// ```
+/// use envcrypt::cli::{convert_newlines, Newline};
+///
+/// assert_eq!(convert_newlines(b"A=1\nB=2\r\n", Newline::Crlf).as_slice(), b"A=1\r\nB=2\r\n");
+/// assert_eq!(convert_newlines(b"A=1\r\nB=2", Newline::Lf).as_slice(), b"A=1\nB=2");
+/// ```
+pub fn convert_newlines(text: &[u8], newline: Newline) -> Zeroizing<Vec<u8>> {
+    let mut converted = Zeroizing::new(Vec::with_capacity(text.len() + text.len() / 16));
+    for line in text.split_inclusive(|&byte| byte == b'\n') {
+        match line.strip_suffix(b"\n") {
+            Some(content) => {
+                converted.extend_from_slice(content.strip_suffix(b"\r").unwrap_or(content));
+                converted.extend_from_slice(newline.as_str().as_bytes());
+            }
+            None => converted.extend_from_slice(line),
+        }
+    }
+    converted
+}
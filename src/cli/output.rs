@@ -1,6 +1,78 @@
-//! Output control utilities for verbosity management.
+//! Output control utilities for verbosity management and colored terminal output.
+//!
+//! Colors are only used when the stream is a terminal, and never when `NO_COLOR` is set
+//! or `--no-color` was given.
+
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Set by `--no-color`.
+static COLOR_DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Emphasis applied to a message on color terminals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// Completed operations (green)
+    Success,
+    /// Warnings and notices that need attention (bold yellow)
+    Warning,
+    /// Errors (bold red)
+    Error,
+    /// Important values such as a generated key (bold)
+    Emphasis,
+}
+
+impl Style {
+    fn ansi_code(self) -> &'static str {
+        match self {
+            Style::Success => "32",
+            Style::Warning => "1;33",
+            Style::Error => "1;31",
+            Style::Emphasis => "1",
+        }
+    }
+}
+
+/// Turns colors off for the rest of the process (`--no-color`).
+pub fn disable_color() {
+    COLOR_DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// Whether colors are allowed at all, ignoring whether the stream is a terminal.
+fn color_allowed() -> bool {
+    !COLOR_DISABLED.load(Ordering::Relaxed)
+        && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+/// Wraps `text` in the escape codes for `style` if `enabled`. Leading newlines stay
+/// outside the escape codes.
+fn paint(text: &str, style: Style, enabled: bool) -> String {
+    if !enabled || text.trim().is_empty() {
+        return text.to_string();
+    }
+    let body = text.trim_start_matches('\n');
+    let newlines = &text[..text.len() - body.len()];
+    format!("{}\x1b[{}m{}\x1b[0m", newlines, style.ansi_code(), body)
+}
+
+fn paint_stdout(text: &str, style: Style) -> String {
+    paint(text, style, color_allowed() && io::stdout().is_terminal())
+}
+
+fn paint_stderr(text: &str, style: Style) -> String {
+    paint(text, style, color_allowed() && io::stderr().is_terminal())
+}
+
+/// Formats an error for printing to stderr, with a red "Error:" on color terminals.
+pub fn format_error(message: &dyn std::fmt::Display) -> String {
+    format!("{} {}", paint_stderr("Error:", Style::Error), message)
+}
 
 /// Output control configuration
+#[derive(Clone, Copy)]
 pub struct OutputConfig {
     silent: bool,
     quiet: bool,
@@ -27,6 +99,11 @@ impl OutputConfig {
         !self.silent && !self.quiet
     }
 
+    /// Check if critical messages, which cannot be shown again later, should be displayed
+    pub fn should_show_critical(&self) -> bool {
+        !self.silent
+    }
+
     /// Check if verbose messages should be displayed
     pub fn should_show_verbose(&self) -> bool {
         !self.silent && !self.quiet && self.verbose_level >= 2
@@ -45,16 +122,114 @@ pub fn info(config: &OutputConfig, message: &str) {
     }
 }
 
-/// Print a verbose message (shown at verbosity level 2+)
-pub fn verbose(config: &OutputConfig, message: &str) {
-    if config.should_show_verbose() {
+/// Print an info message with emphasis (shown unless quiet/silent)
+pub fn styled(config: &OutputConfig, style: Style, message: &str) {
+    if config.should_show_info() {
+        println!("{}", paint_stdout(message, style));
+    }
+}
+
+/// Print information that is lost if it is not shown now, such as a generated key
+/// (shown unless silent)
+pub fn critical(config: &OutputConfig, message: &str) {
+    if config.should_show_critical() {
         println!("{}", message);
     }
 }
 
-/// Print a debug message (shown at verbosity level 3+)
-pub fn debug(config: &OutputConfig, message: &str) {
-    if config.should_show_debug() {
-        eprintln!("[DEBUG] {}", message);
+/// Print a message about a completed operation (shown unless quiet/silent)
+pub fn success(config: &OutputConfig, message: &str) {
+    tracing::info!("{}", message.trim());
+    styled(config, Style::Success, message);
+}
+
+/// Print a warning to stderr (shown unless quiet/silent)
+pub fn warning(config: &OutputConfig, message: &str) {
+    tracing::warn!("{}", message);
+    if config.should_show_info() {
+        eprintln!("{} {}", paint_stderr("Warning:", Style::Warning), message);
+    }
+}
+
+/// Print an action planned by `--dry-run` (shown unless quiet/silent)
+pub fn dry_run(config: &OutputConfig, message: &str) {
+    if config.should_show_info() {
+        println!("[dry-run] {}", message);
+    }
+}
+
+/// Operations finishing sooner than this never show a spinner, so fast runs stay quiet.
+const SPINNER_DELAY: Duration = Duration::from_millis(300);
+
+const SPINNER_INTERVAL: Duration = Duration::from_millis(80);
+
+const SPINNER_FRAMES: [char; 4] = ['|', '/', '-', '\\'];
+
+/// Progress indicator for slow steps such as key derivation, drawn on stderr.
+///
+/// The spinner only appears if the step is still running after a short delay, and only
+/// when info messages are shown and stderr is a terminal. It is cleared when dropped.
+pub struct Spinner {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Spinner {
+    /// Starts a spinner labelled with `message`.
+    pub fn start(config: &OutputConfig, message: &str) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        if !config.should_show_info() || !io::stderr().is_terminal() {
+            return Self { stop, handle: None };
+        }
+
+        let message = message.to_string();
+        let thread_stop = Arc::clone(&stop);
+        let handle = thread::spawn(move || {
+            let started = Instant::now();
+            let mut frame = 0;
+            let mut drawn = false;
+            while !thread_stop.load(Ordering::Relaxed) {
+                if started.elapsed() >= SPINNER_DELAY {
+                    eprint!("\r{} {}", SPINNER_FRAMES[frame % SPINNER_FRAMES.len()], message);
+                    let _ = io::stderr().flush();
+                    frame += 1;
+                    drawn = true;
+                }
+                thread::sleep(SPINNER_INTERVAL);
+            }
+            if drawn {
+                eprint!("\r\x1b[2K");
+                let _ = io::stderr().flush();
+            }
+        });
+        Self { stop, handle: Some(handle) }
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paint() {
+        assert_eq!(paint("done", Style::Success, false), "done");
+        assert_eq!(paint("done", Style::Success, true), "\x1b[32mdone\x1b[0m");
+        assert_eq!(paint("\nWarning", Style::Warning, true), "\n\x1b[1;33mWarning\x1b[0m");
+        assert_eq!(paint("\n", Style::Emphasis, true), "\n");
+    }
+
+    #[test]
+    fn test_spinner_is_inactive_when_quiet() {
+        let spinner = Spinner::start(&OutputConfig::new(false, true, 0), "Deriving keys");
+        assert!(spinner.handle.is_none());
     }
 }
@@ -1,6 +1,7 @@
 //! Path resolution utilities for encryption and decryption operations.
 
-use std::path::PathBuf;
+use std::ffi::OsStr;
+use std::path::Path;
 
 /// Derives the output file path from an input path based on the operation type.
 ///
@@ -39,34 +40,36 @@ use std::path::PathBuf;
 /// assert_eq!(derive_output_path(".env.encrypted", false), ".env");
 /// ```
 pub fn derive_output_path(input_path: &str, is_encrypt: bool) -> String {
+    // Only the file name changes, so the output lands next to the input whatever the
+    // directory names or separators are
+    let path = Path::new(input_path);
+    let Some(file_name) = path.file_name().and_then(OsStr::to_str) else {
+        return input_path.to_string();
+    };
+
     if is_encrypt {
         // For encryption: .env -> .env.encrypted, .env.{env} -> .env.{env}.encrypted
-        if input_path.ends_with(".encrypted") {
-            // Already encrypted, just return as-is (shouldn't happen normally)
-            input_path.to_string()
-        } else if input_path == ".env" {
-            // Simple .env case
-            ".env.encrypted".to_string()
-        } else if input_path.starts_with(".env.") {
-            // .env.{something} case - append .encrypted
-            format!("{}.encrypted", input_path)
-        } else {
-            // Other paths - append .encrypted
-            format!("{}.encrypted", input_path)
-        }
+        // Always append, even to a name that already ends in .encrypted: encrypting such a
+        // file must never overwrite it (see --allow-double-encrypt)
+        with_file_name(path, &format!("{}{}", file_name, ENCRYPTED_SUFFIX))
     } else {
         // For decryption: .env.encrypted -> .env, .env.{env}.encrypted -> .env.{env}
-        if input_path.ends_with(".env.encrypted") {
-            input_path.replace(".env.encrypted", ".env")
-        } else if input_path.ends_with(".encrypted") {
-            input_path.strip_suffix(".encrypted").unwrap_or(input_path).to_string()
-        } else {
+        match file_name.strip_suffix(ENCRYPTED_SUFFIX) {
+            Some(stem) if !stem.is_empty() => with_file_name(path, stem),
             // Fallback: return input as-is (shouldn't happen in normal usage)
-            input_path.to_string()
+            _ => input_path.to_string(),
         }
     }
 }
 
+/// Suffix that marks encrypted files.
+pub(crate) const ENCRYPTED_SUFFIX: &str = ".encrypted";
+
+/// Replaces the file name of `path`, keeping its directory, drive and separators.
+fn with_file_name(path: &Path, file_name: &str) -> String {
+    path.with_file_name(file_name).to_string_lossy().into_owned()
+}
+
 /// Resolves the input path for encryption operations.
 pub fn resolve_encrypt_input_path(input: &Option<String>, env: &Option<String>) -> String {
     if let Some(input) = input {
@@ -83,14 +86,11 @@ pub fn resolve_encrypt_input_path(input: &Option<String>, env: &Option<String>)
 /// Resolves the output path for encryption operations.
 pub fn resolve_encrypt_output_path(input_path: &str, env: &Option<String>) -> String {
     if let Some(env_name) = env {
-        let input_path_buf = PathBuf::from(input_path);
-        let output_filename = format!(".env.{}.encrypted", env_name);
-        
-        if let Some(parent_dir) = input_path_buf.parent() {
-            return parent_dir.join(&output_filename).to_string_lossy().to_string();
-        } else {
-            return output_filename;
-        }
+        let output_filename = format!(".env.{}{}", env_name, ENCRYPTED_SUFFIX);
+        return match Path::new(input_path).file_name() {
+            Some(_) => with_file_name(Path::new(input_path), &output_filename),
+            None => output_filename,
+        };
     }
     
     derive_output_path(input_path, true)
@@ -101,6 +101,11 @@ pub fn resolve_decrypt_input(input: String) -> String {
     input
 }
 
+/// Resolves the input path for decrypting an environment (`decrypt --env`).
+pub fn resolve_decrypt_env_input(env: &str) -> String {
+    format!(".env.{}{}", env, ENCRYPTED_SUFFIX)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -134,4 +139,46 @@ mod tests {
     fn test_derive_output_path_decrypt_custom_encrypted() {
         assert_eq!(derive_output_path("file.encrypted", false), "file");
     }
+
+    #[test]
+    fn test_derive_output_path_only_changes_file_name() {
+        assert_eq!(derive_output_path("backups.encrypted/.env", true), "backups.encrypted/.env.encrypted");
+        assert_eq!(derive_output_path("old.env.encrypted/.env.encrypted", false), "old.env.encrypted/.env");
+        assert_eq!(derive_output_path("./.env.production", true), "./.env.production.encrypted");
+    }
+
+    #[test]
+    fn test_derive_output_path_never_returns_input_when_encrypting() {
+        assert_eq!(derive_output_path(".env.encrypted", true), ".env.encrypted.encrypted");
+    }
+
+    #[test]
+    fn test_derive_output_path_without_file_name() {
+        assert_eq!(derive_output_path(".encrypted", false), ".encrypted");
+        assert_eq!(derive_output_path("..", false), "..");
+    }
+
+    #[test]
+    fn test_resolve_encrypt_output_path_with_env() {
+        let env = Some("staging".to_string());
+        assert_eq!(resolve_encrypt_output_path(".env.staging", &env), ".env.staging.encrypted");
+        assert_eq!(resolve_encrypt_output_path("config/.env.staging", &env), "config/.env.staging.encrypted");
+    }
+
+    #[test]
+    fn test_windows_style_paths_keep_their_directory() {
+        // Holds on every platform: only the end of the file name is touched
+        assert_eq!(derive_output_path(r"C:\app\.env", true), r"C:\app\.env.encrypted");
+        assert_eq!(derive_output_path(r"C:\app\.env.local.encrypted", false), r"C:\app\.env.local");
+        assert_eq!(derive_output_path(r"..\shared\.env.encrypted", false), r"..\shared\.env");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_windows_env_output_next_to_input() {
+        let env = Some("staging".to_string());
+        assert_eq!(resolve_encrypt_output_path(r"C:\app\.env.staging", &env), r"C:\app\.env.staging.encrypted");
+        assert_eq!(resolve_encrypt_output_path(r"D:.env.staging", &env), r"D:.env.staging.encrypted");
+        assert_eq!(derive_output_path(r"C:\app.encrypted\.env", true), r"C:\app.encrypted\.env.encrypted");
+    }
 }
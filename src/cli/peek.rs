@@ -0,0 +1,114 @@
+//! Previewing the variables of encrypted files with masked values (`envcrypt peek`).
+
+use std::rc::Rc;
+
+use crate::cli::decrypt::{decrypt_file, DecryptOptions};
+use crate::cli::dotenv::parse_dotenv;
+use crate::cli::key_handling::{KeyFormat, KeyProvider};
+use crate::cli::memory::lock_secret;
+use crate::cli::output::OutputConfig;
+
+/// Shown instead of values too short to preview; always the same length so it does not
+/// give the length of the value away.
+const MASK: &str = "••••";
+
+/// Values shorter than this are masked completely, so a preview never shows more than
+/// half of a value.
+const MIN_PREVIEW_LEN: usize = 8;
+
+/// Options controlling how [`peek_env()`] decrypts the file.
+#[derive(Default)]
+pub struct PeekOptions {
+    /// Skip interactive prompts (error if no key is provided).
+    pub no_interaction: bool,
+    /// Encoding of the provided or prompted key.
+    pub key_format: KeyFormat,
+    /// Lock the key, derived keys, and plaintext into RAM so they are not swapped to disk.
+    pub mlock: bool,
+    /// Context string the file was bound to when it was encrypted.
+    pub aad: Option<String>,
+    /// Where to get the key when none is passed as `key_arg` (default: interactive prompt).
+    pub key_provider: Option<Rc<dyn KeyProvider>>,
+}
+
+/// Decrypts an encrypted environment file in memory and returns the name of each
+/// variable with a masked preview of its value (see [`mask_value()`]), in file order.
+///
+/// Values are previewed as they are written in the file; `$VAR` references are not
+/// expanded.
+///
+/// # Errors
+///
+/// Returns an error string if the encrypted file cannot be read or decrypted or is not a
+/// valid dotenv file.
+///
+/// # Example
+///
+/// ```no_run
+/// use envcrypt::cli::{peek_env, OutputConfig, PeekOptions};
+///
+/// let output_config = OutputConfig::new(false, false, 0);
+/// for (name, preview) in peek_env(Some("my-key"), ".env.encrypted", &output_config, &PeekOptions::default())? {
+///     println!("{} {}", name, preview);
+/// }
+/// # Ok::<(), String>(())
+/// ```
+pub fn peek_env(
+    key_arg: Option<&str>,
+    input_path: &str,
+    output_config: &OutputConfig,
+    options: &PeekOptions,
+) -> Result<Vec<(String, String)>, String> {
+    let _span = tracing::info_span!("peek", input = input_path).entered();
+    let decrypt_options = DecryptOptions {
+        no_interaction: options.no_interaction,
+        key_format: options.key_format,
+        mlock: options.mlock,
+        aad: options.aad.clone(),
+        key_provider: options.key_provider.clone(),
+        ..DecryptOptions::default()
+    };
+    let (_, plaintext) = decrypt_file(key_arg, input_path, output_config, &decrypt_options)?;
+    let _plaintext_lock = lock_secret(output_config, options.mlock, plaintext.as_bytes());
+    let variables = parse_dotenv(&plaintext)
+        .map_err(|e| format!("{} is not a valid dotenv file: {}", input_path, e))?;
+
+    Ok(variables.into_iter().map(|(name, value)| (name, mask_value(&value))).collect())
+}
+
+/// Masks a value for display, keeping its first and last 2 characters if it has at
+/// least 8, so it can be recognised without being exposed.
+///
+/// # Example
+///
+/// ```
+/// use envcrypt::cli::mask_value;
+///
+/// assert_eq!(mask_value("sk_live_51HqLyj"), "sk…yj");
+/// assert_eq!(mask_value("hunter2"), "••••");
+/// assert_eq!(mask_value(""), "(empty)");
+/// ```
+pub fn mask_value(value: &str) -> String {
+    // Slice the value instead of collecting its characters, so no copy of it is left behind
+    match value.chars().count() {
+        0 => "(empty)".to_string(),
+        len if len < MIN_PREVIEW_LEN => MASK.to_string(),
+        _ => {
+            let head_end = value.char_indices().nth(2).map_or(value.len(), |(index, _)| index);
+            let tail_start = value.char_indices().nth_back(1).map_or(0, |(index, _)| index);
+            format!("{}…{}", &value[..head_end], &value[tail_start..])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mask_value_counts_characters() {
+        assert_eq!(mask_value("pässwörter"), "pä…er");
+        assert_eq!(mask_value("密码密码密码密码"), "密码…密码");
+        assert_eq!(mask_value("äöüäöüä"), MASK);
+    }
+}
@@ -0,0 +1,73 @@
+//! Running the external tools that secrets are exchanged with (`docker`, `aws`, and the
+//! `op` and `bw` password manager CLIs).
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use zeroize::Zeroizing;
+
+/// Result of a finished external command.
+pub(crate) struct ProgramOutput {
+    /// Whether the command exited successfully.
+    pub(crate) success: bool,
+    /// Everything the command wrote to stdout; zeroized when dropped, as it may hold
+    /// secrets read back from a store.
+    pub(crate) stdout: Zeroizing<Vec<u8>>,
+    /// Everything the command wrote to stderr, trimmed.
+    pub(crate) stderr: String,
+}
+
+/// Runs `program` with `args` and waits for it to finish, writing `stdin` to it if given.
+///
+/// Secrets should be passed as `stdin` rather than in `args`, where other users can see
+/// them in the process list.
+///
+/// # Errors
+///
+/// Returns an error string only if the program cannot be started or its input and output
+/// cannot be transferred; a failing command is reported in [`ProgramOutput::success`].
+pub(crate) fn run_program(program: &str, args: &[&str], stdin: Option<&[u8]>) -> Result<ProgramOutput, String> {
+    tracing::trace!("Running {} {}", program, args.join(" "));
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Error running {}: {} - is it installed and on PATH?", program, e))?;
+
+    // Feed stdin from another thread so a command that writes a lot before reading all
+    // of its input cannot deadlock against us
+    let output = std::thread::scope(|scope| {
+        let writer = match (stdin, child.stdin.take()) {
+            (Some(data), Some(mut pipe)) => Some(scope.spawn(move || pipe.write_all(data))),
+            _ => None,
+        };
+        let output = child.wait_with_output();
+        if let Some(writer) = writer {
+            match writer.join().unwrap_or(Ok(())) {
+                // The command exited without reading all of its input; its exit status
+                // and stderr say why
+                Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => {}
+                result => result.map_err(|e| format!("Error writing to {}: {}", program, e))?,
+            }
+        }
+        output.map_err(|e| format!("Error running {}: {}", program, e))
+    })?;
+
+    Ok(ProgramOutput {
+        success: output.status.success(),
+        stdout: Zeroizing::new(output.stdout),
+        stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+    })
+}
+
+/// Like [`run_program()`], but a failing command is an error naming the program and the
+/// first two arguments (the subcommand), followed by what the command printed to stderr.
+pub(crate) fn check_program(program: &str, args: &[&str], stdin: Option<&[u8]>) -> Result<Zeroizing<Vec<u8>>, String> {
+    let output = run_program(program, args, stdin)?;
+    if !output.success {
+        return Err(format!("{} {} failed: {}", program, args[..args.len().min(2)].join(" "), output.stderr));
+    }
+    Ok(output.stdout)
+}
@@ -0,0 +1,244 @@
+//! Interactive input: the [`Prompt`] abstraction and yes/no questions.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use zeroize::Zeroizing;
+
+use super::console::{read_secret, Console};
+
+/// Source of interactive input and sink for the messages that go with it.
+///
+/// Interactive flows such as the key menu talk to the user only through this trait, so
+/// they can run on the terminal ([`TerminalPrompt`]), from a script in tests
+/// ([`ScriptedPrompt`]), or in another front end.
+pub trait Prompt {
+    /// Shows a message, such as menu options or a notice.
+    fn message(&self, text: &str);
+
+    /// Asks for a line of visible input and returns it without the line ending.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error string if input cannot be read.
+    fn read_line(&self, prompt: &str) -> Result<String, String>;
+
+    /// Asks for a secret without echoing it. The returned text is trimmed and zeroized
+    /// when dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error string if input cannot be read.
+    fn read_secret(&self, prompt: &str) -> Result<Zeroizing<String>, String>;
+
+    /// Whether someone is there to answer. Flows that cannot continue without an answer
+    /// check this first and fail with a helpful error instead of blocking.
+    fn is_interactive(&self) -> bool {
+        true
+    }
+}
+
+/// Reads from the terminal: stdin for lines, the TTY without echo for secrets.
+///
+/// Secrets are read in the way that works for the terminal: through the console API in
+/// Windows consoles, with `stty` in mintty, and a line at a time from piped input on
+/// Windows.
+///
+/// A closed stdin reads as an empty line. The prompt is interactive only when stdin is a
+/// terminal.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TerminalPrompt;
+
+impl Prompt for TerminalPrompt {
+    fn message(&self, text: &str) {
+        println!("{}", text);
+    }
+
+    fn read_line(&self, prompt: &str) -> Result<String, String> {
+        print!("{}", prompt);
+        io::stdout().flush().map_err(|e| format!("Failed to flush stdout: {}", e))?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)
+            .map_err(|e| format!("Failed to read input: {}", e))?;
+        Ok(input.trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    fn read_secret(&self, prompt: &str) -> Result<Zeroizing<String>, String> {
+        print!("{}", prompt);
+        io::stdout().flush().map_err(|e| format!("Failed to flush stdout: {}", e))?;
+
+        let key = read_secret(Console::detect())
+            .map_err(|e| format!("Failed to read password: {}", e))?;
+        Ok(Zeroizing::new(key.trim().to_string()))
+    }
+
+    fn is_interactive(&self) -> bool {
+        io::stdin().is_terminal()
+    }
+}
+
+/// Answers prompts from a fixed list, recording everything shown.
+///
+/// Lines and secrets are taken from the same queue in order. Running out of answers is
+/// an error, which makes unexpected extra prompts visible in tests.
+///
+/// # Example
+///
+/// ```
+/// use envcrypt::cli::{Prompt, ScriptedPrompt};
+///
+/// let prompt = ScriptedPrompt::new(["2", "hunter2"]);
+/// assert_eq!(prompt.read_line("Enter choice [1]: ")?, "2");
+/// assert_eq!(prompt.read_secret("Enter key: ")?.as_str(), "hunter2");
+/// assert!(prompt.read_line("More? ").is_err());
+/// assert_eq!(prompt.transcript()[0], "Enter choice [1]: ");
+/// # Ok::<(), String>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct ScriptedPrompt {
+    answers: RefCell<VecDeque<String>>,
+    transcript: RefCell<Vec<String>>,
+}
+
+impl ScriptedPrompt {
+    /// Creates a prompt that gives `answers` in order.
+    pub fn new<I, S>(answers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            answers: RefCell::new(answers.into_iter().map(Into::into).collect()),
+            transcript: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Returns the messages and prompts shown so far, in order.
+    pub fn transcript(&self) -> Vec<String> {
+        self.transcript.borrow().clone()
+    }
+
+    fn next_answer(&self, prompt: &str) -> Result<String, String> {
+        self.transcript.borrow_mut().push(prompt.to_string());
+        self.answers.borrow_mut().pop_front()
+            .ok_or_else(|| format!("No scripted answer for prompt: {}", prompt.trim()))
+    }
+}
+
+impl Prompt for ScriptedPrompt {
+    fn message(&self, text: &str) {
+        self.transcript.borrow_mut().push(text.to_string());
+    }
+
+    fn read_line(&self, prompt: &str) -> Result<String, String> {
+        self.next_answer(prompt)
+    }
+
+    fn read_secret(&self, prompt: &str) -> Result<Zeroizing<String>, String> {
+        self.next_answer(prompt).map(|answer| Zeroizing::new(answer.trim().to_string()))
+    }
+}
+
+/// Environment variable that answers "yes" to confirmation questions when set to `1`,
+/// `true` or `yes`.
+pub const ASSUME_YES_ENV_VAR: &str = "ENVCRYPT_ASSUME_YES";
+
+/// Set by `--yes` for the rest of the process.
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+/// Environment variable that answers the key menu when encrypting: `generate` or `custom`.
+pub const KEY_CHOICE_ENV_VAR: &str = "ENVCRYPT_KEY_CHOICE";
+
+/// Reads a yes/no answer from the environment variable `name`.
+///
+/// Unset or empty variables count as "no"; unrecognized values are rejected so a typo
+/// does not silently change the answer.
+pub fn env_answer(name: &str) -> Result<bool, String> {
+    match std::env::var(name) {
+        Ok(value) => parse_env_bool(name, &value),
+        Err(_) => Ok(false),
+    }
+}
+
+fn parse_env_bool(name: &str, value: &str) -> Result<bool, String> {
+    match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "y" => Ok(true),
+        "" | "0" | "false" | "no" | "n" => Ok(false),
+        other => Err(format!("Invalid value for {}: {} (expected 1 or 0)", name, other)),
+    }
+}
+
+/// Answers "yes" to confirmation questions for the rest of the process (`--yes`).
+pub(crate) fn set_assume_yes() {
+    ASSUME_YES.store(true, Ordering::Relaxed);
+}
+
+/// Whether confirmation questions are answered "yes" without asking, by `--yes` or
+/// `ENVCRYPT_ASSUME_YES`.
+///
+/// # Errors
+///
+/// Returns an error string if `ENVCRYPT_ASSUME_YES` has an unrecognized value.
+pub(crate) fn assumed_yes() -> Result<bool, String> {
+    Ok(ASSUME_YES.load(Ordering::Relaxed) || env_answer(ASSUME_YES_ENV_VAR)?)
+}
+
+/// Asks a yes/no question, defaulting to "no".
+///
+/// Only "y" and "yes" (case-insensitive) count as confirmation; an empty answer or a
+/// closed stdin is treated as "no".
+pub fn confirm(prompt: &dyn Prompt, question: &str) -> Result<bool, String> {
+    let input = prompt.read_line(&format!("{} [y/N] ", question))?;
+    Ok(matches!(input.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Checks whether `path` may be overwritten.
+///
+/// Missing files, `force`, `--yes` and `ENVCRYPT_ASSUME_YES` always pass. Otherwise the user is
+/// asked for confirmation, unless `no_interaction` is set, in which case the overwrite is
+/// refused.
+///
+/// # Errors
+///
+/// Returns an error string if the file exists and the overwrite was not allowed.
+pub fn confirm_overwrite(path: &Path, force: bool, no_interaction: bool) -> Result<(), String> {
+    if !path.exists() || force || assumed_yes()? {
+        return Ok(());
+    }
+
+    let name = path.display();
+    if no_interaction {
+        return Err(format!("Output file {} already exists. Use --force to overwrite.", name));
+    }
+    if confirm(&TerminalPrompt, &format!("Overwrite {}?", name))? {
+        Ok(())
+    } else {
+        Err(format!("Output file {} already exists and was not overwritten. Use --force to overwrite.", name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confirm_accepts_only_yes() {
+        let prompt = ScriptedPrompt::new(["Y", "yes", "", "no", "yep"]);
+        let answers: Vec<bool> = (0..5).map(|_| confirm(&prompt, "Continue?").unwrap()).collect();
+        assert_eq!(answers, vec![true, true, false, false, false]);
+        assert_eq!(prompt.transcript()[0], "Continue? [y/N] ");
+    }
+
+    #[test]
+    fn test_parse_env_bool() {
+        assert!(parse_env_bool("X", "1").unwrap());
+        assert!(parse_env_bool("X", " YES ").unwrap());
+        assert!(!parse_env_bool("X", "").unwrap());
+        assert!(!parse_env_bool("X", "false").unwrap());
+        assert_eq!(parse_env_bool("X", "sure").unwrap_err(), "Invalid value for X: sure (expected 1 or 0)");
+    }
+}
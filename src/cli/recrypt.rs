@@ -0,0 +1,244 @@
+//! Re-encryption command implementation (`envcrypt recrypt`).
+
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::format::{self, Kdf};
+use crate::recovery::RECOVERY_KEY_LEN;
+use crate::cli::cipher::canonical_cipher_name;
+use crate::armor::{decode_file, FileFormat};
+use crate::cli::backup::{backup_file, BackupConfig};
+use crate::cli::cleanup::TempPath;
+use crate::cli::decrypt::{decrypt_contents, DecryptOptions};
+use crate::cli::encrypt::{check_iteration_range, encrypt_contents, EncryptOptions};
+use crate::cli::key_handling::{get_key, KeyFormat, KeyProvider, KeyRequest};
+use crate::cli::memory::lock_secret;
+use crate::cli::lock::{lock_file, LockWait};
+use crate::cli::output::{OutputConfig, info, dry_run, success};
+use crate::cli::logging::Timer;
+use crate::cli::attributes::FileAttributes;
+use crate::cli::audit::{note_read, note_written};
+
+/// Options controlling how [`recrypt_env()`] migrates a file.
+#[derive(Clone, Default)]
+pub struct RecryptOptions {
+    /// Cipher the file is expected to be encrypted with. Required for legacy files that
+    /// are not AES-256-CBC; must match the header otherwise.
+    pub from_cipher: Option<String>,
+    /// Cipher to re-encrypt with (default: keep the current cipher).
+    pub to_cipher: Option<String>,
+    /// PBKDF2 iteration count to re-encrypt with (default: keep the current count).
+    pub kdf_iterations: Option<u32>,
+    /// Switch to raw key mode (HKDF instead of PBKDF2). The key must be a base64-encoded
+    /// 32-byte key. Files already in raw key mode stay in it.
+    pub raw_key: bool,
+    /// Skip interactive prompts (error if no key is provided).
+    pub no_interaction: bool,
+    /// Encoding of the provided or prompted key.
+    pub key_format: KeyFormat,
+    /// Recovery public key to wrap the new file keys for (key escrow).
+    pub recovery_public_key: Option<[u8; RECOVERY_KEY_LEN]>,
+    /// OpenPGP key IDs to encrypt the new file keys to with `gpg`.
+    pub gpg_recipients: Vec<String>,
+    /// SSH public keys to encrypt the new file keys to.
+    pub ssh_recipients: Vec<String>,
+    /// Back up the original file before replacing it.
+    pub backup: Option<BackupConfig>,
+    /// Validate everything and report the planned actions without writing files.
+    pub dry_run: bool,
+    /// Lock the key, derived keys, and plaintext into RAM so they are not swapped to disk.
+    pub mlock: bool,
+    /// Encoding to write the file in (default: keep the current encoding).
+    pub format: Option<FileFormat>,
+    /// Context string the file is bound to; the re-encrypted file stays bound to it.
+    pub aad: Option<String>,
+    /// Where to get the key when none is passed as `key_arg` (default: interactive prompt).
+    pub key_provider: Option<Rc<dyn KeyProvider>>,
+    /// Rewrite the files [`migrate_env()`](crate::cli::migrate_env) finds in a directory
+    /// without asking first.
+    pub force: bool,
+    /// How long to wait for another envcrypt process writing the file.
+    pub lock_wait: LockWait,
+    /// Only allow re-encrypting with AEAD ciphers and modern KDF parameters
+    /// (`--strict-crypto`, see [`EncryptOptions::strict_crypto`]).
+    pub strict_crypto: bool,
+}
+
+/// Re-encrypts an encrypted environment file in place with a new cipher and/or KDF.
+///
+/// The file is decrypted in memory with the given key and encrypted again with the same
+/// key, a fresh salt, and the current file format. The content and the header comment are
+/// preserved exactly; the creation time is updated. The original file is only replaced
+/// once the new contents have been written successfully.
+///
+/// # Arguments
+///
+/// * `key_arg` - Optional key. If `None`, the user will be prompted (unless `no_interaction` is set).
+/// * `input_path` - Path to the encrypted file to migrate
+/// * `output_config` - Output configuration for verbosity control
+/// * `options` - Target cipher and KDF, and key handling options
+///
+/// # Returns
+///
+/// Returns the name of the cipher the file was re-encrypted with.
+///
+/// # Errors
+///
+/// Returns an error string if the file cannot be read or written, the key is wrong, the
+/// requested KDF parameters are invalid, or any error [`crate::cli::decrypt_env()`] or
+/// [`crate::cli::encrypt_env()`] would report.
+///
+/// # Example
+///
+/// ```no_run
+/// use envcrypt::cli::{recrypt_env, OutputConfig, RecryptOptions};
+///
+/// let output_config = OutputConfig::new(false, false, 0);
+/// let options = RecryptOptions {
+///     to_cipher: Some("AES-256-GCM".to_string()),
+///     ..RecryptOptions::default()
+/// };
+/// recrypt_env(Some("my-key"), ".env.encrypted", &output_config, &options)?;
+/// # Ok::<(), String>(())
+/// ```
+pub fn recrypt_env(
+    key_arg: Option<&str>,
+    input_path: &str,
+    output_config: &OutputConfig,
+    options: &RecryptOptions,
+) -> Result<String, String> {
+    let encrypted_path = Path::new(input_path);
+
+    if !encrypted_path.exists() {
+        return Err(format!("{} file not found", input_path));
+    }
+
+    let _lock = if options.dry_run {
+        None
+    } else {
+        Some(lock_file(encrypted_path, options.lock_wait, output_config)?)
+    };
+
+    let _span = tracing::info_span!("recrypt", input = input_path).entered();
+    let (new_content, to_cipher) = recrypt_contents(key_arg, None, input_path, output_config, options)?;
+
+    if options.dry_run {
+        if let Some(backup) = &options.backup {
+            dry_run(output_config, &format!("Would back up {} to {}", input_path, backup.backup_path(encrypted_path).display()));
+        }
+        dry_run(output_config, &format!("Would re-encrypt {} with {}", input_path, to_cipher));
+        return Ok(to_cipher);
+    }
+
+    if let Some(backup) = &options.backup {
+        if let Some(backup_path) = backup_file(encrypted_path, backup)? {
+            info(output_config, &format!("Backed up {} to {}", input_path, backup_path.display()));
+        }
+    }
+
+    // Write next to the original and rename so a failure never leaves a truncated file
+    let temp = TempPath::new(format!("{}.tmp", input_path));
+    let timer = Timer::start("Writing output");
+    fs::write(temp.path(), new_content)
+        .map_err(|e| format!("Error writing {}: {}", temp.path().display(), e))?;
+    timer.finish();
+    fs::rename(temp.path(), encrypted_path)
+        .map_err(|e| format!("Error replacing {}: {}", input_path, e))?;
+    note_written(input_path);
+
+    success(output_config, &format!("Successfully re-encrypted {} with {}", input_path, to_cipher));
+    Ok(to_cipher)
+}
+
+/// Reads the encrypted file at `input_path` and encrypts its contents again as
+/// [`recrypt_env()`] does, without writing anything. With `new_key`, the contents are
+/// encrypted with it instead of the key they were decrypted with.
+///
+/// # Returns
+///
+/// Returns the new contents of the file and the name of the cipher they are encrypted with.
+pub(crate) fn recrypt_contents(
+    key_arg: Option<&str>,
+    new_key: Option<&str>,
+    input_path: &str,
+    output_config: &OutputConfig,
+    options: &RecryptOptions,
+) -> Result<(Vec<u8>, String), String> {
+    if let Some(iterations) = options.kdf_iterations {
+        check_iteration_range(iterations)?;
+    }
+    let encrypted_path = Path::new(input_path);
+    tracing::trace!("Starting re-encryption: {}", input_path);
+
+    let timer = Timer::start("Reading input");
+    let encrypted_content = fs::read(encrypted_path)
+        .map_err(|e| format!("Error reading {} file: {}", input_path, e))?;
+    timer.finish();
+    note_read(input_path);
+    let (data, current_format) = decode_file(&encrypted_content)?;
+
+    // Ask for the key once and hand the decoded key to both halves verbatim
+    let challenge = format::decode(&data).ok().and_then(|(header, _)| header.challenge);
+    let request = KeyRequest { is_encrypt: false, no_interaction: options.no_interaction, key_format: options.key_format, challenge };
+    let key_input = get_key(key_arg, options.key_provider.as_deref(), &request)?.value;
+    let _key_lock = lock_secret(output_config, options.mlock, key_input.as_bytes());
+    let decrypt_options = DecryptOptions {
+        no_interaction: true,
+        key_format: KeyFormat::Utf8,
+        mlock: options.mlock,
+        aad: options.aad.clone(),
+        ..DecryptOptions::default()
+    };
+    let (header, plaintext) = decrypt_contents(
+        &data,
+        options.from_cipher.as_deref(),
+        Some(&key_input),
+        output_config,
+        &decrypt_options,
+    )?;
+    let _plaintext_lock = lock_secret(output_config, options.mlock, plaintext.as_bytes());
+
+    let from_cipher = header.cipher.clone()
+        .or_else(|| options.from_cipher.clone())
+        .unwrap_or_else(|| "AES-256-CBC".to_string())
+        .to_uppercase();
+    let to_cipher = match options.to_cipher.as_deref() {
+        Some(to_cipher) => canonical_cipher_name(to_cipher)?.to_string(),
+        None => from_cipher.clone(),
+    };
+
+    // Keep the current KDF unless a different one was requested
+    let use_raw_key = options.raw_key || (header.kdf == Kdf::HkdfSha256 && options.kdf_iterations.is_none());
+    let kdf_iterations = match (options.kdf_iterations, header.kdf) {
+        (Some(iterations), _) => iterations,
+        (None, Kdf::Pbkdf2Sha256 { iterations }) => iterations,
+        (None, Kdf::HkdfSha256) => EncryptOptions::default().kdf_iterations,
+    };
+    let encrypt_options = EncryptOptions {
+        kdf_iterations,
+        recovery_public_key: options.recovery_public_key,
+        gpg_recipients: options.gpg_recipients.clone(),
+        ssh_recipients: options.ssh_recipients.clone(),
+        mlock: options.mlock,
+        format: options.format.unwrap_or(current_format),
+        comment: header.metadata.comment.clone(),
+        aad: options.aad.clone(),
+        // The same key and challenge keep the file decryptable with the hardware token
+        challenge: if new_key.is_some() { None } else { header.challenge },
+        strict_crypto: options.strict_crypto,
+        ..EncryptOptions::default()
+    };
+
+    let new_content = encrypt_contents(
+        &to_cipher,
+        plaintext.as_bytes(),
+        &FileAttributes::from_metadata(&header.metadata),
+        new_key.unwrap_or(&key_input),
+        use_raw_key,
+        output_config,
+        &encrypt_options,
+    )?;
+    tracing::debug!("Cipher: {} -> {}", from_cipher, to_cipher);
+    Ok((new_content, to_cipher))
+}
@@ -0,0 +1,246 @@
+//! Filling in config file templates with decrypted variables (`envcrypt render`).
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::rc::Rc;
+
+use zeroize::Zeroizing;
+
+use crate::cli::attributes::{write_with_mode, FileAttributes};
+use crate::cli::audit::note_read;
+use crate::cli::backup::{backup_file, report_planned_write, BackupConfig};
+use crate::cli::decrypt::{decrypt_file, DecryptOptions};
+use crate::cli::dotenv::{is_valid_key, load_variables};
+use crate::cli::key_handling::{KeyFormat, KeyProvider};
+use crate::cli::memory::lock_secret;
+use crate::cli::output::{dry_run, info, success, OutputConfig};
+use crate::cli::prompt::confirm_overwrite;
+
+/// Options controlling how [`render_env()`] decrypts the variables and writes the result.
+#[derive(Default)]
+pub struct RenderOptions {
+    /// Overwrite an existing output file instead of failing.
+    pub force: bool,
+    /// Skip interactive prompts (error if no key is provided).
+    pub no_interaction: bool,
+    /// Encoding of the provided or prompted key.
+    pub key_format: KeyFormat,
+    /// Back up an existing output file before overwriting it.
+    pub backup: Option<BackupConfig>,
+    /// Validate everything and report the planned actions without writing anything.
+    pub dry_run: bool,
+    /// Lock the key, derived keys, and plaintext into RAM so they are not swapped to disk.
+    pub mlock: bool,
+    /// Context string the file was bound to when it was encrypted.
+    pub aad: Option<String>,
+    /// Where to get the key when none is passed as `key_arg` (default: interactive prompt).
+    pub key_provider: Option<Rc<dyn KeyProvider>>,
+    /// Leave placeholders for variables the file does not define as they are, instead of
+    /// failing.
+    pub keep_undefined: bool,
+    /// Take `$VAR` references in values literally instead of expanding them.
+    pub no_expand: bool,
+}
+
+/// Decrypts an encrypted environment file in memory and fills in the placeholders of a
+/// template with its variables (see [`render_template()`]).
+///
+/// The rendered template is written to `output_path`, with the permissions the env file
+/// had when it was encrypted, or printed to stdout if `output_path` is `None`. No
+/// decrypted `.env` file is written. References to other variables in the values of the
+/// env file are expanded first, unless `no_expand` is set.
+///
+/// # Errors
+///
+/// Returns an error string if the template cannot be read, the output file exists and
+/// may not be overwritten, the encrypted file cannot be read or decrypted or is not a
+/// valid dotenv file, the template uses variables the file does not define (unless
+/// `keep_undefined` is set), or the result cannot be written.
+///
+/// # Example
+///
+/// ```no_run
+/// use envcrypt::cli::{render_env, OutputConfig, RenderOptions};
+///
+/// let output_config = OutputConfig::new(false, false, 0);
+/// render_env(Some("my-key"), ".env.production.encrypted", "nginx.conf.tpl", Some("nginx.conf"), &output_config, &RenderOptions::default())?;
+/// # Ok::<(), String>(())
+/// ```
+pub fn render_env(
+    key_arg: Option<&str>,
+    input_path: &str,
+    template_path: &str,
+    output_path: Option<&str>,
+    output_config: &OutputConfig,
+    options: &RenderOptions,
+) -> Result<(), String> {
+    let template = fs::read_to_string(template_path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => format!("{} file not found", template_path),
+        _ => format!("Error reading {} file: {}", template_path, e),
+    })?;
+    note_read(template_path);
+    if let Some(output_path) = output_path {
+        if !options.dry_run || options.no_interaction {
+            confirm_overwrite(Path::new(output_path), options.force, options.no_interaction)?;
+        }
+    }
+
+    let _span = tracing::info_span!("render", input = input_path, template = template_path).entered();
+    let decrypt_options = DecryptOptions {
+        no_interaction: options.no_interaction,
+        key_format: options.key_format,
+        mlock: options.mlock,
+        aad: options.aad.clone(),
+        key_provider: options.key_provider.clone(),
+        ..DecryptOptions::default()
+    };
+    let (header, plaintext) = decrypt_file(key_arg, input_path, output_config, &decrypt_options)?;
+    let _plaintext_lock = lock_secret(output_config, options.mlock, plaintext.as_bytes());
+    let variables = load_variables(&plaintext, !options.no_expand)
+        .map_err(|e| format!("{} is not a valid dotenv file: {}", input_path, e))?;
+
+    let rendered = render_template(&template, &variables, options.keep_undefined).map_err(|e| {
+        format!("Error rendering {}: {} in {} (use --keep-undefined to leave them as they are)", template_path, e, input_path)
+    })?;
+    let _rendered_lock = lock_secret(output_config, options.mlock, rendered.as_bytes());
+
+    let Some(output_path) = output_path else {
+        if options.dry_run {
+            dry_run(output_config, &format!("Would print {} rendered", template_path));
+        } else {
+            std::io::stdout()
+                .write_all(rendered.as_bytes())
+                .map_err(|e| format!("Error writing to stdout: {}", e))?;
+        }
+        return Ok(());
+    };
+
+    let path = Path::new(output_path);
+    if options.dry_run {
+        report_planned_write(output_config, path, options.force, options.backup.as_ref());
+        return Ok(());
+    }
+    if let Some(backup) = &options.backup {
+        if let Some(backup_path) = backup_file(path, backup)? {
+            info(output_config, &format!("Backed up {} to {}", output_path, backup_path.display()));
+        }
+    }
+    let attributes = FileAttributes::from_metadata(&header.metadata);
+    write_with_mode(path, rendered.as_bytes(), attributes.mode)
+        .map_err(|e| format!("Error writing {}: {}", output_path, e))?;
+
+    success(output_config, &format!("Rendered {} to {}", template_path, output_path));
+    Ok(())
+}
+
+/// Replaces the `${VAR}` and `{{VAR}}` placeholders in `template` with the values of
+/// `variables`.
+///
+/// Spaces around the name are allowed in `{{ VAR }}`. Anything else that only looks like
+/// a placeholder, such as `$VAR`, `${VAR:-default}` or `{{ .Values.name }}`, is left as it
+/// is, so templates can still contain shell, nginx or Helm syntax. Placeholders for
+/// variables that are not defined are an error listing their names, or are left as they
+/// are with `keep_undefined`.
+///
+/// # Example
+///
+/// ```
+/// use envcrypt::cli::render_template;
+/// use zeroize::Zeroizing;
+///
+/// let variables = vec![("PORT".to_string(), Zeroizing::new("8080".to_string()))];
+/// assert_eq!(render_template("listen ${PORT}; # {{ PORT }}", &variables, false)?.as_str(), "listen 8080; # 8080");
+/// assert!(render_template("${HOST}", &variables, false).is_err());
+/// # Ok::<(), String>(())
+/// ```
+pub fn render_template(
+    template: &str,
+    variables: &[(String, Zeroizing<String>)],
+    keep_undefined: bool,
+) -> Result<Zeroizing<String>, String> {
+    let mut rendered = Zeroizing::new(String::with_capacity(template.len()));
+    let mut undefined: Vec<&str> = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find(['$', '{']) {
+        rendered.push_str(&rest[..start]);
+        let (open, close) = if rest[start..].starts_with("${") { ("${", "}") } else { ("{{", "}}") };
+        let placeholder = rest[start..].strip_prefix(open).and_then(|after| {
+            let end = after.find(close)?;
+            let name = if open == "{{" { after[..end].trim() } else { &after[..end] };
+            is_valid_key(name).then_some((name, open.len() + end + close.len()))
+        });
+
+        let Some((name, length)) = placeholder else {
+            // Not a placeholder: keep the character and look further
+            let c = rest[start..].chars().next().unwrap_or_default();
+            rendered.push(c);
+            rest = &rest[start + c.len_utf8()..];
+            continue;
+        };
+        match variables.iter().rev().find(|(key, _)| key == name) {
+            Some((_, value)) => rendered.push_str(value),
+            None => {
+                if !undefined.contains(&name) {
+                    undefined.push(name);
+                }
+                rendered.push_str(&rest[start..start + length]);
+            }
+        }
+        rest = &rest[start + length..];
+    }
+    rendered.push_str(rest);
+
+    if !undefined.is_empty() && !keep_undefined {
+        let noun = if undefined.len() == 1 { "variable" } else { "variables" };
+        return Err(format!("undefined {} {}", noun, undefined.join(", ")));
+    }
+    tracing::debug!("Rendered template with {} undefined placeholders", undefined.len());
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables(pairs: &[(&str, &str)]) -> Vec<(String, Zeroizing<String>)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), Zeroizing::new(v.to_string()))).collect()
+    }
+
+    #[test]
+    fn test_render_template() {
+        let vars = variables(&[("HOST", "db.internal"), ("PORT", "5432"), ("EMPTY", "")]);
+
+        assert_eq!(
+            render_template("url: postgres://${HOST}:{{PORT}}/app\nopt: '{{ EMPTY }}'\n", &vars, false).unwrap().as_str(),
+            "url: postgres://db.internal:5432/app\nopt: ''\n"
+        );
+        // Values are inserted as they are, not rendered again
+        let nested = variables(&[("A", "${B}"), ("B", "b")]);
+        assert_eq!(render_template("${A}", &nested, false).unwrap().as_str(), "${B}");
+    }
+
+    #[test]
+    fn test_render_template_leaves_other_syntax() {
+        let vars = variables(&[("HOST", "example.com")]);
+        let template = "server_name $host ${HOST};\n${HOST:-x} {{ .Values.name }} {{HOST} ${ unclosed {single} $";
+        assert_eq!(
+            render_template(template, &vars, false).unwrap().as_str(),
+            "server_name $host example.com;\n${HOST:-x} {{ .Values.name }} {{HOST} ${ unclosed {single} $"
+        );
+        assert_eq!(render_template("héllo {{ HOST }} wörld", &vars, false).unwrap().as_str(), "héllo example.com wörld");
+    }
+
+    #[test]
+    fn test_render_template_undefined() {
+        let vars = variables(&[("HOST", "example.com")]);
+
+        let error = render_template("${USER}@${HOST} {{ PASSWORD }} ${USER}", &vars, false).unwrap_err();
+        assert_eq!(error, "undefined variables USER, PASSWORD");
+        assert_eq!(
+            render_template("${USER}@${HOST}", &vars, true).unwrap().as_str(),
+            "${USER}@example.com"
+        );
+    }
+}
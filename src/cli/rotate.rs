@@ -0,0 +1,165 @@
+//! Rotating the key of every encrypted file in a directory tree (`envcrypt rotate`).
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::cli::audit::key_fingerprint;
+use crate::cli::backup::backup_file;
+use crate::cli::decrypt::AUTHENTICATION_FAILED;
+use crate::cli::key_handling::{get_key, KeyFormat, KeyRequest};
+use crate::cli::memory::lock_secret;
+use crate::cli::output::{dry_run, info, warning, OutputConfig};
+use crate::cli::paths::ENCRYPTED_SUFFIX;
+use crate::cli::recrypt::{recrypt_contents, RecryptOptions};
+use crate::cli::transaction::Transaction;
+use crate::cli::walk::{collect_files, WalkOptions};
+
+/// What [`rotate_env()`] did with each file it found, as printed by `envcrypt rotate`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RotateReport {
+    /// Fingerprint of the old key, as in the audit log.
+    pub old_key_fingerprint: String,
+    /// Fingerprint of the new key, as in the audit log.
+    pub new_key_fingerprint: String,
+    /// `true` if nothing was written (`--dry-run`).
+    pub dry_run: bool,
+    /// Files re-encrypted with the new key (or that would be, with `dry_run`).
+    pub rotated: Vec<PathBuf>,
+    /// Files that could not be decrypted with the old key, and were left as they are.
+    pub skipped: Vec<PathBuf>,
+    /// Files that could not be rotated for another reason, and were left as they are.
+    pub failed: Vec<RotateFailure>,
+}
+
+/// A file [`rotate_env()`] could not rotate.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RotateFailure {
+    /// Path of the file.
+    pub path: PathBuf,
+    /// Why it could not be rotated.
+    pub error: String,
+}
+
+/// Re-encrypts every encrypted file under `path` that is encrypted with the old key with
+/// the new key.
+///
+/// `path` is either a single encrypted file or a directory, in which case every file
+/// whose name ends in `.encrypted` is rotated (and, with `walk.recursive`, those in its
+/// subdirectories as well), skipping those excluded by `walk` (see [`WalkOptions`]). The
+/// cipher and KDF of each file are kept unless `options` ask for others.
+///
+/// Files that cannot be decrypted with the old key are skipped, as they may belong to
+/// another key; a tampered file looks the same, so it is skipped too. Every file is
+/// re-encrypted to a temporary file first, and the files rotated are replaced together at
+/// the end (see [`migrate_env()`](crate::cli::migrate_env)), so an interrupted rotation
+/// leaves either all of them or none of them rotated.
+///
+/// # Returns
+///
+/// Returns which files were rotated, skipped and failed.
+///
+/// # Errors
+///
+/// Returns an error string if `path` does not exist, a key cannot be obtained, both keys
+/// are the same, or the rotated files cannot be written, in which case no file was changed.
+///
+/// # Example
+///
+/// ```no_run
+/// use envcrypt::cli::{rotate_env, OutputConfig, RecryptOptions, WalkOptions};
+///
+/// let output_config = OutputConfig::new(false, false, 0);
+/// let walk = WalkOptions { recursive: true, ..WalkOptions::default() };
+/// let report = rotate_env(Some("old-key"), Some("new-key"), ".", &walk, &output_config, &RecryptOptions::default())?;
+/// println!("Rotated {} files", report.rotated.len());
+/// # Ok::<(), String>(())
+/// ```
+pub fn rotate_env(
+    old_key_arg: Option<&str>,
+    new_key_arg: Option<&str>,
+    path: &str,
+    walk: &WalkOptions,
+    output_config: &OutputConfig,
+    options: &RecryptOptions,
+) -> Result<RotateReport, String> {
+    let root = Path::new(path);
+    if !root.exists() {
+        return Err(format!("{} not found", path));
+    }
+    let files = if root.is_dir() {
+        collect_files(root, walk, |name| name.ends_with(ENCRYPTED_SUFFIX))?
+    } else {
+        vec![root.to_path_buf()]
+    };
+
+    let request = KeyRequest { is_encrypt: false, no_interaction: options.no_interaction, key_format: options.key_format, challenge: None };
+    let old_key = get_key(old_key_arg, options.key_provider.as_deref(), &request)?.value;
+    let _old_key_lock = lock_secret(output_config, options.mlock, old_key.as_bytes());
+    if new_key_arg.is_none() && options.no_interaction {
+        return Err("The new key is required when using --no-interaction. Please provide --new-key".to_string());
+    }
+    let request = KeyRequest { is_encrypt: true, ..request };
+    let new_key = get_key(new_key_arg, None, &request)?.value;
+    let _new_key_lock = lock_secret(output_config, options.mlock, new_key.as_bytes());
+    if new_key == old_key {
+        return Err("The new key is the same as the old key".to_string());
+    }
+
+    let mut report = RotateReport {
+        old_key_fingerprint: key_fingerprint(&old_key),
+        new_key_fingerprint: key_fingerprint(&new_key),
+        dry_run: options.dry_run,
+        ..RotateReport::default()
+    };
+    if files.is_empty() {
+        info(output_config, &format!("No encrypted files found in {}", path));
+        return Ok(report);
+    }
+
+    let batch_dir = if root.is_dir() { root } else { root.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new(".")) };
+    let mut transaction = if options.dry_run {
+        None
+    } else {
+        Some(Transaction::begin(batch_dir, options.lock_wait, output_config)?)
+    };
+    let file_options = RecryptOptions { key_format: KeyFormat::Utf8, ..options.clone() };
+
+    for file in files {
+        let file_path = file.to_string_lossy().to_string();
+        if let Some(transaction) = transaction.as_mut() {
+            transaction.lock(&file, options.lock_wait, output_config)?;
+        }
+        let new_content = match recrypt_contents(Some(&old_key), Some(&new_key), &file_path, output_config, &file_options) {
+            Ok((new_content, _)) => new_content,
+            Err(e) if e == AUTHENTICATION_FAILED => {
+                warning(output_config, &format!("Skipping {}: it is not encrypted with the old key", file_path));
+                report.skipped.push(file);
+                continue;
+            }
+            Err(e) => {
+                warning(output_config, &format!("Error rotating {}: {}", file_path, e));
+                report.failed.push(RotateFailure { path: file, error: e });
+                continue;
+            }
+        };
+
+        match transaction.as_mut() {
+            Some(transaction) => {
+                if let Some(backup) = &options.backup {
+                    if let Some(backup_path) = backup_file(&file, backup)? {
+                        info(output_config, &format!("Backed up {} to {}", file_path, backup_path.display()));
+                    }
+                }
+                transaction.stage(&file, &new_content)?;
+            }
+            None => dry_run(output_config, &format!("Would rotate the key of {}", file_path)),
+        }
+        report.rotated.push(file);
+    }
+
+    if let Some(transaction) = transaction {
+        transaction.commit()?;
+    }
+    Ok(report)
+}
@@ -0,0 +1,256 @@
+//! Updating the installed binary from GitHub releases (`envcrypt self-update`).
+//!
+//! Releases are looked up with the GitHub API and downloaded with `curl`, the same way
+//! `install.sh` installs envcrypt. Every release binary is published with a `.sha256`
+//! file; the download is only installed if it matches and runs.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::cli::output::{dry_run, success, OutputConfig, Spinner};
+use crate::cli::process::{check_program, run_program};
+
+/// Environment variable naming the GitHub repository (`owner/repo`) to update from, as
+/// for `install.sh`.
+pub const REPO_ENV_VAR: &str = "ENVCRYPT_REPO";
+
+/// Repository updates come from unless [`REPO_ENV_VAR`] is set.
+const DEFAULT_REPO: &str = "SanderCokart/envcrypt";
+
+/// Which releases [`self_update()`] considers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum UpdateChannel {
+    /// The latest full release
+    #[default]
+    Stable,
+    /// The newest release, including pre-releases
+    Prerelease,
+}
+
+impl std::fmt::Display for UpdateChannel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpdateChannel::Stable => write!(f, "stable"),
+            UpdateChannel::Prerelease => write!(f, "prerelease"),
+        }
+    }
+}
+
+/// Options controlling [`self_update()`].
+#[derive(Debug, Clone, Default)]
+pub struct SelfUpdateOptions {
+    /// Releases to consider.
+    pub channel: UpdateChannel,
+    /// Install the release even if it is not newer than the running version.
+    pub force: bool,
+    /// Report the update without downloading or installing it.
+    pub dry_run: bool,
+}
+
+/// A release binary for this platform.
+struct Release {
+    version: String,
+    binary_url: String,
+    checksum_url: Option<String>,
+}
+
+/// Replaces the running binary with the newest release on `options.channel`.
+///
+/// The release binary for this platform is downloaded next to the running binary,
+/// checked against the SHA-256 checksum published with it, run with `--version`, and
+/// only then moved over the running binary.
+///
+/// # Returns
+///
+/// Returns the version that was installed, or `None` if the running version is already
+/// the newest (or `dry_run` is set).
+///
+/// # Errors
+///
+/// Returns an error string if `curl` is missing or a request fails, the release has no
+/// binary or checksum for this platform, the checksum does not match, the downloaded
+/// binary does not run, or the running binary cannot be replaced.
+///
+/// # Example
+///
+/// ```no_run
+/// use envcrypt::cli::{self_update, OutputConfig, SelfUpdateOptions};
+///
+/// let output_config = OutputConfig::new(false, false, 0);
+/// self_update(&output_config, &SelfUpdateOptions::default())?;
+/// # Ok::<(), String>(())
+/// ```
+pub fn self_update(output_config: &OutputConfig, options: &SelfUpdateOptions) -> Result<Option<String>, String> {
+    let current = env!("CARGO_PKG_VERSION");
+    let spinner = Spinner::start(output_config, "Checking for updates...");
+    let release = find_release(options.channel)?;
+    drop(spinner);
+
+    if !options.force && !is_newer(&release.version, current) {
+        success(output_config, &format!("envcrypt {} is up to date ({} channel)", current, options.channel));
+        return Ok(None);
+    }
+    let checksum_url = release.checksum_url.as_deref().ok_or_else(|| format!(
+        "Release {} has no checksum for {} - refusing to install it unverified",
+        release.version,
+        asset_name(&release.version),
+    ))?;
+    let target = std::env::current_exe().map_err(|e| format!("Cannot find the running binary: {}", e))?;
+    if options.dry_run {
+        dry_run(output_config, &format!("Would update {} from {} to {} ({})", target.display(), current, release.version, release.binary_url));
+        return Ok(None);
+    }
+
+    let spinner = Spinner::start(output_config, &format!("Downloading envcrypt {}...", release.version));
+    let checksum = check_program("curl", &["-fsSL", checksum_url], None)?;
+    let download = temporary_path(&target);
+    let result = download_binary(&release.binary_url, &download, &checksum)
+        .and_then(|()| replace_binary(&download, &target));
+    drop(spinner);
+    if result.is_err() {
+        let _ = fs::remove_file(&download);
+    }
+    result?;
+
+    success(output_config, &format!("Updated envcrypt {} to {}", current, release.version));
+    Ok(Some(release.version))
+}
+
+/// Looks up the newest release on `channel` and its binary for this platform.
+fn find_release(channel: UpdateChannel) -> Result<Release, String> {
+    let repo = std::env::var(REPO_ENV_VAR).ok().filter(|repo| !repo.is_empty()).unwrap_or_else(|| DEFAULT_REPO.to_string());
+    let url = match channel {
+        UpdateChannel::Stable => format!("https://api.github.com/repos/{}/releases/latest", repo),
+        UpdateChannel::Prerelease => format!("https://api.github.com/repos/{}/releases?per_page=20", repo),
+    };
+    let response = check_program("curl", &["-fsSL", "-H", "Accept: application/vnd.github+json", &url], None)?;
+    let json: serde_json::Value = serde_json::from_slice(&response)
+        .map_err(|e| format!("Unexpected response from {}: {}", url, e))?;
+
+    // The list of releases is sorted newest first
+    let release = match &json {
+        serde_json::Value::Array(releases) => releases.iter()
+            .find(|release| release["draft"] != true)
+            .ok_or_else(|| format!("{} has no releases", repo))?,
+        release => release,
+    };
+    let tag = release["tag_name"].as_str()
+        .ok_or_else(|| format!("Unexpected response from {}: no tag_name", url))?;
+    let version = tag.trim_start_matches('v').to_string();
+
+    let name = asset_name(&version);
+    let asset_url = |name: &str| {
+        release["assets"].as_array()?.iter()
+            .find(|asset| asset["name"] == name)?["browser_download_url"].as_str()
+            .map(str::to_string)
+    };
+    let binary_url = asset_url(&name)
+        .ok_or_else(|| format!("Release {} has no binary for this platform ({})", version, name))?;
+    let checksum_url = asset_url(&format!("{}.sha256", name));
+    Ok(Release { version, binary_url, checksum_url })
+}
+
+/// Name of the release binary for this platform, as built by the release workflow.
+fn asset_name(version: &str) -> String {
+    let os = match std::env::consts::OS {
+        "macos" => "darwin",
+        os => os,
+    };
+    format!("envcrypt-{}-{}-{}{}", version, os, std::env::consts::ARCH, std::env::consts::EXE_SUFFIX)
+}
+
+/// Downloads the binary at `url` to `path`, checks it against the `.sha256` file
+/// contents `checksum` and that it runs.
+fn download_binary(url: &str, path: &Path, checksum: &[u8]) -> Result<(), String> {
+    let path_str = path.to_string_lossy();
+    check_program("curl", &["-fsSL", "-o", &path_str, url], None)?;
+
+    let expected = std::str::from_utf8(checksum).ok()
+        .and_then(|text| text.split_whitespace().next())
+        .map(str::to_ascii_lowercase)
+        .filter(|hex| hex.len() == 64)
+        .ok_or_else(|| "The release checksum file is invalid".to_string())?;
+    let contents = fs::read(path).map_err(|e| format!("Error reading the download {}: {}", path.display(), e))?;
+    let actual: String = Sha256::digest(&contents).iter().map(|b| format!("{:02x}", b)).collect();
+    if actual != expected {
+        return Err(format!("Checksum mismatch for {} - expected {}, got {}", url, expected, actual));
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+            .map_err(|e| format!("Error making {} executable: {}", path.display(), e))?;
+    }
+    let output = run_program(&path_str, &["--version"], None)?;
+    if !output.success {
+        return Err(format!("The downloaded binary does not run: {}", output.stderr));
+    }
+    Ok(())
+}
+
+/// Path next to `target` to download the new binary to, so it can be renamed over it.
+fn temporary_path(target: &Path) -> PathBuf {
+    let name = target.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    target.with_file_name(format!(".{}.update", name))
+}
+
+/// Moves the downloaded binary over the running one.
+fn replace_binary(download: &Path, target: &Path) -> Result<(), String> {
+    // Windows cannot replace a running executable, but it can rename it out of the way
+    #[cfg(windows)]
+    {
+        let old = target.with_extension("old.exe");
+        let _ = fs::remove_file(&old);
+        fs::rename(target, &old).map_err(|e| format!("Error moving {} aside: {}", target.display(), e))?;
+    }
+    fs::rename(download, target).map_err(|e| format!("Error replacing {}: {}", target.display(), e))
+}
+
+/// Whether version `candidate` is newer than `current` (`MAJOR.MINOR.PATCH`, optionally
+/// followed by `-PRERELEASE`, which sorts before the release).
+fn is_newer(candidate: &str, current: &str) -> bool {
+    fn parse(version: &str) -> Option<([u64; 3], Option<&str>)> {
+        let (numbers, prerelease) = match version.split_once('-') {
+            Some((numbers, prerelease)) => (numbers, Some(prerelease)),
+            None => (version, None),
+        };
+        let numbers: Vec<u64> = numbers.split('.').map(|part| part.parse().ok()).collect::<Option<_>>()?;
+        Some((numbers.try_into().ok()?, prerelease))
+    }
+    match (parse(candidate), parse(current)) {
+        (Some((new, new_pre)), Some((old, old_pre))) if new == old => match (new_pre, old_pre) {
+            (None, Some(_)) => true,
+            (Some(new_pre), Some(old_pre)) => new_pre > old_pre,
+            _ => false,
+        },
+        (Some((new, _)), Some((old, _))) => new > old,
+        _ => candidate != current,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_newer() {
+        assert!(is_newer("0.3.0", "0.2.1"));
+        assert!(is_newer("1.0.0", "0.9.9"));
+        assert!(!is_newer("0.2.1", "0.2.1"));
+        assert!(!is_newer("0.2.0", "0.2.1"));
+        assert!(is_newer("0.3.0", "0.3.0-beta.1"));
+        assert!(!is_newer("0.3.0-beta.1", "0.3.0"));
+        assert!(is_newer("0.3.0-beta.2", "0.3.0-beta.1"));
+        assert!(is_newer("0.3.0-beta.1", "0.2.1"));
+    }
+
+    #[test]
+    fn test_asset_name() {
+        let name = asset_name("1.2.3");
+        assert!(name.starts_with("envcrypt-1.2.3-"));
+        assert!(name.contains(std::env::consts::ARCH));
+    }
+}
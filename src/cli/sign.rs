@@ -0,0 +1,119 @@
+//! Signing encrypted files (`encrypt --sign-key`) and verifying them (`envcrypt verify`).
+//!
+//! Signing keys are Ed25519 key pairs written as `base64:` strings, like recovery keys.
+//! The private key is read from a file so it stays out of argv; detached signatures are
+//! written next to the encrypted file as `<file>.sig` (see [`crate::signature`]).
+
+use std::fs;
+use std::path::Path;
+
+use base64::Engine;
+use zeroize::Zeroizing;
+
+use crate::armor::decode_file;
+use crate::format;
+use crate::key::decode_raw_key;
+use crate::signature::{verify_detached, verify_embedded, SignatureError, SIGNING_KEY_LEN};
+
+use super::attributes::write_with_mode;
+use super::audit::note_read;
+use super::key_handling::strip_base64_prefix;
+
+/// Suffix of detached signature files.
+pub const SIGNATURE_SUFFIX: &str = ".sig";
+
+/// Returns where the detached signature of `path` is written.
+pub(crate) fn detached_signature_path(path: &str) -> String {
+    format!("{}{}", path, SIGNATURE_SUFFIX)
+}
+
+/// Formats a signing public key the way `key sign-keygen` prints it.
+pub(crate) fn public_key_text(public: &[u8; SIGNING_KEY_LEN]) -> String {
+    format!("base64:{}", base64::engine::general_purpose::STANDARD.encode(public))
+}
+
+/// Decodes a signing public key (`base64:...`).
+///
+/// # Errors
+///
+/// Returns an error string if the key is not a base64-encoded 32-byte key.
+pub fn decode_signing_public_key(key: &str) -> Result<[u8; SIGNING_KEY_LEN], String> {
+    decode_raw_key(strip_base64_prefix(key.trim()))
+        .ok_or_else(|| format!("Invalid signing public key '{}': expected a base64-encoded 32-byte key", key.trim()))
+}
+
+/// Reads a signing private key from the file at `path`, as written by `key sign-keygen`.
+///
+/// # Errors
+///
+/// Returns an error string if the file cannot be read or does not hold a base64-encoded
+/// 32-byte key.
+pub fn read_signing_key(path: &str) -> Result<Zeroizing<[u8; SIGNING_KEY_LEN]>, String> {
+    let contents = Zeroizing::new(fs::read_to_string(path)
+        .map_err(|e| format!("Error reading signing key {}: {}", path, e))?);
+    decode_raw_key(strip_base64_prefix(contents.trim()))
+        .map(Zeroizing::new)
+        .ok_or_else(|| format!("{} does not contain a signing private key (a base64-encoded 32-byte key)", path))
+}
+
+/// Writes a detached signature as a line of base64 text.
+pub(crate) fn write_detached_signature(path: &str, signature: &[u8]) -> Result<(), String> {
+    let text = format!("{}\n", base64::engine::general_purpose::STANDARD.encode(signature));
+    write_with_mode(Path::new(path), text.as_bytes(), None)
+        .map_err(|e| format!("Error writing {}: {}", path, e))
+}
+
+/// Verifies the signature of an encrypted file against the `trusted` public keys.
+///
+/// The detached signature at `signature_path` is checked if given. Otherwise the
+/// embedded signature is checked, or `<input>.sig` if the file has none.
+///
+/// # Returns
+///
+/// Returns the public key that signed the file.
+///
+/// # Errors
+///
+/// Returns an error string if no trusted key is given, the file or signature cannot be
+/// read, the file is not signed, or the signature is invalid or made by an untrusted key.
+pub fn verify_env(
+    input_path: &str,
+    signature_path: Option<&str>,
+    trusted: &[[u8; SIGNING_KEY_LEN]],
+) -> Result<[u8; SIGNING_KEY_LEN], String> {
+    if trusted.is_empty() {
+        return Err("No trusted signing key. Pass --signer-pub or set signer_public_keys in envcrypt.toml.".to_string());
+    }
+    let contents = fs::read(input_path)
+        .map_err(|e| format!("Error reading {} file: {}", input_path, e))?;
+    note_read(input_path);
+
+    let data = decode_file(&contents)?.0;
+    let default_signature_path = detached_signature_path(input_path);
+    let detached_path = match signature_path {
+        Some(path) => Some(path.to_string()),
+        None if format::decode(&data).is_ok_and(|(header, _)| header.signed) => None,
+        None if Path::new(&default_signature_path).exists() => Some(default_signature_path),
+        None => {
+            return Err(format!("{} has no embedded signature and {} does not exist", input_path, default_signature_path));
+        }
+    };
+    let result = match detached_path {
+        Some(path) => verify_detached(&contents, &read_detached_signature(&path)?, trusted),
+        None => verify_embedded(&data, trusted),
+    };
+    result.map_err(|e| match e {
+        SignatureError::UntrustedSigner(signer) => {
+            format!("{} is signed by {}, which is not a trusted signing key", input_path, public_key_text(&signer))
+        }
+        e => format!("Signature verification of {} failed: {}", input_path, e),
+    })
+}
+
+fn read_detached_signature(path: &str) -> Result<Vec<u8>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Error reading signature {}: {}", path, e))?;
+    note_read(path);
+    base64::engine::general_purpose::STANDARD.decode(text.trim())
+        .map_err(|e| format!("Invalid base64 in signature {}: {}", path, e))
+}
@@ -0,0 +1,235 @@
+//! Wrapping file keys for SSH public keys, the way `age` does.
+//!
+//! `encrypt --recipient-ssh` encrypts the file keys (the derived encryption and MAC keys)
+//! to `ssh-ed25519` and `ssh-rsa` public keys and stores the resulting age message in the
+//! file header. `decrypt --ssh` decrypts it with the matching private key, so teammates
+//! can use the SSH keys they already have instead of sharing a symmetric key.
+//!
+//! Recipients are given as a public key line (`ssh-ed25519 AAAA... alice@laptop`), a file
+//! of such lines (`~/.ssh/id_ed25519.pub`, `authorized_keys`), or `github:<username>`,
+//! which fetches the keys published at `https://github.com/<username>.keys`.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use age::secrecy::SecretString;
+use age::ssh::{Identity, ParseRecipientKeyError, Recipient, UnsupportedKey};
+
+use super::dirs::home_dir;
+use super::output::{warning, OutputConfig};
+use super::process::check_program;
+use super::prompt::{Prompt, TerminalPrompt};
+
+/// Environment variable holding the passphrase of an encrypted SSH private key, for
+/// decrypting without a prompt.
+pub const SSH_PASSPHRASE_ENV_VAR: &str = "ENVCRYPT_SSH_PASSPHRASE";
+
+/// Length of each wrapped file key (encryption key and MAC key) in bytes.
+const FILE_KEY_LEN: usize = 32;
+
+/// Largest age message stored in a header, leaving room for the other fields within the
+/// 64 KiB header limit.
+const MAX_MESSAGE_LEN: usize = 32 * 1024;
+
+/// Private keys tried by `decrypt --ssh` when no key is named, in order.
+const DEFAULT_IDENTITIES: &[&str] = &["id_ed25519", "id_rsa"];
+
+/// Resolves recipient specifications to SSH public keys.
+///
+/// Key types that cannot be used for encryption (such as `ecdsa-sha2-*` keys) are skipped
+/// with a warning, as long as every specification yields at least one usable key.
+///
+/// # Errors
+///
+/// Returns an error string if a file cannot be read, a GitHub user's keys cannot be
+/// fetched, a key is malformed, or a specification yields no usable key.
+pub(crate) fn resolve_ssh_recipients(specs: &[String], output_config: &OutputConfig) -> Result<Vec<Recipient>, String> {
+    let mut recipients = Vec::new();
+    for spec in specs {
+        let lines = if let Some(user) = spec.strip_prefix("github:") {
+            if user.is_empty() || !user.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                return Err(format!("Invalid GitHub username in SSH recipient '{}'", spec));
+            }
+            let keys = check_program("curl", &["-fsSL", &format!("https://github.com/{}.keys", user)], None)?;
+            String::from_utf8_lossy(&keys).into_owned()
+        } else if is_public_key_line(spec) {
+            spec.clone()
+        } else {
+            let path = expand_home(spec);
+            std::fs::read_to_string(&path)
+                .map_err(|e| format!("Error reading SSH public key {}: {}", path.display(), e))?
+        };
+
+        let before = recipients.len();
+        for line in lines.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            match Recipient::from_str(line) {
+                Ok(recipient) => recipients.push(recipient),
+                Err(ParseRecipientKeyError::Unsupported(key_type)) => {
+                    warning(output_config, &format!("Skipping the {} key of {}: only ssh-ed25519 and ssh-rsa keys are supported", key_type, spec));
+                }
+                Err(ParseRecipientKeyError::RsaModulusTooSmall) => {
+                    return Err(format!("The ssh-rsa key of {} is too weak: use a key of at least 2048 bits", spec));
+                }
+                Err(_) => return Err(format!("Invalid SSH public key in {}", spec)),
+            }
+        }
+        if recipients.len() == before {
+            return Err(format!("No ssh-ed25519 or ssh-rsa public key found in {}", spec));
+        }
+    }
+    Ok(recipients)
+}
+
+/// Encrypts the file keys to `recipients`, returning an age message that any one of them
+/// can decrypt.
+///
+/// # Errors
+///
+/// Returns an error string if encryption fails or the message is too large.
+pub(crate) fn wrap_keys_ssh(recipients: &[Recipient], encryption_key: &[u8], mac_key: &[u8]) -> Result<Vec<u8>, String> {
+    let encryptor = age::Encryptor::with_recipients(recipients.iter().map(|recipient| recipient as &dyn age::Recipient))
+        .map_err(|e| format!("Cannot encrypt the file keys to the SSH recipients: {}", e))?;
+
+    let mut message = Vec::new();
+    let write = |message: &mut Vec<u8>| -> std::io::Result<()> {
+        let mut writer = encryptor.wrap_output(message)?;
+        writer.write_all(encryption_key)?;
+        writer.write_all(mac_key)?;
+        writer.finish()?;
+        Ok(())
+    };
+    write(&mut message).map_err(|e| format!("Cannot encrypt the file keys to the SSH recipients: {}", e))?;
+    if message.len() > MAX_MESSAGE_LEN {
+        return Err(format!("Too many SSH recipients: the wrapped keys exceed {} bytes", MAX_MESSAGE_LEN));
+    }
+    tracing::debug!("Wrapped file keys for {} SSH recipient(s)", recipients.len());
+    Ok(message)
+}
+
+/// Decrypts file keys wrapped by [`wrap_keys_ssh()`] with an SSH private key.
+///
+/// `identity` is the private key file; without one, `~/.ssh/id_ed25519` and
+/// `~/.ssh/id_rsa` are tried. The passphrase of an encrypted key is read from
+/// [`SSH_PASSPHRASE_ENV_VAR`] or prompted for, unless `no_interaction` is set.
+///
+/// # Returns
+///
+/// Returns `(encryption_key, mac_key)`.
+///
+/// # Errors
+///
+/// Returns an error string if no private key is found or it cannot be read or decrypted,
+/// the key is not one of the recipients, or the message does not contain file keys.
+pub(crate) fn unwrap_keys_ssh(message: &[u8], identity: Option<&Path>, no_interaction: bool) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let path = match identity {
+        Some(path) => path.to_path_buf(),
+        None => default_identity()?,
+    };
+    let identity = read_identity(&path, no_interaction)?;
+
+    let decryptor = age::Decryptor::new_buffered(message)
+        .map_err(|e| format!("Invalid SSH recipient data in the file header: {}", e))?;
+    let mut reader = decryptor.decrypt(std::iter::once(&identity as &dyn age::Identity))
+        .map_err(|e| match e {
+            age::DecryptError::NoMatchingKeys => format!("{} is not one of the SSH recipients of this file", path.display()),
+            e => format!("Cannot decrypt the file keys with {}: {}", path.display(), e),
+        })?;
+    let mut file_keys = zeroize::Zeroizing::new(Vec::with_capacity(2 * FILE_KEY_LEN));
+    std::io::Read::read_to_end(&mut reader, &mut file_keys)
+        .map_err(|e| format!("Cannot decrypt the file keys with {}: {}", path.display(), e))?;
+    if file_keys.len() != 2 * FILE_KEY_LEN {
+        return Err("The SSH recipient data in the file header does not contain file keys".to_string());
+    }
+    tracing::trace!("Using file keys unwrapped with {}", path.display());
+    let (encryption_key, mac_key) = file_keys.split_at(FILE_KEY_LEN);
+    Ok((encryption_key.to_vec(), mac_key.to_vec()))
+}
+
+/// Reads a private key, decrypting it once up front if it is protected by a passphrase.
+fn read_identity(path: &Path, no_interaction: bool) -> Result<Identity, String> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| format!("Error reading SSH private key {}: {}", path.display(), e))?;
+    let identity = Identity::from_buffer(std::io::BufReader::new(file), Some(path.display().to_string()))
+        .map_err(|e| format!("Error reading SSH private key {}: {}", path.display(), e))?;
+
+    match identity {
+        Identity::Unencrypted(_) => Ok(identity),
+        Identity::Encrypted(key) => {
+            let passphrase = match std::env::var(SSH_PASSPHRASE_ENV_VAR) {
+                Ok(passphrase) => passphrase,
+                Err(_) if no_interaction || !TerminalPrompt.is_interactive() => {
+                    return Err(format!(
+                        "{} is protected by a passphrase. Set {} to decrypt without interaction.",
+                        path.display(),
+                        SSH_PASSPHRASE_ENV_VAR,
+                    ));
+                }
+                Err(_) => TerminalPrompt.read_secret(&format!("Passphrase for {}: ", path.display()))?.to_string(),
+            };
+            key.decrypt(SecretString::from(passphrase))
+                .map(Identity::Unencrypted)
+                .map_err(|_| format!("Wrong passphrase for {}", path.display()))
+        }
+        Identity::Unsupported(key) => Err(match key {
+            UnsupportedKey::EncryptedPem => format!("{} is an encrypted PEM key, which is not supported. Convert it with `ssh-keygen -o -p -f {}`.", path.display(), path.display()),
+            UnsupportedKey::EncryptedSsh(cipher) => format!("{} is encrypted with {}, which is not supported", path.display(), cipher),
+            UnsupportedKey::Hardware(key_type) | UnsupportedKey::Type(key_type) => {
+                format!("{} is an {} key; only ssh-ed25519 and ssh-rsa keys are supported", path.display(), key_type)
+            }
+        }),
+    }
+}
+
+/// Finds the first of the default private keys in `~/.ssh`.
+fn default_identity() -> Result<PathBuf, String> {
+    let ssh_dir = home_dir()
+        .map(|home| home.join(".ssh"))
+        .ok_or_else(|| "Cannot find the home directory; name the private key with --ssh <PATH>".to_string())?;
+    DEFAULT_IDENTITIES.iter()
+        .map(|name| ssh_dir.join(name))
+        .find(|path| path.exists())
+        .ok_or_else(|| format!("No SSH private key found in {}; name it with --ssh <PATH>", ssh_dir.display()))
+}
+
+/// Returns `true` if `spec` is a public key line (`<type> AAAA... [comment]`) rather than
+/// a path.
+fn is_public_key_line(spec: &str) -> bool {
+    spec.split_whitespace().nth(1).is_some_and(|blob| blob.starts_with("AAAA"))
+}
+
+/// Expands a leading `~/` to the home directory, for paths from `envcrypt.toml`.
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ED25519_PUBLIC_KEY: &str = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIA56kZA98HgLYcThb/e8Zu3Fc2wYq2iTY3nOOUuS3y1F alice@example.com";
+
+    #[test]
+    fn test_resolve_public_key_line() {
+        let output_config = OutputConfig::new(true, false, 0);
+        let recipients = resolve_ssh_recipients(&[ED25519_PUBLIC_KEY.to_string()], &output_config).unwrap();
+        assert_eq!(recipients.len(), 1);
+    }
+
+    #[test]
+    fn test_resolve_rejects_invalid_key() {
+        let output_config = OutputConfig::new(true, false, 0);
+        let error = resolve_ssh_recipients(&["ssh-ed25519 AAAAC3Nz-invalid".to_string()], &output_config).unwrap_err();
+        assert!(error.contains("Invalid SSH public key"), "{}", error);
+    }
+
+    #[test]
+    fn test_resolve_rejects_invalid_github_user() {
+        let output_config = OutputConfig::new(true, false, 0);
+        let error = resolve_ssh_recipients(&["github:../evil".to_string()], &output_config).unwrap_err();
+        assert!(error.contains("Invalid GitHub username"), "{}", error);
+    }
+}
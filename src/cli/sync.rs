@@ -0,0 +1,397 @@
+//! Synchronising encrypted files with cloud secret stores (`envcrypt sync`).
+
+use std::fmt;
+use std::path::Path;
+
+use zeroize::Zeroizing;
+
+use crate::format::Kdf;
+use crate::cli::attributes::FileAttributes;
+use crate::cli::cipher::DEFAULT_CIPHER;
+use crate::cli::decrypt::{decrypt_file, DecryptOptions};
+use crate::cli::dotenv::{is_valid_key, parse_dotenv};
+use crate::cli::encrypt::{check_kdf_iterations, write_encrypted, EncryptOptions};
+use crate::cli::export::{format_variables, ExportFormat};
+use crate::cli::import::{parse_variables, ImportFormat};
+use crate::cli::key_handling::{get_key, KeyFormat, KeyRequest, UserKey};
+use crate::cli::memory::lock_secret;
+use crate::cli::newline::Newline;
+use crate::cli::output::{dry_run, info, success, OutputConfig};
+use crate::cli::process::{check_program, run_program};
+
+/// Variables as parsed from a dotenv file or a store, in order.
+type Variables = Vec<(String, Zeroizing<String>)>;
+
+/// Largest number of parameters `aws ssm delete-parameters` accepts at once.
+const SSM_DELETE_BATCH: usize = 10;
+
+/// A cloud secret store that [`sync_env()`] pushes to or pulls from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncStore {
+    /// One AWS Secrets Manager secret holding a JSON object of all variables.
+    AwsSecretsManager {
+        /// Name or ARN of the secret.
+        secret_id: String,
+    },
+    /// One AWS Systems Manager Parameter Store `SecureString` parameter per variable,
+    /// named `<path><KEY>`.
+    AwsSsm {
+        /// Path the parameters live under, e.g. `/app/prod/`.
+        path: String,
+    },
+}
+
+impl fmt::Display for SyncStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncStore::AwsSecretsManager { secret_id } => write!(f, "AWS Secrets Manager secret {}", secret_id),
+            SyncStore::AwsSsm { path } => write!(f, "AWS SSM parameters under {}", ssm_prefix(path)),
+        }
+    }
+}
+
+/// Which way [`sync_env()`] copies variables.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SyncDirection {
+    /// Make the store match the encrypted file.
+    #[default]
+    Push,
+    /// Make the encrypted file match the store.
+    Pull,
+}
+
+/// Options controlling how [`sync_env()`] talks to the store and handles the file.
+#[derive(Clone, Default)]
+pub struct SyncOptions {
+    /// Which way to copy variables.
+    pub direction: SyncDirection,
+    /// AWS region to use instead of the AWS CLI default.
+    pub region: Option<String>,
+    /// AWS CLI profile to use instead of the default.
+    pub profile: Option<String>,
+    /// Cipher to encrypt with when pulling (default: keep the current cipher, or
+    /// AES-256-CBC for a new file).
+    pub cipher: Option<String>,
+    /// Key handling and encryption options. `dry_run` reports the differences without
+    /// changing the store or the file.
+    pub encrypt: EncryptOptions,
+}
+
+/// Variables that differ between the source and the destination of a sync.
+#[derive(Debug, Default)]
+pub struct SyncChanges {
+    /// Variables only in the source.
+    pub added: Vec<String>,
+    /// Variables in both whose values differ.
+    pub changed: Vec<String>,
+    /// Variables only in the destination.
+    pub removed: Vec<String>,
+    /// Key a new encrypted file was created with when pulling, to show to the user.
+    pub new_file_key: Option<UserKey>,
+}
+
+impl SyncChanges {
+    /// Whether source and destination already hold the same variables.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+
+    /// Counts of the changes, for messages.
+    fn summary(&self) -> String {
+        format!("{} added, {} changed, {} removed", self.added.len(), self.changed.len(), self.removed.len())
+    }
+}
+
+/// Makes a cloud secret store and an encrypted environment file hold the same variables.
+///
+/// Pushing decrypts `file_path` in memory and adds, updates and removes variables in
+/// `store` until it matches. Pulling reads the variables from `store` and writes them to
+/// `file_path` as normalized dotenv (comments are not kept), encrypted with the same key
+/// and, unless `options.cipher` says otherwise, the same cipher; the file is created if
+/// it does not exist. Only variables that differ are written.
+///
+/// The store is accessed with the `aws` command-line tool, which must be installed and
+/// configured with credentials. Secret values are passed to it on stdin (as
+/// `file:///dev/stdin`), never as arguments, so this is not supported on Windows.
+///
+/// # Returns
+///
+/// Returns the variables that differed (and were synced unless `dry_run` is set).
+///
+/// # Errors
+///
+/// Returns an error string if the file cannot be decrypted or written, the store holds
+/// something other than a flat map of variables, or an `aws` command fails.
+///
+/// # Example
+///
+/// ```no_run
+/// use envcrypt::cli::{sync_env, OutputConfig, SyncDirection, SyncOptions, SyncStore};
+///
+/// let output_config = OutputConfig::new(false, false, 0);
+/// let store = SyncStore::AwsSecretsManager { secret_id: "app/prod".to_string() };
+/// let options = SyncOptions { direction: SyncDirection::Push, ..Default::default() };
+/// let changes = sync_env(&store, Some("my-key"), ".env.production.encrypted", &output_config, &options)?;
+/// println!("{} variables updated", changes.changed.len());
+/// # Ok::<(), String>(())
+/// ```
+pub fn sync_env(
+    store: &SyncStore,
+    key_arg: Option<&str>,
+    file_path: &str,
+    output_config: &OutputConfig,
+    options: &SyncOptions,
+) -> Result<SyncChanges, String> {
+    let _span = tracing::info_span!("sync", file = file_path, store = %store).entered();
+    let encrypt = &options.encrypt;
+    let file_exists = Path::new(file_path).exists();
+    if options.direction == SyncDirection::Push && !file_exists {
+        return Err(format!("{} file not found", file_path));
+    }
+    check_kdf_iterations(encrypt)?;
+
+    // Decrypt the current file once; pulling encrypts it again with the same key
+    let mut current = None;
+    if file_exists {
+        let request = KeyRequest { is_encrypt: false, no_interaction: encrypt.no_interaction, key_format: encrypt.key_format, challenge: None };
+        let key_input = get_key(key_arg, encrypt.key_provider.as_deref(), &request)?.value;
+        let decrypt_options = DecryptOptions {
+            no_interaction: encrypt.no_interaction,
+            key_format: KeyFormat::Utf8,
+            mlock: encrypt.mlock,
+            aad: encrypt.aad.clone(),
+            ..DecryptOptions::default()
+        };
+        let (header, plaintext) = decrypt_file(Some(&key_input), file_path, output_config, &decrypt_options)?;
+        let variables = parse_dotenv(&plaintext)
+            .map_err(|e| format!("{} is not a valid dotenv file: {}", file_path, e))?;
+        current = Some((header, key_input, variables, Newline::detect(plaintext.as_bytes())));
+    }
+    let local = current.as_ref().map(|(_, _, variables, _)| variables.as_slice()).unwrap_or_default();
+
+    let aws = Aws { region: options.region.as_deref(), profile: options.profile.as_deref() };
+    let remote = match store {
+        SyncStore::AwsSecretsManager { secret_id } => aws.get_secret(secret_id)?,
+        SyncStore::AwsSsm { path } => Some(aws.get_parameters(path)?),
+    };
+    if options.direction == SyncDirection::Pull && remote.is_none() {
+        return Err(format!("{} does not exist", store));
+    }
+    let remote_variables = remote.as_deref().unwrap_or_default();
+
+    let (destination, mut changes) = match options.direction {
+        SyncDirection::Push => (store.to_string(), diff(remote_variables, local)),
+        SyncDirection::Pull => (file_path.to_string(), diff(local, remote_variables)),
+    };
+    if changes.is_empty() && file_exists && remote.is_some() {
+        info(output_config, &format!("{} is already in sync", destination));
+        return Ok(changes);
+    }
+    if encrypt.dry_run {
+        dry_run(output_config, &format!("Would update {} ({})", destination, changes.summary()));
+        let lines = changes.added.iter().map(|key| ('+', key))
+            .chain(changes.changed.iter().map(|key| ('~', key)))
+            .chain(changes.removed.iter().map(|key| ('-', key)));
+        for (marker, key) in lines {
+            dry_run(output_config, &format!("  {} {}", marker, key));
+        }
+        return Ok(changes);
+    }
+
+    match (options.direction, store) {
+        (SyncDirection::Push, SyncStore::AwsSecretsManager { secret_id }) => {
+            aws.put_secret(secret_id, local, remote.is_some())?;
+        }
+        (SyncDirection::Push, SyncStore::AwsSsm { path }) => {
+            let updated: Vec<&(String, Zeroizing<String>)> = local.iter()
+                .filter(|(key, _)| changes.added.contains(key) || changes.changed.contains(key))
+                .collect();
+            aws.put_parameters(path, &updated, &changes.removed)?;
+        }
+        (SyncDirection::Pull, _) => {
+            let plaintext = format_variables(remote_variables, ExportFormat::Dotenv);
+            let _plaintext_lock = lock_secret(output_config, encrypt.mlock, plaintext.as_bytes());
+            let (cipher, file_options, key) = match &current {
+                Some((header, key_input, _, newline)) => {
+                    let file_options = EncryptOptions {
+                        key_format: KeyFormat::Utf8,
+                        raw_key: encrypt.raw_key || header.kdf == Kdf::HkdfSha256,
+                        newline: encrypt.newline.or(*newline),
+                        ..encrypt.clone()
+                    };
+                    (header.cipher.clone(), file_options, Some(key_input.as_str()))
+                }
+                None => (None, encrypt.clone(), key_arg),
+            };
+            let cipher = options.cipher.clone().or(cipher).unwrap_or_else(|| DEFAULT_CIPHER.to_string());
+            let key_input = write_encrypted(&cipher, key, plaintext.as_bytes(), &FileAttributes::default(), file_path, output_config, &file_options)?;
+            if current.is_none() {
+                changes.new_file_key = Some(key_input);
+            }
+        }
+    }
+
+    let verb = match options.direction {
+        SyncDirection::Push => format!("Pushed {} to {}", file_path, store),
+        SyncDirection::Pull => format!("Pulled {} into {}", store, file_path),
+    };
+    success(output_config, &format!("{}: {}", verb, changes.summary()));
+    Ok(changes)
+}
+
+/// Compares the variables at the destination (`old`) with those at the source (`new`).
+fn diff(old: &[(String, Zeroizing<String>)], new: &[(String, Zeroizing<String>)]) -> SyncChanges {
+    let mut changes = SyncChanges::default();
+    for (key, value) in new {
+        match old.iter().find(|(k, _)| k == key) {
+            None => changes.added.push(key.clone()),
+            Some((_, old_value)) if old_value != value => changes.changed.push(key.clone()),
+            Some(_) => {}
+        }
+    }
+    changes.removed = old.iter()
+        .filter(|(key, _)| !new.iter().any(|(k, _)| k == key))
+        .map(|(key, _)| key.clone())
+        .collect();
+    changes
+}
+
+/// Parameter name prefix for an SSM path, ending in `/`.
+fn ssm_prefix(path: &str) -> String {
+    if path.ends_with('/') {
+        path.to_string()
+    } else {
+        format!("{}/", path)
+    }
+}
+
+/// Runs the `aws` command-line tool with common options.
+struct Aws<'a> {
+    region: Option<&'a str>,
+    profile: Option<&'a str>,
+}
+
+impl Aws<'_> {
+    /// Runs `aws <args>` with JSON output, the region and the profile.
+    fn args<'b>(&'b self, args: &[&'b str]) -> Vec<&'b str> {
+        let mut all = args.to_vec();
+        all.extend(["--output", "json"]);
+        if let Some(region) = self.region {
+            all.extend(["--region", region]);
+        }
+        if let Some(profile) = self.profile {
+            all.extend(["--profile", profile]);
+        }
+        all
+    }
+
+    /// Runs `aws <args>` with `input` as `--cli-input-json`, passed on stdin.
+    fn call_with_input(&self, args: &[&str], input: &serde_json::Value) -> Result<(), String> {
+        let input = Zeroizing::new(input.to_string());
+        let mut args = args.to_vec();
+        args.extend(["--cli-input-json", "file:///dev/stdin"]);
+        check_program("aws", &self.args(&args), Some(input.as_bytes()))?;
+        Ok(())
+    }
+
+    /// Reads the variables in a Secrets Manager secret, or `None` if it does not exist.
+    fn get_secret(&self, secret_id: &str) -> Result<Option<Variables>, String> {
+        let output = run_program("aws", &self.args(&["secretsmanager", "get-secret-value", "--secret-id", secret_id]), None)?;
+        if !output.success {
+            if output.stderr.contains("ResourceNotFoundException") {
+                return Ok(None);
+            }
+            return Err(format!("aws secretsmanager get-secret-value failed: {}", output.stderr));
+        }
+        let response: serde_json::Value = serde_json::from_slice(&output.stdout)
+            .map_err(|e| format!("Unexpected output from aws: {}", e))?;
+        let secret_string = response["SecretString"].as_str()
+            .ok_or_else(|| format!("Secret {} has no string value", secret_id))?;
+        parse_variables(secret_string, ImportFormat::Json)
+            .map(Some)
+            .map_err(|e| format!("Secret {} is not a JSON object of variables: {}", secret_id, e))
+    }
+
+    /// Replaces the value of a Secrets Manager secret, creating it if it does not exist.
+    fn put_secret(&self, secret_id: &str, variables: &[(String, Zeroizing<String>)], exists: bool) -> Result<(), String> {
+        let secret_string = format_variables(variables, ExportFormat::Json);
+        if exists {
+            let input = serde_json::json!({ "SecretId": secret_id, "SecretString": secret_string.as_str() });
+            self.call_with_input(&["secretsmanager", "put-secret-value"], &input)
+        } else {
+            let input = serde_json::json!({ "Name": secret_id, "SecretString": secret_string.as_str() });
+            self.call_with_input(&["secretsmanager", "create-secret"], &input)
+        }
+    }
+
+    /// Reads the variables stored as parameters directly under `path`.
+    fn get_parameters(&self, path: &str) -> Result<Variables, String> {
+        let prefix = ssm_prefix(path);
+        let stdout = check_program("aws", &self.args(&["ssm", "get-parameters-by-path", "--path", &prefix, "--with-decryption"]), None)?;
+        let response: serde_json::Value = serde_json::from_slice(&stdout)
+            .map_err(|e| format!("Unexpected output from aws: {}", e))?;
+        let parameters = response["Parameters"].as_array().map(Vec::as_slice).unwrap_or_default();
+
+        let mut variables = Vec::new();
+        for parameter in parameters {
+            let (Some(name), Some(value)) = (parameter["Name"].as_str(), parameter["Value"].as_str()) else {
+                return Err("Unexpected output from aws: parameter without a name or value".to_string());
+            };
+            let key = name.strip_prefix(prefix.as_str()).unwrap_or(name);
+            if !is_valid_key(key) {
+                return Err(format!("Parameter {} is not named after a valid variable", name));
+            }
+            variables.push((key.to_string(), Zeroizing::new(value.to_string())));
+        }
+        Ok(variables)
+    }
+
+    /// Writes `updated` as `SecureString` parameters under `path` and deletes `removed`.
+    fn put_parameters(&self, path: &str, updated: &[&(String, Zeroizing<String>)], removed: &[String]) -> Result<(), String> {
+        let prefix = ssm_prefix(path);
+        for (key, value) in updated {
+            let input = serde_json::json!({
+                "Name": format!("{}{}", prefix, key),
+                "Value": value.as_str(),
+                "Type": "SecureString",
+                "Overwrite": true,
+            });
+            self.call_with_input(&["ssm", "put-parameter"], &input)?;
+            tracing::debug!("Wrote parameter {}{}", prefix, key);
+        }
+        for batch in removed.chunks(SSM_DELETE_BATCH) {
+            let names: Vec<String> = batch.iter().map(|key| format!("{}{}", prefix, key)).collect();
+            let mut args = vec!["ssm", "delete-parameters", "--names"];
+            args.extend(names.iter().map(String::as_str));
+            check_program("aws", &self.args(&args), None)?;
+            tracing::debug!("Deleted parameters {}", names.join(", "));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables(pairs: &[(&str, &str)]) -> Variables {
+        pairs.iter().map(|(k, v)| (k.to_string(), Zeroizing::new(v.to_string()))).collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = variables(&[("KEEP", "1"), ("CHANGE", "old"), ("REMOVE", "x")]);
+        let new = variables(&[("ADD", "a"), ("KEEP", "1"), ("CHANGE", "new")]);
+        let changes = diff(&old, &new);
+        assert_eq!(changes.added, vec!["ADD"]);
+        assert_eq!(changes.changed, vec!["CHANGE"]);
+        assert_eq!(changes.removed, vec!["REMOVE"]);
+        assert!(diff(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn test_ssm_prefix() {
+        assert_eq!(ssm_prefix("/app/prod"), "/app/prod/");
+        assert_eq!(ssm_prefix("/app/prod/"), "/app/prod/");
+    }
+}
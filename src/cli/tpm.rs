@@ -0,0 +1,138 @@
+//! Sealing keys to the machine's TPM 2.0 with `tpm2-tools`.
+//!
+//! `envcrypt key tpm-seal` seals a key under a storage key derived from the TPM's owner
+//! hierarchy and writes the sealed blob to `.envcrypt.tpm`. Only the same TPM can unseal
+//! it, so the blob can stay on a server's disk: the `tpm` key provider unseals it whenever
+//! a file is encrypted or decrypted, and a copy taken to another host is useless.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use zeroize::Zeroizing;
+
+use super::cleanup::TempPath;
+use super::key_handling::{KeyProvider, KeyRequest, UserKey};
+use super::process::check_program;
+
+/// Default location of the sealed key.
+pub const TPM_KEY_FILE: &str = ".envcrypt.tpm";
+
+/// First line of a sealed key file.
+const SEALED_KEY_HEADER: &str = "envcrypt tpm-sealed key v1";
+
+/// Largest key a TPM seals (`MAX_SYM_DATA`).
+const MAX_SEALED_LEN: usize = 128;
+
+/// Unseals the key in a sealed key file with this machine's TPM (`tpm` key provider).
+pub struct TpmKeyProvider {
+    /// Sealed key file, from `tpm_key_file` in `envcrypt.toml` (default `.envcrypt.tpm`).
+    pub path: PathBuf,
+}
+
+impl KeyProvider for TpmKeyProvider {
+    fn name(&self) -> &str {
+        "tpm"
+    }
+
+    fn provide(&self, _request: &KeyRequest) -> Result<UserKey, String> {
+        if !self.path.exists() {
+            return Err(format!(
+                "{} not found. Seal a key to this machine's TPM with `envcrypt key tpm-seal`.",
+                self.path.display()
+            ));
+        }
+        // Keys are sealed decoded, as `key tpm-seal` reads them
+        Ok(UserKey::provided(unseal_key(&self.path)?))
+    }
+}
+
+/// Seals `key` to this machine's TPM, returning the contents of a sealed key file.
+///
+/// # Errors
+///
+/// Returns an error string if the key is too long or `tpm2-tools` cannot reach the TPM.
+pub(crate) fn seal_key(key: &str) -> Result<String, String> {
+    if key.len() > MAX_SEALED_LEN {
+        return Err(format!("The TPM can only seal keys of up to {} bytes", MAX_SEALED_LEN));
+    }
+    let work_dir = WorkDir::create()?;
+    let primary = create_primary(&work_dir)?;
+    let public = work_dir.file("seal.pub");
+    let private = work_dir.file("seal.priv");
+    check_program(
+        "tpm2_create",
+        &["-Q", "-C", &primary, "-g", "sha256", "-u", &public, "-r", &private, "-i", "-"],
+        Some(key.as_bytes()),
+    )?;
+
+    let engine = &base64::engine::general_purpose::STANDARD;
+    let read = |path: &str| fs::read(path).map_err(|e| format!("Error reading {}: {}", path, e));
+    Ok(format!("{}\n{}\n{}\n", SEALED_KEY_HEADER, engine.encode(read(&public)?), engine.encode(read(&private)?)))
+}
+
+/// Unseals the key in the sealed key file at `path` with this machine's TPM.
+///
+/// # Errors
+///
+/// Returns an error string if the file cannot be read or is not a sealed key, or the TPM
+/// refuses to unseal it, e.g. because it was sealed on another machine.
+pub(crate) fn unseal_key(path: &Path) -> Result<Zeroizing<String>, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+    let engine = &base64::engine::general_purpose::STANDARD;
+    let mut lines = contents.lines().map(str::trim);
+    let blobs = match (lines.next(), lines.next(), lines.next()) {
+        (Some(SEALED_KEY_HEADER), Some(public), Some(private)) => engine.decode(public).ok().zip(engine.decode(private).ok()),
+        _ => None,
+    };
+    let (public_blob, private_blob) = blobs
+        .ok_or_else(|| format!("{} is not a TPM-sealed envcrypt key", path.display()))?;
+
+    let work_dir = WorkDir::create()?;
+    let primary = create_primary(&work_dir)?;
+    let public = work_dir.file("seal.pub");
+    let private = work_dir.file("seal.priv");
+    let sealed = work_dir.file("seal.ctx");
+    fs::write(&public, public_blob).map_err(|e| format!("Error writing {}: {}", public, e))?;
+    fs::write(&private, private_blob).map_err(|e| format!("Error writing {}: {}", private, e))?;
+    check_program("tpm2_load", &["-Q", "-C", &primary, "-u", &public, "-r", &private, "-c", &sealed], None)
+        .map_err(|e| format!("Cannot load the key sealed in {} (was it sealed on this machine?): {}", path.display(), e))?;
+    let key = check_program("tpm2_unseal", &["-c", &sealed], None)?;
+
+    let key = String::from_utf8(key.to_vec())
+        .map_err(|_| format!("The key sealed in {} is not valid UTF-8", path.display()))?;
+    tracing::debug!("Unsealed the key in {} with the TPM", path.display());
+    Ok(Zeroizing::new(key))
+}
+
+/// Recreates the storage key the keys are sealed under. The same TPM always derives the
+/// same key from the owner hierarchy, so it does not need to be persisted.
+fn create_primary(work_dir: &WorkDir) -> Result<String, String> {
+    let primary = work_dir.file("primary.ctx");
+    check_program("tpm2_createprimary", &["-Q", "-C", "o", "-g", "sha256", "-G", "ecc", "-c", &primary], None)?;
+    Ok(primary)
+}
+
+/// Private temporary directory for the context files `tpm2-tools` exchange, removed
+/// when dropped or when the process is interrupted.
+struct WorkDir(TempPath);
+
+impl WorkDir {
+    fn create() -> Result<Self, String> {
+        let path = std::env::temp_dir().join(format!("envcrypt-tpm-{:016x}", rand::random::<u64>()));
+        let mut builder = fs::DirBuilder::new();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            builder.mode(0o700);
+        }
+        builder.create(&path)
+            .map_err(|e| format!("Error creating {}: {}", path.display(), e))?;
+        Ok(Self(TempPath::new(path)))
+    }
+
+    fn file(&self, name: &str) -> String {
+        self.0.path().join(name).to_string_lossy().into_owned()
+    }
+}
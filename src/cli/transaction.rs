@@ -0,0 +1,254 @@
+//! Crash-safe rewrites of several files at once, for batch commands like `migrate`.
+//!
+//! A [`Transaction`] writes the new contents of every file to `<file>.tmp` first, and only
+//! then renames them over the originals. A journal (`.envcrypt-journal`) in the batch
+//! directory lists the files; once every new file is written, a commit line is appended.
+//! If the batch is interrupted, the next batch in the directory finds the journal and
+//! either finishes the renames (the journal was committed) or deletes the temporary files
+//! (it was not), so the files end up either all rewritten or all untouched.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::audit::note_written;
+use super::lock::{lock_file, FileLock, LockWait};
+use super::output::{info, OutputConfig};
+
+/// Journal of the batch running in a directory.
+pub(crate) const JOURNAL_FILE: &str = ".envcrypt-journal";
+
+/// Last line of a journal whose new files were all written.
+const COMMIT_LINE: &str = "commit";
+
+/// A batch of file rewrites that are applied together by [`Transaction::commit()`].
+///
+/// Dropping a transaction that was not committed deletes the files staged so far and the
+/// journal, leaving the original files as they were.
+pub(crate) struct Transaction {
+    journal_path: PathBuf,
+    journal: File,
+    staged: Vec<PathBuf>,
+    committing: bool,
+    _locks: Vec<FileLock>,
+}
+
+impl Transaction {
+    /// Starts a transaction journaled in `dir`, after finishing or undoing a batch that was
+    /// interrupted there (see [`recover()`]).
+    pub(crate) fn begin(dir: &Path, wait: LockWait, output_config: &OutputConfig) -> Result<Self, String> {
+        let journal_path = dir.join(JOURNAL_FILE);
+        let journal_lock = lock_file(&journal_path, wait, output_config)?;
+        recover_locked(&journal_path, output_config)?;
+        let journal = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&journal_path)
+            .map_err(|e| format!("Error creating {}: {}", journal_path.display(), e))?;
+        Ok(Self { journal_path, journal, staged: Vec::new(), committing: false, _locks: vec![journal_lock] })
+    }
+
+    /// Locks `path` against other envcrypt processes until the transaction ends. Call it
+    /// before reading a file that will be staged.
+    pub(crate) fn lock(&mut self, path: &Path, wait: LockWait, output_config: &OutputConfig) -> Result<(), String> {
+        self._locks.push(lock_file(path, wait, output_config)?);
+        Ok(())
+    }
+
+    /// Writes `contents` to `<path>.tmp`, to replace `path` on commit.
+    pub(crate) fn stage(&mut self, path: &Path, contents: &[u8]) -> Result<(), String> {
+        let absolute = fs::canonicalize(path)
+            .map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+
+        // Journal the file first, so an interruption while it is written still removes it
+        self.write_journal(&absolute.to_string_lossy())?;
+        let temp_path = temp_path(&absolute);
+        self.staged.push(absolute);
+        let mut temp = File::create(&temp_path)
+            .map_err(|e| format!("Error writing {}: {}", temp_path.display(), e))?;
+        temp.write_all(contents)
+            .and_then(|()| temp.sync_all())
+            .map_err(|e| format!("Error writing {}: {}", temp_path.display(), e))
+    }
+
+    /// Replaces every staged file with its new contents.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error string if the journal cannot be committed, in which case no file
+    /// was changed, or if a file cannot be replaced, in which case the next batch in the
+    /// directory replaces the rest.
+    pub(crate) fn commit(mut self) -> Result<(), String> {
+        self.write_journal(COMMIT_LINE)?;
+        self.committing = true;
+        for path in &self.staged {
+            fs::rename(temp_path(path), path).map_err(|e| {
+                format!(
+                    "Error replacing {}: {}. Run the command again to finish replacing the files listed in {}",
+                    path.display(), e, self.journal_path.display(),
+                )
+            })?;
+            note_written(&path.to_string_lossy());
+        }
+        fs::remove_file(&self.journal_path)
+            .map_err(|e| format!("Error removing {}: {}", self.journal_path.display(), e))
+    }
+
+    fn write_journal(&mut self, line: &str) -> Result<(), String> {
+        writeln!(self.journal, "{}", line)
+            .and_then(|()| self.journal.sync_data())
+            .map_err(|e| format!("Error writing {}: {}", self.journal_path.display(), e))
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        if self.committing {
+            return;
+        }
+        for path in &self.staged {
+            let _ = fs::remove_file(temp_path(path));
+        }
+        let _ = fs::remove_file(&self.journal_path);
+    }
+}
+
+/// Finishes or undoes a batch that was interrupted in `dir`: if its journal was
+/// committed, the remaining new files replace the originals; otherwise the new files are
+/// deleted.
+///
+/// # Returns
+///
+/// Returns the number of files that were replaced or deleted, or `None` if no batch was
+/// interrupted in `dir`.
+///
+/// # Errors
+///
+/// Returns an error string if the journal cannot be read or a file cannot be replaced or
+/// deleted.
+pub(crate) fn recover(dir: &Path, wait: LockWait, output_config: &OutputConfig) -> Result<Option<usize>, String> {
+    let journal_path = dir.join(JOURNAL_FILE);
+    if !journal_path.exists() {
+        return Ok(None);
+    }
+    let _lock = lock_file(&journal_path, wait, output_config)?;
+    recover_locked(&journal_path, output_config)
+}
+
+fn recover_locked(journal_path: &Path, output_config: &OutputConfig) -> Result<Option<usize>, String> {
+    let journal = match fs::read_to_string(journal_path) {
+        Ok(journal) => journal,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Error reading {}: {}", journal_path.display(), e)),
+    };
+    let mut lines: Vec<&str> = journal.lines().collect();
+    let committed = lines.last() == Some(&COMMIT_LINE);
+    if committed {
+        lines.pop();
+    }
+
+    let mut count = 0;
+    for path in lines.iter().map(PathBuf::from) {
+        let temp_path = temp_path(&path);
+        if !temp_path.exists() {
+            continue;
+        }
+        if committed {
+            fs::rename(&temp_path, &path)
+                .map_err(|e| format!("Error replacing {}: {}", path.display(), e))?;
+        } else {
+            fs::remove_file(&temp_path)
+                .map_err(|e| format!("Error removing {}: {}", temp_path.display(), e))?;
+        }
+        count += 1;
+    }
+    fs::remove_file(journal_path)
+        .map_err(|e| format!("Error removing {}: {}", journal_path.display(), e))?;
+
+    let noun = if count == 1 { "file" } else { "files" };
+    if committed {
+        info(output_config, &format!("Finished an interrupted batch: replaced {} {} listed in {}", count, noun, journal_path.display()));
+    } else {
+        info(output_config, &format!("Undid an interrupted batch: removed {} new {} listed in {}", count, noun, journal_path.display()));
+    }
+    Ok(Some(count))
+}
+
+/// The file a transaction writes the new contents of `path` to.
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".tmp");
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quiet() -> OutputConfig {
+        OutputConfig::new(true, false, 0)
+    }
+
+    #[test]
+    fn test_commit_replaces_all_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let (first, second) = (dir.path().join("a.encrypted"), dir.path().join("b.encrypted"));
+        fs::write(&first, "old a").unwrap();
+        fs::write(&second, "old b").unwrap();
+
+        let mut transaction = Transaction::begin(dir.path(), LockWait::Forever, &quiet()).unwrap();
+        transaction.stage(&first, b"new a").unwrap();
+        transaction.stage(&second, b"new b").unwrap();
+        assert_eq!(fs::read_to_string(&first).unwrap(), "old a");
+        transaction.commit().unwrap();
+
+        assert_eq!(fs::read_to_string(&first).unwrap(), "new a");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "new b");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_dropped_transaction_changes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.encrypted");
+        fs::write(&path, "old").unwrap();
+
+        let mut transaction = Transaction::begin(dir.path(), LockWait::Forever, &quiet()).unwrap();
+        transaction.stage(&path, b"new").unwrap();
+        drop(transaction);
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_recover_interrupted_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        let (first, second) = (dir.path().join("a.encrypted"), dir.path().join("b.encrypted"));
+        fs::write(&first, "old a").unwrap();
+        fs::write(&second, "old b").unwrap();
+        let journal = |lines: &[&Path], committed: bool| {
+            let mut text: String = lines.iter().map(|path| format!("{}\n", path.display())).collect();
+            if committed {
+                text.push_str("commit\n");
+            }
+            fs::write(dir.path().join(JOURNAL_FILE), text).unwrap();
+        };
+
+        // Interrupted before the commit: the new files are removed
+        fs::write(temp_path(&first), "new a").unwrap();
+        journal(&[&first, &second], false);
+        assert_eq!(recover(dir.path(), LockWait::Forever, &quiet()).unwrap(), Some(1));
+        assert_eq!(fs::read_to_string(&first).unwrap(), "old a");
+        assert!(!temp_path(&first).exists());
+
+        // Interrupted while replacing: the rest are replaced
+        fs::write(&first, "new a").unwrap();
+        fs::write(temp_path(&second), "new b").unwrap();
+        journal(&[&first, &second], true);
+        assert_eq!(recover(dir.path(), LockWait::Forever, &quiet()).unwrap(), Some(1));
+        assert_eq!(fs::read_to_string(&second).unwrap(), "new b");
+        assert!(!dir.path().join(JOURNAL_FILE).exists());
+        assert_eq!(recover(dir.path(), LockWait::Forever, &quiet()).unwrap(), None);
+    }
+}
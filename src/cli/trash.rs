@@ -0,0 +1,183 @@
+//! The trash directory `--prune=backup` moves plaintext originals to instead of deleting
+//! them, and its cleanup (`envcrypt prune --gc`).
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use clap::ValueEnum;
+
+use super::attributes::{set_modified, write_with_mode};
+use super::inspect::format_timestamp;
+
+/// Default trash directory, relative to the current directory.
+pub const TRASH_DIR: &str = ".envcrypt-trash";
+
+/// Days pruned originals are kept in the trash by default.
+pub const DEFAULT_TRASH_DAYS: u32 = 7;
+
+/// What `--prune` does with the original file after encrypting it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PruneMode {
+    /// Delete it permanently
+    Delete,
+    /// Move it to the trash directory, where `envcrypt prune --gc` deletes it once the grace period is over
+    Backup,
+}
+
+/// Where pruned originals go and how long they stay there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrashConfig {
+    /// Directory the originals are moved to (default `.envcrypt-trash`).
+    pub dir: PathBuf,
+    /// Days an original is kept before `envcrypt prune --gc` deletes it (default 7).
+    pub keep_days: u32,
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from(TRASH_DIR),
+            keep_days: DEFAULT_TRASH_DAYS,
+        }
+    }
+}
+
+impl TrashConfig {
+    /// Returns the files in the trash with the time they were moved there, oldest first.
+    /// A missing trash directory is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error string if the trash directory cannot be read.
+    pub fn entries(&self) -> Result<Vec<(PathBuf, SystemTime)>, String> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let read_error = |e: std::io::Error| format!("Error reading {}: {}", self.dir.display(), e);
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.dir).map_err(read_error)? {
+            let entry = entry.map_err(read_error)?;
+            let metadata = entry.metadata().map_err(read_error)?;
+            if metadata.is_file() && entry.file_name() != ".gitignore" {
+                entries.push((entry.path(), metadata.modified().unwrap_or(UNIX_EPOCH)));
+            }
+        }
+        entries.sort_by_key(|(path, trashed)| (*trashed, path.clone()));
+        Ok(entries)
+    }
+
+    /// Returns when a file moved to the trash at `trashed` is deleted by `prune --gc`.
+    pub fn expiry(&self, trashed: SystemTime) -> SystemTime {
+        trashed + Duration::from_secs(u64::from(self.keep_days) * 86_400)
+    }
+}
+
+/// Moves `path` into the trash as `<file name>.<UTC timestamp>`, creating the directory
+/// with a `.gitignore` that keeps its contents out of Git.
+///
+/// The modification time of the moved file is set to now, which starts its grace period.
+///
+/// # Returns
+///
+/// Returns the path of the file in the trash.
+///
+/// # Errors
+///
+/// Returns an error string if the trash directory cannot be created or the file cannot
+/// be moved.
+pub fn trash_file(path: &Path, config: &TrashConfig) -> Result<PathBuf, String> {
+    create_trash_dir(&config.dir)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+    let target = trash_path(path, &config.dir, now);
+
+    // A rename fails across file systems; copy and delete then
+    if fs::rename(path, &target).is_err() {
+        fs::copy(path, &target)
+            .and_then(|_| fs::remove_file(path))
+            .map_err(|e| format!("Error moving {} to {}: {}", path.display(), target.display(), e))?;
+    }
+    set_modified(&target, now)
+        .map_err(|e| format!("Error setting the modification time of {}: {}", target.display(), e))?;
+    Ok(target)
+}
+
+/// Deletes the files in the trash whose grace period is over, or all of them with `all`.
+/// With `dry_run` nothing is deleted.
+///
+/// # Returns
+///
+/// Returns the files that were deleted (or would be).
+///
+/// # Errors
+///
+/// Returns an error string if the trash directory cannot be read or a file cannot be
+/// deleted.
+pub fn gc_trash(config: &TrashConfig, all: bool, dry_run: bool) -> Result<Vec<PathBuf>, String> {
+    let now = SystemTime::now();
+    let mut deleted = Vec::new();
+    for (path, trashed) in config.entries()? {
+        if !all && config.expiry(trashed) > now {
+            continue;
+        }
+        if !dry_run {
+            fs::remove_file(&path)
+                .map_err(|e| format!("Error deleting {}: {}", path.display(), e))?;
+        }
+        deleted.push(path);
+    }
+    Ok(deleted)
+}
+
+/// Creates the trash directory, readable only by the user on Unix since it holds
+/// plaintext, with a `.gitignore` ignoring everything in it.
+fn create_trash_dir(dir: &Path) -> Result<(), String> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(dir)
+        .map_err(|e| format!("Error creating the trash directory {}: {}", dir.display(), e))?;
+
+    let gitignore = dir.join(".gitignore");
+    if !gitignore.exists() {
+        write_with_mode(&gitignore, b"# Pruned env files moved here by `envcrypt encrypt --prune=backup`\n*\n", None)
+            .map_err(|e| format!("Error writing {}: {}", gitignore.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Returns a path in `dir` for `path` trashed at `secs` seconds since the Unix epoch, such
+/// as `.env.20231114T221320Z`, numbered if that name is taken.
+fn trash_path(path: &Path, dir: &Path, secs: u64) -> PathBuf {
+    let stamp: String = format_timestamp(secs).chars().filter(char::is_ascii_digit).collect();
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}T{}Z", &stamp[..8], &stamp[8..]));
+
+    let mut target = dir.join(&name);
+    let mut n = 1;
+    while target.exists() {
+        let mut numbered = name.clone();
+        numbered.push(format!(".{}", n));
+        target = dir.join(numbered);
+        n += 1;
+    }
+    target
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trash_path_is_timestamped_and_unique() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = trash_path(Path::new("config/.env.local"), dir.path(), 1_700_000_000);
+        assert_eq!(first, dir.path().join(".env.local.20231114T221320Z"));
+        fs::write(&first, "").unwrap();
+        assert_eq!(trash_path(Path::new(".env.local"), dir.path(), 1_700_000_000), dir.path().join(".env.local.20231114T221320Z.1"));
+    }
+}
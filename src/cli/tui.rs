@@ -0,0 +1,866 @@
+//! Interactive terminal UI (`envcrypt tui`), built with the `tui` feature.
+//!
+//! Lists the env files in a directory with their encryption status, and encrypts,
+//! decrypts, views and edits them without any flags to remember. Values are masked
+//! unless revealed one at a time.
+//!
+//! The state lives in [`App`], which handles key presses and does the file work through
+//! the same functions as the commands; [`run_tui()`] only draws it and feeds it terminal
+//! events, so the behavior can be tested without a terminal.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Cell, Clear, Paragraph, Row, Table, TableState, Wrap};
+use ratatui::Frame;
+use zeroize::Zeroizing;
+
+use crate::armor::{decode_file, looks_encrypted, FileFormat};
+use crate::format::{self, Header, Kdf};
+use crate::cli::cipher::DEFAULT_CIPHER;
+use crate::cli::attributes::FileAttributes;
+use crate::cli::decrypt::{decrypt_env, decrypt_file, DecryptOptions};
+use crate::cli::dotenv::{dotenv_lines, parse_dotenv};
+use crate::cli::encoding::{check_utf8, UTF8_BOM};
+use crate::cli::encrypt::{check_kdf_iterations, write_encrypted, EncryptOptions};
+use crate::cli::export::{format_variables, ExportFormat};
+use crate::cli::key_handling::{decode_key, generate_base64_key, get_key, KeyFormat, KeyRequest};
+use crate::cli::newline::Newline;
+use crate::cli::output::OutputConfig;
+use crate::cli::paths::{derive_output_path, ENCRYPTED_SUFFIX};
+use crate::cli::walk::{collect_files, WalkOptions};
+
+/// Directories the file list never descends into, besides [`DEFAULT_EXCLUDES`](crate::cli::DEFAULT_EXCLUDES).
+const TUI_EXCLUDES: &[&str] = &[".*/", "target/"];
+
+/// Shown instead of values that are not revealed; always the same length so it does not
+/// give the length of the value away.
+const MASK: &str = "••••••••";
+
+/// Options controlling [`run_tui()`].
+#[derive(Clone)]
+pub struct TuiOptions {
+    /// Cipher to encrypt plaintext files with.
+    pub cipher: String,
+    /// Which env files are listed: whether subdirectories are searched, and the exclude
+    /// and include patterns. Hidden directories and `target` are always skipped.
+    pub walk: WalkOptions,
+    /// How files are encrypted and written. `key_provider` is used instead of asking for
+    /// keys, `force` accepts weak keys, and `backup` also applies to decrypted files.
+    pub encrypt: EncryptOptions,
+}
+
+impl Default for TuiOptions {
+    fn default() -> Self {
+        Self {
+            cipher: DEFAULT_CIPHER.to_string(),
+            walk: WalkOptions::default(),
+            encrypt: EncryptOptions::default(),
+        }
+    }
+}
+
+/// Runs the interactive UI on the env files in `dir` until the user quits.
+///
+/// `key_arg` is used for every file if given (decoded according to
+/// `options.encrypt.key_format`); otherwise the key comes from
+/// `options.encrypt.key_provider` or is asked for once per file.
+///
+/// # Errors
+///
+/// Returns an error string if stdin or stdout is not a terminal, the terminal cannot be
+/// set up, or `dir` cannot be read. Errors of the individual actions are shown in the UI.
+///
+/// # Example
+///
+/// ```no_run
+/// use envcrypt::cli::{run_tui, TuiOptions};
+///
+/// run_tui(".", None, &TuiOptions::default())?;
+/// # Ok::<(), String>(())
+/// ```
+pub fn run_tui(dir: &str, key_arg: Option<&str>, options: &TuiOptions) -> Result<(), String> {
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        return Err("envcrypt tui needs a terminal - use the encrypt and decrypt commands in scripts".to_string());
+    }
+    if options.encrypt.key_provider.as_ref().is_some_and(|provider| provider.name() == "stdin") {
+        return Err("envcrypt tui cannot read the key from stdin - use --key or another key provider".to_string());
+    }
+    check_kdf_iterations(&options.encrypt)?;
+
+    let mut app = App::new(Path::new(dir), key_arg, options)?;
+    let mut terminal = ratatui::try_init().map_err(|e| format!("Cannot set up the terminal: {}", e))?;
+    let result = loop {
+        if let Err(e) = terminal.draw(|frame| app.draw(frame)) {
+            break Err(format!("Cannot draw to the terminal: {}", e));
+        }
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => app.handle_key(key),
+            Ok(_) => {}
+            Err(e) => break Err(format!("Cannot read from the terminal: {}", e)),
+        }
+        if app.quit {
+            break Ok(());
+        }
+    };
+    ratatui::restore();
+    result
+}
+
+/// An env file found in the directory.
+struct EnvFile {
+    path: PathBuf,
+    /// Path relative to the listed directory, for display.
+    name: String,
+    status: FileStatus,
+}
+
+enum FileStatus {
+    Plaintext,
+    Encrypted { cipher: String, version: u8, format: FileFormat },
+    Unreadable(String),
+}
+
+impl EnvFile {
+    fn read(root: &Path, path: PathBuf) -> Self {
+        let name = path.strip_prefix(root).unwrap_or(&path).to_string_lossy().into_owned();
+        let status = match fs::read(&path) {
+            Ok(contents) if looks_encrypted(&contents) => match decode_file(&contents)
+                .and_then(|(data, format)| Ok((format::decode(&data).map_err(|e| e.to_string())?.0, format)))
+            {
+                Ok((header, format)) => FileStatus::Encrypted {
+                    cipher: header.cipher.unwrap_or_else(|| "AES-256-CBC".to_string()),
+                    version: header.version,
+                    format,
+                },
+                Err(e) => FileStatus::Unreadable(e),
+            },
+            Ok(_) => FileStatus::Plaintext,
+            Err(e) => FileStatus::Unreadable(e.to_string()),
+        };
+        Self { path, name, status }
+    }
+}
+
+/// Whether `name` looks like an env file: `.env`, `.env.*`, `*.env` or `*.encrypted`,
+/// but not a backup.
+fn is_env_file_name(name: &str) -> bool {
+    !name.ends_with(".bak") && (name.starts_with(".env") || name.ends_with(".env") || name.ends_with(ENCRYPTED_SUFFIX))
+}
+
+/// Actions on a file that need its key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Encrypt,
+    Decrypt,
+    View,
+}
+
+/// What to do with the answer to a dialog.
+enum Step {
+    /// The key for an action on the selected file.
+    Key(Action),
+    /// Confirmed overwriting the output of an action.
+    Overwrite(Action, Option<Zeroizing<String>>),
+    /// The new value of a variable.
+    Value(String),
+    /// The name of a new variable.
+    NewName,
+    /// Confirmed deleting a variable.
+    Delete(String),
+}
+
+enum Dialog {
+    Input { title: String, value: Zeroizing<String>, masked: bool, step: Step },
+    Confirm { question: String, step: Step },
+    Message { title: String, text: Zeroizing<String> },
+}
+
+/// The variables of a file being viewed.
+struct VariableView {
+    file: usize,
+    /// Key and header of an encrypted file, to encrypt it again after an edit.
+    encryption: Option<(Zeroizing<String>, Header)>,
+    text: Zeroizing<String>,
+    variables: Vec<(String, Zeroizing<String>)>,
+    selected: usize,
+    revealed: bool,
+}
+
+enum Screen {
+    Files,
+    Variables(Box<VariableView>),
+}
+
+/// State of the UI.
+struct App<'a> {
+    root: PathBuf,
+    files: Vec<EnvFile>,
+    selected: usize,
+    screen: Screen,
+    dialog: Option<Dialog>,
+    /// Last result, and whether it is an error.
+    status: Option<(String, bool)>,
+    /// Key given with `--key`, used for every file.
+    key: Option<Zeroizing<String>>,
+    /// Keys that worked for a file, so it is only asked for once.
+    file_keys: HashMap<PathBuf, Zeroizing<String>>,
+    options: &'a TuiOptions,
+    /// Messages of the commands would write over the UI.
+    output_config: OutputConfig,
+    quit: bool,
+}
+
+impl<'a> App<'a> {
+    fn new(root: &Path, key_arg: Option<&str>, options: &'a TuiOptions) -> Result<Self, String> {
+        let key = key_arg.map(|key| decode_key(key, options.encrypt.key_format)).transpose()?;
+        let mut app = Self {
+            root: root.to_path_buf(),
+            files: Vec::new(),
+            selected: 0,
+            screen: Screen::Files,
+            dialog: None,
+            status: None,
+            key,
+            file_keys: HashMap::new(),
+            options,
+            output_config: OutputConfig::new(true, false, 0),
+            quit: false,
+        };
+        app.refresh()?;
+        Ok(app)
+    }
+
+    /// Lists the env files again, keeping the selection on the same file if it still exists.
+    fn refresh(&mut self) -> Result<(), String> {
+        let selected = self.files.get(self.selected).map(|file| file.path.clone());
+        let walk = WalkOptions {
+            exclude: TUI_EXCLUDES.iter().map(|pattern| pattern.to_string()).chain(self.options.walk.exclude.iter().cloned()).collect(),
+            ..self.options.walk.clone()
+        };
+        let paths = collect_files(&self.root, &walk, is_env_file_name)?;
+        self.files = paths.into_iter().map(|path| EnvFile::read(&self.root, path)).collect();
+        self.selected = selected
+            .and_then(|selected| self.files.iter().position(|file| file.path == selected))
+            .unwrap_or(0)
+            .min(self.files.len().saturating_sub(1));
+        Ok(())
+    }
+
+    fn report(&mut self, result: Result<String, String>) {
+        self.status = Some(match result {
+            Ok(message) => (message, false),
+            Err(e) => (e, true),
+        });
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return;
+        }
+        if let Some(dialog) = self.dialog.take() {
+            self.handle_dialog_key(dialog, key);
+            return;
+        }
+        match &mut self.screen {
+            Screen::Files => self.handle_files_key(key),
+            Screen::Variables(_) => self.handle_variables_key(key),
+        }
+    }
+
+    fn handle_files_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = (self.selected + 1).min(self.files.len().saturating_sub(1));
+            }
+            KeyCode::Char('r') => {
+                let result = self.refresh().map(|()| "Reloaded the file list".to_string());
+                self.report(result);
+            }
+            KeyCode::Char('e') => self.start(Action::Encrypt),
+            KeyCode::Char('d') => self.start(Action::Decrypt),
+            KeyCode::Char('v') | KeyCode::Enter => self.start(Action::View),
+            _ => {}
+        }
+    }
+
+    fn handle_variables_key(&mut self, key: KeyEvent) {
+        let Screen::Variables(view) = &mut self.screen else { return };
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.screen = Screen::Files,
+            KeyCode::Up | KeyCode::Char('k') => {
+                view.selected = view.selected.saturating_sub(1);
+                view.revealed = false;
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                view.selected = (view.selected + 1).min(view.variables.len().saturating_sub(1));
+                view.revealed = false;
+            }
+            KeyCode::Char(' ') => view.revealed = !view.revealed,
+            KeyCode::Char('e') | KeyCode::Enter => {
+                if let Some((name, value)) = view.variables.get(view.selected) {
+                    // Only show the current value if it is revealed already
+                    let value = if view.revealed { value.clone() } else { Zeroizing::new(String::new()) };
+                    self.dialog = Some(Dialog::Input {
+                        title: format!("New value for {}", name),
+                        value,
+                        masked: !view.revealed,
+                        step: Step::Value(name.clone()),
+                    });
+                }
+            }
+            KeyCode::Char('a') => {
+                self.dialog = Some(Dialog::Input {
+                    title: "Name of the new variable".to_string(),
+                    value: Zeroizing::new(String::new()),
+                    masked: false,
+                    step: Step::NewName,
+                });
+            }
+            KeyCode::Char('x') => {
+                if let Some((name, _)) = view.variables.get(view.selected) {
+                    self.dialog = Some(Dialog::Confirm {
+                        question: format!("Delete {}?", name),
+                        step: Step::Delete(name.clone()),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_dialog_key(&mut self, dialog: Dialog, key: KeyEvent) {
+        match dialog {
+            Dialog::Input { title, mut value, masked, step } => match key.code {
+                KeyCode::Esc => {}
+                KeyCode::Enter => self.answer(step, Some(value)),
+                KeyCode::Backspace => {
+                    value.pop();
+                    self.dialog = Some(Dialog::Input { title, value, masked, step });
+                }
+                KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.dialog = Some(Dialog::Input { title, value: Zeroizing::new(String::new()), masked, step });
+                }
+                KeyCode::Char(c) => {
+                    value.push(c);
+                    self.dialog = Some(Dialog::Input { title, value, masked, step });
+                }
+                _ => self.dialog = Some(Dialog::Input { title, value, masked, step }),
+            },
+            Dialog::Confirm { question, step } => match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => self.answer(step, None),
+                KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {}
+                _ => self.dialog = Some(Dialog::Confirm { question, step }),
+            },
+            Dialog::Message { .. } => {}
+        }
+    }
+
+    /// Continues after a dialog was answered: `input` is the text entered, `None` for a
+    /// confirmation.
+    fn answer(&mut self, step: Step, input: Option<Zeroizing<String>>) {
+        let input = input.unwrap_or_default();
+        match step {
+            Step::Key(action) => {
+                if input.is_empty() && action != Action::Encrypt {
+                    self.report(Err("A key is required".to_string()));
+                    return;
+                }
+                // An empty key when encrypting generates one
+                let key = if input.is_empty() {
+                    None
+                } else {
+                    match decode_key(&input, self.options.encrypt.key_format) {
+                        Ok(key) => Some(key),
+                        Err(e) => return self.report(Err(e)),
+                    }
+                };
+                self.perform(action, key, false);
+            }
+            Step::Overwrite(action, key) => self.perform(action, key, true),
+            Step::Value(name) => self.save_variable(&name, Some(&input)),
+            Step::NewName => {
+                let name = input.trim().to_string();
+                if !crate::cli::dotenv::is_valid_key(&name) {
+                    self.report(Err(format!("{} is not a valid variable name", name)));
+                    return;
+                }
+                self.dialog = Some(Dialog::Input {
+                    title: format!("Value for {}", name),
+                    value: Zeroizing::new(String::new()),
+                    masked: true,
+                    step: Step::Value(name),
+                });
+            }
+            Step::Delete(name) => self.save_variable(&name, None),
+        }
+    }
+
+    /// Starts `action` on the selected file, asking for the key if it is not known.
+    fn start(&mut self, action: Action) {
+        let Some(file) = self.files.get(self.selected) else { return };
+        let needs_key = match (action, &file.status) {
+            (_, FileStatus::Unreadable(e)) => return self.report(Err(format!("Cannot read {}: {}", file.name, e))),
+            (Action::Encrypt, FileStatus::Encrypted { .. }) => {
+                return self.report(Err(format!("{} is already encrypted", file.name)));
+            }
+            (Action::Decrypt, FileStatus::Plaintext) => {
+                return self.report(Err(format!("{} is not encrypted", file.name)));
+            }
+            (Action::View, FileStatus::Plaintext) => false,
+            _ => true,
+        };
+        if !needs_key {
+            return self.perform(action, None, false);
+        }
+
+        let output = derive_output_path(&file.path.to_string_lossy(), true);
+        let known = self.key.clone()
+            .or_else(|| self.file_keys.get(&file.path).cloned())
+            .or_else(|| self.file_keys.get(Path::new(&output)).cloned());
+        if let Some(key) = known {
+            return self.perform(action, Some(key), false);
+        }
+        if let Some(provider) = &self.options.encrypt.key_provider {
+            if provider.name() != "prompt" {
+                let request = KeyRequest { is_encrypt: action == Action::Encrypt, no_interaction: true, key_format: self.options.encrypt.key_format, challenge: None };
+                return match get_key(None, Some(provider.as_ref()), &request) {
+                    Ok(key) => self.perform(action, Some(key.value), false),
+                    Err(e) => self.report(Err(e)),
+                };
+            }
+        }
+
+        let title = match action {
+            Action::Encrypt => format!("Key to encrypt {} with (leave empty to generate one)", file.name),
+            Action::Decrypt | Action::View => format!("Key for {}", file.name),
+        };
+        self.dialog = Some(Dialog::Input { title, value: Zeroizing::new(String::new()), masked: true, step: Step::Key(action) });
+    }
+
+    /// Runs `action` on the selected file with `key` (`None` to generate one when
+    /// encrypting), asking first if it would overwrite a file and `overwrite` is not set.
+    fn perform(&mut self, action: Action, key: Option<Zeroizing<String>>, overwrite: bool) {
+        let Some(file) = self.files.get(self.selected) else { return };
+        let path = file.path.clone();
+        let input = path.to_string_lossy().into_owned();
+        let output = match action {
+            Action::Encrypt => derive_output_path(&input, true),
+            Action::Decrypt => derive_output_path(&input, false),
+            Action::View => String::new(),
+        };
+        if action != Action::View && !overwrite && Path::new(&output).exists() {
+            let name = Path::new(&output).strip_prefix(&self.root).unwrap_or(Path::new(&output)).display().to_string();
+            self.dialog = Some(Dialog::Confirm {
+                question: format!("Overwrite {}?", name),
+                step: Step::Overwrite(action, key),
+            });
+            return;
+        }
+
+        let result = match action {
+            Action::Encrypt => self.encrypt(&input, &output, key),
+            Action::Decrypt => self.decrypt(&input, &output, key.unwrap_or_default()),
+            Action::View => self.view(key),
+        };
+        let result = result.and_then(|message| {
+            self.refresh()?;
+            Ok(message)
+        });
+        self.report(result);
+    }
+
+    fn encrypt(&mut self, input: &str, output: &str, key: Option<Zeroizing<String>>) -> Result<String, String> {
+        let plaintext = Zeroizing::new(fs::read(input).map_err(|e| format!("Error reading {}: {}", input, e))?);
+        check_utf8(&plaintext).map_err(|e| format!("{} is {}", input, e))?;
+        let generated = key.is_none();
+        let key = key.unwrap_or_else(generate_base64_key);
+        let options = EncryptOptions {
+            no_interaction: true,
+            key_format: KeyFormat::Utf8,
+            raw_key: self.options.encrypt.raw_key || generated,
+            ..self.options.encrypt.clone()
+        };
+        write_encrypted(&self.options.cipher, Some(&key), &plaintext, &FileAttributes::read(Path::new(input)), output, &self.output_config, &options)?;
+
+        let message = format!("Encrypted {} to {}", self.display_name(input), self.display_name(output));
+        if generated {
+            self.dialog = Some(Dialog::Message {
+                title: "Store this key in a safe place".to_string(),
+                text: Zeroizing::new(format!("base64:{}\n\nYou need it to decrypt {}. It will not be shown again.", key.as_str(), self.display_name(output))),
+            });
+        }
+        self.file_keys.insert(PathBuf::from(output), key);
+        Ok(message)
+    }
+
+    fn decrypt(&mut self, input: &str, output: &str, key: Zeroizing<String>) -> Result<String, String> {
+        let options = DecryptOptions {
+            force: true,
+            no_interaction: true,
+            key_format: KeyFormat::Utf8,
+            backup: self.options.encrypt.backup.clone(),
+            mlock: self.options.encrypt.mlock,
+            aad: self.options.encrypt.aad.clone(),
+            ..DecryptOptions::default()
+        };
+        decrypt_env(None, Some(&key), input, output, &self.output_config, &options)?;
+        self.file_keys.insert(PathBuf::from(input), key);
+        Ok(format!("Decrypted {} to {}", self.display_name(input), self.display_name(output)))
+    }
+
+    fn view(&mut self, key: Option<Zeroizing<String>>) -> Result<String, String> {
+        let file = &self.files[self.selected];
+        let input = file.path.to_string_lossy().into_owned();
+        let (encryption, text) = match key {
+            Some(key) => {
+                let options = DecryptOptions {
+                    no_interaction: true,
+                    key_format: KeyFormat::Utf8,
+                    mlock: self.options.encrypt.mlock,
+                    aad: self.options.encrypt.aad.clone(),
+                    ..DecryptOptions::default()
+                };
+                let (header, text) = decrypt_file(Some(&key), &input, &self.output_config, &options)?;
+                self.file_keys.insert(file.path.clone(), key.clone());
+                (Some((key, header)), text)
+            }
+            None => {
+                let text = fs::read_to_string(&file.path).map_err(|e| format!("Error reading {}: {}", file.name, e))?;
+                (None, Zeroizing::new(text))
+            }
+        };
+        let variables = parse_dotenv(&text).map_err(|e| format!("{} is not a valid dotenv file: {}", file.name, e))?;
+        let message = format!("{}: {} variables", file.name, variables.len());
+        self.screen = Screen::Variables(Box::new(VariableView { file: self.selected, encryption, text, variables, selected: 0, revealed: false }));
+        Ok(message)
+    }
+
+    /// Sets variable `name` to `value` (deletes it if `None`) in the viewed file and
+    /// writes the file, encrypting it again with the same key and settings.
+    fn save_variable(&mut self, name: &str, value: Option<&str>) {
+        let Screen::Variables(view) = &mut self.screen else { return };
+        let file = &self.files[view.file];
+        let text = set_assignment(&view.text, name, value);
+
+        let result = match (&view.encryption, &file.status) {
+            (Some((key, header)), FileStatus::Encrypted { format, .. }) => {
+                let options = EncryptOptions {
+                    no_interaction: true,
+                    key_format: KeyFormat::Utf8,
+                    raw_key: header.kdf == Kdf::HkdfSha256,
+                    kdf_iterations: match header.kdf {
+                        Kdf::Pbkdf2Sha256 { iterations } => iterations,
+                        Kdf::HkdfSha256 => self.options.encrypt.kdf_iterations,
+                    },
+                    format: *format,
+                    comment: header.metadata.comment.clone(),
+                    ..self.options.encrypt.clone()
+                };
+                let cipher = header.cipher.clone().unwrap_or_else(|| "AES-256-CBC".to_string());
+                let attributes = FileAttributes { modified: None, ..FileAttributes::from_metadata(&header.metadata) };
+                write_encrypted(&cipher, Some(key), text.as_bytes(), &attributes, &file.path.to_string_lossy(), &self.output_config, &options)
+                    .map(|_| ())
+            }
+            _ => fs::write(&file.path, text.as_bytes()).map_err(|e| format!("Error writing {}: {}", file.name, e)),
+        };
+        let result = result.and_then(|()| {
+            let variables = parse_dotenv(&text).map_err(|e| format!("{} is not a valid dotenv file: {}", file.name, e))?;
+            let verb = if value.is_some() { "Saved" } else { "Deleted" };
+            let message = format!("{} {} in {}", verb, name, file.name);
+            view.selected = variables.iter().position(|(existing, _)| existing == name)
+                .unwrap_or(view.selected)
+                .min(variables.len().saturating_sub(1));
+            view.revealed = false;
+            view.variables = variables;
+            view.text = text;
+            Ok(message)
+        });
+        self.report(result);
+    }
+
+    fn display_name(&self, path: &str) -> String {
+        Path::new(path).strip_prefix(&self.root).unwrap_or(Path::new(path)).display().to_string()
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [main, status, help] = Layout::vertical([Constraint::Min(3), Constraint::Length(1), Constraint::Length(1)])
+            .areas(frame.area());
+        let highlight = Style::default().add_modifier(Modifier::REVERSED);
+
+        let help_text = match &self.screen {
+            Screen::Files => {
+                let rows = self.files.iter().map(|file| {
+                    let (status, details) = match &file.status {
+                        FileStatus::Plaintext => {
+                            let encrypted = derive_output_path(&file.path.to_string_lossy(), true);
+                            let details = if Path::new(&encrypted).exists() {
+                                format!("encrypted copy: {}", self.display_name(&encrypted))
+                            } else {
+                                "no encrypted copy".to_string()
+                            };
+                            (Cell::from("plaintext").style(Style::default().fg(Color::Yellow)), details)
+                        }
+                        FileStatus::Encrypted { cipher, version, format } => (
+                            Cell::from("encrypted").style(Style::default().fg(Color::Green)),
+                            format!("{}, format v{}, {}", cipher, version, format),
+                        ),
+                        FileStatus::Unreadable(e) => (Cell::from("unreadable").style(Style::default().fg(Color::Red)), e.clone()),
+                    };
+                    Row::new([Cell::from(file.name.clone()), status, Cell::from(details)])
+                });
+                let table = Table::new(rows, [Constraint::Percentage(40), Constraint::Length(10), Constraint::Fill(1)])
+                    .header(Row::new(["File", "Status", "Details"]).style(Style::default().add_modifier(Modifier::BOLD)))
+                    .block(Block::bordered().title(format!(" envcrypt - {} ", self.root.display())))
+                    .row_highlight_style(highlight);
+                let mut state = TableState::default().with_selected((!self.files.is_empty()).then_some(self.selected));
+                frame.render_stateful_widget(table, main, &mut state);
+                if self.files.is_empty() {
+                    let inner = Block::bordered().inner(main);
+                    frame.render_widget(Paragraph::new("No env files found"), Rect { y: inner.y + 1, ..inner });
+                }
+                "↑/↓ select  enter view  e encrypt  d decrypt  r reload  q quit"
+            }
+            Screen::Variables(view) => {
+                let rows = view.variables.iter().enumerate().map(|(index, (name, value))| {
+                    let shown = if view.revealed && index == view.selected { value.as_str() } else { MASK };
+                    Row::new([Cell::from(name.clone()), Cell::from(shown.to_string())])
+                });
+                let table = Table::new(rows, [Constraint::Percentage(35), Constraint::Fill(1)])
+                    .header(Row::new(["Variable", "Value"]).style(Style::default().add_modifier(Modifier::BOLD)))
+                    .block(Block::bordered().title(format!(" {} ", self.files[view.file].name)))
+                    .row_highlight_style(highlight);
+                let mut state = TableState::default().with_selected((!view.variables.is_empty()).then_some(view.selected));
+                frame.render_stateful_widget(table, main, &mut state);
+                "↑/↓ select  space reveal  e edit  a add  x delete  esc back"
+            }
+        };
+
+        if let Some((message, is_error)) = &self.status {
+            let style = if *is_error { Style::default().fg(Color::Red) } else { Style::default().fg(Color::Green) };
+            frame.render_widget(Paragraph::new(message.as_str()).style(style), status);
+        }
+        frame.render_widget(Paragraph::new(help_text).style(Style::default().add_modifier(Modifier::DIM)), help);
+
+        match &self.dialog {
+            Some(Dialog::Input { title, value, masked, .. }) => {
+                let shown = if *masked { "•".repeat(value.chars().count()) } else { value.to_string() };
+                draw_popup(frame, title, vec![Line::from(format!("{}_", shown)), Line::from(""), Line::from("enter confirm  esc cancel")]);
+            }
+            Some(Dialog::Confirm { question, .. }) => {
+                draw_popup(frame, "Confirm", vec![Line::from(question.as_str()), Line::from(""), Line::from("y yes  n no")]);
+            }
+            Some(Dialog::Message { title, text }) => {
+                let mut lines: Vec<Line> = text.lines().map(Line::from).collect();
+                lines.extend([Line::from(""), Line::from("press any key")]);
+                draw_popup(frame, title, lines);
+            }
+            None => {}
+        }
+    }
+}
+
+/// Draws a bordered box with `lines` over the middle of the screen.
+fn draw_popup(frame: &mut Frame, title: &str, lines: Vec<Line>) {
+    let screen = frame.area();
+    let width = screen.width.saturating_sub(4).min(72);
+    let height = (lines.len() as u16 + 4).min(screen.height);
+    let area = Rect {
+        x: screen.x + (screen.width - width) / 2,
+        y: screen.y + (screen.height - height) / 2,
+        width,
+        height,
+    };
+    frame.render_widget(Clear, area);
+    let block = Block::bordered().title(format!(" {} ", title));
+    frame.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }).block(block), area);
+}
+
+/// Sets the assignment to `name` in dotenv `text` to `value`, or removes it if `value` is
+/// `None`. The last assignment is replaced (earlier ones are removed) so that other lines,
+/// comments and line endings stay as they are; a new variable is appended with the line
+/// ending of the first line.
+fn set_assignment(text: &str, name: &str, value: Option<&str>) -> Zeroizing<String> {
+    let assigns = |line: &str| {
+        let line = line.trim_start();
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        line.split_once('=').is_some_and(|(key, _)| key.trim_end() == name)
+    };
+    let assignment = value.map(|value| {
+        let variables = [(name.to_string(), Zeroizing::new(value.to_string()))];
+        format_variables(&variables, ExportFormat::Dotenv)
+    });
+
+    let lines: Vec<&str> = dotenv_lines(text).into_iter().map(|(_, line)| line).collect();
+    let last = lines.iter().rposition(|line| assigns(line));
+    let mut result = Zeroizing::new(String::with_capacity(text.len()));
+    if text.starts_with(UTF8_BOM) {
+        result.push_str(UTF8_BOM);
+    }
+    for (index, line) in lines.iter().enumerate() {
+        if !assigns(line) {
+            result.push_str(line);
+        } else if let (Some(assignment), true) = (&assignment, Some(index) == last) {
+            let ending = if line.ends_with("\r\n") { "\r\n" } else if line.ends_with('\n') { "\n" } else { "" };
+            result.push_str(assignment.trim_end());
+            result.push_str(ending);
+        }
+    }
+    if let (Some(assignment), None) = (&assignment, last) {
+        let newline = Newline::detect(text.as_bytes()).unwrap_or(Newline::Lf).as_str();
+        if !result.is_empty() && !result.ends_with('\n') {
+            result.push_str(newline);
+        }
+        result.push_str(assignment.trim_end());
+        result.push_str(newline);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    const KEY: &str = "correct horse battery staple tui";
+
+    fn press(app: &mut App, keys: &str) {
+        for c in keys.chars() {
+            let code = match c {
+                '\n' => KeyCode::Enter,
+                '\x1b' => KeyCode::Esc,
+                c => KeyCode::Char(c),
+            };
+            app.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+        }
+    }
+
+    fn screen(app: &App) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(100, 20)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        buffer.content.chunks(buffer.area.width as usize)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_set_assignment() {
+        let text = "# comment\nA=1\r\nexport B=2\nA=3\n";
+        assert_eq!(set_assignment(text, "A", Some("new value")).as_str(), "# comment\nexport B=2\nA=\"new value\"\n");
+        assert_eq!(set_assignment(text, "B", None).as_str(), "# comment\nA=1\r\nA=3\n");
+        assert_eq!(set_assignment("A=1", "C", Some("x")).as_str(), "A=1\nC=x\n");
+        assert_eq!(set_assignment("A=1\r\nB=2", "C", Some("x")).as_str(), "A=1\r\nB=2\r\nC=x\r\n");
+        assert_eq!(set_assignment("\u{feff}A=1\n", "A", Some("2")).as_str(), "\u{feff}A=2\n");
+        // Values spanning several lines are replaced as a whole
+        let text = "KEY=\"-----BEGIN KEY-----\nabc\n-----END KEY-----\"\nB=2\n";
+        assert_eq!(set_assignment(text, "KEY", Some("a\nb")).as_str(), "KEY=\"a\\nb\"\nB=2\n");
+    }
+
+    #[test]
+    fn test_lists_env_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(".env"), "A=1\n").unwrap();
+        fs::write(dir.path().join(".env.bak"), "A=0\n").unwrap();
+        fs::write(dir.path().join("README.md"), "docs\n").unwrap();
+        fs::create_dir(dir.path().join("api")).unwrap();
+        fs::write(dir.path().join("api").join("prod.env"), "B=2\n").unwrap();
+
+        let options = TuiOptions::default();
+        let app = App::new(dir.path(), None, &options).unwrap();
+        let names: Vec<&str> = app.files.iter().map(|file| file.name.as_str()).collect();
+        assert_eq!(names, [".env"]);
+
+        let options = TuiOptions { walk: WalkOptions { recursive: true, ..WalkOptions::default() }, ..TuiOptions::default() };
+        let app = App::new(dir.path(), None, &options).unwrap();
+        assert_eq!(app.files.len(), 2);
+    }
+
+    #[test]
+    fn test_encrypt_view_edit_decrypt() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(".env"), "# settings\nAPI_TOKEN=secret-token\n").unwrap();
+        let options = TuiOptions {
+            encrypt: EncryptOptions { kdf_iterations: crate::key::MIN_PBKDF2_ITERATIONS, ..EncryptOptions::default() },
+            ..TuiOptions::default()
+        };
+        let mut app = App::new(dir.path(), None, &options).unwrap();
+
+        // Encrypt with a typed key
+        press(&mut app, "e");
+        press(&mut app, &format!("{}\n", KEY));
+        assert!(app.status.as_ref().is_some_and(|(_, is_error)| !is_error), "{:?}", app.status);
+        assert!(dir.path().join(".env.encrypted").exists());
+        assert!(screen(&app).contains("encrypted copy: .env.encrypted"));
+
+        // View the encrypted file: the key is remembered and values are masked
+        press(&mut app, "j\n");
+        assert!(matches!(app.screen, Screen::Variables(_)));
+        let shown = screen(&app);
+        assert!(shown.contains("API_TOKEN"));
+        assert!(!shown.contains("secret-token"));
+        press(&mut app, " ");
+        assert!(screen(&app).contains("secret-token"));
+
+        // Edit the value, which encrypts the file again
+        press(&mut app, "e");
+        for _ in 0.."secret-token".len() {
+            app.handle_key(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+        }
+        press(&mut app, "rotated\n\x1b");
+        let contents = fs::read(dir.path().join(".env.encrypted")).unwrap();
+        let plaintext = crate::envelope::decrypt(&contents, KEY, None).unwrap();
+        assert_eq!(plaintext.as_slice(), b"# settings\nAPI_TOKEN=rotated\n");
+
+        // Decrypt over the plaintext after confirming
+        press(&mut app, "d");
+        assert!(matches!(app.dialog, Some(Dialog::Confirm { .. })));
+        press(&mut app, "y");
+        assert_eq!(fs::read_to_string(dir.path().join(".env")).unwrap(), "# settings\nAPI_TOKEN=rotated\n");
+    }
+
+    #[test]
+    fn test_encrypt_generates_key() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(".env"), "A=1\n").unwrap();
+        let options = TuiOptions::default();
+        let mut app = App::new(dir.path(), None, &options).unwrap();
+
+        press(&mut app, "e\n");
+        let Some(Dialog::Message { text, .. }) = &app.dialog else { panic!("no key shown: {:?}", app.status) };
+        let key = text.lines().next().unwrap().trim_start_matches("base64:").to_string();
+        press(&mut app, " ");
+        assert!(app.dialog.is_none());
+
+        let contents = fs::read(dir.path().join(".env.encrypted")).unwrap();
+        assert_eq!(crate::envelope::decrypt(&contents, &key, None).unwrap().as_slice(), b"A=1\n");
+    }
+
+    #[test]
+    fn test_wrong_key_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(".env"), "A=1\n").unwrap();
+        let options = TuiOptions {
+            encrypt: EncryptOptions { kdf_iterations: crate::key::MIN_PBKDF2_ITERATIONS, ..EncryptOptions::default() },
+            ..TuiOptions::default()
+        };
+        let mut app = App::new(dir.path(), Some(KEY), &options).unwrap();
+        press(&mut app, "e");
+
+        let mut app = App::new(dir.path(), None, &options).unwrap();
+        press(&mut app, "j\nwrong key\n");
+        assert!(matches!(app.screen, Screen::Files));
+        assert!(app.status.as_ref().is_some_and(|(message, is_error)| *is_error && message.starts_with("Authentication failed")));
+    }
+}
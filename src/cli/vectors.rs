@@ -0,0 +1,172 @@
+//! Known-answer test vectors for the file format (`envcrypt vectors`).
+//!
+//! The vectors in `test-vectors/vectors.json` are encrypted with fixed keys, salts and
+//! nonces, so the same inputs always give the same files. A test regenerates them and
+//! decrypts the checked-in files, so an accidental change to the format fails it, and
+//! other implementations can check themselves against them.
+
+use base64::Engine;
+
+use crate::armor::{encode_file, FileFormat};
+use crate::cipher::{cipher_layout, get_cipher, get_supported_ciphers};
+use crate::envelope::{derive_file_keys, seal_with_nonce};
+use crate::format::{self, Header, Kdf, LEGACY_VERSION};
+use crate::key::{KeySchedule, DEFAULT_PBKDF2_ITERATIONS, MIN_PBKDF2_ITERATIONS};
+
+/// Key the PBKDF2 vectors are encrypted with.
+pub const VECTOR_KEY: &str = "envcrypt-test-vector-key";
+
+/// Plaintext of every vector: a small env file with a quoted value.
+pub const VECTOR_PLAINTEXT: &str = "APP_KEY=test123\nDB_PASSWORD=\"p@ss word\"\n";
+
+/// Context string of the vectors bound to one.
+pub const VECTOR_CONTEXT: &str = "production";
+
+/// One encrypted file with everything that went into it.
+#[derive(Debug, Clone)]
+pub struct TestVector {
+    /// Short unique description, e.g. `v2-aes-256-gcm-pbkdf2`.
+    pub name: String,
+    /// Format version of the file ([`LEGACY_VERSION`] for files without a header).
+    pub version: u8,
+    /// Canonical cipher name.
+    pub cipher: String,
+    /// Key derivation function and parameters.
+    pub kdf: Kdf,
+    /// How the encryption and MAC keys were separated.
+    pub key_schedule: KeySchedule,
+    /// Key as given to envcrypt: [`VECTOR_KEY`], or a base64 raw key for HKDF.
+    pub key: String,
+    /// Key derivation salt.
+    pub salt: [u8; 16],
+    /// IV or nonce of the cipher.
+    pub nonce: Vec<u8>,
+    /// Context string the file is bound to, if any.
+    pub context: Option<String>,
+    /// Derived encryption key.
+    pub encryption_key: Vec<u8>,
+    /// Derived MAC key (unused by AES-256-GCM and ChaCha20-Poly1305).
+    pub mac_key: Vec<u8>,
+    /// Contents of the encrypted file, armored.
+    pub file: String,
+}
+
+/// Generates the test vectors: each cipher in the current format with PBKDF2, with a raw
+/// key, and with PBKDF2 and the split key schedule, plus a legacy file without a header.
+///
+/// # Errors
+///
+/// Returns an error string if encryption fails, which would be a bug.
+///
+/// # Example
+///
+/// ```
+/// use envcrypt::cli::{generate_test_vectors, VECTOR_PLAINTEXT};
+/// use envcrypt::envelope;
+///
+/// for vector in generate_test_vectors()? {
+///     let plaintext = envelope::decrypt(vector.file.as_bytes(), &vector.key, vector.context.as_deref())
+///         .map_err(|e| e.to_string())?;
+///     assert_eq!(plaintext.as_slice(), VECTOR_PLAINTEXT.as_bytes());
+/// }
+/// # Ok::<(), String>(())
+/// ```
+pub fn generate_test_vectors() -> Result<Vec<TestVector>, String> {
+    let raw_key = base64::engine::general_purpose::STANDARD.encode((0..32).collect::<Vec<u8>>());
+    let pbkdf2 = Kdf::Pbkdf2Sha256 { iterations: MIN_PBKDF2_ITERATIONS };
+
+    let mut vectors = Vec::new();
+    for cipher in get_supported_ciphers() {
+        let slug = cipher.to_lowercase();
+        vectors.push(generate_vector(&format!("v2-{}-pbkdf2", slug), format::FORMAT_VERSION, cipher, pbkdf2, KeySchedule::HkdfExpand, VECTOR_KEY, None)?);
+        vectors.push(generate_vector(&format!("v2-{}-raw-key-context", slug), format::FORMAT_VERSION, cipher, Kdf::HkdfSha256, KeySchedule::HkdfExpand, &raw_key, Some(VECTOR_CONTEXT))?);
+        vectors.push(generate_vector(&format!("v2-{}-pbkdf2-split", slug), format::FORMAT_VERSION, cipher, pbkdf2, KeySchedule::Split, VECTOR_KEY, None)?);
+    }
+    let legacy_kdf = Kdf::Pbkdf2Sha256 { iterations: DEFAULT_PBKDF2_ITERATIONS };
+    vectors.push(generate_vector("legacy-aes-256-cbc", LEGACY_VERSION, "AES-256-CBC", legacy_kdf, KeySchedule::Split, VECTOR_KEY, None)?);
+    Ok(vectors)
+}
+
+fn generate_vector(
+    name: &str,
+    version: u8,
+    cipher_name: &str,
+    kdf: Kdf,
+    key_schedule: KeySchedule,
+    key: &str,
+    context: Option<&str>,
+) -> Result<TestVector, String> {
+    let salt: [u8; 16] = std::array::from_fn(|i| 0x10 + i as u8);
+    let (nonce_len, _) = cipher_layout(cipher_name).ok_or_else(|| format!("Unknown cipher {}", cipher_name))?;
+    let nonce: Vec<u8> = (0..nonce_len).map(|i| 0xa0 + i as u8).collect();
+
+    let mut header = Header::new(cipher_name, kdf, salt);
+    header.version = version;
+    header.key_schedule = key_schedule;
+    let (encryption_key, mac_key) = derive_file_keys(&header, key).map_err(|e| e.to_string())?;
+
+    let plaintext = VECTOR_PLAINTEXT.as_bytes();
+    let data = if version == LEGACY_VERSION {
+        // Files without a header were encrypted without associated data
+        let encrypted = get_cipher(cipher_name)?
+            .encrypt_with_nonce(plaintext, &[], &nonce, &encryption_key, &mac_key)
+            .map_err(|e| e.to_string())?;
+        [salt.as_slice(), &encrypted].concat()
+    } else {
+        seal_with_nonce(plaintext, &mut header, &encryption_key, &mac_key, context, &nonce).map_err(|e| e.to_string())?
+    };
+
+    Ok(TestVector {
+        name: name.to_string(),
+        version,
+        cipher: cipher_name.to_string(),
+        kdf,
+        key_schedule,
+        key: key.to_string(),
+        salt,
+        nonce,
+        context: context.map(str::to_string),
+        encryption_key,
+        mac_key,
+        file: String::from_utf8(encode_file(&data, FileFormat::Armor)).map_err(|e| e.to_string())?,
+    })
+}
+
+/// Renders test vectors as the pretty-printed JSON of `test-vectors/vectors.json`.
+///
+/// Binary values are hex-encoded; `file` holds the armored file contents.
+pub fn test_vectors_json(vectors: &[TestVector]) -> String {
+    let entries: Vec<serde_json::Value> = vectors.iter().map(|vector| {
+        let (kdf, iterations) = match vector.kdf {
+            Kdf::Pbkdf2Sha256 { iterations } => ("PBKDF2-HMAC-SHA256", Some(iterations)),
+            Kdf::HkdfSha256 => ("HKDF-SHA256", None),
+        };
+        let key_schedule = match vector.key_schedule {
+            KeySchedule::Split => "split",
+            KeySchedule::HkdfExpand => "hkdf-expand",
+        };
+        serde_json::json!({
+            "name": vector.name,
+            "version": vector.version,
+            "cipher": vector.cipher,
+            "kdf": kdf,
+            "iterations": iterations,
+            "key_schedule": key_schedule,
+            "key": vector.key,
+            "salt": hex(&vector.salt),
+            "nonce": hex(&vector.nonce),
+            "context": vector.context,
+            "plaintext": VECTOR_PLAINTEXT,
+            "encryption_key": hex(&vector.encryption_key),
+            "mac_key": hex(&vector.mac_key),
+            "file": vector.file,
+        })
+    }).collect();
+    let mut json = serde_json::to_string_pretty(&entries).unwrap_or_default();
+    json.push('\n');
+    json
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
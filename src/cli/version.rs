@@ -0,0 +1,131 @@
+//! `envcrypt --version --format json`: what a build supports (features, ciphers, key
+//! derivation functions and file format versions), so scripts can check a binary before
+//! using it.
+
+use clap::builder::{EnumValueParser, TypedValueParser};
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::armor::FileFormat;
+use crate::cipher::get_supported_ciphers;
+use crate::format::{FORMAT_VERSION, LEGACY_VERSION};
+
+/// Output of `--version`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum VersionFormat {
+    /// The version and release date, e.g. `envcrypt 0.2.1 (2026-01-11)`
+    #[default]
+    Text,
+    /// A JSON object with the version, compiled features, ciphers, KDFs and file format versions
+    Json,
+}
+
+/// Cargo features of envcrypt and whether this binary was built with them.
+const FEATURES: &[(&str, bool)] = &[
+    ("cipher", cfg!(feature = "cipher")),
+    ("encrypt", cfg!(feature = "encrypt")),
+    ("decrypt", cfg!(feature = "decrypt")),
+    ("key-flag", cfg!(feature = "key-flag")),
+    ("env-flag", cfg!(feature = "env-flag")),
+    ("input-flag", cfg!(feature = "input-flag")),
+    ("mlock", cfg!(feature = "mlock")),
+    ("tui", cfg!(feature = "tui")),
+    ("clipboard", cfg!(feature = "clipboard")),
+    ("fido2", cfg!(feature = "fido2")),
+    ("ffi", cfg!(feature = "ffi")),
+];
+
+/// What this build of envcrypt supports, as printed by `--version --format json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VersionInfo {
+    /// Version of envcrypt, e.g. `0.2.1`.
+    pub version: &'static str,
+    /// Release date of the version, e.g. `2026-01-11`.
+    pub release_date: &'static str,
+    /// Cargo features the binary was built with.
+    pub features: Vec<&'static str>,
+    /// Canonical names of the supported ciphers (`--cipher`).
+    pub ciphers: Vec<&'static str>,
+    /// Key derivation functions: PBKDF2 for keys and passwords, HKDF for raw keys.
+    pub kdfs: Vec<&'static str>,
+    /// Encodings of encrypted files (`--format`).
+    pub file_encodings: Vec<String>,
+    /// Format version of the files written.
+    pub format_version: u8,
+    /// Format versions that can be read, with 0 for legacy files without a header.
+    pub readable_format_versions: Vec<u8>,
+}
+
+impl VersionInfo {
+    /// Returns what this binary supports.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use envcrypt::cli::VersionInfo;
+    ///
+    /// let info = VersionInfo::current();
+    /// assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+    /// assert!(info.ciphers.contains(&"AES-256-GCM"));
+    /// ```
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            release_date: env!("RELEASE_DATE"),
+            features: FEATURES.iter().filter(|(_, enabled)| *enabled).map(|(name, _)| *name).collect(),
+            ciphers: get_supported_ciphers(),
+            kdfs: vec!["PBKDF2-HMAC-SHA256", "HKDF-SHA256"],
+            file_encodings: FileFormat::value_variants().iter().map(ToString::to_string).collect(),
+            format_version: FORMAT_VERSION,
+            readable_format_versions: std::iter::once(LEGACY_VERSION).chain(1..=FORMAT_VERSION).collect(),
+        }
+    }
+}
+
+/// Returns the `--version` output in `format`, without a trailing newline.
+pub fn render_version(format: VersionFormat) -> String {
+    match format {
+        VersionFormat::Text => format!("envcrypt {}", super::VERSION),
+        VersionFormat::Json => serde_json::to_string_pretty(&VersionInfo::current())
+            .expect("version information serializes to JSON"),
+    }
+}
+
+/// Returns the `--format` given with `--version` before the command in `args`, or `None`
+/// when there is none and clap prints the version as text. An invalid format exits with
+/// clap's usage error.
+///
+/// This runs before parsing because clap prints the version as soon as it sees `--version`.
+pub(crate) fn requested_format(args: &[String]) -> Option<VersionFormat> {
+    if !args.iter().any(|arg| arg == "--version" || arg == "-V") {
+        return None;
+    }
+    let mut command = super::help::command();
+    command.build();
+    let mut version = false;
+    let mut format = None;
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--" || command.find_subcommand(arg).is_some() {
+            break;
+        }
+        match arg.as_str() {
+            "-V" | "--version" => version = true,
+            "--format" => format = args.next().map(String::as_str),
+            _ => {
+                if let Some(value) = arg.strip_prefix("--format=") {
+                    format = Some(value);
+                }
+            }
+        }
+    }
+    let (true, Some(value)) = (version, format) else {
+        return None;
+    };
+    let arg = command.get_arguments().find(|arg| arg.get_id() == "format");
+    Some(
+        EnumValueParser::<VersionFormat>::new()
+            .parse_ref(&command, arg, value.as_ref())
+            .unwrap_or_else(|e| e.exit()),
+    )
+}
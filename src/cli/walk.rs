@@ -0,0 +1,206 @@
+//! Finding files in directory trees for commands that work on many files (`migrate`,
+//! `tui`).
+//!
+//! Which files are picked up is controlled with gitignore-style patterns: the `exclude`
+//! and `include` lists of `envcrypt.toml`, the `--exclude` and `--include` flags, and the
+//! `.gitignore` and `.ignore` files found along the way. Env files are usually listed in
+//! `.gitignore` themselves, so those files only prune directories (vendored code,
+//! `node_modules`, build output); files are chosen by name and the patterns.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+/// Patterns excluded from every walk, in addition to the configured ones.
+pub const DEFAULT_EXCLUDES: &[&str] = &[".git/", "node_modules/", "vendor/", ".env.example"];
+
+/// Files in a directory's `.gitignore` and `.ignore` that prune its subdirectories.
+const IGNORE_FILES: &[&str] = &[".gitignore", ".ignore"];
+
+/// Options controlling which files a command working on a directory picks up.
+#[derive(Debug, Clone, Default)]
+pub struct WalkOptions {
+    /// Also search subdirectories (symbolic links are not followed).
+    pub recursive: bool,
+    /// Gitignore-style patterns of files and directories to skip, in addition to
+    /// [`DEFAULT_EXCLUDES`]. Patterns containing a `/` are relative to the searched
+    /// directory.
+    pub exclude: Vec<String>,
+    /// Gitignore-style patterns of the files to pick up, or of directories to pick up
+    /// every file in; if empty, every file is.
+    pub include: Vec<String>,
+}
+
+/// Returns the files in `dir` whose name satisfies `is_candidate` in name order,
+/// descending into subdirectories if `options.recursive`. Files and directories matching
+/// an exclude pattern are skipped, as are directories ignored by a `.gitignore` or
+/// `.ignore` file, and files not matching an include pattern.
+///
+/// # Errors
+///
+/// Returns an error string if a pattern is invalid or a directory cannot be read.
+pub(crate) fn collect_files(
+    dir: &Path,
+    options: &WalkOptions,
+    is_candidate: impl Fn(&str) -> bool,
+) -> Result<Vec<PathBuf>, String> {
+    let excluded = DEFAULT_EXCLUDES.iter().copied().chain(options.exclude.iter().map(String::as_str));
+    let walker = Walker {
+        root: dir,
+        recursive: options.recursive,
+        exclude: build_patterns(dir, "exclude", excluded)?,
+        include: if options.include.is_empty() {
+            None
+        } else {
+            Some(build_patterns(dir, "include", options.include.iter().map(String::as_str))?)
+        },
+        is_candidate,
+    };
+
+    let mut files = Vec::new();
+    walker.walk(dir, &mut Vec::new(), &mut files)?;
+    Ok(files)
+}
+
+struct Walker<'a, F> {
+    root: &'a Path,
+    recursive: bool,
+    exclude: Gitignore,
+    include: Option<Gitignore>,
+    is_candidate: F,
+}
+
+impl<F: Fn(&str) -> bool> Walker<'_, F> {
+    /// Adds the files in `dir` to `files`, with `ignores` holding the ignore files of the
+    /// directories above it.
+    fn walk(&self, dir: &Path, ignores: &mut Vec<Gitignore>, files: &mut Vec<PathBuf>) -> Result<(), String> {
+        let mut entries = fs::read_dir(dir)
+            .and_then(|entries| entries.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| format!("Error reading directory {}: {}", dir.display(), e))?;
+        entries.sort_by_key(|entry| entry.file_name());
+        ignores.push(read_ignore_files(dir));
+
+        for entry in entries {
+            let path = entry.path();
+            let relative = path.strip_prefix(self.root).unwrap_or(&path);
+            let Ok(file_type) = entry.file_type() else { continue };
+            if file_type.is_dir() {
+                if self.recursive && !self.is_excluded_dir(&path, relative, ignores) {
+                    self.walk(&path, ignores, files)?;
+                }
+            } else if file_type.is_file()
+                && (self.is_candidate)(&entry.file_name().to_string_lossy())
+                && !self.exclude.matched(relative, false).is_ignore()
+                && self.include.as_ref().is_none_or(|include| include.matched_path_or_any_parents(relative, false).is_ignore())
+            {
+                files.push(path);
+            }
+        }
+        ignores.pop();
+        Ok(())
+    }
+
+    fn is_excluded_dir(&self, path: &Path, relative: &Path, ignores: &[Gitignore]) -> bool {
+        if self.exclude.matched(relative, true).is_ignore() {
+            tracing::debug!("Skipping {}: excluded", path.display());
+            return true;
+        }
+        // The closest ignore file with a matching rule decides
+        let ignored = ignores.iter().rev()
+            .map(|ignore| ignore.matched(path, true))
+            .find(|matched| !matched.is_none())
+            .is_some_and(|matched| matched.is_ignore());
+        if ignored {
+            tracing::debug!("Skipping {}: ignored", path.display());
+        }
+        ignored
+    }
+}
+
+fn build_patterns<'a>(root: &Path, kind: &str, patterns: impl Iterator<Item = &'a str>) -> Result<Gitignore, String> {
+    let mut builder = GitignoreBuilder::new(root);
+    for pattern in patterns {
+        builder.add_line(None, pattern)
+            .map_err(|e| format!("Invalid {} pattern '{}': {}", kind, pattern, e))?;
+    }
+    builder.build().map_err(|e| format!("Invalid {} patterns: {}", kind, e))
+}
+
+/// Reads the ignore files of `dir`, skipping (and logging) lines that cannot be parsed.
+fn read_ignore_files(dir: &Path) -> Gitignore {
+    let mut builder = GitignoreBuilder::new(dir);
+    for name in IGNORE_FILES {
+        let path = dir.join(name);
+        if path.is_file() {
+            if let Some(e) = builder.add(&path) {
+                tracing::debug!("Error reading {}: {}", path.display(), e);
+            }
+        }
+    }
+    builder.build().unwrap_or_else(|e| {
+        tracing::debug!("Ignoring the ignore files in {}: {}", dir.display(), e);
+        Gitignore::empty()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(dir: &Path, options: &WalkOptions) -> Vec<String> {
+        collect_files(dir, options, |name| name.starts_with(".env"))
+            .unwrap()
+            .iter()
+            .map(|path| path.strip_prefix(dir).unwrap().to_string_lossy().replace('\\', "/"))
+            .collect()
+    }
+
+    fn tree() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for path in [".env", ".env.example", "api/.env", "node_modules/pkg/.env", "build/.env", "legacy/.env", "README.md"] {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "A=1\n").unwrap();
+        }
+        fs::write(dir.path().join(".gitignore"), ".env\nbuild/\n").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_default_excludes_and_gitignored_directories() {
+        let dir = tree();
+        let options = WalkOptions { recursive: true, ..WalkOptions::default() };
+        // The gitignored .env is still found; only directories are pruned
+        assert_eq!(names(dir.path(), &options), [".env", "api/.env", "legacy/.env"]);
+
+        let options = WalkOptions::default();
+        assert_eq!(names(dir.path(), &options), [".env"]);
+    }
+
+    #[test]
+    fn test_exclude_and_include_patterns() {
+        let dir = tree();
+        let options = WalkOptions { recursive: true, exclude: vec!["legacy".to_string()], ..WalkOptions::default() };
+        assert_eq!(names(dir.path(), &options), [".env", "api/.env"]);
+
+        let options = WalkOptions { recursive: true, include: vec!["/api/".to_string()], ..WalkOptions::default() };
+        assert_eq!(names(dir.path(), &options), ["api/.env"]);
+    }
+
+    #[test]
+    fn test_gitignore_negation_keeps_directory() {
+        let dir = tree();
+        fs::write(dir.path().join(".gitignore"), "*/\n!api/\n").unwrap();
+        let options = WalkOptions { recursive: true, ..WalkOptions::default() };
+        assert_eq!(names(dir.path(), &options), [".env", "api/.env"]);
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = WalkOptions { exclude: vec!["[z-a]".to_string()], ..WalkOptions::default() };
+        let error = collect_files(dir.path(), &options, |_| true).unwrap_err();
+        assert!(error.contains("Invalid exclude pattern '[z-a]'"), "{}", error);
+    }
+}
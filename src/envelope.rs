@@ -0,0 +1,433 @@
+//! Encrypting and decrypting complete files in memory.
+//!
+//! This is the part of encryption and decryption that needs neither a filesystem nor a
+//! terminal. [`encrypt()`] turns plaintext into the contents of an encrypted file, with
+//! a [header](crate::format) and in either [encoding](crate::armor); given those
+//! contents and the key, [`decrypt()`] parses the header, derives the file keys and
+//! returns the plaintext. Together with the [`cipher`](crate::cipher),
+//! [`key`](crate::key) and [`format`](crate::format) modules it builds without the
+//! `encrypt`/`decrypt` CLI features, so envcrypt files can be read wherever the data
+//! comes from, including `wasm32-unknown-unknown` targets such as browsers and edge
+//! workers.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use envcrypt::envelope;
+//!
+//! let contents = std::fs::read(".env.encrypted").unwrap();
+//! let plaintext = envelope::decrypt(&contents, "my-secret-key", None)?;
+//! println!("{}", String::from_utf8_lossy(&plaintext));
+//! # Ok::<(), envelope::EnvelopeError>(())
+//! ```
+
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::armor::{decode_file, encode_file, FileFormat};
+use crate::cipher::{canonical_cipher_name, get_cipher, CipherError};
+use crate::format::{self, FormatError, Header, Kdf, Metadata};
+use crate::key::{decode_raw_key, derive_keys_from_raw_key, generate_salt, KeyMaterial, MAX_PBKDF2_ITERATIONS, MIN_PBKDF2_ITERATIONS};
+
+/// Errors that can occur while decrypting a file in memory.
+#[derive(Debug)]
+pub enum EnvelopeError {
+    /// The contents are neither binary data nor valid base64 armor.
+    Encoding(String),
+
+    /// The header could not be parsed.
+    Format(FormatError),
+
+    /// The file was encrypted with a cipher that is not available in this build.
+    UnsupportedCipher(String),
+
+    /// The file is bound to a context string, but none was given.
+    ContextRequired,
+
+    /// A context string was given, but the file is not bound to one.
+    UnexpectedContext,
+
+    /// The file was encrypted in raw key mode, but the key is not a base64-encoded
+    /// 32-byte key.
+    InvalidRawKey,
+
+    /// The PBKDF2 iteration count to encrypt with is outside
+    /// [`MIN_PBKDF2_ITERATIONS`]`..=`[`MAX_PBKDF2_ITERATIONS`], so the file would be too
+    /// weak or could not be decrypted.
+    InvalidIterations(u32),
+
+    /// Decryption failed: the key or context string is wrong, or the data was modified.
+    Cipher(CipherError),
+}
+
+impl std::fmt::Display for EnvelopeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EnvelopeError::Encoding(msg) => write!(f, "{}", msg),
+            EnvelopeError::Format(e @ FormatError::HeaderTooLarge(_)) => write!(f, "{}", e),
+            EnvelopeError::Format(e) => write!(f, "Invalid encrypted file format: {}", e),
+            EnvelopeError::UnsupportedCipher(msg) => write!(f, "{}", msg),
+            EnvelopeError::ContextRequired => write!(f, "This file is bound to a context string, but none was given"),
+            EnvelopeError::UnexpectedContext => write!(f, "This file is not bound to a context string, but one was given"),
+            EnvelopeError::InvalidRawKey => write!(f, "This file was encrypted with a raw key - provide the base64-encoded 32-byte key that was generated for it"),
+            EnvelopeError::InvalidIterations(iterations) => write!(f, "KDF iterations must be between {} and {} (got {})", MIN_PBKDF2_ITERATIONS, MAX_PBKDF2_ITERATIONS, iterations),
+            EnvelopeError::Cipher(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for EnvelopeError {}
+
+/// Derives the encryption and MAC keys of a file from its header and key.
+///
+/// PBKDF2 files use the iteration count and key schedule recorded in the header; raw key
+/// files expect `key` to be the base64-encoded 32-byte key, without a "base64:" prefix.
+///
+/// # Errors
+///
+/// Returns [`EnvelopeError::InvalidRawKey`] if the file uses raw key mode and `key` is
+/// not a raw key.
+pub fn derive_file_keys(header: &Header, key: &str) -> Result<(Vec<u8>, Vec<u8>), EnvelopeError> {
+    derive_file_keys_cached(header, key, &KeyMaterial::new())
+}
+
+/// Like [`derive_file_keys()`], but PBKDF2 keys are taken from `cache` if they were
+/// derived for the same key and header before, and added to it otherwise.
+pub fn derive_file_keys_cached(header: &Header, key: &str, cache: &KeyMaterial) -> Result<(Vec<u8>, Vec<u8>), EnvelopeError> {
+    match header.kdf {
+        Kdf::Pbkdf2Sha256 { iterations } => Ok(cache.derive_keys(key, &header.salt, iterations, header.key_schedule)),
+        Kdf::HkdfSha256 => {
+            let mut raw_key = decode_raw_key(key).ok_or(EnvelopeError::InvalidRawKey)?;
+            let keys = derive_keys_from_raw_key(&raw_key, &header.salt);
+            raw_key.zeroize();
+            Ok(keys)
+        }
+    }
+}
+
+/// Encrypts `plaintext` into the contents of an encrypted file.
+///
+/// The file keys are derived from `key` with `kdf` and a fresh salt; for
+/// [`Kdf::HkdfSha256`], `key` must be a base64-encoded 32-byte key. If `context` is set,
+/// the file is bound to it and only decrypts with the same string. The header records
+/// the cipher, KDF and salt, the tool version and, where a clock is available, the
+/// creation time.
+///
+/// # Errors
+///
+/// Returns [`EnvelopeError::UnsupportedCipher`] for unknown cipher names,
+/// [`EnvelopeError::InvalidIterations`] if a PBKDF2 iteration count is out of range,
+/// [`EnvelopeError::InvalidRawKey`] if raw key mode is requested with another key, and
+/// [`EnvelopeError::Cipher`] if encryption fails.
+///
+/// # Example
+///
+/// ```
+/// use envcrypt::armor::FileFormat;
+/// use envcrypt::envelope;
+/// use envcrypt::format::Kdf;
+///
+/// let kdf = Kdf::Pbkdf2Sha256 { iterations: 1_000 };
+/// let contents = envelope::encrypt(b"APP_KEY=test123", "secret", "AES-256-GCM", kdf, None, FileFormat::Armor)?;
+/// assert_eq!(envelope::decrypt(&contents, "secret", None)?.as_slice(), b"APP_KEY=test123");
+/// # Ok::<(), envelope::EnvelopeError>(())
+/// ```
+pub fn encrypt(
+    plaintext: &[u8],
+    key: &str,
+    cipher_name: &str,
+    kdf: Kdf,
+    context: Option<&str>,
+    file_format: FileFormat,
+) -> Result<Vec<u8>, EnvelopeError> {
+    let cipher_name = canonical_cipher_name(cipher_name).map_err(EnvelopeError::UnsupportedCipher)?;
+    // format::decode() refuses files outside this range, so none may be written
+    if let Kdf::Pbkdf2Sha256 { iterations } = kdf {
+        if !(MIN_PBKDF2_ITERATIONS..=MAX_PBKDF2_ITERATIONS).contains(&iterations) {
+            return Err(EnvelopeError::InvalidIterations(iterations));
+        }
+    }
+    let mut header = Header::new(cipher_name, kdf, generate_salt());
+    header.metadata = Metadata {
+        created: unix_now(),
+        tool_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+        ..Metadata::default()
+    };
+
+    let (mut encryption_key, mut mac_key) = derive_file_keys(&header, key)?;
+    let data = seal(plaintext, &mut header, &encryption_key, &mac_key, context);
+    encryption_key.zeroize();
+    mac_key.zeroize();
+
+    Ok(encode_file(&data?, file_format))
+}
+
+/// Encrypts `plaintext` with already derived file keys and returns the header followed
+/// by the cipher output, before [encoding](crate::armor::encode_file).
+///
+/// The header is marked as bound to `context` if one is given, and is authenticated
+/// along with the ciphertext.
+///
+/// # Errors
+///
+/// Returns [`EnvelopeError::UnsupportedCipher`] if the header names an unknown cipher,
+/// [`EnvelopeError::Format`] if the header is too large to write, and
+/// [`EnvelopeError::Cipher`] if encryption fails.
+pub fn seal(
+    plaintext: &[u8],
+    header: &mut Header,
+    encryption_key: &[u8],
+    mac_key: &[u8],
+    context: Option<&str>,
+) -> Result<Vec<u8>, EnvelopeError> {
+    seal_inner(plaintext, header, encryption_key, mac_key, context, None)
+}
+
+/// Like [`seal()`], but encrypts with the given IV or nonce instead of a random one, for
+/// reproducible output such as test vectors.
+///
+/// See [`Cipher::encrypt_with_nonce()`](crate::cipher::Cipher::encrypt_with_nonce) for
+/// why a nonce must never be reused.
+///
+/// # Errors
+///
+/// See [`seal()`]; also returns [`EnvelopeError::Cipher`] if `nonce` has the wrong
+/// length for the cipher.
+pub fn seal_with_nonce(
+    plaintext: &[u8],
+    header: &mut Header,
+    encryption_key: &[u8],
+    mac_key: &[u8],
+    context: Option<&str>,
+    nonce: &[u8],
+) -> Result<Vec<u8>, EnvelopeError> {
+    seal_inner(plaintext, header, encryption_key, mac_key, context, Some(nonce))
+}
+
+fn seal_inner(
+    plaintext: &[u8],
+    header: &mut Header,
+    encryption_key: &[u8],
+    mac_key: &[u8],
+    context: Option<&str>,
+    nonce: Option<&[u8]>,
+) -> Result<Vec<u8>, EnvelopeError> {
+    let cipher = get_cipher(header.cipher.as_deref().unwrap_or("AES-256-CBC"))
+        .map_err(EnvelopeError::UnsupportedCipher)?;
+    header.context_bound = context.is_some();
+
+    // The header and the context are authenticated along with the ciphertext
+    let mut output = format::encode_header(header).map_err(EnvelopeError::Format)?;
+    let aad = format::associated_data(&output, context);
+    let encrypted = match nonce {
+        Some(nonce) => cipher.encrypt_with_nonce(plaintext, &aad, nonce, encryption_key, mac_key),
+        None => cipher.encrypt_with_aad(plaintext, &aad, encryption_key, mac_key),
+    };
+    output.extend_from_slice(&encrypted.map_err(EnvelopeError::Cipher)?);
+    Ok(output)
+}
+
+/// Current time in seconds since the Unix epoch, if the platform has a clock.
+fn unix_now() -> Option<u64> {
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    {
+        std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).ok().map(|d| d.as_secs())
+    }
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    {
+        None
+    }
+}
+
+/// Decrypts the contents of an encrypted file, armored or binary, with `key`.
+///
+/// `context` is the context string the file was bound to when it was encrypted with
+/// `--aad`, if any. The plaintext is zeroized when dropped.
+///
+/// Legacy files without a header do not record their cipher and are decrypted with
+/// AES-256-CBC, the default when they were written. Use
+/// [`decrypt_with_legacy_cipher()`] for legacy files encrypted with another cipher.
+///
+/// # Errors
+///
+/// Returns an [`EnvelopeError`] if the contents cannot be parsed, the context string
+/// does not match how the file was encrypted, or decryption fails.
+pub fn decrypt(contents: &[u8], key: &str, context: Option<&str>) -> Result<Zeroizing<Vec<u8>>, EnvelopeError> {
+    decrypt_with_legacy_cipher(contents, key, context, "AES-256-CBC")
+}
+
+/// Like [`decrypt()`], but decrypts legacy files without a header with `legacy_cipher`,
+/// as `envcrypt decrypt --cipher` does. Files with a header are decrypted with the
+/// cipher it records, whatever `legacy_cipher` is.
+///
+/// # Errors
+///
+/// See [`decrypt()`]; also returns [`EnvelopeError::UnsupportedCipher`] if the file is a
+/// legacy file and `legacy_cipher` is not a known cipher.
+///
+/// # Example
+///
+/// ```no_run
+/// use envcrypt::envelope;
+///
+/// let contents = std::fs::read(".env.encrypted").unwrap();
+/// let plaintext = envelope::decrypt_with_legacy_cipher(&contents, "my-secret-key", None, "AES-256-GCM")?;
+/// # Ok::<(), envelope::EnvelopeError>(())
+/// ```
+pub fn decrypt_with_legacy_cipher(
+    contents: &[u8],
+    key: &str,
+    context: Option<&str>,
+    legacy_cipher: &str,
+) -> Result<Zeroizing<Vec<u8>>, EnvelopeError> {
+    let (data, _) = decode_file(contents).map_err(EnvelopeError::Encoding)?;
+    let (header, body) = format::decode(&data).map_err(EnvelopeError::Format)?;
+
+    match (header.context_bound, context) {
+        (true, None) => return Err(EnvelopeError::ContextRequired),
+        (false, Some(_)) => return Err(EnvelopeError::UnexpectedContext),
+        _ => {}
+    }
+
+    let cipher = get_cipher(header.cipher.as_deref().unwrap_or(legacy_cipher))
+        .map_err(EnvelopeError::UnsupportedCipher)?;
+    let (mut encryption_key, mut mac_key) = derive_file_keys(&header, key)?;
+
+    // Authenticated headers and the context are bound to the ciphertext as associated data
+    let aad = if header.is_authenticated() {
+        format::associated_data(&data[..header.encoded_len(&data, body)], context)
+    } else {
+        Vec::new()
+    };
+    let plaintext = cipher.decrypt_with_aad(body, &aad, &encryption_key, &mac_key);
+    encryption_key.zeroize();
+    mac_key.zeroize();
+
+    plaintext.map_err(EnvelopeError::Cipher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Encrypts `plaintext` with a cheap PBKDF2 iteration count.
+    fn encrypt_test(plaintext: &[u8], key: &str, context: Option<&str>) -> Vec<u8> {
+        encrypt(plaintext, key, "AES-256-GCM", Kdf::Pbkdf2Sha256 { iterations: 1_000 }, context, FileFormat::Armor).unwrap()
+    }
+
+    #[test]
+    fn test_decrypt_roundtrip() {
+        let contents = encrypt_test(b"APP_KEY=test123", "secret", None);
+        assert_eq!(decrypt(&contents, "secret", None).unwrap().as_slice(), b"APP_KEY=test123");
+        assert!(matches!(decrypt(&contents, "wrong", None), Err(EnvelopeError::Cipher(_))));
+    }
+
+    #[test]
+    fn test_decrypt_checks_context() {
+        let contents = encrypt_test(b"APP_KEY=test123", "secret", Some("production"));
+        assert_eq!(decrypt(&contents, "secret", Some("production")).unwrap().as_slice(), b"APP_KEY=test123");
+        assert!(matches!(decrypt(&contents, "secret", None), Err(EnvelopeError::ContextRequired)));
+        assert!(matches!(decrypt(&contents, "secret", Some("staging")), Err(EnvelopeError::Cipher(_))));
+
+        let unbound = encrypt_test(b"APP_KEY=test123", "secret", None);
+        assert!(matches!(decrypt(&unbound, "secret", Some("production")), Err(EnvelopeError::UnexpectedContext)));
+    }
+
+    #[test]
+    fn test_encrypt_records_header() {
+        let raw_key = "q83vEjRWeJCrze8SNFZ4kKvN7xI0VniQq83vEjRWeJA=";
+        let contents = encrypt(b"A=1", raw_key, "chacha20", Kdf::HkdfSha256, None, FileFormat::Binary).unwrap();
+        let (header, _) = format::decode(&contents).unwrap();
+        assert_eq!(header.cipher.as_deref(), Some("CHACHA20-POLY1305"));
+        assert_eq!(header.kdf, Kdf::HkdfSha256);
+        assert!(header.metadata.created.is_some());
+        assert_eq!(decrypt(&contents, raw_key, None).unwrap().as_slice(), b"A=1");
+
+        let err = encrypt(b"A=1", "not-a-raw-key", "AES-256-CBC", Kdf::HkdfSha256, None, FileFormat::Armor);
+        assert!(matches!(err, Err(EnvelopeError::InvalidRawKey)));
+    }
+
+    #[test]
+    fn test_encrypt_checks_iterations() {
+        for iterations in [0, MIN_PBKDF2_ITERATIONS - 1, MAX_PBKDF2_ITERATIONS + 1, u32::MAX] {
+            let err = encrypt(b"A=1", "secret", "AES-256-GCM", Kdf::Pbkdf2Sha256 { iterations }, None, FileFormat::Armor);
+            assert!(matches!(err, Err(EnvelopeError::InvalidIterations(got)) if got == iterations));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "cipher")]
+    fn test_decrypt_legacy_file_with_other_cipher() {
+        use crate::key::{derive_keys_with_schedule, KeySchedule, DEFAULT_PBKDF2_ITERATIONS};
+
+        let salt = [7u8; 16];
+        let (encryption_key, mac_key) = derive_keys_with_schedule("secret", &salt, DEFAULT_PBKDF2_ITERATIONS, KeySchedule::Split);
+        let encrypted = get_cipher("AES-256-GCM").unwrap().encrypt(b"A=1", &encryption_key, &mac_key).unwrap();
+        let contents = encode_file(&[salt.as_slice(), &encrypted].concat(), FileFormat::Armor);
+
+        assert!(matches!(decrypt(&contents, "secret", None), Err(EnvelopeError::Cipher(_))));
+        assert_eq!(decrypt_with_legacy_cipher(&contents, "secret", None, "aes-256-gcm").unwrap().as_slice(), b"A=1");
+        assert!(matches!(decrypt_with_legacy_cipher(&contents, "secret", None, "rot13"), Err(EnvelopeError::UnsupportedCipher(_))));
+
+        // The cipher recorded in a header wins
+        let contents = encrypt_test(b"A=1", "secret", None);
+        assert_eq!(decrypt_with_legacy_cipher(&contents, "secret", None, "AES-256-CBC").unwrap().as_slice(), b"A=1");
+    }
+
+    #[test]
+    fn test_decrypt_rejects_garbage() {
+        assert!(matches!(decrypt(b"APP_KEY=test123", "secret", None), Err(EnvelopeError::Encoding(_))));
+    }
+
+    /// Raw key for the property tests; raw key files skip PBKDF2, so each case is fast.
+    const RAW_KEY: &str = "q83vEjRWeJCrze8SNFZ4kKvN7xI0VniQq83vEjRWeJA=";
+
+    fn any_cipher() -> impl Strategy<Value = &'static str> {
+        proptest::sample::select(crate::cipher::get_supported_ciphers())
+    }
+
+    proptest! {
+        #[test]
+        fn prop_roundtrip(plaintext in proptest::collection::vec(any::<u8>(), 0..512), cipher in any_cipher(), binary in any::<bool>()) {
+            let format = if binary { FileFormat::Binary } else { FileFormat::Armor };
+            let contents = encrypt(&plaintext, RAW_KEY, cipher, Kdf::HkdfSha256, Some("ctx"), format).unwrap();
+            let decrypted = decrypt(&contents, RAW_KEY, Some("ctx")).unwrap();
+            prop_assert_eq!(decrypted.as_slice(), plaintext.as_slice());
+        }
+
+        #[test]
+        fn prop_truncated_files_are_rejected(plaintext in proptest::collection::vec(any::<u8>(), 0..64), cipher in any_cipher(), cut in any::<proptest::sample::Index>()) {
+            let contents = encrypt(&plaintext, RAW_KEY, cipher, Kdf::HkdfSha256, None, FileFormat::Binary).unwrap();
+            let truncated = &contents[..cut.index(contents.len())];
+            prop_assert!(decrypt(truncated, RAW_KEY, None).is_err());
+        }
+
+        #[test]
+        fn prop_bit_flips_are_rejected(plaintext in proptest::collection::vec(any::<u8>(), 0..64), cipher in any_cipher(), byte in any::<proptest::sample::Index>(), bit in 0..8u8) {
+            let mut contents = encrypt(&plaintext, RAW_KEY, cipher, Kdf::HkdfSha256, None, FileFormat::Binary).unwrap();
+            let index = byte.index(contents.len());
+            contents[index] ^= 1 << bit;
+            // The header is authenticated too, so no flip goes unnoticed
+            prop_assert!(decrypt(&contents, RAW_KEY, None).is_err());
+        }
+
+        #[test]
+        fn prop_arbitrary_headers_never_panic(version in 0..4u8, header_len in any::<u16>(), fields in proptest::collection::vec(any::<u8>(), 0..128)) {
+            let mut data = format::MAGIC.to_vec();
+            data.push(version);
+            data.extend_from_slice(&header_len.to_be_bytes());
+            data.extend_from_slice(&fields);
+            // Only parse, as a valid header could ask for an expensive key derivation
+            let _ = format::decode(&data);
+        }
+
+        #[test]
+        fn prop_arbitrary_input_never_panics(data in proptest::collection::vec(any::<u8>(), 0..256)) {
+            let _ = decode_file(&data);
+            let _ = format::decode(&data);
+            for cipher in crate::cipher::get_supported_ciphers() {
+                prop_assert!(get_cipher(cipher).unwrap().decrypt(&data, &[1u8; 32], &[2u8; 32]).is_err());
+            }
+        }
+    }
+}
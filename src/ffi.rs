@@ -0,0 +1,360 @@
+//! C bindings for in-memory encryption and decryption (`ffi` feature).
+//!
+//! These functions wrap [`envelope::encrypt()`] and [`envelope::decrypt()`] so tooling
+//! written in other languages can read and write envcrypt files with this
+//! implementation instead of reimplementing the format. The C declarations are in
+//! `include/envcrypt.h`; build the shared library with
+//! `cargo build --release --lib --no-default-features --features ffi`.
+//!
+//! Every function returns an [`EnvcryptStatus`] code. Output buffers are allocated by
+//! the library and must be released with [`envcrypt_buffer_free()`], which also
+//! zeroizes them. After a failure, [`envcrypt_last_error()`] describes what went wrong
+//! on the calling thread.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::ptr;
+
+use zeroize::Zeroize;
+
+use crate::armor::FileFormat;
+use crate::envelope::{self, EnvelopeError};
+use crate::format::{FormatError, Kdf};
+use crate::key::DEFAULT_PBKDF2_ITERATIONS;
+
+/// Result of a call into the library.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvcryptStatus {
+    /// The call succeeded.
+    Ok = 0,
+    /// A required pointer was null, a string was not valid UTF-8, or an option was invalid
+    /// (for example an unknown cipher or a raw key that is not 32 bytes).
+    InvalidArgument = 1,
+    /// The input is not an encrypted file this version can read.
+    InvalidFormat = 2,
+    /// The context string does not match how the file was encrypted.
+    ContextMismatch = 3,
+    /// Decryption failed: wrong key or context string, or the file was modified.
+    AuthenticationFailed = 4,
+    /// Encryption failed.
+    EncryptionFailed = 5,
+}
+
+/// A byte buffer allocated by the library.
+#[repr(C)]
+#[derive(Debug)]
+pub struct EnvcryptBuffer {
+    /// Start of the data, or null for an empty buffer.
+    pub data: *mut u8,
+    /// Number of bytes at `data`.
+    pub len: usize,
+}
+
+/// Options for [`envcrypt_encrypt()`]. A null options pointer uses the defaults.
+#[repr(C)]
+#[derive(Debug)]
+pub struct EnvcryptEncryptOptions {
+    /// Cipher name or alias, or null for AES-256-CBC.
+    pub cipher: *const c_char,
+    /// PBKDF2 iteration count between 1,000 and 100,000,000, or 0 for the default.
+    /// Ignored with `raw_key`.
+    pub kdf_iterations: u32,
+    /// Non-zero if the key is a base64-encoded 32-byte key to use with HKDF.
+    pub raw_key: c_int,
+    /// Context string to bind the file to, or null.
+    pub context: *const c_char,
+    /// Non-zero to write raw bytes instead of base64 armor.
+    pub binary: c_int,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Records `message` as the last error of this thread and returns `status`.
+fn fail(status: EnvcryptStatus, message: impl ToString) -> EnvcryptStatus {
+    let message = CString::new(message.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    status
+}
+
+fn status_of(error: &EnvelopeError) -> EnvcryptStatus {
+    match error {
+        EnvelopeError::Format(FormatError::HeaderTooLarge(_)) => EnvcryptStatus::InvalidArgument,
+        EnvelopeError::Encoding(_) | EnvelopeError::Format(_) => EnvcryptStatus::InvalidFormat,
+        EnvelopeError::UnsupportedCipher(_) | EnvelopeError::InvalidRawKey | EnvelopeError::InvalidIterations(_) => EnvcryptStatus::InvalidArgument,
+        EnvelopeError::ContextRequired | EnvelopeError::UnexpectedContext => EnvcryptStatus::ContextMismatch,
+        EnvelopeError::Cipher(_) => EnvcryptStatus::AuthenticationFailed,
+    }
+}
+
+/// Reads an optional C string. Null pointers become `None`.
+///
+/// # Safety
+///
+/// `value` must be null or point to a NUL-terminated string.
+unsafe fn optional_str<'a>(value: *const c_char, name: &str) -> Result<Option<&'a str>, EnvcryptStatus> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(value).to_str()
+        .map(Some)
+        .map_err(|_| fail(EnvcryptStatus::InvalidArgument, format!("{} is not valid UTF-8", name)))
+}
+
+/// Reads a required C string.
+///
+/// # Safety
+///
+/// As for [`optional_str()`].
+unsafe fn required_str<'a>(value: *const c_char, name: &str) -> Result<&'a str, EnvcryptStatus> {
+    optional_str(value, name)?
+        .ok_or_else(|| fail(EnvcryptStatus::InvalidArgument, format!("{} must not be null", name)))
+}
+
+/// Views `len` bytes at `data` as a slice. A null pointer is only accepted for `len == 0`.
+///
+/// # Safety
+///
+/// If not null, `data` must be valid for reads of `len` bytes.
+unsafe fn input_bytes<'a>(data: *const u8, len: usize, name: &str) -> Result<&'a [u8], EnvcryptStatus> {
+    match (data.is_null(), len) {
+        (true, 0) => Ok(&[]),
+        (true, _) => Err(fail(EnvcryptStatus::InvalidArgument, format!("{} must not be null", name))),
+        (false, _) => Ok(std::slice::from_raw_parts(data, len)),
+    }
+}
+
+/// Hands a copy of `bytes` to the caller through `out`. The copy is allocated at its
+/// exact size, so [`envcrypt_buffer_free`] zeroizes all of it and the caller's
+/// `Zeroizing` source wipes the original when it drops; converting a `Vec` with spare
+/// capacity could instead reallocate and free the old buffer unzeroized.
+fn into_buffer(bytes: &[u8], out: &mut EnvcryptBuffer) {
+    let boxed = Box::<[u8]>::from(bytes);
+    out.len = boxed.len();
+    out.data = if boxed.is_empty() { ptr::null_mut() } else { Box::into_raw(boxed).cast::<u8>() };
+}
+
+/// Encrypts `plaintext_len` bytes at `plaintext` with `key` into the contents of an
+/// encrypted file, written to `out`.
+///
+/// # Safety
+///
+/// `plaintext` must be valid for reads of `plaintext_len` bytes (it may be null if the
+/// length is 0), `key` must be a NUL-terminated string, `options` must be null or point
+/// to a valid [`EnvcryptEncryptOptions`] whose strings are null or NUL-terminated, and
+/// `out` must point to writable memory for an [`EnvcryptBuffer`].
+#[no_mangle]
+pub unsafe extern "C" fn envcrypt_encrypt(
+    plaintext: *const u8,
+    plaintext_len: usize,
+    key: *const c_char,
+    options: *const EnvcryptEncryptOptions,
+    out: *mut EnvcryptBuffer,
+) -> EnvcryptStatus {
+    let result = (|| {
+        let out = out.as_mut()
+            .ok_or_else(|| fail(EnvcryptStatus::InvalidArgument, "out must not be null"))?;
+        let plaintext = input_bytes(plaintext, plaintext_len, "plaintext")?;
+        let key = required_str(key, "key")?;
+
+        let (cipher, kdf, context, file_format) = match options.as_ref() {
+            None => (None, Kdf::Pbkdf2Sha256 { iterations: DEFAULT_PBKDF2_ITERATIONS }, None, FileFormat::Armor),
+            Some(options) => {
+                let kdf = match (options.raw_key != 0, options.kdf_iterations) {
+                    (true, _) => Kdf::HkdfSha256,
+                    (false, 0) => Kdf::Pbkdf2Sha256 { iterations: DEFAULT_PBKDF2_ITERATIONS },
+                    (false, iterations) => Kdf::Pbkdf2Sha256 { iterations },
+                };
+                let file_format = if options.binary != 0 { FileFormat::Binary } else { FileFormat::Armor };
+                (optional_str(options.cipher, "cipher")?, kdf, optional_str(options.context, "context")?, file_format)
+            }
+        };
+
+        let contents = envelope::encrypt(plaintext, key, cipher.unwrap_or("AES-256-CBC"), kdf, context, file_format)
+            .map_err(|e| match e {
+                EnvelopeError::Cipher(_) => fail(EnvcryptStatus::EncryptionFailed, e),
+                _ => fail(status_of(&e), e),
+            })?;
+        into_buffer(&contents, out);
+        Ok(())
+    })();
+    result.err().unwrap_or(EnvcryptStatus::Ok)
+}
+
+/// Decrypts the contents of an encrypted file, armored or binary, into `out`.
+///
+/// `context` is the context string the file was bound to, or null. Legacy files without
+/// a header are decrypted with AES-256-CBC; see
+/// [`envcrypt_decrypt_with_legacy_cipher()`] for others.
+///
+/// # Safety
+///
+/// `contents` must be valid for reads of `contents_len` bytes, `key` must be a
+/// NUL-terminated string, `context` must be null or NUL-terminated, and `out` must point
+/// to writable memory for an [`EnvcryptBuffer`].
+#[no_mangle]
+pub unsafe extern "C" fn envcrypt_decrypt(
+    contents: *const u8,
+    contents_len: usize,
+    key: *const c_char,
+    context: *const c_char,
+    out: *mut EnvcryptBuffer,
+) -> EnvcryptStatus {
+    envcrypt_decrypt_with_legacy_cipher(contents, contents_len, key, context, ptr::null(), out)
+}
+
+/// Like [`envcrypt_decrypt()`], but decrypts legacy files without a header with
+/// `legacy_cipher` (a cipher name or alias, or null for AES-256-CBC), as
+/// `envcrypt decrypt --cipher` does. Files with a header use the cipher it records.
+///
+/// # Safety
+///
+/// As for [`envcrypt_decrypt()`]; `legacy_cipher` must be null or NUL-terminated.
+#[no_mangle]
+pub unsafe extern "C" fn envcrypt_decrypt_with_legacy_cipher(
+    contents: *const u8,
+    contents_len: usize,
+    key: *const c_char,
+    context: *const c_char,
+    legacy_cipher: *const c_char,
+    out: *mut EnvcryptBuffer,
+) -> EnvcryptStatus {
+    let result = (|| {
+        let out = out.as_mut()
+            .ok_or_else(|| fail(EnvcryptStatus::InvalidArgument, "out must not be null"))?;
+        let contents = input_bytes(contents, contents_len, "contents")?;
+        let key = required_str(key, "key")?;
+        let context = optional_str(context, "context")?;
+        let legacy_cipher = optional_str(legacy_cipher, "legacy_cipher")?.unwrap_or("AES-256-CBC");
+
+        let plaintext = envelope::decrypt_with_legacy_cipher(contents, key, context, legacy_cipher)
+            .map_err(|e| fail(status_of(&e), e))?;
+        into_buffer(&plaintext, out);
+        Ok(())
+    })();
+    result.err().unwrap_or(EnvcryptStatus::Ok)
+}
+
+/// Zeroizes and frees a buffer returned by the library, leaving it empty. Freeing an
+/// empty buffer or a null pointer does nothing.
+///
+/// # Safety
+///
+/// `buffer` must be null or point to a buffer filled by this library that has not been
+/// freed or modified since.
+#[no_mangle]
+pub unsafe extern "C" fn envcrypt_buffer_free(buffer: *mut EnvcryptBuffer) {
+    let Some(buffer) = buffer.as_mut() else { return };
+    if !buffer.data.is_null() {
+        let mut data = Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.data, buffer.len));
+        data.zeroize();
+    }
+    buffer.data = ptr::null_mut();
+    buffer.len = 0;
+}
+
+/// Returns a description of the last error on the calling thread, or null if there was
+/// none. The string stays valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn envcrypt_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+/// Returns the library version as a static NUL-terminated string.
+#[no_mangle]
+pub extern "C" fn envcrypt_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_buffer() -> EnvcryptBuffer {
+        EnvcryptBuffer { data: ptr::null_mut(), len: 0 }
+    }
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(envcrypt_last_error()) }.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let key = c"secret";
+        let context = c"production";
+        let options = EnvcryptEncryptOptions {
+            cipher: c"gcm".as_ptr(),
+            kdf_iterations: 1_000,
+            raw_key: 0,
+            context: context.as_ptr(),
+            binary: 1,
+        };
+        let plaintext = b"APP_KEY=test123";
+
+        let mut encrypted = empty_buffer();
+        let status = unsafe { envcrypt_encrypt(plaintext.as_ptr(), plaintext.len(), key.as_ptr(), &options, &mut encrypted) };
+        assert_eq!(status, EnvcryptStatus::Ok);
+
+        let mut decrypted = empty_buffer();
+        let status = unsafe { envcrypt_decrypt(encrypted.data, encrypted.len, key.as_ptr(), context.as_ptr(), &mut decrypted) };
+        assert_eq!(status, EnvcryptStatus::Ok);
+        assert_eq!(unsafe { std::slice::from_raw_parts(decrypted.data, decrypted.len) }, plaintext);
+
+        unsafe {
+            envcrypt_buffer_free(&mut encrypted);
+            envcrypt_buffer_free(&mut decrypted);
+        }
+        assert!(encrypted.data.is_null() && decrypted.len == 0);
+    }
+
+    #[test]
+    fn test_legacy_cipher() {
+        use crate::armor::{encode_file, FileFormat};
+        use crate::cipher::get_cipher;
+        use crate::key::{derive_keys_with_schedule, KeySchedule, DEFAULT_PBKDF2_ITERATIONS};
+
+        let salt = [7u8; 16];
+        let (encryption_key, mac_key) = derive_keys_with_schedule("secret", &salt, DEFAULT_PBKDF2_ITERATIONS, KeySchedule::Split);
+        let encrypted = get_cipher("AES-256-GCM").unwrap().encrypt(b"A=1", &encryption_key, &mac_key).unwrap();
+        let contents = encode_file(&[salt.as_slice(), &encrypted].concat(), FileFormat::Armor);
+
+        let mut out = empty_buffer();
+        let status = unsafe { envcrypt_decrypt(contents.as_ptr(), contents.len(), c"secret".as_ptr(), ptr::null(), &mut out) };
+        assert_ne!(status, EnvcryptStatus::Ok);
+
+        let status = unsafe {
+            envcrypt_decrypt_with_legacy_cipher(contents.as_ptr(), contents.len(), c"secret".as_ptr(), ptr::null(), c"gcm".as_ptr(), &mut out)
+        };
+        assert_eq!(status, EnvcryptStatus::Ok);
+        assert_eq!(unsafe { std::slice::from_raw_parts(out.data, out.len) }, b"A=1");
+        unsafe { envcrypt_buffer_free(&mut out) };
+    }
+
+    #[test]
+    fn test_errors_are_reported() {
+        let mut out = empty_buffer();
+        let status = unsafe { envcrypt_decrypt(b"APP_KEY=1".as_ptr(), 9, ptr::null(), ptr::null(), &mut out) };
+        assert_eq!(status, EnvcryptStatus::InvalidArgument);
+        assert_eq!(last_error(), "key must not be null");
+
+        let options = EnvcryptEncryptOptions {
+            cipher: c"aes-265-gcm".as_ptr(),
+            kdf_iterations: 0,
+            raw_key: 0,
+            context: ptr::null(),
+            binary: 0,
+        };
+        let status = unsafe { envcrypt_encrypt(ptr::null(), 0, c"secret".as_ptr(), &options, &mut out) };
+        assert_eq!(status, EnvcryptStatus::InvalidArgument);
+        assert!(last_error().contains("did you mean AES-256-GCM?"));
+        assert!(out.data.is_null());
+
+        let options = EnvcryptEncryptOptions { cipher: ptr::null(), kdf_iterations: 1, ..options };
+        let status = unsafe { envcrypt_encrypt(ptr::null(), 0, c"secret".as_ptr(), &options, &mut out) };
+        assert_eq!(status, EnvcryptStatus::InvalidArgument);
+        assert!(last_error().contains("KDF iterations must be between"));
+        assert!(out.data.is_null());
+    }
+}
@@ -0,0 +1,717 @@
+//! Versioned on-disk format for encrypted environment files.
+//!
+//! Every file written by `envcrypt` starts with a small self-describing header that
+//! records everything needed to decrypt it except the key: the cipher, the key
+//! derivation function and its parameters, the salt, optional provenance
+//! [`Metadata`], and optionally the file keys wrapped for a
+//! [recovery recipient](crate::recovery) or for OpenPGP or SSH recipients. The header is followed by the raw output of
+//! the selected [`Cipher`](crate::cipher::Cipher) and, in signed files, an Ed25519
+//! [signature](crate::signature) of everything before it.
+//!
+//! # Layout
+//!
+//! ```text
+//! [Magic "ENVC" (4 bytes)][Version (1 byte)][Header Length (u16 BE)][Header Fields][Cipher Output][Signature Trailer (signed files only)]
+//! ```
+//!
+//! Header fields are encoded as `[Tag (1 byte)][Length (u16 BE)][Value]` so new fields
+//! can be added without breaking older readers. Unknown tags are skipped.
+//!
+//! Since version 2 the encoded header (magic, version, length and fields) is passed to
+//! the cipher as associated data, so changing any header field, including the metadata,
+//! makes decryption fail. Version 1 headers, which were not authenticated, are rejected:
+//! no version 1 file was released, and reading one would let a version 2 file be
+//! re-framed with forged metadata.
+//! A file can additionally be bound to a context string that is appended to the
+//! associated data but not stored (see [`associated_data()`]).
+//!
+//! # Legacy Files
+//!
+//! Files produced before the header was introduced have the layout
+//! `[Salt (16 bytes)][Cipher Output]` and were always derived with PBKDF2 at
+//! [`DEFAULT_PBKDF2_ITERATIONS`] and the [`KeySchedule::Split`] key schedule. [`decode()`] recognises them by the missing magic
+//! bytes and reports them with [`Header::version`] set to [`LEGACY_VERSION`].
+
+use crate::key::{KeySchedule, DEFAULT_PBKDF2_ITERATIONS, MAX_PBKDF2_ITERATIONS};
+
+/// Magic bytes identifying an `envcrypt` file.
+pub const MAGIC: &[u8; 4] = b"ENVC";
+
+/// Current format version written by [`Header::new()`].
+pub const FORMAT_VERSION: u8 = 2;
+
+/// First format version whose header is authenticated as associated data.
+pub const AUTHENTICATED_HEADER_VERSION: u8 = 2;
+
+/// Pseudo version reported for files without a header.
+pub const LEGACY_VERSION: u8 = 0;
+
+/// Length of the key derivation salt in bytes.
+pub const SALT_LEN: usize = 16;
+
+/// Length of the hardware token challenge in bytes.
+pub const CHALLENGE_LEN: usize = 32;
+
+/// Length of the signature trailer of signed files: the Ed25519 signature (64 bytes)
+/// followed by the signer's public key (32 bytes).
+pub const SIGNATURE_TRAILER_LEN: usize = 96;
+
+/// Maximum length in bytes of the header fields, which are preceded by a 16-bit length.
+pub const MAX_HEADER_LEN: usize = u16::MAX as usize;
+
+const TAG_CIPHER: u8 = 0x01;
+const TAG_KDF: u8 = 0x02;
+const TAG_SALT: u8 = 0x03;
+const TAG_KEY_SCHEDULE: u8 = 0x04;
+const TAG_RECOVERY: u8 = 0x05;
+const TAG_CREATED: u8 = 0x06;
+const TAG_TOOL_VERSION: u8 = 0x07;
+const TAG_COMMENT: u8 = 0x08;
+const TAG_CONTEXT_BOUND: u8 = 0x09;
+const TAG_MODE: u8 = 0x0a;
+const TAG_MODIFIED: u8 = 0x0b;
+const TAG_GPG: u8 = 0x0c;
+const TAG_SSH: u8 = 0x0d;
+const TAG_CHALLENGE: u8 = 0x0e;
+const TAG_SIGNED: u8 = 0x0f;
+
+const KDF_PBKDF2_SHA256: u8 = 0x01;
+const KDF_HKDF_SHA256: u8 = 0x02;
+
+const SCHEDULE_SPLIT: u8 = 0x01;
+const SCHEDULE_HKDF_EXPAND: u8 = 0x02;
+
+/// Key derivation function used to turn the user key into cipher keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kdf {
+    /// PBKDF2-HMAC-SHA256 with the given iteration count.
+    Pbkdf2Sha256 {
+        /// Number of PBKDF2 iterations.
+        iterations: u32,
+    },
+    /// HKDF-SHA256 over a raw 32-byte random key (no password stretching).
+    HkdfSha256,
+}
+
+impl std::fmt::Display for Kdf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Kdf::Pbkdf2Sha256 { iterations } => write!(f, "PBKDF2-HMAC-SHA256 ({} iterations)", iterations),
+            Kdf::HkdfSha256 => write!(f, "HKDF-SHA256 (raw key)"),
+        }
+    }
+}
+
+/// Provenance information recorded in the header.
+///
+/// Metadata is readable without the key and, in authenticated headers, cannot be
+/// changed without breaking decryption.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// Time the file was encrypted, in seconds since the Unix epoch.
+    pub created: Option<u64>,
+    /// Version of the tool that encrypted the file.
+    pub tool_version: Option<String>,
+    /// Free-form comment supplied when encrypting.
+    pub comment: Option<String>,
+    /// Unix permission bits of the plaintext file.
+    pub mode: Option<u32>,
+    /// Modification time of the plaintext file, in seconds since the Unix epoch.
+    pub modified: Option<u64>,
+}
+
+/// Parsed header of an encrypted file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    /// Format version ([`LEGACY_VERSION`] for files without a header).
+    pub version: u8,
+    /// Canonical cipher name (e.g. "AES-256-GCM"). `None` for legacy files, which
+    /// do not record the cipher.
+    pub cipher: Option<String>,
+    /// Key derivation function and parameters.
+    pub kdf: Kdf,
+    /// How the encryption and MAC keys are separated from the KDF output. Headers
+    /// without this field use [`KeySchedule::Split`].
+    pub key_schedule: KeySchedule,
+    /// Salt used for key derivation.
+    pub salt: [u8; SALT_LEN],
+    /// File keys wrapped for the recovery recipient (see [`crate::recovery`]), if any.
+    pub recovery: Option<Vec<u8>>,
+    /// File keys encrypted to one or more OpenPGP recipients, as a binary OpenPGP
+    /// message, if any.
+    pub gpg: Option<Vec<u8>>,
+    /// File keys encrypted to one or more SSH public keys, as an age message, if any.
+    pub ssh: Option<Vec<u8>>,
+    /// Challenge a hardware token turned into the key (see `envcrypt key fido2-enroll`),
+    /// if the key came from one.
+    pub challenge: Option<[u8; CHALLENGE_LEN]>,
+    /// Provenance information.
+    pub metadata: Metadata,
+    /// Whether the ciphertext is bound to a context string that must be supplied to
+    /// decrypt it. The context itself is not stored.
+    pub context_bound: bool,
+    /// Whether a signature trailer of [`SIGNATURE_TRAILER_LEN`] bytes follows the cipher
+    /// output. [`decode()`] leaves it out of the returned cipher output.
+    pub signed: bool,
+}
+
+impl Header {
+    /// Creates a header for a new file in the current format, using the
+    /// [`KeySchedule::HkdfExpand`] key schedule.
+    pub fn new(cipher: &str, kdf: Kdf, salt: [u8; SALT_LEN]) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            cipher: Some(cipher.to_uppercase()),
+            kdf,
+            key_schedule: KeySchedule::HkdfExpand,
+            salt,
+            recovery: None,
+            gpg: None,
+            ssh: None,
+            challenge: None,
+            metadata: Metadata::default(),
+            context_bound: false,
+            signed: false,
+        }
+    }
+
+    /// Returns `true` if the header was synthesised for a file without one.
+    pub fn is_legacy(&self) -> bool {
+        self.version == LEGACY_VERSION
+    }
+
+    /// Returns `true` if the encoded header is authenticated as associated data.
+    pub fn is_authenticated(&self) -> bool {
+        self.version >= AUTHENTICATED_HEADER_VERSION
+    }
+
+    /// Returns the length of the encoded header in `data`, given the cipher output
+    /// [`decode()`] returned for it.
+    pub fn encoded_len(&self, data: &[u8], body: &[u8]) -> usize {
+        let trailer_len = if self.signed { SIGNATURE_TRAILER_LEN } else { 0 };
+        data.len() - body.len() - trailer_len
+    }
+}
+
+/// Errors that can occur while parsing or writing an encrypted file.
+#[derive(Debug, PartialEq, Eq)]
+pub enum FormatError {
+    /// The data ended before a complete header or body could be read.
+    Truncated,
+
+    /// The file was written by an unsupported format version: a newer one, or version 1,
+    /// whose header is not authenticated.
+    UnsupportedVersion(u8),
+
+    /// The key derivation function identifier is not known.
+    UnknownKdf(u8),
+
+    /// A header field is malformed or a required field is missing.
+    InvalidHeader(String),
+
+    /// The header to write is longer than the header length field can hold. Contains
+    /// the length of its fields in bytes.
+    HeaderTooLarge(usize),
+}
+
+impl std::fmt::Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FormatError::Truncated => write!(f, "Encrypted data is truncated"),
+            FormatError::UnsupportedVersion(v) if *v > FORMAT_VERSION => write!(f, "Unsupported file format version {} - upgrade envcrypt to read this file", v),
+            FormatError::UnsupportedVersion(v) => write!(f, "Unsupported file format version {} - its header is not authenticated", v),
+            FormatError::UnknownKdf(id) => write!(f, "Unknown key derivation function id {}", id),
+            FormatError::InvalidHeader(msg) => write!(f, "Invalid file header: {}", msg),
+            FormatError::HeaderTooLarge(len) => write!(f, "The file header would be {} bytes, more than the {} a file can hold - use fewer recipients or a shorter comment", len, MAX_HEADER_LEN),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+/// Serialises a header followed by the cipher output.
+///
+/// The header is written with its own [`Header::version`]; legacy headers are upgraded
+/// to [`FORMAT_VERSION`]. Use [`encode_header()`] to obtain the associated data for
+/// authenticated headers before encrypting.
+///
+/// # Example
+///
+/// ```
+/// use envcrypt::format::{decode, encode, Header, Kdf};
+///
+/// let header = Header::new("AES-256-GCM", Kdf::Pbkdf2Sha256 { iterations: 100_000 }, [7u8; 16]);
+/// let data = encode(&header, b"ciphertext")?;
+///
+/// let (parsed, body) = decode(&data)?;
+/// assert_eq!(parsed, header);
+/// assert_eq!(body, b"ciphertext");
+/// # Ok::<(), envcrypt::format::FormatError>(())
+/// ```
+pub fn encode(header: &Header, body: &[u8]) -> Result<Vec<u8>, FormatError> {
+    let mut output = encode_header(header)?;
+    output.extend_from_slice(body);
+    Ok(output)
+}
+
+/// Serialises just the header, as it appears at the start of the file.
+///
+/// For [authenticated](Header::is_authenticated) headers this is the associated data
+/// passed to the cipher.
+///
+/// # Errors
+///
+/// Returns [`FormatError::HeaderTooLarge`] if the fields are longer than
+/// [`MAX_HEADER_LEN`] bytes, as can happen with many GPG or SSH recipients.
+pub fn encode_header(header: &Header) -> Result<Vec<u8>, FormatError> {
+    let mut fields = Vec::new();
+
+    if let Some(cipher) = &header.cipher {
+        push_field(&mut fields, TAG_CIPHER, cipher.as_bytes());
+    }
+
+    let mut kdf = Vec::with_capacity(5);
+    match header.kdf {
+        Kdf::Pbkdf2Sha256 { iterations } => {
+            kdf.push(KDF_PBKDF2_SHA256);
+            kdf.extend_from_slice(&iterations.to_be_bytes());
+        }
+        Kdf::HkdfSha256 => kdf.push(KDF_HKDF_SHA256),
+    }
+    push_field(&mut fields, TAG_KDF, &kdf);
+    push_field(&mut fields, TAG_SALT, &header.salt);
+
+    let schedule = match header.key_schedule {
+        KeySchedule::Split => SCHEDULE_SPLIT,
+        KeySchedule::HkdfExpand => SCHEDULE_HKDF_EXPAND,
+    };
+    push_field(&mut fields, TAG_KEY_SCHEDULE, &[schedule]);
+
+    if let Some(recovery) = &header.recovery {
+        push_field(&mut fields, TAG_RECOVERY, recovery);
+    }
+    if let Some(gpg) = &header.gpg {
+        push_field(&mut fields, TAG_GPG, gpg);
+    }
+    if let Some(ssh) = &header.ssh {
+        push_field(&mut fields, TAG_SSH, ssh);
+    }
+    if let Some(challenge) = &header.challenge {
+        push_field(&mut fields, TAG_CHALLENGE, challenge);
+    }
+
+    if let Some(created) = header.metadata.created {
+        push_field(&mut fields, TAG_CREATED, &created.to_be_bytes());
+    }
+    if let Some(tool_version) = &header.metadata.tool_version {
+        push_field(&mut fields, TAG_TOOL_VERSION, tool_version.as_bytes());
+    }
+    if let Some(comment) = &header.metadata.comment {
+        push_field(&mut fields, TAG_COMMENT, comment.as_bytes());
+    }
+    if let Some(mode) = header.metadata.mode {
+        push_field(&mut fields, TAG_MODE, &mode.to_be_bytes());
+    }
+    if let Some(modified) = header.metadata.modified {
+        push_field(&mut fields, TAG_MODIFIED, &modified.to_be_bytes());
+    }
+    if header.context_bound {
+        push_field(&mut fields, TAG_CONTEXT_BOUND, &[]);
+    }
+    if header.signed {
+        push_field(&mut fields, TAG_SIGNED, &[]);
+    }
+
+    // Every field is shorter than all of them together, so their lengths fit as well
+    let fields_len = u16::try_from(fields.len()).map_err(|_| FormatError::HeaderTooLarge(fields.len()))?;
+    let version = if header.is_legacy() { FORMAT_VERSION } else { header.version };
+    let mut output = Vec::with_capacity(MAGIC.len() + 3 + fields.len());
+    output.extend_from_slice(MAGIC);
+    output.push(version);
+    output.extend_from_slice(&fields_len.to_be_bytes());
+    output.extend_from_slice(&fields);
+    Ok(output)
+}
+
+/// Builds the associated data passed to the cipher for an authenticated header.
+///
+/// This is the encoded header (see [`encode_header()`]) followed by the context string
+/// the file is bound to, if any. The header length field makes the boundary between
+/// the two unambiguous.
+pub fn associated_data(encoded_header: &[u8], context: Option<&str>) -> Vec<u8> {
+    let mut aad = encoded_header.to_vec();
+    if let Some(context) = context {
+        aad.extend_from_slice(context.as_bytes());
+    }
+    aad
+}
+
+/// Parses an encrypted file, returning its header and the cipher output.
+///
+/// Data without the [`MAGIC`] prefix is treated as a legacy file.
+///
+/// # Errors
+///
+/// Returns a [`FormatError`] if the data is truncated, uses an unsupported version,
+/// or contains a malformed header.
+pub fn decode(data: &[u8]) -> Result<(Header, &[u8]), FormatError> {
+    if !data.starts_with(MAGIC) {
+        return decode_legacy(data);
+    }
+
+    let rest = &data[MAGIC.len()..];
+    let (&version, rest) = rest.split_first().ok_or(FormatError::Truncated)?;
+    if version < AUTHENTICATED_HEADER_VERSION || version > FORMAT_VERSION {
+        return Err(FormatError::UnsupportedVersion(version));
+    }
+
+    let (len_bytes, rest) = split(rest, 2)?;
+    let header_len = u16::from_be_bytes([len_bytes[0], len_bytes[1]]) as usize;
+    let (mut fields, body) = split(rest, header_len)?;
+
+    let mut cipher = None;
+    let mut kdf = None;
+    let mut salt = None;
+    let mut key_schedule = KeySchedule::Split;
+    let mut recovery = None;
+    let mut gpg = None;
+    let mut ssh = None;
+    let mut challenge = None;
+    let mut metadata = Metadata::default();
+    let mut context_bound = false;
+    let mut signed = false;
+
+    while !fields.is_empty() {
+        let (tag_and_len, rest) = split(fields, 3)?;
+        let len = u16::from_be_bytes([tag_and_len[1], tag_and_len[2]]) as usize;
+        let (value, rest) = split(rest, len)?;
+        fields = rest;
+
+        match tag_and_len[0] {
+            TAG_CIPHER => {
+                let name = std::str::from_utf8(value)
+                    .map_err(|_| FormatError::InvalidHeader("cipher name is not valid UTF-8".to_string()))?;
+                cipher = Some(name.to_string());
+            }
+            TAG_KDF => kdf = Some(decode_kdf(value)?),
+            TAG_SALT => {
+                let bytes: [u8; SALT_LEN] = value.try_into()
+                    .map_err(|_| FormatError::InvalidHeader(format!("salt must be {} bytes", SALT_LEN)))?;
+                salt = Some(bytes);
+            }
+            TAG_KEY_SCHEDULE => {
+                key_schedule = match value {
+                    [SCHEDULE_SPLIT] => KeySchedule::Split,
+                    [SCHEDULE_HKDF_EXPAND] => KeySchedule::HkdfExpand,
+                    _ => return Err(FormatError::InvalidHeader("unknown key schedule".to_string())),
+                };
+            }
+            TAG_RECOVERY => recovery = Some(value.to_vec()),
+            TAG_GPG => gpg = Some(value.to_vec()),
+            TAG_SSH => ssh = Some(value.to_vec()),
+            TAG_CHALLENGE => {
+                let bytes: [u8; CHALLENGE_LEN] = value.try_into()
+                    .map_err(|_| FormatError::InvalidHeader(format!("challenge must be {} bytes", CHALLENGE_LEN)))?;
+                challenge = Some(bytes);
+            }
+            TAG_CREATED => {
+                let bytes: [u8; 8] = value.try_into()
+                    .map_err(|_| FormatError::InvalidHeader("creation time must be 8 bytes".to_string()))?;
+                metadata.created = Some(u64::from_be_bytes(bytes));
+            }
+            TAG_TOOL_VERSION => metadata.tool_version = Some(decode_text(value, "tool version")?),
+            TAG_COMMENT => metadata.comment = Some(decode_text(value, "comment")?),
+            TAG_CONTEXT_BOUND => context_bound = true,
+            TAG_SIGNED => signed = true,
+            TAG_MODE => {
+                let bytes: [u8; 4] = value.try_into()
+                    .map_err(|_| FormatError::InvalidHeader("file mode must be 4 bytes".to_string()))?;
+                metadata.mode = Some(u32::from_be_bytes(bytes));
+            }
+            TAG_MODIFIED => {
+                let bytes: [u8; 8] = value.try_into()
+                    .map_err(|_| FormatError::InvalidHeader("modification time must be 8 bytes".to_string()))?;
+                metadata.modified = Some(u64::from_be_bytes(bytes));
+            }
+            _ => {}
+        }
+    }
+
+    let header = Header {
+        version,
+        cipher,
+        kdf: kdf.ok_or_else(|| FormatError::InvalidHeader("missing key derivation field".to_string()))?,
+        key_schedule,
+        salt: salt.ok_or_else(|| FormatError::InvalidHeader("missing salt field".to_string()))?,
+        recovery,
+        gpg,
+        ssh,
+        challenge,
+        metadata,
+        context_bound,
+        signed,
+    };
+
+    // The signature trailer is not part of the cipher output
+    let body = if signed {
+        &body[..body.len().checked_sub(SIGNATURE_TRAILER_LEN).ok_or(FormatError::Truncated)?]
+    } else {
+        body
+    };
+    Ok((header, body))
+}
+
+fn decode_legacy(data: &[u8]) -> Result<(Header, &[u8]), FormatError> {
+    let (salt, body) = split(data, SALT_LEN)?;
+    let header = Header {
+        version: LEGACY_VERSION,
+        cipher: None,
+        kdf: Kdf::Pbkdf2Sha256 { iterations: DEFAULT_PBKDF2_ITERATIONS },
+        key_schedule: KeySchedule::Split,
+        salt: salt.try_into().map_err(|_| FormatError::Truncated)?,
+        recovery: None,
+        gpg: None,
+        ssh: None,
+        challenge: None,
+        metadata: Metadata::default(),
+        context_bound: false,
+        signed: false,
+    };
+    Ok((header, body))
+}
+
+fn decode_kdf(value: &[u8]) -> Result<Kdf, FormatError> {
+    let (&id, params) = value.split_first()
+        .ok_or_else(|| FormatError::InvalidHeader("empty key derivation field".to_string()))?;
+    match id {
+        KDF_PBKDF2_SHA256 => {
+            let iterations: [u8; 4] = params.try_into()
+                .map_err(|_| FormatError::InvalidHeader("invalid PBKDF2 parameters".to_string()))?;
+            let iterations = u32::from_be_bytes(iterations);
+            if iterations == 0 || iterations > MAX_PBKDF2_ITERATIONS {
+                return Err(FormatError::InvalidHeader(format!("PBKDF2 iteration count {} is out of range", iterations)));
+            }
+            Ok(Kdf::Pbkdf2Sha256 { iterations })
+        }
+        KDF_HKDF_SHA256 if params.is_empty() => Ok(Kdf::HkdfSha256),
+        KDF_HKDF_SHA256 => Err(FormatError::InvalidHeader("unexpected HKDF parameters".to_string())),
+        other => Err(FormatError::UnknownKdf(other)),
+    }
+}
+
+fn decode_text(value: &[u8], field: &str) -> Result<String, FormatError> {
+    std::str::from_utf8(value)
+        .map(str::to_string)
+        .map_err(|_| FormatError::InvalidHeader(format!("{} is not valid UTF-8", field)))
+}
+
+fn push_field(fields: &mut Vec<u8>, tag: u8, value: &[u8]) {
+    fields.push(tag);
+    fields.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    fields.extend_from_slice(value);
+}
+
+fn split(data: &[u8], at: usize) -> Result<(&[u8], &[u8]), FormatError> {
+    if data.len() < at {
+        return Err(FormatError::Truncated);
+    }
+    Ok(data.split_at(at))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_header() -> Header {
+        Header::new("aes-256-gcm", Kdf::Pbkdf2Sha256 { iterations: 250_000 }, [42u8; SALT_LEN])
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let header = sample_header();
+        let data = encode(&header, b"body").unwrap();
+        let (parsed, body) = decode(&data).unwrap();
+        assert_eq!(parsed, header);
+        assert_eq!(parsed.cipher.as_deref(), Some("AES-256-GCM"));
+        assert_eq!(body, b"body");
+    }
+
+    #[test]
+    fn test_header_too_large() {
+        let mut header = sample_header();
+        header.ssh = Some(vec![0u8; 40_000]);
+        header.gpg = Some(vec![0u8; 40_000]);
+        assert!(matches!(encode(&header, b"body"), Err(FormatError::HeaderTooLarge(len)) if len > MAX_HEADER_LEN));
+
+        header.gpg = Some(vec![0u8; 20_000]);
+        let (parsed, _) = decode(&encode(&header, b"body").unwrap()).unwrap();
+        assert_eq!(parsed.gpg.map(|gpg| gpg.len()), Some(20_000));
+    }
+
+    #[test]
+    fn test_roundtrip_raw_key_kdf() {
+        let header = Header::new("AES-256-CBC", Kdf::HkdfSha256, [1u8; SALT_LEN]);
+        let data = encode(&header, b"body").unwrap();
+        let (parsed, _) = decode(&data).unwrap();
+        assert_eq!(parsed.kdf, Kdf::HkdfSha256);
+    }
+
+    #[test]
+    fn test_roundtrip_recovery_field() {
+        let mut header = sample_header();
+        header.recovery = Some(vec![5u8; 112]);
+        let data = encode(&header, b"body").unwrap();
+        let (parsed, body) = decode(&data).unwrap();
+        assert_eq!(parsed.recovery, Some(vec![5u8; 112]));
+        assert_eq!(body, b"body");
+    }
+
+    #[test]
+    fn test_roundtrip_gpg_field() {
+        let mut header = sample_header();
+        header.gpg = Some(vec![7u8; 600]);
+        let data = encode(&header, b"body").unwrap();
+        let (parsed, _) = decode(&data).unwrap();
+        assert_eq!(parsed.gpg, Some(vec![7u8; 600]));
+        assert_eq!(parsed.recovery, None);
+    }
+
+    #[test]
+    fn test_roundtrip_ssh_field() {
+        let mut header = sample_header();
+        header.ssh = Some(vec![8u8; 300]);
+        let data = encode(&header, b"body").unwrap();
+        let (parsed, _) = decode(&data).unwrap();
+        assert_eq!(parsed.ssh, Some(vec![8u8; 300]));
+        assert_eq!(parsed.gpg, None);
+    }
+
+    #[test]
+    fn test_roundtrip_challenge_field() {
+        let mut header = sample_header();
+        header.challenge = Some([9u8; CHALLENGE_LEN]);
+        let data = encode(&header, b"body").unwrap();
+        let (parsed, _) = decode(&data).unwrap();
+        assert_eq!(parsed.challenge, Some([9u8; CHALLENGE_LEN]));
+    }
+
+    #[test]
+    fn test_roundtrip_metadata() {
+        let mut header = sample_header();
+        header.metadata = Metadata {
+            created: Some(1_700_000_000),
+            tool_version: Some("1.2.3".to_string()),
+            comment: Some("rotated after incident #42".to_string()),
+            mode: Some(0o600),
+            modified: Some(1_699_999_000),
+        };
+        let data = encode(&header, b"body").unwrap();
+        let (parsed, _) = decode(&data).unwrap();
+        assert_eq!(parsed.metadata, header.metadata);
+    }
+
+    #[test]
+    fn test_roundtrip_context_bound() {
+        let mut header = sample_header();
+        header.context_bound = true;
+        let (parsed, _) = decode(&encode(&header, b"body").unwrap()).unwrap();
+        assert!(parsed.context_bound);
+        assert!(!decode(&encode(&sample_header(), b"body").unwrap()).unwrap().0.context_bound);
+    }
+
+    #[test]
+    fn test_decode_strips_signature_trailer() {
+        let mut header = sample_header();
+        header.signed = true;
+        let mut data = encode(&header, b"body").unwrap();
+        data.extend_from_slice(&[0u8; SIGNATURE_TRAILER_LEN]);
+        let (parsed, body) = decode(&data).unwrap();
+        assert!(parsed.signed);
+        assert_eq!(body, b"body");
+
+        let truncated = encode(&header, b"body").unwrap();
+        assert_eq!(decode(&truncated).unwrap_err(), FormatError::Truncated);
+    }
+
+    #[test]
+    fn test_version_1_header_is_rejected() {
+        let mut header = sample_header();
+        header.version = 1;
+        let data = encode(&header, b"body").unwrap();
+        assert_eq!(data[MAGIC.len()], 1);
+        assert_eq!(decode(&data), Err(FormatError::UnsupportedVersion(1)));
+        assert!(sample_header().is_authenticated());
+    }
+
+    #[test]
+    fn test_encode_header_is_file_prefix() {
+        let header = sample_header();
+        let data = encode(&header, b"body").unwrap();
+        assert_eq!(data[..data.len() - 4], encode_header(&header).unwrap()[..]);
+    }
+
+    #[test]
+    fn test_legacy_data_without_magic() {
+        let mut data = vec![9u8; SALT_LEN];
+        data.extend_from_slice(b"legacy body");
+        let (header, body) = decode(&data).unwrap();
+        assert!(header.is_legacy());
+        assert_eq!(header.cipher, None);
+        assert_eq!(header.kdf, Kdf::Pbkdf2Sha256 { iterations: DEFAULT_PBKDF2_ITERATIONS });
+        assert_eq!(header.key_schedule, KeySchedule::Split);
+        assert_eq!(header.salt, [9u8; SALT_LEN]);
+        assert_eq!(body, b"legacy body");
+    }
+
+    #[test]
+    fn test_missing_key_schedule_defaults_to_split() {
+        let header = sample_header();
+        let data = encode(&header, b"body").unwrap();
+        // Drop the trailing key schedule field (tag + u16 length + 1 byte value)
+        let header_len = u16::from_be_bytes([data[5], data[6]]) as usize - 4;
+        let mut patched = data[..5].to_vec();
+        patched.extend_from_slice(&(header_len as u16).to_be_bytes());
+        patched.extend_from_slice(&data[7..7 + header_len]);
+        patched.extend_from_slice(b"body");
+        let (parsed, _) = decode(&patched).unwrap();
+        assert_eq!(parsed.key_schedule, KeySchedule::Split);
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let mut data = encode(&sample_header(), b"body").unwrap();
+        data[MAGIC.len()] = 99;
+        assert_eq!(decode(&data), Err(FormatError::UnsupportedVersion(99)));
+    }
+
+    #[test]
+    fn test_iteration_count_out_of_range() {
+        for iterations in [0, MAX_PBKDF2_ITERATIONS + 1, u32::MAX] {
+            let header = Header::new("AES-256-GCM", Kdf::Pbkdf2Sha256 { iterations }, [7u8; 16]);
+            assert!(matches!(decode(&encode(&header, b"body").unwrap()), Err(FormatError::InvalidHeader(_))));
+        }
+    }
+
+    #[test]
+    fn test_truncated_header() {
+        let data = encode(&sample_header(), b"").unwrap();
+        assert_eq!(decode(&data[..data.len() - 1]), Err(FormatError::Truncated));
+    }
+
+    #[test]
+    fn test_unknown_fields_are_skipped() {
+        let header = sample_header();
+        let mut fields = Vec::new();
+        push_field(&mut fields, 0x7f, b"from the future");
+        let data = encode(&header, b"body").unwrap();
+        // Splice an unknown field in front of the known ones and fix up the length.
+        let header_len = u16::from_be_bytes([data[5], data[6]]) as usize + fields.len();
+        let mut patched = data[..5].to_vec();
+        patched.extend_from_slice(&(header_len as u16).to_be_bytes());
+        patched.extend_from_slice(&fields);
+        patched.extend_from_slice(&data[7..]);
+        let (parsed, body) = decode(&patched).unwrap();
+        assert_eq!(parsed, header);
+        assert_eq!(body, b"body");
+    }
+}
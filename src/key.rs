@@ -5,8 +5,18 @@
 //!
 //! # Key Derivation
 //!
-//! Keys are derived using PBKDF2-HMAC-SHA256 with 100,000 iterations, which provides
-//! protection against brute-force attacks while maintaining reasonable performance.
+//! Keys are derived using PBKDF2-HMAC-SHA256. The default of 100,000 iterations provides
+//! protection against brute-force attacks while maintaining reasonable performance; the
+//! iteration count can be tuned per file and is recorded in the file header.
+//!
+//! The 32-byte PBKDF2 output is then expanded into independent encryption and MAC keys
+//! with HKDF-Expand using the info labels "enc" and "mac" (see [`KeySchedule`]). Files
+//! written before this scheme existed sliced a single 64-byte PBKDF2 output in half;
+//! [`KeySchedule::Split`] keeps them decryptable.
+//!
+//! Keys generated by `envcrypt` itself are already 256 bits of randomness, so stretching
+//! them with PBKDF2 adds no security. For such "raw" keys, [`derive_keys_from_raw_key()`]
+//! splits the key with HKDF-SHA256 instead, which is effectively instant.
 //!
 //! # Security Considerations
 //!
@@ -15,16 +25,37 @@
 //! - Derived keys are automatically zeroized when dropped
 //! - Never reuse salts across different encryptions
 
+use std::cell::RefCell;
+use std::time::Duration;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::time::Instant;
+
+use base64::Engine;
+use hkdf::Hkdf;
 use pbkdf2::pbkdf2_hmac;
-use sha2::Sha256;
-use zeroize::Zeroize;
+use sha2::{Digest, Sha256};
+use zeroize::{Zeroize, Zeroizing};
 
-/// Number of PBKDF2 iterations for key derivation.
+/// Default number of PBKDF2 iterations for key derivation.
 ///
 /// This value (100,000) provides a good balance between security and performance.
 /// It's high enough to slow down brute-force attacks while remaining practical
-/// for normal use cases.
-const PBKDF2_ITERATIONS: u32 = 100_000;
+/// for normal use cases. Files written before the iteration count was recorded in
+/// the header were always derived with this value.
+pub const DEFAULT_PBKDF2_ITERATIONS: u32 = 100_000;
+
+/// Lowest PBKDF2 iteration count accepted for new encryptions.
+pub const MIN_PBKDF2_ITERATIONS: u32 = 1_000;
+
+/// Highest PBKDF2 iteration count accepted, for new encryptions and in file headers.
+///
+/// Deriving keys with this many iterations takes about a minute. The iteration count of
+/// a file comes from its header, so without a limit a crafted file could keep envcrypt
+/// busy for days before decryption fails.
+pub const MAX_PBKDF2_ITERATIONS: u32 = 100_000_000;
+
+/// Default time budget used by [`calibrate_pbkdf2_iterations()`].
+pub const DEFAULT_CALIBRATION_TARGET: Duration = Duration::from_millis(250);
 
 /// Length of the encryption key in bytes (256 bits for AES-256).
 const ENCRYPTION_KEY_LEN: usize = 32;
@@ -32,20 +63,57 @@ const ENCRYPTION_KEY_LEN: usize = 32;
 /// Length of the MAC key in bytes (256 bits for HMAC-SHA256).
 const MAC_KEY_LEN: usize = 32;
 
-/// Total length of the derived key material (encryption key + MAC key).
+/// Total length of the derived key material (encryption key + MAC key) in the legacy split schedule.
 const DERIVED_KEY_LEN: usize = ENCRYPTION_KEY_LEN + MAC_KEY_LEN;
 
+/// Length of the PBKDF2 output used as HKDF pseudorandom key (one SHA-256 block).
+const PRK_LEN: usize = 32;
+
+/// Length of a raw key in bytes (256 bits).
+pub const RAW_KEY_LEN: usize = 32;
+
+/// HKDF info label for the encryption key.
+const HKDF_INFO_ENCRYPTION: &[u8] = b"enc";
+
+/// HKDF info label for the MAC key.
+const HKDF_INFO_MAC: &[u8] = b"mac";
+
+/// How the encryption and MAC keys are separated from the password-derived key material.
+///
+/// The schedule is recorded in the file header so that files written with the legacy
+/// scheme remain decryptable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeySchedule {
+    /// Legacy scheme: a single 64-byte PBKDF2 output is sliced into the two keys.
+    Split,
+    /// A 32-byte PBKDF2 output is used as an HKDF pseudorandom key and expanded into
+    /// the two keys with the distinct info labels "enc" and "mac".
+    HkdfExpand,
+}
+
+impl std::fmt::Display for KeySchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeySchedule::Split => write!(f, "split (legacy)"),
+            KeySchedule::HkdfExpand => write!(f, "HKDF-Expand"),
+        }
+    }
+}
+
 /// Derives encryption and MAC keys from a user-provided key string using PBKDF2.
 ///
 /// This function takes a user-provided password/key string and derives two separate
-/// 256-bit keys: one for encryption and one for MAC computation. The derivation uses
-/// PBKDF2-HMAC-SHA256 with 100,000 iterations to protect against brute-force attacks.
+/// 256-bit keys: one for encryption and one for MAC computation. The password is
+/// stretched with PBKDF2-HMAC-SHA256 at [`DEFAULT_PBKDF2_ITERATIONS`] to protect against
+/// brute-force attacks, and the two keys are separated with HKDF-Expand
+/// ([`KeySchedule::HkdfExpand`]). Use [`derive_keys_with_iterations()`] when the iteration
+/// count is configurable.
 ///
 /// # Arguments
 ///
 /// * `key_input` - The user-provided password or key string
 /// * `salt` - A 16-byte random salt. Must be unique for each encryption operation.
-///            The salt should be stored with the encrypted data for decryption.
+///   The salt should be stored with the encrypted data for decryption.
 ///
 /// # Returns
 ///
@@ -72,25 +140,239 @@ const DERIVED_KEY_LEN: usize = ENCRYPTION_KEY_LEN + MAC_KEY_LEN;
 /// // Use encryption_key and mac_key for encryption...
 /// ```
 pub fn derive_keys(key_input: &str, salt: &[u8; 16]) -> (Vec<u8>, Vec<u8>) {
-    let mut derived_key = [0u8; DERIVED_KEY_LEN];
-    
-    pbkdf2_hmac::<Sha256>(
-        key_input.as_bytes(),
-        salt,
-        PBKDF2_ITERATIONS,
-        &mut derived_key,
-    );
-    
-    // Split the derived key into encryption key and MAC key
-    let encryption_key = derived_key[..ENCRYPTION_KEY_LEN].to_vec();
-    let mac_key = derived_key[ENCRYPTION_KEY_LEN..].to_vec();
-    
-    // Zeroize the derived key array
-    derived_key.zeroize();
-    
+    derive_keys_with_iterations(key_input, salt, DEFAULT_PBKDF2_ITERATIONS)
+}
+
+/// Derives encryption and MAC keys using an explicit PBKDF2 iteration count.
+///
+/// Behaves exactly like [`derive_keys()`], but lets the caller choose the number of
+/// PBKDF2 iterations. The same count must be used for encryption and decryption, which
+/// is why it is stored in the encrypted file header.
+///
+/// # Example
+///
+/// ```no_run
+/// use envcrypt::key::{derive_keys_with_iterations, generate_salt};
+///
+/// let salt = generate_salt();
+/// let (enc_key, mac_key) = derive_keys_with_iterations("password", &salt, 600_000);
+/// ```
+pub fn derive_keys_with_iterations(key_input: &str, salt: &[u8; 16], iterations: u32) -> (Vec<u8>, Vec<u8>) {
+    derive_keys_with_schedule(key_input, salt, iterations, KeySchedule::HkdfExpand)
+}
+
+/// Derives encryption and MAC keys with PBKDF2 and an explicit [`KeySchedule`].
+///
+/// [`KeySchedule::Split`] reproduces the derivation used by files written before the
+/// schedule was recorded in the header; new files should use [`KeySchedule::HkdfExpand`].
+///
+/// # Example
+///
+/// ```no_run
+/// use envcrypt::key::{derive_keys_with_schedule, generate_salt, KeySchedule};
+///
+/// let salt = generate_salt();
+/// let (enc_key, mac_key) = derive_keys_with_schedule("password", &salt, 100_000, KeySchedule::Split);
+/// ```
+pub fn derive_keys_with_schedule(
+    key_input: &str,
+    salt: &[u8; 16],
+    iterations: u32,
+    schedule: KeySchedule,
+) -> (Vec<u8>, Vec<u8>) {
+    match schedule {
+        KeySchedule::Split => {
+            let mut derived_key = [0u8; DERIVED_KEY_LEN];
+
+            pbkdf2_hmac::<Sha256>(
+                key_input.as_bytes(),
+                salt,
+                iterations,
+                &mut derived_key,
+            );
+
+            // Split the derived key into encryption key and MAC key
+            let encryption_key = derived_key[..ENCRYPTION_KEY_LEN].to_vec();
+            let mac_key = derived_key[ENCRYPTION_KEY_LEN..].to_vec();
+
+            // Zeroize the derived key array
+            derived_key.zeroize();
+
+            (encryption_key, mac_key)
+        }
+        KeySchedule::HkdfExpand => {
+            let mut prk = [0u8; PRK_LEN];
+
+            pbkdf2_hmac::<Sha256>(
+                key_input.as_bytes(),
+                salt,
+                iterations,
+                &mut prk,
+            );
+
+            // A 32-byte PRK always satisfies HKDF-SHA256's minimum length
+            let hkdf = Hkdf::<Sha256>::from_prk(&prk)
+                .expect("32 bytes is a valid HKDF-SHA256 PRK length");
+            prk.zeroize();
+
+            expand_keys(&hkdf)
+        }
+    }
+}
+
+/// Expands an HKDF instance into the encryption and MAC keys.
+fn expand_keys(hkdf: &Hkdf<Sha256>) -> (Vec<u8>, Vec<u8>) {
+    let mut encryption_key = vec![0u8; ENCRYPTION_KEY_LEN];
+    let mut mac_key = vec![0u8; MAC_KEY_LEN];
+    // Expanding 32 bytes can never exceed the HKDF output limit (255 * 32 bytes)
+    hkdf.expand(HKDF_INFO_ENCRYPTION, &mut encryption_key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    hkdf.expand(HKDF_INFO_MAC, &mut mac_key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+
     (encryption_key, mac_key)
 }
 
+/// Keys derived from passphrases, cached so that deriving the same keys again is free.
+///
+/// Decrypting a file more than once with the same key, for example to verify it and then
+/// to read it, or decrypting several copies of it while merging or rotating, would pay the
+/// full PBKDF2 cost each time. [`KeyMaterial::derive_keys()`] remembers the keys it
+/// derived by a SHA-256 hash of the passphrase, the salt, the iteration count and the
+/// key schedule, and returns them again for the same inputs. New encryptions use a fresh
+/// salt and are never served from the cache.
+///
+/// The passphrase hashes and derived keys are zeroized when the `KeyMaterial` is
+/// dropped, so it should live only as long as the operation that needs it.
+///
+/// # Example
+///
+/// ```
+/// use envcrypt::key::{derive_keys_with_schedule, generate_salt, KeyMaterial, KeySchedule};
+///
+/// let cache = KeyMaterial::new();
+/// let salt = generate_salt();
+/// let keys = cache.derive_keys("password", &salt, 1_000, KeySchedule::HkdfExpand);
+/// assert_eq!(keys, derive_keys_with_schedule("password", &salt, 1_000, KeySchedule::HkdfExpand));
+/// // The second derivation is served from the cache
+/// assert_eq!(cache.derive_keys("password", &salt, 1_000, KeySchedule::HkdfExpand), keys);
+/// assert_eq!(cache.len(), 1);
+/// assert_ne!(cache.derive_keys("other", &salt, 1_000, KeySchedule::HkdfExpand), keys);
+/// assert_eq!(cache.len(), 2);
+/// ```
+#[derive(Default)]
+pub struct KeyMaterial {
+    entries: RefCell<Vec<CachedKeys>>,
+}
+
+/// Keys derived by [`KeyMaterial`] and the inputs they were derived from.
+struct CachedKeys {
+    passphrase_hash: Zeroizing<[u8; 32]>,
+    salt: [u8; 16],
+    iterations: u32,
+    schedule: KeySchedule,
+    encryption_key: Zeroizing<Vec<u8>>,
+    mac_key: Zeroizing<Vec<u8>>,
+}
+
+impl KeyMaterial {
+    /// Creates an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Derives encryption and MAC keys like [`derive_keys_with_schedule()`], returning
+    /// the cached keys if they were derived from the same inputs before.
+    pub fn derive_keys(
+        &self,
+        key_input: &str,
+        salt: &[u8; 16],
+        iterations: u32,
+        schedule: KeySchedule,
+    ) -> (Vec<u8>, Vec<u8>) {
+        let passphrase_hash = Zeroizing::new(<[u8; 32]>::from(Sha256::digest(key_input.as_bytes())));
+        let mut entries = self.entries.borrow_mut();
+        let cached = entries.iter().find(|entry| {
+            *entry.passphrase_hash == *passphrase_hash
+                && entry.salt == *salt
+                && entry.iterations == iterations
+                && entry.schedule == schedule
+        });
+        if let Some(entry) = cached {
+            return (entry.encryption_key.to_vec(), entry.mac_key.to_vec());
+        }
+
+        let (encryption_key, mac_key) = derive_keys_with_schedule(key_input, salt, iterations, schedule);
+        entries.push(CachedKeys {
+            passphrase_hash,
+            salt: *salt,
+            iterations,
+            schedule,
+            encryption_key: Zeroizing::new(encryption_key.clone()),
+            mac_key: Zeroizing::new(mac_key.clone()),
+        });
+        (encryption_key, mac_key)
+    }
+
+    /// Returns the number of cached key pairs.
+    pub fn len(&self) -> usize {
+        self.entries.borrow().len()
+    }
+
+    /// Returns `true` if no keys have been derived yet.
+    pub fn is_empty(&self) -> bool {
+        self.entries.borrow().is_empty()
+    }
+}
+
+/// Decodes a raw key string into its 32 key bytes.
+///
+/// A raw key is the base64 encoding of exactly [`RAW_KEY_LEN`] random bytes, as produced
+/// by `envcrypt` when it generates a key. The "base64:" prefix must already be stripped.
+///
+/// # Returns
+///
+/// Returns `Some(bytes)` if the string is valid base64 for exactly 32 bytes, otherwise `None`.
+///
+/// # Example
+///
+/// ```
+/// use envcrypt::key::decode_raw_key;
+///
+/// assert!(decode_raw_key("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=").is_some());
+/// assert!(decode_raw_key("my-secret-password").is_none());
+/// ```
+pub fn decode_raw_key(key_input: &str) -> Option<[u8; RAW_KEY_LEN]> {
+    let mut decoded = base64::engine::general_purpose::STANDARD.decode(key_input).ok()?;
+    let key = decoded.as_slice().try_into().ok();
+    decoded.zeroize();
+    key
+}
+
+/// Derives encryption and MAC keys from a raw 32-byte key using HKDF-SHA256.
+///
+/// Unlike [`derive_keys()`], no password stretching is performed: the raw key must
+/// already be uniformly random (see [`decode_raw_key()`]). HKDF-Extract is keyed with the
+/// salt and HKDF-Expand separates the two keys with distinct info labels.
+///
+/// # Returns
+///
+/// Returns a tuple `(encryption_key, mac_key)` of 32-byte keys.
+///
+/// # Example
+///
+/// ```
+/// use envcrypt::key::{derive_keys_from_raw_key, generate_salt};
+///
+/// let salt = generate_salt();
+/// let (enc_key, mac_key) = derive_keys_from_raw_key(&[7u8; 32], &salt);
+/// assert_ne!(enc_key, mac_key);
+/// ```
+pub fn derive_keys_from_raw_key(raw_key: &[u8; RAW_KEY_LEN], salt: &[u8; 16]) -> (Vec<u8>, Vec<u8>) {
+    let hkdf = Hkdf::<Sha256>::new(Some(salt), raw_key);
+    expand_keys(&hkdf)
+}
+
 /// Generates a cryptographically secure random 16-byte salt for key derivation.
 ///
 /// This function uses the system's secure random number generator to create
@@ -122,3 +404,56 @@ pub fn generate_salt() -> [u8; 16] {
     rand::thread_rng().fill_bytes(&mut salt);
     salt
 }
+
+/// Measures how many PBKDF2 iterations fit in the given time budget on this machine.
+///
+/// A probe derivation is timed (doubling the probe until the measurement is long enough
+/// to be meaningful) and the result is scaled to `target`. The returned count is rounded
+/// down to a multiple of 1,000 and kept between [`MIN_PBKDF2_ITERATIONS`] and
+/// [`MAX_PBKDF2_ITERATIONS`], so it can always be written to a file header.
+///
+/// Not available on `wasm32-unknown-unknown`, where the standard library has no clock.
+///
+/// # Example
+///
+/// ```no_run
+/// use envcrypt::key::{calibrate_pbkdf2_iterations, DEFAULT_CALIBRATION_TARGET};
+///
+/// let iterations = calibrate_pbkdf2_iterations(DEFAULT_CALIBRATION_TARGET);
+/// println!("Use {} iterations", iterations);
+/// ```
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub fn calibrate_pbkdf2_iterations(target: Duration) -> u32 {
+    const MIN_SAMPLE: Duration = Duration::from_millis(20);
+    const MAX_PROBE: u32 = 1 << 24;
+
+    let salt = [0u8; 16];
+    let mut output = [0u8; PRK_LEN];
+    let mut probe: u32 = 10_000;
+
+    let elapsed = loop {
+        let start = Instant::now();
+        pbkdf2_hmac::<Sha256>(b"envcrypt-calibration", &salt, probe, &mut output);
+        let elapsed = start.elapsed();
+        if elapsed >= MIN_SAMPLE || probe >= MAX_PROBE {
+            break elapsed;
+        }
+        probe *= 2;
+    };
+
+    let per_iteration = elapsed.as_secs_f64() / f64::from(probe);
+    let estimate = (target.as_secs_f64() / per_iteration).min(f64::from(u32::MAX)) as u32;
+    (estimate / 1_000 * 1_000).clamp(MIN_PBKDF2_ITERATIONS, MAX_PBKDF2_ITERATIONS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn test_calibration_stays_in_range() {
+        assert_eq!(calibrate_pbkdf2_iterations(Duration::ZERO), MIN_PBKDF2_ITERATIONS);
+        assert_eq!(calibrate_pbkdf2_iterations(Duration::from_secs(365 * 24 * 3600)), MAX_PBKDF2_ITERATIONS);
+    }
+}
@@ -1,3 +1,23 @@
+#[cfg(feature = "cipher")]
 pub mod cipher;
+#[cfg(feature = "cipher")]
 pub mod key;
+#[cfg(feature = "cipher")]
+pub mod format;
+#[cfg(feature = "cipher")]
+pub mod armor;
+#[cfg(feature = "cipher")]
+pub mod envelope;
+#[cfg(feature = "cipher")]
+pub mod shamir;
+#[cfg(feature = "cipher")]
+pub mod recovery;
+#[cfg(feature = "cipher")]
+pub mod signature;
+pub mod memlock;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "tokio")]
+pub mod nonblocking;
+#[cfg(any(feature = "encrypt", feature = "decrypt"))]
 pub mod cli;
@@ -2,7 +2,7 @@ use envcrypt::cli;
 
 fn main() {
     if let Err(e) = cli::run(std::env::args()) {
-        eprintln!("Error: {}", e);
+        eprintln!("{}", cli::format_error(&e));
         std::process::exit(1);
     }
 }
@@ -0,0 +1,136 @@
+//! Locking memory that holds secrets so it is not swapped to disk.
+//!
+//! [`lock()`] pins the pages spanning a buffer in RAM with `mlock(2)` until the returned
+//! [`MemoryLock`] is dropped. Locking is only available on Unix platforms with the
+//! `mlock` feature enabled; elsewhere [`lock()`] returns [`MlockError::Unsupported`] and
+//! callers are expected to carry on without it.
+//!
+//! Locks apply to whole pages and do not nest: dropping one lock unlocks every page it
+//! covered, even if another lock covers part of the same page. Drop a lock before the
+//! buffer it covers is freed.
+//!
+//! # Example
+//!
+//! ```
+//! use envcrypt::memlock;
+//!
+//! let key = vec![0u8; 32];
+//! match memlock::lock(&key) {
+//!     Ok(_guard) => { /* key stays in RAM while _guard lives */ }
+//!     Err(e) => eprintln!("Running without memory locking: {}", e),
+//! }
+//! ```
+
+/// Errors that can occur while locking memory.
+#[derive(Debug, PartialEq, Eq)]
+pub enum MlockError {
+    /// Memory locking is not available on this platform or build.
+    Unsupported,
+
+    /// The operating system refused the lock, typically because of `RLIMIT_MEMLOCK`.
+    Failed(String),
+}
+
+impl std::fmt::Display for MlockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MlockError::Unsupported => write!(f, "memory locking is not supported on this platform"),
+            MlockError::Failed(msg) => write!(f, "mlock failed: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for MlockError {}
+
+/// Guard that keeps a memory range locked until it is dropped.
+#[derive(Debug)]
+pub struct MemoryLock {
+    addr: usize,
+    len: usize,
+}
+
+/// Locks the pages spanning `data` into RAM.
+///
+/// # Errors
+///
+/// Returns [`MlockError::Unsupported`] if locking is unavailable and
+/// [`MlockError::Failed`] if the operating system refuses the lock.
+pub fn lock(data: &[u8]) -> Result<MemoryLock, MlockError> {
+    if data.is_empty() {
+        return Ok(MemoryLock { addr: 0, len: 0 });
+    }
+    sys::lock(data.as_ptr() as usize, data.len())
+}
+
+impl Drop for MemoryLock {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            sys::unlock(self.addr, self.len);
+        }
+    }
+}
+
+#[cfg(all(unix, feature = "mlock"))]
+mod sys {
+    use super::{MemoryLock, MlockError};
+
+    /// Expands `[addr, addr + len)` to whole pages.
+    fn page_range(addr: usize, len: usize) -> (usize, usize) {
+        // SAFETY: sysconf has no preconditions
+        let page = match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+            size if size > 0 => size as usize,
+            _ => 4096,
+        };
+        let start = addr & !(page - 1);
+        let end = (addr + len).div_ceil(page) * page;
+        (start, end - start)
+    }
+
+    pub fn lock(addr: usize, len: usize) -> Result<MemoryLock, MlockError> {
+        let (start, len) = page_range(addr, len);
+        // SAFETY: mlock only changes page residency and does not access the memory
+        if unsafe { libc::mlock(start as *const libc::c_void, len) } == 0 {
+            Ok(MemoryLock { addr: start, len })
+        } else {
+            Err(MlockError::Failed(std::io::Error::last_os_error().to_string()))
+        }
+    }
+
+    pub fn unlock(addr: usize, len: usize) {
+        // SAFETY: munlock only changes page residency and does not access the memory
+        unsafe {
+            libc::munlock(addr as *const libc::c_void, len);
+        }
+    }
+}
+
+#[cfg(not(all(unix, feature = "mlock")))]
+mod sys {
+    use super::{MemoryLock, MlockError};
+
+    pub fn lock(_addr: usize, _len: usize) -> Result<MemoryLock, MlockError> {
+        Err(MlockError::Unsupported)
+    }
+
+    pub fn unlock(_addr: usize, _len: usize) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_buffer_needs_no_lock() {
+        assert!(lock(&[]).is_ok());
+    }
+
+    #[cfg(all(unix, feature = "mlock"))]
+    #[test]
+    fn test_lock_small_buffer() {
+        // A single page is within even the most restrictive default RLIMIT_MEMLOCK
+        let key = vec![7u8; 64];
+        let guard = lock(&key).unwrap();
+        assert!(guard.len >= 64);
+        drop(guard);
+    }
+}
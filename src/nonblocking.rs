@@ -0,0 +1,140 @@
+//! Async encryption and decryption for tokio applications (`tokio` feature).
+//!
+//! Deriving the keys of a passphrase takes 100,000 PBKDF2 iterations by default, long
+//! enough to stall every other task on a runtime thread. These functions read and write
+//! files with [`tokio::fs`] and run [`envelope::encrypt()`] and [`envelope::decrypt()`]
+//! with [`tokio::task::spawn_blocking()`], so web services embedding envcrypt stay
+//! responsive. They never prompt: the key must be given.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use zeroize::Zeroizing;
+
+use crate::armor::FileFormat;
+use crate::cli::parse_dotenv;
+use crate::envelope;
+use crate::format::Kdf;
+
+/// Encrypts the file at `input_path` and writes the encrypted file to `output_path`,
+/// replacing it if it exists.
+///
+/// The arguments are as for [`envelope::encrypt()`]: the file keys are derived from `key`
+/// with `kdf`, and the file is bound to `context` if one is given.
+///
+/// # Errors
+///
+/// Returns an error string if a file cannot be read or written, or encryption fails (for
+/// an unknown cipher, or a raw key KDF with a key that is not a raw key).
+///
+/// # Example
+///
+/// ```no_run
+/// use envcrypt::armor::FileFormat;
+/// use envcrypt::format::Kdf;
+/// use envcrypt::nonblocking::encrypt_file_async;
+///
+/// # async fn example() -> Result<(), String> {
+/// let kdf = Kdf::Pbkdf2Sha256 { iterations: 100_000 };
+/// encrypt_file_async(".env", ".env.encrypted", "my-key", "AES-256-GCM", kdf, None, FileFormat::Armor).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn encrypt_file_async(
+    input_path: impl AsRef<Path>,
+    output_path: impl AsRef<Path>,
+    key: &str,
+    cipher_name: &str,
+    kdf: Kdf,
+    context: Option<&str>,
+    file_format: FileFormat,
+) -> Result<(), String> {
+    let (input_path, output_path) = (input_path.as_ref(), output_path.as_ref());
+    let plaintext = Zeroizing::new(tokio::fs::read(input_path).await
+        .map_err(|e| format!("Error reading {}: {}", input_path.display(), e))?);
+
+    let key = Zeroizing::new(key.to_string());
+    let cipher_name = cipher_name.to_string();
+    let context = context.map(str::to_string);
+    let contents = tokio::task::spawn_blocking(move || {
+        envelope::encrypt(&plaintext, &key, &cipher_name, kdf, context.as_deref(), file_format)
+    })
+    .await
+    .map_err(|e| format!("Error encrypting {}: {}", input_path.display(), e))?
+    .map_err(|e| format!("Error encrypting {}: {}", input_path.display(), e))?;
+
+    tokio::fs::write(output_path, contents).await
+        .map_err(|e| format!("Error writing {}: {}", output_path.display(), e))
+}
+
+/// Decrypts the encrypted file at `path` and parses the plaintext as a `.env` file,
+/// returning its variables by name.
+///
+/// `context` is the context string the file was bound to when it was encrypted, if any.
+/// References to other variables are kept as they are (see [`parse_dotenv()`]); if a
+/// variable is assigned more than once, the last value wins. The values are zeroized when
+/// dropped.
+///
+/// # Errors
+///
+/// Returns an error string if the file cannot be read, decryption fails (a wrong key or
+/// context string, or a modified file), or the plaintext is not a valid `.env` file.
+///
+/// # Example
+///
+/// ```no_run
+/// use envcrypt::nonblocking::decrypt_to_map_async;
+///
+/// # async fn example() -> Result<(), String> {
+/// let variables = decrypt_to_map_async(".env.encrypted", "my-key", None).await?;
+/// let database_url = variables.get("DATABASE_URL");
+/// # Ok(())
+/// # }
+/// ```
+pub async fn decrypt_to_map_async(
+    path: impl AsRef<Path>,
+    key: &str,
+    context: Option<&str>,
+) -> Result<BTreeMap<String, Zeroizing<String>>, String> {
+    let path = path.as_ref();
+    let contents = tokio::fs::read(path).await
+        .map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+
+    let key = Zeroizing::new(key.to_string());
+    let context = context.map(str::to_string);
+    let plaintext = tokio::task::spawn_blocking(move || envelope::decrypt(&contents, &key, context.as_deref()))
+        .await
+        .map_err(|e| format!("Error decrypting {}: {}", path.display(), e))?
+        .map_err(|e| format!("Error decrypting {}: {}", path.display(), e))?;
+
+    let text = std::str::from_utf8(&plaintext)
+        .map_err(|_| format!("{} does not contain valid UTF-8 text", path.display()))?;
+    let variables = parse_dotenv(text).map_err(|e| format!("Error parsing {}: {}", path.display(), e))?;
+    Ok(variables.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(future)
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let (input, output) = (dir.path().join(".env"), dir.path().join(".env.encrypted"));
+        std::fs::write(&input, "APP_KEY=test123\nDEBUG=false\nDEBUG=true\n").unwrap();
+
+        let kdf = Kdf::Pbkdf2Sha256 { iterations: 1_000 };
+        block_on(encrypt_file_async(&input, &output, "secret", "AES-256-GCM", kdf, Some("production"), FileFormat::Armor)).unwrap();
+        let variables = block_on(decrypt_to_map_async(&output, "secret", Some("production"))).unwrap();
+        assert_eq!(variables.len(), 2);
+        assert_eq!(variables["APP_KEY"].as_str(), "test123");
+        assert_eq!(variables["DEBUG"].as_str(), "true");
+
+        let error = block_on(decrypt_to_map_async(&output, "wrong", Some("production"))).unwrap_err();
+        assert!(error.starts_with("Error decrypting"), "{}", error);
+    }
+}
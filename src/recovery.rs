@@ -0,0 +1,196 @@
+//! Key escrow for an organisation-wide recovery recipient.
+//!
+//! When a recovery public key is configured, every encryption additionally wraps the
+//! file keys (the derived encryption and MAC keys) for that recipient and stores the
+//! result in the file header. Whoever holds the matching recovery private key can then
+//! decrypt the file even if the personal key used to encrypt it is lost.
+//!
+//! Wrapping uses X25519 with a fresh ephemeral key per file, HKDF-SHA256 to derive a
+//! single-use wrapping key, and ChaCha20-Poly1305 to encrypt the file keys. Because the
+//! wrapping key is never reused, a fixed all-zero nonce is safe.
+//!
+//! # Wrapped Key Layout
+//!
+//! ```text
+//! [Ephemeral Public Key (32 bytes)][Encrypted File Keys (64 bytes)][Tag (16 bytes)]
+//! ```
+//!
+//! # Example
+//!
+//! ```
+//! use envcrypt::recovery::{generate_recovery_keypair, unwrap_keys, wrap_keys};
+//!
+//! let (secret, public) = generate_recovery_keypair();
+//! let wrapped = wrap_keys(&public, &[1u8; 32], &[2u8; 32]);
+//!
+//! let (encryption_key, mac_key) = unwrap_keys(&secret, &wrapped)?;
+//! assert_eq!(encryption_key, vec![1u8; 32]);
+//! assert_eq!(mac_key, vec![2u8; 32]);
+//! # Ok::<(), envcrypt::recovery::RecoveryError>(())
+//! ```
+
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use rand::RngCore;
+use sha2::Sha256;
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroize;
+
+/// Length of recovery public and private keys in bytes.
+pub const RECOVERY_KEY_LEN: usize = 32;
+
+/// HKDF info label for the key wrapping key.
+const HKDF_INFO_WRAP: &[u8] = b"envcrypt recovery wrap";
+
+/// Length of each wrapped file key (encryption key and MAC key) in bytes.
+const FILE_KEY_LEN: usize = 32;
+
+/// Errors that can occur while unwrapping file keys.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RecoveryError {
+    /// The wrapped key data has the wrong length or structure.
+    InvalidWrappedKey,
+
+    /// The recovery private key does not match the recipient the keys were wrapped for.
+    WrongKey,
+}
+
+impl std::fmt::Display for RecoveryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecoveryError::InvalidWrappedKey => write!(f, "Invalid wrapped recovery key"),
+            RecoveryError::WrongKey => write!(f, "The recovery key does not match this file"),
+        }
+    }
+}
+
+impl std::error::Error for RecoveryError {}
+
+/// Generates a new recovery key pair.
+///
+/// # Returns
+///
+/// Returns `(private_key, public_key)`. The public key goes into `envcrypt.toml`; the
+/// private key must be stored offline by whoever is responsible for recovery.
+pub fn generate_recovery_keypair() -> ([u8; RECOVERY_KEY_LEN], [u8; RECOVERY_KEY_LEN]) {
+    let mut secret = [0u8; RECOVERY_KEY_LEN];
+    rand::thread_rng().fill_bytes(&mut secret);
+    let public = PublicKey::from(&StaticSecret::from(secret)).to_bytes();
+    (secret, public)
+}
+
+/// Wraps the file keys for a recovery recipient.
+///
+/// # Panics
+///
+/// Panics if either key is not 32 bytes long, which the key derivation functions in
+/// [`crate::key`] guarantee.
+pub fn wrap_keys(recipient: &[u8; RECOVERY_KEY_LEN], encryption_key: &[u8], mac_key: &[u8]) -> Vec<u8> {
+    assert_eq!(encryption_key.len(), FILE_KEY_LEN, "encryption key must be 32 bytes");
+    assert_eq!(mac_key.len(), FILE_KEY_LEN, "MAC key must be 32 bytes");
+
+    let mut ephemeral_bytes = [0u8; RECOVERY_KEY_LEN];
+    rand::thread_rng().fill_bytes(&mut ephemeral_bytes);
+    let ephemeral = StaticSecret::from(ephemeral_bytes);
+    ephemeral_bytes.zeroize();
+    let ephemeral_public = PublicKey::from(&ephemeral).to_bytes();
+
+    let shared = ephemeral.diffie_hellman(&PublicKey::from(*recipient));
+    let wrapping_key = derive_wrapping_key(shared.as_bytes(), &ephemeral_public, recipient);
+
+    let mut file_keys = [0u8; 2 * FILE_KEY_LEN];
+    file_keys[..FILE_KEY_LEN].copy_from_slice(encryption_key);
+    file_keys[FILE_KEY_LEN..].copy_from_slice(mac_key);
+
+    let ciphertext = ChaCha20Poly1305::new(&wrapping_key)
+        .encrypt(&Nonce::default(), file_keys.as_slice())
+        .expect("ChaCha20-Poly1305 encryption of 64 bytes cannot fail");
+    file_keys.zeroize();
+
+    let mut wrapped = Vec::with_capacity(RECOVERY_KEY_LEN + ciphertext.len());
+    wrapped.extend_from_slice(&ephemeral_public);
+    wrapped.extend_from_slice(&ciphertext);
+    wrapped
+}
+
+/// Unwraps file keys with the recovery private key.
+///
+/// # Returns
+///
+/// Returns a tuple `(encryption_key, mac_key)` of 32-byte keys.
+///
+/// # Errors
+///
+/// Returns [`RecoveryError::InvalidWrappedKey`] if the data is malformed and
+/// [`RecoveryError::WrongKey`] if it was wrapped for a different recipient or tampered with.
+pub fn unwrap_keys(secret: &[u8; RECOVERY_KEY_LEN], wrapped: &[u8]) -> Result<(Vec<u8>, Vec<u8>), RecoveryError> {
+    if wrapped.len() <= RECOVERY_KEY_LEN {
+        return Err(RecoveryError::InvalidWrappedKey);
+    }
+    let (ephemeral_public, ciphertext) = wrapped.split_at(RECOVERY_KEY_LEN);
+    let ephemeral_public: [u8; RECOVERY_KEY_LEN] = ephemeral_public.try_into()
+        .map_err(|_| RecoveryError::InvalidWrappedKey)?;
+
+    let secret = StaticSecret::from(*secret);
+    let recipient = PublicKey::from(&secret).to_bytes();
+    let shared = secret.diffie_hellman(&PublicKey::from(ephemeral_public));
+    let wrapping_key = derive_wrapping_key(shared.as_bytes(), &ephemeral_public, &recipient);
+
+    let mut file_keys = ChaCha20Poly1305::new(&wrapping_key)
+        .decrypt(&Nonce::default(), ciphertext)
+        .map_err(|_| RecoveryError::WrongKey)?;
+    if file_keys.len() != 2 * FILE_KEY_LEN {
+        file_keys.zeroize();
+        return Err(RecoveryError::InvalidWrappedKey);
+    }
+
+    let mac_key = file_keys.split_off(FILE_KEY_LEN);
+    Ok((file_keys, mac_key))
+}
+
+/// Derives the single-use wrapping key, binding both public keys into the salt.
+fn derive_wrapping_key(shared: &[u8], ephemeral_public: &[u8; RECOVERY_KEY_LEN], recipient: &[u8; RECOVERY_KEY_LEN]) -> Key {
+    let mut salt = [0u8; 2 * RECOVERY_KEY_LEN];
+    salt[..RECOVERY_KEY_LEN].copy_from_slice(ephemeral_public);
+    salt[RECOVERY_KEY_LEN..].copy_from_slice(recipient);
+
+    let mut key = Key::default();
+    Hkdf::<Sha256>::new(Some(&salt), shared)
+        .expand(HKDF_INFO_WRAP, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_unwrap_roundtrip() {
+        let (secret, public) = generate_recovery_keypair();
+        let wrapped = wrap_keys(&public, &[3u8; 32], &[4u8; 32]);
+        assert_eq!(wrapped.len(), RECOVERY_KEY_LEN + 2 * FILE_KEY_LEN + 16);
+        assert_eq!(unwrap_keys(&secret, &wrapped).unwrap(), (vec![3u8; 32], vec![4u8; 32]));
+    }
+
+    #[test]
+    fn test_wrapping_is_randomised() {
+        let (_, public) = generate_recovery_keypair();
+        assert_ne!(wrap_keys(&public, &[3u8; 32], &[4u8; 32]), wrap_keys(&public, &[3u8; 32], &[4u8; 32]));
+    }
+
+    #[test]
+    fn test_unwrap_with_wrong_key_fails() {
+        let (_, public) = generate_recovery_keypair();
+        let (other_secret, _) = generate_recovery_keypair();
+        let wrapped = wrap_keys(&public, &[3u8; 32], &[4u8; 32]);
+        assert_eq!(unwrap_keys(&other_secret, &wrapped), Err(RecoveryError::WrongKey));
+    }
+
+    #[test]
+    fn test_unwrap_truncated_fails() {
+        let (secret, _) = generate_recovery_keypair();
+        assert_eq!(unwrap_keys(&secret, &[0u8; 10]), Err(RecoveryError::InvalidWrappedKey));
+    }
+}
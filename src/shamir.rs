@@ -0,0 +1,268 @@
+//! Shamir secret sharing over GF(256).
+//!
+//! This module splits a secret (such as an encryption key) into `n` shares so that any
+//! `threshold` of them reconstruct the secret while fewer reveal nothing about it. Each
+//! byte of the secret is shared independently with a random polynomial of degree
+//! `threshold - 1` over GF(2^8), using the AES reduction polynomial.
+//!
+//! # Example
+//!
+//! ```
+//! use envcrypt::shamir::{combine, split};
+//!
+//! let shares = split(b"my master key", 5, 3)?;
+//! let recovered = combine(&shares[1..4])?;
+//! assert_eq!(recovered, b"my master key");
+//! # Ok::<(), envcrypt::shamir::ShamirError>(())
+//! ```
+
+use base64::Engine;
+use rand::RngCore;
+use zeroize::Zeroize;
+
+/// Prefix identifying the text encoding of a share.
+const SHARE_PREFIX: &str = "envcrypt-share";
+
+/// One share of a split secret.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Share {
+    /// Number of shares required to recover the secret.
+    pub threshold: u8,
+    /// Evaluation point of this share (1-255, unique per share).
+    pub index: u8,
+    /// Share bytes, one per secret byte.
+    pub data: Vec<u8>,
+}
+
+impl Share {
+    /// Encodes the share as `envcrypt-share:<threshold>:<index>:<base64 data>`.
+    pub fn encode(&self) -> String {
+        format!(
+            "{}:{}:{}:{}",
+            SHARE_PREFIX,
+            self.threshold,
+            self.index,
+            base64::engine::general_purpose::STANDARD.encode(&self.data)
+        )
+    }
+
+    /// Parses a share produced by [`Share::encode()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ShamirError::InvalidShare`] with the reason the share is malformed; the
+    /// error never contains the share itself.
+    pub fn decode(text: &str) -> Result<Self, ShamirError> {
+        let invalid = |reason: &str| ShamirError::InvalidShare(reason.to_string());
+        let mut parts = text.trim().splitn(4, ':');
+
+        if parts.next() != Some(SHARE_PREFIX) {
+            return Err(invalid("it does not start with envcrypt-share:"));
+        }
+        let threshold = parts.next()
+            .and_then(|p| p.parse::<u8>().ok())
+            .filter(|&threshold| threshold >= 2)
+            .ok_or_else(|| invalid("the threshold is not a number from 2 to 255"))?;
+        let index = parts.next()
+            .and_then(|p| p.parse::<u8>().ok())
+            .filter(|&index| index != 0)
+            .ok_or_else(|| invalid("the index is not a number from 1 to 255"))?;
+        let data = parts.next()
+            .and_then(|p| base64::engine::general_purpose::STANDARD.decode(p).ok())
+            .filter(|data| !data.is_empty())
+            .ok_or_else(|| invalid(&format!("the data of share {} is missing or not valid base64", index)))?;
+        Ok(Self { threshold, index, data })
+    }
+}
+
+/// Errors that can occur while splitting or combining shares.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ShamirError {
+    /// The share count or threshold is out of range.
+    InvalidParameters(String),
+
+    /// A share could not be parsed. Contains the reason, never the share, which is
+    /// secret.
+    InvalidShare(String),
+
+    /// The shares do not belong together or are not enough to recover the secret.
+    IncompatibleShares(String),
+}
+
+impl std::fmt::Display for ShamirError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShamirError::InvalidParameters(msg) => write!(f, "Invalid share parameters: {}", msg),
+            ShamirError::InvalidShare(reason) => write!(f, "Invalid share: {}", reason),
+            ShamirError::IncompatibleShares(msg) => write!(f, "Cannot recover secret: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ShamirError {}
+
+/// Splits a secret into `shares` shares, any `threshold` of which recover it.
+///
+/// # Errors
+///
+/// Returns [`ShamirError::InvalidParameters`] unless `2 <= threshold <= shares <= 255`
+/// and the secret is non-empty.
+pub fn split(secret: &[u8], shares: u8, threshold: u8) -> Result<Vec<Share>, ShamirError> {
+    if threshold < 2 {
+        return Err(ShamirError::InvalidParameters("threshold must be at least 2".to_string()));
+    }
+    if shares < threshold {
+        return Err(ShamirError::InvalidParameters("number of shares must be at least the threshold".to_string()));
+    }
+    if secret.is_empty() {
+        return Err(ShamirError::InvalidParameters("secret is empty".to_string()));
+    }
+
+    let mut result: Vec<Share> = (1..=shares)
+        .map(|index| Share { threshold, index, data: Vec::with_capacity(secret.len()) })
+        .collect();
+
+    let mut coefficients = vec![0u8; threshold as usize];
+    for &byte in secret {
+        // f(x) = byte + c1*x + ... + c(t-1)*x^(t-1)
+        coefficients[0] = byte;
+        rand::thread_rng().fill_bytes(&mut coefficients[1..]);
+
+        for share in result.iter_mut() {
+            // Horner's rule, highest coefficient first
+            let y = coefficients.iter().rev().fold(0u8, |acc, &c| gf_mul(acc, share.index) ^ c);
+            share.data.push(y);
+        }
+    }
+    coefficients.zeroize();
+
+    Ok(result)
+}
+
+/// Recovers the secret from at least `threshold` distinct shares.
+///
+/// # Errors
+///
+/// Returns [`ShamirError::IncompatibleShares`] if the shares disagree on threshold or
+/// length, contain duplicates, or are fewer than the threshold.
+pub fn combine(shares: &[Share]) -> Result<Vec<u8>, ShamirError> {
+    let first = shares.first()
+        .ok_or_else(|| ShamirError::IncompatibleShares("no shares provided".to_string()))?;
+
+    if shares.iter().any(|s| s.threshold != first.threshold || s.data.len() != first.data.len()) {
+        return Err(ShamirError::IncompatibleShares("shares come from different splits".to_string()));
+    }
+    for (i, share) in shares.iter().enumerate() {
+        if shares[..i].iter().any(|other| other.index == share.index) {
+            return Err(ShamirError::IncompatibleShares(format!("share {} was provided more than once", share.index)));
+        }
+    }
+    if shares.len() < first.threshold as usize {
+        return Err(ShamirError::IncompatibleShares(format!(
+            "{} shares required, {} provided",
+            first.threshold,
+            shares.len()
+        )));
+    }
+
+    let shares = &shares[..first.threshold as usize];
+    let secret = (0..first.data.len())
+        .map(|position| {
+            // Lagrange interpolation at x = 0
+            shares.iter().fold(0u8, |acc, share| {
+                let basis = shares.iter()
+                    .filter(|other| other.index != share.index)
+                    .fold(1u8, |basis, other| {
+                        gf_mul(basis, gf_div(other.index, other.index ^ share.index))
+                    });
+                acc ^ gf_mul(share.data[position], basis)
+            })
+        })
+        .collect();
+
+    Ok(secret)
+}
+
+/// Multiplies two elements of GF(2^8) modulo x^8 + x^4 + x^3 + x + 1.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80;
+        a <<= 1;
+        if carry != 0 {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+/// Divides two elements of GF(2^8); `b` must be non-zero.
+fn gf_div(a: u8, b: u8) -> u8 {
+    // b^254 is the multiplicative inverse of b in GF(2^8)
+    let mut inverse = 1u8;
+    for _ in 0..254 {
+        inverse = gf_mul(inverse, b);
+    }
+    gf_mul(a, inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_any_threshold_subset_recovers_secret() {
+        let secret = b"q83vEjRWeJCrze8SNFZ4kKvN7xI0VniQq83vEjRWeJA=";
+        let shares = split(secret, 5, 3).unwrap();
+        assert_eq!(combine(&shares[..3]).unwrap(), secret);
+        assert_eq!(combine(&[shares[4].clone(), shares[0].clone(), shares[2].clone()]).unwrap(), secret);
+        assert_eq!(combine(&shares).unwrap(), secret);
+    }
+
+    #[test]
+    fn test_too_few_shares_fail() {
+        let shares = split(b"secret", 5, 3).unwrap();
+        assert!(matches!(combine(&shares[..2]), Err(ShamirError::IncompatibleShares(_))));
+    }
+
+    #[test]
+    fn test_duplicate_shares_fail() {
+        let shares = split(b"secret", 3, 2).unwrap();
+        assert!(combine(&[shares[0].clone(), shares[0].clone()]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        assert!(split(b"secret", 3, 1).is_err());
+        assert!(split(b"secret", 2, 3).is_err());
+        assert!(split(b"", 3, 2).is_err());
+    }
+
+    #[test]
+    fn test_share_encoding_roundtrip() {
+        let share = split(b"secret", 3, 2).unwrap().remove(1);
+        let encoded = share.encode();
+        assert!(encoded.starts_with("envcrypt-share:2:2:"));
+        assert_eq!(Share::decode(&encoded).unwrap(), share);
+        assert!(Share::decode("envcrypt-share:2:0:AAAA").is_err());
+        assert!(Share::decode("garbage").is_err());
+
+        // The error explains what is wrong without repeating the secret share
+        let error = Share::decode("envcrypt-share:2:3:not base64 c2VjcmV0").unwrap_err().to_string();
+        assert_eq!(error, "Invalid share: the data of share 3 is missing or not valid base64");
+        assert!(!Share::decode("c2VjcmV0").unwrap_err().to_string().contains("c2VjcmV0"));
+    }
+
+    #[test]
+    fn test_gf_div_inverts_mul() {
+        for a in 1..=255u8 {
+            for b in [1u8, 2, 3, 0x53, 0xca, 0xff] {
+                assert_eq!(gf_div(gf_mul(a, b), b), a);
+            }
+        }
+    }
+}
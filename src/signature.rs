@@ -0,0 +1,218 @@
+//! Ed25519 signatures of encrypted files.
+//!
+//! The MAC of an encrypted file proves that it was written by someone who has the key,
+//! which includes everyone who can decrypt it. A signature proves that it was written by
+//! the holder of a specific signing key, such as a release pipeline, so consumers can
+//! tell its files apart from ones encrypted on a teammate's laptop.
+//!
+//! A signature is either embedded or detached:
+//!
+//! - An **embedded** signature covers the encoded file (header and cipher output) and
+//!   is appended to it as a trailer of [`SIGNATURE_TRAILER_LEN`] bytes:
+//!   `[Signature (64 bytes)][Signer Public Key (32 bytes)]`. The header records that the
+//!   trailer is present (see [`Header::signed`](crate::format::Header::signed)), so it
+//!   cannot be stripped without making the file undecryptable.
+//! - A **detached** signature covers the file exactly as stored on disk and has the same
+//!   96-byte layout, kept in a separate file.
+//!
+//! Signatures are made over [`SIGNING_CONTEXT`] followed by the signed bytes, so they
+//! cannot be replayed as signatures of anything other than an envcrypt file.
+//!
+//! # Example
+//!
+//! ```
+//! use envcrypt::signature::{generate_signing_keypair, sign_detached, verify_detached};
+//!
+//! let (secret, public) = generate_signing_keypair();
+//! let signature = sign_detached(&secret, b"encrypted file");
+//!
+//! assert_eq!(verify_detached(b"encrypted file", &signature, &[public])?, public);
+//! assert!(verify_detached(b"tampered file", &signature, &[public]).is_err());
+//! # Ok::<(), envcrypt::signature::SignatureError>(())
+//! ```
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::RngCore;
+
+pub use crate::format::SIGNATURE_TRAILER_LEN;
+use crate::format;
+
+/// Length of signing (private) and verifying (public) keys in bytes.
+pub const SIGNING_KEY_LEN: usize = 32;
+
+/// Prefix of every signed message.
+pub const SIGNING_CONTEXT: &[u8] = b"envcrypt signature v1\0";
+
+/// Length of the Ed25519 signature at the start of a trailer.
+const SIGNATURE_LEN: usize = 64;
+
+/// Errors that can occur while verifying a signature.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SignatureError {
+    /// The file has no embedded signature.
+    NotSigned,
+
+    /// The file or signature is malformed.
+    Malformed,
+
+    /// The signature was made by a key that is not one of the trusted keys. Contains the
+    /// signer's public key.
+    UntrustedSigner([u8; SIGNING_KEY_LEN]),
+
+    /// The signature does not match the data: it was changed after signing.
+    Invalid,
+}
+
+impl std::fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignatureError::NotSigned => write!(f, "The file is not signed"),
+            SignatureError::Malformed => write!(f, "The signature is malformed"),
+            SignatureError::UntrustedSigner(_) => write!(f, "The file is signed by a key that is not trusted"),
+            SignatureError::Invalid => write!(f, "The signature does not match the file; it was changed after signing"),
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+/// Generates a new signing key pair.
+///
+/// # Returns
+///
+/// Returns `(private_key, public_key)`. The private key belongs to whoever produces the
+/// files, such as a release pipeline; the public key is given to the consumers.
+pub fn generate_signing_keypair() -> ([u8; SIGNING_KEY_LEN], [u8; SIGNING_KEY_LEN]) {
+    let mut secret = [0u8; SIGNING_KEY_LEN];
+    rand::thread_rng().fill_bytes(&mut secret);
+    let public = signing_public_key(&secret);
+    (secret, public)
+}
+
+/// Returns the public key of a signing private key.
+pub fn signing_public_key(secret: &[u8; SIGNING_KEY_LEN]) -> [u8; SIGNING_KEY_LEN] {
+    SigningKey::from_bytes(secret).verifying_key().to_bytes()
+}
+
+/// Signs `data` (the complete file as stored), returning a detached signature.
+pub fn sign_detached(secret: &[u8; SIGNING_KEY_LEN], data: &[u8]) -> [u8; SIGNATURE_TRAILER_LEN] {
+    let key = SigningKey::from_bytes(secret);
+    let signature = key.sign(&signed_message(data));
+    let mut trailer = [0u8; SIGNATURE_TRAILER_LEN];
+    trailer[..SIGNATURE_LEN].copy_from_slice(&signature.to_bytes());
+    trailer[SIGNATURE_LEN..].copy_from_slice(key.verifying_key().as_bytes());
+    trailer
+}
+
+/// Appends an embedded signature to encoded file data whose header has
+/// [`signed`](crate::format::Header::signed) set.
+pub fn append_signature(secret: &[u8; SIGNING_KEY_LEN], data: &mut Vec<u8>) {
+    let trailer = sign_detached(secret, data);
+    data.extend_from_slice(&trailer);
+}
+
+/// Verifies a detached signature of `data` against the `trusted` public keys.
+///
+/// # Returns
+///
+/// Returns the public key that made the signature.
+///
+/// # Errors
+///
+/// Returns a [`SignatureError`] if the signature is malformed, made by an untrusted key,
+/// or does not match `data`.
+pub fn verify_detached(
+    data: &[u8],
+    signature: &[u8],
+    trusted: &[[u8; SIGNING_KEY_LEN]],
+) -> Result<[u8; SIGNING_KEY_LEN], SignatureError> {
+    let trailer: &[u8; SIGNATURE_TRAILER_LEN] = signature.try_into().map_err(|_| SignatureError::Malformed)?;
+    let (signature, signer) = trailer.split_at(SIGNATURE_LEN);
+    let signer: [u8; SIGNING_KEY_LEN] = signer.try_into().map_err(|_| SignatureError::Malformed)?;
+    if !trusted.contains(&signer) {
+        return Err(SignatureError::UntrustedSigner(signer));
+    }
+
+    let key = VerifyingKey::from_bytes(&signer).map_err(|_| SignatureError::Malformed)?;
+    let signature = Signature::from_slice(signature).map_err(|_| SignatureError::Malformed)?;
+    key.verify(&signed_message(data), &signature).map_err(|_| SignatureError::Invalid)?;
+    Ok(signer)
+}
+
+/// Verifies the embedded signature of encoded file data against the `trusted` public keys.
+///
+/// # Returns
+///
+/// Returns the public key that made the signature.
+///
+/// # Errors
+///
+/// Returns [`SignatureError::NotSigned`] if the header does not announce a signature, and
+/// the errors of [`verify_detached()`] otherwise.
+pub fn verify_embedded(data: &[u8], trusted: &[[u8; SIGNING_KEY_LEN]]) -> Result<[u8; SIGNING_KEY_LEN], SignatureError> {
+    let (header, _) = format::decode(data).map_err(|_| SignatureError::Malformed)?;
+    if !header.signed {
+        return Err(SignatureError::NotSigned);
+    }
+    let (signed, trailer) = data.split_at(data.len() - SIGNATURE_TRAILER_LEN);
+    verify_detached(signed, trailer, trusted)
+}
+
+fn signed_message(data: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(SIGNING_CONTEXT.len() + data.len());
+    message.extend_from_slice(SIGNING_CONTEXT);
+    message.extend_from_slice(data);
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{encode, Header, Kdf};
+
+    fn signed_file(secret: &[u8; SIGNING_KEY_LEN]) -> Vec<u8> {
+        let mut header = Header::new("AES-256-GCM", Kdf::HkdfSha256, [1u8; 16]);
+        header.signed = true;
+        let mut data = encode(&header, b"cipher output").unwrap();
+        append_signature(secret, &mut data);
+        data
+    }
+
+    #[test]
+    fn test_embedded_roundtrip() {
+        let (secret, public) = generate_signing_keypair();
+        let data = signed_file(&secret);
+        assert_eq!(verify_embedded(&data, &[public]), Ok(public));
+        assert_eq!(format::decode(&data).unwrap().1, b"cipher output");
+    }
+
+    #[test]
+    fn test_embedded_rejects_tampering() {
+        let (secret, public) = generate_signing_keypair();
+        let mut data = signed_file(&secret);
+        let body_start = data.len() - SIGNATURE_TRAILER_LEN - 1;
+        data[body_start] ^= 1;
+        assert_eq!(verify_embedded(&data, &[public]), Err(SignatureError::Invalid));
+    }
+
+    #[test]
+    fn test_embedded_rejects_untrusted_signer() {
+        let (secret, public) = generate_signing_keypair();
+        let (_, other) = generate_signing_keypair();
+        let data = signed_file(&secret);
+        assert_eq!(verify_embedded(&data, &[other]), Err(SignatureError::UntrustedSigner(public)));
+    }
+
+    #[test]
+    fn test_embedded_requires_signed_header() {
+        let (_, public) = generate_signing_keypair();
+        let data = encode(&Header::new("AES-256-GCM", Kdf::HkdfSha256, [1u8; 16]), b"cipher output").unwrap();
+        assert_eq!(verify_embedded(&data, &[public]), Err(SignatureError::NotSigned));
+    }
+
+    #[test]
+    fn test_detached_rejects_malformed_signature() {
+        let (_, public) = generate_signing_keypair();
+        assert_eq!(verify_detached(b"data", &[0u8; 10], &[public]), Err(SignatureError::Malformed));
+    }
+}
@@ -0,0 +1,77 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+
+fn encrypt_with_context(temp_dir: &std::path::Path, context: &str) {
+    fs::write(temp_dir.join(".env"), "APP_KEY=test123").unwrap();
+    let mut cmd = create_encrypt_command(temp_dir, TEST_KEY);
+    cmd.arg("--aad").arg(context);
+    cmd.assert().success();
+    fs::remove_file(temp_dir.join(".env")).unwrap();
+}
+
+#[test]
+fn test_aad_roundtrip() {
+    let temp_dir = create_temp_dir();
+    encrypt_with_context(temp_dir.path(), "production:payments-service");
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--aad").arg("production:payments-service");
+    cmd.assert().success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "APP_KEY=test123");
+}
+
+#[test]
+fn test_aad_mismatch_fails() {
+    let temp_dir = create_temp_dir();
+    encrypt_with_context(temp_dir.path(), "production:payments-service");
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--aad").arg("production:billing-service");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Authentication failed"));
+    assert!(!temp_dir.path().join(".env").exists());
+}
+
+#[test]
+fn test_aad_required_for_bound_file() {
+    let temp_dir = create_temp_dir();
+    encrypt_with_context(temp_dir.path(), "production:payments-service");
+
+    create_decrypt_command(temp_dir.path(), TEST_KEY).assert()
+        .failure()
+        .stderr(predicate::str::contains("bound to a context string"));
+}
+
+#[test]
+fn test_aad_rejected_for_unbound_file() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    fs::remove_file(temp_dir.path().join(".env")).unwrap();
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--aad").arg("production:payments-service");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("not bound to a context string"));
+}
+
+#[test]
+fn test_aad_from_config() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join("envcrypt.toml"), "aad = \"staging:api\"\n").unwrap();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    fs::remove_file(temp_dir.path().join(".env")).unwrap();
+
+    create_command(temp_dir.path()).arg("inspect").assert()
+        .success()
+        .stdout(predicate::str::contains("Context:       bound"));
+
+    fs::remove_file(temp_dir.path().join("envcrypt.toml")).unwrap();
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--aad").arg("staging:api");
+    cmd.assert().success();
+}
@@ -0,0 +1,136 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+
+fn enable_audit_log(temp_dir: &Path) {
+    fs::write(temp_dir.join("envcrypt.toml"), "audit_log = \"logs/audit.log\"\n").unwrap();
+}
+
+fn read_entries(path: &Path) -> Vec<serde_json::Value> {
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_audit_log_records_commands() {
+    let temp_dir = create_temp_dir();
+    enable_audit_log(temp_dir.path());
+    fs::write(temp_dir.path().join(".env"), "SECRET=hunter2-value\n").unwrap();
+
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    create_decrypt_command(temp_dir.path(), TEST_KEY).arg("--force").assert().success();
+
+    let log_path = temp_dir.path().join("logs").join("audit.log");
+    let log = fs::read_to_string(&log_path).unwrap();
+    assert!(!log.contains("hunter2-value"));
+    assert!(!log.contains(TEST_KEY));
+
+    let entries = read_entries(&log_path);
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["command"], "encrypt");
+    assert_eq!(entries[0]["read"], serde_json::json!([".env"]));
+    assert_eq!(entries[0]["written"], serde_json::json!([".env.encrypted"]));
+    assert_eq!(entries[0]["outcome"], "success");
+    assert_eq!(entries[1]["command"], "decrypt");
+    assert_eq!(entries[1]["read"], serde_json::json!([".env.encrypted"]));
+    assert_eq!(entries[1]["written"], serde_json::json!([".env"]));
+
+    // The same key has the same fingerprint
+    let fingerprints = entries[0]["key_fingerprints"].as_array().unwrap();
+    assert_eq!(fingerprints.len(), 1);
+    assert_eq!(entries[1]["key_fingerprints"], entries[0]["key_fingerprints"]);
+    assert!(entries[0]["timestamp"].is_string());
+}
+
+#[test]
+fn test_audit_log_records_failures() {
+    let temp_dir = create_temp_dir();
+    enable_audit_log(temp_dir.path());
+    fs::write(temp_dir.path().join(".env"), "SECRET=value\n").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+
+    create_decrypt_command(temp_dir.path(), "wrong-key").arg("--force").assert().failure();
+
+    let entries = read_entries(&temp_dir.path().join("logs").join("audit.log"));
+    let entry = &entries[1];
+    assert_eq!(entry["command"], "decrypt");
+    assert_eq!(entry["outcome"], "error");
+    assert!(entry["error"].is_string());
+    assert_eq!(entry["written"], serde_json::json!([]));
+    assert_ne!(entry["key_fingerprints"], entries[0]["key_fingerprints"]);
+}
+
+#[test]
+fn test_audit_log_records_subcommands_and_dry_runs() {
+    let temp_dir = create_temp_dir();
+    enable_audit_log(temp_dir.path());
+    fs::write(temp_dir.path().join(".env"), "SECRET=value\n").unwrap();
+
+    create_encrypt_command(temp_dir.path(), TEST_KEY).arg("--dry-run").assert().success();
+    let mut cmd = create_command(temp_dir.path());
+    cmd.args(["key", "split", "--key", TEST_KEY, "--shares", "3", "--threshold", "2"]);
+    cmd.assert().success();
+
+    let entries = read_entries(&temp_dir.path().join("logs").join("audit.log"));
+    assert_eq!(entries[0]["dry_run"], true);
+    assert_eq!(entries[0]["written"], serde_json::json!([]));
+    assert_eq!(entries[1]["command"], "key split");
+    assert!(entries[1].get("dry_run").is_none());
+}
+
+#[test]
+fn test_audit_log_default_location() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join("envcrypt.toml"), "audit = true\n").unwrap();
+    fs::write(temp_dir.path().join(".env"), "SECRET=value\n").unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.env("XDG_DATA_HOME", temp_dir.path().join("data")).env_remove("ENVCRYPT_HOME");
+    cmd.assert().success();
+
+    let entries = read_entries(&temp_dir.path().join("data").join("envcrypt").join("audit.log"));
+    assert_eq!(entries[0]["command"], "encrypt");
+}
+
+#[test]
+fn test_audit_log_in_envcrypt_home() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join("envcrypt.toml"), "audit = true\n").unwrap();
+    fs::write(temp_dir.path().join(".env"), "SECRET=value\n").unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.env("ENVCRYPT_HOME", temp_dir.path().join("home")).env("XDG_DATA_HOME", temp_dir.path().join("data"));
+    cmd.assert().success();
+
+    let entries = read_entries(&temp_dir.path().join("home").join("audit.log"));
+    assert_eq!(entries[0]["command"], "encrypt");
+    assert!(!temp_dir.path().join("data").exists());
+}
+
+#[test]
+fn test_audit_log_disabled() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join("envcrypt.toml"), "audit = false\naudit_log = \"audit.log\"\n").unwrap();
+    fs::write(temp_dir.path().join(".env"), "SECRET=value\n").unwrap();
+
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    assert!(!temp_dir.path().join("audit.log").exists());
+}
+
+#[test]
+fn test_audit_log_unwritable_fails_before_running() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join("envcrypt.toml"), "audit_log = \"blocker/audit.log\"\n").unwrap();
+    fs::write(temp_dir.path().join("blocker"), "").unwrap();
+    fs::write(temp_dir.path().join(".env"), "SECRET=value\n").unwrap();
+
+    create_encrypt_command(temp_dir.path(), TEST_KEY)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Cannot create the audit log directory blocker"));
+    assert!(!temp_dir.path().join(".env.encrypted").exists());
+}
@@ -0,0 +1,78 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+
+#[test]
+fn test_force_decrypt_with_backup_keeps_local_edits() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+
+    fs::write(&env_path, "APP_KEY=original").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    fs::write(&env_path, "APP_KEY=local-edit").unwrap();
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--force").arg("--backup");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Backed up .env to .env.bak"));
+
+    assert_eq!(fs::read_to_string(&env_path).unwrap(), "APP_KEY=original");
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env.bak")).unwrap(), "APP_KEY=local-edit");
+}
+
+#[test]
+fn test_force_encrypt_with_backup() {
+    let temp_dir = create_temp_dir();
+    let encrypted_path = temp_dir.path().join(".env.encrypted");
+
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    let previous = fs::read_to_string(&encrypted_path).unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--force").arg("--backup");
+    cmd.assert().success();
+
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env.encrypted.bak")).unwrap(), previous);
+}
+
+#[test]
+fn test_force_without_backup_writes_no_backup() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+
+    fs::write(&env_path, "APP_KEY=original").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--force");
+    cmd.assert().success();
+
+    assert!(!temp_dir.path().join(".env.bak").exists());
+}
+
+#[test]
+fn test_backup_directory_suffix_and_rotation_from_config() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    fs::write(
+        temp_dir.path().join("envcrypt.toml"),
+        "backup_dir = \"backups\"\nbackup_suffix = \".old\"\nbackup_keep = 2\n",
+    ).unwrap();
+
+    fs::write(&env_path, "APP_KEY=original").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+
+    for edit in ["APP_KEY=first-edit", "APP_KEY=second-edit", "APP_KEY=third-edit"] {
+        fs::write(&env_path, edit).unwrap();
+        let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+        cmd.arg("--force").arg("--backup");
+        cmd.assert().success();
+    }
+
+    let backups = temp_dir.path().join("backups");
+    assert_eq!(fs::read_to_string(backups.join(".env.old")).unwrap(), "APP_KEY=third-edit");
+    assert_eq!(fs::read_to_string(backups.join(".env.old.1")).unwrap(), "APP_KEY=second-edit");
+    assert!(!backups.join(".env.old.2").exists());
+}
@@ -0,0 +1,106 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+
+const SCHEMA: &str = r#"
+[DATABASE_URL]
+type = "url"
+
+[PORT]
+type = "int"
+
+[DEBUG]
+type = "bool"
+required = false
+
+[LOG_LEVEL]
+required = false
+pattern = "debug|info|warn|error"
+"#;
+
+fn setup(temp_dir: &Path, content: &str) {
+    fs::write(temp_dir.join(".env"), content).unwrap();
+    create_encrypt_command(temp_dir, TEST_KEY).assert().success();
+    fs::write(temp_dir.join("env.schema.toml"), SCHEMA).unwrap();
+}
+
+fn create_check_command(temp_dir: &Path) -> assert_cmd::Command {
+    let mut cmd = create_command(temp_dir);
+    cmd.arg("check").arg("--key").arg(TEST_KEY);
+    cmd
+}
+
+#[test]
+fn test_check_schema_passes() {
+    let temp_dir = create_temp_dir();
+    setup(temp_dir.path(), "HOST=db\nDATABASE_URL=postgres://${HOST}:5432/app\nPORT=8080\nDEBUG=false\n");
+
+    let mut cmd = create_check_command(temp_dir.path());
+    cmd.arg("--schema").arg("env.schema.toml");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(".env.encrypted passed the check against env.schema.toml (4 variables)"));
+}
+
+#[test]
+fn test_check_schema_reports_every_problem() {
+    let temp_dir = create_temp_dir();
+    setup(temp_dir.path(), "DATABASE_URL=not-a-url\nDEBUG=s3cret-value\nLOG_LEVEL=loud\n");
+
+    let mut cmd = create_check_command(temp_dir.path());
+    cmd.arg("--schema").arg("env.schema.toml");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(".env.encrypted failed the check (4 problems)"))
+        .stderr(predicate::str::contains("DATABASE_URL must be a URL"))
+        .stderr(predicate::str::contains("DEBUG must be a boolean"))
+        .stderr(predicate::str::contains("LOG_LEVEL does not match the pattern"))
+        .stderr(predicate::str::contains("PORT is required but not set"))
+        // Values are never shown
+        .stderr(predicate::str::contains("s3cret").not());
+}
+
+#[test]
+fn test_check_invalid_schema_fails_before_decrypting() {
+    let temp_dir = create_temp_dir();
+    setup(temp_dir.path(), "PORT=8080\n");
+    fs::write(temp_dir.path().join("env.schema.toml"), "[PORT]\ntype = \"float\"\n").unwrap();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("check").arg("--schema").arg("env.schema.toml").arg("--no-interaction");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid schema in env.schema.toml"));
+}
+
+#[test]
+fn test_check_against_example() {
+    let temp_dir = create_temp_dir();
+    setup(temp_dir.path(), "APP_KEY=test123\nEXTRA=1\n");
+    fs::write(temp_dir.path().join(".env.example"), "APP_KEY=\nDB_PASSWORD=changeme\n").unwrap();
+
+    let mut cmd = create_check_command(temp_dir.path());
+    cmd.arg("--against").arg(".env.example");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("DB_PASSWORD is in .env.example but not set"))
+        .stderr(predicate::str::contains("EXTRA is set but not in .env.example"));
+
+    fs::write(temp_dir.path().join(".env.example"), "APP_KEY=\nEXTRA=\n").unwrap();
+    let mut cmd = create_check_command(temp_dir.path());
+    cmd.arg("--against").arg(".env.example");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("passed the check against .env.example"));
+}
+
+#[test]
+fn test_check_requires_schema_or_example() {
+    let temp_dir = create_temp_dir();
+
+    create_check_command(temp_dir.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--schema <SCHEMA>"));
+}
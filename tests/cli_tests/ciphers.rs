@@ -1,4 +1,5 @@
 use crate::common::*;
+use predicates::prelude::*;
 use std::fs;
 
 #[test]
@@ -142,7 +143,9 @@ fn test_wrong_cipher_fails() {
     fs::remove_file(&env_path).unwrap();
     let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
     cmd.arg("--cipher").arg("AES-256-CBC");
-    cmd.assert().failure(); // Should fail because cipher doesn't match
+    cmd.assert()
+        .failure() // Should fail because cipher doesn't match
+        .stderr(predicates::str::contains("was encrypted with AES-256-GCM"));
 }
 
 #[test]
@@ -158,3 +161,99 @@ fn test_unsupported_cipher_fails() {
     cmd.arg("--cipher").arg("UNSUPPORTED-CIPHER");
     cmd.assert().failure();
 }
+
+#[test]
+fn test_cipher_alias_is_accepted() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    fs::write(&env_path, "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--cipher").arg("aes256gcm");
+    cmd.assert().success();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("inspect");
+    cmd.assert()
+        .success()
+        .stdout(predicates::str::contains("AES-256-GCM"));
+}
+
+#[test]
+fn test_mistyped_cipher_suggests_closest() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--cipher").arg("AES-265-GCM");
+    cmd.assert()
+        .failure()
+        .stderr(predicates::str::contains("did you mean AES-256-GCM?"));
+}
+
+#[test]
+fn test_decrypt_suggests_upgrading_cbc_files() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    fs::write(&env_path, "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).arg("--cipher").arg("AES-256-CBC").assert().success();
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--force");
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains(
+            ".env.encrypted is encrypted with AES-256-CBC; AES-256-GCM is recommended. Upgrade it with `envcrypt recrypt .env.encrypted --to-cipher AES-256-GCM`",
+        ));
+
+    fs::write(temp_dir.path().join("envcrypt.toml"), "warn_on_legacy_cipher = false\n").unwrap();
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--force");
+    cmd.assert().success().stderr(predicate::str::contains("recommended").not());
+
+    fs::remove_file(temp_dir.path().join("envcrypt.toml")).unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).args(["--cipher", "AES-256-GCM", "--force"]).assert().success();
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--force");
+    cmd.assert().success().stderr(predicate::str::contains("recommended").not());
+}
+
+#[test]
+fn test_strict_crypto_refuses_cbc_and_weak_kdf() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.args(["--strict-crypto", "--cipher", "AES-256-CBC"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("AES-256-CBC is not allowed with --strict-crypto, since it is not an AEAD cipher. Use --cipher AES-256-GCM"));
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.args(["--strict-crypto", "--cipher", "AES-256-GCM", "--kdf-iterations", "100000"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("100000 PBKDF2 iterations are not allowed with --strict-crypto, which requires at least 600000"));
+    assert!(!temp_dir.path().join(".env.encrypted").exists());
+
+    // A generated raw key needs no stretching
+    let mut cmd = create_command(temp_dir.path());
+    cmd.args(["encrypt", "--strict-crypto", "--cipher", "AES-256-GCM", "--no-interaction"]);
+    cmd.assert().success();
+}
+
+#[test]
+fn test_strict_crypto_from_config_applies_to_recrypt() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).args(["--cipher", "AES-256-CBC"]).assert().success();
+    let before = fs::read(temp_dir.path().join(".env.encrypted")).unwrap();
+
+    fs::write(temp_dir.path().join("envcrypt.toml"), "strict_crypto = true\n").unwrap();
+    let mut cmd = create_command(temp_dir.path());
+    cmd.args(["recrypt", "--key", TEST_KEY]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("AES-256-CBC is not allowed with --strict-crypto"));
+    assert_eq!(fs::read(temp_dir.path().join(".env.encrypted")).unwrap(), before);
+}
@@ -0,0 +1,91 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+
+const PASSPHRASE: &str = "correct horse battery staple";
+
+/// Runs envcrypt the way the fish completion script does, returning the candidates for
+/// the last word of `line`.
+fn complete(temp_dir: &Path, line: &[&str]) -> String {
+    let output = create_command(temp_dir)
+        .env("_ENVCRYPT_COMPLETE", "fish")
+        .env("ENVCRYPT_KEYRING_PASSPHRASE", PASSPHRASE)
+        .arg("--")
+        .arg("envcrypt")
+        .args(line)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn create_env_files(temp_dir: &Path) {
+    for file in [".env", ".env.local", ".env.staging", ".env.staging.encrypted", ".env.production.encrypted", ".env.example"] {
+        fs::write(temp_dir.join(file), "A=1\n").unwrap();
+    }
+}
+
+#[test]
+fn test_completions_prints_script() {
+    let temp_dir = create_temp_dir();
+    for (shell, expected) in [("bash", "complete -o nospace -o bashdefault"), ("zsh", "compdef"), ("fish", "complete --keep-order")] {
+        create_command(temp_dir.path())
+            .args(["completions", shell])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("_ENVCRYPT_COMPLETE"))
+            .stdout(predicate::str::contains(expected));
+    }
+}
+
+#[test]
+fn test_completions_env_from_files() {
+    let temp_dir = create_temp_dir();
+    create_env_files(temp_dir.path());
+
+    assert_eq!(complete(temp_dir.path(), &["encrypt", "--env", ""]), "local\t.env.local\nstaging\t.env.staging\n");
+    assert_eq!(
+        complete(temp_dir.path(), &["decrypt", "--env", ""]),
+        "production\t.env.production.encrypted\nstaging\t.env.staging.encrypted\n",
+    );
+    assert_eq!(complete(temp_dir.path(), &["peek", "--env", "p"]), "production\t.env.production.encrypted\n");
+}
+
+#[test]
+fn test_completions_env_from_keyring() {
+    let temp_dir = create_temp_dir();
+    create_env_files(temp_dir.path());
+    for environment in ["ci", "staging"] {
+        create_command(temp_dir.path())
+            .env("ENVCRYPT_KEYRING_PASSPHRASE", PASSPHRASE)
+            .args(["key", "add", environment, "--generate"])
+            .assert()
+            .success();
+    }
+
+    assert_eq!(
+        complete(temp_dir.path(), &["decrypt", "--env", ""]),
+        "ci\tkeyring\nproduction\t.env.production.encrypted\nstaging\t.env.staging.encrypted\n",
+    );
+    // Options follow the values of a positional argument
+    assert!(complete(temp_dir.path(), &["key", "remove", ""]).starts_with("ci\tkeyring\nstaging\tkeyring\n--"));
+}
+
+#[test]
+fn test_completions_never_prompt_for_locked_keyring() {
+    let temp_dir = create_temp_dir();
+    create_command(temp_dir.path())
+        .env("ENVCRYPT_KEYRING_PASSPHRASE", PASSPHRASE)
+        .args(["key", "add", "ci", "--generate"])
+        .assert()
+        .success();
+
+    create_command(temp_dir.path())
+        .env("_ENVCRYPT_COMPLETE", "fish")
+        .env_remove("ENVCRYPT_KEYRING_PASSPHRASE")
+        .args(["--", "envcrypt", "key", "remove", "c"])
+        .assert()
+        .success()
+        .stdout("");
+}
@@ -0,0 +1,125 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+
+fn encrypt_test_file(temp_dir: &Path) {
+    fs::write(temp_dir.join(".env"), "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir, TEST_KEY).assert().success();
+    fs::remove_file(temp_dir.join(".env")).unwrap();
+}
+
+/// A command whose user configuration directory is `config_home`.
+fn create_user_command(temp_dir: &Path, config_home: &Path) -> assert_cmd::Command {
+    let mut cmd = create_command(temp_dir);
+    cmd.env("XDG_CONFIG_HOME", config_home).env_remove("ENVCRYPT_HOME").env_remove("ENVCRYPT_CONFIG");
+    cmd
+}
+
+fn write_user_config(config_home: &Path, content: &str) {
+    fs::create_dir_all(config_home.join("envcrypt")).unwrap();
+    fs::write(config_home.join("envcrypt").join("config.toml"), content).unwrap();
+}
+
+#[test]
+fn test_user_config_provides_defaults() {
+    let temp_dir = create_temp_dir();
+    let config_home = create_temp_dir();
+    encrypt_test_file(temp_dir.path());
+    let key_file = config_home.path().join("key.txt");
+    fs::write(&key_file, TEST_KEY).unwrap();
+    write_user_config(config_home.path(), &format!("key_provider = \"file\"\nkey_file = {:?}\n", key_file.display().to_string()));
+
+    create_user_command(temp_dir.path(), config_home.path()).arg("decrypt").assert().success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "APP_KEY=test123");
+}
+
+#[test]
+fn test_envcrypt_home_holds_user_config() {
+    let temp_dir = create_temp_dir();
+    let envcrypt_home = create_temp_dir();
+    encrypt_test_file(temp_dir.path());
+    let key_file = envcrypt_home.path().join("key.txt");
+    fs::write(&key_file, TEST_KEY).unwrap();
+    fs::write(envcrypt_home.path().join("config.toml"), format!("key_provider = \"file\"\nkey_file = {:?}\n", key_file.display().to_string())).unwrap();
+
+    // ENVCRYPT_HOME takes precedence over XDG_CONFIG_HOME
+    let mut cmd = create_user_command(temp_dir.path(), &temp_dir.path().join("unused"));
+    cmd.env("ENVCRYPT_HOME", envcrypt_home.path()).arg("decrypt");
+    cmd.assert().success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "APP_KEY=test123");
+}
+
+#[test]
+fn test_project_config_overrides_user_config() {
+    let temp_dir = create_temp_dir();
+    let config_home = create_temp_dir();
+    encrypt_test_file(temp_dir.path());
+    fs::write(temp_dir.path().join("key.txt"), TEST_KEY).unwrap();
+    write_user_config(config_home.path(), "key_provider = \"file\"\nkey_file = \"missing.txt\"\n");
+    fs::write(temp_dir.path().join("envcrypt.toml"), "key_file = \"key.txt\"\n").unwrap();
+
+    create_user_command(temp_dir.path(), config_home.path()).arg("decrypt").assert().success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "APP_KEY=test123");
+}
+
+#[test]
+fn test_config_flag_and_env_var_replace_project_config() {
+    let temp_dir = create_temp_dir();
+    let config_home = create_temp_dir();
+    encrypt_test_file(temp_dir.path());
+    fs::write(temp_dir.path().join("key.txt"), TEST_KEY).unwrap();
+    fs::write(temp_dir.path().join("envcrypt.toml"), "key_provider = \"env\"\n").unwrap();
+    fs::write(temp_dir.path().join("local.toml"), "key_provider = \"file\"\nkey_file = \"key.txt\"\n").unwrap();
+
+    create_user_command(temp_dir.path(), config_home.path())
+        .args(["decrypt", "--config", "local.toml"])
+        .env_remove("ENVCRYPT_KEY")
+        .assert()
+        .success();
+    create_user_command(temp_dir.path(), config_home.path())
+        .args(["decrypt", "--force"])
+        .env("ENVCRYPT_CONFIG", "local.toml")
+        .env_remove("ENVCRYPT_KEY")
+        .assert()
+        .success();
+    create_user_command(temp_dir.path(), config_home.path())
+        .args(["decrypt", "--force", "--config", "missing.toml"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Configuration file missing.toml not found"));
+}
+
+#[test]
+fn test_calibrate_writes_to_config_flag_file() {
+    let temp_dir = create_temp_dir();
+    let config_home = create_temp_dir();
+    write_user_config(config_home.path(), "aad = \"personal\"\n");
+    fs::write(temp_dir.path().join("ci.toml"), "key_provider = \"env\"\n").unwrap();
+
+    create_user_command(temp_dir.path(), config_home.path())
+        .args(["calibrate", "--target-ms", "5", "--config", "ci.toml"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Saved kdf_iterations = ").and(predicate::str::contains(" to ci.toml")));
+
+    let config: toml::Table = fs::read_to_string(temp_dir.path().join("ci.toml")).unwrap().parse().unwrap();
+    assert!(config["kdf_iterations"].is_integer());
+    assert_eq!(config["key_provider"].as_str(), Some("env"));
+    assert!(!config.contains_key("aad"));
+    assert!(!temp_dir.path().join("envcrypt.toml").exists());
+}
+
+#[test]
+fn test_invalid_user_config_is_reported() {
+    let temp_dir = create_temp_dir();
+    let config_home = create_temp_dir();
+    write_user_config(config_home.path(), "editor = \"vim\"\n");
+
+    create_user_command(temp_dir.path(), config_home.path())
+        .args(["calibrate", "--target-ms", "5", "--no-save"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid configuration in").and(predicate::str::contains("config.toml")))
+        .stderr(predicate::str::contains("editor"));
+}
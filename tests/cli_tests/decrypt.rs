@@ -20,5 +20,180 @@ fn test_decrypt_with_wrong_key_fails() {
     let mut cmd = create_decrypt_command(temp_dir.path(), "wrong-key");
     cmd.assert()
         .failure()
-        .stderr(predicates::str::contains("MAC verification failed").or(predicates::str::contains("Decryption failed")));
+        .stderr(predicate::str::contains("Authentication failed"));
+}
+
+#[test]
+fn test_decrypt_failures_share_one_message() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    let encrypted_path = temp_dir.path().join(".env.encrypted");
+    std::fs::write(&env_path, "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--cipher").arg("AES-256-CBC");
+    cmd.assert().success();
+    std::fs::remove_file(&env_path).unwrap();
+
+    let wrong_key = create_decrypt_command(temp_dir.path(), "wrong-key")
+        .assert()
+        .failure()
+        .get_output()
+        .stderr
+        .clone();
+
+    // Flip a bit in the last byte of the ciphertext (part of the MAC)
+    use base64::Engine;
+    let engine = base64::engine::general_purpose::STANDARD;
+    let mut data = engine.decode(std::fs::read_to_string(&encrypted_path).unwrap().trim()).unwrap();
+    *data.last_mut().unwrap() ^= 1;
+    std::fs::write(&encrypted_path, engine.encode(&data)).unwrap();
+
+    let tampered = create_decrypt_command(temp_dir.path(), TEST_KEY)
+        .assert()
+        .failure()
+        .get_output()
+        .stderr
+        .clone();
+
+    assert_eq!(wrong_key, tampered);
+    assert!(String::from_utf8_lossy(&tampered).contains("Authentication failed"));
+}
+
+#[test]
+fn test_decrypt_failure_detail_at_debug_verbosity() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    std::fs::write(&env_path, "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    std::fs::remove_file(&env_path).unwrap();
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), "wrong-key");
+    cmd.arg("-vvv");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("[DEBUG] Decryption failed:"))
+        .stderr(predicate::str::contains("Authentication failed"));
+}
+
+#[test]
+fn test_decrypt_check_matches() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    std::fs::write(&env_path, "APP_KEY=test123\nDB_PASSWORD=secret456\n").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--check");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(".env matches .env.encrypted"));
+}
+
+#[test]
+fn test_decrypt_check_reports_drift_without_values() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    std::fs::write(&env_path, "APP_KEY=test123\nDB_PASSWORD=secret456\nOLD=1\n").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+
+    let drifted = "APP_KEY=test123\nDB_PASSWORD=changed789\nNEW=2\n";
+    std::fs::write(&env_path, drifted).unwrap();
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--check");
+    cmd.assert()
+        .failure()
+        .code(1)
+        .stderr(predicate::str::contains(".env differs from .env.encrypted (3 variables differ)"))
+        .stderr(predicate::str::contains("DB_PASSWORD: value differs"))
+        .stderr(predicate::str::contains("OLD: only in .env.encrypted"))
+        .stderr(predicate::str::contains("NEW: only in .env"))
+        .stderr(predicate::str::contains("secret456").not())
+        .stderr(predicate::str::contains("changed789").not());
+
+    // Nothing is written
+    assert_eq!(std::fs::read_to_string(&env_path).unwrap(), drifted);
+}
+
+#[test]
+fn test_decrypt_check_formatting_and_missing_file() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    std::fs::write(&env_path, "APP_KEY=test123\n").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+
+    std::fs::write(&env_path, "# comment\nAPP_KEY=test123\n").unwrap();
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--check");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("only in comments, formatting or order"));
+
+    std::fs::remove_file(&env_path).unwrap();
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--check");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(".env does not exist"));
+    assert!(!env_path.exists());
+}
+
+#[test]
+fn test_decrypt_tries_each_key() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    std::fs::write(&env_path, "APP_KEY=test123\n").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    std::fs::remove_file(&env_path).unwrap();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.args(["decrypt", "--key", "old-key", "--key", TEST_KEY, "--no-interaction"]);
+    cmd.assert().success();
+    assert_eq!(std::fs::read_to_string(&env_path).unwrap(), "APP_KEY=test123\n");
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.args(["decrypt", "--key", "old-key", "--key", "older-key", "--no-interaction", "--force"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Authentication failed"));
+}
+
+#[test]
+fn test_decrypt_tries_raw_key_after_passphrase() {
+    const RAW_KEY: &str = "base64:q83vEjRWeJCrze8SNFZ4kKvN7xI0VniQq83vEjRWeJA=";
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    std::fs::write(&env_path, "APP_KEY=test123\n").unwrap();
+    create_encrypt_command(temp_dir.path(), RAW_KEY).arg("--raw-key").assert().success();
+    std::fs::remove_file(&env_path).unwrap();
+
+    // The passphrase cannot be used as a raw key, but that does not stop the next key
+    let mut cmd = create_command(temp_dir.path());
+    cmd.args(["decrypt", "--key", "old-passphrase", "--key", RAW_KEY, "--no-interaction"]);
+    cmd.assert().success();
+    assert_eq!(std::fs::read_to_string(&env_path).unwrap(), "APP_KEY=test123\n");
+}
+
+#[test]
+fn test_decrypt_with_key_file() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    std::fs::write(&env_path, "APP_KEY=test123\n").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    std::fs::remove_file(&env_path).unwrap();
+
+    let keys_path = temp_dir.path().join("keys.txt");
+    std::fs::write(&keys_path, format!("# old key, retired in March\nold-key\n\n{}\n", TEST_KEY)).unwrap();
+    let mut cmd = create_command(temp_dir.path());
+    cmd.args(["decrypt", "--key-file", "keys.txt", "--no-interaction"]);
+    cmd.assert().success();
+    assert_eq!(std::fs::read_to_string(&env_path).unwrap(), "APP_KEY=test123\n");
+
+    std::fs::write(&keys_path, "# nothing here\n").unwrap();
+    let mut cmd = create_command(temp_dir.path());
+    cmd.args(["decrypt", "--key-file", "keys.txt", "--no-interaction", "--force"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Key file keys.txt lists no keys"));
 }
@@ -0,0 +1,74 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+
+#[test]
+fn test_dry_run_encrypt_writes_nothing() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    fs::write(&env_path, "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--dry-run").arg("--prune");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("[dry-run] Would write .env.encrypted"))
+        .stdout(predicate::str::contains("[dry-run] Would delete .env"))
+        .stdout(predicate::str::contains("Encryption key:").not());
+
+    assert!(env_path.exists());
+    assert!(!temp_dir.path().join(".env.encrypted").exists());
+}
+
+#[test]
+fn test_dry_run_decrypt_reports_overwrite_and_backup() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    fs::write(&env_path, "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    fs::write(&env_path, "APP_KEY=modified").unwrap();
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--dry-run").arg("--force").arg("--backup");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("[dry-run] Would back up .env to .env.bak"))
+        .stdout(predicate::str::contains("[dry-run] Would overwrite .env"));
+
+    assert_eq!(fs::read_to_string(&env_path).unwrap(), "APP_KEY=modified");
+    assert!(!temp_dir.path().join(".env.bak").exists());
+}
+
+#[test]
+fn test_dry_run_decrypt_checks_key() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    fs::write(&env_path, "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    fs::remove_file(&env_path).unwrap();
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), "wrong-key");
+    cmd.arg("--dry-run");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Authentication failed"));
+
+    assert!(!env_path.exists());
+}
+
+#[test]
+fn test_dry_run_recrypt_leaves_file_untouched() {
+    let temp_dir = create_temp_dir();
+    let encrypted_path = temp_dir.path().join(".env.encrypted");
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    let before = fs::read_to_string(&encrypted_path).unwrap();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("recrypt").arg("--key").arg(TEST_KEY).arg("--to-cipher").arg("AES-256-GCM").arg("--dry-run");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("[dry-run] Would re-encrypt .env.encrypted with AES-256-GCM"));
+
+    assert_eq!(fs::read_to_string(&encrypted_path).unwrap(), before);
+}
@@ -0,0 +1,97 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+
+#[test]
+fn test_byte_order_mark_is_accepted_and_kept() {
+    let temp_dir = create_temp_dir();
+    let contents = "\u{feff}# Saved by Notepad\nAPP_KEY=test123\n";
+    fs::write(temp_dir.path().join(".env"), contents).unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+
+    create_command(temp_dir.path())
+        .args(["export", "--format", "json", "--key", TEST_KEY])
+        .assert()
+        .success()
+        .stdout("{\n  \"APP_KEY\": \"test123\"\n}\n");
+    create_command(temp_dir.path())
+        .args(["example", "--stdout", "--key", TEST_KEY])
+        .assert()
+        .success()
+        .stdout("\u{feff}# Saved by Notepad\nAPP_KEY=\n");
+
+    fs::remove_file(temp_dir.path().join(".env")).unwrap();
+    create_decrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), contents);
+}
+
+#[test]
+fn test_invalid_utf8_error_gives_position() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), b"APP_KEY=test123\nNAME=Jos\xe9\n").unwrap();
+
+    create_encrypt_command(temp_dir.path(), TEST_KEY)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("not valid UTF-8: byte 0xE9 at line 2, column 9 (byte offset 24)"))
+        .stderr(predicate::str::contains("pass --encoding latin1"));
+    create_command(temp_dir.path())
+        .arg("lint")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(".env is not valid UTF-8: byte 0xE9 at line 2"));
+    create_command(temp_dir.path())
+        .args(["lint", "--encoding", "latin1"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_utf16_file_is_recognized() {
+    let temp_dir = create_temp_dir();
+    let utf16: Vec<u8> = [0xff, 0xfe].into_iter().chain("A=1\n".encode_utf16().flat_map(u16::to_le_bytes)).collect();
+    fs::write(temp_dir.path().join(".env"), utf16).unwrap();
+
+    create_encrypt_command(temp_dir.path(), TEST_KEY)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("the file is UTF-16 encoded; save it as UTF-8"));
+}
+
+#[test]
+fn test_latin1_encrypt_and_decrypt() {
+    let temp_dir = create_temp_dir();
+    let latin1 = b"NAME=Jos\xe9\nCITY=M\xfcnchen\n";
+    fs::write(temp_dir.path().join(".env"), latin1).unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).args(["--encoding", "latin1"]).assert().success();
+
+    // The encrypted file holds UTF-8
+    create_command(temp_dir.path())
+        .args(["export", "--key", TEST_KEY])
+        .assert()
+        .success()
+        .stdout("NAME=\"José\"\nCITY=\"München\"\n");
+    create_decrypt_command(temp_dir.path(), TEST_KEY).arg("--force").assert().success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "NAME=José\nCITY=München\n");
+
+    create_decrypt_command(temp_dir.path(), TEST_KEY).args(["--force", "--encoding", "latin1"]).assert().success();
+    assert_eq!(fs::read(temp_dir.path().join(".env")).unwrap(), latin1);
+    create_decrypt_command(temp_dir.path(), TEST_KEY)
+        .args(["--check", "--encoding", "latin1"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_latin1_decrypt_refuses_unrepresentable_characters() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "PRICE=5€\n").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).arg("--prune").assert().success();
+
+    create_decrypt_command(temp_dir.path(), TEST_KEY)
+        .args(["--encoding", "latin1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Cannot write .env: U+20AC at line 1, column 8 cannot be written as Latin-1"));
+    assert!(!temp_dir.path().join(".env").exists());
+}
@@ -44,3 +44,35 @@ fn test_default_cipher() {
     let decrypted_content = fs::read_to_string(&env_path).unwrap();
     assert_eq!(decrypted_content, original_content);
 }
+
+#[test]
+fn test_encrypt_refuses_encrypted_input() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+
+    for format in ["armor", "binary"] {
+        let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+        cmd.arg("--format").arg(format).arg("--force").arg("--input").arg(".env.encrypted");
+        cmd.assert()
+            .failure()
+            .stderr(predicates::str::contains("Input .env.encrypted already appears encrypted; use --allow-double-encrypt to override"));
+    }
+    assert!(!temp_dir.path().join(".env.encrypted.encrypted").exists());
+}
+
+#[test]
+fn test_encrypt_allow_double_encrypt() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    let encrypted = fs::read(temp_dir.path().join(".env.encrypted")).unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--input").arg(".env.encrypted").arg("--allow-double-encrypt");
+    cmd.assert().success();
+
+    // The input is left alone and the result goes next to it
+    assert_eq!(fs::read(temp_dir.path().join(".env.encrypted")).unwrap(), encrypted);
+    assert!(temp_dir.path().join(".env.encrypted.encrypted").exists());
+}
@@ -0,0 +1,143 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+
+#[test]
+fn test_assume_yes_overwrites_existing_output() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    fs::write(temp_dir.path().join(".env.encrypted"), "old").unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.env("ENVCRYPT_ASSUME_YES", "1");
+    cmd.assert().success();
+    assert_ne!(fs::read_to_string(temp_dir.path().join(".env.encrypted")).unwrap(), "old");
+}
+
+#[test]
+fn test_assume_yes_off_keeps_existing_output() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    fs::write(temp_dir.path().join(".env.encrypted"), "old").unwrap();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("--no-interaction").arg("encrypt").arg("--key").arg(TEST_KEY).env("ENVCRYPT_ASSUME_YES", "0");
+    cmd.assert().failure().stderr(predicate::str::contains("already exists"));
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env.encrypted")).unwrap(), "old");
+}
+
+#[test]
+fn test_yes_flag_overwrites_existing_output() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    for flag in ["--yes", "-y", "--assume-yes"] {
+        fs::write(temp_dir.path().join(".env.encrypted"), "old").unwrap();
+
+        let mut cmd = create_command(temp_dir.path());
+        cmd.args(["--no-interaction", flag, "encrypt", "--key", TEST_KEY]).env_remove("ENVCRYPT_ASSUME_YES");
+        cmd.assert().success();
+        assert_ne!(fs::read_to_string(temp_dir.path().join(".env.encrypted")).unwrap(), "old");
+    }
+}
+
+#[test]
+fn test_invalid_assume_yes_is_rejected() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    fs::write(temp_dir.path().join(".env.encrypted"), "old").unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.env("ENVCRYPT_ASSUME_YES", "maybe");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid value for ENVCRYPT_ASSUME_YES: maybe"));
+}
+
+#[test]
+fn test_key_choice_generate_skips_menu() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("encrypt").env("ENVCRYPT_KEY_CHOICE", "generate");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Generated new encryption key"))
+        .stdout(predicate::str::contains("Select encryption key option").not());
+    assert!(temp_dir.path().join(".env.encrypted").exists());
+}
+
+#[test]
+fn test_invalid_key_choice_is_rejected() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("encrypt").env("ENVCRYPT_KEY_CHOICE", "random");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid value for ENVCRYPT_KEY_CHOICE: random (expected generate or custom)"));
+}
+
+#[test]
+fn test_force_and_no_interaction_from_environment() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    fs::write(temp_dir.path().join(".env.encrypted"), "old").unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.env("ENVCRYPT_NO_INTERACTION", "true").env("ENVCRYPT_FORCE", "1");
+    cmd.assert().success();
+    assert_ne!(fs::read_to_string(temp_dir.path().join(".env.encrypted")).unwrap(), "old");
+}
+
+#[test]
+fn test_command_line_overrides_environment() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.args(["--quiet", "encrypt", "--key", TEST_KEY, "--cipher", "AES-256-GCM"])
+        .env("ENVCRYPT_NO_INTERACTION", "1")
+        .env("ENVCRYPT_SILENT", "off")
+        .env("ENVCRYPT_CIPHER", "CHACHA20-POLY1305");
+    cmd.assert().success();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("inspect");
+    cmd.assert().success().stdout(predicate::str::contains("AES-256-GCM"));
+}
+
+#[test]
+fn test_cipher_from_environment() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--no-interaction").env("ENVCRYPT_CIPHER", "CHACHA20-POLY1305");
+    cmd.assert().success();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("inspect");
+    cmd.assert().success().stdout(predicate::str::contains("CHACHA20-POLY1305"));
+
+    // Decryption takes the cipher from the header, not from ENVCRYPT_CIPHER
+    fs::remove_file(temp_dir.path().join(".env")).unwrap();
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.env("ENVCRYPT_CIPHER", "AES-256-CBC");
+    cmd.assert().success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "APP_KEY=test123");
+}
+
+#[test]
+fn test_invalid_global_option_variable_is_rejected() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.env("ENVCRYPT_FORCE", "maybe");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value 'maybe' for '--force'"));
+    assert!(!temp_dir.path().join(".env.encrypted").exists());
+}
@@ -1,4 +1,5 @@
 use crate::common::*;
+use std::fs;
 
 #[test]
 fn test_encrypt_with_nonexistent_input_fails() {
@@ -23,3 +24,31 @@ fn test_decrypt_with_nonexistent_input_fails() {
         .failure()
         .stderr(predicates::str::contains("file not found"));
 }
+
+#[test]
+fn test_decrypt_plaintext_input_suggests_encrypt() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123\nDEBUG=true\n").unwrap();
+    fs::write(temp_dir.path().join(".env.encrypted"), "APP_KEY=test123\n").unwrap();
+    fs::write(temp_dir.path().join(".env.production.encrypted"), "irrelevant").unwrap();
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--input").arg(".env").arg("--force");
+    cmd.assert()
+        .failure()
+        .stderr(predicates::str::contains(".env looks unencrypted - did you mean encrypt?"))
+        .stderr(predicates::str::contains("Encrypted files nearby: --input .env.encrypted, --input .env.production.encrypted"));
+}
+
+#[test]
+fn test_decrypt_missing_input_suggests_nearby_files() {
+    let temp_dir = create_temp_dir();
+    let config_dir = create_subdir(temp_dir.path(), "config");
+    fs::write(config_dir.join(".env.staging.encrypted"), "irrelevant").unwrap();
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--input").arg("config/.env.encrypted");
+    cmd.assert()
+        .failure()
+        .stderr(predicates::str::contains("config/.env.encrypted file not found. Encrypted files nearby: --input config/.env.staging.encrypted"));
+}
@@ -0,0 +1,76 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+
+fn encrypt(temp_dir: &Path, content: &str) {
+    fs::write(temp_dir.join(".env"), content).unwrap();
+    create_encrypt_command(temp_dir, TEST_KEY).arg("--force").assert().success();
+}
+
+fn create_example_command(temp_dir: &Path) -> assert_cmd::Command {
+    let mut cmd = create_command(temp_dir);
+    cmd.arg("example").arg("--key").arg(TEST_KEY);
+    cmd
+}
+
+#[test]
+fn test_example_keeps_comments_without_values() {
+    let temp_dir = create_temp_dir();
+    encrypt(temp_dir.path(), "# Database\nDB_PASSWORD=\"s3cret\" # rotated monthly\n\nexport API_TOKEN=abc123\n");
+
+    create_example_command(temp_dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Wrote .env.example from .env.encrypted"));
+    let example = fs::read_to_string(temp_dir.path().join(".env.example")).unwrap();
+    assert_eq!(example, "# Database\nDB_PASSWORD= # rotated monthly\n\nexport API_TOKEN=\n");
+
+    // The example describes the file it was made from
+    create_command(temp_dir.path())
+        .args(["check", "--against", ".env.example", "--key", TEST_KEY])
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_example_check_detects_new_variables() {
+    let temp_dir = create_temp_dir();
+    encrypt(temp_dir.path(), "APP_KEY=one\n");
+    create_example_command(temp_dir.path()).assert().success();
+
+    // An up-to-date example is left alone without asking
+    create_example_command(temp_dir.path())
+        .args(["--check", "--no-interaction"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(".env.example is up to date"));
+
+    encrypt(temp_dir.path(), "APP_KEY=one\nNEW_SECRET=two\n");
+    create_example_command(temp_dir.path())
+        .arg("--check")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(".env.example is out of date with .env.encrypted"));
+    create_example_command(temp_dir.path())
+        .arg("--no-interaction")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Use --force to overwrite"));
+
+    create_example_command(temp_dir.path()).arg("--force").assert().success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env.example")).unwrap(), "APP_KEY=\nNEW_SECRET=\n");
+}
+
+#[test]
+fn test_example_stdout_with_placeholder() {
+    let temp_dir = create_temp_dir();
+    encrypt(temp_dir.path(), "APP_KEY=secret\n");
+
+    create_example_command(temp_dir.path())
+        .args(["--stdout", "--placeholder", "changeme"])
+        .assert()
+        .success()
+        .stdout("APP_KEY=changeme\n");
+    assert!(!temp_dir.path().join(".env.example").exists());
+}
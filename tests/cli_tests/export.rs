@@ -0,0 +1,329 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Installs a fake `docker` in `temp_dir/bin` that keeps secrets as files in
+/// `temp_dir/secrets` and logs its arguments to `temp_dir/docker.log`.
+/// Returns the PATH to run envcrypt with.
+#[cfg(unix)]
+fn install_fake_docker(temp_dir: &Path) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let bin = create_subdir(temp_dir, "bin");
+    create_subdir(temp_dir, "secrets");
+    let script = format!(
+        r#"#!/bin/sh
+echo "$@" >> "{dir}/docker.log"
+secret="{dir}/secrets/$3"
+case "$2" in
+    inspect) test -f "$secret" ;;
+    rm) rm "$secret" ;;
+    create) cat > "{dir}/secrets/$3" ;;
+esac
+"#,
+        dir = temp_dir.display()
+    );
+    let docker = bin.join("docker");
+    fs::write(&docker, script).unwrap();
+    fs::set_permissions(&docker, fs::Permissions::from_mode(0o755)).unwrap();
+    format!("{}:{}", bin.display(), std::env::var("PATH").unwrap_or_default())
+}
+
+fn encrypt_env_file(temp_dir: &Path, content: &str) {
+    fs::write(temp_dir.join(".env"), content).unwrap();
+    create_encrypt_command(temp_dir, TEST_KEY).assert().success();
+}
+
+fn create_export_command(temp_dir: &Path, path: &str) -> assert_cmd::Command {
+    let mut cmd = create_command(temp_dir);
+    cmd.env("PATH", path).arg("export").arg("--target").arg("docker-secrets").arg("--key").arg(TEST_KEY);
+    cmd
+}
+
+fn secret(temp_dir: &Path, name: &str) -> PathBuf {
+    temp_dir.join("secrets").join(name)
+}
+
+#[test]
+#[cfg(unix)]
+fn test_export_docker_secrets_per_variable() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_docker(temp_dir.path());
+    encrypt_env_file(temp_dir.path(), "DB_PASSWORD=\"s3cret value\"\nAPI_TOKEN=abc123 # comment\n");
+
+    let mut cmd = create_export_command(temp_dir.path(), &path);
+    cmd.arg("--prefix").arg("app_");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Exported .env.encrypted to 2 Docker secrets"));
+
+    assert_eq!(fs::read_to_string(secret(temp_dir.path(), "app_DB_PASSWORD")).unwrap(), "s3cret value");
+    assert_eq!(fs::read_to_string(secret(temp_dir.path(), "app_API_TOKEN")).unwrap(), "abc123");
+    // Values are passed on stdin, never as arguments
+    let log = fs::read_to_string(temp_dir.path().join("docker.log")).unwrap();
+    assert!(log.contains("secret create app_DB_PASSWORD -"));
+    assert!(!log.contains("s3cret"));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_export_docker_secrets_replaces_existing() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_docker(temp_dir.path());
+    encrypt_env_file(temp_dir.path(), "DB_PASSWORD=new");
+    fs::write(secret(temp_dir.path(), "DB_PASSWORD"), "old").unwrap();
+
+    create_export_command(temp_dir.path(), &path).assert().success();
+
+    assert_eq!(fs::read_to_string(secret(temp_dir.path(), "DB_PASSWORD")).unwrap(), "new");
+    let log = fs::read_to_string(temp_dir.path().join("docker.log")).unwrap();
+    assert!(log.contains("secret rm DB_PASSWORD"));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_export_docker_secrets_blob() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_docker(temp_dir.path());
+    fs::write(temp_dir.path().join(".env.production"), "APP_KEY=test123\nDEBUG=false\n").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).arg(".env.production").assert().success();
+
+    let mut cmd = create_export_command(temp_dir.path(), &path);
+    cmd.arg(".env.production.encrypted").arg("--blob");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("to 1 Docker secret"));
+
+    assert_eq!(
+        fs::read_to_string(secret(temp_dir.path(), "env.production")).unwrap(),
+        "APP_KEY=test123\nDEBUG=false\n"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_export_docker_secrets_dry_run() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_docker(temp_dir.path());
+    encrypt_env_file(temp_dir.path(), "APP_KEY=test123");
+
+    let mut cmd = create_export_command(temp_dir.path(), &path);
+    cmd.arg("--dry-run").arg("--blob").arg("--name").arg("app_env");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Would create Docker secret app_env"));
+
+    assert!(!secret(temp_dir.path(), "app_env").exists());
+}
+
+#[test]
+#[cfg(unix)]
+fn test_export_docker_secrets_reports_docker_errors() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_docker(temp_dir.path());
+    encrypt_env_file(temp_dir.path(), "APP_KEY=test123");
+    // Make `docker secret create` fail
+    fs::remove_dir(temp_dir.path().join("secrets")).unwrap();
+
+    create_export_command(temp_dir.path(), &path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("docker secret create failed"));
+}
+
+#[test]
+fn test_export_wrong_key_fails() {
+    let temp_dir = create_temp_dir();
+    encrypt_env_file(temp_dir.path(), "APP_KEY=test123");
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("export").arg("--target").arg("docker-secrets").arg("--key").arg("wrong-key");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Authentication failed"));
+}
+
+fn create_format_command(temp_dir: &Path, format: &str) -> assert_cmd::Command {
+    let mut cmd = create_command(temp_dir);
+    cmd.arg("export").arg("--format").arg(format).arg("--key").arg(TEST_KEY);
+    cmd
+}
+
+#[test]
+fn test_export_prints_dotenv_by_default() {
+    let temp_dir = create_temp_dir();
+    encrypt_env_file(temp_dir.path(), "# comment\nexport APP_KEY=test123\nGREETING='hello world'\n");
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("export").arg("--key").arg(TEST_KEY);
+    cmd.assert()
+        .success()
+        .stdout("APP_KEY=test123\nGREETING=\"hello world\"\n");
+}
+
+#[test]
+fn test_export_format_json_and_yaml() {
+    let temp_dir = create_temp_dir();
+    encrypt_env_file(temp_dir.path(), "PORT=8080\nMESSAGE=\"say \\\"hi\\\"\"\n");
+
+    create_format_command(temp_dir.path(), "json")
+        .assert()
+        .success()
+        .stdout("{\n  \"PORT\": \"8080\",\n  \"MESSAGE\": \"say \\\"hi\\\"\"\n}\n");
+    create_format_command(temp_dir.path(), "yaml")
+        .assert()
+        .success()
+        .stdout("PORT: \"8080\"\nMESSAGE: \"say \\\"hi\\\"\"\n");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_export_format_shell_can_be_evaluated() {
+    let temp_dir = create_temp_dir();
+    encrypt_env_file(temp_dir.path(), "QUOTES=\"it's \\\"quoted\\\" \\$HOME\"\nSIMPLE=value\n");
+
+    let output = create_format_command(temp_dir.path(), "shell").output().unwrap();
+    assert!(output.status.success());
+    let script = format!("{}printf '%s|%s' \"$QUOTES\" \"$SIMPLE\"", String::from_utf8(output.stdout).unwrap());
+    let evaluated = std::process::Command::new("sh").arg("-c").arg(script).output().unwrap();
+    assert_eq!(String::from_utf8(evaluated.stdout).unwrap(), "it's \"quoted\" $HOME|value");
+}
+
+#[test]
+fn test_export_format_conflicts_with_target() {
+    let temp_dir = create_temp_dir();
+    encrypt_env_file(temp_dir.path(), "APP_KEY=test123");
+
+    let mut cmd = create_format_command(temp_dir.path(), "json");
+    cmd.arg("--target").arg("docker-secrets");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[test]
+fn test_export_format_quiet_still_prints_variables() {
+    let temp_dir = create_temp_dir();
+    encrypt_env_file(temp_dir.path(), "APP_KEY=test123");
+
+    let mut cmd = create_format_command(temp_dir.path(), "dotenv");
+    cmd.arg("--quiet");
+    cmd.assert()
+        .success()
+        .stdout("APP_KEY=test123\n");
+}
+
+fn create_github_actions_command(temp_dir: &Path) -> assert_cmd::Command {
+    let mut cmd = create_command(temp_dir);
+    cmd.env("GITHUB_ENV", temp_dir.join("github_env"))
+        .arg("export")
+        .arg("--target")
+        .arg("github-actions")
+        .arg("--key")
+        .arg(TEST_KEY);
+    cmd
+}
+
+#[test]
+fn test_export_github_actions_masks_and_appends() {
+    let temp_dir = create_temp_dir();
+    encrypt_env_file(temp_dir.path(), "DB_PASSWORD=s3cret\nCERT=\"line one\\nline two\"\n");
+    fs::write(temp_dir.path().join("github_env"), "EXISTING=1\n").unwrap();
+
+    let output = create_github_actions_command(temp_dir.path()).output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("::add-mask::s3cret\n::add-mask::line one%0Aline two\n::add-mask::line one\n::add-mask::line two\n"));
+    assert!(stdout.contains("Exported .env.encrypted to 2 GitHub Actions variables"));
+
+    let github_env = fs::read_to_string(temp_dir.path().join("github_env")).unwrap();
+    let lines: Vec<&str> = github_env.lines().collect();
+    assert_eq!(lines[0], "EXISTING=1");
+    let delimiter = lines[1].strip_prefix("DB_PASSWORD<<").unwrap();
+    assert_eq!(&lines[2..4], ["s3cret", delimiter]);
+    let delimiter = lines[4].strip_prefix("CERT<<").unwrap();
+    assert_eq!(&lines[5..], ["line one", "line two", delimiter]);
+}
+
+#[test]
+fn test_export_github_actions_blob_requires_variable_name() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env.my-app"), "APP_KEY=test123\n").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).arg(".env.my-app").assert().success();
+
+    let mut cmd = create_github_actions_command(temp_dir.path());
+    cmd.arg(".env.my-app.encrypted").arg("--blob");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("env.my-app is not a valid variable name for GitHub Actions; choose another one with --name"));
+
+    let mut cmd = create_github_actions_command(temp_dir.path());
+    cmd.arg(".env.my-app.encrypted").arg("--blob").arg("--name").arg("DOTENV");
+    cmd.assert().success();
+    let github_env = fs::read_to_string(temp_dir.path().join("github_env")).unwrap();
+    assert!(github_env.starts_with("DOTENV<<ENVCRYPT_EOF_"));
+    assert!(github_env.contains("\nAPP_KEY=test123\n"));
+}
+
+#[test]
+fn test_export_github_actions_requires_github_env() {
+    let temp_dir = create_temp_dir();
+    encrypt_env_file(temp_dir.path(), "APP_KEY=test123");
+
+    let mut cmd = create_github_actions_command(temp_dir.path());
+    cmd.env_remove("GITHUB_ENV");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("GITHUB_ENV is not set"))
+        .stdout(predicate::str::contains("add-mask").not());
+}
+
+#[test]
+fn test_export_github_actions_dry_run() {
+    let temp_dir = create_temp_dir();
+    encrypt_env_file(temp_dir.path(), "APP_KEY=test123");
+
+    let mut cmd = create_github_actions_command(temp_dir.path());
+    cmd.arg("--dry-run");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Would set GitHub Actions variable APP_KEY"))
+        .stdout(predicate::str::contains("test123").not());
+    assert!(!temp_dir.path().join("github_env").exists());
+}
+
+#[test]
+fn test_export_expands_references() {
+    let temp_dir = create_temp_dir();
+    encrypt_env_file(temp_dir.path(), "HOST=db\nPORT=5432\nURL=postgres://${HOST}:$PORT/${DB_NAME:-app}\nLITERAL='$HOST'\n");
+
+    create_format_command(temp_dir.path(), "dotenv")
+        .env("PORT", "1")
+        .assert()
+        .success()
+        .stdout("HOST=db\nPORT=5432\nURL=postgres://db:5432/app\nLITERAL=\"\\$HOST\"\n");
+
+    let mut cmd = create_format_command(temp_dir.path(), "json");
+    cmd.arg("--no-expand");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"URL\": \"postgres://${HOST}:$PORT/${DB_NAME:-app}\""));
+}
+
+#[test]
+fn test_export_expands_from_environment() {
+    let temp_dir = create_temp_dir();
+    encrypt_env_file(temp_dir.path(), "TOKEN=${CI_TOKEN:?CI_TOKEN must be set}\n");
+
+    create_format_command(temp_dir.path(), "dotenv")
+        .env("CI_TOKEN", "abc")
+        .assert()
+        .success()
+        .stdout("TOKEN=abc\n");
+    create_format_command(temp_dir.path(), "dotenv")
+        .env_remove("CI_TOKEN")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("CI_TOKEN must be set for TOKEN: CI_TOKEN must be set"));
+}
@@ -0,0 +1,174 @@
+#![cfg(all(feature = "fido2", unix))]
+
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+
+const CREDENTIAL: &str = "Y3JlZGVudGlhbC1pZA==";
+
+/// Installs fake libfido2 tools in `temp_dir/bin` that log their arguments to
+/// `temp_dir/fido2.log`. The fake token's "HMAC secret" is the salt itself, so the same
+/// challenge always yields the same key. Returns the PATH to run envcrypt with.
+fn install_fake_fido2(temp_dir: &Path) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let bin = create_subdir(temp_dir, "bin");
+    let log = temp_dir.join("fido2.log");
+    let scripts = [
+        ("fido2-token", format!(
+            "#!/bin/sh\necho \"fido2-token $@\" >> \"{}\"\necho '/dev/hidraw7: vendor=0x1050, product=0x0407 (Yubico YubiKey OTP+FIDO+CCID)'\n",
+            log.display()
+        )),
+        ("fido2-cred", format!(
+            "#!/bin/sh\necho \"fido2-cred $@\" >> \"{}\"\nread cdh; read rp; read user; read id\nprintf '%s\\n%s\\npacked\\nYXV0aGRhdGE=\\n{}\\nc2ln\\n' \"$cdh\" \"$rp\"\n",
+            log.display(),
+            CREDENTIAL
+        )),
+        ("fido2-assert", format!(
+            "#!/bin/sh\necho \"fido2-assert $@\" >> \"{}\"\nread cdh; read rp; read cred; read salt\necho \"credential $cred\" >> \"{}\"\nprintf '%s\\n%s\\nYXV0aGRhdGE=\\nc2ln\\n%s\\n' \"$cdh\" \"$rp\" \"$salt\"\n",
+            log.display(),
+            log.display()
+        )),
+    ];
+    for (name, script) in scripts {
+        let path = bin.join(name);
+        fs::write(&path, script).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    format!("{}:{}", bin.display(), std::env::var("PATH").unwrap_or_default())
+}
+
+fn configure_credential(temp_dir: &Path) {
+    fs::write(temp_dir.join("envcrypt.toml"), format!("fido2_credential = \"{}\"\n", CREDENTIAL)).unwrap();
+}
+
+#[test]
+fn test_fido2_enroll_saves_credential() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_fido2(temp_dir.path());
+
+    create_command(temp_dir.path())
+        .env("PATH", &path)
+        .args(["key", "fido2-enroll"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Saved fido2_credential"));
+    let config = fs::read_to_string(temp_dir.path().join("envcrypt.toml")).unwrap();
+    assert!(config.contains(CREDENTIAL), "{}", config);
+    let log = fs::read_to_string(temp_dir.path().join("fido2.log")).unwrap();
+    assert!(log.contains("fido2-cred -M -h /dev/hidraw7"), "{}", log);
+
+    // An existing credential is only replaced with --force
+    create_command(temp_dir.path())
+        .env("PATH", &path)
+        .args(["key", "fido2-enroll", "--device", "/dev/hidraw3"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("already has a fido2_credential"));
+    create_command(temp_dir.path())
+        .env("PATH", &path)
+        .args(["key", "fido2-enroll", "--device", "/dev/hidraw3", "--force"])
+        .assert()
+        .success();
+    let log = fs::read_to_string(temp_dir.path().join("fido2.log")).unwrap();
+    assert!(log.contains("fido2-cred -M -h /dev/hidraw3"), "{}", log);
+}
+
+#[test]
+fn test_fido2_roundtrip() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_fido2(temp_dir.path());
+    configure_credential(temp_dir.path());
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    create_command(temp_dir.path())
+        .env("PATH", &path)
+        .args(["encrypt", "--key-provider", "fido2", "--no-interaction"])
+        .assert()
+        .success();
+    let log = fs::read_to_string(temp_dir.path().join("fido2.log")).unwrap();
+    assert!(log.contains("fido2-assert -G -h /dev/hidraw7"), "{}", log);
+    assert!(log.contains(&format!("credential {}", CREDENTIAL)), "{}", log);
+
+    create_command(temp_dir.path())
+        .arg("inspect")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("derived by a hardware token"));
+
+    fs::remove_file(temp_dir.path().join(".env")).unwrap();
+    create_command(temp_dir.path())
+        .env("PATH", &path)
+        .args(["decrypt", "--key-provider", "fido2", "--no-interaction"])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "APP_KEY=test123");
+}
+
+#[test]
+fn test_fido2_recrypt_keeps_challenge() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_fido2(temp_dir.path());
+    fs::write(
+        temp_dir.path().join("envcrypt.toml"),
+        format!("fido2_credential = \"{}\"\nfido2_device = \"/dev/hidraw2\"\nkey_provider = \"fido2\"\n", CREDENTIAL),
+    ).unwrap();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_command(temp_dir.path())
+        .env("PATH", &path)
+        .args(["encrypt", "--no-interaction"])
+        .assert()
+        .success();
+
+    create_command(temp_dir.path())
+        .env("PATH", &path)
+        .args(["recrypt", "--to-cipher", "AES-256-GCM", "--no-interaction"])
+        .assert()
+        .success();
+    let log = fs::read_to_string(temp_dir.path().join("fido2.log")).unwrap();
+    assert!(log.contains("fido2-assert -G -h /dev/hidraw2"), "{}", log);
+    assert!(!log.contains("fido2-token"), "{}", log);
+
+    fs::remove_file(temp_dir.path().join(".env")).unwrap();
+    create_command(temp_dir.path())
+        .env("PATH", &path)
+        .args(["decrypt", "--no-interaction"])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "APP_KEY=test123");
+}
+
+#[test]
+fn test_fido2_requires_credential() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_fido2(temp_dir.path());
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    create_command(temp_dir.path())
+        .env("PATH", &path)
+        .args(["encrypt", "--key-provider", "fido2", "--no-interaction"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("requires fido2_credential"));
+}
+
+#[test]
+fn test_fido2_decrypt_without_challenge_fails() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_fido2(temp_dir.path());
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_command(temp_dir.path())
+        .args(["encrypt", "--key", TEST_KEY, "--no-interaction"])
+        .assert()
+        .success();
+    fs::remove_file(temp_dir.path().join(".env")).unwrap();
+    configure_credential(temp_dir.path());
+
+    create_command(temp_dir.path())
+        .env("PATH", &path)
+        .args(["decrypt", "--key-provider", "fido2", "--no-interaction"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no hardware token challenge"));
+}
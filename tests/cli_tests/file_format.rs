@@ -0,0 +1,79 @@
+use crate::common::*;
+use base64::Engine;
+use std::fs;
+
+#[test]
+fn test_binary_format_roundtrip() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    let encrypted_path = temp_dir.path().join(".env.encrypted");
+    let original_content = "APP_KEY=test123\nDB_PASSWORD=secret456";
+    fs::write(&env_path, original_content).unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--format").arg("binary");
+    cmd.assert().success();
+
+    let data = fs::read(&encrypted_path).unwrap();
+    assert!(data.starts_with(b"ENVC"));
+
+    fs::remove_file(&env_path).unwrap();
+    create_decrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    assert_eq!(fs::read_to_string(&env_path).unwrap(), original_content);
+}
+
+#[test]
+fn test_binary_format_is_smaller_than_armor() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123\n".repeat(20)).unwrap();
+    let encrypted_path = temp_dir.path().join(".env.encrypted");
+
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    let armor = fs::read(&encrypted_path).unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--format").arg("binary").arg("--force");
+    cmd.assert().success();
+    let binary = fs::read(&encrypted_path).unwrap();
+
+    assert_eq!(base64::engine::general_purpose::STANDARD.decode(&armor).unwrap().len(), binary.len());
+    assert!(binary.len() < armor.len());
+}
+
+#[test]
+fn test_decrypt_accepts_wrapped_armor() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    let encrypted_path = temp_dir.path().join(".env.encrypted");
+    fs::write(&env_path, "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+
+    let armor = fs::read_to_string(&encrypted_path).unwrap();
+    let wrapped: Vec<&str> = armor.as_bytes().chunks(64).map(|line| std::str::from_utf8(line).unwrap()).collect();
+    fs::write(&encrypted_path, wrapped.join("\n") + "\n").unwrap();
+
+    fs::remove_file(&env_path).unwrap();
+    create_decrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    assert_eq!(fs::read_to_string(&env_path).unwrap(), "APP_KEY=test123");
+}
+
+#[test]
+fn test_recrypt_keeps_or_changes_format() {
+    let temp_dir = create_temp_dir();
+    let encrypted_path = temp_dir.path().join(".env.encrypted");
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--format").arg("binary");
+    cmd.assert().success();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("recrypt").arg("--key").arg(TEST_KEY).arg("--to-cipher").arg("AES-256-GCM");
+    cmd.assert().success();
+    assert!(fs::read(&encrypted_path).unwrap().starts_with(b"ENVC"));
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("recrypt").arg("--key").arg(TEST_KEY).arg("--format").arg("armor");
+    cmd.assert().success();
+    let armor = fs::read_to_string(&encrypted_path).unwrap();
+    assert!(base64::engine::general_purpose::STANDARD.decode(armor.trim()).is_ok());
+}
@@ -10,12 +10,47 @@ fn test_version_flag() {
     cmd.assert()
         .success()
         .stdout(
-            predicates::str::contains("envcrypt 0.1.0")
+            predicates::str::contains(format!("envcrypt {}", env!("CARGO_PKG_VERSION")))
                 .and(predicates::str::contains("("))
                 .and(predicates::str::contains(")"))
         );
 }
 
+#[test]
+fn test_version_json_lists_features_and_ciphers() {
+    let temp_dir = create_temp_dir();
+    let mut cmd = create_command(temp_dir.path());
+    cmd.args(["--version", "--format", "json"]);
+    let output = cmd.assert().success().get_output().stdout.clone();
+
+    let info: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+    assert!(info["features"].as_array().unwrap().contains(&"encrypt".into()));
+    for cipher in ["AES-256-CBC", "AES-256-GCM", "CHACHA20-POLY1305"] {
+        assert!(info["ciphers"].as_array().unwrap().contains(&cipher.into()), "{}", cipher);
+    }
+    assert_eq!(info["format_version"], 2);
+    assert_eq!(info["readable_format_versions"], serde_json::json!([0, 1, 2]));
+}
+
+#[test]
+fn test_version_format_text_and_invalid() {
+    let temp_dir = create_temp_dir();
+    let mut cmd = create_command(temp_dir.path());
+    cmd.args(["--format=text", "-V"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with(format!("envcrypt {} (", env!("CARGO_PKG_VERSION"))));
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.args(["--version", "--format", "xml"]);
+    cmd.assert().failure().stderr(predicate::str::contains("invalid value 'xml' for '--format <FORMAT>'"));
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.args(["--format", "json", "encrypt"]);
+    cmd.assert().failure().stderr(predicate::str::contains("--version"));
+}
+
 #[test]
 fn test_silent_flag_suppresses_output() {
     let temp_dir = create_temp_dir();
@@ -56,6 +91,47 @@ fn test_quiet_flag_suppresses_info_but_shows_errors() {
         .stderr(predicates::str::contains("file not found"));
 }
 
+#[test]
+fn test_quiet_flag_still_prints_generated_key() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.args(["--quiet", "--no-interaction", "encrypt"]);
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let output = String::from_utf8(output).unwrap();
+    let key = output.trim().strip_prefix("Encryption key: ").expect(&output);
+
+    fs::remove_file(temp_dir.path().join(".env")).unwrap();
+    create_decrypt_command(temp_dir.path(), key).assert().success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "APP_KEY=test123");
+}
+
+#[test]
+fn test_silent_flag_refuses_to_generate_hidden_key() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.args(["--silent", "--no-interaction", "encrypt"]);
+    cmd.assert()
+        .failure()
+        .stdout(predicates::str::is_empty())
+        .stderr(predicate::str::contains("Refusing to generate a key that --silent would hide"));
+    assert!(!temp_dir.path().join(".env.encrypted").exists());
+
+    // Written to --key-out, the key is not lost
+    let mut cmd = create_command(temp_dir.path());
+    cmd.args(["--silent", "--no-interaction", "encrypt", "--key-out"]).arg(temp_dir.path().join("key"));
+    cmd.assert().success().stderr(predicates::str::is_empty());
+    let key = fs::read_to_string(temp_dir.path().join("key")).unwrap();
+    create_decrypt_command(temp_dir.path(), key.trim()).arg("--force").assert().success();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.args(["--silent", "--no-interaction", "--force", "--i-know-what-im-doing", "encrypt"]);
+    cmd.assert().success().stdout(predicates::str::is_empty()).stderr(predicates::str::is_empty());
+}
+
 #[test]
 fn test_force_flag_overwrites_existing_file() {
     let temp_dir = create_temp_dir();
@@ -148,6 +224,36 @@ fn test_no_interaction_requires_key_for_decrypt() {
         .stderr(predicates::str::contains("key is required"));
 }
 
+#[test]
+fn test_missing_tty_fails_fast_for_decrypt() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    fs::write(&env_path, "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    fs::remove_file(&env_path).unwrap();
+
+    // Test processes have no terminal on stdin, so there is nobody to ask for the key
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("decrypt").write_stdin("");
+    cmd.assert()
+        .failure()
+        .stderr(predicates::str::contains("stdin is not a terminal. Pass --key"));
+    assert!(!env_path.exists());
+}
+
+#[test]
+fn test_missing_tty_fails_fast_for_encrypt() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("encrypt");
+    cmd.assert()
+        .failure()
+        .stderr(predicates::str::contains("ENVCRYPT_KEY_CHOICE=generate"));
+    assert!(!temp_dir.path().join(".env.encrypted").exists());
+}
+
 #[test]
 fn test_verbose_flag_increases_output() {
     let temp_dir = create_temp_dir();
@@ -224,6 +330,56 @@ fn test_force_flag_for_decrypt() {
     assert_eq!(decrypted_content, original_content);
 }
 
+#[test]
+fn test_overwrite_prompt_confirmed() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+
+    fs::write(&env_path, "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    fs::write(&env_path, "APP_KEY=modified").unwrap();
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.write_stdin("y\n");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Overwrite .env? [y/N]"));
+
+    assert_eq!(fs::read_to_string(&env_path).unwrap(), "APP_KEY=test123");
+}
+
+#[test]
+fn test_overwrite_prompt_declined() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+
+    fs::write(&env_path, "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    fs::write(&env_path, "APP_KEY=modified").unwrap();
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.write_stdin("\n");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("was not overwritten"));
+
+    assert_eq!(fs::read_to_string(&env_path).unwrap(), "APP_KEY=modified");
+}
+
+#[test]
+fn test_no_interaction_does_not_prompt_before_overwrite() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--no-interaction").write_stdin("y\n");
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("Overwrite").not())
+        .stderr(predicate::str::contains("already exists. Use --force to overwrite."));
+}
+
 #[test]
 fn test_prune_only_applies_to_encrypt() {
     let temp_dir = create_temp_dir();
@@ -246,3 +402,51 @@ fn test_prune_only_applies_to_encrypt() {
     // Encrypted file should still exist (prune only applies to encrypt)
     assert!(encrypted_path.exists(), ".env.encrypted should still exist after decrypt with --prune");
 }
+
+#[test]
+fn test_mlock_flag_roundtrip() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+
+    let original_content = "APP_KEY=test123";
+    fs::write(&env_path, original_content).unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--mlock").arg("-vvv");
+    // Locking may be refused by rlimits in CI; either way the command must succeed
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("Locked").or(predicate::str::contains("Could not lock memory")));
+
+    fs::remove_file(&env_path).unwrap();
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--mlock");
+    cmd.assert().success();
+
+    assert_eq!(fs::read_to_string(&env_path).unwrap(), original_content);
+}
+
+#[test]
+fn test_output_has_no_color_codes_when_piped() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("encrypt").arg("--no-interaction");
+    cmd.assert()
+        .success()
+        .stdout(predicates::str::contains("Successfully encrypted"))
+        .stdout(predicates::str::contains("\x1b[").not());
+}
+
+#[test]
+fn test_no_color_flag() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--no-color").env("NO_COLOR", "1");
+    cmd.assert()
+        .success()
+        .stdout(predicates::str::contains("\x1b[").not());
+}
@@ -0,0 +1,133 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+
+/// Installs a fake `gpg` in `temp_dir/bin` that logs its arguments to `temp_dir/gpg.log`,
+/// "encrypts" by prefixing its input with `PGP` and "decrypts" by stripping the prefix.
+/// Returns the PATH to run envcrypt with.
+#[cfg(unix)]
+fn install_fake_gpg(temp_dir: &Path) -> String {
+    install_gpg_script(temp_dir, &format!(
+        "#!/bin/sh\necho \"$@\" >> \"{}/gpg.log\"\ncase \"$1\" in\n  --encrypt) printf 'PGP'; cat ;;\n  --decrypt) tail -c +4 ;;\nesac\n",
+        temp_dir.display()
+    ))
+}
+
+#[cfg(unix)]
+fn install_gpg_script(temp_dir: &Path, script: &str) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let bin = temp_dir.join("bin");
+    fs::create_dir_all(&bin).unwrap();
+    let path = bin.join("gpg");
+    fs::write(&path, script).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    format!("{}:{}", bin.display(), std::env::var("PATH").unwrap_or_default())
+}
+
+#[test]
+#[cfg(unix)]
+fn test_recipient_gpg_roundtrip() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_gpg(temp_dir.path());
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    create_command(temp_dir.path())
+        .env("PATH", &path)
+        .args(["encrypt", "--key", TEST_KEY, "--no-interaction"])
+        .args(["--recipient-gpg", "0xABCD1234", "--recipient-gpg", "alice@example.com"])
+        .assert()
+        .success();
+    let log = fs::read_to_string(temp_dir.path().join("gpg.log")).unwrap();
+    assert!(log.contains("--encrypt"), "{}", log);
+    assert!(log.contains("--recipient 0xABCD1234 --recipient alice@example.com"), "{}", log);
+
+    create_command(temp_dir.path())
+        .arg("inspect")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("encrypted to GPG recipients"));
+
+    fs::remove_file(temp_dir.path().join(".env")).unwrap();
+    create_command(temp_dir.path())
+        .env("PATH", &path)
+        .args(["decrypt", "--gpg", "--no-interaction"])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "APP_KEY=test123");
+    assert!(fs::read_to_string(temp_dir.path().join("gpg.log")).unwrap().contains("--decrypt"));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_gpg_recipients_from_config() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_gpg(temp_dir.path());
+    fs::write(temp_dir.path().join("envcrypt.toml"), "gpg_recipients = [\"team@example.com\"]\n").unwrap();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    create_command(temp_dir.path())
+        .env("PATH", &path)
+        .args(["encrypt", "--key", TEST_KEY, "--no-interaction"])
+        .assert()
+        .success();
+    let log = fs::read_to_string(temp_dir.path().join("gpg.log")).unwrap();
+    assert!(log.contains("--recipient team@example.com"), "{}", log);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_recipient_gpg_failure_writes_nothing() {
+    let temp_dir = create_temp_dir();
+    let path = install_gpg_script(temp_dir.path(), "#!/bin/sh\necho 'gpg: nobody@example.com: skipped: No public key' >&2\nexit 2\n");
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    create_command(temp_dir.path())
+        .env("PATH", &path)
+        .args(["encrypt", "--key", TEST_KEY, "--no-interaction", "--recipient-gpg", "nobody@example.com"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Cannot encrypt the file keys to the GPG recipients"))
+        .stderr(predicate::str::contains("No public key"));
+    assert!(!temp_dir.path().join(".env.encrypted").exists());
+}
+
+#[test]
+fn test_recipient_gpg_rejects_options() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    create_command(temp_dir.path())
+        .args(["encrypt", "--key", TEST_KEY, "--no-interaction", "--recipient-gpg=--output=/tmp/x"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid GPG recipient"));
+}
+
+#[test]
+fn test_decrypt_gpg_without_gpg_recipients_fails() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_command(temp_dir.path())
+        .args(["encrypt", "--key", TEST_KEY, "--no-interaction"])
+        .assert()
+        .success();
+    fs::remove_file(temp_dir.path().join(".env")).unwrap();
+
+    create_command(temp_dir.path())
+        .args(["decrypt", "--gpg", "--no-interaction"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no keys wrapped for GPG recipients"));
+}
+
+#[test]
+fn test_decrypt_gpg_conflicts_with_key() {
+    let temp_dir = create_temp_dir();
+    create_command(temp_dir.path())
+        .args(["decrypt", "--gpg", "--key", TEST_KEY])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
@@ -0,0 +1,69 @@
+use crate::common::*;
+use std::fs;
+
+#[test]
+fn test_long_help_shows_examples() {
+    let temp_dir = create_temp_dir();
+    let output = create_command(temp_dir.path()).args(["encrypt", "--help"]).output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Examples:"));
+    assert!(stdout.contains("envcrypt encrypt --env production --key-provider env"));
+
+    let output = create_command(temp_dir.path()).args(["encrypt", "-h"]).output().unwrap();
+    assert!(!String::from_utf8(output.stdout).unwrap().contains("Examples:"));
+}
+
+#[test]
+fn test_help_long_includes_subcommands() {
+    let temp_dir = create_temp_dir();
+    let output = create_command(temp_dir.path()).arg("--help-long").output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("Encrypt and decrypt environment files"));
+    assert!(stdout.contains("\nenvcrypt decrypt\n================\n"));
+    assert!(stdout.contains("\nenvcrypt key split\n"));
+    assert!(stdout.contains("envcrypt key split --shares 5 --threshold 3"));
+    // Hidden commands stay hidden
+    assert!(!stdout.contains("\nenvcrypt vectors\n"));
+}
+
+#[test]
+fn test_help_long_for_subcommand() {
+    let temp_dir = create_temp_dir();
+    // Works without the required arguments of the command, like --help
+    let output = create_command(temp_dir.path()).args(["key", "split", "--help-long"]).output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Usage: envcrypt key split"));
+    assert!(stdout.contains("Examples:"));
+    assert!(!stdout.contains("envcrypt key recover"));
+}
+
+#[test]
+fn test_man_prints_page() {
+    let temp_dir = create_temp_dir();
+    let output = create_command(temp_dir.path()).arg("man").output().unwrap();
+    assert!(output.status.success());
+    let page = String::from_utf8(output.stdout).unwrap();
+    assert!(page.contains(".TH envcrypt 1"));
+    assert!(page.contains(".SH OPTIONS"));
+    assert!(page.contains(".SH EXAMPLES"));
+    assert!(page.contains("\\fBenvcrypt \\-\\-help\\-long\\fR"));
+}
+
+#[test]
+fn test_man_writes_pages() {
+    let temp_dir = create_temp_dir();
+    let output = create_command(temp_dir.path()).args(["man", "--output", "man"]).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let dir = temp_dir.path().join("man");
+    assert!(dir.join("envcrypt.1").exists());
+    assert!(dir.join("envcrypt-encrypt.1").exists());
+    assert!(dir.join("envcrypt-sync-aws-ssm.1").exists());
+    let page = fs::read_to_string(dir.join("envcrypt-key-split.1")).unwrap();
+    assert!(page.contains("\\fBenvcrypt key split \\-\\-shares 5 \\-\\-threshold 3\\fR"));
+    assert!(!dir.join("envcrypt-vectors.1").exists());
+    assert!(!dir.join("envcrypt-help.1").exists());
+}
@@ -0,0 +1,106 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+
+fn create_import_command(temp_dir: &Path, source: &str) -> assert_cmd::Command {
+    let mut cmd = create_command(temp_dir);
+    cmd.arg("import").arg(source).arg("--key").arg(TEST_KEY);
+    cmd
+}
+
+#[test]
+fn test_import_json() {
+    let temp_dir = create_temp_dir();
+    fs::write(
+        temp_dir.path().join("secrets.json"),
+        r#"{"APP_KEY": "test123", "PORT": 8080, "GREETING": "hello world", "EMPTY": null}"#,
+    ).unwrap();
+
+    let mut cmd = create_import_command(temp_dir.path(), "secrets.json");
+    cmd.arg("--output").arg(".env.production.encrypted");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Imported 4 variables from secrets.json to .env.production.encrypted"));
+
+    create_decrypt_command(temp_dir.path(), TEST_KEY).arg(".env.production.encrypted").assert().success();
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join(".env.production")).unwrap(),
+        "APP_KEY=test123\nPORT=8080\nGREETING=\"hello world\"\nEMPTY=\n"
+    );
+}
+
+#[test]
+fn test_import_yaml_roundtrips_through_export() {
+    let temp_dir = create_temp_dir();
+    fs::write(
+        temp_dir.path().join("secrets.yml"),
+        "DB_URL: postgres://localhost/app\nCERT: |\n  line one\n  line two\nQUOTE: \"it's $5\"\n",
+    ).unwrap();
+
+    create_import_command(temp_dir.path(), "secrets.yml").assert().success();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("export").arg("--format").arg("yaml").arg("--key").arg(TEST_KEY);
+    cmd.assert()
+        .success()
+        .stdout("DB_URL: \"postgres://localhost/app\"\nCERT: \"line one\\nline two\\n\"\nQUOTE: \"it's $5\"\n");
+}
+
+#[test]
+fn test_import_format_override() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join("secrets.txt"), r#"{"APP_KEY": "test123"}"#).unwrap();
+
+    create_import_command(temp_dir.path(), "secrets.txt")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("use --from json or --from yaml"));
+
+    let mut cmd = create_import_command(temp_dir.path(), "secrets.txt");
+    cmd.arg("--from").arg("json");
+    cmd.assert().success();
+    assert!(temp_dir.path().join(".env.encrypted").exists());
+}
+
+#[test]
+fn test_import_rejects_nested_values() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join("secrets.json"), r#"{"DATABASE": {"HOST": "localhost"}}"#).unwrap();
+
+    create_import_command(temp_dir.path(), "secrets.json")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("DATABASE has a nested value"));
+    assert!(!temp_dir.path().join(".env.encrypted").exists());
+}
+
+#[test]
+fn test_import_does_not_overwrite_without_force() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join("secrets.json"), r#"{"APP_KEY": "new"}"#).unwrap();
+    fs::write(temp_dir.path().join(".env.encrypted"), "existing").unwrap();
+
+    let mut cmd = create_import_command(temp_dir.path(), "secrets.json");
+    cmd.arg("--no-interaction");
+    cmd.assert().failure();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env.encrypted")).unwrap(), "existing");
+
+    let mut cmd = create_import_command(temp_dir.path(), "secrets.json");
+    cmd.arg("--force");
+    cmd.assert().success();
+    assert_ne!(fs::read_to_string(temp_dir.path().join(".env.encrypted")).unwrap(), "existing");
+}
+
+#[test]
+fn test_import_dry_run() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join("secrets.json"), r#"{"APP_KEY": "test123"}"#).unwrap();
+
+    let mut cmd = create_import_command(temp_dir.path(), "secrets.json");
+    cmd.arg("--dry-run");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Would write .env.encrypted"));
+    assert!(!temp_dir.path().join(".env.encrypted").exists());
+}
@@ -0,0 +1,143 @@
+use crate::common::*;
+use base64::Engine;
+use predicates::prelude::*;
+use std::fs;
+
+#[test]
+fn test_inspect_shows_metadata_without_key() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--comment").arg("rotated after incident #42");
+    cmd.assert().success();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("inspect");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Comment:       rotated after incident #42"))
+        .stdout(predicate::str::contains(format!("Tool version:  {}", env!("CARGO_PKG_VERSION"))))
+        .stdout(predicate::str::contains("Created:       20"))
+        .stdout(predicate::str::contains("APP_KEY").not());
+}
+
+#[test]
+fn test_inspect_shows_header_and_sizes() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--cipher").arg("AES-256-GCM").arg("--kdf-iterations").arg("2000").arg("--format").arg("binary");
+    cmd.assert().success();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("inspect").arg(".env.encrypted");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Encoding:      binary"))
+        .stdout(predicate::str::contains("Version:       2"))
+        .stdout(predicate::str::contains("Cipher:        AES-256-GCM"))
+        .stdout(predicate::str::contains("KDF:           PBKDF2-HMAC-SHA256 (2000 iterations)"))
+        .stdout(predicate::str::contains("Salt:          16 bytes"))
+        .stdout(predicate::str::contains("IV/nonce:      12 bytes"))
+        .stdout(predicate::str::contains("MAC/tag:       16 bytes"))
+        .stdout(predicate::str::contains("Ciphertext:    15 bytes"))
+        .stdout(predicate::str::contains("Recovery:      none"));
+}
+
+#[test]
+fn test_inspect_legacy_file() {
+    let temp_dir = create_temp_dir();
+    // base64(salt + iv + one block + mac): a pre-header AES-256-CBC file
+    let data = vec![0u8; 16 + 16 + 16 + 32];
+    fs::write(temp_dir.path().join(".env.encrypted"), base64::engine::general_purpose::STANDARD.encode(data)).unwrap();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("inspect");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Encoding:      armor"))
+        .stdout(predicate::str::contains("Version:       legacy (no header)"))
+        .stdout(predicate::str::contains("Cipher:        not recorded (AES-256-CBC assumed)"))
+        .stdout(predicate::str::contains("Ciphertext:    16 bytes"))
+        .stdout(predicate::str::contains("Created:       unknown"))
+        .stdout(predicate::str::contains("Authenticated: no"));
+}
+
+#[test]
+fn test_inspect_missing_file_fails() {
+    let temp_dir = create_temp_dir();
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("inspect").arg("missing.encrypted");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("missing.encrypted file not found"));
+}
+
+#[test]
+fn test_tampered_comment_fails_decryption() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    let encrypted_path = temp_dir.path().join(".env.encrypted");
+    fs::write(&env_path, "APP_KEY=test123").unwrap();
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--comment").arg("approved by alice");
+    cmd.assert().success();
+    fs::remove_file(&env_path).unwrap();
+
+    // Same length, so the header still parses
+    let engine = base64::engine::general_purpose::STANDARD;
+    let data = engine.decode(fs::read_to_string(&encrypted_path).unwrap().trim()).unwrap();
+    let position = data.windows(5).position(|w| w == b"alice").unwrap();
+    let mut patched = data.clone();
+    patched[position..position + 5].copy_from_slice(b"mallo");
+    fs::write(&encrypted_path, engine.encode(&patched)).unwrap();
+
+    create_command(temp_dir.path()).arg("inspect").assert()
+        .success()
+        .stdout(predicate::str::contains("approved by mallo"));
+    create_decrypt_command(temp_dir.path(), TEST_KEY).assert()
+        .failure()
+        .stderr(predicate::str::contains("Authentication failed"));
+    assert!(!env_path.exists());
+}
+
+#[test]
+fn test_downgraded_header_fails_decryption() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    let encrypted_path = temp_dir.path().join(".env.encrypted");
+    fs::write(&env_path, "APP_KEY=test123").unwrap();
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.args(["--cipher", "AES-256-CBC", "--comment", "approved by alice"]);
+    cmd.assert().success();
+    fs::remove_file(&env_path).unwrap();
+
+    // Version 1 headers were not authenticated, so their metadata could be forged
+    let engine = base64::engine::general_purpose::STANDARD;
+    let mut data = engine.decode(fs::read_to_string(&encrypted_path).unwrap().trim()).unwrap();
+    assert_eq!(data[4], 2);
+    data[4] = 1;
+    fs::write(&encrypted_path, engine.encode(&data)).unwrap();
+
+    create_decrypt_command(temp_dir.path(), TEST_KEY).assert()
+        .failure()
+        .stderr(predicate::str::contains("Unsupported file format version 1"));
+    assert!(!env_path.exists());
+}
+
+#[test]
+fn test_recrypt_keeps_comment() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--comment").arg("quarterly rotation");
+    cmd.assert().success();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("recrypt").arg("--key").arg(TEST_KEY).arg("--to-cipher").arg("AES-256-GCM");
+    cmd.assert().success();
+
+    create_command(temp_dir.path()).arg("inspect").assert()
+        .success()
+        .stdout(predicate::str::contains("quarterly rotation"));
+}
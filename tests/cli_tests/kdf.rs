@@ -0,0 +1,230 @@
+use crate::common::*;
+use base64::Engine;
+use envcrypt::cipher::{Aes256Cbc, Cipher};
+use envcrypt::format::{self, Kdf};
+use envcrypt::key::{derive_keys_with_schedule, generate_salt, KeySchedule, DEFAULT_PBKDF2_ITERATIONS};
+use std::fs;
+use std::path::Path;
+
+fn read_header(path: &Path) -> format::Header {
+    let content = fs::read_to_string(path).unwrap();
+    let data = base64::engine::general_purpose::STANDARD.decode(content.trim()).unwrap();
+    let (header, _) = format::decode(&data).unwrap();
+    header
+}
+
+#[test]
+fn test_kdf_iterations_recorded_in_header() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    let encrypted_path = temp_dir.path().join(".env.encrypted");
+
+    let original_content = "APP_KEY=test123";
+    fs::write(&env_path, original_content).unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--kdf-iterations").arg("2000").arg("--cipher").arg("AES-256-GCM");
+    cmd.assert().success();
+
+    let header = read_header(&encrypted_path);
+    assert_eq!(header.version, format::FORMAT_VERSION);
+    assert_eq!(header.cipher.as_deref(), Some("AES-256-GCM"));
+    assert_eq!(header.kdf, Kdf::Pbkdf2Sha256 { iterations: 2000 });
+    assert_eq!(header.key_schedule, KeySchedule::HkdfExpand);
+
+    // Decrypt picks up both the cipher and the iteration count from the header
+    fs::remove_file(&env_path).unwrap();
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.assert().success();
+
+    let decrypted_content = fs::read_to_string(&env_path).unwrap();
+    assert_eq!(decrypted_content, original_content);
+}
+
+#[test]
+fn test_kdf_iterations_below_minimum_fails() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--kdf-iterations").arg("10");
+    cmd.assert()
+        .failure()
+        .stderr(predicates::str::contains("KDF iterations must be at least"));
+}
+
+#[test]
+fn test_kdf_iterations_above_maximum_fails() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--kdf-iterations").arg("4000000000");
+    cmd.assert()
+        .failure()
+        .stderr(predicates::str::contains("KDF iterations must be at most"));
+}
+
+#[test]
+fn test_calibrate_writes_config_used_by_encrypt() {
+    let temp_dir = create_temp_dir();
+    let config_path = temp_dir.path().join("envcrypt.toml");
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("calibrate").arg("--target-ms").arg("5");
+    cmd.assert()
+        .success()
+        .stdout(predicates::str::contains("Saved kdf_iterations"));
+
+    let config: toml::Table = fs::read_to_string(&config_path).unwrap().parse().unwrap();
+    let iterations = config["kdf_iterations"].as_integer().unwrap() as u32;
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.assert().success();
+
+    let header = read_header(&temp_dir.path().join(".env.encrypted"));
+    assert_eq!(header.kdf, Kdf::Pbkdf2Sha256 { iterations });
+}
+
+#[test]
+fn test_calibrate_no_save() {
+    let temp_dir = create_temp_dir();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("calibrate").arg("--target-ms").arg("5").arg("--no-save");
+    cmd.assert()
+        .success()
+        .stdout(predicates::str::contains("iterations"));
+
+    assert!(!temp_dir.path().join("envcrypt.toml").exists());
+}
+
+#[test]
+fn test_legacy_file_without_header_decrypts() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    let encrypted_path = temp_dir.path().join(".env.encrypted");
+
+    // Build a file in the pre-header format: base64(salt + iv + ciphertext + mac)
+    let original_content = "APP_KEY=legacy";
+    let salt = generate_salt();
+    let (encryption_key, mac_key) = derive_keys_with_schedule(TEST_KEY, &salt, DEFAULT_PBKDF2_ITERATIONS, KeySchedule::Split);
+    let encrypted = Aes256Cbc.encrypt(original_content.as_bytes(), &encryption_key, &mac_key).unwrap();
+    let mut data = salt.to_vec();
+    data.extend_from_slice(&encrypted);
+    fs::write(&encrypted_path, base64::engine::general_purpose::STANDARD.encode(data)).unwrap();
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.assert().success();
+
+    let decrypted_content = fs::read_to_string(&env_path).unwrap();
+    assert_eq!(decrypted_content, original_content);
+}
+
+const RAW_KEY: &str = "base64:q83vEjRWeJCrze8SNFZ4kKvN7xI0VniQq83vEjRWeJA=";
+
+#[test]
+fn test_generated_key_uses_raw_key_mode() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    let encrypted_path = temp_dir.path().join(".env.encrypted");
+
+    let original_content = "APP_KEY=test123";
+    fs::write(&env_path, original_content).unwrap();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("encrypt").arg("--no-interaction").arg("--show-key");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let stdout = String::from_utf8(output).unwrap();
+    let key = stdout
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Encryption key: "))
+        .expect("generated key should be printed")
+        .to_string();
+
+    assert_eq!(read_header(&encrypted_path).kdf, Kdf::HkdfSha256);
+
+    fs::remove_file(&env_path).unwrap();
+    let mut cmd = create_decrypt_command(temp_dir.path(), &key);
+    cmd.assert().success();
+
+    let decrypted_content = fs::read_to_string(&env_path).unwrap();
+    assert_eq!(decrypted_content, original_content);
+}
+
+#[test]
+fn test_raw_key_flag_roundtrip() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    let encrypted_path = temp_dir.path().join(".env.encrypted");
+
+    let original_content = "APP_KEY=test123";
+    fs::write(&env_path, original_content).unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), RAW_KEY);
+    cmd.arg("--raw-key");
+    cmd.assert().success();
+
+    assert_eq!(read_header(&encrypted_path).kdf, Kdf::HkdfSha256);
+
+    fs::remove_file(&env_path).unwrap();
+    let mut cmd = create_decrypt_command(temp_dir.path(), RAW_KEY);
+    cmd.assert().success();
+
+    let decrypted_content = fs::read_to_string(&env_path).unwrap();
+    assert_eq!(decrypted_content, original_content);
+}
+
+#[test]
+fn test_raw_key_flag_rejects_passphrase() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--raw-key");
+    cmd.assert()
+        .failure()
+        .stderr(predicates::str::contains("--raw-key requires a base64-encoded 32-byte key"));
+}
+
+#[test]
+fn test_raw_key_file_rejects_passphrase_on_decrypt() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    fs::write(&env_path, "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), RAW_KEY);
+    cmd.arg("--raw-key");
+    cmd.assert().success();
+
+    fs::remove_file(&env_path).unwrap();
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.assert()
+        .failure()
+        .stderr(predicates::str::contains("encrypted with a raw key"));
+}
+
+#[test]
+fn test_split_key_schedule_header_decrypts() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    let encrypted_path = temp_dir.path().join(".env.encrypted");
+
+    // The split key schedule slices the PBKDF2 output instead of expanding it
+    let original_content = "APP_KEY=split";
+    let salt = generate_salt();
+    let (encryption_key, mac_key) = derive_keys_with_schedule(TEST_KEY, &salt, 2000, KeySchedule::Split);
+    let mut header = format::Header::new("AES-256-CBC", Kdf::Pbkdf2Sha256 { iterations: 2000 }, salt);
+    header.key_schedule = KeySchedule::Split;
+    let aad = format::associated_data(&format::encode_header(&header).unwrap(), None);
+    let encrypted = Aes256Cbc.encrypt_with_aad(original_content.as_bytes(), &aad, &encryption_key, &mac_key).unwrap();
+    let data = format::encode(&header, &encrypted).unwrap();
+    fs::write(&encrypted_path, base64::engine::general_purpose::STANDARD.encode(data)).unwrap();
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.assert().success();
+
+    let decrypted_content = fs::read_to_string(&env_path).unwrap();
+    assert_eq!(decrypted_content, original_content);
+}
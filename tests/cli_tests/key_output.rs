@@ -0,0 +1,238 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+
+fn encrypt_generating_key(temp_dir: &std::path::Path) -> assert_cmd::Command {
+    fs::write(temp_dir.join(".env"), "APP_KEY=test123").unwrap();
+    let mut cmd = create_command(temp_dir);
+    cmd.arg("encrypt").arg("--no-interaction");
+    cmd
+}
+
+#[test]
+fn test_user_key_shows_fingerprint_by_default() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY)
+        .arg("--no-interaction")
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"Key fingerprint: [0-9a-f]{16}\n").unwrap())
+        .stdout(predicate::str::contains("Use --show-key to print it"))
+        .stdout(predicate::str::contains("Encryption key:").not());
+}
+
+#[test]
+fn test_generated_key_is_shown_when_nobody_can_be_asked() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    let output = create_command(temp_dir.path())
+        .arg("encrypt")
+        .env("ENVCRYPT_KEY_CHOICE", "generate")
+        .write_stdin("")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let key = stdout.lines()
+        .find_map(|line| line.trim().strip_prefix("Encryption key: "))
+        .expect("the generated key is printed");
+
+    fs::remove_file(temp_dir.path().join(".env")).unwrap();
+    create_decrypt_command(temp_dir.path(), key).assert().success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "APP_KEY=test123");
+}
+
+#[test]
+fn test_show_key_prints_key() {
+    let temp_dir = create_temp_dir();
+    encrypt_generating_key(temp_dir.path())
+        .arg("--show-key")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Encryption key: base64:"))
+        .stdout(predicate::str::contains("Key fingerprint:").not());
+}
+
+#[test]
+fn test_hide_key_prints_fingerprint_only() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY)
+        .arg("--no-interaction")
+        .arg("--hide-key")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Key fingerprint:"))
+        .stdout(predicate::str::contains("Encryption key:").not());
+}
+
+#[test]
+fn test_hide_key_refuses_to_generate_key() {
+    let temp_dir = create_temp_dir();
+    encrypt_generating_key(temp_dir.path())
+        .arg("--hide-key")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Refusing to generate a key that would not be shown"));
+    assert!(!temp_dir.path().join(".env.encrypted").exists());
+
+    encrypt_generating_key(temp_dir.path())
+        .arg("--hide-key")
+        .arg("--i-know-what-im-doing")
+        .assert()
+        .success();
+    assert!(temp_dir.path().join(".env.encrypted").exists());
+}
+
+#[test]
+fn test_show_key_conflicts_with_hide_key() {
+    let temp_dir = create_temp_dir();
+    encrypt_generating_key(temp_dir.path())
+        .arg("--show-key")
+        .arg("--hide-key")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+/// Installs a fake `wl-copy` in `temp_dir/bin` that writes the clipboard to
+/// `temp_dir/clipboard`. Returns the PATH to run envcrypt with.
+#[cfg(target_os = "linux")]
+fn install_fake_wl_copy(temp_dir: &std::path::Path) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let bin = temp_dir.join("bin");
+    fs::create_dir_all(&bin).unwrap();
+    let path = bin.join("wl-copy");
+    fs::write(&path, format!("#!/bin/sh\ncat > \"{}/clipboard\"\n", temp_dir.display())).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    format!("{}:{}", bin.display(), std::env::var("PATH").unwrap_or_default())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_copy_puts_key_on_clipboard() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_wl_copy(temp_dir.path());
+    encrypt_generating_key(temp_dir.path())
+        .env("PATH", path)
+        .env("WAYLAND_DISPLAY", "wayland-0")
+        .arg("--copy")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Copied the encryption key to the clipboard (wl-copy)"))
+        .stdout(predicate::str::contains("Key fingerprint:"))
+        .stdout(predicate::str::contains("Encryption key:").not());
+
+    let key = fs::read_to_string(temp_dir.path().join("clipboard")).unwrap();
+    fs::remove_file(temp_dir.path().join(".env")).unwrap();
+    create_decrypt_command(temp_dir.path(), &format!("base64:{}", key)).assert().success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "APP_KEY=test123");
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_copy_without_clipboard_tool_warns() {
+    let temp_dir = create_temp_dir();
+    encrypt_generating_key(temp_dir.path())
+        .env("PATH", temp_dir.path())
+        .env_remove("WAYLAND_DISPLAY")
+        .arg("--copy")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Could not copy the encryption key to the clipboard"))
+        .stdout(predicate::str::contains("Key fingerprint:"))
+        .stdout(predicate::str::contains("Encryption key: base64:"));
+}
+
+#[test]
+#[cfg(all(feature = "clipboard", target_os = "linux"))]
+fn test_copy_key_without_display_warns() {
+    let temp_dir = create_temp_dir();
+    encrypt_generating_key(temp_dir.path())
+        .env_remove("DISPLAY")
+        .env_remove("WAYLAND_DISPLAY")
+        .arg("--copy-key")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Could not copy the encryption key to the clipboard"))
+        .stdout(predicate::str::contains("Key fingerprint:"))
+        .stdout(predicate::str::contains("Encryption key: base64:"));
+}
+
+#[test]
+#[cfg(feature = "clipboard")]
+fn test_copy_key_timeout_must_be_positive() {
+    let temp_dir = create_temp_dir();
+    encrypt_generating_key(temp_dir.path())
+        .arg("--copy-key")
+        .arg("--copy-key-timeout")
+        .arg("0")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--copy-key-timeout"));
+}
+
+#[test]
+fn test_key_qr_prints_qr_code() {
+    let temp_dir = create_temp_dir();
+    encrypt_generating_key(temp_dir.path())
+        .arg("--key-qr")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Scan the key"))
+        .stdout(predicate::str::contains("█▀"))
+        .stdout(predicate::str::contains("Encryption key:").not());
+}
+
+#[test]
+fn test_key_qr_conflicts_with_show_key() {
+    let temp_dir = create_temp_dir();
+    encrypt_generating_key(temp_dir.path())
+        .arg("--key-qr")
+        .arg("--show-key")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[test]
+fn test_key_out_writes_key_file() {
+    let temp_dir = create_temp_dir();
+    let key_dir = create_temp_dir();
+    let key_path = key_dir.path().join("envcrypt.key");
+    encrypt_generating_key(temp_dir.path())
+        .arg("--key-out")
+        .arg(&key_path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Wrote the encryption key to"))
+        .stdout(predicate::str::contains("Encryption key:").not());
+
+    let key = fs::read_to_string(&key_path).unwrap();
+    fs::remove_file(temp_dir.path().join(".env")).unwrap();
+    create_decrypt_command(temp_dir.path(), key.trim()).assert().success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "APP_KEY=test123");
+}
+
+#[test]
+fn test_key_out_inside_repository_requires_force() {
+    let temp_dir = create_temp_dir();
+    fs::create_dir(temp_dir.path().join(".git")).unwrap();
+    encrypt_generating_key(temp_dir.path())
+        .arg("--key-out")
+        .arg("envcrypt.key")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("inside the Git repository"));
+    assert!(!temp_dir.path().join(".env.encrypted").exists());
+
+    encrypt_generating_key(temp_dir.path())
+        .arg("--key-out")
+        .arg("envcrypt.key")
+        .arg("--force")
+        .assert()
+        .success();
+    assert!(temp_dir.path().join("envcrypt.key").exists());
+}
@@ -0,0 +1,176 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+
+fn encrypt_test_file(temp_dir: &std::path::Path) {
+    fs::write(temp_dir.join(".env"), "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir, TEST_KEY).assert().success();
+    fs::remove_file(temp_dir.join(".env")).unwrap();
+}
+
+#[test]
+fn test_env_key_provider() {
+    let temp_dir = create_temp_dir();
+    encrypt_test_file(temp_dir.path());
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("decrypt").arg("--key-provider").arg("env").env("ENVCRYPT_KEY", TEST_KEY);
+    cmd.assert().success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "APP_KEY=test123");
+}
+
+#[test]
+fn test_env_key_provider_without_variable_fails() {
+    let temp_dir = create_temp_dir();
+    encrypt_test_file(temp_dir.path());
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("decrypt").arg("--key-provider").arg("env").env_remove("ENVCRYPT_KEY");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("ENVCRYPT_KEY is not set"));
+}
+
+#[test]
+fn test_file_key_provider_from_config() {
+    let temp_dir = create_temp_dir();
+    encrypt_test_file(temp_dir.path());
+    fs::write(temp_dir.path().join("key.txt"), format!("{}\n", TEST_KEY)).unwrap();
+    fs::write(temp_dir.path().join("envcrypt.toml"), "key_provider = \"file\"\nkey_file = \"key.txt\"\n").unwrap();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("decrypt");
+    cmd.assert().success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "APP_KEY=test123");
+}
+
+#[test]
+fn test_key_flag_overrides_provider() {
+    let temp_dir = create_temp_dir();
+    encrypt_test_file(temp_dir.path());
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--key-provider").arg("env").env_remove("ENVCRYPT_KEY");
+    cmd.assert().success();
+}
+
+#[test]
+fn test_unknown_key_provider_fails() {
+    let temp_dir = create_temp_dir();
+    encrypt_test_file(temp_dir.path());
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--key-provider").arg("vault");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown key provider: vault. Available providers: prompt, env, file, stdin"));
+}
+
+#[test]
+fn test_key_stdin() {
+    let temp_dir = create_temp_dir();
+    encrypt_test_file(temp_dir.path());
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("decrypt").arg("--key-stdin").write_stdin(format!("{}\n", TEST_KEY));
+    cmd.assert().success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "APP_KEY=test123");
+}
+
+#[test]
+fn test_key_stdin_without_newline_for_encrypt() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("encrypt").arg("--key-stdin").write_stdin(TEST_KEY);
+    cmd.assert().success();
+    fs::remove_file(temp_dir.path().join(".env")).unwrap();
+
+    create_decrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "APP_KEY=test123");
+}
+
+#[test]
+fn test_key_stdin_empty_fails() {
+    let temp_dir = create_temp_dir();
+    encrypt_test_file(temp_dir.path());
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("decrypt").arg("--key-stdin").write_stdin("");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("No key was provided on stdin"));
+}
+
+#[test]
+fn test_key_stdin_conflicts_with_key_provider() {
+    let temp_dir = create_temp_dir();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("decrypt").arg("--key-stdin").arg("--key-provider").arg("env");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+/// Installs a fake password manager CLI called `name` in `temp_dir/bin` that logs its
+/// arguments to `temp_dir/<name>.log` and prints `output`. Returns the PATH to run
+/// envcrypt with.
+#[cfg(unix)]
+fn install_fake_cli(temp_dir: &std::path::Path, name: &str, output: &str) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let bin = temp_dir.join("bin");
+    fs::create_dir_all(&bin).unwrap();
+    let script = format!("#!/bin/sh\necho \"$@\" >> \"{}/{}.log\"\nprintf '%s' '{}'\n", temp_dir.display(), name, output);
+    let path = bin.join(name);
+    fs::write(&path, script).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    format!("{}:{}", bin.display(), std::env::var("PATH").unwrap_or_default())
+}
+
+#[test]
+#[cfg(unix)]
+fn test_1password_key_provider_from_config() {
+    let temp_dir = create_temp_dir();
+    encrypt_test_file(temp_dir.path());
+    let path = install_fake_cli(temp_dir.path(), "op", TEST_KEY);
+    fs::write(
+        temp_dir.path().join("envcrypt.toml"),
+        "key_provider = \"1password\"\nonepassword_ref = \"op://Deploy/envcrypt/password\"\n",
+    ).unwrap();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.env("PATH", path).arg("decrypt");
+    cmd.assert().success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "APP_KEY=test123");
+    let log = fs::read_to_string(temp_dir.path().join("op.log")).unwrap();
+    assert_eq!(log.trim(), "read --no-newline op://Deploy/envcrypt/password");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_bitwarden_key_provider() {
+    let temp_dir = create_temp_dir();
+    encrypt_test_file(temp_dir.path());
+    let path = install_fake_cli(temp_dir.path(), "bw", &format!("{}\n", TEST_KEY));
+    fs::write(temp_dir.path().join("envcrypt.toml"), "bitwarden_item = \"envcrypt production\"\n").unwrap();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.env("PATH", path).arg("decrypt").arg("--key-provider").arg("bitwarden");
+    cmd.assert().success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "APP_KEY=test123");
+}
+
+#[test]
+fn test_password_manager_key_provider_requires_config() {
+    let temp_dir = create_temp_dir();
+    encrypt_test_file(temp_dir.path());
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("decrypt").arg("--key-provider").arg("1password");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("requires onepassword_ref in envcrypt.toml"));
+}
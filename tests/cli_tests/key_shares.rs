@@ -0,0 +1,86 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+
+fn split_shares(temp_dir: &std::path::Path, key: &str) -> Vec<String> {
+    let mut cmd = create_command(temp_dir);
+    cmd.arg("key").arg("split")
+        .arg("--key").arg(key)
+        .arg("--shares").arg("5")
+        .arg("--threshold").arg("3")
+        .arg("--quiet");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    String::from_utf8(output).unwrap().lines().map(str::to_string).collect()
+}
+
+#[test]
+fn test_split_and_recover_key_decrypts() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+
+    let original_content = "APP_KEY=test123";
+    fs::write(&env_path, original_content).unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    fs::remove_file(&env_path).unwrap();
+
+    let shares = split_shares(temp_dir.path(), TEST_KEY);
+    assert_eq!(shares.len(), 5);
+    assert!(shares.iter().all(|s| s.starts_with("envcrypt-share:3:")));
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("key").arg("recover")
+        .arg("--share").arg(&shares[4])
+        .arg("--share").arg(&shares[1])
+        .arg("--share").arg(&shares[2]);
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let recovered = String::from_utf8(output).unwrap();
+    assert_eq!(recovered.trim(), TEST_KEY);
+
+    create_decrypt_command(temp_dir.path(), recovered.trim()).assert().success();
+    assert_eq!(fs::read_to_string(&env_path).unwrap(), original_content);
+}
+
+#[test]
+fn test_recover_with_too_few_shares_fails() {
+    let temp_dir = create_temp_dir();
+    let shares = split_shares(temp_dir.path(), TEST_KEY);
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("key").arg("recover")
+        .arg("--share").arg(&shares[0])
+        .arg("--share").arg(&shares[3]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("3 shares required, 2 provided"));
+}
+
+#[test]
+fn test_recover_with_invalid_share_does_not_print_it() {
+    let temp_dir = create_temp_dir();
+    let shares = split_shares(temp_dir.path(), TEST_KEY);
+    let damaged = format!("{}!", shares[1]);
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("key").arg("recover")
+        .arg("--share").arg(&shares[0])
+        .arg("--share").arg(&damaged)
+        .arg("--share").arg(&shares[2]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid share 2: the data of share 2 is missing or not valid base64"))
+        .stderr(predicate::str::contains(&shares[1][18..]).not());
+}
+
+#[test]
+fn test_split_threshold_above_shares_fails() {
+    let temp_dir = create_temp_dir();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("key").arg("split")
+        .arg("--key").arg(TEST_KEY)
+        .arg("--shares").arg("2")
+        .arg("--threshold").arg("3");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("number of shares must be at least the threshold"));
+}
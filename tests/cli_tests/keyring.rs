@@ -0,0 +1,157 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+
+const PASSPHRASE: &str = "correct horse battery staple";
+
+fn create_keyring_command(temp_dir: &Path) -> assert_cmd::Command {
+    let mut cmd = create_command(temp_dir);
+    cmd.env("ENVCRYPT_KEYRING_PASSPHRASE", PASSPHRASE);
+    cmd
+}
+
+fn add_key(temp_dir: &Path, environment: &str, key: &str) {
+    create_keyring_command(temp_dir)
+        .args(["key", "add", environment, "--key", key])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("Added the key of {}", environment)));
+}
+
+#[test]
+fn test_key_add_generate_creates_encrypted_keyring() {
+    let temp_dir = create_temp_dir();
+    create_keyring_command(temp_dir.path())
+        .args(["key", "add", "staging", "--generate"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Added the key of staging"));
+
+    let keyring = fs::read_to_string(temp_dir.path().join(".envcrypt.keys")).unwrap();
+    assert!(!keyring.contains("envcrypt keyring"), "{}", keyring);
+    assert!(!keyring.contains("staging"));
+}
+
+#[test]
+fn test_decrypt_env_uses_keyring_key() {
+    let temp_dir = create_temp_dir();
+    add_key(temp_dir.path(), "staging", TEST_KEY);
+    fs::write(temp_dir.path().join(".env.staging"), "APP_KEY=staging").unwrap();
+
+    create_keyring_command(temp_dir.path())
+        .args(["encrypt", "--env", "staging", "--no-interaction"])
+        .assert()
+        .success();
+    fs::remove_file(temp_dir.path().join(".env.staging")).unwrap();
+
+    create_keyring_command(temp_dir.path())
+        .args(["decrypt", "--env", "staging", "--no-interaction"])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env.staging")).unwrap(), "APP_KEY=staging");
+}
+
+#[test]
+fn test_decrypt_env_without_keyring_entry_fails() {
+    let temp_dir = create_temp_dir();
+    add_key(temp_dir.path(), "staging", TEST_KEY);
+    fs::write(temp_dir.path().join(".env.production"), "APP_KEY=production").unwrap();
+    create_keyring_command(temp_dir.path())
+        .args(["encrypt", "--env", "production", "--key", TEST_KEY, "--no-interaction"])
+        .assert()
+        .success();
+    fs::remove_file(temp_dir.path().join(".env.production")).unwrap();
+
+    create_keyring_command(temp_dir.path())
+        .args(["decrypt", "--env", "production", "--no-interaction"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("has no key for environment 'production'"));
+}
+
+#[test]
+fn test_key_list_and_remove() {
+    let temp_dir = create_temp_dir();
+    add_key(temp_dir.path(), "staging", TEST_KEY);
+    add_key(temp_dir.path(), "production", TEST_KEY);
+
+    create_keyring_command(temp_dir.path())
+        .args(["key", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"production  [0-9a-f]{16}\nstaging  [0-9a-f]{16}\n").unwrap());
+
+    create_keyring_command(temp_dir.path())
+        .args(["key", "remove", "staging"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Removed the key of staging"));
+
+    create_keyring_command(temp_dir.path())
+        .args(["key", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("staging").not())
+        .stdout(predicate::str::contains("production"));
+}
+
+#[test]
+fn test_key_ls_and_rm_aliases() {
+    let temp_dir = create_temp_dir();
+    add_key(temp_dir.path(), "staging", TEST_KEY);
+
+    create_keyring_command(temp_dir.path())
+        .args(["key", "rm", "staging"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Removed the key of staging"));
+
+    create_keyring_command(temp_dir.path())
+        .args(["key", "ls"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("staging").not());
+}
+
+#[test]
+fn test_key_add_existing_environment_requires_force() {
+    let temp_dir = create_temp_dir();
+    add_key(temp_dir.path(), "staging", TEST_KEY);
+
+    create_keyring_command(temp_dir.path())
+        .args(["key", "add", "staging", "--generate"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Use --force to replace it"));
+
+    create_keyring_command(temp_dir.path())
+        .args(["key", "add", "staging", "--generate", "--force"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_keyring_with_wrong_passphrase_fails() {
+    let temp_dir = create_temp_dir();
+    add_key(temp_dir.path(), "staging", TEST_KEY);
+
+    create_command(temp_dir.path())
+        .env("ENVCRYPT_KEYRING_PASSPHRASE", "wrong")
+        .args(["key", "list"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_locked_keyring_without_interaction_fails() {
+    let temp_dir = create_temp_dir();
+    add_key(temp_dir.path(), "staging", TEST_KEY);
+
+    create_command(temp_dir.path())
+        .env_remove("ENVCRYPT_KEYRING_PASSPHRASE")
+        .args(["key", "list", "--no-interaction"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("ENVCRYPT_KEYRING_PASSPHRASE"));
+}
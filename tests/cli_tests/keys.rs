@@ -78,3 +78,83 @@ fn test_decrypt_with_base64_prefix_and_whitespace() {
     let decrypted_content = fs::read_to_string(&env_path).unwrap();
     assert_eq!(decrypted_content, original_content);
 }
+
+#[test]
+fn test_encrypt_with_weak_key_fails() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    fs::write(&env_path, "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), "password");
+    cmd.assert()
+        .failure()
+        .stderr(predicates::str::contains("Encryption key is too weak"));
+
+    assert!(!temp_dir.path().join(".env.encrypted").exists());
+}
+
+#[test]
+fn test_encrypt_with_weak_key_and_force_warns() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    fs::write(&env_path, "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), "password");
+    cmd.arg("--force");
+    cmd.assert()
+        .success()
+        .stderr(predicates::str::contains("Warning: Encryption key is weak"));
+
+    assert!(temp_dir.path().join(".env.encrypted").exists());
+}
+
+#[test]
+fn test_hex_key_matches_base64_key() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    let original_content = "APP_KEY=test123";
+    fs::write(&env_path, original_content).unwrap();
+
+    // Encrypt with a base64 raw key, decrypt with the same key in hex
+    let mut cmd = create_encrypt_command(temp_dir.path(), "base64:q83vEjRWeJCrze8SNFZ4kKvN7xI0VniQq83vEjRWeJA=");
+    cmd.arg("--raw-key");
+    cmd.assert().success();
+
+    fs::remove_file(&env_path).unwrap();
+    let mut cmd = create_decrypt_command(temp_dir.path(), "hex:abcdef1234567890abcdef1234567890abcdef1234567890abcdef1234567890");
+    cmd.assert().success();
+
+    let decrypted_content = fs::read_to_string(&env_path).unwrap();
+    assert_eq!(decrypted_content, original_content);
+}
+
+#[test]
+fn test_key_format_hex_without_prefix() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    let original_content = "APP_KEY=test123";
+    fs::write(&env_path, original_content).unwrap();
+
+    let hex_key = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff";
+    let mut cmd = create_encrypt_command(temp_dir.path(), hex_key);
+    cmd.arg("--key-format").arg("hex");
+    cmd.assert().success();
+
+    fs::remove_file(&env_path).unwrap();
+    let mut cmd = create_decrypt_command(temp_dir.path(), &format!("hex:{}", hex_key));
+    cmd.assert().success();
+
+    let decrypted_content = fs::read_to_string(&env_path).unwrap();
+    assert_eq!(decrypted_content, original_content);
+}
+
+#[test]
+fn test_invalid_hex_key_fails() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), "hex:not-hex");
+    cmd.assert()
+        .failure()
+        .stderr(predicates::str::contains("Key is not valid hex"));
+}
@@ -0,0 +1,61 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+
+#[test]
+fn test_lint_plaintext_file() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123\nGREETING=\"hello world\"\n").unwrap();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("lint");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(".env: no problems found in 2 lines"));
+}
+
+#[test]
+fn test_lint_reports_problems() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env.local"), "APP_KEY=test123 \r\nGREETING=hello world\nAPP_KEY=other\n").unwrap();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("lint").arg(".env.local");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Found 4 problems in .env.local"))
+        .stderr(predicate::str::contains(".env.local:1: trailing whitespace"))
+        .stderr(predicate::str::contains(".env.local:2: line ends with LF, but earlier lines end with CRLF"))
+        .stderr(predicate::str::contains(".env.local:2: unquoted value of GREETING contains spaces; quote it"))
+        .stderr(predicate::str::contains(".env.local:3: APP_KEY is already assigned on line 1"))
+        .stderr(predicate::str::contains("test123").not());
+}
+
+#[test]
+fn test_lint_encrypted_file() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123\nbroken line\n").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("lint").arg(".env.encrypted").arg("--key").arg(TEST_KEY);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(".env.encrypted:2: expected KEY=value"));
+
+    // The key is needed to lint encrypted files
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("lint").arg(".env.encrypted").arg("--no-interaction");
+    cmd.assert().failure().stderr(predicate::str::contains("expected KEY=value").not());
+}
+
+#[test]
+fn test_lint_missing_file() {
+    let temp_dir = create_temp_dir();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("lint");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(".env file not found"));
+}
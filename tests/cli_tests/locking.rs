@@ -0,0 +1,97 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Starts `envcrypt encrypt --key-stdin`, which holds the lock of `.env.encrypted` while
+/// it waits for the key on stdin, and returns once the lock file exists.
+fn spawn_lock_holder(temp_dir: &Path, envcrypt_home: &Path) -> Child {
+    let child = Command::new(assert_cmd::cargo::cargo_bin!("envcrypt"))
+        .current_dir(temp_dir)
+        .env("ENVCRYPT_HOME", envcrypt_home)
+        .args(["encrypt", "--key-stdin", "--force"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let locks = envcrypt_home.join("cache").join("locks");
+    let started = Instant::now();
+    while fs::read_dir(&locks).map(|mut entries| entries.next().is_none()).unwrap_or(true) {
+        assert!(started.elapsed() < Duration::from_secs(10), "the lock was not taken");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    // The lock file is created just before it is locked
+    std::thread::sleep(Duration::from_millis(200));
+    child
+}
+
+fn release(mut child: Child) {
+    child.stdin.take().unwrap().write_all(format!("{}\n", TEST_KEY).as_bytes()).unwrap();
+    assert!(child.wait().unwrap().success());
+}
+
+#[test]
+fn test_no_wait_fails_while_locked() {
+    let temp_dir = create_temp_dir();
+    let envcrypt_home = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    let holder = spawn_lock_holder(temp_dir.path(), envcrypt_home.path());
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.args(["--force", "--no-wait"]).env("ENVCRYPT_HOME", envcrypt_home.path());
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(".env.encrypted is locked by another envcrypt process"));
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.args(["--force", "--wait=1"]).env("ENVCRYPT_HOME", envcrypt_home.path());
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(".env.encrypted is still locked by another envcrypt process after 1s"));
+
+    release(holder);
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.args(["--force", "--no-wait"]).env("ENVCRYPT_HOME", envcrypt_home.path());
+    cmd.assert().success();
+}
+
+#[test]
+fn test_wait_for_lock() {
+    let temp_dir = create_temp_dir();
+    let envcrypt_home = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    let holder = spawn_lock_holder(temp_dir.path(), envcrypt_home.path());
+
+    let waiting = Command::new(assert_cmd::cargo::cargo_bin!("envcrypt"))
+        .current_dir(temp_dir.path())
+        .env("ENVCRYPT_HOME", envcrypt_home.path())
+        .args(["encrypt", "--key", TEST_KEY, "--force"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(Duration::from_millis(300));
+    release(holder);
+
+    let output = waiting.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let messages = String::from_utf8_lossy(&output.stdout).to_string() + &String::from_utf8_lossy(&output.stderr);
+    assert!(messages.contains("Waiting for another envcrypt process writing .env.encrypted"), "{}", messages);
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.args(["--force", "--no-interaction"]);
+    cmd.assert().success();
+}
+
+#[test]
+fn test_wait_conflicts_with_no_wait() {
+    let temp_dir = create_temp_dir();
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.args(["--wait=5", "--no-wait"]);
+    cmd.assert().failure().stderr(predicate::str::contains("cannot be used with"));
+}
@@ -0,0 +1,105 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+
+#[test]
+fn test_json_logs_on_stderr() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--log-format").arg("json").arg("-vv");
+    let output = cmd.assert().success().get_output().clone();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let lines: Vec<&str> = stderr.lines().collect();
+    assert!(!lines.is_empty());
+    assert!(lines.iter().all(|line| line.starts_with('{') && line.ends_with('}')));
+    assert!(stderr.contains(r#""level":"DEBUG""#));
+    assert!(stderr.contains(r#""message":"Successfully encrypted .env to .env.encrypted""#));
+    assert!(stderr.contains(r#""name":"encrypt""#));
+    // Verbose details go to the JSON log instead of stdout
+    assert!(!String::from_utf8(output.stdout).unwrap().contains("Input file:"));
+}
+
+#[test]
+fn test_json_logs_respect_silent() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--log-format").arg("json").arg("--silent").arg("-vvv");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::is_empty())
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn test_log_file_records_errors() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), "wrong-key");
+    cmd.arg("--force").arg("--log-file").arg("envcrypt.log").arg("--log-format").arg("json");
+    cmd.assert().failure().stderr(predicate::str::contains("Authentication failed"));
+
+    let log = fs::read_to_string(temp_dir.path().join("envcrypt.log")).unwrap();
+    assert!(log.contains(r#""level":"ERROR""#));
+    assert!(log.contains("Authentication failed"));
+    assert!(!log.contains("wrong-key"));
+}
+
+#[test]
+fn test_verbose_text_output_unchanged() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("-vvv").arg("--log-file").arg("envcrypt.log");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Input file: .env"))
+        .stderr(predicate::str::contains("[DEBUG] Cipher: AES-256-CBC"));
+
+    let log = fs::read_to_string(temp_dir.path().join("envcrypt.log")).unwrap();
+    assert!(log.contains("TRACE"));
+    assert!(log.contains("Successfully encrypted"));
+}
+
+#[test]
+fn test_timings_at_verbose_level() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("-vv");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Reading input took"))
+        .stdout(predicate::str::contains("Key derivation took"))
+        .stdout(predicate::str::contains("Encryption took"))
+        .stdout(predicate::str::contains("Writing output took"));
+
+    // Not shown below -vv
+    fs::remove_file(temp_dir.path().join(".env")).unwrap();
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("-v");
+    cmd.assert().success().stdout(predicate::str::contains("took").not());
+}
+
+#[test]
+fn test_timings_in_json_logs() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--force").arg("--log-format").arg("json").arg("-vv");
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains(r#""step":"Key derivation""#))
+        .stderr(predicate::str::contains(r#""step":"Decryption""#))
+        .stderr(predicate::str::contains(r#""duration_ms":"#));
+}
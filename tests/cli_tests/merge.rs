@@ -0,0 +1,99 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+
+fn encrypt_file(temp_dir: &Path, name: &str, content: &str, extra_args: &[&str]) {
+    fs::write(temp_dir.join(name), content).unwrap();
+    create_encrypt_command(temp_dir, TEST_KEY).arg(name).args(extra_args).assert().success();
+}
+
+fn decrypt_to_string(temp_dir: &Path, name: &str) -> String {
+    create_decrypt_command(temp_dir, TEST_KEY).arg(name).arg("--force").assert().success();
+    fs::read_to_string(temp_dir.join(name.trim_end_matches(".encrypted"))).unwrap()
+}
+
+fn create_merge_command(temp_dir: &Path) -> assert_cmd::Command {
+    let mut cmd = create_command(temp_dir);
+    cmd.arg("merge").arg("--key").arg(TEST_KEY);
+    cmd
+}
+
+#[test]
+fn test_merge_later_files_override() {
+    let temp_dir = create_temp_dir();
+    encrypt_file(temp_dir.path(), "base.env", "# shared\nDB_HOST=localhost\nDB_PORT=5432\nURL=http://${DB_HOST}\n", &[]);
+    encrypt_file(temp_dir.path(), "production.env", "DB_HOST=db.internal\nSENTRY_DSN=https://sentry\n", &[]);
+
+    let mut cmd = create_merge_command(temp_dir.path());
+    cmd.arg("base.env.encrypted").arg("production.env.encrypted").arg("--output").arg("combined.env.encrypted");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Merged base.env.encrypted, production.env.encrypted into combined.env.encrypted (4 variables, 1 overridden)",
+        ));
+
+    assert_eq!(
+        decrypt_to_string(temp_dir.path(), "combined.env.encrypted"),
+        "DB_HOST=db.internal\nDB_PORT=5432\nURL=\"http://\\${DB_HOST}\"\nSENTRY_DSN=https://sentry\n"
+    );
+}
+
+#[test]
+fn test_merge_keeps_cipher_of_first_input() {
+    let temp_dir = create_temp_dir();
+    encrypt_file(temp_dir.path(), "a.env", "A=1\n", &["--cipher", "ChaCha20-Poly1305"]);
+    encrypt_file(temp_dir.path(), "b.env", "B=2\n", &[]);
+    encrypt_file(temp_dir.path(), "c.env", "A=3\n", &[]);
+
+    let mut cmd = create_merge_command(temp_dir.path());
+    cmd.args(["a.env.encrypted", "b.env.encrypted", "c.env.encrypted", "-o", "out.env.encrypted"]);
+    cmd.assert().success();
+
+    let mut inspect = create_command(temp_dir.path());
+    inspect.arg("inspect").arg("out.env.encrypted");
+    inspect.assert().success().stdout(predicate::str::contains("CHACHA20-POLY1305"));
+    assert_eq!(decrypt_to_string(temp_dir.path(), "out.env.encrypted"), "A=3\nB=2\n");
+}
+
+#[test]
+fn test_merge_wrong_key_for_an_input_fails() {
+    let temp_dir = create_temp_dir();
+    encrypt_file(temp_dir.path(), "a.env", "A=1\n", &[]);
+    fs::write(temp_dir.path().join("b.env"), "B=2\n").unwrap();
+    create_encrypt_command(temp_dir.path(), "another-key-67890-abcdef").arg("b.env").assert().success();
+
+    let mut cmd = create_merge_command(temp_dir.path());
+    cmd.args(["a.env.encrypted", "b.env.encrypted", "-o", "out.env.encrypted"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Authentication failed"));
+    assert!(!temp_dir.path().join("out.env.encrypted").exists());
+}
+
+#[test]
+fn test_merge_requires_two_inputs_and_output() {
+    let temp_dir = create_temp_dir();
+
+    let mut cmd = create_merge_command(temp_dir.path());
+    cmd.args(["a.env.encrypted", "-o", "out.env.encrypted"]);
+    cmd.assert().failure().stderr(predicate::str::contains("2 values required"));
+
+    let mut cmd = create_merge_command(temp_dir.path());
+    cmd.args(["a.env.encrypted", "b.env.encrypted"]);
+    cmd.assert().failure().stderr(predicate::str::contains("--output <OUTPUT>"));
+}
+
+#[test]
+fn test_merge_dry_run() {
+    let temp_dir = create_temp_dir();
+    encrypt_file(temp_dir.path(), "a.env", "A=1\n", &[]);
+    encrypt_file(temp_dir.path(), "b.env", "B=2\n", &[]);
+
+    let mut cmd = create_merge_command(temp_dir.path());
+    cmd.args(["a.env.encrypted", "b.env.encrypted", "-o", "out.env.encrypted", "--dry-run"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("No files were changed"));
+    assert!(!temp_dir.path().join("out.env.encrypted").exists());
+}
@@ -0,0 +1,250 @@
+use crate::common::*;
+use base64::Engine;
+use envcrypt::cipher::{Aes256Cbc, Cipher};
+use envcrypt::format;
+use envcrypt::key::{derive_keys_with_schedule, generate_salt, KeySchedule, DEFAULT_PBKDF2_ITERATIONS};
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+
+/// Writes a legacy file (salt followed by the AES-256-CBC output, no header).
+fn write_legacy_file(path: &Path, content: &str) {
+    write_legacy_file_with_key(path, content, TEST_KEY);
+}
+
+fn write_legacy_file_with_key(path: &Path, content: &str, key: &str) {
+    let salt = generate_salt();
+    let (encryption_key, mac_key) = derive_keys_with_schedule(key, &salt, DEFAULT_PBKDF2_ITERATIONS, KeySchedule::Split);
+    let encrypted = Aes256Cbc.encrypt(content.as_bytes(), &encryption_key, &mac_key).unwrap();
+    let mut data = salt.to_vec();
+    data.extend_from_slice(&encrypted);
+    fs::write(path, base64::engine::general_purpose::STANDARD.encode(data)).unwrap();
+}
+
+fn format_version(path: &Path) -> u8 {
+    let content = fs::read_to_string(path).unwrap();
+    let data = base64::engine::general_purpose::STANDARD.decode(content.trim()).unwrap();
+    format::decode(&data).unwrap().0.version
+}
+
+fn create_migrate_command(temp_dir: &Path) -> assert_cmd::Command {
+    let mut cmd = create_command(temp_dir);
+    cmd.arg("migrate").arg("--key").arg(TEST_KEY).arg("--yes");
+    cmd
+}
+
+#[test]
+fn test_migrate_directory_tree() {
+    let temp_dir = create_temp_dir();
+    let nested = create_subdir(&create_subdir(temp_dir.path(), "services"), "api");
+    write_legacy_file(&temp_dir.path().join(".env.encrypted"), "APP_KEY=root");
+    write_legacy_file(&nested.join(".env.production.encrypted"), "APP_KEY=nested");
+
+    // A file already in the current format is left untouched
+    fs::write(temp_dir.path().join(".env.local"), "APP_KEY=current").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).arg(".env.local").assert().success();
+    let current = fs::read(temp_dir.path().join(".env.local.encrypted")).unwrap();
+
+    let mut cmd = create_migrate_command(temp_dir.path());
+    cmd.arg("--recursive");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Migrated 2 files to format version"));
+
+    assert_eq!(format_version(&temp_dir.path().join(".env.encrypted")), format::FORMAT_VERSION);
+    assert_eq!(format_version(&nested.join(".env.production.encrypted")), format::FORMAT_VERSION);
+    assert_eq!(fs::read(temp_dir.path().join(".env.local.encrypted")).unwrap(), current);
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("services/api/.env.production.encrypted");
+    cmd.assert().success();
+    assert_eq!(fs::read_to_string(nested.join(".env.production")).unwrap(), "APP_KEY=nested");
+}
+
+#[test]
+fn test_migrate_without_recursive_stays_in_directory() {
+    let temp_dir = create_temp_dir();
+    let nested = create_subdir(temp_dir.path(), "nested");
+    write_legacy_file(&temp_dir.path().join(".env.encrypted"), "APP_KEY=root");
+    write_legacy_file(&nested.join(".env.encrypted"), "APP_KEY=nested");
+
+    create_migrate_command(temp_dir.path()).assert().success();
+
+    assert_eq!(format_version(&temp_dir.path().join(".env.encrypted")), format::FORMAT_VERSION);
+    assert_eq!(format_version(&nested.join(".env.encrypted")), format::LEGACY_VERSION);
+}
+
+#[test]
+fn test_migrate_directory_requires_confirmation() {
+    let temp_dir = create_temp_dir();
+    let encrypted_path = temp_dir.path().join(".env.encrypted");
+    write_legacy_file(&encrypted_path, "APP_KEY=legacy");
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.args(["migrate", "--key", TEST_KEY]).env_remove("ENVCRYPT_ASSUME_YES");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Migrating . rewrites 1 file in place. Pass --yes to go ahead"));
+    assert_eq!(format_version(&encrypted_path), format::LEGACY_VERSION);
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.args(["migrate", "--key", TEST_KEY, "--force"]);
+    cmd.assert().success();
+    assert_eq!(format_version(&encrypted_path), format::FORMAT_VERSION);
+}
+
+#[test]
+fn test_migrate_single_file_can_change_cipher() {
+    let temp_dir = create_temp_dir();
+    let encrypted_path = temp_dir.path().join("secrets.env.encrypted");
+    write_legacy_file(&encrypted_path, "APP_KEY=legacy");
+
+    let mut cmd = create_migrate_command(temp_dir.path());
+    cmd.arg("secrets.env.encrypted").arg("--to-cipher").arg("AES-256-GCM");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Migrated 1 file to format version"));
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("inspect").arg("secrets.env.encrypted");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("AES-256-GCM"));
+}
+
+#[test]
+fn test_migrate_current_files_is_a_no_op() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=current").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+
+    create_migrate_command(temp_dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No files in an older format found in ."));
+}
+
+#[test]
+fn test_migrate_dry_run_changes_nothing() {
+    let temp_dir = create_temp_dir();
+    let encrypted_path = temp_dir.path().join(".env.encrypted");
+    write_legacy_file(&encrypted_path, "APP_KEY=legacy");
+    let before = fs::read(&encrypted_path).unwrap();
+
+    let mut cmd = create_migrate_command(temp_dir.path());
+    cmd.arg("--dry-run");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("[dry-run] Would re-encrypt"));
+
+    assert_eq!(fs::read(&encrypted_path).unwrap(), before);
+}
+
+#[test]
+fn test_migrate_wrong_key_names_the_file() {
+    let temp_dir = create_temp_dir();
+    write_legacy_file(&temp_dir.path().join(".env.encrypted"), "APP_KEY=legacy");
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("migrate").arg("--key").arg("wrong-key").arg("--yes");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Error migrating ./.env.encrypted"));
+}
+
+#[test]
+fn test_migrate_failure_changes_no_file() {
+    let temp_dir = create_temp_dir();
+    write_legacy_file(&temp_dir.path().join(".env.a.encrypted"), "APP_KEY=first");
+    write_legacy_file_with_key(&temp_dir.path().join(".env.b.encrypted"), "APP_KEY=second", "other-key");
+    let before = fs::read(temp_dir.path().join(".env.a.encrypted")).unwrap();
+
+    let mut cmd = create_migrate_command(temp_dir.path());
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Error migrating ./.env.b.encrypted"))
+        .stderr(predicate::str::contains("no files were changed"));
+
+    assert_eq!(fs::read(temp_dir.path().join(".env.a.encrypted")).unwrap(), before);
+    let mut names: Vec<_> = fs::read_dir(temp_dir.path()).unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    assert_eq!(names, [".env.a.encrypted", ".env.b.encrypted"]);
+}
+
+#[test]
+fn test_migrate_finishes_interrupted_batch() {
+    let temp_dir = create_temp_dir();
+    let first = temp_dir.path().join(".env.a.encrypted");
+    let second = temp_dir.path().join(".env.b.encrypted");
+    write_legacy_file(&first, "APP_KEY=first");
+    write_legacy_file(&second, "APP_KEY=second");
+    let staged = create_temp_dir();
+    fs::write(staged.path().join(".env"), "APP_KEY=second").unwrap();
+    create_encrypt_command(staged.path(), TEST_KEY).assert().success();
+    fs::rename(staged.path().join(".env.encrypted"), temp_dir.path().join(".env.b.encrypted.tmp")).unwrap();
+
+    // A batch was interrupted after its commit, before the second file was replaced
+    let journal = format!("{}\n{}\ncommit\n", first.canonicalize().unwrap().display(), second.canonicalize().unwrap().display());
+    fs::write(temp_dir.path().join(".envcrypt-journal"), journal).unwrap();
+
+    let mut cmd = create_migrate_command(temp_dir.path());
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Finished an interrupted batch: replaced 1 file"))
+        .stdout(predicate::str::contains("Migrated 1 file to format version"));
+
+    assert_eq!(format_version(&first), format::FORMAT_VERSION);
+    assert_eq!(format_version(&second), format::FORMAT_VERSION);
+    assert!(!temp_dir.path().join(".envcrypt-journal").exists());
+    assert!(!temp_dir.path().join(".env.b.encrypted.tmp").exists());
+}
+
+#[test]
+fn test_migrate_skips_excluded_and_ignored_directories() {
+    let temp_dir = create_temp_dir();
+    let vendored = create_subdir(&create_subdir(temp_dir.path(), "node_modules"), "pkg");
+    let build = create_subdir(temp_dir.path(), "build");
+    let legacy = create_subdir(temp_dir.path(), "legacy");
+    let api = create_subdir(temp_dir.path(), "api");
+    for dir in [&vendored, &build, &legacy, &api] {
+        write_legacy_file(&dir.join(".env.encrypted"), "APP_KEY=nested");
+    }
+    fs::write(temp_dir.path().join(".gitignore"), "build/\n").unwrap();
+    fs::write(temp_dir.path().join("envcrypt.toml"), "exclude = [\"legacy/\"]\n").unwrap();
+
+    create_migrate_command(temp_dir.path())
+        .arg("--recursive")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Migrated 1 file to format version"));
+    assert_eq!(format_version(&api.join(".env.encrypted")), format::FORMAT_VERSION);
+    for dir in [&vendored, &build, &legacy] {
+        assert_eq!(format_version(&dir.join(".env.encrypted")), format::LEGACY_VERSION);
+    }
+}
+
+#[test]
+fn test_migrate_exclude_and_include_flags() {
+    let temp_dir = create_temp_dir();
+    let api = create_subdir(temp_dir.path(), "api");
+    let web = create_subdir(temp_dir.path(), "web");
+    write_legacy_file(&api.join(".env.encrypted"), "APP_KEY=api");
+    write_legacy_file(&api.join(".env.staging.encrypted"), "APP_KEY=staging");
+    write_legacy_file(&web.join(".env.encrypted"), "APP_KEY=web");
+
+    create_migrate_command(temp_dir.path())
+        .args(["--recursive", "--include", "/api/", "--exclude", "*.staging.encrypted"])
+        .assert()
+        .success();
+    assert_eq!(format_version(&api.join(".env.encrypted")), format::FORMAT_VERSION);
+    assert_eq!(format_version(&api.join(".env.staging.encrypted")), format::LEGACY_VERSION);
+    assert_eq!(format_version(&web.join(".env.encrypted")), format::LEGACY_VERSION);
+
+    create_migrate_command(temp_dir.path())
+        .args(["--recursive", "--exclude", "[z-a]"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid exclude pattern '[z-a]'"));
+}
@@ -10,3 +10,45 @@ pub mod keys;
 pub mod errors;
 pub mod flags;
 pub mod ciphers;
+pub mod kdf;
+pub mod key_shares;
+pub mod recovery;
+pub mod recrypt;
+pub mod backup;
+pub mod dry_run;
+pub mod file_format;
+pub mod inspect;
+pub mod aad;
+pub mod key_providers;
+pub mod env_answers;
+pub mod logging;
+pub mod migrate;
+pub mod export;
+pub mod import;
+pub mod sync;
+pub mod render;
+pub mod example;
+pub mod peek;
+pub mod multiline;
+pub mod newline;
+pub mod encoding;
+pub mod check;
+pub mod lint;
+pub mod merge;
+pub mod audit;
+pub mod vectors;
+pub mod self_update;
+pub mod help;
+pub mod tui;
+pub mod key_output;
+pub mod keyring;
+pub mod gpg;
+pub mod ssh;
+pub mod tpm;
+pub mod fido2;
+pub mod signing;
+pub mod completions;
+pub mod config;
+pub mod trash;
+pub mod locking;
+pub mod rotate;
@@ -0,0 +1,62 @@
+use crate::common::*;
+use std::fs;
+use std::path::Path;
+
+fn encrypt_file(temp_dir: &Path, name: &str, content: &str) {
+    fs::write(temp_dir.join(name), content).unwrap();
+    create_encrypt_command(temp_dir, TEST_KEY).arg(name).arg("--force").assert().success();
+}
+
+#[test]
+fn test_decrypt_keeps_or_converts_line_endings() {
+    let temp_dir = create_temp_dir();
+    encrypt_file(temp_dir.path(), ".env", "A=1\r\nB=2\r\n");
+    create_decrypt_command(temp_dir.path(), TEST_KEY).arg("--force").assert().success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "A=1\r\nB=2\r\n");
+
+    create_decrypt_command(temp_dir.path(), TEST_KEY).args(["--force", "--newline", "lf"]).assert().success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "A=1\nB=2\n");
+}
+
+#[test]
+fn test_encrypt_newline_converts_plaintext() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "A=1\nB=\"x\ny\"\n").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).args(["--newline", "crlf"]).assert().success();
+
+    // The original file is left alone; the encrypted copy has CRLF line endings
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "A=1\nB=\"x\ny\"\n");
+    create_decrypt_command(temp_dir.path(), TEST_KEY).arg("--force").assert().success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "A=1\r\nB=\"x\r\ny\"\r\n");
+}
+
+#[test]
+fn test_merge_keeps_line_endings_of_first_input() {
+    let temp_dir = create_temp_dir();
+    encrypt_file(temp_dir.path(), "base.env", "# Windows\r\nA=1\r\nB=2\r\n");
+    encrypt_file(temp_dir.path(), "override.env", "B=3\n");
+
+    let merge = |extra_args: &[&str]| {
+        create_command(temp_dir.path())
+            .args(["merge", "--key", TEST_KEY, "base.env.encrypted", "override.env.encrypted", "-o", "merged.env.encrypted", "--force"])
+            .args(extra_args)
+            .assert()
+            .success();
+        create_decrypt_command(temp_dir.path(), TEST_KEY).args(["merged.env.encrypted", "--force"]).assert().success();
+        fs::read_to_string(temp_dir.path().join("merged.env")).unwrap()
+    };
+    assert_eq!(merge(&[]), "A=1\r\nB=3\r\n");
+    assert_eq!(merge(&["--newline", "lf"]), "A=1\nB=3\n");
+}
+
+#[test]
+fn test_example_newline() {
+    let temp_dir = create_temp_dir();
+    encrypt_file(temp_dir.path(), ".env", "# App\nA=1\n");
+
+    create_command(temp_dir.path())
+        .args(["example", "--key", TEST_KEY, "--newline", "crlf"])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env.example")).unwrap(), "# App\r\nA=\r\n");
+}
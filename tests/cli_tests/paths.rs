@@ -58,3 +58,70 @@ fn test_decrypt_with_custom_input_path() {
     let decrypted_content = fs::read_to_string(&env_path).unwrap();
     assert_eq!(decrypted_content, original_content);
 }
+
+#[test]
+fn test_positional_input_path() {
+    let temp_dir = create_temp_dir();
+    let tmp_dir = create_subdir(temp_dir.path(), "tmp");
+    let env_path = tmp_dir.join(".env");
+    fs::write(&env_path, "APP_KEY=test123").unwrap();
+
+    // Encrypt and decrypt with the path given as a positional argument
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("tmp/.env");
+    cmd.assert().success();
+    assert!(tmp_dir.join(".env.encrypted").exists());
+
+    fs::remove_file(&env_path).unwrap();
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("tmp/.env.encrypted");
+    cmd.assert().success();
+    assert_eq!(fs::read_to_string(&env_path).unwrap(), "APP_KEY=test123");
+}
+
+#[test]
+fn test_positional_input_conflicts_with_input_flag() {
+    let temp_dir = create_temp_dir();
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("a.env.encrypted").arg("--input").arg("b.env.encrypted");
+    cmd.assert()
+        .failure()
+        .stderr(predicates::str::contains("cannot be used with"));
+}
+
+#[test]
+fn test_cwd_flag_resolves_defaults_in_directory() {
+    let temp_dir = create_temp_dir();
+    let project = create_subdir(temp_dir.path(), "project");
+    fs::write(project.join(".env"), "APP_KEY=test123").unwrap();
+    fs::write(project.join("envcrypt.toml"), "kdf_iterations = 1000\n").unwrap();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("-C").arg("project");
+    cmd.assert().success();
+    assert!(project.join(".env.encrypted").exists());
+    assert!(!temp_dir.path().join(".env.encrypted").exists());
+
+    // The project's envcrypt.toml was used
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("inspect").arg("project/.env.encrypted");
+    cmd.assert().success().stdout(predicates::str::contains("1000 iterations"));
+
+    fs::remove_file(project.join(".env")).unwrap();
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--cwd").arg("project");
+    cmd.assert().success();
+    assert_eq!(fs::read_to_string(project.join(".env")).unwrap(), "APP_KEY=test123");
+}
+
+#[test]
+fn test_cwd_flag_missing_directory_fails() {
+    let temp_dir = create_temp_dir();
+
+    let mut cmd = create_encrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("-C").arg("missing");
+    cmd.assert()
+        .failure()
+        .stderr(predicates::str::contains("Cannot change to directory missing"));
+}
@@ -0,0 +1,44 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+
+#[test]
+fn test_peek_masks_values() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "# Stripe\nSTRIPE_KEY=sk_live_51HqLyjWDarjtT1zdp7dc\nDEBUG=true\nEMPTY=\n").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+
+    create_command(temp_dir.path())
+        .args(["peek", "--key", TEST_KEY])
+        .assert()
+        .success()
+        .stdout("STRIPE_KEY  sk…dc\nDEBUG       ••••\nEMPTY       (empty)\n");
+}
+
+#[test]
+fn test_peek_env_file_writes_nothing() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env.production"), "DB_PASSWORD=correct-horse-battery").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).args(["--env", "production", "--prune"]).assert().success();
+
+    create_command(temp_dir.path())
+        .args(["peek", "--env", "production", "--key", TEST_KEY])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("DB_PASSWORD  co…ry"))
+        .stdout(predicate::str::contains("horse").not());
+    assert!(!temp_dir.path().join(".env.production").exists());
+}
+
+#[test]
+fn test_peek_wrong_key_fails() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+
+    create_command(temp_dir.path())
+        .args(["peek", "--key", "wrong-key-123456"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Authentication failed"));
+}
@@ -0,0 +1,94 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+
+/// Runs `key recovery-keygen` and returns the printed private key.
+fn generate_recovery_key(temp_dir: &std::path::Path) -> String {
+    let mut cmd = create_command(temp_dir);
+    cmd.arg("key").arg("recovery-keygen");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    String::from_utf8(output).unwrap()
+        .lines()
+        .find_map(|line| line.strip_prefix("Recovery private key: "))
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn test_recovery_key_decrypts_without_user_key() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+
+    let private_key = generate_recovery_key(temp_dir.path());
+    let config = fs::read_to_string(temp_dir.path().join("envcrypt.toml")).unwrap();
+    assert!(config.contains("recovery_public_key = \"base64:"));
+
+    let original_content = "APP_KEY=test123";
+    fs::write(&env_path, original_content).unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    fs::remove_file(&env_path).unwrap();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("decrypt").arg("--recovery-key").arg(&private_key);
+    cmd.assert().success();
+    assert_eq!(fs::read_to_string(&env_path).unwrap(), original_content);
+
+    // The personal key still works as well
+    fs::remove_file(&env_path).unwrap();
+    create_decrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+}
+
+#[test]
+fn test_recovery_key_without_escrow_fails() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("key").arg("recovery-keygen").arg("--no-save");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let private_key = String::from_utf8(output).unwrap()
+        .lines()
+        .find_map(|line| line.strip_prefix("Recovery private key: ").map(str::to_string))
+        .unwrap();
+    assert!(!temp_dir.path().join("envcrypt.toml").exists());
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("decrypt").arg("--recovery-key").arg(&private_key).arg("--force");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("no keys wrapped for a recovery recipient"));
+}
+
+#[test]
+fn test_wrong_recovery_key_fails() {
+    let temp_dir = create_temp_dir();
+    generate_recovery_key(temp_dir.path());
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+
+    let other_dir = create_temp_dir();
+    let other_key = generate_recovery_key(other_dir.path());
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("decrypt").arg("--recovery-key").arg(&other_key).arg("--force");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Authentication failed"));
+}
+
+#[test]
+fn test_recovery_keygen_refuses_to_replace_without_force() {
+    let temp_dir = create_temp_dir();
+    generate_recovery_key(temp_dir.path());
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("key").arg("recovery-keygen");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("already has a recovery_public_key"));
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("key").arg("recovery-keygen").arg("--force");
+    cmd.assert().success();
+}
@@ -0,0 +1,123 @@
+use crate::common::*;
+use base64::Engine;
+use envcrypt::cipher::{Aes256Cbc, Cipher};
+use envcrypt::format::{self, Kdf};
+use envcrypt::key::{derive_keys_with_schedule, generate_salt, KeySchedule, DEFAULT_PBKDF2_ITERATIONS};
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+
+const RAW_KEY: &str = "base64:q83vEjRWeJCrze8SNFZ4kKvN7xI0VniQq83vEjRWeJA=";
+
+fn read_header(path: &Path) -> format::Header {
+    let content = fs::read_to_string(path).unwrap();
+    let data = base64::engine::general_purpose::STANDARD.decode(content.trim()).unwrap();
+    let (header, _) = format::decode(&data).unwrap();
+    header
+}
+
+fn create_recrypt_command(temp_dir: &Path, key: &str) -> assert_cmd::Command {
+    let mut cmd = create_command(temp_dir);
+    cmd.arg("recrypt").arg("--key").arg(key);
+    cmd
+}
+
+#[test]
+fn test_recrypt_changes_cipher_and_preserves_content() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    let encrypted_path = temp_dir.path().join(".env.encrypted");
+
+    let original_content = "APP_KEY=test123\nDB_PASSWORD=secret\n";
+    fs::write(&env_path, original_content).unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).arg("--kdf-iterations").arg("2000").assert().success();
+    fs::remove_file(&env_path).unwrap();
+
+    let mut cmd = create_recrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--from-cipher").arg("AES-256-CBC").arg("--to-cipher").arg("AES-256-GCM");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("re-encrypted .env.encrypted with AES-256-GCM"));
+
+    let header = read_header(&encrypted_path);
+    assert_eq!(header.cipher.as_deref(), Some("AES-256-GCM"));
+    assert_eq!(header.kdf, Kdf::Pbkdf2Sha256 { iterations: 2000 });
+    assert!(!temp_dir.path().join(".env.encrypted.tmp").exists());
+
+    create_decrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    assert_eq!(fs::read_to_string(&env_path).unwrap(), original_content);
+}
+
+#[test]
+fn test_recrypt_changes_kdf_iterations() {
+    let temp_dir = create_temp_dir();
+    let encrypted_path = temp_dir.path().join(".env.encrypted");
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).arg("--kdf-iterations").arg("2000").assert().success();
+
+    let mut cmd = create_recrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--kdf-iterations").arg("3000");
+    cmd.assert().success();
+
+    let header = read_header(&encrypted_path);
+    assert_eq!(header.cipher.as_deref(), Some("AES-256-CBC"));
+    assert_eq!(header.kdf, Kdf::Pbkdf2Sha256 { iterations: 3000 });
+}
+
+#[test]
+fn test_recrypt_upgrades_legacy_file() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    let encrypted_path = temp_dir.path().join(".env.encrypted");
+
+    let original_content = "APP_KEY=legacy";
+    let salt = generate_salt();
+    let (encryption_key, mac_key) = derive_keys_with_schedule(TEST_KEY, &salt, DEFAULT_PBKDF2_ITERATIONS, KeySchedule::Split);
+    let encrypted = Aes256Cbc.encrypt(original_content.as_bytes(), &encryption_key, &mac_key).unwrap();
+    let mut data = salt.to_vec();
+    data.extend_from_slice(&encrypted);
+    fs::write(&encrypted_path, base64::engine::general_purpose::STANDARD.encode(data)).unwrap();
+
+    let mut cmd = create_recrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--to-cipher").arg("chacha20-poly1305");
+    cmd.assert().success();
+
+    let header = read_header(&encrypted_path);
+    assert_eq!(header.version, format::FORMAT_VERSION);
+    assert_eq!(header.cipher.as_deref(), Some("CHACHA20-POLY1305"));
+    assert_eq!(header.key_schedule, KeySchedule::HkdfExpand);
+
+    create_decrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    assert_eq!(fs::read_to_string(&env_path).unwrap(), original_content);
+}
+
+#[test]
+fn test_recrypt_keeps_raw_key_mode() {
+    let temp_dir = create_temp_dir();
+    let encrypted_path = temp_dir.path().join(".env.encrypted");
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), RAW_KEY).arg("--raw-key").assert().success();
+
+    let mut cmd = create_recrypt_command(temp_dir.path(), RAW_KEY);
+    cmd.arg("--to-cipher").arg("AES-256-GCM");
+    cmd.assert().success();
+
+    assert_eq!(read_header(&encrypted_path).kdf, Kdf::HkdfSha256);
+}
+
+#[test]
+fn test_recrypt_wrong_key_leaves_file_untouched() {
+    let temp_dir = create_temp_dir();
+    let encrypted_path = temp_dir.path().join(".env.encrypted");
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    let before = fs::read_to_string(&encrypted_path).unwrap();
+
+    let mut cmd = create_recrypt_command(temp_dir.path(), "wrong-key");
+    cmd.arg("--to-cipher").arg("AES-256-GCM");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Authentication failed"));
+
+    assert_eq!(fs::read_to_string(&encrypted_path).unwrap(), before);
+}
@@ -0,0 +1,114 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+
+fn setup(temp_dir: &Path) {
+    fs::write(temp_dir.join(".env"), "SERVER_NAME=example.com\nUPSTREAM_PORT=8080\n").unwrap();
+    create_encrypt_command(temp_dir, TEST_KEY).assert().success();
+    fs::remove_file(temp_dir.join(".env")).unwrap();
+    fs::write(
+        temp_dir.join("nginx.conf.tpl"),
+        "server {\n    server_name ${SERVER_NAME};\n    location / { proxy_pass http://127.0.0.1:{{ UPSTREAM_PORT }}$request_uri; }\n}\n",
+    ).unwrap();
+}
+
+fn create_render_command(temp_dir: &Path) -> assert_cmd::Command {
+    let mut cmd = create_command(temp_dir);
+    cmd.arg("render").arg("nginx.conf.tpl").arg("--key").arg(TEST_KEY);
+    cmd
+}
+
+const RENDERED: &str = "server {\n    server_name example.com;\n    location / { proxy_pass http://127.0.0.1:8080$request_uri; }\n}\n";
+
+#[test]
+fn test_render_to_stdout() {
+    let temp_dir = create_temp_dir();
+    setup(temp_dir.path());
+
+    create_render_command(temp_dir.path())
+        .assert()
+        .success()
+        .stdout(RENDERED);
+    // The variables are never written to a plaintext .env file
+    assert!(!temp_dir.path().join(".env").exists());
+}
+
+#[test]
+fn test_render_to_file() {
+    let temp_dir = create_temp_dir();
+    setup(temp_dir.path());
+
+    let mut cmd = create_render_command(temp_dir.path());
+    cmd.arg("--output").arg("nginx.conf");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Rendered nginx.conf.tpl to nginx.conf"));
+    assert_eq!(fs::read_to_string(temp_dir.path().join("nginx.conf")).unwrap(), RENDERED);
+
+    // An existing output file is only replaced with --force
+    let mut cmd = create_render_command(temp_dir.path());
+    cmd.arg("--output").arg("nginx.conf").arg("--no-interaction");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("already exists"));
+}
+
+#[test]
+fn test_render_undefined_variables_fail() {
+    let temp_dir = create_temp_dir();
+    setup(temp_dir.path());
+    fs::write(temp_dir.path().join("nginx.conf.tpl"), "${SERVER_NAME} ${SSL_CERT} {{SSL_KEY}}").unwrap();
+
+    create_render_command(temp_dir.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("undefined variables SSL_CERT, SSL_KEY in .env.encrypted"));
+
+    let mut cmd = create_render_command(temp_dir.path());
+    cmd.arg("--keep-undefined");
+    cmd.assert()
+        .success()
+        .stdout("example.com ${SSL_CERT} {{SSL_KEY}}");
+}
+
+#[test]
+fn test_render_missing_template_fails() {
+    let temp_dir = create_temp_dir();
+    setup(temp_dir.path());
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("render").arg("missing.tpl").arg("--key").arg(TEST_KEY);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("missing.tpl file not found"));
+}
+
+#[test]
+fn test_render_dry_run() {
+    let temp_dir = create_temp_dir();
+    setup(temp_dir.path());
+
+    let mut cmd = create_render_command(temp_dir.path());
+    cmd.arg("--output").arg("nginx.conf").arg("--dry-run");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("example.com").not());
+    assert!(!temp_dir.path().join("nginx.conf").exists());
+}
+
+#[test]
+fn test_render_uses_expanded_values() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "HOST=example.com\nURL=https://${HOST}/api\n").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    fs::write(temp_dir.path().join("app.yml.tpl"), "url: {{ URL }}\n").unwrap();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("render").arg("app.yml.tpl").arg("--key").arg(TEST_KEY);
+    cmd.assert().success().stdout("url: https://example.com/api\n");
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("render").arg("app.yml.tpl").arg("--key").arg(TEST_KEY).arg("--no-expand");
+    cmd.assert().success().stdout("url: https://${HOST}/api\n");
+}
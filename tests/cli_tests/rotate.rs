@@ -0,0 +1,104 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+
+const NEW_KEY: &str = "rotated-encryption-key-67890";
+
+/// Writes `content` to `<dir>/<name>` and encrypts it with `key`, removing the plaintext.
+fn write_encrypted(dir: &Path, name: &str, content: &str, key: &str) {
+    fs::write(dir.join(name), content).unwrap();
+    create_encrypt_command(dir, key).arg(name).assert().success();
+    fs::remove_file(dir.join(name)).unwrap();
+}
+
+fn create_rotate_command(temp_dir: &Path) -> assert_cmd::Command {
+    let mut cmd = create_command(temp_dir);
+    cmd.args(["rotate", "--old-key", TEST_KEY, "--new-key", NEW_KEY]);
+    cmd
+}
+
+fn report(output: &[u8]) -> serde_json::Value {
+    serde_json::from_slice(output).unwrap()
+}
+
+#[test]
+fn test_rotate_directory_tree() {
+    let temp_dir = create_temp_dir();
+    let nested = create_subdir(&create_subdir(temp_dir.path(), "services"), "api");
+    write_encrypted(temp_dir.path(), ".env", "APP_KEY=root", TEST_KEY);
+    write_encrypted(&nested, ".env.production", "APP_KEY=nested", TEST_KEY);
+    write_encrypted(&nested, ".env.other", "APP_KEY=other", "another-team-key-13579");
+    let other = fs::read(nested.join(".env.other.encrypted")).unwrap();
+
+    let mut cmd = create_rotate_command(temp_dir.path());
+    cmd.arg("--recursive");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let report = report(&output);
+    assert_eq!(report["rotated"], serde_json::json!(["./.env.encrypted", "./services/api/.env.production.encrypted"]));
+    assert_eq!(report["skipped"], serde_json::json!(["./services/api/.env.other.encrypted"]));
+    assert_eq!(report["failed"], serde_json::json!([]));
+    assert_eq!(report["dry_run"], false);
+    assert_ne!(report["old_key_fingerprint"], report["new_key_fingerprint"]);
+
+    assert_eq!(fs::read(nested.join(".env.other.encrypted")).unwrap(), other);
+    create_decrypt_command(temp_dir.path(), TEST_KEY).arg("--no-interaction").assert().failure();
+    let mut cmd = create_decrypt_command(temp_dir.path(), NEW_KEY);
+    cmd.arg("services/api/.env.production.encrypted");
+    cmd.assert().success();
+    assert_eq!(fs::read_to_string(nested.join(".env.production")).unwrap(), "APP_KEY=nested");
+}
+
+#[test]
+fn test_rotate_dry_run_writes_report_file() {
+    let temp_dir = create_temp_dir();
+    write_encrypted(temp_dir.path(), ".env", "APP_KEY=root", TEST_KEY);
+    let before = fs::read(temp_dir.path().join(".env.encrypted")).unwrap();
+
+    let mut cmd = create_rotate_command(temp_dir.path());
+    cmd.args(["--dry-run", "--report", "rotation.json"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("[dry-run] Would rotate the key of ./.env.encrypted"))
+        .stdout(predicate::str::contains("Rotated 1, skipped 0 and failed 0 of 1 files; wrote the report to rotation.json"));
+
+    let report = report(&fs::read(temp_dir.path().join("rotation.json")).unwrap());
+    assert_eq!(report["rotated"], serde_json::json!(["./.env.encrypted"]));
+    assert_eq!(report["dry_run"], true);
+    assert_eq!(fs::read(temp_dir.path().join(".env.encrypted")).unwrap(), before);
+}
+
+#[test]
+fn test_rotate_reports_failed_files() {
+    let temp_dir = create_temp_dir();
+    write_encrypted(temp_dir.path(), ".env", "APP_KEY=root", TEST_KEY);
+    fs::write(temp_dir.path().join(".env.broken.encrypted"), "not an encrypted file").unwrap();
+
+    let output = create_rotate_command(temp_dir.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to rotate 1 of the files"))
+        .get_output()
+        .stdout
+        .clone();
+    let report = report(&output);
+    assert_eq!(report["rotated"], serde_json::json!(["./.env.encrypted"]));
+    assert_eq!(report["failed"][0]["path"], "./.env.broken.encrypted");
+    assert!(report["failed"][0]["error"].is_string());
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), NEW_KEY);
+    cmd.arg("--no-interaction");
+    cmd.assert().success();
+}
+
+#[test]
+fn test_rotate_rejects_the_same_key() {
+    let temp_dir = create_temp_dir();
+    write_encrypted(temp_dir.path(), ".env", "APP_KEY=root", TEST_KEY);
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.args(["rotate", "--old-key", TEST_KEY, "--new-key", TEST_KEY]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("The new key is the same as the old key"));
+}
@@ -33,6 +33,26 @@ fn test_encrypt_decrypt_roundtrip() {
     assert_eq!(decrypted_content, original_content);
 }
 
+#[test]
+fn test_encrypt_decrypt_aliases() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    let original_content = "APP_KEY=test123";
+
+    for (encrypt, decrypt) in [("enc", "dec"), ("e", "d")] {
+        fs::write(&env_path, original_content).unwrap();
+        let mut cmd = create_command(temp_dir.path());
+        cmd.args([encrypt, "--key", TEST_KEY, "--force"]);
+        cmd.assert().success();
+        fs::remove_file(&env_path).unwrap();
+
+        let mut cmd = create_command(temp_dir.path());
+        cmd.args([decrypt, "--key", TEST_KEY]);
+        cmd.assert().success();
+        assert_eq!(fs::read_to_string(&env_path).unwrap(), original_content);
+    }
+}
+
 #[test]
 fn test_encrypt_decrypt_roundtrip_with_custom_paths() {
     let temp_dir = create_temp_dir();
@@ -68,3 +88,47 @@ fn test_encrypt_decrypt_roundtrip_with_custom_paths() {
     let decrypted_content = fs::read_to_string(&env_path).unwrap();
     assert_eq!(decrypted_content, original_content);
 }
+
+#[cfg(unix)]
+#[test]
+fn test_roundtrip_restores_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    fs::write(&env_path, "APP_KEY=test123").unwrap();
+    fs::set_permissions(&env_path, fs::Permissions::from_mode(0o600)).unwrap();
+
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    fs::remove_file(&env_path).unwrap();
+
+    create_decrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    assert_eq!(fs::metadata(&env_path).unwrap().permissions().mode() & 0o777, 0o600);
+
+    // Overwriting a looser existing file tightens it too
+    fs::set_permissions(&env_path, fs::Permissions::from_mode(0o644)).unwrap();
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--force");
+    cmd.assert().success();
+    assert_eq!(fs::metadata(&env_path).unwrap().permissions().mode() & 0o777, 0o600);
+}
+
+#[test]
+fn test_roundtrip_preserve_times() {
+    let temp_dir = create_temp_dir();
+    let env_path = temp_dir.path().join(".env");
+    fs::write(&env_path, "APP_KEY=test123").unwrap();
+    let modified = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_600_000_000);
+    fs::File::options().write(true).open(&env_path).unwrap().set_modified(modified).unwrap();
+
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    fs::remove_file(&env_path).unwrap();
+
+    create_decrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    assert_ne!(fs::metadata(&env_path).unwrap().modified().unwrap(), modified);
+
+    let mut cmd = create_decrypt_command(temp_dir.path(), TEST_KEY);
+    cmd.arg("--force").arg("--preserve-times");
+    cmd.assert().success();
+    assert_eq!(fs::metadata(&env_path).unwrap().modified().unwrap(), modified);
+}
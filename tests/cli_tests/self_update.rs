@@ -0,0 +1,173 @@
+#![cfg(unix)]
+
+use crate::common::*;
+use predicates::prelude::*;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// Stand-in release binary; it only needs to answer `--version`.
+const NEW_BINARY: &str = "#!/bin/sh\necho \"envcrypt 9.9.9\"\n";
+
+/// Installs a fake `curl` in `temp_dir/bin` that serves the files in `temp_dir/served`
+/// by the last path segment of the URL and logs its arguments to `temp_dir/curl.log`.
+/// Returns the PATH to run envcrypt with.
+fn install_fake_curl(temp_dir: &Path) -> String {
+    let bin = create_subdir(temp_dir, "bin");
+    create_subdir(temp_dir, "served");
+    let script = format!(
+        r#"#!/bin/sh
+echo "$@" >> "{dir}/curl.log"
+out=""
+while [ $# -gt 0 ]; do
+    case "$1" in
+        -o) out="$2"; shift 2 ;;
+        -H) shift 2 ;;
+        -*) shift ;;
+        *) url="$1"; shift ;;
+    esac
+done
+file="{dir}/served/${{url##*/}}"
+if [ ! -f "$file" ]; then
+    echo "curl: (22) The requested URL returned error: 404" >&2
+    exit 22
+fi
+if [ -n "$out" ]; then cp "$file" "$out"; else cat "$file"; fi
+"#,
+        dir = temp_dir.display()
+    );
+    let curl = bin.join("curl");
+    fs::write(&curl, script).unwrap();
+    fs::set_permissions(&curl, fs::Permissions::from_mode(0o755)).unwrap();
+    format!("{}:{}", bin.display(), std::env::var("PATH").unwrap_or_default())
+}
+
+fn asset_name(version: &str) -> String {
+    let os = if std::env::consts::OS == "macos" { "darwin" } else { std::env::consts::OS };
+    format!("envcrypt-{}-{}-{}", version, os, std::env::consts::ARCH)
+}
+
+/// Serves a release `tag` with the stand-in binary and, unless `checksum` is `None`, a
+/// checksum file; `name` is the API response file it is served as.
+fn serve_release(temp_dir: &Path, name: &str, tag: &str, checksum: Option<&str>, prerelease_list: bool) {
+    let version = tag.trim_start_matches('v');
+    let binary = asset_name(version);
+    let served = temp_dir.join("served");
+    fs::write(served.join(&binary), NEW_BINARY).unwrap();
+    let mut assets = vec![serde_json::json!({
+        "name": binary,
+        "browser_download_url": format!("https://example.com/download/{}", binary),
+    })];
+    if let Some(checksum) = checksum {
+        fs::write(served.join(format!("{}.sha256", binary)), format!("{}  {}\n", checksum, binary)).unwrap();
+        assets.push(serde_json::json!({
+            "name": format!("{}.sha256", binary),
+            "browser_download_url": format!("https://example.com/download/{}.sha256", binary),
+        }));
+    }
+    let release = serde_json::json!({ "tag_name": tag, "draft": false, "assets": assets });
+    let response = if prerelease_list { serde_json::json!([release]) } else { release };
+    fs::write(served.join(name), response.to_string()).unwrap();
+}
+
+fn new_binary_checksum() -> String {
+    Sha256::digest(NEW_BINARY.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Copies envcrypt into `temp_dir/install`, so the update replaces the copy.
+fn install_copy(temp_dir: &Path) -> PathBuf {
+    let install = create_subdir(temp_dir, "install");
+    let copy = install.join("envcrypt");
+    fs::copy(assert_cmd::cargo::cargo_bin!("envcrypt"), &copy).unwrap();
+    copy
+}
+
+fn self_update_command(temp_dir: &Path, binary: &Path, path: &str) -> assert_cmd::Command {
+    let mut cmd = assert_cmd::Command::new(binary);
+    cmd.current_dir(temp_dir).env("PATH", path).arg("self-update");
+    cmd
+}
+
+#[test]
+fn test_self_update_replaces_binary() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_curl(temp_dir.path());
+    serve_release(temp_dir.path(), "latest", "v9.9.9", Some(&new_binary_checksum()), false);
+    let binary = install_copy(temp_dir.path());
+
+    self_update_command(temp_dir.path(), &binary, &path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("Updated envcrypt {} to 9.9.9", env!("CARGO_PKG_VERSION"))));
+    assert_eq!(fs::read_to_string(&binary).unwrap(), NEW_BINARY);
+    assert_eq!(fs::read_dir(binary.parent().unwrap()).unwrap().count(), 1);
+}
+
+#[test]
+fn test_self_update_rejects_checksum_mismatch() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_curl(temp_dir.path());
+    serve_release(temp_dir.path(), "latest", "v9.9.9", Some(&"0".repeat(64)), false);
+    let binary = install_copy(temp_dir.path());
+    let original = fs::read(&binary).unwrap();
+
+    self_update_command(temp_dir.path(), &binary, &path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Checksum mismatch"));
+    assert_eq!(fs::read(&binary).unwrap(), original);
+    assert_eq!(fs::read_dir(binary.parent().unwrap()).unwrap().count(), 1);
+}
+
+#[test]
+fn test_self_update_requires_checksum() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_curl(temp_dir.path());
+    serve_release(temp_dir.path(), "latest", "v9.9.9", None, false);
+    let binary = install_copy(temp_dir.path());
+
+    self_update_command(temp_dir.path(), &binary, &path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("has no checksum"));
+}
+
+#[test]
+fn test_self_update_up_to_date_and_dry_run() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_curl(temp_dir.path());
+    serve_release(temp_dir.path(), "latest", "v0.0.1", Some(&new_binary_checksum()), false);
+    let binary = install_copy(temp_dir.path());
+    let original = fs::read(&binary).unwrap();
+
+    self_update_command(temp_dir.path(), &binary, &path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("is up to date (stable channel)"));
+
+    serve_release(temp_dir.path(), "latest", "v9.9.9", Some(&new_binary_checksum()), false);
+    self_update_command(temp_dir.path(), &binary, &path)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[dry-run] Would update"));
+    assert_eq!(fs::read(&binary).unwrap(), original);
+    // Only the release lookups were made
+    assert_eq!(fs::read_to_string(temp_dir.path().join("curl.log")).unwrap().lines().count(), 2);
+}
+
+#[test]
+fn test_self_update_prerelease_channel() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_curl(temp_dir.path());
+    serve_release(temp_dir.path(), "releases?per_page=20", "v9.9.9-beta.1", Some(&new_binary_checksum()), true);
+    let binary = install_copy(temp_dir.path());
+
+    self_update_command(temp_dir.path(), &binary, &path)
+        .args(["--channel", "prerelease"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("to 9.9.9-beta.1"));
+    assert_eq!(fs::read_to_string(&binary).unwrap(), NEW_BINARY);
+}
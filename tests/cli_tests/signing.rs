@@ -0,0 +1,164 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+
+/// Runs `key sign-keygen --out signing.key` and returns the printed public key.
+fn generate_signing_key(temp_dir: &Path) -> String {
+    let output = create_command(temp_dir)
+        .args(["key", "sign-keygen", "--out", "signing.key"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    String::from_utf8(output).unwrap()
+        .lines()
+        .find_map(|line| line.strip_prefix("Signing public key:  "))
+        .unwrap()
+        .to_string()
+}
+
+fn encrypt_signed(temp_dir: &Path, extra_args: &[&str]) {
+    fs::write(temp_dir.join(".env"), "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir, TEST_KEY)
+        .args(["--sign-key", "signing.key"])
+        .args(extra_args)
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_embedded_signature_roundtrip() {
+    let temp_dir = create_temp_dir();
+    let public_key = generate_signing_key(temp_dir.path());
+    let config = fs::read_to_string(temp_dir.path().join("envcrypt.toml")).unwrap();
+    assert!(config.contains(&public_key), "{}", config);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(temp_dir.path().join("signing.key")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    encrypt_signed(temp_dir.path(), &[]);
+    assert!(!temp_dir.path().join(".env.encrypted.sig").exists());
+    create_command(temp_dir.path())
+        .arg("verify")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Signature of .env.encrypted is valid"))
+        .stdout(predicate::str::contains(public_key.as_str()));
+
+    create_command(temp_dir.path())
+        .arg("inspect")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Signature:     embedded"));
+
+    // The signature does not get in the way of decryption
+    fs::remove_file(temp_dir.path().join(".env")).unwrap();
+    create_decrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "APP_KEY=test123");
+}
+
+#[test]
+fn test_detached_signature_roundtrip() {
+    let temp_dir = create_temp_dir();
+    let public_key = generate_signing_key(temp_dir.path());
+    fs::remove_file(temp_dir.path().join("envcrypt.toml")).unwrap();
+
+    encrypt_signed(temp_dir.path(), &["--detached-signature"]);
+    assert!(temp_dir.path().join(".env.encrypted.sig").exists());
+    create_command(temp_dir.path())
+        .args(["verify", "--signer-pub", &public_key])
+        .assert()
+        .success();
+
+    // A detached signature covers the file exactly as stored
+    let encrypted_path = temp_dir.path().join(".env.encrypted");
+    let mut encrypted = fs::read_to_string(&encrypted_path).unwrap();
+    encrypted.push('\n');
+    fs::write(&encrypted_path, encrypted).unwrap();
+    create_command(temp_dir.path())
+        .args(["verify", "--signer-pub", &public_key])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Signature verification of .env.encrypted failed"));
+}
+
+#[test]
+fn test_verify_rejects_tampered_file() {
+    let temp_dir = create_temp_dir();
+    generate_signing_key(temp_dir.path());
+    encrypt_signed(temp_dir.path(), &["--format", "binary"]);
+
+    let encrypted_path = temp_dir.path().join(".env.encrypted");
+    let mut encrypted = fs::read(&encrypted_path).unwrap();
+    let last_body_byte = encrypted.len() - 97;
+    encrypted[last_body_byte] ^= 1;
+    fs::write(&encrypted_path, encrypted).unwrap();
+
+    create_command(temp_dir.path())
+        .arg("verify")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("changed after signing"));
+}
+
+#[test]
+fn test_verify_rejects_untrusted_signer() {
+    let temp_dir = create_temp_dir();
+    let trusted_dir = create_temp_dir();
+    let trusted_key = generate_signing_key(trusted_dir.path());
+    let signer_key = generate_signing_key(temp_dir.path());
+    encrypt_signed(temp_dir.path(), &[]);
+
+    create_command(temp_dir.path())
+        .args(["verify", "--signer-pub", &trusted_key])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!("is signed by {}, which is not a trusted signing key", signer_key)));
+}
+
+#[test]
+fn test_verify_unsigned_file_fails() {
+    let temp_dir = create_temp_dir();
+    let public_key = generate_signing_key(temp_dir.path());
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+
+    create_command(temp_dir.path())
+        .args(["verify", "--signer-pub", &public_key])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("has no embedded signature and .env.encrypted.sig does not exist"));
+}
+
+#[test]
+fn test_verify_requires_trusted_key() {
+    let temp_dir = create_temp_dir();
+    create_command(temp_dir.path())
+        .args(["key", "sign-keygen", "--out", "signing.key", "--no-save"])
+        .assert()
+        .success();
+    assert!(!temp_dir.path().join("envcrypt.toml").exists());
+    encrypt_signed(temp_dir.path(), &[]);
+
+    create_command(temp_dir.path())
+        .arg("verify")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No trusted signing key"));
+}
+
+#[test]
+fn test_detached_signature_requires_sign_key() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY)
+        .arg("--detached-signature")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--sign-key"));
+}
@@ -0,0 +1,210 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+
+/// Installs a fake `aws` in `temp_dir/bin` that answers reads with `temp_dir/response.json`
+/// (or a not-found error if it does not exist), appends the stdin of writes to
+/// `temp_dir/input.json` and logs its arguments to `temp_dir/aws.log`.
+/// Returns the PATH to run envcrypt with.
+#[cfg(unix)]
+fn install_fake_aws(temp_dir: &Path) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let bin = create_subdir(temp_dir, "bin");
+    let script = format!(
+        r#"#!/bin/sh
+echo "$@" >> "{dir}/aws.log"
+case "$1 $2" in
+    "secretsmanager get-secret-value"|"ssm get-parameters-by-path")
+        if [ -f "{dir}/response.json" ]; then
+            cat "{dir}/response.json"
+        else
+            echo "An error occurred (ResourceNotFoundException) when calling the GetSecretValue operation" >&2
+            exit 254
+        fi ;;
+    *) cat >> "{dir}/input.json"; echo >> "{dir}/input.json" ;;
+esac
+"#,
+        dir = temp_dir.display()
+    );
+    let aws = bin.join("aws");
+    fs::write(&aws, script).unwrap();
+    fs::set_permissions(&aws, fs::Permissions::from_mode(0o755)).unwrap();
+    format!("{}:{}", bin.display(), std::env::var("PATH").unwrap_or_default())
+}
+
+fn encrypt_env_file(temp_dir: &Path, content: &str) {
+    fs::write(temp_dir.join(".env"), content).unwrap();
+    create_encrypt_command(temp_dir, TEST_KEY).assert().success();
+}
+
+fn create_sync_command(temp_dir: &Path, path: &str, store: &[&str], direction: &str) -> assert_cmd::Command {
+    let mut cmd = create_command(temp_dir);
+    cmd.env("PATH", path).arg("sync").args(store).arg(direction).arg("--key").arg(TEST_KEY);
+    cmd
+}
+
+/// Writes a Secrets Manager response holding `secret_string`.
+fn write_secret_response(temp_dir: &Path, secret_string: &str) {
+    let response = serde_json::json!({ "Name": "app/prod", "SecretString": secret_string });
+    fs::write(temp_dir.join("response.json"), response.to_string()).unwrap();
+}
+
+/// Reads the `--cli-input-json` documents passed to the fake `aws`.
+fn read_inputs(temp_dir: &Path) -> Vec<serde_json::Value> {
+    fs::read_to_string(temp_dir.join("input.json")).unwrap_or_default()
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+const SECRETS_MANAGER: &[&str] = &["aws-secretsmanager", "--secret-id", "app/prod"];
+
+#[test]
+#[cfg(unix)]
+fn test_sync_push_creates_secret() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_aws(temp_dir.path());
+    encrypt_env_file(temp_dir.path(), "DB_PASSWORD=s3cret\nPORT=5432\n");
+
+    create_sync_command(temp_dir.path(), &path, SECRETS_MANAGER, "--push")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Pushed .env.encrypted to AWS Secrets Manager secret app/prod: 2 added, 0 changed, 0 removed"));
+
+    let inputs = read_inputs(temp_dir.path());
+    assert_eq!(inputs.len(), 1);
+    assert_eq!(inputs[0]["Name"], "app/prod");
+    let secret: serde_json::Value = serde_json::from_str(inputs[0]["SecretString"].as_str().unwrap()).unwrap();
+    assert_eq!(secret, serde_json::json!({ "DB_PASSWORD": "s3cret", "PORT": "5432" }));
+
+    // Values are passed on stdin, never as arguments
+    let log = fs::read_to_string(temp_dir.path().join("aws.log")).unwrap();
+    assert!(log.contains("secretsmanager create-secret --cli-input-json file:///dev/stdin"));
+    assert!(!log.contains("s3cret"));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_sync_push_dry_run_shows_diff() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_aws(temp_dir.path());
+    encrypt_env_file(temp_dir.path(), "KEEP=1\nCHANGED=new\nADDED=x\n");
+    write_secret_response(temp_dir.path(), r#"{"KEEP": "1", "CHANGED": "old", "REMOVED": "y"}"#);
+
+    let mut cmd = create_sync_command(temp_dir.path(), &path, SECRETS_MANAGER, "--push");
+    cmd.arg("--dry-run");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Would update AWS Secrets Manager secret app/prod (1 added, 1 changed, 1 removed)"))
+        .stdout(predicate::str::contains("+ ADDED"))
+        .stdout(predicate::str::contains("~ CHANGED"))
+        .stdout(predicate::str::contains("- REMOVED"))
+        .stdout(predicate::str::contains("KEEP").not())
+        .stdout(predicate::str::contains("new").not());
+
+    assert!(read_inputs(temp_dir.path()).is_empty());
+}
+
+#[test]
+#[cfg(unix)]
+fn test_sync_push_up_to_date() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_aws(temp_dir.path());
+    encrypt_env_file(temp_dir.path(), "APP_KEY=test123\n");
+    write_secret_response(temp_dir.path(), r#"{"APP_KEY": "test123"}"#);
+
+    create_sync_command(temp_dir.path(), &path, SECRETS_MANAGER, "--push")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("is already in sync"));
+    assert!(read_inputs(temp_dir.path()).is_empty());
+}
+
+#[test]
+#[cfg(unix)]
+fn test_sync_pull_updates_file_with_same_key() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_aws(temp_dir.path());
+    encrypt_env_file(temp_dir.path(), "APP_KEY=old\n");
+    fs::remove_file(temp_dir.path().join(".env")).unwrap();
+    write_secret_response(temp_dir.path(), r#"{"APP_KEY": "new value", "DEBUG": false}"#);
+
+    create_sync_command(temp_dir.path(), &path, SECRETS_MANAGER, "--pull")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Pulled AWS Secrets Manager secret app/prod into .env.encrypted: 1 added, 1 changed, 0 removed"));
+
+    create_decrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "APP_KEY=\"new value\"\nDEBUG=false\n");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_sync_pull_creates_file() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_aws(temp_dir.path());
+    write_secret_response(temp_dir.path(), r#"{"APP_KEY": "test123"}"#);
+
+    let mut cmd = create_sync_command(temp_dir.path(), &path, SECRETS_MANAGER, "--pull");
+    cmd.arg(".env.production.encrypted");
+    cmd.assert().success();
+
+    create_decrypt_command(temp_dir.path(), TEST_KEY).arg(".env.production.encrypted").assert().success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env.production")).unwrap(), "APP_KEY=test123\n");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_sync_pull_missing_secret_fails() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_aws(temp_dir.path());
+
+    create_sync_command(temp_dir.path(), &path, SECRETS_MANAGER, "--pull")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("AWS Secrets Manager secret app/prod does not exist"));
+    assert!(!temp_dir.path().join(".env.encrypted").exists());
+}
+
+#[test]
+#[cfg(unix)]
+fn test_sync_push_ssm_writes_only_differences() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_aws(temp_dir.path());
+    encrypt_env_file(temp_dir.path(), "KEEP=1\nCHANGED=new\n");
+    let response = serde_json::json!({ "Parameters": [
+        { "Name": "/app/prod/KEEP", "Value": "1", "Type": "SecureString" },
+        { "Name": "/app/prod/CHANGED", "Value": "old", "Type": "SecureString" },
+        { "Name": "/app/prod/REMOVED", "Value": "x", "Type": "SecureString" },
+    ]});
+    fs::write(temp_dir.path().join("response.json"), response.to_string()).unwrap();
+
+    create_sync_command(temp_dir.path(), &path, &["aws-ssm", "--path", "/app/prod"], "--push")
+        .arg("--region")
+        .arg("eu-west-1")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("0 added, 1 changed, 1 removed"));
+
+    let inputs = read_inputs(temp_dir.path());
+    assert_eq!(inputs, vec![serde_json::json!({
+        "Name": "/app/prod/CHANGED", "Value": "new", "Type": "SecureString", "Overwrite": true,
+    })]);
+    let log = fs::read_to_string(temp_dir.path().join("aws.log")).unwrap();
+    assert!(log.contains("ssm get-parameters-by-path --path /app/prod/ --with-decryption --output json --region eu-west-1"));
+    assert!(log.contains("ssm delete-parameters --names /app/prod/REMOVED"));
+}
+
+#[test]
+fn test_sync_requires_direction() {
+    let temp_dir = create_temp_dir();
+
+    let mut cmd = create_command(temp_dir.path());
+    cmd.arg("sync").arg("aws-secretsmanager").arg("--secret-id").arg("app/prod");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("--push"));
+}
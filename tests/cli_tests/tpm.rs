@@ -0,0 +1,187 @@
+#![cfg(unix)]
+
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+
+/// Installs fake `tpm2-tools` in `temp_dir/bin` that log their arguments to
+/// `temp_dir/tpm.log`. The fake TPM "seals" by storing the key as the private blob.
+/// Returns the PATH to run envcrypt with.
+fn install_fake_tpm(temp_dir: &Path) -> String {
+    install_tpm_script(temp_dir, &format!(
+        "#!/bin/sh\nname=$(basename \"$0\")\necho \"$name $@\" >> \"{}/tpm.log\"\n\
+         while [ $# -gt 0 ]; do\n  case \"$1\" in\n    -c) ctx=\"$2\"; shift ;;\n    -u) pub=\"$2\"; shift ;;\n    -r) priv=\"$2\"; shift ;;\n  esac\n  shift\ndone\n\
+         case \"$name\" in\n  tpm2_createprimary) echo primary > \"$ctx\" ;;\n  tpm2_create) echo public > \"$pub\"; cat > \"$priv\" ;;\n  tpm2_load) cp \"$priv\" \"$ctx\" ;;\n  tpm2_unseal) cat \"$ctx\" ;;\nesac\n",
+        temp_dir.display()
+    ))
+}
+
+fn install_tpm_script(temp_dir: &Path, script: &str) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let bin = temp_dir.join("bin");
+    fs::create_dir_all(&bin).unwrap();
+    for name in ["tpm2_createprimary", "tpm2_create", "tpm2_load", "tpm2_unseal"] {
+        let path = bin.join(name);
+        fs::write(&path, script).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    format!("{}:{}", bin.display(), std::env::var("PATH").unwrap_or_default())
+}
+
+#[test]
+fn test_tpm_seal_and_roundtrip() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = create_temp_dir();
+    let path = install_fake_tpm(temp_dir.path());
+
+    create_command(temp_dir.path())
+        .env("PATH", &path)
+        .args(["key", "tpm-seal", "--key", TEST_KEY])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Sealed the key"));
+    let sealed_path = temp_dir.path().join(".envcrypt.tpm");
+    let sealed = fs::read_to_string(&sealed_path).unwrap();
+    assert!(sealed.starts_with("envcrypt tpm-sealed key v1\n"), "{}", sealed);
+    assert!(!sealed.contains(TEST_KEY));
+    assert_eq!(fs::metadata(&sealed_path).unwrap().permissions().mode() & 0o777, 0o600);
+
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_command(temp_dir.path())
+        .env("PATH", &path)
+        .args(["encrypt", "--key-provider", "tpm", "--no-interaction"])
+        .assert()
+        .success();
+    fs::remove_file(temp_dir.path().join(".env")).unwrap();
+    create_command(temp_dir.path())
+        .env("PATH", &path)
+        .args(["decrypt", "--key-provider", "tpm", "--no-interaction"])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "APP_KEY=test123");
+
+    // The key only ever reaches the tools on stdin
+    let log = fs::read_to_string(temp_dir.path().join("tpm.log")).unwrap();
+    assert!(log.contains("tpm2_unseal -c"), "{}", log);
+    assert!(!log.contains(TEST_KEY), "{}", log);
+
+    // The sealed key is the key itself
+    fs::remove_file(temp_dir.path().join(".env")).unwrap();
+    create_decrypt_command(temp_dir.path(), TEST_KEY).assert().success();
+}
+
+#[test]
+fn test_tpm_seal_refuses_to_overwrite() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_tpm(temp_dir.path());
+    fs::write(temp_dir.path().join(".envcrypt.tpm"), "existing").unwrap();
+
+    create_command(temp_dir.path())
+        .env("PATH", &path)
+        .args(["key", "tpm-seal", "--key", TEST_KEY])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("already exists"));
+    assert_eq!(fs::read_to_string(temp_dir.path().join(".envcrypt.tpm")).unwrap(), "existing");
+
+    create_command(temp_dir.path())
+        .env("PATH", &path)
+        .args(["key", "tpm-seal", "--key", TEST_KEY, "--force"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_tpm_seal_generate_shows_key() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_tpm(temp_dir.path());
+    let tpm_dir = create_subdir(temp_dir.path(), "secrets");
+    fs::write(temp_dir.path().join("envcrypt.toml"), "tpm_key_file = \"secrets/host.tpm\"\nkey_provider = \"tpm\"\n").unwrap();
+
+    create_command(temp_dir.path())
+        .env("PATH", &path)
+        .args(["key", "tpm-seal", "--generate", "--show-key"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("base64:"));
+    assert!(tpm_dir.join("host.tpm").exists());
+
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_command(temp_dir.path())
+        .env("PATH", &path)
+        .args(["encrypt", "--no-interaction"])
+        .assert()
+        .success();
+    fs::remove_file(temp_dir.path().join(".env")).unwrap();
+    create_command(temp_dir.path())
+        .env("PATH", &path)
+        .args(["decrypt", "--no-interaction"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_tpm_dry_run_seals_nothing() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_tpm(temp_dir.path());
+
+    create_command(temp_dir.path())
+        .env("PATH", &path)
+        .args(["key", "tpm-seal", "--key", TEST_KEY, "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Would seal the key"));
+    assert!(!temp_dir.path().join(".envcrypt.tpm").exists());
+    assert!(!temp_dir.path().join("tpm.log").exists());
+}
+
+#[test]
+fn test_tpm_without_sealed_key_fails() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    create_command(temp_dir.path())
+        .args(["encrypt", "--key-provider", "tpm", "--no-interaction"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(".envcrypt.tpm not found"))
+        .stderr(predicate::str::contains("envcrypt key tpm-seal"));
+}
+
+#[test]
+fn test_tpm_key_sealed_elsewhere_fails() {
+    let temp_dir = create_temp_dir();
+    let path = install_fake_tpm(temp_dir.path());
+    create_command(temp_dir.path())
+        .env("PATH", &path)
+        .args(["key", "tpm-seal", "--key", TEST_KEY])
+        .assert()
+        .success();
+
+    // Another machine's TPM cannot load the sealed blob
+    let path = install_tpm_script(temp_dir.path(), "#!/bin/sh\n[ \"$(basename \"$0\")\" = tpm2_load ] || exit 0\necho 'ERROR: Esys_Load(0x1DF) - tpm:parameter(1):integrity check failed' >&2\nexit 1\n");
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_command(temp_dir.path())
+        .env("PATH", &path)
+        .args(["encrypt", "--key-provider", "tpm", "--no-interaction"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("was it sealed on this machine?"))
+        .stderr(predicate::str::contains("integrity check failed"));
+}
+
+#[test]
+fn test_tpm_rejects_invalid_sealed_key_file() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".envcrypt.tpm"), "not a sealed key").unwrap();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    create_command(temp_dir.path())
+        .args(["encrypt", "--key-provider", "tpm", "--no-interaction"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is not a TPM-sealed envcrypt key"));
+}
@@ -0,0 +1,114 @@
+use crate::common::*;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+
+fn trashed_files(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name != ".gitignore")
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_prune_backup_moves_original_to_trash() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    create_encrypt_command(temp_dir.path(), TEST_KEY)
+        .arg("--prune=backup")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Moved .env to .envcrypt-trash/.env."));
+
+    let trash = temp_dir.path().join(".envcrypt-trash");
+    assert!(!temp_dir.path().join(".env").exists());
+    assert!(temp_dir.path().join(".env.encrypted").exists());
+    assert_eq!(fs::read_to_string(trash.join(".gitignore")).unwrap().lines().last(), Some("*"));
+    let names = trashed_files(&trash);
+    assert_eq!(names.len(), 1);
+    let stamp = names[0].strip_prefix(".env.").unwrap();
+    assert!(stamp.len() == 16 && stamp.ends_with('Z') && stamp.as_bytes()[8] == b'T', "{}", stamp);
+    assert_eq!(fs::read_to_string(trash.join(&names[0])).unwrap(), "APP_KEY=test123");
+
+    // Pruning the same file again in the same second does not overwrite the first copy
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=second").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).args(["--prune=backup", "--force"]).assert().success();
+    assert_eq!(trashed_files(&trash).len(), 2);
+}
+
+#[test]
+fn test_prune_backup_dry_run_and_plain_prune() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+
+    create_encrypt_command(temp_dir.path(), TEST_KEY)
+        .args(["--prune=backup", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[dry-run] Would move .env to .envcrypt-trash"));
+    assert!(!temp_dir.path().join(".envcrypt-trash").exists());
+
+    create_encrypt_command(temp_dir.path(), TEST_KEY).arg("--prune").assert().success();
+    assert!(!temp_dir.path().join(".env").exists());
+    assert!(!temp_dir.path().join(".envcrypt-trash").exists());
+}
+
+#[test]
+fn test_prune_gc_deletes_expired_files() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join("envcrypt.toml"), "prune_dir = \"trash\"\n").unwrap();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).arg("--prune=backup").assert().success();
+    let trash = temp_dir.path().join("trash");
+
+    create_command(temp_dir.path())
+        .arg("prune")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("trash/.env.").and(predicate::str::contains(", deleted by --gc after ")));
+
+    // Still within the grace period
+    create_command(temp_dir.path())
+        .args(["prune", "--gc"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Deleted 0 files from trash"));
+    assert_eq!(trashed_files(&trash).len(), 1);
+
+    fs::write(temp_dir.path().join("envcrypt.toml"), "prune_dir = \"trash\"\nprune_keep_days = 0\n").unwrap();
+    create_command(temp_dir.path())
+        .args(["prune", "--gc", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[dry-run] Would delete trash/.env."));
+    assert_eq!(trashed_files(&trash).len(), 1);
+    create_command(temp_dir.path())
+        .args(["prune", "--gc"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Deleted 1 file from trash"));
+    assert!(trashed_files(&trash).is_empty());
+    assert!(trash.join(".gitignore").exists());
+}
+
+#[test]
+fn test_prune_gc_all_empties_trash() {
+    let temp_dir = create_temp_dir();
+    fs::write(temp_dir.path().join(".env"), "APP_KEY=test123").unwrap();
+    create_encrypt_command(temp_dir.path(), TEST_KEY).arg("--prune=backup").assert().success();
+
+    create_command(temp_dir.path())
+        .args(["prune", "--gc", "--all"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Deleted 1 file from .envcrypt-trash"));
+    create_command(temp_dir.path())
+        .arg("prune")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(".envcrypt-trash is empty"));
+}
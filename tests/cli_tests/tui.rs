@@ -0,0 +1,14 @@
+#![cfg(feature = "tui")]
+
+use crate::common::*;
+use predicates::prelude::*;
+
+#[test]
+fn test_tui_needs_terminal() {
+    let temp_dir = create_temp_dir();
+    create_command(temp_dir.path())
+        .arg("tui")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("envcrypt tui needs a terminal"));
+}
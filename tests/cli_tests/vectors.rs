@@ -0,0 +1,48 @@
+use crate::common::*;
+use envcrypt::envelope;
+use std::fs;
+use std::path::Path;
+
+fn checked_in_vectors() -> String {
+    fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("test-vectors").join("vectors.json")).unwrap()
+}
+
+#[test]
+fn test_vectors_are_up_to_date() {
+    let temp_dir = create_temp_dir();
+    let output = create_command(temp_dir.path()).arg("vectors").output().unwrap();
+    assert!(output.status.success());
+    // If the format changed on purpose, regenerate with:
+    // envcrypt vectors --output test-vectors/vectors.json
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), checked_in_vectors());
+}
+
+#[test]
+fn test_vectors_decrypt() {
+    let vectors: Vec<serde_json::Value> = serde_json::from_str(&checked_in_vectors()).unwrap();
+    assert!(vectors.len() >= 10);
+    for vector in vectors {
+        let key = vector["key"].as_str().unwrap();
+        let context = vector["context"].as_str();
+        let plaintext = envelope::decrypt(vector["file"].as_str().unwrap().as_bytes(), key, context)
+            .unwrap_or_else(|e| panic!("{}: {}", vector["name"], e));
+        assert_eq!(plaintext.as_slice(), vector["plaintext"].as_str().unwrap().as_bytes(), "{}", vector["name"]);
+    }
+}
+
+#[test]
+fn test_vectors_decrypt_with_cli() {
+    let vectors: Vec<serde_json::Value> = serde_json::from_str(&checked_in_vectors()).unwrap();
+    for vector in vectors {
+        let temp_dir = create_temp_dir();
+        fs::write(temp_dir.path().join(".env.encrypted"), vector["file"].as_str().unwrap()).unwrap();
+
+        let mut cmd = create_decrypt_command(temp_dir.path(), vector["key"].as_str().unwrap());
+        if let Some(context) = vector["context"].as_str() {
+            cmd.arg("--aad").arg(context);
+        }
+        cmd.assert().success();
+        let decrypted = fs::read_to_string(temp_dir.path().join(".env")).unwrap();
+        assert_eq!(decrypted, vector["plaintext"].as_str().unwrap(), "{}", vector["name"]);
+    }
+}